@@ -0,0 +1,346 @@
+//! End-to-end benchmarks of a client and a server connected through a socket pair
+//!
+//! Both ends run in the same thread and are driven in turn, so that the measurements include the
+//! serialization, the socket transfer and the dispatching of the messages, but no scheduling.
+//!
+//! The rust backends are measured a second time under the `rs_loopback` prefix, connected by a
+//! [`LoopbackStream`] rather than a socket: the messages are then passed through memory, which leaves
+//! out the cost and the noise of the kernel to profile the serialization and dispatching alone.
+//!
+//! ```text
+//! cargo bench -p wayland-backend [FILTER]
+//! cargo bench -p wayland-backend --features client_system,server_system [FILTER]
+//! ```
+//!
+//! The rust backends are always measured, the system backends only when both the `client_system` and
+//! `server_system` features are enabled. Only the benchmarks whose name contains `FILTER` are run.
+
+use std::ffi::CString;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use wayland_backend::protocol::{Argument, Message};
+use wayland_backend::rs::LoopbackStream;
+use wayland_backend::smallvec::smallvec;
+
+mod interfaces {
+    wayland_scanner::generate_interfaces!(
+        "../wayland-scanner/tests/scanner_assets/test-protocol.xml"
+    );
+}
+
+// Events are sent in batches, so that the socket buffers do not fill up
+const BATCH: usize = 32;
+const LARGE_STRING_LEN: usize = 2000;
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let filter = filter.as_deref().unwrap_or("");
+
+    rs::run(filter);
+    rs_loopback::run(filter);
+    #[cfg(all(feature = "client_system", feature = "server_system"))]
+    sys::run(filter);
+}
+
+/// Run `routine` with an increasing number of iterations until it takes long enough to be measured,
+/// and print the time per iteration along with the throughput of elements
+fn bench(name: &str, filter: &str, elements_per_iter: usize, mut routine: impl FnMut(usize)) {
+    if !name.contains(filter) {
+        return;
+    }
+    // warm up
+    routine(10);
+
+    let mut iterations = 10;
+    let elapsed = loop {
+        let start = Instant::now();
+        routine(iterations);
+        let elapsed = start.elapsed();
+        if elapsed >= Duration::from_millis(500) {
+            break elapsed;
+        }
+        iterations *= 2;
+    };
+
+    let per_iter = elapsed / iterations as u32;
+    let throughput = (iterations * elements_per_iter) as f64 / elapsed.as_secs_f64();
+    println!("{:<24} {:>12.2?}/iter {:>14.0} msg/s", name, per_iter, throughput);
+}
+
+// Connect a client to the server through a transport, returning the client and its id on the server
+macro_rules! connect {
+    (socket, $server:expr) => {{
+        let (tx, rx) = UnixStream::pair().unwrap();
+        let client_id = $server.handle().insert_client(rx, Arc::new(())).unwrap();
+        (client::Backend::connect(tx).unwrap(), client_id)
+    }};
+    (loopback, $server:expr) => {{
+        let (client_end, server_end) = LoopbackStream::pair().unwrap();
+        let client_id = $server.handle().insert_loopback_client(server_end, Arc::new(())).unwrap();
+        (client::Backend::connect_loopback(client_end), client_id)
+    }};
+}
+
+macro_rules! backend_benches {
+    ($name:ident, $client:path, $server:path, $transport:ident) => {
+        mod $name {
+            use super::*;
+
+            use $client as client;
+            use $server as server;
+
+            struct ServerData {
+                test_global: Mutex<Option<server::ObjectId>>,
+            }
+
+            impl server::ObjectData<()> for ServerData {
+                fn request(
+                    self: Arc<Self>,
+                    _: &server::Handle,
+                    _: &mut (),
+                    _: server::ClientId,
+                    _: Message<server::ObjectId, OwnedFd>,
+                ) -> Option<Arc<dyn server::ObjectData<()>>> {
+                    None
+                }
+
+                fn destroyed(
+                    self: Arc<Self>,
+                    _: &server::Handle,
+                    _: &mut (),
+                    _: server::ClientId,
+                    _: server::ObjectId,
+                ) {
+                }
+            }
+
+            impl server::GlobalHandler<()> for ServerData {
+                fn bind(
+                    self: Arc<Self>,
+                    _: &server::Handle,
+                    _: &mut (),
+                    _: server::ClientId,
+                    _: server::GlobalId,
+                    object_id: server::ObjectId,
+                ) -> Arc<dyn server::ObjectData<()>> {
+                    *self.test_global.lock().unwrap() = Some(object_id);
+                    self
+                }
+            }
+
+            #[derive(Default)]
+            struct ClientData {
+                events: AtomicUsize,
+                done: AtomicBool,
+            }
+
+            impl client::ObjectData for ClientData {
+                fn event(
+                    self: Arc<Self>,
+                    _: &client::Backend,
+                    _: Message<client::ObjectId, OwnedFd>,
+                ) -> Option<Arc<dyn client::ObjectData>> {
+                    self.events.fetch_add(1, Ordering::Relaxed);
+                    self.done.store(true, Ordering::Relaxed);
+                    None
+                }
+
+                fn destroyed(&self, _: client::ObjectId) {}
+            }
+
+            struct Pair {
+                client: client::Backend,
+                server: server::Backend<()>,
+                registry: server::ObjectId,
+                test_global: server::ObjectId,
+                events: Arc<ClientData>,
+            }
+
+            impl Pair {
+                fn new() -> Self {
+                    let server = server::Backend::new().unwrap();
+                    let (client, client_id) = connect!($transport, server);
+                    let server_data = Arc::new(ServerData { test_global: Mutex::new(None) });
+                    server.handle().create_global(
+                        &interfaces::TEST_GLOBAL_INTERFACE,
+                        1,
+                        server_data.clone(),
+                    );
+
+                    let events = Arc::new(ClientData::default());
+                    let registry = client
+                        .send_request(
+                            Message {
+                                sender_id: client.display_id(),
+                                opcode: 1,
+                                args: smallvec![Argument::NewId(client::ObjectId::null())],
+                            },
+                            Some(events.clone()),
+                            Some((&interfaces::WL_REGISTRY_INTERFACE, 1)),
+                        )
+                        .unwrap();
+                    let name = CString::new(interfaces::TEST_GLOBAL_INTERFACE.name).unwrap();
+                    client
+                        .send_request(
+                            Message {
+                                sender_id: registry.clone(),
+                                opcode: 0,
+                                args: smallvec![
+                                    Argument::Uint(1),
+                                    Argument::Str(Some(Box::new(name))),
+                                    Argument::Uint(1),
+                                    Argument::NewId(client::ObjectId::null()),
+                                ],
+                            },
+                            Some(events.clone()),
+                            Some((&interfaces::TEST_GLOBAL_INTERFACE, 1)),
+                        )
+                        .unwrap();
+
+                    let mut pair = Self {
+                        client,
+                        server,
+                        registry: server::ObjectId::null(),
+                        test_global: server::ObjectId::null(),
+                        events,
+                    };
+                    pair.roundtrip();
+                    pair.registry = pair
+                        .server
+                        .handle()
+                        .object_for_protocol_id(
+                            client_id,
+                            &interfaces::WL_REGISTRY_INTERFACE,
+                            registry.protocol_id(),
+                        )
+                        .unwrap();
+                    pair.test_global = server_data.test_global.lock().unwrap().clone().unwrap();
+                    pair
+                }
+
+                // Read and dispatch the events available on the client socket
+                fn read_client(&self) {
+                    let guard = match self.client.prepare_read() {
+                        Some(guard) => guard,
+                        None => {
+                            self.client.dispatch_inner_queue().unwrap();
+                            return;
+                        }
+                    };
+                    match guard.read() {
+                        Ok(_) => {}
+                        Err(client::WaylandError::Io(e))
+                            if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                        Err(e) => panic!("{}", e),
+                    }
+                }
+
+                fn roundtrip(&mut self) {
+                    let done = Arc::new(ClientData::default());
+                    self.client
+                        .send_request(
+                            Message {
+                                sender_id: self.client.display_id(),
+                                opcode: 0,
+                                args: smallvec![Argument::NewId(client::ObjectId::null())],
+                            },
+                            Some(done.clone()),
+                            Some((&interfaces::WL_CALLBACK_INTERFACE, 1)),
+                        )
+                        .unwrap();
+                    self.client.flush().unwrap();
+                    self.server.dispatch_all_clients(&mut ()).unwrap();
+                    self.server.flush(None).unwrap();
+                    while !done.done.load(Ordering::Relaxed) {
+                        self.read_client();
+                    }
+                }
+
+                // Send a batch of events from the server, and wait for the client to dispatch them
+                fn transfer(&mut self, make_event: impl Fn() -> Message<server::ObjectId, i32>) {
+                    let expected = self.events.events.load(Ordering::Relaxed) + BATCH;
+                    for _ in 0..BATCH {
+                        self.server.handle().send_event(make_event()).unwrap();
+                    }
+                    self.server.flush(None).unwrap();
+                    while self.events.events.load(Ordering::Relaxed) < expected {
+                        self.read_client();
+                    }
+                }
+            }
+
+            pub fn run(filter: &str) {
+                let prefix = stringify!($name);
+                let mut pair = Pair::new();
+
+                bench(&format!("{}/roundtrip", prefix), filter, 1, |iterations| {
+                    for _ in 0..iterations {
+                        pair.roundtrip();
+                    }
+                });
+
+                let registry = pair.registry.clone();
+                let global = |interface: &str| {
+                    let interface = CString::new(interface).unwrap();
+                    let registry = registry.clone();
+                    move || Message {
+                        sender_id: registry.clone(),
+                        opcode: 0,
+                        args: smallvec![
+                            Argument::Uint(1),
+                            Argument::Str(Some(Box::new(interface.clone()))),
+                            Argument::Uint(1),
+                        ],
+                    }
+                };
+
+                let small = global("wl_small");
+                bench(&format!("{}/small_events", prefix), filter, BATCH, |iterations| {
+                    for _ in 0..iterations {
+                        pair.transfer(&small);
+                    }
+                });
+
+                let large = global(&"x".repeat(LARGE_STRING_LEN));
+                bench(&format!("{}/large_events", prefix), filter, BATCH, |iterations| {
+                    for _ in 0..iterations {
+                        pair.transfer(&large);
+                    }
+                });
+
+                let (read, write) = nix::unistd::pipe().unwrap();
+                // SAFETY: the file descriptors were just created and are owned by nobody else
+                let (read, _write) =
+                    unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) };
+                let test_global = pair.test_global.clone();
+                let fd = read.as_raw_fd();
+                let with_fd = move || Message {
+                    sender_id: test_global.clone(),
+                    opcode: 0,
+                    args: smallvec![
+                        Argument::Uint(1),
+                        Argument::Int(2),
+                        Argument::Fixed(3),
+                        Argument::Array(Box::new(vec![4, 5, 6])),
+                        Argument::Str(Some(Box::new(CString::new("fd").unwrap()))),
+                        Argument::Fd(fd),
+                    ],
+                };
+                bench(&format!("{}/fd_passing", prefix), filter, BATCH, |iterations| {
+                    for _ in 0..iterations {
+                        pair.transfer(&with_fd);
+                    }
+                });
+            }
+        }
+    };
+}
+
+backend_benches!(rs, wayland_backend::rs::client, wayland_backend::rs::server, socket);
+backend_benches!(rs_loopback, wayland_backend::rs::client, wayland_backend::rs::server, loopback);
+#[cfg(all(feature = "client_system", feature = "server_system"))]
+backend_benches!(sys, wayland_backend::sys::client, wayland_backend::sys::server, socket);
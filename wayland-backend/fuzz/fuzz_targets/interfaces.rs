@@ -0,0 +1,39 @@
+use wayland_backend::protocol::Interface;
+
+wayland_scanner::generate_interfaces!("../../wayland-client/wayland.xml");
+
+pub static ALL: &[&Interface] = &[
+    &WL_DISPLAY_INTERFACE,
+    &WL_REGISTRY_INTERFACE,
+    &WL_CALLBACK_INTERFACE,
+    &WL_COMPOSITOR_INTERFACE,
+    &WL_SHM_POOL_INTERFACE,
+    &WL_SHM_INTERFACE,
+    &WL_BUFFER_INTERFACE,
+    &WL_DATA_OFFER_INTERFACE,
+    &WL_DATA_SOURCE_INTERFACE,
+    &WL_DATA_DEVICE_INTERFACE,
+    &WL_DATA_DEVICE_MANAGER_INTERFACE,
+    &WL_SHELL_INTERFACE,
+    &WL_SHELL_SURFACE_INTERFACE,
+    &WL_SURFACE_INTERFACE,
+    &WL_SEAT_INTERFACE,
+    &WL_POINTER_INTERFACE,
+    &WL_KEYBOARD_INTERFACE,
+    &WL_TOUCH_INTERFACE,
+    &WL_OUTPUT_INTERFACE,
+    &WL_REGION_INTERFACE,
+    &WL_SUBCOMPOSITOR_INTERFACE,
+    &WL_SUBSURFACE_INTERFACE,
+];
+
+/// Interfaces that can be advertised as globals
+pub static GLOBALS: &[&Interface] = &[
+    &WL_COMPOSITOR_INTERFACE,
+    &WL_SHM_INTERFACE,
+    &WL_DATA_DEVICE_MANAGER_INTERFACE,
+    &WL_SHELL_INTERFACE,
+    &WL_SEAT_INTERFACE,
+    &WL_OUTPUT_INTERFACE,
+    &WL_SUBCOMPOSITOR_INTERFACE,
+];
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+mod interfaces;
+
+fuzz_target!(|data: &[u8]| {
+    // the first byte decides how many file descriptors are available
+    if let Some((&fd_count, data)) = data.split_first() {
+        wayland_backend::rs::fuzzing::parse_messages(data, fd_count as usize, interfaces::ALL);
+    }
+});
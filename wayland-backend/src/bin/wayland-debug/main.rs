@@ -0,0 +1,279 @@
+//! Pretty-printer for the output of Wayland programs run with `WAYLAND_DEBUG=1`
+//!
+//! Reads the debug output from a file or from stdin, and prints the messages with colors, optionally
+//! filtered by interface or object id, either in order or grouped by object. The time elapsed between
+//! the creation of a `wl_callback` and its `done` event (as for `wl_display.sync` or
+//! `wl_surface.frame`) is printed along the `done` event.
+//!
+//! ```text
+//! WAYLAND_DEBUG=1 my_client 2>&1 | wayland-debug --interface wl_surface
+//! ```
+//!
+//! This binary is only built with the `tools` cargo feature.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::exit;
+use std::time::Duration;
+
+use parse::{parse_line, Arg, Line, Object};
+
+mod parse;
+
+const USAGE: &str = "\
+Usage: wayland-debug [OPTIONS] [FILE]
+
+Pretty-print the WAYLAND_DEBUG output read from FILE, or from stdin.
+
+Options:
+  -i, --interface <NAME>    Only show the messages about objects of this interface
+  -o, --object <ID>         Only show the messages about the object with this id
+  -t, --timeline            Group the messages by object
+      --color <WHEN>        Use colors: auto, always or never [default: auto]
+  -h, --help                Print this help";
+
+struct Options {
+    interfaces: Vec<String>,
+    objects: Vec<u32>,
+    timeline: bool,
+    color: bool,
+    input: Option<String>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        interfaces: Vec::new(),
+        objects: Vec::new(),
+        timeline: false,
+        color: nix::unistd::isatty(1).unwrap_or(false),
+        input: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value =
+            |name: &str| args.next().ok_or_else(|| format!("Missing value for {}", name));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            "-i" | "--interface" => options.interfaces.push(value(&arg)?),
+            "-o" | "--object" => {
+                let id = value(&arg)?;
+                options.objects.push(id.parse().map_err(|_| format!("Invalid object id: {}", id))?)
+            }
+            "-t" | "--timeline" => options.timeline = true,
+            "--color" => match value(&arg)?.as_str() {
+                "auto" => {}
+                "always" => options.color = true,
+                "never" => options.color = false,
+                other => return Err(format!("Invalid value for --color: {}", other)),
+            },
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("Unknown option: {}", arg))
+            }
+            _ if options.input.is_none() => options.input = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, USAGE);
+            exit(2);
+        }
+    };
+
+    let input: Box<dyn BufRead> = match options.input.as_deref() {
+        None | Some("-") => Box::new(BufReader::new(io::stdin())),
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Failed to open {}: {}", path, e);
+                exit(1);
+            }
+        },
+    };
+
+    let mut output = BufWriter::new(io::stdout());
+    match run(&options, input, &mut output).and_then(|()| output.flush()) {
+        Ok(()) => {}
+        // the reader went away, as when piping into `head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn run(options: &Options, input: Box<dyn BufRead>, output: &mut impl Write) -> io::Result<()> {
+    let style = Style { color: options.color };
+    let filtered = !options.interfaces.is_empty() || !options.objects.is_empty();
+    let mut latencies = Latencies::default();
+    let mut start = None;
+    let mut timeline = Timeline::default();
+
+    for text in input.lines() {
+        let text = text?;
+        let line = match parse_line(&text) {
+            Some(line) => line,
+            None => {
+                // keep the output of the program around the messages
+                if !filtered && !options.timeline {
+                    writeln!(output, "{}", style.paint("2", &text))?;
+                }
+                continue;
+            }
+        };
+
+        let start = *start.get_or_insert(line.time);
+        let latency = latencies.observe(&line);
+        if !objects_of(&line).any(|object| selected(options, object)) {
+            continue;
+        }
+
+        let rendered = render(&style, &line, line.time.saturating_sub(start), latency);
+        if options.timeline {
+            timeline.insert(objects_of(&line).filter(|object| selected(options, object)), rendered);
+        } else {
+            writeln!(output, "{}", rendered)?;
+        }
+    }
+
+    if options.timeline {
+        for (object, lines) in timeline.objects {
+            writeln!(
+                output,
+                "{}",
+                style.paint("1", &format!("{}@{}", object.interface, object.id))
+            )?;
+            for line in lines {
+                writeln!(output, "  {}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The objects a message is about: its target, and the objects it creates
+fn objects_of(line: &Line) -> impl Iterator<Item = &Object> {
+    std::iter::once(&line.target).chain(line.args.iter().filter_map(|arg| match arg {
+        Arg::NewId(object) => Some(object),
+        _ => None,
+    }))
+}
+
+fn selected(options: &Options, object: &Object) -> bool {
+    (options.interfaces.is_empty() || options.interfaces.contains(&object.interface))
+        && (options.objects.is_empty() || options.objects.contains(&object.id))
+}
+
+/// Tracks the `wl_callback` objects from their creation to their `done` event
+#[derive(Default)]
+struct Latencies {
+    pending: HashMap<u32, (Duration, String)>,
+}
+
+impl Latencies {
+    /// Returns the time elapsed since the creation of the callback for `done` events, along with the
+    /// message that created it
+    fn observe(&mut self, line: &Line) -> Option<(Duration, String)> {
+        if line.target.interface == "wl_callback" {
+            if line.name != "done" {
+                return None;
+            }
+            let (created, origin) = self.pending.remove(&line.target.id)?;
+            return Some((line.time.saturating_sub(created), origin));
+        }
+        for arg in &line.args {
+            if let Arg::NewId(object) | Arg::Object(object) = arg {
+                // the rust backends do not mark the created objects, the first mention is the creation
+                if object.interface == "wl_callback" && !self.pending.contains_key(&object.id) {
+                    let origin =
+                        format!("{}@{}.{}", line.target.interface, line.target.id, line.name);
+                    self.pending.insert(object.id, (line.time, origin));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The rendered messages, grouped by object in order of first appearance
+#[derive(Default)]
+struct Timeline {
+    objects: Vec<(Object, Vec<String>)>,
+    index: HashMap<Object, usize>,
+}
+
+impl Timeline {
+    fn insert<'a>(&mut self, objects: impl Iterator<Item = &'a Object>, rendered: String) {
+        for object in objects {
+            let objects = &mut self.objects;
+            let idx = *self.index.entry(object.clone()).or_insert_with(|| {
+                objects.push((object.clone(), Vec::new()));
+                objects.len() - 1
+            });
+            self.objects[idx].1.push(rendered.clone());
+        }
+    }
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    /// Wrap the text into the given SGR escape sequence
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn object(&self, object: &Object) -> String {
+        format!("{}@{}", self.paint("36", &object.interface), object.id)
+    }
+}
+
+fn render(
+    style: &Style,
+    line: &Line,
+    time: Duration,
+    latency: Option<(Duration, String)>,
+) -> String {
+    let mut rendered = format!("{:>12.3}ms ", time.as_secs_f64() * 1000.);
+    if let Some(ref queue) = line.queue {
+        rendered += &style.paint("2", &format!("{{{}}} ", queue));
+    }
+    rendered += &if line.sent { style.paint("32", "->") } else { style.paint("34", "<-") };
+    rendered.push(' ');
+    if line.discarded {
+        rendered += &style.paint("31", "discarded ");
+    }
+    rendered += &format!("{}.{}(", style.object(&line.target), style.paint("1;33", &line.name));
+
+    let args = line.args.iter().map(|arg| match arg {
+        Arg::NewId(object) => format!("new id {}", style.object(object)),
+        Arg::Object(object) => style.object(object),
+        Arg::Null => style.paint("2", "nil"),
+        Arg::Value(value) => value.clone(),
+    });
+    rendered += &args.collect::<Vec<_>>().join(", ");
+    rendered.push(')');
+
+    if let Some((elapsed, origin)) = latency {
+        rendered += &style
+            .paint("35", &format!(" [{:.3}ms after {}]", elapsed.as_secs_f64() * 1000., origin));
+    }
+    rendered
+}
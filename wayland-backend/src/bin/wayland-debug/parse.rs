@@ -0,0 +1,237 @@
+//! Parsing of the lines printed when `WAYLAND_DEBUG` is set
+//!
+//! Both the format of libwayland and the one of the rust backends are supported:
+//!
+//! ```text
+//! [3626514.330] {Default Queue}  -> wl_display@1.get_registry(new id wl_registry@2)
+//! [1700000000.123456] <- wl_registry@2.global, (1, Some("wl_compositor"), 4)
+//! ```
+
+use std::time::Duration;
+
+/// A message parsed from a line of debug output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// Timestamp of the message
+    pub time: Duration,
+    /// Name of the event queue the message was dispatched to, if printed
+    pub queue: Option<String>,
+    /// Whether the message was discarded because its target was already destroyed
+    pub discarded: bool,
+    /// Whether the message was sent by the process that printed it, rather than received
+    pub sent: bool,
+    /// The object the message is about
+    pub target: Object,
+    /// Name of the request or event
+    pub name: String,
+    /// Arguments of the message
+    pub args: Vec<Arg>,
+}
+
+/// An object identified by its interface and its protocol id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Object {
+    pub interface: String,
+    pub id: u32,
+}
+
+/// An argument of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    /// An object created by this message
+    NewId(Object),
+    /// A reference to an existing object
+    Object(Object),
+    /// A null object or string
+    Null,
+    /// Any other value, as printed
+    Value(String),
+}
+
+/// Parse a line of debug output
+///
+/// Returns `None` if the line is not a message, which happens when the output of the program is mixed
+/// with the debug output.
+pub fn parse_line(line: &str) -> Option<Line> {
+    let rest = line.trim_end().strip_prefix('[')?;
+    let (timestamp, rest) = rest.split_once(']')?;
+    let time = parse_timestamp(timestamp.trim())?;
+    let mut rest = rest.trim_start();
+
+    let mut queue = None;
+    if let Some(queued) = rest.strip_prefix('{') {
+        let (name, after) = queued.split_once('}')?;
+        queue = Some(name.to_owned());
+        rest = after.trim_start();
+    }
+
+    let mut discarded = false;
+    if let Some(after) = rest.strip_prefix("discarded ") {
+        discarded = true;
+        rest = after.trim_start();
+    }
+
+    let mut sent = false;
+    if let Some(after) = rest.strip_prefix("->") {
+        sent = true;
+        rest = after.trim_start();
+    } else if let Some(after) = rest.strip_prefix("<-") {
+        rest = after.trim_start();
+    }
+
+    let (head, args) = rest.split_once('(')?;
+    // the rust backends put a space, and sometimes a comma, before the arguments
+    let head = head.trim_end().trim_end_matches(',');
+    let (target, name) = head.split_once('.')?;
+    let target = parse_object(target)?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let args = args.strip_suffix(')')?;
+
+    Some(Line {
+        time,
+        queue,
+        discarded,
+        sent,
+        target,
+        name: name.to_owned(),
+        args: split_args(args).into_iter().map(parse_arg).collect(),
+    })
+}
+
+// libwayland prints milliseconds with 3 decimals, the rust backends print seconds with 6 decimals
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let (int, frac) = timestamp.split_once('.')?;
+    let int = int.parse::<u64>().ok()?;
+    let frac_value = frac.parse::<u64>().ok()?;
+    match frac.len() {
+        3 => Some(Duration::from_millis(int) + Duration::from_micros(frac_value)),
+        6 => Some(Duration::from_secs(int) + Duration::from_micros(frac_value)),
+        _ => None,
+    }
+}
+
+fn parse_object(text: &str) -> Option<Object> {
+    let (interface, id) = text.split_once('@')?;
+    if interface.is_empty()
+        || !interface.chars().all(|c| c.is_ascii_alphanumeric() || "_[]<>".contains(c))
+    {
+        return None;
+    }
+    Some(Object { interface: interface.to_owned(), id: id.parse().ok()? })
+}
+
+// Split the arguments on the commas that are not part of a string or an array
+fn split_args(args: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                result.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = args[start..].trim();
+    if !last.is_empty() || !result.is_empty() {
+        result.push(last);
+    }
+    result
+}
+
+fn parse_arg(arg: &str) -> Arg {
+    if let Some(object) = arg.strip_prefix("new id ").and_then(parse_object) {
+        return Arg::NewId(object);
+    }
+    if arg == "nil" || arg == "None" {
+        return Arg::Null;
+    }
+    // strings are printed as an `Option` by the rust backends
+    if let Some(string) = arg.strip_prefix("Some(").and_then(|s| s.strip_suffix(')')) {
+        return Arg::Value(string.to_owned());
+    }
+    match parse_object(arg) {
+        Some(object) if object.id == 0 => Arg::Null,
+        Some(object) => Arg::Object(object),
+        None => Arg::Value(arg.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obj(interface: &str, id: u32) -> Object {
+        Object { interface: interface.into(), id }
+    }
+
+    #[test]
+    fn libwayland_format() {
+        let line = parse_line(
+            "[3626514.330] {Default Queue}  -> wl_display@1.get_registry(new id wl_registry@2)",
+        )
+        .unwrap();
+        assert_eq!(line.time, Duration::from_micros(3_626_514_330));
+        assert_eq!(line.queue.as_deref(), Some("Default Queue"));
+        assert!(line.sent);
+        assert_eq!(line.target, obj("wl_display", 1));
+        assert_eq!(line.name, "get_registry");
+        assert_eq!(line.args, vec![Arg::NewId(obj("wl_registry", 2))]);
+
+        let line = parse_line("[3626514.493] discarded wl_pointer@7.motion(15, 1.5, nil)").unwrap();
+        assert!(line.discarded);
+        assert!(!line.sent);
+        assert_eq!(line.args, vec![Arg::Value("15".into()), Arg::Value("1.5".into()), Arg::Null]);
+    }
+
+    #[test]
+    fn rust_format() {
+        let line = parse_line(
+            "[1700000000.123456] <- wl_registry@2.global, (1, Some(\"wl, \\\"output\\\"\"), 4)",
+        )
+        .unwrap();
+        assert_eq!(line.time, Duration::from_secs(1_700_000_000) + Duration::from_micros(123456));
+        assert!(!line.sent);
+        assert_eq!(line.target, obj("wl_registry", 2));
+        assert_eq!(
+            line.args,
+            vec![
+                Arg::Value("1".into()),
+                Arg::Value("\"wl, \\\"output\\\"\"".into()),
+                Arg::Value("4".into())
+            ]
+        );
+
+        let line = parse_line("[1700000000.123456] -> wl_surface@3.frame (wl_callback@8)").unwrap();
+        assert!(line.sent);
+        assert_eq!(line.args, vec![Arg::Object(obj("wl_callback", 8))]);
+
+        let line = parse_line("[1700000000.123456] -> wl_surface@3.commit ()").unwrap();
+        assert!(line.args.is_empty());
+    }
+
+    #[test]
+    fn not_a_message() {
+        assert_eq!(parse_line("Hello world"), None);
+        assert_eq!(parse_line("[INFO] starting"), None);
+        assert_eq!(parse_line("[12.5] wl_display@1.sync(new id wl_callback@3)"), None);
+    }
+}
@@ -6,11 +6,13 @@ use std::{
     sync::Arc,
 };
 
-use crate::protocol::{Interface, Message, ObjectInfo};
+use crate::protocol::{
+    Direction, IdReusePolicy, Interface, IoStats, Message, MessageLimits, Metrics, ObjectInfo,
+};
 
 use super::client_impl;
 
-pub use crate::types::client::{InvalidId, NoWaylandLib, WaylandError};
+pub use crate::types::client::{FlushPolicy, InvalidId, NoWaylandLib, WaylandError};
 
 /// A trait representing your data associated to an object
 ///
@@ -60,6 +62,87 @@ impl std::fmt::Debug for dyn ObjectData {
 
 downcast_rs::impl_downcast!(sync ObjectData);
 
+/// A hook receiving the messages exchanged with the server
+///
+/// See [`Backend::set_protocol_logger()`]. It is implemented for the closures with the same
+/// arguments as [`log()`](ProtocolLogger::log).
+pub trait ProtocolLogger: Send + Sync {
+    /// A message was received from or is about to be sent to the server
+    ///
+    /// The message describes objects by their protocol id, and its file descriptors are only valid
+    /// during this call. The `interface` is the one of the object sending or receiving the message.
+    /// [`DisplayMessage`](crate::protocol::DisplayMessage) formats the message like the
+    /// `WAYLAND_DEBUG` output.
+    fn log(
+        &self,
+        direction: Direction,
+        interface: &'static Interface,
+        message: &Message<u32, RawFd>,
+    );
+}
+
+impl<F> ProtocolLogger for F
+where
+    F: Fn(Direction, &'static Interface, &Message<u32, RawFd>) + Send + Sync,
+{
+    fn log(
+        &self,
+        direction: Direction,
+        interface: &'static Interface,
+        message: &Message<u32, RawFd>,
+    ) {
+        self(direction, interface, message)
+    }
+}
+
+impl std::fmt::Debug for dyn ProtocolLogger {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolLogger").finish_non_exhaustive()
+    }
+}
+
+/// An event the server sent to an object the client had already destroyed
+///
+/// This is a normal race of the protocol: the server may send events to an object until it receives
+/// its destructor request. Like `libwayland-client` does for its zombie proxies, these events are
+/// discarded: the objects they create are zombies too, and their file descriptors are closed.
+#[derive(Debug, Clone, Copy)]
+pub struct ZombieEvent {
+    /// The protocol id of the destroyed object
+    pub object_id: u32,
+    /// The interface of the destroyed object
+    pub interface: &'static Interface,
+    /// The opcode of the event
+    pub opcode: u16,
+    /// The number of file descriptors the event carried, which were closed
+    pub fds: usize,
+}
+
+/// A hook notified of the events discarded because their object was destroyed
+///
+/// See [`Backend::set_zombie_hook()`]. It is implemented for the closures taking a [`ZombieEvent`].
+pub trait ZombieHook: Send + Sync {
+    /// An event was discarded
+    fn discarded(&self, event: &ZombieEvent);
+}
+
+impl<F> ZombieHook for F
+where
+    F: Fn(&ZombieEvent) + Send + Sync,
+{
+    fn discarded(&self, event: &ZombieEvent) {
+        self(event)
+    }
+}
+
+impl std::fmt::Debug for dyn ZombieHook {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZombieHook").finish_non_exhaustive()
+    }
+}
+
 /// An ID representing a Wayland object
 ///
 /// The backend internally tracks which IDs are still valid, invalidates them when the protocol object they
@@ -129,7 +212,9 @@ impl ObjectId {
 ///
 /// This type hosts all the interface for interacting with the wayland protocol. It can be
 /// cloned, all clones refer to the same underlying connection.
-#[derive(Clone, Debug)]
+///
+/// Two backends compare equal if they refer to the same connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Backend {
     pub(crate) backend: client_impl::InnerBackend,
 }
@@ -138,7 +223,9 @@ pub struct Backend {
 ///
 /// This handle behaves similarly to [`Weak`](std::sync::Weak), and can be used to keep access to
 /// the backend without actually preventing it from being dropped.
-#[derive(Clone, Debug)]
+///
+/// Two weak handles compare equal if they refer to the same connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct WeakBackend {
     inner: client_impl::WeakInnerBackend,
 }
@@ -209,6 +296,98 @@ impl Backend {
         self.backend.info(id)
     }
 
+    /// Get the IDs of all the objects that are currently alive, in order of protocol id
+    ///
+    /// The `wl_display` is not included, so this list is empty once all the objects created on this
+    /// connection have been destroyed. This can be used by tests to assert that no object is leaked. With the
+    /// `leak_report` cargo feature, the objects still alive when the connection is dropped are logged.
+    pub fn live_objects(&self) -> Vec<ObjectId> {
+        let mut objects = self.backend.live_objects();
+        objects.sort_by_key(ObjectId::protocol_id);
+        objects
+    }
+
+    /// Start counting the messages sent and received on this connection, by interface
+    ///
+    /// The counters start from zero when this is first called, further calls have no effect.
+    pub fn enable_metrics(&self) {
+        self.backend.enable_metrics()
+    }
+
+    /// Set the policy for reusing the ids of the objects destroyed from now on
+    ///
+    /// This has no effect with the system backend, whose ids are allocated by `libwayland-client.so`.
+    pub fn set_id_reuse_policy(&self, policy: IdReusePolicy) {
+        self.backend.set_id_reuse_policy(policy)
+    }
+
+    /// Set the hook receiving every message exchanged with the server, or remove it with `None`
+    ///
+    /// The logger is given the requests as they are sent and the events before they are dispatched,
+    /// from the thread sending or dispatching them. This gives access to the traffic of the
+    /// connection without parsing the `WAYLAND_DEBUG` output. The logger must not call back into
+    /// the backend.
+    ///
+    /// With the system backend, the events of the `wl_display`, processed by `libwayland-client.so`
+    /// itself, are not given to the logger.
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.backend.set_protocol_logger(logger)
+    }
+
+    /// Set the hook notified of the events received for destroyed objects, or remove it with `None`
+    ///
+    /// These events are discarded, see [`ZombieEvent`]. The hook is invoked from the thread reading
+    /// the events, and must not call back into the backend. With the system backend,
+    /// `libwayland-client.so` discards them itself and the hook is never invoked.
+    pub fn set_zombie_hook(&self, hook: Option<Arc<dyn ZombieHook>>) {
+        self.backend.set_zombie_hook(hook)
+    }
+
+    /// Get the current value of the message counters of this connection
+    ///
+    /// The returned counters are empty if [`enable_metrics()`](Backend::enable_metrics) was not called.
+    /// With the system backend, only the events received by objects managed by this library are
+    /// counted, those of the `wl_display` are handled by `libwayland-client.so` directly.
+    pub fn metrics(&self) -> Metrics {
+        self.backend.metrics()
+    }
+
+    /// Set when the requests sent on this connection are written to its socket
+    ///
+    /// See [`FlushPolicy`] for details, the default is [`FlushPolicy::Dispatch`].
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        self.backend.set_flush_policy(policy)
+    }
+
+    /// Set the limits on the size and file descriptors of the messages of this connection
+    ///
+    /// Sending a request exceeding them fails with an `E2BIG` IO error. An event exceeding them is a fatal
+    /// error of the connection: the dispatching fails with an `InvalidData` IO error whose inner error is
+    /// a [`MessageTooLarge`](crate::protocol::MessageTooLarge). The server must accept messages as large as
+    /// the ones it is sent. This has no effect with the system backend, whose limits are fixed.
+    pub fn set_message_limits(&self, limits: MessageLimits) {
+        self.backend.set_message_limits(limits)
+    }
+
+    /// The current flush policy of this connection
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.backend.flush_policy()
+    }
+
+    /// Number of bytes of requests waiting in the outgoing buffer to be written
+    ///
+    /// This is always 0 with the system backend, as `libwayland-client.so` does not expose its buffer.
+    pub fn pending_bytes(&self) -> usize {
+        self.backend.pending_bytes()
+    }
+
+    /// Get the I/O counters of this connection
+    ///
+    /// Unlike the message counters, these are always maintained.
+    pub fn io_stats(&self) -> IoStats {
+        self.backend.io_stats()
+    }
+
     /// Sends a request to the server
     ///
     /// Returns an error if the sender ID of the provided message is no longer valid.
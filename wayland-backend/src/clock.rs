@@ -0,0 +1,113 @@
+//! Sources of time for the timing-dependent helpers
+//!
+//! The helpers measuring time, like timeouts, watchdogs or rate limits, read it through a
+//! [`Clock`] rather than directly from [`Instant::now()`]. They use the [`SystemClock`] by default,
+//! and tests can give them a [`VirtualClock`] instead, advanced by hand, to check their behavior
+//! without sleeping:
+//!
+//! ```
+//! use std::time::Duration;
+//! use wayland_backend::clock::{Clock, VirtualClock};
+//!
+//! let clock = VirtualClock::new();
+//! let start = clock.now();
+//! clock.advance(Duration::from_secs(10));
+//! assert_eq!(clock.now() - start, Duration::from_secs(10));
+//! ```
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A source of time
+pub trait Clock: Debug + Send + Sync {
+    /// The current time
+    ///
+    /// Successive calls never return an earlier time.
+    fn now(&self) -> Instant;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+/// The clock of the system, giving [`Instant::now()`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock whose time only changes when it is advanced
+///
+/// The clones of a virtual clock share the same time, so that a test can keep one to advance the
+/// time seen by the helpers it gave the others to.
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl VirtualClock {
+    /// A virtual clock, starting at the current time of the system
+    pub fn new() -> Self {
+        Self { now: Arc::new(Mutex::new(Instant::now())) }
+    }
+
+    /// Move the time of the clock forward
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    /// Move the time of the clock forward to `instant`, if it is later than the current time
+    pub fn advance_to(&self, instant: Instant) {
+        let mut now = self.now.lock().unwrap();
+        *now = (*now).max(instant);
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_clock() {
+        let clock = VirtualClock::new();
+        let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+        let start = shared.now();
+        assert_eq!(shared.now(), start);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(shared.now() - start, Duration::from_millis(1500));
+
+        // the time never goes backward
+        clock.advance_to(start);
+        assert_eq!(shared.now() - start, Duration::from_millis(1500));
+        clock.advance_to(start + Duration::from_secs(2));
+        assert_eq!(shared.now() - start, Duration::from_secs(2));
+    }
+}
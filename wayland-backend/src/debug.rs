@@ -0,0 +1,657 @@
+//! Machine-readable traces of the protocol traffic
+//!
+//! The text printed when `WAYLAND_DEBUG` is set is meant to be read by humans. For traces to be
+//! post-processed by tools, the rust backends print them as JSON lines instead when
+//! `WAYLAND_DEBUG_FORMAT=json` is set as well.
+//!
+//! The same lines can be obtained programmatically, and with the system backends, by giving a
+//! [`JsonLogger`] to [`client::Backend::set_protocol_logger()`](crate::client::Backend::set_protocol_logger)
+//! or [`server::Handle::set_protocol_logger()`](crate::server::Handle::set_protocol_logger):
+//!
+//! ```no_run
+//! # fn setup(backend: &wayland_backend::client::Backend) -> std::io::Result<()> {
+//! use std::sync::Arc;
+//! use wayland_backend::debug::JsonLogger;
+//!
+//! let trace = std::fs::File::create("trace.jsonl")?;
+//! backend.set_protocol_logger(Some(Arc::new(JsonLogger::new(trace))));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each line is a JSON object with the following fields:
+//!
+//! - `time`: the time the message was logged at, in seconds since the unix epoch
+//! - `client`: on the server side, a number identifying the client among those connected
+//! - `direction`: `"sent"` or `"received"`
+//! - `interface`, `id`: the interface and protocol id of the object sending or receiving the message
+//! - `opcode`, `message`: the opcode and name of the message
+//! - `args`: the arguments of the message, as objects with a `type` and a `value`. The value of
+//!   `int`, `uint`, `object`, `new_id` and `fd` arguments is a number, with `0` for null objects.
+//!   It is a number as well for `fixed` arguments, a string or `null` for `string` arguments, and a
+//!   string of hexadecimal digits for `array` arguments.
+//!
+//! For example:
+//!
+//! ```text
+//! {"time":1700000000.123456,"direction":"sent","interface":"wl_surface","id":3,"opcode":1,"message":"attach","args":[{"type":"object","value":7},{"type":"int","value":0},{"type":"int","value":0}]}
+//! ```
+//!
+//! ## Transcripts
+//!
+//! For snapshot tests, a [`Transcript`] records the messages as canonical text lines instead, which do
+//! not depend on the time or on the values of the file descriptors, so that the traffic of a test can be
+//! compared to an expected sequence with [`diff_lines()`]:
+//!
+//! ```text
+//! -> wl_compositor@4.create_surface(new id wl_surface@5)
+//! <- wl_surface@5.enter(wl_output@6)
+//! -> wl_shm@3.create_pool(new id wl_shm_pool@7, fd#0, 4096)
+//! ```
+//!
+//! Each line starts with `->` for the messages sent and `<-` for those received, followed by the
+//! interface and id of the object and the name of the message. The arguments are formatted as follows:
+//!
+//! - `int`, `uint` and `fixed` arguments as decimal numbers
+//! - `string` arguments quoted and escaped as in JSON, or `nil` if null
+//! - `object` arguments as the interface and id of the object, or `nil` if null
+//! - `new_id` arguments as `new id` followed by the interface and id of the object, the interface
+//!   being `[unknown]` if the message does not specify it, as in `wl_registry.bind`
+//! - `array` arguments as `array[...]` around their bytes in hexadecimal
+//! - `fd` arguments as `fd#N`, where `N` numbers the file descriptors in the order of the transcript
+//!
+//! On the server side, the lines are prefixed by `[client N]`, numbering the clients in the order they
+//! first appear in the transcript.
+
+use std::{
+    fmt::{self, Write as _},
+    io::Write,
+    os::unix::io::{AsRawFd, RawFd},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::protocol::{
+    same_interface, Argument, Direction, Interface, Message, ANONYMOUS_INTERFACE,
+};
+
+/// A protocol logger writing the messages as JSON lines
+///
+/// It can be given to the clients and servers of all backends. Errors writing the lines are
+/// ignored.
+#[derive(Debug)]
+pub struct JsonLogger<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLogger<W> {
+    /// A logger writing to the given writer
+    ///
+    /// Each line is written with a single call to [`Write::write_all()`], wrap the writer in a
+    /// [`BufWriter`](std::io::BufWriter) if needed.
+    pub fn new(writer: W) -> Self {
+        Self { writer: Mutex::new(writer) }
+    }
+
+    /// Get back the writer of this logger
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn write(
+        &self,
+        client: Option<u64>,
+        direction: Direction,
+        interface: &'static Interface,
+        message: &Message<u32, RawFd>,
+        is_request: bool,
+    ) {
+        let messages = if is_request { interface.requests } else { interface.events };
+        let name = messages.get(message.opcode as usize).map_or("", |desc| desc.name);
+        let line = JsonLine {
+            client,
+            direction,
+            interface: interface.name,
+            id: message.sender_id,
+            opcode: message.opcode,
+            name,
+            args: &message.args,
+        };
+        let mut writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = writer.write_all(format!("{}\n", line).as_bytes());
+    }
+}
+
+impl JsonLogger<std::io::Stderr> {
+    /// A logger writing to the standard error, like `WAYLAND_DEBUG`
+    pub fn stderr() -> Self {
+        Self::new(std::io::stderr())
+    }
+}
+
+macro_rules! impl_json_logger {
+    ($backend:tt) => {
+        impl<W: Write + Send> crate::$backend::client::ProtocolLogger for JsonLogger<W> {
+            fn log(
+                &self,
+                direction: Direction,
+                interface: &'static Interface,
+                message: &Message<u32, RawFd>,
+            ) {
+                self.write(None, direction, interface, message, direction == Direction::Sent)
+            }
+        }
+    };
+}
+
+macro_rules! impl_server_json_logger {
+    ($backend:tt) => {
+        impl<W: Write + Send> crate::$backend::server::ProtocolLogger for JsonLogger<W> {
+            fn log(
+                &self,
+                client: crate::$backend::server::ClientId,
+                direction: Direction,
+                interface: &'static Interface,
+                message: &Message<u32, RawFd>,
+            ) {
+                let client = Some(client.as_u64());
+                self.write(client, direction, interface, message, direction == Direction::Received)
+            }
+        }
+    };
+}
+
+impl_json_logger!(rs);
+#[cfg(any(test, feature = "client_system"))]
+impl_json_logger!(sys);
+impl_server_json_logger!(rs);
+#[cfg(any(test, feature = "server_system"))]
+impl_server_json_logger!(sys);
+
+/// The protocol id of an object, as printed in the JSON lines
+pub(crate) trait ProtocolId {
+    fn protocol_id(&self) -> u32;
+}
+
+impl ProtocolId for u32 {
+    fn protocol_id(&self) -> u32 {
+        *self
+    }
+}
+
+impl ProtocolId for crate::rs::client::ObjectId {
+    fn protocol_id(&self) -> u32 {
+        self.protocol_id()
+    }
+}
+
+impl ProtocolId for crate::rs::server::ObjectId {
+    fn protocol_id(&self) -> u32 {
+        self.protocol_id()
+    }
+}
+
+/// A message formatted as a JSON line, without the line feed
+pub(crate) struct JsonLine<'a, Id, Fd> {
+    pub(crate) client: Option<u64>,
+    pub(crate) direction: Direction,
+    pub(crate) interface: &'a str,
+    pub(crate) id: u32,
+    pub(crate) opcode: u16,
+    pub(crate) name: &'a str,
+    pub(crate) args: &'a [Argument<Id, Fd>],
+}
+
+impl<'a, Id: ProtocolId, Fd: AsRawFd> fmt::Display for JsonLine<'a, Id, Fd> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(f, "{{\"time\":{}.{:06},", time.as_secs(), time.subsec_micros())?;
+        if let Some(client) = self.client {
+            write!(f, "\"client\":{},", client)?;
+        }
+        let direction = match self.direction {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        };
+        write!(f, "\"direction\":\"{}\",\"interface\":", direction)?;
+        write_json_str(f, self.interface)?;
+        write!(f, ",\"id\":{},\"opcode\":{},\"message\":", self.id, self.opcode)?;
+        write_json_str(f, self.name)?;
+        f.write_str(",\"args\":[")?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                f.write_char(',')?;
+            }
+            write_json_arg(f, arg)?;
+        }
+        f.write_str("]}")
+    }
+}
+
+fn write_json_arg<Id: ProtocolId, Fd: AsRawFd>(
+    f: &mut fmt::Formatter<'_>,
+    arg: &Argument<Id, Fd>,
+) -> fmt::Result {
+    match *arg {
+        Argument::Int(val) => write!(f, "{{\"type\":\"int\",\"value\":{}}}", val),
+        Argument::Uint(val) => write!(f, "{{\"type\":\"uint\",\"value\":{}}}", val),
+        Argument::Fixed(val) => {
+            write!(f, "{{\"type\":\"fixed\",\"value\":{}}}", val as f64 / 256.)
+        }
+        Argument::Str(ref val) => {
+            f.write_str("{\"type\":\"string\",\"value\":")?;
+            match *val {
+                Some(ref val) => write_json_str(f, &val.to_string_lossy())?,
+                None => f.write_str("null")?,
+            }
+            f.write_char('}')
+        }
+        Argument::Object(ref id) => {
+            write!(f, "{{\"type\":\"object\",\"value\":{}}}", id.protocol_id())
+        }
+        Argument::NewId(ref id) => {
+            write!(f, "{{\"type\":\"new_id\",\"value\":{}}}", id.protocol_id())
+        }
+        Argument::Array(ref val) => {
+            f.write_str("{\"type\":\"array\",\"value\":\"")?;
+            for byte in val.iter() {
+                write!(f, "{:02x}", byte)?;
+            }
+            f.write_str("\"}")
+        }
+        Argument::Fd(ref fd) => write!(f, "{{\"type\":\"fd\",\"value\":{}}}", fd.as_raw_fd()),
+    }
+}
+
+/// A protocol logger recording the messages as canonical text lines
+///
+/// See the [module documentation](self) for the format of the lines. It can be given to the clients
+/// and servers of all backends.
+#[derive(Debug, Default)]
+pub struct Transcript {
+    inner: Mutex<TranscriptInner>,
+}
+
+#[derive(Debug, Default)]
+struct TranscriptInner {
+    lines: Vec<String>,
+    // the number of file descriptors of the lines
+    fds: usize,
+    // the clients in the order they appeared
+    clients: Vec<u64>,
+}
+
+impl Transcript {
+    /// An empty transcript
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The lines recorded so far
+    pub fn lines(&self) -> Vec<String> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner()).lines.clone()
+    }
+
+    /// Take the lines recorded so far, emptying the transcript
+    ///
+    /// The file descriptors of the next lines are numbered from zero again, while the clients keep
+    /// their numbers.
+    pub fn take_lines(&self) -> Vec<String> {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.fds = 0;
+        std::mem::take(&mut inner.lines)
+    }
+
+    fn record(
+        &self,
+        client: Option<u64>,
+        direction: Direction,
+        interface: &'static Interface,
+        message: &Message<u32, RawFd>,
+        is_request: bool,
+    ) {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        let mut line = String::new();
+        if let Some(client) = client {
+            let index = match inner.clients.iter().position(|&c| c == client) {
+                Some(index) => index,
+                None => {
+                    inner.clients.push(client);
+                    inner.clients.len() - 1
+                }
+            };
+            let _ = write!(line, "[client {}] ", index);
+        }
+        let messages = if is_request { interface.requests } else { interface.events };
+        let desc = messages.get(message.opcode as usize);
+        let arrow = match direction {
+            Direction::Sent => "->",
+            Direction::Received => "<-",
+        };
+        let _ = write!(line, "{} {}@{}.", arrow, interface.name, message.sender_id);
+        match desc {
+            Some(desc) => line.push_str(desc.name),
+            None => {
+                let _ = write!(line, "#{}", message.opcode);
+            }
+        }
+        line.push('(');
+        let mut arg_interfaces = desc.map_or(&[][..], |desc| desc.arg_interfaces).iter();
+        for (i, arg) in message.args.iter().enumerate() {
+            if i > 0 {
+                line.push_str(", ");
+            }
+            let _ = match *arg {
+                Argument::Int(val) => write!(line, "{}", val),
+                Argument::Uint(val) => write!(line, "{}", val),
+                Argument::Fixed(val) => write!(line, "{}", val as f64 / 256.),
+                Argument::Str(Some(ref val)) => {
+                    write!(line, "{}", JsonStr(&val.to_string_lossy()))
+                }
+                Argument::Str(None) => write!(line, "nil"),
+                Argument::Object(id) => {
+                    let name = arg_interfaces.next().map_or("[unknown]", |iface| known_name(iface));
+                    if id == 0 {
+                        write!(line, "nil")
+                    } else {
+                        write!(line, "{}@{}", name, id)
+                    }
+                }
+                Argument::NewId(id) => {
+                    let name =
+                        desc.and_then(|desc| desc.child_interface).map_or("[unknown]", known_name);
+                    write!(line, "new id {}@{}", name, id)
+                }
+                Argument::Array(ref val) => {
+                    line.push_str("array[");
+                    for byte in val.iter() {
+                        let _ = write!(line, "{:02x}", byte);
+                    }
+                    write!(line, "]")
+                }
+                Argument::Fd(_) => {
+                    inner.fds += 1;
+                    write!(line, "fd#{}", inner.fds - 1)
+                }
+            };
+        }
+        line.push(')');
+        inner.lines.push(line);
+    }
+}
+
+// the name of an interface in the transcripts, objects of any interface being `[unknown]`
+fn known_name(interface: &'static Interface) -> &'static str {
+    if same_interface(interface, &ANONYMOUS_INTERFACE) {
+        "[unknown]"
+    } else {
+        interface.name
+    }
+}
+
+macro_rules! impl_transcript {
+    ($backend:tt) => {
+        impl crate::$backend::client::ProtocolLogger for Transcript {
+            fn log(
+                &self,
+                direction: Direction,
+                interface: &'static Interface,
+                message: &Message<u32, RawFd>,
+            ) {
+                self.record(None, direction, interface, message, direction == Direction::Sent)
+            }
+        }
+    };
+}
+
+macro_rules! impl_server_transcript {
+    ($backend:tt) => {
+        impl crate::$backend::server::ProtocolLogger for Transcript {
+            fn log(
+                &self,
+                client: crate::$backend::server::ClientId,
+                direction: Direction,
+                interface: &'static Interface,
+                message: &Message<u32, RawFd>,
+            ) {
+                let client = Some(client.as_u64());
+                self.record(client, direction, interface, message, direction == Direction::Received)
+            }
+        }
+    };
+}
+
+impl_transcript!(rs);
+#[cfg(any(test, feature = "client_system"))]
+impl_transcript!(sys);
+impl_server_transcript!(rs);
+#[cfg(any(test, feature = "server_system"))]
+impl_server_transcript!(sys);
+
+/// The differences between two sequences of lines
+///
+/// It is displayed as the lines of both sequences, the lines only expected being prefixed by `-`, those
+/// only in the actual sequence by `+`, and the common lines by two spaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDiff {
+    lines: Vec<(char, String)>,
+}
+
+impl LineDiff {
+    /// The lines expected but missing from the actual sequence
+    pub fn missing(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter(|(tag, _)| *tag == '-').map(|(_, line)| line.as_str())
+    }
+
+    /// The lines of the actual sequence which were not expected
+    pub fn unexpected(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter(|(tag, _)| *tag == '+').map(|(_, line)| line.as_str())
+    }
+}
+
+impl fmt::Display for LineDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (tag, line) in &self.lines {
+            let tag = if *tag == '=' { ' ' } else { *tag };
+            writeln!(f, "{} {}", tag, line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the lines of a transcript to the expected ones
+///
+/// Returns `None` if they are the same, and their differences otherwise, based on their longest common
+/// subsequence of lines.
+pub fn diff_lines<E: AsRef<str>, A: AsRef<str>>(expected: &[E], actual: &[A]) -> Option<LineDiff> {
+    let (n, m) = (expected.len(), actual.len());
+    if n == m && expected.iter().zip(actual).all(|(e, a)| e.as_ref() == a.as_ref()) {
+        return None;
+    }
+    // lengths of the longest common subsequences of the suffixes
+    let mut lcs = vec![0usize; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if expected[i].as_ref() == actual[j].as_ref() {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i].as_ref() == actual[j].as_ref() {
+            lines.push(('=', expected[i].as_ref().to_owned()));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i * (m + 1) + j + 1] >= lcs[(i + 1) * (m + 1) + j]) {
+            lines.push(('+', actual[j].as_ref().to_owned()));
+            j += 1;
+        } else {
+            lines.push(('-', expected[i].as_ref().to_owned()));
+            i += 1;
+        }
+    }
+    Some(LineDiff { lines })
+}
+
+// A string quoted and escaped as in JSON
+struct JsonStr<'a>(&'a str);
+
+impl fmt::Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_json_str(f, self.0)
+    }
+}
+
+fn write_json_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_interfaces::WL_DISPLAY_INTERFACE;
+    use std::ffi::CString;
+
+    #[test]
+    fn json_line() {
+        let logger = JsonLogger::new(Vec::new());
+        let message = Message {
+            sender_id: 1u32,
+            opcode: 0,
+            args: smallvec::smallvec![
+                Argument::Object(3),
+                Argument::Uint(2),
+                Argument::Str(Some(Box::new(CString::new("a \"quoted\"\n\u{1}").unwrap()))),
+                Argument::Array(Box::new(vec![0, 0xab])),
+                Argument::Fixed(-384),
+                Argument::Str(None),
+            ],
+        };
+        crate::rs::client::ProtocolLogger::log(
+            &logger,
+            Direction::Received,
+            &WL_DISPLAY_INTERFACE,
+            &message,
+        );
+        let output = String::from_utf8(logger.into_inner()).unwrap();
+        let (time, line) = output.split_once(',').unwrap();
+        assert!(time.starts_with("{\"time\":"));
+        assert_eq!(
+            line,
+            concat!(
+                r#""direction":"received","interface":"wl_display","id":1,"opcode":0,"#,
+                r#""message":"error","args":[{"type":"object","value":3},"#,
+                r#"{"type":"uint","value":2},"#,
+                r#"{"type":"string","value":"a \"quoted\"\n\u0001"},"#,
+                r#"{"type":"array","value":"00ab"},{"type":"fixed","value":-1.5},"#,
+                r#"{"type":"string","value":null}]}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn transcript_lines() {
+        use crate::core_interfaces::{WL_CALLBACK_INTERFACE, WL_REGISTRY_INTERFACE};
+
+        let transcript = Transcript::new();
+        let error = Message {
+            sender_id: 1u32,
+            opcode: 0,
+            args: smallvec::smallvec![
+                Argument::Object(3),
+                Argument::Uint(2),
+                Argument::Str(Some(Box::new(CString::new("a \"quoted\"\n").unwrap()))),
+            ],
+        };
+        let sync =
+            Message { sender_id: 1u32, opcode: 0, args: smallvec::smallvec![Argument::NewId(4)] };
+        let bind = Message {
+            sender_id: 2u32,
+            opcode: 0,
+            args: smallvec::smallvec![
+                Argument::Uint(1),
+                Argument::Str(Some(Box::new(CString::new("wl_shm").unwrap()))),
+                Argument::Uint(1),
+                Argument::NewId(5),
+            ],
+        };
+        let done =
+            Message { sender_id: 4u32, opcode: 0, args: smallvec::smallvec![Argument::Uint(7)] };
+        let client = |trans: &Transcript, dir, iface, msg: &Message<u32, RawFd>| {
+            crate::rs::client::ProtocolLogger::log(trans, dir, iface, msg)
+        };
+        client(&transcript, Direction::Sent, &WL_DISPLAY_INTERFACE, &sync);
+        client(&transcript, Direction::Sent, &WL_REGISTRY_INTERFACE, &bind);
+        client(&transcript, Direction::Received, &WL_CALLBACK_INTERFACE, &done);
+        client(&transcript, Direction::Received, &WL_DISPLAY_INTERFACE, &error);
+        assert_eq!(
+            transcript.lines(),
+            [
+                "-> wl_display@1.sync(new id wl_callback@4)",
+                "-> wl_registry@2.bind(1, \"wl_shm\", 1, new id [unknown]@5)",
+                "<- wl_callback@4.done(7)",
+                "<- wl_display@1.error([unknown]@3, 2, \"a \\\"quoted\\\"\\n\")",
+            ]
+        );
+
+        // the fds are numbered from zero in each batch of lines, the clients keep their numbers
+        let fds = Message {
+            sender_id: 3u32,
+            opcode: 7,
+            args: smallvec::smallvec![
+                Argument::Fd(12),
+                Argument::Object(0),
+                Argument::Fd(4),
+                Argument::Array(Box::new(vec![0, 0xab])),
+                Argument::Fixed(-384),
+            ],
+        };
+        assert_eq!(transcript.take_lines().len(), 4);
+        for client in [7, 3, 7] {
+            transcript.record(
+                Some(client),
+                Direction::Received,
+                &WL_CALLBACK_INTERFACE,
+                &fds,
+                true,
+            );
+        }
+        assert_eq!(
+            transcript.take_lines(),
+            [
+                "[client 0] <- wl_callback@3.#7(fd#0, nil, fd#1, array[00ab], -1.5)",
+                "[client 1] <- wl_callback@3.#7(fd#2, nil, fd#3, array[00ab], -1.5)",
+                "[client 0] <- wl_callback@3.#7(fd#4, nil, fd#5, array[00ab], -1.5)",
+            ]
+        );
+    }
+
+    #[test]
+    fn line_diff() {
+        let expected = ["a", "b", "c", "d"];
+        assert_eq!(diff_lines(&expected, &expected), None);
+        let diff = diff_lines(&expected, &["a", "c", "e", "d"]).unwrap();
+        assert_eq!(diff.missing().collect::<Vec<_>>(), ["b"]);
+        assert_eq!(diff.unexpected().collect::<Vec<_>>(), ["e"]);
+        assert_eq!(diff.to_string(), "  a\n- b\n  c\n+ e\n  d\n");
+        let diff = diff_lines::<&str, _>(&[], &["a"]).unwrap();
+        assert_eq!(diff.to_string(), "+ a\n");
+    }
+}
@@ -31,6 +31,13 @@
 //! those messages are printed to stderr. If you activate the `log` cargo feature, they will instead be
 //! piped through the `log` crate.
 //!
+//! ## Tracing
+//!
+//! With the `tracing` cargo feature, the rust backends emit spans of the `tracing` crate at the trace
+//! level around the reads from the socket (`read`) and the flushes (`flush`, with the number of `bytes`
+//! to write), and an event for each message decoded, with the `interface`, `id` and `opcode` of its
+//! object. On the server side, they all carry the `client` they concern. The system backends emit none.
+//!
 //! ## raw-window-handle integration
 //!
 //! This crate can implement [`HasRawWindowHandle`](raw_window_handle::HasRawWindowHandle) for the client
@@ -72,6 +79,30 @@ use std::{
     eprintln as log_error, eprintln as log_warn, eprintln as log_info, eprintln as log_debug,
 };
 
+// internal spans and events of the `tracing` crate, which expand to nothing without the `tracing` feature
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($name: literal $(, $($fields: tt)*)?) => {
+        let _span = tracing::trace_span!($name $(, $($fields)*)?).entered();
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($args: tt)*) => {};
+}
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($($args: tt)*) => {
+        tracing::trace!($($args)*)
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+    ($($args: tt)*) => {};
+}
+#[allow(unused_imports)]
+use {trace_event, trace_span};
+
 #[cfg(any(test, feature = "client_system", feature = "server_system"))]
 pub mod sys;
 
@@ -90,7 +121,9 @@ pub use sys::server;
 #[cfg(test)]
 mod test;
 
+pub mod clock;
 mod core_interfaces;
+pub mod debug;
 pub mod protocol;
 mod types;
 
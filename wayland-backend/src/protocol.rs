@@ -1,138 +1,48 @@
 //! Types and utilities for manipulating the Wayland protocol
 
-use std::{ffi::CString, os::unix::prelude::AsRawFd};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    os::unix::prelude::{AsRawFd, RawFd},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::clock::{Clock, SystemClock};
 
 pub use wayland_sys::common::{wl_argument, wl_interface, wl_message};
 
-/// Describes whether an argument may have a null value.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum AllowNull {
-    /// Null values are allowed.
-    Yes,
-    /// Null values are forbidden.
-    No,
-}
-
-/// Enum of possible argument types as recognized by the wire
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub enum ArgumentType {
-    /// An integer argument. Represented by a [`i32`].
-    Int,
-    /// An unsigned integer argument. Represented by a [`u32`].
-    Uint,
-    /// A signed fixed point number with 1/256 precision
-    Fixed,
-    /// A string. This is represented as a [`CString`] in a message.
-    Str(AllowNull),
-    /// Id of a wayland object
-    Object(AllowNull),
-    /// Id of a newly created wayland object
-    NewId,
-    /// `Vec<u8>`
-    Array,
-    /// A file descriptor argument. Represented by a [`RawFd`].
-    ///
-    /// [`RawFd`]: std::os::fd::RawFd
-    Fd,
-}
+pub use wayland_wire::{AllowNull, Argument, ArgumentType, Message, INLINE_ARGS};
 
-impl ArgumentType {
-    /// Returns true if the type of the argument is the same.
-    pub fn same_type(self, other: Self) -> bool {
-        std::mem::discriminant(&self) == std::mem::discriminant(&other)
-    }
+/// Copies of the messages and arguments with protocol ids and raw fds, as given to the protocol loggers
+pub(crate) trait ToLogged<Id> {
+    type Logged;
+    fn to_logged(&self, protocol_id: impl Fn(&Id) -> u32) -> Self::Logged;
 }
 
-/// Enum of possible argument of the protocol
-#[derive(Debug, Clone)]
-#[allow(clippy::box_collection)]
-pub enum Argument<Id, Fd> {
-    /// An integer argument. Represented by a [`i32`].
-    Int(i32),
-    /// An unsigned integer argument. Represented by a [`u32`].
-    Uint(u32),
-    /// A signed fixed point number with 1/256 precision
-    Fixed(i32),
-    /// CString
-    ///
-    /// The value is boxed to reduce the stack size of Argument. The performance
-    /// impact is negligible as `string` arguments are pretty rare in the protocol.
-    Str(Option<Box<CString>>),
-    /// Id of a wayland object
-    Object(Id),
-    /// Id of a newly created wayland object
-    NewId(Id),
-    /// `Vec<u8>`
-    ///
-    /// The value is boxed to reduce the stack size of Argument. The performance
-    /// impact is negligible as `array` arguments are pretty rare in the protocol.
-    Array(Box<Vec<u8>>),
-    /// A file descriptor argument. Represented by a [`RawFd`].
-    ///
-    /// [`RawFd`]: std::os::fd::RawFd
-    Fd(Fd),
-}
+impl<Id, Fd: AsRawFd> ToLogged<Id> for Argument<Id, Fd> {
+    type Logged = Argument<u32, RawFd>;
 
-impl<Id, Fd> Argument<Id, Fd> {
-    /// Retrieve the type of a given argument instance
-    pub fn get_type(&self) -> ArgumentType {
+    fn to_logged(&self, protocol_id: impl Fn(&Id) -> u32) -> Argument<u32, RawFd> {
         match *self {
-            Self::Int(_) => ArgumentType::Int,
-            Self::Uint(_) => ArgumentType::Uint,
-            Self::Fixed(_) => ArgumentType::Fixed,
-            Self::Str(_) => ArgumentType::Str(AllowNull::Yes),
-            Self::Object(_) => ArgumentType::Object(AllowNull::Yes),
-            Self::NewId(_) => ArgumentType::NewId,
-            Self::Array(_) => ArgumentType::Array,
-            Self::Fd(_) => ArgumentType::Fd,
-        }
-    }
-
-    fn map_fd<T>(self, f: &mut impl FnMut(Fd) -> T) -> Argument<Id, T> {
-        match self {
-            Self::Int(val) => Argument::Int(val),
-            Self::Uint(val) => Argument::Uint(val),
-            Self::Fixed(val) => Argument::Fixed(val),
-            Self::Str(val) => Argument::Str(val),
-            Self::Object(val) => Argument::Object(val),
-            Self::NewId(val) => Argument::NewId(val),
-            Self::Array(val) => Argument::Array(val),
-            Self::Fd(val) => Argument::Fd(f(val)),
+            Argument::Int(val) => Argument::Int(val),
+            Argument::Uint(val) => Argument::Uint(val),
+            Argument::Fixed(val) => Argument::Fixed(val),
+            Argument::Str(ref val) => Argument::Str(val.clone()),
+            Argument::Object(ref id) => Argument::Object(protocol_id(id)),
+            Argument::NewId(ref id) => Argument::NewId(protocol_id(id)),
+            Argument::Array(ref val) => Argument::Array(val.clone()),
+            Argument::Fd(ref fd) => Argument::Fd(fd.as_raw_fd()),
         }
     }
 }
 
-impl<Id: PartialEq, Fd: AsRawFd> PartialEq for Argument<Id, Fd> {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Int(a), Self::Int(b)) => a == b,
-            (Self::Uint(a), Self::Uint(b)) => a == b,
-            (Self::Fixed(a), Self::Fixed(b)) => a == b,
-            (Self::Str(a), Self::Str(b)) => a == b,
-            (Self::Object(a), Self::Object(b)) => a == b,
-            (Self::NewId(a), Self::NewId(b)) => a == b,
-            (Self::Array(a), Self::Array(b)) => a == b,
-            (Self::Fd(a), Self::Fd(b)) => a.as_raw_fd() == b.as_raw_fd(),
-            _ => false,
-        }
-    }
-}
+impl<Id, Fd: AsRawFd> ToLogged<Id> for Message<Id, Fd> {
+    type Logged = Message<u32, RawFd>;
 
-impl<Id: Eq, Fd: AsRawFd> Eq for Argument<Id, Fd> {}
-
-impl<Id: std::fmt::Display, Fd: AsRawFd> std::fmt::Display for Argument<Id, Fd> {
-    #[cfg_attr(coverage, coverage(off))]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Int(value) => write!(f, "{}", value),
-            Self::Uint(value) => write!(f, "{}", value),
-            Self::Fixed(value) => write!(f, "{}", value),
-            Self::Str(value) => write!(f, "{:?}", value),
-            Self::Object(value) => write!(f, "{}", value),
-            Self::NewId(value) => write!(f, "{}", value),
-            Self::Array(value) => write!(f, "{:?}", value),
-            Self::Fd(value) => write!(f, "{}", value.as_raw_fd()),
-        }
+    fn to_logged(&self, protocol_id: impl Fn(&Id) -> u32) -> Message<u32, RawFd> {
+        let args = self.args.iter().map(|arg| arg.to_logged(&protocol_id)).collect();
+        Message { sender_id: protocol_id(&self.sender_id), opcode: self.opcode, args }
     }
 }
 
@@ -195,6 +105,197 @@ pub struct ObjectInfo {
     pub version: u32,
 }
 
+/// Number of messages exchanged about objects of a given interface
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageCounts {
+    /// Number of messages sent (requests for a client, events for a server)
+    pub sent: u64,
+    /// Number of messages received (events for a client, requests for a server)
+    pub received: u64,
+}
+
+/// Counters of the messages exchanged on a connection, by interface
+///
+/// These are only maintained once they have been enabled on the backend, as they add a hash map
+/// lookup to the processing of every message.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Counters for each interface, by interface name
+    pub interfaces: HashMap<&'static str, MessageCounts>,
+}
+
+impl Metrics {
+    /// Sum of the counters over all interfaces
+    pub fn total(&self) -> MessageCounts {
+        self.interfaces.values().fold(MessageCounts::default(), |total, counts| MessageCounts {
+            sent: total.sent + counts.sent,
+            received: total.received + counts.received,
+        })
+    }
+
+    /// Counters for the interface with this name, zero if no message about it was seen
+    pub fn interface(&self, name: &str) -> MessageCounts {
+        self.interfaces.get(name).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn count_sent(&mut self, interface: &'static Interface) {
+        self.interfaces.entry(interface.name).or_default().sent += 1;
+    }
+
+    pub(crate) fn count_received(&mut self, interface: &'static Interface) {
+        self.interfaces.entry(interface.name).or_default().received += 1;
+    }
+}
+
+/// Counters of the I/O performed on a connection
+///
+/// On the server, they are given for each client by `Handle::get_client_io_stats()` and summed over
+/// all the clients by `Handle::io_stats()`, for example to be exported as metrics.
+///
+/// With the system backends, the socket is read and written by libwayland, so only the message and
+/// object counters are maintained, the others remain zero. These counters then also leave out the
+/// messages and objects handled by libwayland itself, like the `wl_display` and, on the server, the
+/// `wl_registry`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IoStats {
+    /// Number of bytes read from the socket
+    pub bytes_read: u64,
+    /// Number of bytes written to the socket
+    pub bytes_written: u64,
+    /// Number of messages received (events for a client, requests for a server)
+    pub messages_received: u64,
+    /// Number of messages sent (requests for a client, events for a server)
+    pub messages_sent: u64,
+    /// Number of file descriptors received
+    pub fds_received: u64,
+    /// Number of file descriptors sent
+    pub fds_sent: u64,
+    /// Number of system calls reading from the socket
+    pub read_calls: u64,
+    /// Number of system calls writing to the socket
+    pub write_calls: u64,
+    /// Number of objects currently alive on the connection, excluding the `wl_display`
+    pub live_objects: u64,
+    /// Number of file descriptors currently buffered, either received and not yet handed to a
+    /// message, or queued and not yet sent
+    pub fds_in_flight: u64,
+}
+
+impl std::ops::AddAssign for IoStats {
+    fn add_assign(&mut self, other: IoStats) {
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+        self.messages_received += other.messages_received;
+        self.messages_sent += other.messages_sent;
+        self.fds_received += other.fds_received;
+        self.fds_sent += other.fds_sent;
+        self.read_calls += other.read_calls;
+        self.write_calls += other.write_calls;
+        self.live_objects += other.live_objects;
+        self.fds_in_flight += other.fds_in_flight;
+    }
+}
+
+/// Policy for reusing the ids of destroyed objects
+///
+/// By default, the id of a destroyed object is given to the next object created, which hides the
+/// messages still in flight for the destroyed object: they end up on its successor. Delaying the
+/// reuse of the ids makes these messages target a dead id instead, which is a protocol error that
+/// [`strict`](IdReusePolicy::strict) mode reports along with the cause.
+///
+/// An id is reused once both delays are over. Only the rust backends follow this policy, libwayland
+/// allocates the ids itself with the system backends.
+#[derive(Clone, Debug)]
+pub struct IdReusePolicy {
+    /// Number of object creations during which a freed id is not reused
+    pub delay_allocations: u32,
+    /// Minimum time between the destruction of an object and the reuse of its id
+    pub delay: Duration,
+    /// Log a warning for each message received for an object that was destroyed and whose id was not
+    /// reused yet
+    pub strict: bool,
+    /// The clock measuring the [`delay`](IdReusePolicy::delay)
+    pub clock: Arc<dyn Clock>,
+}
+
+impl IdReusePolicy {
+    /// Whether ids are reused immediately, without tracking the destroyed objects
+    pub(crate) fn is_immediate(&self) -> bool {
+        self.delay_allocations == 0 && self.delay.is_zero() && !self.strict
+    }
+}
+
+impl Default for IdReusePolicy {
+    fn default() -> Self {
+        Self {
+            delay_allocations: 0,
+            delay: Duration::ZERO,
+            strict: false,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+/// Limits on the messages exchanged over a connection
+///
+/// The defaults are those of libwayland: 4096 bytes and 28 file descriptors per message. A larger
+/// limit still cannot exceed 65535 bytes, the largest size the header of a message can encode.
+/// Only the rust backends apply these limits, libwayland's are fixed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MessageLimits {
+    /// Maximum size in bytes of a message, header included
+    pub max_size: usize,
+    /// Maximum number of file descriptors of a message
+    pub max_fds: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self { max_size: 4096, max_fds: 28 }
+    }
+}
+
+/// Error of a message exceeding the [`MessageLimits`] of its connection
+///
+/// It is the inner error of the `InvalidData` IO error a client gets when the server sends such an
+/// event.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MessageTooLarge {
+    /// Protocol id of the object the message was sent to
+    pub object_id: u32,
+    /// Opcode of the message
+    pub opcode: u16,
+    /// Size in bytes of the message, as given by its header
+    pub size: usize,
+    /// Number of file descriptors of the message, according to its signature
+    ///
+    /// It is 0 if the size of the message alone exceeds the limits.
+    pub fds: usize,
+    /// The limits of the connection
+    pub limits: MessageLimits,
+}
+
+impl std::error::Error for MessageTooLarge {}
+
+impl std::fmt::Display for MessageTooLarge {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        if self.size > self.limits.max_size {
+            write!(
+                f,
+                "Message {} of object {} is {} bytes long, more than the limit of {} bytes.",
+                self.opcode, self.object_id, self.size, self.limits.max_size
+            )
+        } else {
+            write!(
+                f,
+                "Message {} of object {} carries {} file descriptors, more than the limit of {}.",
+                self.opcode, self.object_id, self.fds, self.limits.max_fds
+            )
+        }
+    }
+}
+
 /// A protocol error
 ///
 /// This kind of error is generated by the server if your client didn't respect
@@ -214,42 +315,104 @@ pub struct ProtocolError {
     pub message: String,
 }
 
-/// Number of arguments that are stocked inline in a `Message` before allocating
+impl ProtocolError {
+    /// Interpret the code of this error as a value of the error enum `E`
+    ///
+    /// Returns `None` if the object that caused the error is not of the interface of `E`, or if
+    /// the code is not a known value of `E`. The errors of `wl_display` are an exception, as they
+    /// can be raised on objects of any interface: their interface is not checked.
+    pub fn code_as<E: InterfaceError>(&self) -> Option<E> {
+        let interface = E::interface();
+        if interface.name != "wl_display" && interface.name != self.object_interface {
+            return None;
+        }
+        E::try_from(self.code).ok()
+    }
+}
+
+/// The `error` enum of an interface
 ///
-/// This is a ad-hoc number trying to reach a good balance between avoiding too many allocations
-/// and keeping the stack size of `Message` small.
-// Note: Keep in sync with `wayland_scanner::common::gen_write_body`.
-pub const INLINE_ARGS: usize = 4;
+/// It is implemented by the code generated by `wayland-scanner`, to interpret the code of a
+/// [`ProtocolError`] with [`ProtocolError::code_as()`].
+pub trait InterfaceError: TryFrom<u32> + Into<u32> + Copy + std::fmt::Debug {
+    /// The interface defining this enum
+    fn interface() -> &'static Interface;
+}
 
-/// Represents a message that has been sent from some object.
-#[derive(Clone, Debug)]
-pub struct Message<Id, Fd> {
-    /// The id of the object that sent the message.
-    pub sender_id: Id,
-    /// The opcode of the message.
-    pub opcode: u16,
-    /// The arguments of the message.
-    pub args: smallvec::SmallVec<[Argument<Id, Fd>; INLINE_ARGS]>,
+/// The direction of a message given to a protocol logger
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The message was received, and is about to be dispatched
+    Received,
+    /// The message is about to be sent
+    Sent,
 }
 
-impl<Id, Fd> Message<Id, Fd> {
-    /// Map some closure on all Fd contained in this message, to change the Fd generic parameter.
-    pub fn map_fd<T>(self, mut f: impl FnMut(Fd) -> T) -> Message<Id, T> {
-        Message {
-            sender_id: self.sender_id,
-            opcode: self.opcode,
-            args: self.args.into_iter().map(move |arg| arg.map_fd(&mut f)).collect(),
-        }
-    }
+/// A message displayed with the names of its interface, of itself and of the interfaces of its
+/// arguments
+///
+/// The message is printed like in the `WAYLAND_DEBUG` output of libwayland, for example
+/// `wl_surface@13.attach(wl_buffer@15, 0, 0)`, which makes it suitable for the messages given to the
+/// protocol loggers. Whether the message is a request or an event is given by the constructor,
+/// [`DisplayMessage::request()`] or [`DisplayMessage::event()`], as the interface alone does not
+/// tell.
+#[derive(Debug)]
+pub struct DisplayMessage<'a, Fd> {
+    interface: &'static Interface,
+    desc: Option<&'static MessageDesc>,
+    message: &'a Message<u32, Fd>,
 }
 
-impl<Id: PartialEq, Fd: AsRawFd> PartialEq for Message<Id, Fd> {
-    fn eq(&self, other: &Self) -> bool {
-        self.sender_id == other.sender_id && self.opcode == other.opcode && self.args == other.args
+impl<'a, Fd: AsRawFd> DisplayMessage<'a, Fd> {
+    /// Display a message as a request sent to an object of the given interface
+    pub fn request(message: &'a Message<u32, Fd>, interface: &'static Interface) -> Self {
+        DisplayMessage { interface, desc: interface.requests.get(message.opcode as usize), message }
+    }
+
+    /// Display a message as an event sent by an object of the given interface
+    pub fn event(message: &'a Message<u32, Fd>, interface: &'static Interface) -> Self {
+        DisplayMessage { interface, desc: interface.events.get(message.opcode as usize), message }
     }
 }
 
-impl<Id: Eq, Fd: AsRawFd> Eq for Message<Id, Fd> {}
+impl<'a, Fd: AsRawFd> std::fmt::Display for DisplayMessage<'a, Fd> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}.", self.interface.name, self.message.sender_id)?;
+        match self.desc {
+            Some(desc) => f.write_str(desc.name)?,
+            None => write!(f, "[unknown opcode {}]", self.message.opcode)?,
+        }
+        // the interfaces of the object arguments, in order
+        let mut arg_interfaces = self.desc.map_or(&[][..], |desc| desc.arg_interfaces).iter();
+        let child_interface = self.desc.and_then(|desc| desc.child_interface);
+        f.write_str("(")?;
+        for (i, arg) in self.message.args.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match *arg {
+                Argument::Str(Some(ref value)) => write!(f, "{:?}", value)?,
+                Argument::Str(None) => f.write_str("nil")?,
+                Argument::Object(id) => match arg_interfaces.next() {
+                    _ if id == 0 => f.write_str("nil")?,
+                    Some(interface) if !same_interface(interface, &ANONYMOUS_INTERFACE) => {
+                        write!(f, "{}@{}", interface.name, id)?
+                    }
+                    _ => write!(f, "[unknown]@{}", id)?,
+                },
+                Argument::NewId(id) => match child_interface {
+                    Some(interface) => write!(f, "new id {}@{}", interface.name, id)?,
+                    None => write!(f, "new id [unknown]@{}", id)?,
+                },
+                Argument::Fixed(value) => write!(f, "{}", value as f64 / 256.)?,
+                Argument::Array(ref value) => write!(f, "array[{}]", value.len())?,
+                Argument::Fd(ref fd) => write!(f, "fd {}", fd.as_raw_fd())?,
+                Argument::Int(_) | Argument::Uint(_) => write!(f, "{}", arg)?,
+            }
+        }
+        f.write_str(")")
+    }
+}
 
 impl std::error::Error for ProtocolError {}
 
@@ -356,3 +519,50 @@ impl<T: Into<u32>> From<WEnum<T>> for u32 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_interfaces::WL_DISPLAY_INTERFACE;
+    use std::ffi::CString;
+
+    #[test]
+    fn display_message() {
+        let sync: Message<u32, RawFd> =
+            Message { sender_id: 1, opcode: 0, args: smallvec::smallvec![Argument::NewId(3)] };
+        assert_eq!(
+            DisplayMessage::request(&sync, &WL_DISPLAY_INTERFACE).to_string(),
+            "wl_display@1.sync(new id wl_callback@3)"
+        );
+
+        let error: Message<u32, RawFd> = Message {
+            sender_id: 1,
+            opcode: 0,
+            args: smallvec::smallvec![
+                Argument::Object(4),
+                Argument::Uint(2),
+                Argument::Str(Some(Box::new(CString::new("bad").unwrap()))),
+            ],
+        };
+        assert_eq!(
+            DisplayMessage::event(&error, &WL_DISPLAY_INTERFACE).to_string(),
+            "wl_display@1.error([unknown]@4, 2, \"bad\")"
+        );
+
+        let unknown: Message<u32, RawFd> = Message {
+            sender_id: 1,
+            opcode: 9,
+            args: smallvec::smallvec![
+                Argument::Int(-1),
+                Argument::Fixed(-384),
+                Argument::Array(Box::new(vec![0, 1])),
+                Argument::Object(0),
+                Argument::Str(None),
+            ],
+        };
+        assert_eq!(
+            DisplayMessage::event(&unknown, &WL_DISPLAY_INTERFACE).to_string(),
+            "wl_display@1.[unknown opcode 9](-1, -1.5, array[2], nil, nil)"
+        );
+    }
+}
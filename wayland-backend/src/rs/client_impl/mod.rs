@@ -14,17 +14,19 @@ use crate::{
     core_interfaces::WL_DISPLAY_INTERFACE,
     protocol::{
         check_for_signature, same_interface, same_interface_or_anonymous, AllowNull, Argument,
-        ArgumentType, Interface, Message, ObjectInfo, ProtocolError, ANONYMOUS_INTERFACE,
-        INLINE_ARGS,
+        ArgumentType, Direction, IdReusePolicy, Interface, IoStats, Message, MessageLimits,
+        Metrics, ObjectInfo, ProtocolError, ToLogged, ANONYMOUS_INTERFACE, INLINE_ARGS,
     },
 };
 use smallvec::SmallVec;
 
 use super::{
     client::*,
+    debug::DebugConfig,
     map::{Object, ObjectMap, SERVER_ID_LIMIT},
-    socket::{BufferedSocket, Socket},
+    socket::{BufferedSocket, ReadError, Socket},
     wire::MessageParseError,
+    LoopbackStream,
 };
 
 pub use crate::types::client::{InvalidId, NoWaylandLib, WaylandError};
@@ -96,7 +98,11 @@ struct ProtocolState {
     map: ObjectMap<Data>,
     last_error: Option<WaylandError>,
     last_serial: u32,
-    debug: bool,
+    debug: Option<DebugConfig>,
+    metrics: Option<Metrics>,
+    logger: Option<Arc<dyn ProtocolLogger>>,
+    zombie_hook: Option<Arc<dyn ZombieHook>>,
+    flush_policy: FlushPolicy,
 }
 
 #[derive(Debug)]
@@ -122,6 +128,22 @@ impl ConnectionState {
     }
 }
 
+#[cfg(feature = "leak_report")]
+impl Drop for ConnectionState {
+    fn drop(&mut self) {
+        if let Ok(protocol) = self.protocol.get_mut() {
+            let leaked = protocol.live_objects().map(|id| id.to_string()).collect::<Vec<_>>();
+            if !leaked.is_empty() {
+                crate::log_warn!(
+                    "[wayland-backend-rs] Connection dropped with {} live objects: {}",
+                    leaked.len(),
+                    leaked.join(", ")
+                );
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct InnerBackend {
     state: Arc<ConnectionState>,
@@ -138,13 +160,50 @@ impl WeakInnerBackend {
     }
 }
 
+impl PartialEq for InnerBackend {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl Eq for InnerBackend {}
+
+impl PartialEq for WeakInnerBackend {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl Eq for WeakInnerBackend {}
+
 impl InnerBackend {
     pub fn downgrade(&self) -> WeakInnerBackend {
         WeakInnerBackend { state: Arc::downgrade(&self.state) }
     }
 
     pub fn connect(stream: UnixStream) -> Result<Self, NoWaylandLib> {
-        let socket = BufferedSocket::new(Socket::from(stream));
+        Ok(Self::connect_socket(Socket::from(stream)))
+    }
+
+    pub fn connect_loopback(stream: LoopbackStream) -> Self {
+        Self::connect_socket(Socket::from(stream))
+    }
+
+    #[cfg(any(test, feature = "fault_injection"))]
+    pub fn connect_with_faults(stream: UnixStream, config: super::FaultConfig) -> Self {
+        Self::connect_socket(Socket::with_faults(stream, config))
+    }
+
+    #[cfg(any(test, feature = "fault_injection"))]
+    pub fn connect_loopback_with_faults(
+        stream: LoopbackStream,
+        config: super::FaultConfig,
+    ) -> Self {
+        Self::connect_socket(Socket::with_faults(stream, config))
+    }
+
+    fn connect_socket(socket: Socket) -> Self {
+        let socket = BufferedSocket::new(socket);
         let mut map = ObjectMap::new();
         map.insert_at(
             1,
@@ -161,10 +220,9 @@ impl InnerBackend {
         )
         .unwrap();
 
-        let debug =
-            matches!(std::env::var_os("WAYLAND_DEBUG"), Some(str) if str == "1" || str == "client");
+        let debug = DebugConfig::from_env("client");
 
-        Ok(Self {
+        Self {
             state: Arc::new(ConnectionState {
                 protocol: Mutex::new(ProtocolState {
                     socket,
@@ -172,6 +230,10 @@ impl InnerBackend {
                     last_error: None,
                     last_serial: 0,
                     debug,
+                    metrics: None,
+                    logger: None,
+                    zombie_hook: None,
+                    flush_policy: FlushPolicy::Dispatch,
                 }),
                 read: Mutex::new(ReadingState {
                     prepared_reads: 0,
@@ -179,13 +241,14 @@ impl InnerBackend {
                     read_serial: 0,
                 }),
             }),
-        })
+        }
     }
 
     /// Flush all pending outgoing requests to the server
     pub fn flush(&self) -> Result<(), WaylandError> {
         let mut guard = self.state.lock_protocol();
         guard.no_last_error()?;
+        crate::trace_span!("flush", bytes = guard.socket.pending_bytes());
         if let Err(e) = guard.socket.flush() {
             return Err(guard.store_if_not_wouldblock_and_return_error(e));
         }
@@ -280,6 +343,53 @@ impl InnerBackend {
         self.state.lock_protocol().last_error.clone()
     }
 
+    pub fn live_objects(&self) -> Vec<ObjectId> {
+        self.state.lock_protocol().live_objects().map(|id| ObjectId { id }).collect()
+    }
+
+    pub fn enable_metrics(&self) {
+        self.state.lock_protocol().metrics.get_or_insert_with(Metrics::default);
+    }
+
+    pub fn set_id_reuse_policy(&self, policy: IdReusePolicy) {
+        self.state.lock_protocol().map.set_reuse_policy(policy);
+    }
+
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.state.lock_protocol().logger = logger;
+    }
+
+    pub fn set_zombie_hook(&self, hook: Option<Arc<dyn ZombieHook>>) {
+        self.state.lock_protocol().zombie_hook = hook;
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.state.lock_protocol().metrics.clone().unwrap_or_default()
+    }
+
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        let mut guard = self.state.lock_protocol();
+        guard.flush_policy = policy;
+        guard.socket.set_grow_before_flush(policy == FlushPolicy::Manual);
+    }
+
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.state.lock_protocol().flush_policy
+    }
+
+    pub fn set_message_limits(&self, limits: MessageLimits) {
+        self.state.lock_protocol().socket.set_message_limits(limits);
+    }
+
+    pub fn pending_bytes(&self) -> usize {
+        self.state.lock_protocol().socket.pending_bytes()
+    }
+
+    pub fn io_stats(&self) -> IoStats {
+        let state = self.state.lock_protocol();
+        IoStats { live_objects: state.live_objects().count() as u64, ..state.socket.stats() }
+    }
+
     pub fn info(&self, id: ObjectId) -> Result<ObjectInfo, InvalidId> {
         let object = self.state.lock_protocol().get_object(id.id.clone())?;
         if object.data.client_destroyed {
@@ -408,10 +518,15 @@ impl InnerBackend {
             }
         }).collect::<SmallVec<[_; INLINE_ARGS]>>();
 
-        if guard.debug {
-            super::debug::print_send_message(
+        if let Some(ref mut metrics) = guard.metrics {
+            metrics.count_sent(object.interface);
+        }
+        if let Some(ref debug) = guard.debug {
+            debug.print_send_message(
+                None,
                 object.interface.name,
                 id.id,
+                opcode,
                 message_desc.name,
                 &args,
             );
@@ -454,6 +569,10 @@ impl InnerBackend {
 
         let msg = Message { sender_id: id.id, opcode, args: msg_args };
 
+        if let Some(ref logger) = guard.logger {
+            logger.log(Direction::Sent, object.interface, &msg);
+        }
+
         if let Err(err) = guard.socket.write_message(&msg) {
             guard.last_error = Some(WaylandError::Io(err));
         }
@@ -513,6 +632,15 @@ impl ProtocolState {
         self.last_serial
     }
 
+    fn live_objects(&self) -> impl Iterator<Item = InnerObjectId> + '_ {
+        self.map.all_objects().filter_map(|(id, obj)| {
+            if id == 1 || obj.data.client_destroyed || obj.data.server_destroyed {
+                return None;
+            }
+            Some(InnerObjectId { id, serial: obj.data.serial, interface: obj.interface })
+        })
+    }
+
     #[inline]
     fn no_last_error(&self) -> Result<(), WaylandError> {
         if let Some(ref err) = self.last_error {
@@ -548,14 +676,22 @@ impl ProtocolState {
     }
 
     fn handle_display_event(&mut self, message: Message<u32, OwnedFd>) -> Result<(), WaylandError> {
-        if self.debug {
-            super::debug::print_dispatched_message(
+        if let Some(ref mut metrics) = self.metrics {
+            metrics.count_received(&WL_DISPLAY_INTERFACE);
+        }
+        if let Some(ref debug) = self.debug {
+            debug.print_dispatched_message(
+                None,
                 "wl_display",
                 message.sender_id,
+                message.opcode,
                 if message.opcode == 0 { "error" } else { "delete_id" },
                 &message.args,
             );
         }
+        if let Some(ref logger) = self.logger {
+            logger.log(Direction::Received, &WL_DISPLAY_INTERFACE, &message.to_logged(|&id| id));
+        }
         match message.opcode {
             0 => {
                 // wl_display.error
@@ -609,13 +745,18 @@ fn dispatch_events(state: Arc<ConnectionState>) -> Result<usize, WaylandError> {
         // Attempt to read a message
         let ProtocolState { ref mut socket, ref map, .. } = *guard;
         let message = match socket.read_one_message(|id, opcode| {
+            if map.is_recycled(id) {
+                crate::log_warn!("Received an event for the destroyed object {}.", id);
+            }
             map.find(id)
                 .and_then(|o| o.interface.events.get(opcode as usize))
                 .map(|desc| desc.signature)
         }) {
             Ok(msg) => msg,
-            Err(MessageParseError::MissingData) | Err(MessageParseError::MissingFD) => {
+            Err(ReadError::Parse(MessageParseError::MissingData))
+            | Err(ReadError::Parse(MessageParseError::MissingFD)) => {
                 // need to read more data
+                crate::trace_span!("read");
                 if let Err(e) = guard.socket.fill_incoming_buffers() {
                     if e.kind() != std::io::ErrorKind::WouldBlock {
                         return Err(guard.store_and_return_error(e));
@@ -627,7 +768,11 @@ fn dispatch_events(state: Arc<ConnectionState>) -> Result<usize, WaylandError> {
                 }
                 continue;
             }
-            Err(MessageParseError::Malformed) => {
+            Err(ReadError::TooLarge(e)) => {
+                let err = std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+                return Err(guard.store_and_return_error(err));
+            }
+            Err(ReadError::Parse(MessageParseError::Malformed)) => {
                 // malformed error, protocol error
                 let err = WaylandError::Protocol(ProtocolError {
                     code: 0,
@@ -643,6 +788,12 @@ fn dispatch_events(state: Arc<ConnectionState>) -> Result<usize, WaylandError> {
         // These lookups must succeed otherwise we would not have been able to parse this message
         let receiver = guard.map.find(message.sender_id).unwrap();
         let message_desc = receiver.interface.events.get(message.opcode as usize).unwrap();
+        crate::trace_event!(
+            interface = receiver.interface.name,
+            id = message.sender_id,
+            opcode = message.opcode,
+            "decoded event"
+        );
 
         // Short-circuit display-associated events
         if message.sender_id == 1 {
@@ -651,6 +802,8 @@ fn dispatch_events(state: Arc<ConnectionState>) -> Result<usize, WaylandError> {
         }
 
         let mut created_id = None;
+        // the events of the objects the client destroyed are discarded, like libwayland's zombies
+        let zombie = receiver.data.client_destroyed;
 
         // Convert the arguments and create the new object if applicable
         let mut args = SmallVec::with_capacity(message.args.len());
@@ -663,6 +816,10 @@ fn dispatch_events(state: Arc<ConnectionState>) -> Result<usize, WaylandError> {
                 Argument::Str(s) => Argument::Str(s),
                 Argument::Fixed(f) => Argument::Fixed(f),
                 Argument::Fd(f) => Argument::Fd(f),
+                Argument::Object(o) if zombie && guard.map.find(o).is_none() => {
+                    // the event is discarded, and its object arguments may be destroyed as well
+                    Argument::Object(ObjectId { id: InnerObjectId { id: 0, serial: 0, interface: &ANONYMOUS_INTERFACE }})
+                }
                 Argument::Object(o) => {
                     if o != 0 {
                         // Lookup the object to make the appropriate Id
@@ -679,7 +836,7 @@ fn dispatch_events(state: Arc<ConnectionState>) -> Result<usize, WaylandError> {
                             }
                         };
                         if let Some(next_interface) = arg_interfaces.next() {
-                            if !same_interface_or_anonymous(next_interface, obj.interface) {
+                            if !zombie && !same_interface_or_anonymous(next_interface, obj.interface) {
                                 let err = WaylandError::Protocol(ProtocolError {
                                     code: 0,
                                     object_id: 0,
@@ -747,17 +904,37 @@ fn dispatch_events(state: Arc<ConnectionState>) -> Result<usize, WaylandError> {
             });
         }
 
-        if guard.debug {
-            super::debug::print_dispatched_message(
+        if let Some(ref mut metrics) = guard.metrics {
+            metrics.count_received(receiver.interface);
+        }
+        if let Some(ref debug) = guard.debug {
+            debug.print_dispatched_message(
+                None,
                 receiver.interface.name,
                 message.sender_id,
+                message.opcode,
                 message_desc.name,
                 &args,
             );
         }
+        if let Some(ref logger) = guard.logger {
+            let args = args.iter().map(|arg| arg.to_logged(ObjectId::protocol_id)).collect();
+            let logged = Message { sender_id: message.sender_id, opcode: message.opcode, args };
+            logger.log(Direction::Received, receiver.interface, &logged);
+        }
 
         // If this event is send to an already destroyed object (by the client), swallow it
-        if receiver.data.client_destroyed {
+        if zombie {
+            if let Some(ref hook) = guard.zombie_hook {
+                let fds = args.iter().filter(|arg| matches!(arg, Argument::Fd(_))).count();
+                hook.discarded(&ZombieEvent {
+                    object_id: message.sender_id,
+                    interface: receiver.interface,
+                    opcode: message.opcode,
+                    fds,
+                });
+            }
+            // dropping the arguments closes the file descriptors
             continue;
         }
 
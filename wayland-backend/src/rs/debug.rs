@@ -1,12 +1,136 @@
 //! Debugging helpers to handle `WAYLAND_DEBUG` env variable.
+//!
+//! Like with libwayland, `WAYLAND_DEBUG` can be `1` to print the messages of both the clients and
+//! the servers, or `client` or `server` to only print those of one side. It can also be a comma
+//! separated list of these and of interface patterns, to only print the messages of the matching
+//! interfaces, for example `WAYLAND_DEBUG=client,wl_surface,xdg_*`. A pattern containing a dot is
+//! matched against the full name of the messages instead, and a pattern starting with a `-`
+//! excludes the messages it matches: `WAYLAND_DEBUG=1,-wl_pointer.motion` prints everything but
+//! the pointer motions. Patterns can contain `*` wildcards.
 
 use std::{
     fmt::Display,
     os::unix::prelude::AsRawFd,
+    sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::protocol::Argument;
+use crate::{
+    debug::{JsonLine, ProtocolId},
+    protocol::{Argument, Direction},
+};
+
+/// Which messages are printed when `WAYLAND_DEBUG` is set, and how
+#[derive(Debug, Clone)]
+pub(crate) struct DebugConfig {
+    json: bool,
+    filters: Arc<[Filter]>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Filter {
+    exclude: bool,
+    pattern: String,
+}
+
+impl DebugConfig {
+    /// The configuration of the environment, if `WAYLAND_DEBUG` enables debugging for `side`
+    pub(crate) fn from_env(side: &str) -> Option<Self> {
+        let value = std::env::var("WAYLAND_DEBUG").ok()?;
+        let json = matches!(std::env::var_os("WAYLAND_DEBUG_FORMAT"), Some(str) if str == "json");
+        Self::parse(&value, side, json)
+    }
+
+    fn parse(value: &str, side: &str, json: bool) -> Option<Self> {
+        let mut sides = None;
+        let mut filters = Vec::new();
+        for item in value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item {
+                "1" => sides = Some(true),
+                "0" => sides = Some(sides.unwrap_or(false)),
+                "client" | "server" => {
+                    sides = Some(sides.unwrap_or(false) || item == side);
+                }
+                _ => match item.strip_prefix('-') {
+                    Some(pattern) => {
+                        filters.push(Filter { exclude: true, pattern: pattern.into() })
+                    }
+                    None => filters.push(Filter { exclude: false, pattern: item.into() }),
+                },
+            }
+        }
+        // a list of patterns alone applies to both sides
+        let enabled = sides.unwrap_or(!filters.is_empty());
+        enabled.then(|| Self { json, filters: filters.into() })
+    }
+
+    /// Whether the messages of this interface with this name are printed
+    fn prints(&self, interface: &str, name: &str) -> bool {
+        let matches = |filter: &Filter| {
+            if filter.pattern.contains('.') {
+                let full_name = format!("{}.{}", interface, name);
+                glob_match(&filter.pattern, &full_name)
+            } else {
+                glob_match(&filter.pattern, interface)
+            }
+        };
+        let mut included = None;
+        for filter in self.filters.iter() {
+            if filter.exclude {
+                if matches(filter) {
+                    return false;
+                }
+            } else {
+                included = Some(included.unwrap_or(false) || matches(filter));
+            }
+        }
+        included.unwrap_or(true)
+    }
+
+    /// Print a dispatched message, as JSON or with [`print_dispatched_message()`]
+    #[cfg_attr(coverage, coverage(off))]
+    pub(crate) fn print_dispatched_message<Id: Display + ProtocolId, Fd: AsRawFd>(
+        &self,
+        client: Option<u64>,
+        interface: &str,
+        id: u32,
+        opcode: u16,
+        name: &str,
+        args: &[Argument<Id, Fd>],
+    ) {
+        if !self.prints(interface, name) {
+            return;
+        }
+        if self.json {
+            let direction = Direction::Received;
+            eprintln!("{}", JsonLine { client, direction, interface, id, opcode, name, args });
+        } else {
+            print_dispatched_message(interface, id, name, args);
+        }
+    }
+
+    /// Print a sent message, as JSON or with [`print_send_message()`]
+    #[cfg_attr(coverage, coverage(off))]
+    pub(crate) fn print_send_message<Id: Display + ProtocolId, Fd: AsRawFd>(
+        &self,
+        client: Option<u64>,
+        interface: &str,
+        id: u32,
+        opcode: u16,
+        name: &str,
+        args: &[Argument<Id, Fd>],
+    ) {
+        if !self.prints(interface, name) {
+            return;
+        }
+        if self.json {
+            let direction = Direction::Sent;
+            eprintln!("{}", JsonLine { client, direction, interface, id, opcode, name, args });
+        } else {
+            print_send_message(interface, id, name, args);
+        }
+    }
+}
 
 /// Print the dispatched message to stderr in a following format:
 ///
@@ -71,3 +195,78 @@ fn print_timestamp() {
         eprint!("[{}.{:06}]", sc, ms);
     }
 }
+
+/// Match a name against a pattern where `*` stands for any sequence of characters
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // the pattern contains at least one part, before its first wildcard
+    let first = parts.next().unwrap_or("");
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // the last part must end the name
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    // no wildcard at all
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_sides() {
+        assert!(DebugConfig::parse("1", "client", false).is_some());
+        assert!(DebugConfig::parse("client", "client", false).is_some());
+        assert!(DebugConfig::parse("client", "server", false).is_none());
+        assert!(DebugConfig::parse("client,server", "server", false).is_some());
+        assert!(DebugConfig::parse("0", "client", false).is_none());
+        assert!(DebugConfig::parse("", "client", false).is_none());
+        // patterns alone enable both sides
+        assert!(DebugConfig::parse("wl_surface", "server", false).is_some());
+        assert!(DebugConfig::parse("client,wl_surface", "server", false).is_none());
+    }
+
+    #[test]
+    fn debug_filters() {
+        let config = DebugConfig::parse("client,wl_surface, xdg_*", "client", false).unwrap();
+        assert!(config.prints("wl_surface", "attach"));
+        assert!(config.prints("xdg_toplevel", "configure"));
+        assert!(!config.prints("wl_pointer", "motion"));
+
+        let config = DebugConfig::parse("1,-wl_pointer.motion", "client", false).unwrap();
+        assert!(config.prints("wl_pointer", "button"));
+        assert!(config.prints("wl_surface", "commit"));
+        assert!(!config.prints("wl_pointer", "motion"));
+
+        // exclusions take precedence
+        let config = DebugConfig::parse("wl_*,-wl_pointer", "client", false).unwrap();
+        assert!(config.prints("wl_seat", "capabilities"));
+        assert!(!config.prints("wl_pointer", "enter"));
+        assert!(!config.prints("xdg_surface", "configure"));
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("wl_surface", "wl_surface"));
+        assert!(!glob_match("wl_surface", "wl_surfaces"));
+        assert!(glob_match("xdg_*", "xdg_toplevel"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*_v1", "zwp_linux_dmabuf_v1"));
+        assert!(!glob_match("*_v1", "zwp_linux_dmabuf_v2"));
+        assert!(glob_match("wl_*.m*n", "wl_pointer.motion"));
+        assert!(!glob_match("wl_*.m*n", "wl_pointer.motions"));
+        assert!(glob_match("a*a", "aa"));
+        assert!(!glob_match("a*a", "a"));
+    }
+}
@@ -0,0 +1,214 @@
+//! Injection of transport faults, to exercise the error paths of the rust backends
+
+use std::io::{ErrorKind, Result as IoResult};
+use std::os::unix::io::{AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+use super::socket::Socket;
+
+/// Faults to inject in the transport of a connection
+///
+/// Except for `seed`, `eagain_storm_len` and `latency_duration`, each field is the probability, between 0
+/// and 1, for a given send or receive operation on the socket to be affected by the fault. All
+/// probabilities are zero in the configuration returned by [`FaultConfig::new()`].
+///
+/// The bytes of a connection are never reordered, as the protocol relies on their order. The file
+/// descriptors are the exception: they can arrive after the bytes of their message with `delayed_fds`.
+///
+/// The faults are drawn from a pseudo-random generator initialized with `seed`, so that a given
+/// sequence of operations is always affected in the same way.
+///
+/// ```
+/// # use wayland_backend::rs::FaultConfig;
+/// let config = FaultConfig { short_io: 0.2, delayed_fds: 0.5, ..FaultConfig::new(42) };
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaultConfig {
+    /// Seed of the pseudo-random generator
+    pub seed: u64,
+    /// Only transfer a part of the data
+    ///
+    /// The length transferred is kept a multiple of 4 bytes, as are all wayland messages.
+    pub short_io: f64,
+    /// Send the file descriptors of a message along its last 4 bytes rather than its start
+    ///
+    /// The receiving end then reads the message before its file descriptors are available.
+    pub delayed_fds: f64,
+    /// Fail this operation and the `eagain_storm_len - 1` next ones with `WouldBlock`
+    pub eagain_storm: f64,
+    /// Number of operations failing in a storm of `WouldBlock` errors
+    pub eagain_storm_len: u32,
+    /// Sleep for `latency_duration` before the operation, as with a slow peer or an overloaded system
+    pub latency: f64,
+    /// Duration of the sleep of the operations affected by `latency`
+    pub latency_duration: Duration,
+    /// Drop the file descriptors received, as the kernel does when the fd table of the process is full
+    pub fd_exhaustion: f64,
+    /// Reset the connection
+    ///
+    /// The operation and all the following ones fail with `ECONNRESET`, and the socket is shut down so that
+    /// the other end sees the connection closing.
+    pub reset: f64,
+}
+
+impl FaultConfig {
+    /// A configuration with the given seed, which does not inject any fault
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            short_io: 0.,
+            delayed_fds: 0.,
+            eagain_storm: 0.,
+            eagain_storm_len: 8,
+            latency: 0.,
+            latency_duration: Duration::from_millis(1),
+            fd_exhaustion: 0.,
+            reset: 0.,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Faults {
+    config: FaultConfig,
+    rng: u64,
+    storm: u32,
+    reset: bool,
+    // file descriptors of a delayed message whose end could not be sent yet
+    pending_fds: Vec<OwnedFd>,
+}
+
+impl Faults {
+    pub(crate) fn new(config: FaultConfig) -> Self {
+        // splitmix64, so that small and zero seeds still give a non-zero xorshift state
+        let mut rng = config.seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        rng = (rng ^ (rng >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        rng = (rng ^ (rng >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        rng ^= rng >> 31;
+        Self { config, rng: rng.max(1), storm: 0, reset: false, pending_fds: Vec::new() }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0. && ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    // a length between 4 bytes and `len`, excluded, in multiples of 4 bytes
+    fn short_len(&mut self, len: usize) -> usize {
+        4 + (self.next_u64() as usize % ((len - 1) / 4)) * 4
+    }
+
+    // the faults affecting both directions, which happen without touching the socket
+    fn before_io(&mut self, socket: &Socket) -> IoResult<()> {
+        if self.reset {
+            return Err(Errno::ECONNRESET.into());
+        }
+        if self.chance(self.config.reset) {
+            self.reset = true;
+            socket.shutdown();
+            return Err(Errno::ECONNRESET.into());
+        }
+        if self.storm > 0 {
+            self.storm -= 1;
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        if self.chance(self.config.eagain_storm) {
+            self.storm = self.config.eagain_storm_len.saturating_sub(1);
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        if self.chance(self.config.latency) {
+            std::thread::sleep(self.config.latency_duration);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn send_msg(
+        &mut self,
+        socket: &Socket,
+        bytes: &[u8],
+        fds: &[RawFd],
+    ) -> IoResult<usize> {
+        self.before_io(socket)?;
+
+        if !self.pending_fds.is_empty() {
+            // finish the delayed message: its last 4 bytes are the start of this data
+            let pending = self.pending_fds.iter().map(AsRawFd::as_raw_fd).collect::<Vec<_>>();
+            let written = socket.raw_send_msg(&bytes[..bytes.len().min(4)], &pending)?;
+            self.pending_fds.clear();
+            return Ok(written);
+        }
+
+        let mut bytes = bytes;
+        if bytes.len() > 4 && self.chance(self.config.short_io) {
+            bytes = &bytes[..self.short_len(bytes.len())];
+        }
+
+        if !fds.is_empty() && bytes.len() > 4 && self.chance(self.config.delayed_fds) {
+            let (head, tail) = bytes.split_at(bytes.len() - 4);
+            let written = socket.raw_send_msg(head, &[])?;
+            if written == head.len() {
+                if let Ok(tail_written) = socket.raw_send_msg(tail, fds) {
+                    return Ok(written + tail_written);
+                }
+            }
+            // the caller closes the fds once this returns, keep copies for the next call
+            self.pending_fds = fds
+                .iter()
+                .map(|&fd| unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned())
+                .collect::<IoResult<_>>()?;
+            return Ok(written);
+        }
+
+        socket.raw_send_msg(bytes, fds)
+    }
+
+    pub(crate) fn rcv_msg(
+        &mut self,
+        socket: &Socket,
+        buffer: &mut [u8],
+        fds: &mut [RawFd],
+    ) -> IoResult<(usize, usize)> {
+        self.before_io(socket)?;
+
+        let mut buffer = buffer;
+        if buffer.len() > 4 && self.chance(self.config.short_io) {
+            let len = self.short_len(buffer.len());
+            buffer = &mut buffer[..len];
+        }
+
+        let (bytes, fd_count) = socket.raw_rcv_msg(buffer, fds)?;
+        if fd_count > 0 && self.chance(self.config.fd_exhaustion) {
+            for &fd in &fds[..fd_count] {
+                let _ = nix::unistd::close(fd);
+            }
+            return Err(Errno::EOVERFLOW.into());
+        }
+        Ok((bytes, fd_count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_faults() {
+        let config = FaultConfig { short_io: 0.5, ..FaultConfig::new(0) };
+        let mut first = Faults::new(config.clone());
+        let mut second = Faults::new(config);
+        for _ in 0..100 {
+            assert_eq!(first.chance(0.5), second.chance(0.5));
+            let len = first.short_len(64);
+            assert_eq!(len, second.short_len(64));
+            assert!((4..64).contains(&len) && len % 4 == 0);
+        }
+    }
+}
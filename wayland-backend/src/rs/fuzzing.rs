@@ -0,0 +1,175 @@
+//! Entry points for fuzzing the rust backends
+//!
+//! These functions are not part of the stable API of this crate, they only exist to be called by the
+//! fuzzing targets of the `fuzz` directory. They are only available with the `fuzzing` cargo feature.
+
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
+
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+
+use crate::protocol::{ArgumentType, Interface, Message};
+
+use super::server::{Backend, ClientData, ClientId, GlobalHandler, GlobalId, Handle, ObjectData};
+use super::socket::{MAX_BYTES_OUT, MAX_FDS_OUT};
+use super::wire::{arbitrary_message, arbitrary_signature, parse_message, write_to_buffers};
+use super::LoopbackStream;
+
+/// Parse arbitrary bytes as a sequence of messages
+///
+/// The signature of each message is looked up in `interfaces`, using the sender id of the message as an
+/// index. Up to `fd_count` placeholder file descriptors are available for the messages to consume.
+///
+/// Returns the number of successfully parsed messages.
+pub fn parse_messages(data: &[u8], fd_count: usize, interfaces: &[&'static Interface]) -> usize {
+    if interfaces.is_empty() {
+        return 0;
+    }
+
+    let words = to_words(data);
+    let placeholder = placeholder_fd();
+    let mut available_fds = fd_count.min(MAX_FDS_OUT);
+
+    let mut payload = &words[..];
+    let mut parsed = 0;
+    while payload.len() >= 2 {
+        let interface = interfaces[payload[0] as usize % interfaces.len()];
+        let opcode = (payload[1] & 0x0000_FFFF) as usize;
+        let signature = match interface.requests.get(opcode) {
+            Some(desc) => desc.signature,
+            None => break,
+        };
+
+        // Each message gets its own duplicates, as the parser takes ownership of the fds it reads
+        let fd_args = signature.iter().filter(|arg| matches!(arg, ArgumentType::Fd)).count();
+        let fds = (0..fd_args.min(available_fds))
+            .map(|_| fcntl(placeholder.as_raw_fd(), FcntlArg::F_DUPFD_CLOEXEC(0)).unwrap())
+            .collect::<Vec<RawFd>>();
+
+        let result = match parse_message(payload, signature, &fds) {
+            Ok((msg, rest, _)) => {
+                drop(msg);
+                payload = rest;
+                available_fds -= fd_args;
+                parsed += 1;
+                true
+            }
+            Err(_) => false,
+        };
+
+        // Close the duplicates the parser did not take ownership of. Nothing opened new file
+        // descriptors since the parser closed the other ones, so there is no risk of confusion.
+        for fd in fds {
+            if fcntl(fd, FcntlArg::F_GETFD).is_ok() {
+                let _ = nix::unistd::close(fd);
+            }
+        }
+
+        if !result {
+            break;
+        }
+    }
+    parsed
+}
+
+/// Check that messages built from arbitrary bytes survive a round-trip through the wire format
+///
+/// The bytes are consumed to generate a signature and a message respecting it, until they are
+/// exhausted. Each message is serialized and parsed back, panicking if the result differs.
+pub fn roundtrip_messages(data: &[u8]) {
+    let placeholder = placeholder_fd();
+    let mut data = data;
+    let mut payload = vec![0; MAX_BYTES_OUT / 4];
+    let mut fds = [0; MAX_FDS_OUT];
+    while !data.is_empty() {
+        let signature = arbitrary_signature(&mut data);
+        let msg = arbitrary_message(&mut data, &signature, placeholder.as_raw_fd());
+        let (written, fd_count) = match write_to_buffers(&msg, &mut payload, &mut fds) {
+            Ok(sizes) => sizes,
+            // the message has too many file descriptors
+            Err(_) => continue,
+        };
+        assert_eq!((payload[1] >> 16) as usize, written * 4, "Invalid size of {:?}", msg);
+        let (rebuilt, rest, _) = parse_message(&payload[..written], &signature, &fds[..fd_count])
+            .unwrap_or_else(|e| panic!("Failed to parse {:?}: {}", msg, e));
+        assert!(rest.is_empty(), "Trailing data after {:?}", msg);
+        assert_eq!(rebuilt.map_fd(|_| placeholder.as_raw_fd()), msg);
+    }
+}
+
+/// Feed arbitrary bytes to a server as the requests of a client
+///
+/// The server advertises a global for each of the provided interfaces, and creates stub objects
+/// for every object the client creates. The data is sent in chunks of at most 4096 bytes, each with
+/// `fd_count` placeholder file descriptors, and the server dispatches the client after each
+/// of them, until the client is disconnected or all the data has been sent.
+pub fn dispatch_server(data: &[u8], fd_count: usize, interfaces: &[&'static Interface]) {
+    let mut backend = Backend::<()>::new().unwrap();
+    let mut handle = backend.handle();
+    for interface in interfaces {
+        handle.create_global::<()>(interface, interface.version, Arc::new(StubData));
+    }
+
+    let (client, server) = LoopbackStream::pair().unwrap();
+    handle.insert_loopback_client(server, Arc::new(StubData)).unwrap();
+
+    let placeholder = placeholder_fd();
+    let raw_fds = vec![placeholder.as_raw_fd(); fd_count.min(MAX_FDS_OUT)];
+    for chunk in data.chunks(MAX_BYTES_OUT) {
+        if client.send_msg(chunk, &raw_fds).is_err() {
+            // the client was disconnected by the server
+            break;
+        }
+        let _ = backend.dispatch_all_clients(&mut ());
+        let _ = backend.flush(None);
+    }
+}
+
+fn to_words(data: &[u8]) -> Vec<u32> {
+    data.chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_ne_bytes(word)
+        })
+        .collect()
+}
+
+fn placeholder_fd() -> OwnedFd {
+    let (read, write) = nix::unistd::pipe2(OFlag::O_CLOEXEC).unwrap();
+    // SAFETY: the file descriptors were just created and are owned by nobody else
+    let (read, _write) = unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) };
+    read
+}
+
+struct StubData;
+
+impl ClientData for StubData {}
+
+impl ObjectData<()> for StubData {
+    fn request(
+        self: Arc<Self>,
+        _: &Handle,
+        _: &mut (),
+        _: ClientId,
+        _: Message<super::server::ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<()>>> {
+        Some(self)
+    }
+
+    fn destroyed(self: Arc<Self>, _: &Handle, _: &mut (), _: ClientId, _: super::server::ObjectId) {
+    }
+}
+
+impl GlobalHandler<()> for StubData {
+    fn bind(
+        self: Arc<Self>,
+        _: &Handle,
+        _: &mut (),
+        _: ClientId,
+        _: GlobalId,
+        _: super::server::ObjectId,
+    ) -> Arc<dyn ObjectData<()>> {
+        self
+    }
+}
@@ -0,0 +1,206 @@
+//! In-memory connection between a client and a server of the same process
+
+use std::collections::VecDeque;
+use std::io::Result as IoResult;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use nix::errno::Errno;
+use nix::fcntl::{self, FcntlArg, OFlag};
+use nix::unistd;
+
+/// One end of an in-memory Wayland connection
+///
+/// This is an alternative to a `UnixStream` for connecting a client to a server running in the same
+/// process, created with [`LoopbackStream::pair()`]. The messages are passed through memory queues
+/// rather than through a socket, and file descriptors are passed by value.
+///
+/// Each end provides a file descriptor that becomes readable when messages are available, so that both
+/// the client and the server can be integrated in an event loop as with a regular connection.
+///
+/// This is only supported by the rust backends, see
+/// [`rs::client::Backend::connect_loopback()`](super::client::Backend::connect_loopback) and
+/// [`rs::server::Handle::insert_loopback_client()`](super::server::Handle::insert_loopback_client).
+#[derive(Debug)]
+pub struct LoopbackStream {
+    incoming: Arc<Channel>,
+    outgoing: Arc<Channel>,
+    // Owned by the receiving end only, so that dropping it unregisters it from any epoll, as would
+    // closing a socket.
+    wake_read: OwnedFd,
+}
+
+#[derive(Debug)]
+struct Channel {
+    queue: Mutex<VecDeque<Chunk>>,
+    // The pipe contains a single byte exactly when the queue is not empty. The write end is closed
+    // when either end is dropped, so that the receiving end is woken up to notice it.
+    wake_write: Mutex<Option<OwnedFd>>,
+    closed: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Chunk {
+    bytes: Vec<u8>,
+    fds: Vec<OwnedFd>,
+}
+
+impl Channel {
+    fn new() -> IoResult<(Self, OwnedFd)> {
+        let (read, write) = unistd::pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?;
+        // SAFETY: the pipe file descriptors were just created and are owned by nobody else
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) };
+        let channel = Self {
+            queue: Mutex::new(VecDeque::new()),
+            wake_write: Mutex::new(Some(write)),
+            closed: AtomicBool::new(false),
+        };
+        Ok((channel, read))
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.wake_write.lock().unwrap().take();
+    }
+}
+
+impl LoopbackStream {
+    /// Create a pair of connected streams
+    ///
+    /// One end is meant to be given to the client, and the other one to the server.
+    pub fn pair() -> IoResult<(Self, Self)> {
+        let (a, a_read) = Channel::new()?;
+        let (b, b_read) = Channel::new()?;
+        let (a, b) = (Arc::new(a), Arc::new(b));
+        Ok((
+            Self { incoming: a.clone(), outgoing: b.clone(), wake_read: a_read },
+            Self { incoming: b, outgoing: a, wake_read: b_read },
+        ))
+    }
+
+    /// Queue bytes and file descriptors to be received by the other end
+    ///
+    /// The file descriptors are duplicated, as a socket would do.
+    pub(crate) fn send_msg(&self, bytes: &[u8], fds: &[RawFd]) -> IoResult<usize> {
+        if self.outgoing.closed.load(Ordering::Acquire) {
+            return Err(Errno::EPIPE.into());
+        }
+
+        let fds = fds
+            .iter()
+            .map(|&fd| {
+                let fd = fcntl::fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(0))?;
+                // SAFETY: the file descriptor was just duplicated and is owned by nobody else
+                Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+            })
+            .collect::<IoResult<Vec<_>>>()?;
+
+        let mut queue = self.outgoing.queue.lock().unwrap();
+        if queue.is_empty() {
+            if let Some(ref write) = *self.outgoing.wake_write.lock().unwrap() {
+                unistd::write(write.as_raw_fd(), &[0])?;
+            }
+        }
+        queue.push_back(Chunk { bytes: bytes.to_vec(), fds });
+        Ok(bytes.len())
+    }
+
+    /// Whether the other end received everything sent by this one
+    pub(crate) fn is_read_by_peer(&self) -> bool {
+        self.outgoing.queue.lock().unwrap().is_empty()
+    }
+
+    /// Receive bytes and file descriptors sent by the other end
+    ///
+    /// Returns `(0, 0)` if the other end was dropped, and errors with `WouldBlock` if no data is
+    /// available. The received file descriptors are owned by the caller.
+    pub(crate) fn rcv_msg(&self, buffer: &mut [u8], fds: &mut [RawFd]) -> IoResult<(usize, usize)> {
+        let mut queue = self.incoming.queue.lock().unwrap();
+        let chunk = match queue.front_mut() {
+            Some(chunk) => chunk,
+            None if self.incoming.closed.load(Ordering::Acquire) => return Ok((0, 0)),
+            None => return Err(Errno::EAGAIN.into()),
+        };
+
+        let len = chunk.bytes.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&chunk.bytes[..len]);
+        chunk.bytes.drain(..len);
+
+        let fd_count = chunk.fds.len().min(fds.len());
+        for (fd, place) in chunk.fds.drain(..fd_count).zip(fds.iter_mut()) {
+            *place = fd.into_raw_fd();
+        }
+
+        if chunk.bytes.is_empty() {
+            // file descriptors that did not fit are closed, as they would by a socket
+            queue.pop_front();
+            if queue.is_empty() {
+                let mut byte = [0];
+                let _ = unistd::read(self.wake_read.as_raw_fd(), &mut byte);
+            }
+        }
+
+        Ok((len, fd_count))
+    }
+}
+
+impl LoopbackStream {
+    /// Close both directions of the connection, as dropping this end does
+    #[cfg(any(test, feature = "fault_injection"))]
+    pub(crate) fn shutdown(&self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+impl Drop for LoopbackStream {
+    fn drop(&mut self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+impl AsFd for LoopbackStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.wake_read.as_fd()
+    }
+}
+
+impl AsRawFd for LoopbackStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.wake_read.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_receive() {
+        let (a, b) = LoopbackStream::pair().unwrap();
+        let mut buffer = [0u8; 8];
+        let mut fds = [0; 4];
+
+        assert_eq!(
+            b.rcv_msg(&mut buffer, &mut fds).unwrap_err().kind(),
+            std::io::ErrorKind::WouldBlock
+        );
+
+        a.send_msg(&[1, 2, 3, 4, 5, 6], &[]).unwrap();
+        a.send_msg(&[7, 8], &[a.as_raw_fd()]).unwrap();
+
+        // partial reads keep the remaining bytes
+        assert_eq!(b.rcv_msg(&mut buffer[..4], &mut fds).unwrap(), (4, 0));
+        assert_eq!(b.rcv_msg(&mut buffer[4..], &mut fds).unwrap(), (2, 0));
+        assert_eq!(b.rcv_msg(&mut buffer[6..], &mut fds).unwrap(), (2, 1));
+        assert_eq!(buffer, [1, 2, 3, 4, 5, 6, 7, 8]);
+        // SAFETY: the received file descriptor is owned by us
+        drop(unsafe { OwnedFd::from_raw_fd(fds[0]) });
+
+        drop(a);
+        assert_eq!(b.rcv_msg(&mut buffer, &mut fds).unwrap(), (0, 0));
+        assert_eq!(b.send_msg(&[0], &[]).unwrap_err().raw_os_error(), Some(Errno::EPIPE as i32));
+    }
+}
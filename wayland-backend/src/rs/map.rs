@@ -1,8 +1,8 @@
 //! Wayland objects map
 
-use crate::protocol::Interface;
+use crate::protocol::{IdReusePolicy, Interface};
 
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::BTreeMap, time::Instant};
 
 /// Limit separating server-created from client-created objects IDs in the namespace
 pub const SERVER_ID_LIMIT: u32 = 0xFF00_0000;
@@ -26,12 +26,53 @@ pub struct Object<Data> {
 pub struct ObjectMap<Data> {
     client_objects: Vec<Option<Object<Data>>>,
     server_objects: Vec<Option<Object<Data>>>,
+    // the ids freed since the policy was set, unless ids are reused immediately
+    reuse: Option<Box<Reuse>>,
+}
+
+#[derive(Debug)]
+struct Reuse {
+    policy: IdReusePolicy,
+    allocations: u64,
+    // the number of allocations and the time when each id was freed
+    freed: BTreeMap<u32, (u64, Instant)>,
+}
+
+impl Reuse {
+    fn reusable(&self, id: u32, now: Instant) -> bool {
+        match self.freed.get(&id) {
+            None => true,
+            Some(&(allocations, time)) => {
+                self.allocations - allocations > self.policy.delay_allocations as u64
+                    && now.saturating_duration_since(time) >= self.policy.delay
+            }
+        }
+    }
 }
 
 impl<Data: Clone> ObjectMap<Data> {
     /// Create a new empty object map
     pub fn new() -> Self {
-        Self { client_objects: Vec::new(), server_objects: Vec::new() }
+        Self { client_objects: Vec::new(), server_objects: Vec::new(), reuse: None }
+    }
+
+    /// Set the policy for reusing the ids of the objects destroyed from now on
+    pub fn set_reuse_policy(&mut self, policy: IdReusePolicy) {
+        self.reuse = if policy.is_immediate() {
+            None
+        } else {
+            Some(Box::new(Reuse { policy, allocations: 0, freed: BTreeMap::new() }))
+        };
+    }
+
+    /// Whether this id belonged to an object that was destroyed, and was not reused since
+    ///
+    /// This is only tracked in strict mode, and is always false otherwise.
+    pub fn is_recycled(&self, id: u32) -> bool {
+        match self.reuse {
+            Some(ref reuse) => reuse.policy.strict && reuse.freed.contains_key(&id),
+            None => false,
+        }
     }
 
     /// Find an object in the store
@@ -49,14 +90,19 @@ impl<Data: Clone> ObjectMap<Data> {
     ///
     /// Does nothing if the object didn't previously exists
     pub fn remove(&mut self, id: u32) {
-        if id == 0 {
-            // nothing
+        let place = if id == 0 {
+            None
         } else if id >= SERVER_ID_LIMIT {
-            if let Some(place) = self.server_objects.get_mut((id - SERVER_ID_LIMIT) as usize) {
-                *place = None;
+            self.server_objects.get_mut((id - SERVER_ID_LIMIT) as usize)
+        } else {
+            self.client_objects.get_mut((id - 1) as usize)
+        };
+        if let Some(place) = place {
+            if place.take().is_some() {
+                if let Some(ref mut reuse) = self.reuse {
+                    reuse.freed.insert(id, (reuse.allocations, reuse.policy.clock.now()));
+                }
             }
-        } else if let Some(place) = self.client_objects.get_mut((id - 1) as usize) {
-            *place = None;
         }
     }
 
@@ -71,17 +117,41 @@ impl<Data: Clone> ObjectMap<Data> {
             insert_in_at(&mut self.server_objects, (id - SERVER_ID_LIMIT) as usize, object)
         } else {
             insert_in_at(&mut self.client_objects, (id - 1) as usize, object)
+        }?;
+        // the peer chose this id, the policy does not apply to it
+        if let Some(ref mut reuse) = self.reuse {
+            reuse.freed.remove(&id);
         }
+        Ok(())
     }
 
     /// Allocate a new id for an object in the client namespace
     pub fn client_insert_new(&mut self, object: Object<Data>) -> u32 {
-        insert_in(&mut self.client_objects, object) + 1
+        Self::insert_new(&mut self.client_objects, &mut self.reuse, 1, object)
     }
 
     /// Allocate a new id for an object in the server namespace
     pub fn server_insert_new(&mut self, object: Object<Data>) -> u32 {
-        insert_in(&mut self.server_objects, object) + SERVER_ID_LIMIT
+        Self::insert_new(&mut self.server_objects, &mut self.reuse, SERVER_ID_LIMIT, object)
+    }
+
+    fn insert_new(
+        store: &mut Vec<Option<Object<Data>>>,
+        reuse: &mut Option<Box<Reuse>>,
+        first_id: u32,
+        object: Object<Data>,
+    ) -> u32 {
+        match reuse {
+            None => insert_in(store, object, |_| true) + first_id,
+            Some(reuse) => {
+                reuse.allocations += 1;
+                let now = reuse.policy.clock.now();
+                let id =
+                    insert_in(store, object, |idx| reuse.reusable(idx + first_id, now)) + first_id;
+                reuse.freed.remove(&id);
+                id
+            }
+        }
     }
 
     /// Mutably access an object of the map
@@ -121,9 +191,13 @@ impl<Data: Clone> ObjectMap<Data> {
     }
 }
 
-// insert a new object in a store at the first free place
-fn insert_in<Data>(store: &mut Vec<Option<Object<Data>>>, object: Object<Data>) -> u32 {
-    match store.iter().position(Option::is_none) {
+// insert a new object in a store at the first free place that can be reused
+fn insert_in<Data>(
+    store: &mut Vec<Option<Object<Data>>>,
+    object: Object<Data>,
+    reusable: impl Fn(u32) -> bool,
+) -> u32 {
+    match store.iter().enumerate().position(|(idx, obj)| obj.is_none() && reusable(idx as u32)) {
         Some(id) => {
             store[id] = Some(object);
             id as u32
@@ -157,3 +231,71 @@ fn insert_in_at<Data>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::VirtualClock, core_interfaces::WL_DISPLAY_INTERFACE};
+    use std::{sync::Arc, time::Duration};
+
+    fn object() -> Object<()> {
+        Object { interface: &WL_DISPLAY_INTERFACE, version: 1, data: () }
+    }
+
+    #[test]
+    fn immediate_reuse() {
+        let mut map = ObjectMap::new();
+        assert_eq!(map.client_insert_new(object()), 1);
+        assert_eq!(map.client_insert_new(object()), 2);
+        map.remove(1);
+        assert!(!map.is_recycled(1));
+        assert_eq!(map.client_insert_new(object()), 1);
+    }
+
+    #[test]
+    fn delayed_reuse() {
+        let mut map = ObjectMap::new();
+        map.set_reuse_policy(IdReusePolicy { delay_allocations: 2, ..Default::default() });
+        assert_eq!(map.server_insert_new(object()), SERVER_ID_LIMIT);
+        map.remove(SERVER_ID_LIMIT);
+        assert_eq!(map.server_insert_new(object()), SERVER_ID_LIMIT + 1);
+        assert_eq!(map.server_insert_new(object()), SERVER_ID_LIMIT + 2);
+        assert_eq!(map.server_insert_new(object()), SERVER_ID_LIMIT);
+    }
+
+    #[test]
+    fn timed_reuse() {
+        let clock = VirtualClock::new();
+        let mut map = ObjectMap::new();
+        map.set_reuse_policy(IdReusePolicy {
+            delay: Duration::from_secs(1),
+            clock: Arc::new(clock.clone()),
+            ..Default::default()
+        });
+        assert_eq!(map.client_insert_new(object()), 1);
+        map.remove(1);
+        assert_eq!(map.client_insert_new(object()), 2);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(map.client_insert_new(object()), 1);
+    }
+
+    #[test]
+    fn strict_reuse() {
+        let mut map = ObjectMap::new();
+        map.set_reuse_policy(IdReusePolicy { strict: true, ..Default::default() });
+        map.insert_at(3, object()).unwrap_err();
+        map.insert_at(1, object()).unwrap();
+        map.insert_at(2, object()).unwrap();
+        map.remove(2);
+        map.remove(3);
+        assert!(map.is_recycled(2));
+        assert!(!map.is_recycled(3));
+        // the peer reusing the id ends the tracking
+        map.insert_at(2, object()).unwrap();
+        assert!(!map.is_recycled(2));
+        map.remove(1);
+        assert!(map.is_recycled(1));
+        assert_eq!(map.client_insert_new(object()), 1);
+        assert!(!map.is_recycled(1));
+    }
+}
@@ -4,10 +4,20 @@ mod client_impl;
 mod server_impl;
 
 mod debug;
+#[cfg(any(test, feature = "fault_injection"))]
+mod faults;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
+mod loopback;
 mod map;
 pub(crate) mod socket;
 mod wire;
 
+#[cfg(any(test, feature = "fault_injection"))]
+pub use faults::FaultConfig;
+pub use loopback::LoopbackStream;
+
 /// Client-side rust implementation of a Wayland protocol backend
 ///
 /// The main entrypoint is the [`Backend::connect`](client::Backend::connect) method.
@@ -19,3 +29,77 @@ pub mod client;
 /// The main entrypoint is the [`Backend::new`](server::Backend::new) method.
 #[path = "../server_api.rs"]
 pub mod server;
+
+impl client::Backend {
+    /// Connect to a server running in the same process through a [`LoopbackStream`]
+    ///
+    /// The other end of the stream must be given to
+    /// [`Handle::insert_loopback_client()`](server::Handle::insert_loopback_client).
+    pub fn connect_loopback(stream: LoopbackStream) -> Self {
+        Self { backend: client_impl::InnerBackend::connect_loopback(stream) }
+    }
+}
+
+impl server::Handle {
+    /// Insert a client connected through a [`LoopbackStream`]
+    ///
+    /// The client behaves as any other, its credentials are the ones of the current process.
+    pub fn insert_loopback_client(
+        &mut self,
+        stream: LoopbackStream,
+        data: std::sync::Arc<dyn server::ClientData>,
+    ) -> std::io::Result<server::ClientId> {
+        Ok(server::ClientId { id: self.handle.insert_loopback_client(stream, data)? })
+    }
+}
+
+#[cfg(any(test, feature = "fault_injection"))]
+impl client::Backend {
+    /// Connect to a server through a socket injecting the faults described by `config`
+    ///
+    /// This is meant for testing the handling of transport errors, and is only available with the
+    /// `fault_injection` cargo feature.
+    pub fn connect_with_faults(
+        stream: std::os::unix::net::UnixStream,
+        config: FaultConfig,
+    ) -> Self {
+        Self { backend: client_impl::InnerBackend::connect_with_faults(stream, config) }
+    }
+
+    /// Connect to a server through a [`LoopbackStream`] injecting the faults described by `config`
+    ///
+    /// Only available with the `fault_injection` cargo feature.
+    pub fn connect_loopback_with_faults(stream: LoopbackStream, config: FaultConfig) -> Self {
+        Self { backend: client_impl::InnerBackend::connect_loopback_with_faults(stream, config) }
+    }
+}
+
+#[cfg(any(test, feature = "fault_injection"))]
+impl server::Handle {
+    /// Insert a client whose socket injects the faults described by `config`
+    ///
+    /// This is meant for testing the handling of transport errors, and is only available with the
+    /// `fault_injection` cargo feature.
+    pub fn insert_client_with_faults(
+        &mut self,
+        stream: std::os::unix::net::UnixStream,
+        data: std::sync::Arc<dyn server::ClientData>,
+        config: FaultConfig,
+    ) -> std::io::Result<server::ClientId> {
+        Ok(server::ClientId { id: self.handle.insert_client_with_faults(stream, data, config)? })
+    }
+
+    /// Insert a client connected through a [`LoopbackStream`] injecting the faults described by `config`
+    ///
+    /// Only available with the `fault_injection` cargo feature.
+    pub fn insert_loopback_client_with_faults(
+        &mut self,
+        stream: LoopbackStream,
+        data: std::sync::Arc<dyn server::ClientData>,
+        config: FaultConfig,
+    ) -> std::io::Result<server::ClientId> {
+        Ok(server::ClientId {
+            id: self.handle.insert_loopback_client_with_faults(stream, data, config)?,
+        })
+    }
+}
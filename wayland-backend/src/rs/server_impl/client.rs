@@ -1,37 +1,38 @@
-use std::{
-    ffi::CString,
-    os::unix::io::OwnedFd,
-    os::unix::{io::RawFd, net::UnixStream},
-    sync::Arc,
-};
+use std::{ffi::CString, os::unix::io::OwnedFd, os::unix::io::RawFd, sync::Arc, time::Instant};
 
 use crate::{
     core_interfaces::{WL_CALLBACK_INTERFACE, WL_DISPLAY_INTERFACE, WL_REGISTRY_INTERFACE},
     protocol::{
         check_for_signature, same_interface, same_interface_or_anonymous, AllowNull, Argument,
-        ArgumentType, Interface, Message, ObjectInfo, ProtocolError, ANONYMOUS_INTERFACE,
+        ArgumentType, Direction, IdReusePolicy, Interface, IoStats, Message, MessageLimits,
+        MessageTooLarge, Metrics, ObjectInfo, ProtocolError, ToLogged, ANONYMOUS_INTERFACE,
         INLINE_ARGS,
     },
     rs::map::SERVER_ID_LIMIT,
-    types::server::{DisconnectReason, InvalidId},
+    types::server::{DisconnectReason, DrainCallback, InvalidId},
 };
 
 use smallvec::SmallVec;
 
 use crate::rs::{
+    debug::DebugConfig,
     map::{Object, ObjectMap},
-    socket::{BufferedSocket, Socket},
+    socket::{BufferedSocket, ReadError, Socket},
     wire::MessageParseError,
 };
 
 use super::{
     handle::PendingDestructor, registry::Registry, ClientData, ClientId, Credentials, Data,
-    DumbObjectData, GlobalHandler, InnerClientId, InnerGlobalId, InnerObjectId, ObjectData,
-    ObjectId, UninitObjectData,
+    DumbObjectData, GlobalHandler, InnerClientId, InnerGlobalId, InnerObjectId, KillDecision,
+    KillPolicy, MalformedReason, MalformedRequest, MalformedRequestPolicy, ObjectData, ObjectId,
+    OverflowPolicy, ProtocolLogger, RateLimit, UninitObjectData, ZombieObjectData,
 };
 
 type ArgSmallVec<Fd> = SmallVec<[Argument<ObjectId, Fd>; INLINE_ARGS]>;
 
+/// The maximum number of requests of a client deferred because of paused objects
+pub(crate) const MAX_DEFERRED_REQUESTS: usize = 4096;
+
 #[repr(u32)]
 #[allow(dead_code)]
 pub(crate) enum DisplayError {
@@ -41,15 +42,84 @@ pub(crate) enum DisplayError {
     Implementation = 3,
 }
 
+/// The requests of a client counted against the rate limit
+#[derive(Debug, Default)]
+pub(crate) struct RequestCounter {
+    /// The requests dispatched during the current dispatch cycle
+    pub(crate) cycle: usize,
+    /// The start of the current window, and the requests dispatched since
+    window: Option<(Instant, u64)>,
+    reported: bool,
+}
+
+impl RequestCounter {
+    /// Count dispatched requests, returns whether the client just exceeded the threshold
+    pub(crate) fn count(&mut self, limit: &RateLimit, requests: usize) -> bool {
+        let now = limit.clock.now();
+        let (start, count) = match self.window {
+            Some((start, count)) if now.duration_since(start) < limit.window => (start, count),
+            _ => {
+                self.reported = false;
+                (now, 0)
+            }
+        };
+        let count = count + requests as u64;
+        self.window = Some((start, count));
+        let exceeded = count > limit.threshold && !self.reported;
+        self.reported |= exceeded;
+        exceeded
+    }
+}
+
+/// A request read from a client, ready to be dispatched to the data of its object
+#[derive(Debug)]
+pub(crate) struct PendingRequest<D: 'static> {
+    pub(crate) object: Object<Data<D>>,
+    pub(crate) object_id: InnerObjectId,
+    pub(crate) opcode: u16,
+    pub(crate) arguments: ArgSmallVec<OwnedFd>,
+    pub(crate) is_destructor: bool,
+    pub(crate) created_id: Option<InnerObjectId>,
+}
+
+impl<D> PendingRequest<D> {
+    /// The objects the request is sent to, creates or takes as arguments
+    fn objects(&self) -> impl Iterator<Item = &InnerObjectId> {
+        let arguments = self.arguments.iter().filter_map(|arg| match arg {
+            Argument::Object(ObjectId { id }) if id.id != 0 => Some(id),
+            _ => None,
+        });
+        std::iter::once(&self.object_id).chain(self.created_id.as_ref()).chain(arguments)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Client<D: 'static> {
     socket: BufferedSocket,
     pub(crate) map: ObjectMap<Data<D>>,
-    debug: bool,
+    debug: Option<DebugConfig>,
+    pub(crate) metrics: Option<Metrics>,
+    logger: Option<Arc<dyn ProtocolLogger>>,
+    overflow_policy: OverflowPolicy,
+    malformed_policy: MalformedRequestPolicy,
+    kill_policy: KillPolicy,
+    pub(crate) requests: RequestCounter,
     last_serial: u32,
     pub(crate) id: InnerClientId,
     pub(crate) killed: bool,
+    // why the client was killed, for its destroy listeners
+    disconnect_reason: Option<DisconnectReason>,
+    // the reason of a deferred kill, applied once the outgoing buffer is drained
+    closing: Option<DisconnectReason>,
     pub(crate) data: Arc<dyn ClientData>,
+    // the callbacks waiting for the socket to have written this many bytes
+    drain_callbacks: Vec<(u64, DrainCallback)>,
+    // the callbacks waiting for the client to have read this many bytes
+    barriers: Vec<(u64, DrainCallback)>,
+    // the objects paused by the server, until they are resumed or destroyed
+    paused: Vec<InnerObjectId>,
+    // the requests involving paused objects, in the order they were received
+    deferred: Vec<PendingRequest<D>>,
 }
 
 impl<D> Client<D> {
@@ -61,12 +131,13 @@ impl<D> Client<D> {
 
 impl<D> Client<D> {
     pub(crate) fn new(
-        stream: UnixStream,
+        socket: Socket,
         id: InnerClientId,
-        debug: bool,
+        debug: Option<DebugConfig>,
+        metrics: bool,
         data: Arc<dyn ClientData>,
     ) -> Self {
-        let socket = BufferedSocket::new(Socket::from(stream));
+        let socket = BufferedSocket::new(socket);
         let mut map = ObjectMap::new();
         map.insert_at(
             1,
@@ -80,7 +151,28 @@ impl<D> Client<D> {
 
         data.initialized(ClientId { id: id.clone() });
 
-        Self { socket, map, debug, id, killed: false, last_serial: 0, data }
+        let metrics = if metrics { Some(Metrics::default()) } else { None };
+        Self {
+            socket,
+            map,
+            debug,
+            metrics,
+            logger: None,
+            overflow_policy: OverflowPolicy::Kill,
+            malformed_policy: MalformedRequestPolicy::Kill,
+            kill_policy: KillPolicy::Kill,
+            requests: RequestCounter::default(),
+            id,
+            killed: false,
+            disconnect_reason: None,
+            closing: None,
+            last_serial: 0,
+            data,
+            drain_callbacks: Vec::new(),
+            barriers: Vec::new(),
+            paused: Vec::new(),
+            deferred: Vec::new(),
+        }
     }
 
     pub(crate) fn create_object(
@@ -108,7 +200,8 @@ impl<D> Client<D> {
         Message { sender_id: object_id, opcode, args }: Message<ObjectId, RawFd>,
         pending_destructors: Option<&mut Vec<super::handle::PendingDestructor<D>>>,
     ) -> Result<(), InvalidId> {
-        if self.killed {
+        // the events sent to a client about to be disconnected are dropped
+        if self.killed || self.closing.is_some() {
             return Ok(());
         }
         let object = self.get_object(object_id.id.clone())?;
@@ -134,10 +227,15 @@ impl<D> Client<D> {
             );
         }
 
-        if self.debug {
-            crate::rs::debug::print_send_message(
+        if let Some(ref mut metrics) = self.metrics {
+            metrics.count_sent(object.interface);
+        }
+        if let Some(ref debug) = self.debug {
+            debug.print_send_message(
+                Some(self.id.as_u64()),
                 object.interface.name,
                 object_id.id.id,
+                opcode,
                 message_desc.name,
                 &args,
             );
@@ -191,8 +289,29 @@ impl<D> Client<D> {
 
         let msg = Message { sender_id: object_id.id.id, opcode, args: msg_args };
 
-        if self.socket.write_message(&msg).is_err() {
-            self.kill(DisconnectReason::ConnectionClosed);
+        if let Some(ref logger) = self.logger {
+            logger.log(ClientId { id: self.id.clone() }, Direction::Sent, object.interface, &msg);
+        }
+
+        if let Err(err) = self.socket.write_message(&msg) {
+            // the events which neither create nor destroy objects can be discarded if the buffer is full
+            let droppable = err.raw_os_error() == Some(nix::errno::Errno::E2BIG as i32)
+                && !message_desc.is_destructor
+                && !message_desc
+                    .signature
+                    .iter()
+                    .any(|arg| matches!(arg, ArgumentType::NewId | ArgumentType::Fd));
+            let drop = droppable
+                && match self.overflow_policy {
+                    OverflowPolicy::Kill => false,
+                    OverflowPolicy::DropFrameEvents => true,
+                    OverflowPolicy::Callback(ref callback) => {
+                        callback(ClientId { id: self.id.clone() }, object.interface, opcode)
+                    }
+                };
+            if !drop {
+                self.write_failed(err);
+            }
         }
 
         // Handle destruction if relevant
@@ -211,10 +330,11 @@ impl<D> Client<D> {
         // We should only send delete_id for objects in the client ID space
         if object_id.id < SERVER_ID_LIMIT {
             let msg = message!(1, 1, [Argument::Uint(object_id.id)]);
-            if self.socket.write_message(&msg).is_err() {
-                self.kill(DisconnectReason::ConnectionClosed);
+            if let Err(err) = self.socket.write_message(&msg) {
+                self.write_failed(err);
             }
         }
+        self.paused.retain(|paused| paused != &object_id);
         self.map.remove(object_id.id);
     }
 
@@ -262,7 +382,24 @@ impl<D> Client<D> {
         error_code: u32,
         message: CString,
     ) {
-        let converted_message = message.to_string_lossy().into();
+        let error = ProtocolError {
+            code: error_code,
+            object_id: object_id.id,
+            object_interface: object_id.interface.name.into(),
+            message: message.to_string_lossy().into(),
+        };
+        let reason = DisconnectReason::ProtocolError(error);
+        let decision = self.kill_decision(&reason);
+        if decision == KillDecision::Keep {
+            crate::log_warn!("Kept a client despite a protocol error: {:?}.", reason);
+            return;
+        }
+        self.send_error(object_id, error_code, message);
+        self.apply_kill(decision, reason);
+    }
+
+    /// Send a `wl_display.error` to the client, without disconnecting it
+    fn send_error(&mut self, object_id: InnerObjectId, error_code: u32, message: CString) {
         // errors are ignored, as the client will be killed anyway
         let _ = self.send_event(
             message!(
@@ -284,24 +421,78 @@ impl<D> Client<D> {
             // wl_display.error is not a destructor, this argument will not be used
             None,
         );
-        let _ = self.flush();
-        self.kill(DisconnectReason::ProtocolError(ProtocolError {
-            code: error_code,
-            object_id: object_id.id,
-            object_interface: object_id.interface.name.into(),
-            message: converted_message,
-        }));
+        let _ = self.socket.flush();
+    }
+
+    /// Disconnect the client because the server is shutting down, sending it `message` first
+    pub(crate) fn shutdown(&mut self, message: Option<CString>) {
+        if let Some(message) = message {
+            let display = InnerObjectId {
+                id: 1,
+                interface: &WL_DISPLAY_INTERFACE,
+                client_id: self.id.clone(),
+                serial: 0,
+            };
+            self.send_error(display, DisplayError::Implementation as u32, message);
+        }
+        self.kill(DisconnectReason::ServerShutdown);
+    }
+
+    /// Handle an error writing to the socket, a full buffer disconnects according to the kill policy
+    fn write_failed(&mut self, err: std::io::Error) {
+        if err.raw_os_error() != Some(nix::errno::Errno::E2BIG as i32) {
+            self.kill(DisconnectReason::ConnectionClosed);
+            return;
+        }
+        let reason = DisconnectReason::BufferOverflow;
+        match self.kill_decision(&reason) {
+            KillDecision::Keep => crate::log_warn!("Dropped an event not fitting in the buffer."),
+            decision => self.apply_kill(decision, reason),
+        }
+    }
+
+    fn kill_decision(&self, reason: &DisconnectReason) -> KillDecision {
+        match self.kill_policy {
+            KillPolicy::Kill => KillDecision::Kill,
+            KillPolicy::Callback(ref callback) => {
+                callback(ClientId { id: self.id.clone() }, reason)
+            }
+        }
+    }
+
+    fn apply_kill(&mut self, decision: KillDecision, reason: DisconnectReason) {
+        match decision {
+            KillDecision::Kill => self.kill(reason),
+            KillDecision::Defer => {
+                self.closing = Some(reason);
+                // there may be nothing left to wait for
+                let _ = self.flush();
+            }
+            KillDecision::Keep => {}
+        }
+    }
+
+    pub(crate) fn set_kill_policy(&mut self, policy: KillPolicy) {
+        self.kill_policy = policy;
+    }
+
+    /// Whether the client is killed or about to be
+    pub(crate) fn is_closing(&self) -> bool {
+        self.killed || self.closing.is_some()
     }
 
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub(crate) fn get_credentials(&self) -> Credentials {
-        use std::os::unix::io::AsRawFd;
-        let creds = nix::sys::socket::getsockopt(
-            self.socket.as_raw_fd(),
-            nix::sys::socket::sockopt::PeerCredentials,
-        )
-        .expect("getsockopt failed!?");
-        Credentials { pid: creds.pid(), uid: creds.uid(), gid: creds.gid() }
+        use std::os::unix::io::AsFd;
+        if self.socket.socket().is_loopback() {
+            // the client runs in this very process
+            return Credentials {
+                pid: std::process::id() as i32,
+                uid: nix::unistd::getuid().as_raw(),
+                gid: nix::unistd::getgid().as_raw(),
+            };
+        }
+        Credentials::from_peer(self.socket.as_fd()).expect("getsockopt failed!?")
     }
 
     #[cfg(not(any(target_os = "linux", target_os = "android")))]
@@ -310,13 +501,159 @@ impl<D> Client<D> {
         Credentials { pid: 0, uid: 0, gid: 0 }
     }
 
+    pub(crate) fn get_security_label(&self) -> Option<String> {
+        use std::os::unix::io::AsRawFd;
+        if self.socket.socket().is_loopback() {
+            // there is no peer socket to label
+            return None;
+        }
+        crate::types::server::peer_security_label(self.socket.as_raw_fd())
+    }
+
     pub(crate) fn kill(&mut self, reason: DisconnectReason) {
         self.killed = true;
+        self.closing = None;
+        self.disconnect_reason = Some(reason.clone());
         self.data.disconnected(ClientId { id: self.id.clone() }, reason);
     }
 
     pub(crate) fn flush(&mut self) -> std::io::Result<()> {
-        self.socket.flush()
+        crate::trace_span!("flush", client = self.id.as_u64(), bytes = self.socket.pending_bytes());
+        let ret = self.socket.flush();
+        if self.closing.is_some() {
+            // a deferred kill happens once the events are written, or they cannot be
+            let done = match ret {
+                Ok(()) => self.socket.pending_bytes() == 0,
+                Err(ref e) => e.kind() != std::io::ErrorKind::WouldBlock,
+            };
+            if done {
+                let reason = self.closing.take().unwrap();
+                self.kill(reason);
+            }
+        }
+        ret
+    }
+
+    /// Invoke `callback` once all the events queued so far are written to the socket
+    pub(crate) fn add_drain_callback(&mut self, callback: DrainCallback) {
+        let target = self.socket.stats().bytes_written + self.socket.pending_bytes() as u64;
+        self.drain_callbacks.push((target, callback));
+    }
+
+    /// Take the callbacks whose events have been written
+    pub(crate) fn take_drained(&mut self, drained: &mut Vec<DrainCallback>) {
+        let written = self.socket.stats().bytes_written;
+        // the targets are in increasing order
+        let ready =
+            self.drain_callbacks.iter().take_while(|&&(target, _)| target <= written).count();
+        drained.extend(self.drain_callbacks.drain(..ready).map(|(_, callback)| callback));
+        // the events written so far are all read once the socket is empty
+        if !self.barriers.is_empty() && self.socket.socket().is_read_by_peer().unwrap_or(true) {
+            let ready = self.barriers.iter().take_while(|&&(target, _)| target <= written).count();
+            drained.extend(self.barriers.drain(..ready).map(|(_, callback)| callback));
+        }
+    }
+
+    /// Invoke `callback` once the client has read all the events queued so far
+    pub(crate) fn add_barrier(&mut self, callback: DrainCallback) {
+        let target = self.socket.stats().bytes_written + self.socket.pending_bytes() as u64;
+        self.barriers.push((target, callback));
+    }
+
+    pub(crate) fn set_max_buffer_size(&mut self, size: usize) {
+        self.socket.set_max_buffer_size(size);
+    }
+
+    pub(crate) fn set_message_limits(&mut self, limits: MessageLimits) {
+        self.socket.set_message_limits(limits);
+    }
+
+    /// Pause or resume the dispatch of the requests of an object
+    ///
+    /// Returns whether the client has deferred requests which may now be dispatched.
+    pub(crate) fn set_paused(
+        &mut self,
+        id: InnerObjectId,
+        paused: bool,
+    ) -> Result<bool, InvalidId> {
+        self.get_object(id.clone())?;
+        if paused {
+            if !self.paused.contains(&id) {
+                self.paused.push(id);
+            }
+            Ok(false)
+        } else {
+            self.paused.retain(|paused| paused != &id);
+            Ok(!self.deferred.is_empty())
+        }
+    }
+
+    /// Defer a request involving a paused object, or an object of a request deferred before it
+    ///
+    /// Returns the request if it can be dispatched right away. A client deferring more than
+    /// [`MAX_DEFERRED_REQUESTS`] requests gets a `no_memory` error.
+    pub(crate) fn defer_request(
+        &mut self,
+        request: PendingRequest<D>,
+    ) -> Option<PendingRequest<D>> {
+        let deferred = &self.deferred;
+        let paused = &self.paused;
+        let blocked = |id: &InnerObjectId| {
+            paused.contains(id) || deferred.iter().any(|d| d.objects().any(|o| o == id))
+        };
+        if !request.objects().any(blocked) {
+            return Some(request);
+        }
+        if self.deferred.len() >= MAX_DEFERRED_REQUESTS {
+            let message = format!(
+                "too many requests deferred by paused objects, the limit is {}",
+                MAX_DEFERRED_REQUESTS
+            );
+            self.post_display_error(DisplayError::NoMemory, CString::new(message).unwrap());
+            self.discard_request(request);
+        } else {
+            self.deferred.push(request);
+        }
+        None
+    }
+
+    /// Take the first deferred request whose objects are no longer paused
+    pub(crate) fn next_deferred_request(&mut self) -> Option<PendingRequest<D>> {
+        loop {
+            let (deferred, paused) = (&self.deferred, &self.paused);
+            let index = (0..deferred.len()).find(|&i| {
+                deferred[i].objects().all(|id| {
+                    !paused.contains(id)
+                        && !deferred[..i].iter().any(|d| d.objects().any(|o| o == id))
+                })
+            })?;
+            let request = self.deferred.remove(index);
+            // the object may have been destroyed by an event in the meantime
+            if self.get_object(request.object_id.clone()).is_ok() {
+                return Some(request);
+            }
+            self.discard_request(request);
+        }
+    }
+
+    /// Drop a request without dispatching it
+    ///
+    /// The object it created was inserted in the map when it was parsed, it becomes a zombie so that
+    /// the requests the client sends to it are ignored.
+    fn discard_request(&mut self, request: PendingRequest<D>) {
+        if let Some(created_id) = request.created_id {
+            let _ = self.set_object_data(created_id, Arc::new(ZombieObjectData));
+        }
+    }
+
+    pub(crate) fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow_policy = policy;
+    }
+
+    pub(crate) fn io_stats(&self) -> IoStats {
+        // the wl_display is not counted
+        let live_objects = self.map.all_objects().filter(|&(id, _)| id != 1).count();
+        IoStats { live_objects: live_objects as u64, ..self.socket.stats() }
     }
 
     pub(crate) fn all_objects(&self) -> impl Iterator<Item = ObjectId> + '_ {
@@ -338,16 +675,48 @@ impl<D> Client<D> {
         if self.killed {
             return Err(nix::errno::Errno::EPIPE.into());
         }
+        if self.closing.is_some() {
+            // the requests of a client about to be disconnected are not dispatched
+            loop {
+                self.socket.discard_incoming();
+                if let Err(e) = self.socket.fill_incoming_buffers() {
+                    if e.kind() != std::io::ErrorKind::WouldBlock {
+                        let reason = self.closing.take().unwrap();
+                        self.kill(reason);
+                    }
+                    return Err(e);
+                }
+            }
+        }
         loop {
             let map = &self.map;
             let msg = match self.socket.read_one_message(|id, opcode| {
+                if map.is_recycled(id) {
+                    crate::log_warn!("Received a request for the destroyed object {}.", id);
+                }
                 map.find(id)
                     .and_then(|o| o.interface.requests.get(opcode as usize))
                     .map(|desc| desc.signature)
             }) {
                 Ok(msg) => msg,
-                Err(MessageParseError::MissingData) | Err(MessageParseError::MissingFD) => {
-                    // need to read more data
+                Err(ReadError::Parse(MessageParseError::Malformed))
+                    if self.socket.peek_message_header().is_some() =>
+                {
+                    if self.discard_malformed_request(None) {
+                        continue;
+                    }
+                    return Err(nix::errno::Errno::EPROTO.into());
+                }
+                Err(ReadError::TooLarge(too_large)) => {
+                    if self.discard_malformed_request(Some(too_large)) {
+                        continue;
+                    }
+                    return Err(nix::errno::Errno::EPROTO.into());
+                }
+                Err(_) => {
+                    // need to read more data, a malformed request is only handled once it is
+                    // fully received
+                    crate::trace_span!("read", client = self.id.as_u64());
                     if let Err(e) = self.socket.fill_incoming_buffers() {
                         if e.kind() != std::io::ErrorKind::WouldBlock {
                             self.kill(DisconnectReason::ConnectionClosed);
@@ -356,27 +725,115 @@ impl<D> Client<D> {
                     }
                     continue;
                 }
-                Err(MessageParseError::Malformed) => {
-                    self.kill(DisconnectReason::ConnectionClosed);
-                    return Err(nix::errno::Errno::EPROTO.into());
-                }
             };
 
             let obj = self.map.find(msg.sender_id).unwrap();
+            crate::trace_event!(
+                client = self.id.as_u64(),
+                interface = obj.interface.name,
+                id = msg.sender_id,
+                opcode = msg.opcode,
+                "decoded request"
+            );
 
-            if self.debug {
-                super::super::debug::print_dispatched_message(
+            if let Some(ref mut metrics) = self.metrics {
+                metrics.count_received(obj.interface);
+            }
+            if let Some(ref debug) = self.debug {
+                debug.print_dispatched_message(
+                    Some(self.id.as_u64()),
                     obj.interface.name,
                     msg.sender_id,
+                    msg.opcode,
                     obj.interface.requests.get(msg.opcode as usize).unwrap().name,
                     &msg.args,
                 );
             }
+            if let Some(ref logger) = self.logger {
+                let logged = msg.to_logged(|&id| id);
+                logger.log(
+                    ClientId { id: self.id.clone() },
+                    Direction::Received,
+                    obj.interface,
+                    &logged,
+                );
+            }
 
             return Ok((msg, obj));
         }
     }
 
+    /// Apply the malformed request policy to the request at the front of the incoming buffers
+    ///
+    /// `too_large` is set if the request exceeds the message limits. Returns `true` if the request was
+    /// discarded, the client is killed otherwise.
+    fn discard_malformed_request(&mut self, too_large: Option<MessageTooLarge>) -> bool {
+        let (object_id, opcode, size) = self.socket.peek_message_header().unwrap();
+        let interface = self.map.find(object_id).map(|obj| obj.interface);
+        let (reason, code, error) = match (too_large, interface) {
+            (Some(too_large), _) => {
+                (MalformedReason::TooLarge, DisplayError::InvalidMethod, too_large.to_string())
+            }
+            (None, None) => (
+                MalformedReason::UnknownObject,
+                DisplayError::InvalidObject,
+                format!("invalid object {}", object_id),
+            ),
+            (None, Some(interface)) => match interface.requests.get(opcode as usize) {
+                None => (
+                    MalformedReason::UnknownOpcode,
+                    DisplayError::InvalidMethod,
+                    format!("invalid method {}, object {}@{}", opcode, interface.name, object_id),
+                ),
+                Some(desc) => (
+                    MalformedReason::InvalidArguments,
+                    DisplayError::InvalidMethod,
+                    format!("invalid arguments for {}@{}.{}", interface.name, object_id, desc.name),
+                ),
+            },
+        };
+        let discard = match self.malformed_policy {
+            MalformedRequestPolicy::Kill => false,
+            MalformedRequestPolicy::Callback(ref callback) => callback(
+                ClientId { id: self.id.clone() },
+                &MalformedRequest { object_id, interface, opcode, size, reason },
+            ),
+        };
+        if discard && self.socket.skip_one_message().is_ok() {
+            crate::log_warn!("Discarded a malformed request: {}.", error);
+            return true;
+        }
+        let code = code as u32;
+        let reason = DisconnectReason::InvalidMessage(ProtocolError {
+            code,
+            object_id: 1,
+            object_interface: WL_DISPLAY_INTERFACE.name.into(),
+            message: error.clone(),
+        });
+        let decision = match self.kill_decision(&reason) {
+            KillDecision::Keep if self.socket.skip_one_message().is_ok() => {
+                crate::log_warn!("Kept a client despite a malformed request: {}.", error);
+                return true;
+            }
+            // the request cannot be skipped
+            KillDecision::Keep => KillDecision::Kill,
+            decision => decision,
+        };
+        let display = InnerObjectId {
+            id: 1,
+            interface: &WL_DISPLAY_INTERFACE,
+            client_id: self.id.clone(),
+            serial: 0,
+        };
+        self.send_error(display, code, CString::new(error).unwrap());
+        self.apply_kill(decision, reason);
+        false
+    }
+
+    pub(crate) fn set_malformed_request_policy(&mut self, policy: MalformedRequestPolicy) {
+        self.malformed_policy = policy;
+    }
+
     fn get_object(&self, id: InnerObjectId) -> Result<Object<Data<D>>, InvalidId> {
         let object = self.map.find(id.id).ok_or(InvalidId)?;
         if object.data.serial != id.serial {
@@ -396,6 +853,7 @@ impl<D> Client<D> {
     }
 
     fn queue_all_destructors(&mut self, pending_destructors: &mut Vec<PendingDestructor<D>>) {
+        let start = pending_destructors.len();
         pending_destructors.extend(self.map.all_objects().map(|(id, obj)| {
             (
                 obj.data.user_data.clone(),
@@ -408,6 +866,9 @@ impl<D> Client<D> {
                 },
             )
         }));
+        // the serials follow the creation order, so the objects are destroyed before the ones they were
+        // created from
+        pending_destructors[start..].sort_by_key(|(_, _, id)| std::cmp::Reverse(id.serial));
     }
 
     pub(crate) fn handle_display_request(
@@ -663,17 +1124,81 @@ impl<D> Client<D> {
 pub(crate) struct ClientStore<D: 'static> {
     clients: Vec<Option<Client<D>>>,
     last_serial: u32,
-    debug: bool,
+    debug: Option<DebugConfig>,
+    metrics: bool,
+    id_reuse: IdReusePolicy,
+    logger: Option<Arc<dyn ProtocolLogger>>,
+    malformed_policy: MalformedRequestPolicy,
+    kill_policy: KillPolicy,
+    message_limits: MessageLimits,
 }
 
 impl<D> ClientStore<D> {
-    pub(crate) fn new(debug: bool) -> Self {
-        Self { clients: Vec::new(), last_serial: 0, debug }
+    pub(crate) fn new(debug: Option<DebugConfig>) -> Self {
+        Self {
+            clients: Vec::new(),
+            last_serial: 0,
+            debug,
+            metrics: false,
+            id_reuse: IdReusePolicy::default(),
+            logger: None,
+            malformed_policy: MalformedRequestPolicy::Kill,
+            kill_policy: KillPolicy::Kill,
+            message_limits: MessageLimits::default(),
+        }
+    }
+
+    /// Set the protocol logger of all current and future clients
+    pub(crate) fn set_protocol_logger(&mut self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        for client in self.clients.iter_mut().flatten() {
+            client.logger = logger.clone();
+        }
+        self.logger = logger;
+    }
+
+    /// Set the malformed request policy of all current and future clients
+    pub(crate) fn set_malformed_request_policy(&mut self, policy: MalformedRequestPolicy) {
+        for client in self.clients.iter_mut().flatten() {
+            client.set_malformed_request_policy(policy.clone());
+        }
+        self.malformed_policy = policy;
+    }
+
+    /// Set the kill policy of all current and future clients
+    pub(crate) fn set_kill_policy(&mut self, policy: KillPolicy) {
+        for client in self.clients.iter_mut().flatten() {
+            client.set_kill_policy(policy.clone());
+        }
+        self.kill_policy = policy;
+    }
+
+    /// Set the message limits of all current and future clients
+    pub(crate) fn set_message_limits(&mut self, limits: MessageLimits) {
+        for client in self.clients.iter_mut().flatten() {
+            client.set_message_limits(limits);
+        }
+        self.message_limits = limits;
+    }
+
+    /// Set the id reuse policy of all current and future clients
+    pub(crate) fn set_id_reuse_policy(&mut self, policy: IdReusePolicy) {
+        for client in self.clients.iter_mut().flatten() {
+            client.map.set_reuse_policy(policy.clone());
+        }
+        self.id_reuse = policy;
+    }
+
+    /// Start counting the messages of all current and future clients
+    pub(crate) fn enable_metrics(&mut self) {
+        self.metrics = true;
+        for client in self.clients.iter_mut().flatten() {
+            client.metrics.get_or_insert_with(Metrics::default);
+        }
     }
 
     pub(crate) fn create_client(
         &mut self,
-        stream: UnixStream,
+        socket: Socket,
         data: Arc<dyn ClientData>,
     ) -> InnerClientId {
         let serial = self.next_serial();
@@ -688,7 +1213,13 @@ impl<D> ClientStore<D> {
 
         let id = InnerClientId { id: id as u32, serial };
 
-        *place = Some(Client::new(stream, id.clone(), self.debug, data));
+        let mut client = Client::new(socket, id.clone(), self.debug.clone(), self.metrics, data);
+        client.map.set_reuse_policy(self.id_reuse.clone());
+        client.logger = self.logger.clone();
+        client.set_malformed_request_policy(self.malformed_policy.clone());
+        client.set_kill_policy(self.kill_policy.clone());
+        client.set_message_limits(self.message_limits);
+        *place = Some(client);
 
         id
     }
@@ -713,15 +1244,17 @@ impl<D> ClientStore<D> {
     pub(crate) fn cleanup(
         &mut self,
         pending_destructors: &mut Vec<PendingDestructor<D>>,
-    ) -> SmallVec<[ClientId; 1]> {
+    ) -> SmallVec<[(ClientId, DisconnectReason); 1]> {
         let mut cleaned = SmallVec::new();
         for place in &mut self.clients {
             if place.as_ref().map(|client| client.killed).unwrap_or(false) {
                 // Remove the client from the store and flush it one last time before dropping it
                 let mut client = place.take().unwrap();
                 client.queue_all_destructors(pending_destructors);
-                let _ = client.flush();
-                cleaned.push(ClientId { id: client.id });
+                let _ = client.socket.flush();
+                let reason =
+                    client.disconnect_reason.take().unwrap_or(DisconnectReason::ConnectionClosed);
+                cleaned.push((ClientId { id: client.id }, reason));
             }
         }
         cleaned
@@ -736,6 +1269,10 @@ impl<D> ClientStore<D> {
         self.clients.iter_mut().flat_map(|o| o.as_mut()).filter(|c| !c.killed)
     }
 
+    pub(crate) fn all_clients(&self) -> impl Iterator<Item = &Client<D>> {
+        self.clients.iter().flat_map(|o| o.as_ref()).filter(|c| !c.killed)
+    }
+
     pub(crate) fn all_clients_id(&self) -> impl Iterator<Item = ClientId> + '_ {
         self.clients.iter().flat_map(|opt| {
             opt.as_ref().filter(|c| !c.killed).map(|client| ClientId { id: client.id.clone() })
@@ -1,18 +1,19 @@
 use std::{
-    os::unix::io::{AsRawFd, FromRawFd},
+    ffi::CString,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
     os::unix::io::{BorrowedFd, OwnedFd},
     sync::{Arc, Mutex},
 };
 
 use super::{
-    handle::State, ClientId, Data, GlobalHandler, GlobalId, Handle, InnerClientId, InnerGlobalId,
-    InnerHandle, InnerObjectId, ObjectId,
+    client::PendingRequest, handle::State, ClientData, ClientId, GlobalHandler, GlobalId, Handle,
+    InnerClientId, InnerGlobalId, InnerHandle, InnerObjectId, ObjectId,
 };
 use crate::{
     core_interfaces::{WL_DISPLAY_INTERFACE, WL_REGISTRY_INTERFACE},
-    protocol::{same_interface, Argument, Message},
-    rs::map::Object,
-    types::server::InitError,
+    protocol::{same_interface, Message},
+    types::server::{AcceptCallback, InitError},
+    types::wakeup::{self, WakeupHandle},
 };
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -25,7 +26,76 @@ use nix::sys::epoll::*;
     target_os = "openbsd"
 ))]
 use nix::sys::event::*;
-use smallvec::SmallVec;
+
+// the poll tokens of the wakeup file descriptor and of the listeners, which no client id can take
+pub(crate) const WAKEUP_TOKEN: u64 = u64::MAX;
+const LISTENER_TOKEN: u64 = (u32::MAX as u64) << 32;
+
+/// The poll token of a listener, or of its close fd
+pub(super) fn listener_token(index: usize, close: bool) -> u64 {
+    LISTENER_TOKEN | (index as u64) << 1 | close as u64
+}
+
+/// The index of the listener of a poll token, and whether the token is the one of its close fd
+fn listener_event(token: u64) -> Option<(usize, bool)> {
+    (token & LISTENER_TOKEN == LISTENER_TOKEN)
+        .then_some(((token & u32::MAX as u64) as usize >> 1, token & 1 == 1))
+}
+
+/// Register a file descriptor to be monitored for readability by the poll fd
+pub(super) fn register(poll_fd: RawFd, fd: RawFd, token: u64) -> std::io::Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut evt = EpollEvent::new(EpollFlags::EPOLLIN, token);
+        epoll_ctl(poll_fd, EpollOp::EpollCtlAdd, fd, &mut evt)?;
+    }
+
+    #[cfg(any(
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        let evt = KEvent::new(
+            fd as usize,
+            EventFilter::EVFILT_READ,
+            EventFlag::EV_ADD | EventFlag::EV_RECEIPT,
+            FilterFlag::empty(),
+            0,
+            token as isize,
+        );
+        kevent_ts(poll_fd, &[evt], &mut [], None)?;
+    }
+
+    Ok(())
+}
+
+/// Stop monitoring a file descriptor registered with `register()`
+pub(super) fn deregister(poll_fd: RawFd, fd: RawFd) -> std::io::Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    epoll_ctl(poll_fd, EpollOp::EpollCtlDel, fd, None)?;
+
+    #[cfg(any(
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    {
+        let evt = KEvent::new(
+            fd as usize,
+            EventFilter::EVFILT_READ,
+            EventFlag::EV_DELETE | EventFlag::EV_RECEIPT,
+            FilterFlag::empty(),
+            0,
+            0,
+        );
+        kevent_ts(poll_fd, &[evt], &mut [], None)?;
+    }
+
+    Ok(())
+}
 
 #[derive(Debug)]
 pub struct InnerBackend<D: 'static> {
@@ -53,7 +123,15 @@ impl<D> InnerBackend<D> {
     }
 
     pub fn flush(&self, client: Option<ClientId>) -> std::io::Result<()> {
-        self.state.lock().unwrap().flush(client)
+        let (ret, drained) = {
+            let mut state = self.state.lock().unwrap();
+            let ret = state.flush(client);
+            (ret, std::mem::take(&mut state.drained))
+        };
+        for callback in drained {
+            callback.invoke();
+        }
+        ret
     }
 
     pub fn handle(&self) -> Handle {
@@ -67,21 +145,135 @@ impl<D> InnerBackend<D> {
         unsafe { BorrowedFd::borrow_raw(raw_fd) }
     }
 
+    pub fn wakeup_handle(&self) -> std::io::Result<WakeupHandle> {
+        self.state.lock().unwrap().wakeup_handle()
+    }
+
+    pub fn add_listener(
+        &self,
+        fd: OwnedFd,
+        accept: AcceptCallback<D, dyn ClientData>,
+    ) -> std::io::Result<()> {
+        self.handle().handle.add_listener::<D>(fd, None, accept)
+    }
+
+    fn accept_clients(&self, data: &mut D, index: usize) {
+        let listener = match self.state.lock().unwrap().listeners.get(index) {
+            Some(Some(listener)) => listener.clone(),
+            _ => return,
+        };
+        let mut listener = listener.lock().unwrap();
+        loop {
+            let stream = match listener.next_connection() {
+                Ok(Some(stream)) => stream,
+                Ok(None) => break,
+                Err(e) => {
+                    crate::log_warn!("Failed to accept a client connection: {}", e);
+                    break;
+                }
+            };
+            if let Some(client_data) = (listener.accept)(data, &stream) {
+                if let Err(e) = self.handle().handle.insert_client(stream, client_data) {
+                    crate::log_warn!("Failed to insert an accepted client: {}", e);
+                }
+            }
+        }
+    }
+
+    fn close_listener(&self, index: usize) {
+        let (poll_fd, listener) = {
+            let mut state = self.state.lock().unwrap();
+            match state.listeners.get_mut(index).and_then(Option::take) {
+                Some(listener) => (state.poll_fd.as_raw_fd(), listener),
+                None => return,
+            }
+        };
+        let listener = listener.lock().unwrap();
+        let _ = deregister(poll_fd, listener.fd());
+        if let Some(close_fd) = listener.close_fd() {
+            let _ = deregister(poll_fd, close_fd);
+        }
+    }
+
+    pub fn flush_destructors(&self, data: &mut D) {
+        let cleanup = self.state.lock().unwrap().cleanup();
+        cleanup(&self.handle(), data);
+    }
+
+    pub fn shutdown(&self, data: &mut D, message: Option<CString>) {
+        let listeners = self.state.lock().unwrap().listeners.len();
+        for index in 0..listeners {
+            self.close_listener(index);
+        }
+        let _ = self.flush(None);
+        let handle = self.handle();
+        {
+            let mut state = self.state.lock().unwrap();
+            for client in state.clients.clients_mut() {
+                client.shutdown(message.clone());
+            }
+        }
+        // the clients are flushed a last time before being removed, sending them the errors
+        let cleanup = self.state.lock().unwrap().cleanup();
+        cleanup(&handle, data);
+    }
+
+    fn drain_wakeup(&self) {
+        if let Some(ref wakeup) = self.state.lock().unwrap().wakeup {
+            wakeup::drain(wakeup.fd().as_raw_fd());
+        }
+    }
+
     pub fn dispatch_client(
         &self,
         data: &mut D,
         client_id: InnerClientId,
     ) -> std::io::Result<usize> {
+        self.state.lock().unwrap().begin_dispatch_cycle(Some(client_id.clone()));
         let ret = self.dispatch_events_for(data, client_id);
         let cleanup = self.state.lock().unwrap().cleanup();
         cleanup(&self.handle(), data);
+        self.end_dispatch_cycle();
         ret
     }
 
+    /// Report the clients which exceeded the rate limit, and wake the event loop up again if the
+    /// requests of some clients were deferred
+    fn end_dispatch_cycle(&self) {
+        let (exceeded, on_exceeded, throttled) = {
+            let mut state = self.state.lock().unwrap();
+            let on_exceeded = state.rate_limit.as_ref().and_then(|limit| limit.on_exceeded.clone());
+            (std::mem::take(&mut state.exceeded), on_exceeded, !state.throttled.is_empty())
+        };
+        if let Some(on_exceeded) = on_exceeded {
+            let handle = self.handle();
+            for client in exceeded {
+                on_exceeded(&handle, client);
+            }
+        }
+        if throttled {
+            match self.wakeup_handle() {
+                Ok(wakeup) => wakeup.wake(),
+                Err(e) => crate::log_warn!("Failed to wake up for the deferred requests: {}", e),
+            }
+        }
+    }
+
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fn dispatch_all_clients(&self, data: &mut D) -> std::io::Result<usize> {
-        let poll_fd = self.poll_fd();
         let mut dispatched = 0;
+        let deferred = self.state.lock().unwrap().begin_dispatch_cycle(None);
+        // their pending requests may already be buffered, leaving nothing to read from the socket
+        for id in deferred {
+            if let Ok(count) = self.dispatch_events_for(data, id) {
+                dispatched += count;
+            }
+        }
+        // the globals whose grace period is over are removed even without client activity
+        let cleanup = self.state.lock().unwrap().cleanup();
+        cleanup(&self.handle(), data);
+
+        let poll_fd = self.poll_fd();
         loop {
             let mut events = [EpollEvent::empty(); 32];
             let nevents = epoll_wait(poll_fd.as_raw_fd(), &mut events, 0)?;
@@ -90,8 +282,33 @@ impl<D> InnerBackend<D> {
                 break;
             }
 
+            let mut progress = false;
+
             for event in events.iter().take(nevents) {
+                if event.data() == WAKEUP_TOKEN {
+                    self.drain_wakeup();
+                    progress = true;
+                    continue;
+                }
+                match listener_event(event.data()) {
+                    Some((index, false)) => {
+                        self.accept_clients(data, index);
+                        progress = true;
+                        continue;
+                    }
+                    Some((index, true)) => {
+                        self.close_listener(index);
+                        progress = true;
+                        continue;
+                    }
+                    None => {}
+                }
                 let id = InnerClientId::from_u64(event.data());
+                // the sockets of the throttled clients remain readable until the next cycle
+                if self.state.lock().unwrap().throttled.contains(&id) {
+                    continue;
+                }
+                progress = true;
                 // remove the cb while we call it, to gracefully handle reentrancy
                 if let Ok(count) = self.dispatch_events_for(data, id) {
                     dispatched += count;
@@ -99,7 +316,11 @@ impl<D> InnerBackend<D> {
             }
             let cleanup = self.state.lock().unwrap().cleanup();
             cleanup(&self.handle(), data);
+            if !progress {
+                break;
+            }
         }
+        self.end_dispatch_cycle();
 
         Ok(dispatched)
     }
@@ -111,8 +332,19 @@ impl<D> InnerBackend<D> {
         target_os = "openbsd"
     ))]
     pub fn dispatch_all_clients(&self, data: &mut D) -> std::io::Result<usize> {
-        let poll_fd = self.poll_fd();
         let mut dispatched = 0;
+        let deferred = self.state.lock().unwrap().begin_dispatch_cycle(None);
+        // their pending requests may already be buffered, leaving nothing to read from the socket
+        for id in deferred {
+            if let Ok(count) = self.dispatch_events_for(data, id) {
+                dispatched += count;
+            }
+        }
+        // the globals whose grace period is over are removed even without client activity
+        let cleanup = self.state.lock().unwrap().cleanup();
+        cleanup(&self.handle(), data);
+
+        let poll_fd = self.poll_fd();
         loop {
             let mut events = [KEvent::new(
                 0,
@@ -129,8 +361,33 @@ impl<D> InnerBackend<D> {
                 break;
             }
 
+            let mut progress = false;
+
             for event in events.iter().take(nevents) {
+                if event.udata() as u64 == WAKEUP_TOKEN {
+                    self.drain_wakeup();
+                    progress = true;
+                    continue;
+                }
+                match listener_event(event.udata() as u64) {
+                    Some((index, false)) => {
+                        self.accept_clients(data, index);
+                        progress = true;
+                        continue;
+                    }
+                    Some((index, true)) => {
+                        self.close_listener(index);
+                        progress = true;
+                        continue;
+                    }
+                    None => {}
+                }
                 let id = InnerClientId::from_u64(event.udata() as u64);
+                // the sockets of the throttled clients remain readable until the next cycle
+                if self.state.lock().unwrap().throttled.contains(&id) {
+                    continue;
+                }
+                progress = true;
                 // remove the cb while we call it, to gracefully handle reentrancy
                 if let Ok(count) = self.dispatch_events_for(data, id) {
                     dispatched += count;
@@ -138,7 +395,11 @@ impl<D> InnerBackend<D> {
             }
             let cleanup = self.state.lock().unwrap().cleanup();
             cleanup(&self.handle(), data);
+            if !progress {
+                break;
+            }
         }
+        self.end_dispatch_cycle();
 
         Ok(dispatched)
     }
@@ -155,50 +416,71 @@ impl<D> InnerBackend<D> {
             let action = {
                 let state = &mut *state;
                 if let Ok(client) = state.clients.get_client_mut(client_id.clone()) {
-                    let (message, object) = match client.next_request() {
-                        Ok(v) => v,
-                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                            if dispatched > 0 {
-                                break;
-                            } else {
-                                return Err(e);
+                    if let Some(ref limit) = state.rate_limit {
+                        if client.requests.cycle >= limit.max_requests {
+                            // defer the other requests to the next dispatch cycle
+                            if !state.throttled.contains(&client_id) {
+                                state.throttled.push(client_id.clone());
                             }
+                            break;
                         }
-                        Err(e) => return Err(e),
-                    };
-                    dispatched += 1;
-                    if same_interface(object.interface, &WL_DISPLAY_INTERFACE) {
-                        client.handle_display_request(message, &mut state.registry);
-                        continue;
-                    } else if same_interface(object.interface, &WL_REGISTRY_INTERFACE) {
-                        if let Some((client, global, object, handler)) =
-                            client.handle_registry_request(message, &mut state.registry)
-                        {
-                            DispatchAction::Bind { client, global, object, handler }
-                        } else {
-                            continue;
-                        }
+                    }
+                    // the requests of the objects which were resumed come first
+                    if let Some(request) = client.next_deferred_request() {
+                        dispatched += 1;
+                        client.requests.cycle += 1;
+                        DispatchAction::Request(request)
                     } else {
-                        let object_id = InnerObjectId {
-                            id: message.sender_id,
-                            serial: object.data.serial,
-                            interface: object.interface,
-                            client_id: client.id.clone(),
+                        let (message, object) = match client.next_request() {
+                            Ok(v) => v,
+                            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                                if dispatched > 0 {
+                                    break;
+                                } else {
+                                    return Err(e);
+                                }
+                            }
+                            Err(e) => return Err(e),
                         };
-                        let opcode = message.opcode;
-                        let (arguments, is_destructor, created_id) =
-                            match client.process_request(&object, message) {
-                                Some(args) => args,
-                                None => continue,
+                        dispatched += 1;
+                        client.requests.cycle += 1;
+                        if same_interface(object.interface, &WL_DISPLAY_INTERFACE) {
+                            client.handle_display_request(message, &mut state.registry);
+                            continue;
+                        } else if same_interface(object.interface, &WL_REGISTRY_INTERFACE) {
+                            if let Some((client, global, object, handler)) =
+                                client.handle_registry_request(message, &mut state.registry)
+                            {
+                                DispatchAction::Bind { client, global, object, handler }
+                            } else {
+                                continue;
+                            }
+                        } else {
+                            let object_id = InnerObjectId {
+                                id: message.sender_id,
+                                serial: object.data.serial,
+                                interface: object.interface,
+                                client_id: client.id.clone(),
                             };
-                        // Return the whole set to invoke the callback while handle is not borrower via client
-                        DispatchAction::Request {
-                            object,
-                            object_id,
-                            opcode,
-                            arguments,
-                            is_destructor,
-                            created_id,
+                            let opcode = message.opcode;
+                            let (arguments, is_destructor, created_id) =
+                                match client.process_request(&object, message) {
+                                    Some(args) => args,
+                                    None => continue,
+                                };
+                            let request = PendingRequest {
+                                object,
+                                object_id,
+                                opcode,
+                                arguments,
+                                is_destructor,
+                                created_id,
+                            };
+                            // Return the whole set to invoke the callback while handle is not borrower via client
+                            match client.defer_request(request) {
+                                Some(request) => DispatchAction::Request(request),
+                                None => continue,
+                            }
                         }
                     }
                 } else {
@@ -209,14 +491,14 @@ impl<D> InnerBackend<D> {
                 }
             };
             match action {
-                DispatchAction::Request {
+                DispatchAction::Request(PendingRequest {
                     object,
                     object_id,
                     opcode,
                     arguments,
                     is_destructor,
                     created_id,
-                } => {
+                }) => {
                     // temporarily unlock the state Mutex while this request is dispatched
                     std::mem::drop(state);
                     let ret = object.data.user_data.clone().request(
@@ -258,7 +540,7 @@ impl<D> InnerBackend<D> {
                             // Allow the callback to not return any data if the client is already dead (typically
                             // if the callback provoked a protocol error)
                             if let Ok(client) = state.clients.get_client(client_id.clone()) {
-                                if !client.killed {
+                                if !client.is_closing() {
                                     panic!(
                                         "Callback creating object {} did not provide any object data.",
                                         child_id
@@ -289,19 +571,20 @@ impl<D> InnerBackend<D> {
                 }
             }
         }
+        let state = &mut *state;
+        if let (Some(limit), Ok(client)) =
+            (&state.rate_limit, state.clients.get_client_mut(client_id.clone()))
+        {
+            if client.requests.count(limit, dispatched) {
+                state.exceeded.push(ClientId { id: client_id });
+            }
+        }
         Ok(dispatched)
     }
 }
 
 enum DispatchAction<D: 'static> {
-    Request {
-        object: Object<Data<D>>,
-        object_id: InnerObjectId,
-        opcode: u16,
-        arguments: SmallVec<[Argument<ObjectId, OwnedFd>; 4]>,
-        is_destructor: bool,
-        created_id: Option<InnerObjectId>,
-    },
+    Request(PendingRequest<D>),
     Bind {
         object: InnerObjectId,
         client: InnerClientId,
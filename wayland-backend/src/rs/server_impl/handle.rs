@@ -6,45 +6,124 @@ use std::{
         net::UnixStream,
     },
     sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
 };
 
 use crate::{
-    protocol::{same_interface, Interface, Message, ObjectInfo, ANONYMOUS_INTERFACE},
-    types::server::{DisconnectReason, GlobalInfo, InvalidId},
+    protocol::{
+        same_interface, IdReusePolicy, Interface, IoStats, Message, MessageLimits, Metrics,
+        ObjectInfo, ANONYMOUS_INTERFACE,
+    },
+    rs::{debug::DebugConfig, socket::Socket, LoopbackStream},
+    types::server::{
+        AcceptCallback, ClientDestroyCallback, DestroyListenerId, DestroyListeners,
+        DisconnectReason, DrainCallback, GlobalFinalizer, GlobalInfo, GlobalRemovals, InvalidId,
+        Listener, PauseError,
+    },
+    types::wakeup::{Wakeup, WakeupHandle},
 };
 
 use super::{
-    client::ClientStore, registry::Registry, ClientData, ClientId, Credentials, GlobalHandler,
-    InnerClientId, InnerGlobalId, InnerObjectId, ObjectData, ObjectId,
+    client::{ClientStore, DisplayError},
+    common_poll::{deregister, listener_token, register, WAKEUP_TOKEN},
+    registry::Registry,
+    ClientData, ClientId, Credentials, GlobalHandler, InnerClientId, InnerGlobalId, InnerObjectId,
+    KillPolicy, MalformedRequestPolicy, ObjectData, ObjectId, OverflowPolicy, ProtocolLogger,
+    RateLimit,
 };
 
 pub(crate) type PendingDestructor<D> = (Arc<dyn ObjectData<D>>, InnerClientId, InnerObjectId);
 
+// shared so that the accept callback can be invoked without holding the state lock
+pub(crate) type SharedListener<D> = Arc<Mutex<Listener<AcceptCallback<D, dyn ClientData>>>>;
+
 #[derive(Debug)]
 pub struct State<D: 'static> {
     pub(crate) clients: ClientStore<D>,
     pub(crate) registry: Registry<D>,
     pub(crate) pending_destructors: Vec<PendingDestructor<D>>,
+    pending_removals: GlobalRemovals<InnerGlobalId, super::Handle, D>,
+    destroy_listeners:
+        DestroyListeners<ClientId, ClientDestroyCallback<super::Handle, ClientId, D>>,
     pub(crate) poll_fd: OwnedFd,
+    pub(crate) wakeup: Option<Wakeup>,
+    // the listeners keep their index, which is part of their poll token
+    pub(crate) listeners: Vec<Option<SharedListener<D>>>,
+    pub(crate) rate_limit: Option<RateLimit>,
+    // the clients whose requests were deferred during the current dispatch cycle
+    pub(crate) throttled: Vec<InnerClientId>,
+    // the clients which exceeded the threshold of the rate limit, to report after the cycle
+    pub(crate) exceeded: Vec<ClientId>,
+    // the drain callbacks to invoke once the lock is released
+    pub(crate) drained: Vec<DrainCallback>,
 }
 
 impl<D> State<D> {
     pub(crate) fn new(poll_fd: OwnedFd) -> Self {
-        let debug =
-            matches!(std::env::var_os("WAYLAND_DEBUG"), Some(str) if str == "1" || str == "server");
         Self {
-            clients: ClientStore::new(debug),
+            clients: ClientStore::new(DebugConfig::from_env("server")),
             registry: Registry::new(),
             pending_destructors: Vec::new(),
+            pending_removals: GlobalRemovals::new(),
+            destroy_listeners: DestroyListeners::new(),
             poll_fd,
+            wakeup: None,
+            listeners: Vec::new(),
+            rate_limit: None,
+            throttled: Vec::new(),
+            exceeded: Vec::new(),
+            drained: Vec::new(),
+        }
+    }
+
+    /// Reset the requests counted for the rate limit at the start of a dispatch cycle
+    ///
+    /// Returns the clients whose requests were deferred by the previous cycle.
+    pub(crate) fn begin_dispatch_cycle(
+        &mut self,
+        client: Option<InnerClientId>,
+    ) -> Vec<InnerClientId> {
+        match client {
+            Some(id) => {
+                if let Ok(client) = self.clients.get_client_mut(id.clone()) {
+                    client.requests.cycle = 0;
+                }
+                self.throttled.retain(|throttled| throttled != &id);
+                Vec::new()
+            }
+            None => {
+                for client in self.clients.clients_mut() {
+                    client.requests.cycle = 0;
+                }
+                std::mem::take(&mut self.throttled)
+            }
         }
     }
 
+    pub(crate) fn wakeup_handle(&mut self) -> std::io::Result<WakeupHandle> {
+        if let Some(ref wakeup) = self.wakeup {
+            return Ok(wakeup.handle());
+        }
+        let wakeup = Wakeup::new()?;
+
+        register(self.poll_fd.as_raw_fd(), wakeup.fd().as_raw_fd(), WAKEUP_TOKEN)?;
+
+        let handle = wakeup.handle();
+        self.wakeup = Some(wakeup);
+        Ok(handle)
+    }
+
     pub(crate) fn cleanup<'a>(&mut self) -> impl FnOnce(&super::Handle, &mut D) + 'a {
         let dead_clients = self.clients.cleanup(&mut self.pending_destructors);
-        self.registry.cleanup(&dead_clients);
+        let dead_ids = dead_clients.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+        self.registry.cleanup(&dead_ids);
         // return a closure that will do the cleanup once invoked
         let pending_destructors = std::mem::take(&mut self.pending_destructors);
+        let destroy_listeners = self.destroy_listeners.take(|client| dead_ids.contains(client));
+        let removed_globals = self.pending_removals.take_expired(Instant::now());
+        for (id, _) in &removed_globals {
+            self.registry.remove_global(id.clone(), &mut self.clients);
+        }
         move |handle, data| {
             for (object_data, client_id, object_id) in pending_destructors {
                 object_data.clone().destroyed(
@@ -54,18 +133,34 @@ impl<D> State<D> {
                     ObjectId { id: object_id },
                 );
             }
+            for (client_id, listener) in destroy_listeners {
+                let reason = dead_clients
+                    .iter()
+                    .find(|(id, _)| *id == client_id)
+                    .map(|(_, reason)| reason.clone())
+                    .unwrap();
+                listener(handle, data, client_id, reason);
+            }
+            for (_, finalize) in removed_globals {
+                finalize(handle, data);
+            }
         }
     }
 
     pub(crate) fn flush(&mut self, client: Option<ClientId>) -> std::io::Result<()> {
         if let Some(ClientId { id: client }) = client {
             match self.clients.get_client_mut(client) {
-                Ok(client) => client.flush(),
+                Ok(client) => {
+                    let ret = client.flush();
+                    client.take_drained(&mut self.drained);
+                    ret
+                }
                 Err(InvalidId) => Ok(()),
             }
         } else {
             for client in self.clients.clients_mut() {
                 let _ = client.flush();
+                client.take_drained(&mut self.drained);
             }
             Ok(())
         }
@@ -114,7 +209,35 @@ impl InnerHandle {
         stream: UnixStream,
         data: Arc<dyn ClientData>,
     ) -> std::io::Result<InnerClientId> {
-        self.state.lock().unwrap().insert_client(stream, data)
+        self.state.lock().unwrap().insert_client(Socket::from(stream), data)
+    }
+
+    pub fn insert_loopback_client(
+        &self,
+        stream: LoopbackStream,
+        data: Arc<dyn ClientData>,
+    ) -> std::io::Result<InnerClientId> {
+        self.state.lock().unwrap().insert_client(Socket::from(stream), data)
+    }
+
+    #[cfg(any(test, feature = "fault_injection"))]
+    pub fn insert_client_with_faults(
+        &self,
+        stream: UnixStream,
+        data: Arc<dyn ClientData>,
+        config: crate::rs::FaultConfig,
+    ) -> std::io::Result<InnerClientId> {
+        self.state.lock().unwrap().insert_client(Socket::with_faults(stream, config), data)
+    }
+
+    #[cfg(any(test, feature = "fault_injection"))]
+    pub fn insert_loopback_client_with_faults(
+        &self,
+        stream: LoopbackStream,
+        data: Arc<dyn ClientData>,
+        config: crate::rs::FaultConfig,
+    ) -> std::io::Result<InnerClientId> {
+        self.state.lock().unwrap().insert_client(Socket::with_faults(stream, config), data)
     }
 
     pub fn get_client(&self, id: InnerObjectId) -> Result<ClientId, InvalidId> {
@@ -129,6 +252,69 @@ impl InnerHandle {
         self.state.lock().unwrap().get_client_credentials(id)
     }
 
+    pub fn get_client_security_label(
+        &self,
+        id: InnerClientId,
+    ) -> Result<Option<String>, InvalidId> {
+        self.state.lock().unwrap().get_client_security_label(id)
+    }
+
+    pub fn set_client_max_buffer_size(
+        &self,
+        id: InnerClientId,
+        size: usize,
+    ) -> Result<(), InvalidId> {
+        self.state.lock().unwrap().set_client_max_buffer_size(id, size)
+    }
+
+    pub fn set_client_overflow_policy(
+        &self,
+        id: InnerClientId,
+        policy: OverflowPolicy,
+    ) -> Result<(), InvalidId> {
+        self.state.lock().unwrap().set_client_overflow_policy(id, policy)
+    }
+
+    pub fn enable_metrics(&self) {
+        self.state.lock().unwrap().enable_metrics()
+    }
+
+    pub fn set_id_reuse_policy(&self, policy: IdReusePolicy) {
+        self.state.lock().unwrap().set_id_reuse_policy(policy)
+    }
+
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.state.lock().unwrap().set_rate_limit(limit)
+    }
+
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.state.lock().unwrap().set_protocol_logger(logger)
+    }
+
+    pub fn set_malformed_request_policy(&self, policy: MalformedRequestPolicy) {
+        self.state.lock().unwrap().set_malformed_request_policy(policy)
+    }
+
+    pub fn set_message_limits(&self, limits: MessageLimits) {
+        self.state.lock().unwrap().set_message_limits(limits)
+    }
+
+    pub fn set_kill_policy(&self, policy: KillPolicy) {
+        self.state.lock().unwrap().set_kill_policy(policy)
+    }
+
+    pub fn get_client_metrics(&self, id: InnerClientId) -> Result<Metrics, InvalidId> {
+        self.state.lock().unwrap().get_client_metrics(id)
+    }
+
+    pub fn get_client_io_stats(&self, id: InnerClientId) -> Result<IoStats, InvalidId> {
+        self.state.lock().unwrap().get_client_io_stats(id)
+    }
+
+    pub fn io_stats(&self) -> IoStats {
+        self.state.lock().unwrap().io_stats()
+    }
+
     pub fn with_all_clients(&self, mut f: impl FnMut(ClientId)) {
         self.state.lock().unwrap().with_all_clients(&mut f)
     }
@@ -214,10 +400,18 @@ impl InnerHandle {
         self.state.lock().unwrap().post_error(object_id, error_code, message)
     }
 
+    pub fn set_object_paused(&self, id: InnerObjectId, paused: bool) -> Result<(), PauseError> {
+        self.state.lock().unwrap().set_object_paused(id, paused)
+    }
+
     pub fn kill_client(&self, client_id: InnerClientId, reason: DisconnectReason) {
         self.state.lock().unwrap().kill_client(client_id, reason)
     }
 
+    pub fn post_implementation_error(&self, client_id: InnerClientId, message: CString) {
+        self.state.lock().unwrap().post_implementation_error(client_id, message)
+    }
+
     pub fn create_global<D: 'static>(
         &self,
         interface: &'static Interface,
@@ -249,10 +443,68 @@ impl InnerHandle {
         state.registry.remove_global(id, &mut state.clients)
     }
 
+    pub fn remove_global_after<D: 'static>(
+        &self,
+        id: InnerGlobalId,
+        grace: Duration,
+        finalize: GlobalFinalizer<super::Handle, D>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::remove_global_after().");
+
+        state.registry.disable_global(id.clone(), &mut state.clients);
+        state.pending_removals.schedule(Instant::now() + grace, id, finalize);
+    }
+
     pub fn global_info(&self, id: InnerGlobalId) -> Result<GlobalInfo, InvalidId> {
         self.state.lock().unwrap().global_info(id)
     }
 
+    pub fn add_client_destroy_listener<D: 'static>(
+        &self,
+        client_id: InnerClientId,
+        listener: ClientDestroyCallback<super::Handle, ClientId, D>,
+    ) -> Result<DestroyListenerId, InvalidId> {
+        let mut state = self.state.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::add_client_destroy_listener().");
+
+        state.clients.get_client(client_id.clone())?;
+        Ok(state.destroy_listeners.add(ClientId { id: client_id }, listener))
+    }
+
+    pub fn remove_client_destroy_listener(&self, id: DestroyListenerId) -> bool {
+        self.state.lock().unwrap().remove_client_destroy_listener(id)
+    }
+
+    pub fn add_listener<D: 'static>(
+        &self,
+        fd: OwnedFd,
+        close_fd: Option<OwnedFd>,
+        accept: AcceptCallback<D, dyn ClientData>,
+    ) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::add_listener().");
+
+        let listener = Listener::new(fd, close_fd, accept)?;
+        let index = state.listeners.len();
+        let poll_fd = state.poll_fd.as_raw_fd();
+        register(poll_fd, listener.fd(), listener_token(index, false))?;
+        if let Some(close_fd) = listener.close_fd() {
+            if let Err(e) = register(poll_fd, close_fd, listener_token(index, true)) {
+                let _ = deregister(poll_fd, listener.fd());
+                return Err(e);
+            }
+        }
+        state.listeners.push(Some(Arc::new(Mutex::new(listener))));
+        Ok(())
+    }
+
     pub fn get_global_handler<D: 'static>(
         &self,
         id: InnerGlobalId,
@@ -264,21 +516,87 @@ impl InnerHandle {
         state.registry.get_handler(id)
     }
 
+    pub fn set_global_handler<D: 'static>(
+        &self,
+        id: InnerGlobalId,
+        handler: Arc<dyn GlobalHandler<D>>,
+    ) -> Result<(), InvalidId> {
+        let mut state = self.state.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::set_global_handler().");
+        state.registry.set_handler(id, handler)
+    }
+
     pub fn flush(&mut self, client: Option<ClientId>) -> std::io::Result<()> {
-        self.state.lock().unwrap().flush(client)
+        flush_and_drain(&*self.state, client)
+    }
+
+    pub fn flush_with_callback(
+        &mut self,
+        client: ClientId,
+        callback: DrainCallback,
+    ) -> Result<(), InvalidId> {
+        self.state.lock().unwrap().add_drain_callback(client.id.clone(), callback)?;
+        let _ = flush_and_drain(&*self.state, Some(client));
+        Ok(())
+    }
+
+    pub fn barrier(&mut self, client: ClientId, callback: DrainCallback) -> Result<(), InvalidId> {
+        self.state.lock().unwrap().add_barrier(client.id.clone(), callback)?;
+        let _ = flush_and_drain(&*self.state, Some(client));
+        Ok(())
     }
 }
 
+/// Flush the clients, then invoke the drain callbacks without holding the lock
+pub(crate) fn flush_and_drain(
+    state: &Mutex<dyn ErasedState + Send>,
+    client: Option<ClientId>,
+) -> std::io::Result<()> {
+    let (ret, drained) = {
+        let mut state = state.lock().unwrap();
+        let ret = state.flush(client);
+        (ret, state.take_drained())
+    };
+    for callback in drained {
+        callback.invoke();
+    }
+    ret
+}
+
 pub(crate) trait ErasedState: downcast_rs::Downcast {
     fn object_info(&self, id: InnerObjectId) -> Result<ObjectInfo, InvalidId>;
     fn insert_client(
         &mut self,
-        stream: UnixStream,
+        socket: Socket,
         data: Arc<dyn ClientData>,
     ) -> std::io::Result<InnerClientId>;
     fn get_client(&self, id: InnerObjectId) -> Result<ClientId, InvalidId>;
     fn get_client_data(&self, id: InnerClientId) -> Result<Arc<dyn ClientData>, InvalidId>;
     fn get_client_credentials(&self, id: InnerClientId) -> Result<Credentials, InvalidId>;
+    fn get_client_security_label(&self, id: InnerClientId) -> Result<Option<String>, InvalidId>;
+    fn set_client_max_buffer_size(
+        &mut self,
+        id: InnerClientId,
+        size: usize,
+    ) -> Result<(), InvalidId>;
+    fn set_client_overflow_policy(
+        &mut self,
+        id: InnerClientId,
+        policy: OverflowPolicy,
+    ) -> Result<(), InvalidId>;
+    fn remove_client_destroy_listener(&mut self, id: DestroyListenerId) -> bool;
+    fn enable_metrics(&mut self);
+    fn set_id_reuse_policy(&mut self, policy: IdReusePolicy);
+    fn set_rate_limit(&mut self, limit: Option<RateLimit>);
+    fn set_protocol_logger(&mut self, logger: Option<Arc<dyn ProtocolLogger>>);
+    fn set_malformed_request_policy(&mut self, policy: MalformedRequestPolicy);
+    fn set_kill_policy(&mut self, policy: KillPolicy);
+    fn set_message_limits(&mut self, limits: MessageLimits);
+    fn get_client_metrics(&self, id: InnerClientId) -> Result<Metrics, InvalidId>;
+    fn get_client_io_stats(&self, id: InnerClientId) -> Result<IoStats, InvalidId>;
+    fn io_stats(&self) -> IoStats;
     fn with_all_clients(&self, f: &mut dyn FnMut(ClientId));
     fn with_all_objects_for(
         &self,
@@ -297,9 +615,18 @@ pub(crate) trait ErasedState: downcast_rs::Downcast {
     ) -> Result<Arc<dyn std::any::Any + Send + Sync>, InvalidId>;
     fn send_event(&mut self, msg: Message<ObjectId, RawFd>) -> Result<(), InvalidId>;
     fn post_error(&mut self, object_id: InnerObjectId, error_code: u32, message: CString);
+    fn set_object_paused(&mut self, id: InnerObjectId, paused: bool) -> Result<(), PauseError>;
     fn kill_client(&mut self, client_id: InnerClientId, reason: DisconnectReason);
+    fn post_implementation_error(&mut self, client_id: InnerClientId, message: CString);
     fn global_info(&self, id: InnerGlobalId) -> Result<GlobalInfo, InvalidId>;
     fn flush(&mut self, client: Option<ClientId>) -> std::io::Result<()>;
+    fn add_drain_callback(
+        &mut self,
+        id: InnerClientId,
+        callback: DrainCallback,
+    ) -> Result<(), InvalidId>;
+    fn add_barrier(&mut self, id: InnerClientId, callback: DrainCallback) -> Result<(), InvalidId>;
+    fn take_drained(&mut self) -> Vec<DrainCallback>;
 }
 
 downcast_rs::impl_downcast!(ErasedState);
@@ -311,11 +638,11 @@ impl<D> ErasedState for State<D> {
 
     fn insert_client(
         &mut self,
-        stream: UnixStream,
+        socket: Socket,
         data: Arc<dyn ClientData>,
     ) -> std::io::Result<InnerClientId> {
-        let client_fd = stream.as_raw_fd();
-        let id = self.clients.create_client(stream, data);
+        let client_fd = socket.as_raw_fd();
+        let id = self.clients.create_client(socket, data);
 
         // register the client to the internal epoll
         #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -372,6 +699,77 @@ impl<D> ErasedState for State<D> {
         Ok(client.get_credentials())
     }
 
+    fn get_client_security_label(&self, id: InnerClientId) -> Result<Option<String>, InvalidId> {
+        Ok(self.clients.get_client(id)?.get_security_label())
+    }
+
+    fn set_client_max_buffer_size(
+        &mut self,
+        id: InnerClientId,
+        size: usize,
+    ) -> Result<(), InvalidId> {
+        self.clients.get_client_mut(id)?.set_max_buffer_size(size);
+        Ok(())
+    }
+
+    fn set_client_overflow_policy(
+        &mut self,
+        id: InnerClientId,
+        policy: OverflowPolicy,
+    ) -> Result<(), InvalidId> {
+        self.clients.get_client_mut(id)?.set_overflow_policy(policy);
+        Ok(())
+    }
+
+    fn remove_client_destroy_listener(&mut self, id: DestroyListenerId) -> bool {
+        self.destroy_listeners.remove(id)
+    }
+
+    fn enable_metrics(&mut self) {
+        self.clients.enable_metrics()
+    }
+
+    fn set_id_reuse_policy(&mut self, policy: IdReusePolicy) {
+        self.clients.set_id_reuse_policy(policy)
+    }
+
+    fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate_limit = limit;
+    }
+
+    fn set_protocol_logger(&mut self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.clients.set_protocol_logger(logger)
+    }
+
+    fn set_malformed_request_policy(&mut self, policy: MalformedRequestPolicy) {
+        self.clients.set_malformed_request_policy(policy)
+    }
+
+    fn set_kill_policy(&mut self, policy: KillPolicy) {
+        self.clients.set_kill_policy(policy)
+    }
+
+    fn set_message_limits(&mut self, limits: MessageLimits) {
+        self.clients.set_message_limits(limits)
+    }
+
+    fn get_client_metrics(&self, id: InnerClientId) -> Result<Metrics, InvalidId> {
+        let client = self.clients.get_client(id)?;
+        Ok(client.metrics.clone().unwrap_or_default())
+    }
+
+    fn get_client_io_stats(&self, id: InnerClientId) -> Result<IoStats, InvalidId> {
+        Ok(self.clients.get_client(id)?.io_stats())
+    }
+
+    fn io_stats(&self) -> IoStats {
+        let mut total = IoStats::default();
+        for client in self.clients.all_clients() {
+            total += client.io_stats();
+        }
+        total
+    }
+
     fn with_all_clients(&self, f: &mut dyn FnMut(ClientId)) {
         for client in self.clients.all_clients_id() {
             f(client)
@@ -427,11 +825,30 @@ impl<D> ErasedState for State<D> {
         }
     }
 
+    fn set_object_paused(&mut self, id: InnerObjectId, paused: bool) -> Result<(), PauseError> {
+        let client = self.clients.get_client_mut(id.client_id.clone())?;
+        if client.set_paused(id.clone(), paused)? && !self.throttled.contains(&id.client_id) {
+            // the deferred requests are dispatched by the next dispatch cycle
+            self.throttled.push(id.client_id);
+            match self.wakeup_handle() {
+                Ok(wakeup) => wakeup.wake(),
+                Err(e) => crate::log_warn!("Failed to wake up for the deferred requests: {}", e),
+            }
+        }
+        Ok(())
+    }
+
     fn kill_client(&mut self, client_id: InnerClientId, reason: DisconnectReason) {
         if let Ok(client) = self.clients.get_client_mut(client_id) {
             client.kill(reason)
         }
     }
+
+    fn post_implementation_error(&mut self, client_id: InnerClientId, message: CString) {
+        if let Ok(client) = self.clients.get_client_mut(client_id) {
+            client.post_display_error(DisplayError::Implementation, message)
+        }
+    }
     fn global_info(&self, id: InnerGlobalId) -> Result<GlobalInfo, InvalidId> {
         self.registry.get_info(id)
     }
@@ -439,4 +856,22 @@ impl<D> ErasedState for State<D> {
     fn flush(&mut self, client: Option<ClientId>) -> std::io::Result<()> {
         self.flush(client)
     }
+
+    fn add_drain_callback(
+        &mut self,
+        id: InnerClientId,
+        callback: DrainCallback,
+    ) -> Result<(), InvalidId> {
+        self.clients.get_client_mut(id)?.add_drain_callback(callback);
+        Ok(())
+    }
+
+    fn add_barrier(&mut self, id: InnerClientId, callback: DrainCallback) -> Result<(), InvalidId> {
+        self.clients.get_client_mut(id)?.add_barrier(callback);
+        Ok(())
+    }
+
+    fn take_drained(&mut self) -> Vec<DrainCallback> {
+        std::mem::take(&mut self.drained)
+    }
 }
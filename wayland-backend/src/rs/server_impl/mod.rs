@@ -3,7 +3,7 @@
 use std::os::unix::io::OwnedFd;
 use std::{fmt, sync::Arc};
 
-use crate::protocol::{same_interface, Interface, Message};
+use crate::protocol::{same_interface, Argument, Interface, Message};
 
 mod client;
 mod common_poll;
@@ -83,7 +83,7 @@ pub struct InnerClientId {
 }
 
 impl InnerClientId {
-    fn as_u64(&self) -> u64 {
+    pub(crate) fn as_u64(&self) -> u64 {
         ((self.id as u64) << 32) + self.serial as u64
     }
 
@@ -134,3 +134,32 @@ impl<D> ObjectData<D> for UninitObjectData {
         f.debug_struct("UninitObjectData").finish()
     }
 }
+
+/// The data of the objects created by a request which was discarded without being dispatched
+///
+/// Their requests are ignored until the client destroys them, and the objects they create are
+/// zombies as well.
+struct ZombieObjectData;
+
+impl<D> ObjectData<D> for ZombieObjectData {
+    fn request(
+        self: Arc<Self>,
+        _: &Handle,
+        _: &mut D,
+        _: ClientId,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<D>>> {
+        if msg.args.iter().any(|arg| matches!(arg, Argument::NewId(_))) {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    fn destroyed(self: Arc<Self>, _: &Handle, _: &mut D, _: ClientId, _: ObjectId) {}
+
+    #[cfg_attr(coverage, coverage(off))]
+    fn debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZombieObjectData").finish()
+    }
+}
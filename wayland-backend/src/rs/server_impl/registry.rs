@@ -99,6 +99,21 @@ impl<D> Registry<D> {
         Ok(global.handler.clone())
     }
 
+    pub(crate) fn set_handler(
+        &mut self,
+        id: InnerGlobalId,
+        handler: Arc<dyn GlobalHandler<D>>,
+    ) -> Result<(), InvalidId> {
+        let global = self
+            .globals
+            .get_mut(id.id as usize - 1)
+            .and_then(|o| o.as_mut())
+            .filter(|o| o.id == id)
+            .ok_or(InvalidId)?;
+        global.handler = handler;
+        Ok(())
+    }
+
     pub(crate) fn check_bind(
         &self,
         client: &Client<D>,
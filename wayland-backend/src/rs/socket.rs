@@ -7,14 +7,21 @@ use std::os::unix::net::UnixStream;
 
 use nix::sys::socket;
 
-use crate::protocol::{ArgumentType, Message};
+use crate::protocol::{Argument, ArgumentType, IoStats, Message, MessageLimits, MessageTooLarge};
 
+#[cfg(any(test, feature = "fault_injection"))]
+use super::faults::{FaultConfig, Faults};
+use super::loopback::LoopbackStream;
 use super::wire::{parse_message, write_to_buffers, MessageParseError, MessageWriteError};
 
 /// Maximum number of FD that can be sent in a single socket message
 pub const MAX_FDS_OUT: usize = 28;
 /// Maximum number of bytes that can be sent in a single socket message
 pub const MAX_BYTES_OUT: usize = 4096;
+/// Largest size in bytes the header of a message can encode
+pub const MAX_MESSAGE_SIZE: usize = 0xFFFF;
+/// Size up to which the outgoing buffer grows with the manual flush policy
+pub const MANUAL_FLUSH_BYTES: usize = 256 * 1024;
 
 /*
  * Socket
@@ -23,7 +30,15 @@ pub const MAX_BYTES_OUT: usize = 4096;
 /// A wayland socket
 #[derive(Debug)]
 pub struct Socket {
-    stream: UnixStream,
+    stream: Stream,
+    #[cfg(any(test, feature = "fault_injection"))]
+    faults: Option<std::sync::Mutex<Faults>>,
+}
+
+#[derive(Debug)]
+enum Stream {
+    Unix(UnixStream),
+    Loopback(LoopbackStream),
 }
 
 impl Socket {
@@ -35,14 +50,26 @@ impl Socket {
     /// slice should not be longer than `MAX_BYTES_OUT` otherwise the receiving
     /// end may lose some data.
     pub fn send_msg(&self, bytes: &[u8], fds: &[RawFd]) -> IoResult<usize> {
+        #[cfg(any(test, feature = "fault_injection"))]
+        if let Some(ref faults) = self.faults {
+            return faults.lock().unwrap().send_msg(self, bytes, fds);
+        }
+        self.raw_send_msg(bytes, fds)
+    }
+
+    pub(crate) fn raw_send_msg(&self, bytes: &[u8], fds: &[RawFd]) -> IoResult<usize> {
+        let stream = match self.stream {
+            Stream::Unix(ref stream) => stream,
+            Stream::Loopback(ref stream) => return stream.send_msg(bytes, fds),
+        };
         let flags = socket::MsgFlags::MSG_DONTWAIT | socket::MsgFlags::MSG_NOSIGNAL;
         let iov = [IoSlice::new(bytes)];
 
         if !fds.is_empty() {
             let cmsgs = [socket::ControlMessage::ScmRights(fds)];
-            Ok(socket::sendmsg::<()>(self.stream.as_raw_fd(), &iov, &cmsgs, flags, None)?)
+            Ok(socket::sendmsg::<()>(stream.as_raw_fd(), &iov, &cmsgs, flags, None)?)
         } else {
-            Ok(socket::sendmsg::<()>(self.stream.as_raw_fd(), &iov, &[], flags, None)?)
+            Ok(socket::sendmsg::<()>(stream.as_raw_fd(), &iov, &[], flags, None)?)
         }
     }
 
@@ -54,14 +81,33 @@ impl Socket {
     ///
     /// A single socket message can contain several wayland messages.
     ///
-    /// The `buffer` slice should be at least `MAX_BYTES_OUT` long and the `fds`
-    /// slice `MAX_FDS_OUT` long, otherwise some data of the received message may
+    /// The `buffer` slice should be at least as long as the socket messages of the peer and the
+    /// `fds` slice at least `MAX_FDS_OUT` long, otherwise some data of the received message may
     /// be lost.
     pub fn rcv_msg(&self, buffer: &mut [u8], fds: &mut [RawFd]) -> IoResult<(usize, usize)> {
-        let mut cmsg = nix::cmsg_space!([RawFd; MAX_FDS_OUT]);
+        #[cfg(any(test, feature = "fault_injection"))]
+        if let Some(ref faults) = self.faults {
+            return faults.lock().unwrap().rcv_msg(self, buffer, fds);
+        }
+        self.raw_rcv_msg(buffer, fds)
+    }
+
+    pub(crate) fn raw_rcv_msg(
+        &self,
+        buffer: &mut [u8],
+        fds: &mut [RawFd],
+    ) -> IoResult<(usize, usize)> {
+        let stream = match self.stream {
+            Stream::Unix(ref stream) => stream,
+            Stream::Loopback(ref stream) => return stream.rcv_msg(buffer, fds),
+        };
+        // room for all the file descriptors that fit in `fds`
+        let fd_space = std::mem::size_of_val(fds).max(std::mem::size_of::<[RawFd; MAX_FDS_OUT]>());
+        let cmsg_space = unsafe { socket::CMSG_SPACE(fd_space as socket::c_uint) } as usize;
+        let mut cmsg = Vec::<u8>::with_capacity(cmsg_space);
         let mut iov = [IoSliceMut::new(buffer)];
         let msg = socket::recvmsg::<()>(
-            self.stream.as_raw_fd(),
+            stream.as_raw_fd(),
             &mut iov[..],
             Some(&mut cmsg),
             socket::MsgFlags::MSG_DONTWAIT
@@ -78,28 +124,103 @@ impl Socket {
             fd_count += 1;
             *place = fd;
         }
+        if msg.flags.contains(socket::MsgFlags::MSG_CTRUNC) {
+            // some fds were dropped by the kernel, most likely because the fd table is full, and
+            // the messages they belong to can no longer be parsed
+            for &fd in &fds[..fd_count] {
+                let _ = nix::unistd::close(fd);
+            }
+            return Err(nix::errno::Errno::EOVERFLOW.into());
+        }
         Ok((msg.bytes, fd_count))
     }
+
+    /// Whether the other end read everything that was sent through this socket
+    ///
+    /// This is `None` if the platform does not tell.
+    pub fn is_read_by_peer(&self) -> Option<bool> {
+        match self.stream {
+            Stream::Unix(ref stream) => unix_is_read_by_peer(stream.as_raw_fd()),
+            Stream::Loopback(ref stream) => Some(stream.is_read_by_peer()),
+        }
+    }
+
+    /// Whether this socket is an in-memory connection
+    pub fn is_loopback(&self) -> bool {
+        matches!(self.stream, Stream::Loopback(_))
+    }
+
+    /// Wrap a stream into a socket injecting the faults described by `config`
+    #[cfg(any(test, feature = "fault_injection"))]
+    pub fn with_faults(stream: impl Into<Self>, config: FaultConfig) -> Self {
+        Self { faults: Some(std::sync::Mutex::new(Faults::new(config))), ..stream.into() }
+    }
+
+    #[cfg(any(test, feature = "fault_injection"))]
+    pub(crate) fn shutdown(&self) {
+        match self.stream {
+            Stream::Unix(ref stream) => {
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+            }
+            Stream::Loopback(ref stream) => stream.shutdown(),
+        }
+    }
 }
 
 impl From<UnixStream> for Socket {
     fn from(stream: UnixStream) -> Self {
-        Self { stream }
+        Self {
+            stream: Stream::Unix(stream),
+            #[cfg(any(test, feature = "fault_injection"))]
+            faults: None,
+        }
+    }
+}
+
+impl From<LoopbackStream> for Socket {
+    fn from(stream: LoopbackStream) -> Self {
+        Self {
+            stream: Stream::Loopback(stream),
+            #[cfg(any(test, feature = "fault_injection"))]
+            faults: None,
+        }
     }
 }
 
 impl AsFd for Socket {
     fn as_fd(&self) -> BorrowedFd<'_> {
-        self.stream.as_fd()
+        match self.stream {
+            Stream::Unix(ref stream) => stream.as_fd(),
+            Stream::Loopback(ref stream) => stream.as_fd(),
+        }
     }
 }
 
 impl AsRawFd for Socket {
     fn as_raw_fd(&self) -> RawFd {
-        self.stream.as_raw_fd()
+        self.as_fd().as_raw_fd()
     }
 }
 
+// On unix sockets, SIOCOUTQ (an alias of TIOCOUTQ) counts the memory of the messages the peer did
+// not receive yet, rather than of those the kernel did not send.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn unix_is_read_by_peer(fd: RawFd) -> Option<bool> {
+    use nix::libc;
+    let mut unread: libc::c_int = 0;
+    let ret = unsafe { libc::ioctl(fd, libc::TIOCOUTQ, &mut unread) };
+    if ret == 0 {
+        Some(unread == 0)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn unix_is_read_by_peer(_: RawFd) -> Option<bool> {
+    None
+}
+
 /*
  * BufferedSocket
  */
@@ -113,6 +234,29 @@ pub struct BufferedSocket {
     in_fds: Buffer<RawFd>,
     out_data: Buffer<u32>,
     out_fds: Buffer<RawFd>,
+    // the number of bytes of the first word of `out_data` that were already sent
+    out_partial: usize,
+    max_out_words: usize,
+    grow_before_flush: bool,
+    limits: MessageLimits,
+    // the number of words of a discarded message that were not received yet
+    in_skip: usize,
+    stats: IoStats,
+}
+
+/// Error generated when reading a message from a `BufferedSocket`
+#[derive(Debug)]
+pub enum ReadError {
+    /// The message cannot be parsed
+    Parse(MessageParseError),
+    /// The message exceeds the limits of the socket
+    TooLarge(MessageTooLarge),
+}
+
+impl From<MessageParseError> for ReadError {
+    fn from(e: MessageParseError) -> Self {
+        Self::Parse(e)
+    }
 }
 
 impl BufferedSocket {
@@ -124,9 +268,73 @@ impl BufferedSocket {
             in_fds: Buffer::new(2 * MAX_FDS_OUT),        // able to store leftover data if needed
             out_data: Buffer::new(MAX_BYTES_OUT / 4),
             out_fds: Buffer::new(MAX_FDS_OUT),
+            out_partial: 0,
+            max_out_words: MAX_BYTES_OUT / 4,
+            grow_before_flush: false,
+            limits: MessageLimits::default(),
+            in_skip: 0,
+            stats: IoStats::default(),
         }
     }
 
+    /// The counters of the I/O performed on this socket
+    ///
+    /// The `live_objects` counter is left to the owner of the socket.
+    pub fn stats(&self) -> IoStats {
+        let fds_in_flight = self.in_fds.get_contents().len() + self.out_fds.get_contents().len();
+        IoStats { fds_in_flight: fds_in_flight as u64, ..self.stats }
+    }
+
+    /// The underlying socket
+    pub fn socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Set the size in bytes up to which the outgoing buffer can grow when the socket is full
+    ///
+    /// It cannot be smaller than `MAX_BYTES_OUT` nor than the maximum message size. A buffer which
+    /// already grew larger is shrunk once flushed.
+    pub fn set_max_buffer_size(&mut self, size: usize) {
+        self.max_out_words = size.max(self.min_out_words() * 4) / 4;
+    }
+
+    /// Set the limits of the messages written to and read from this socket
+    ///
+    /// The incoming buffers grow to hold two messages of the maximum size, and the outgoing buffers
+    /// one. No buffer shrinks below its default size.
+    pub fn set_message_limits(&mut self, limits: MessageLimits) {
+        self.limits = MessageLimits { max_size: limits.max_size.min(MAX_MESSAGE_SIZE), ..limits };
+        let words = (self.limits.max_size + 3) / 4;
+        self.in_data.grow_to(2 * words);
+        self.in_fds.grow_to(2 * limits.max_fds);
+        self.out_data.grow_to(words);
+        self.out_fds.grow_to(limits.max_fds);
+        self.max_out_words = self.max_out_words.max(self.min_out_words());
+    }
+
+    // the size of the outgoing buffer it is shrunk back to
+    fn min_out_words(&self) -> usize {
+        (self.limits.max_size.max(MAX_BYTES_OUT) + 3) / 4
+    }
+
+    /// Grow the outgoing buffer when it is full, rather than flushing it
+    ///
+    /// The buffer then grows up to `MANUAL_FLUSH_BYTES`, and is only flushed when a message does not
+    /// fit in it anymore.
+    pub fn set_grow_before_flush(&mut self, grow: bool) {
+        self.grow_before_flush = grow;
+        self.max_out_words = if grow {
+            (MANUAL_FLUSH_BYTES / 4).max(self.min_out_words())
+        } else {
+            self.min_out_words()
+        };
+    }
+
+    /// Number of bytes waiting in the outgoing buffer
+    pub fn pending_bytes(&self) -> usize {
+        self.out_data.get_contents().len() * 4 - self.out_partial
+    }
+
     /// Flush the contents of the outgoing buffer into the socket
     pub fn flush(&mut self) -> IoResult<()> {
         let written = {
@@ -137,16 +345,27 @@ impl BufferedSocket {
             let bytes = unsafe {
                 ::std::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * 4)
             };
+            let bytes = &bytes[self.out_partial..];
             let fds = self.out_fds.get_contents();
+            self.stats.write_calls += 1;
             let written = self.socket.send_msg(bytes, fds)?;
+            self.stats.bytes_written += written as u64;
+            self.stats.fds_sent += fds.len() as u64;
             for &fd in fds {
                 // once the fds are sent, we can close them
                 let _ = ::nix::unistd::close(fd);
             }
             written
         };
+        // The socket can accept only a part of the data, which is routine once the buffer grew
+        // larger than the send buffer of the socket. The write can then end in the middle of a
+        // word, whose remaining bytes are sent first by the next flush.
+        let written = self.out_partial + written;
         self.out_data.offset(written / 4);
+        self.out_partial = written % 4;
         self.out_data.move_to_front();
+        self.out_data.shrink_to(self.max_out_words);
+        // the fds were all sent along with the first byte of data, even if the rest was not
         self.out_fds.clear();
         Ok(())
     }
@@ -178,10 +397,27 @@ impl BufferedSocket {
     ///
     /// This method may flush the internal buffer if necessary (if it is full).
     ///
-    /// If the message is too big to fit in the buffer, the error `Error::Sys(E2BIG)`
-    /// will be returned.
+    /// If the socket is full, the buffer grows up to the size set with `set_max_buffer_size()`.
+    /// If the message exceeds the limits set with `set_message_limits()` or does not fit in the
+    /// buffer, the error `Error::Sys(E2BIG)` will be returned.
     pub fn write_message(&mut self, msg: &Message<u32, RawFd>) -> IoResult<()> {
-        if !self.attempt_write_message(msg)? {
+        let (size, fds) = wire_size(msg);
+        if size > self.limits.max_size || fds > self.limits.max_fds {
+            return Err(::nix::errno::Errno::E2BIG.into());
+        }
+        let mut written = self.attempt_write_message(msg)?;
+        if !written && self.grow_before_flush {
+            // the file descriptors buffer does not grow
+            while !written
+                && self.out_data.storage.len() < self.max_out_words
+                && self.out_fds.get_writable_storage().len() >= fds
+            {
+                let size = self.out_data.storage.len();
+                self.out_data.grow_to((2 * size).min(self.max_out_words));
+                written = self.attempt_write_message(msg)?;
+            }
+        }
+        if !written {
             // the attempt failed, there is not enough space in the buffer
             // we need to flush it
             if let Err(e) = self.flush() {
@@ -189,12 +425,17 @@ impl BufferedSocket {
                     return Err(e);
                 }
             }
-            if !self.attempt_write_message(msg)? {
-                // If this fails again, this means the message is too big
-                // to be transmitted at all
-                return Err(::nix::errno::Errno::E2BIG.into());
+            while !self.attempt_write_message(msg)? {
+                let size = self.out_data.storage.len();
+                if size >= self.max_out_words {
+                    // If this fails again, this means the message is too big
+                    // to be transmitted at all, or the socket is full
+                    return Err(::nix::errno::Errno::E2BIG.into());
+                }
+                self.out_data.grow_to((2 * size).min(self.max_out_words));
             }
         }
+        self.stats.messages_sent += 1;
         Ok(())
     }
 
@@ -211,8 +452,11 @@ impl BufferedSocket {
                 ::std::slice::from_raw_parts_mut(words.as_ptr() as *mut u8, words.len() * 4)
             };
             let fds = self.in_fds.get_writable_storage();
+            self.stats.read_calls += 1;
             self.socket.rcv_msg(bytes, fds)?
         };
+        self.stats.bytes_read += in_bytes as u64;
+        self.stats.fds_received += in_fds as u64;
         if in_bytes == 0 {
             // the other end of the socket was closed
             return Err(::nix::errno::Errno::EPIPE.into());
@@ -220,6 +464,10 @@ impl BufferedSocket {
         // advance the storage
         self.in_data.advance(in_bytes / 4 + usize::from(in_bytes % 4 > 0));
         self.in_fds.advance(in_fds);
+        // drop the rest of a discarded message
+        let skipped = self.in_skip.min(self.in_data.get_contents().len());
+        self.in_data.offset(skipped);
+        self.in_skip -= skipped;
         Ok(())
     }
 
@@ -228,10 +476,13 @@ impl BufferedSocket {
     /// This method requires one closure that given an object id and an opcode,
     /// must provide the signature of the associated request/event, in the form of
     /// a `&'static [ArgumentType]`.
+    ///
+    /// A message exceeding the limits of the socket fails with `TooLarge` as soon as its header was
+    /// received, or once the whole message was if only its file descriptors exceed them.
     pub fn read_one_message<F>(
         &mut self,
         mut signature: F,
-    ) -> Result<Message<u32, OwnedFd>, MessageParseError>
+    ) -> Result<Message<u32, OwnedFd>, ReadError>
     where
         F: FnMut(u32, u16) -> Option<&'static [ArgumentType]>,
     {
@@ -239,28 +490,109 @@ impl BufferedSocket {
             let data = self.in_data.get_contents();
             let fds = self.in_fds.get_contents();
             if data.len() < 2 {
-                return Err(MessageParseError::MissingData);
+                return Err(MessageParseError::MissingData.into());
             }
             let object_id = data[0];
             let opcode = (data[1] & 0x0000_FFFF) as u16;
+            let size = (data[1] >> 16) as usize;
+            let too_large =
+                |fds| MessageTooLarge { object_id, opcode, size, fds, limits: self.limits };
+            if size > self.limits.max_size {
+                return Err(ReadError::TooLarge(too_large(0)));
+            }
             if let Some(sig) = signature(object_id, opcode) {
+                let sig_fds = sig.iter().filter(|&&arg| arg == ArgumentType::Fd).count();
+                if sig_fds > self.limits.max_fds {
+                    if data.len() * 4 < size {
+                        return Err(MessageParseError::MissingData.into());
+                    }
+                    return Err(ReadError::TooLarge(too_large(sig_fds)));
+                }
                 match parse_message(data, sig, fds) {
                     Ok((msg, rest_data, rest_fds)) => {
                         (msg, data.len() - rest_data.len(), fds.len() - rest_fds.len())
                     }
-                    Err(e) => return Err(e),
+                    Err(e) => return Err(e.into()),
                 }
             } else {
                 // no signature found ?
-                return Err(MessageParseError::Malformed);
+                return Err(MessageParseError::Malformed.into());
             }
         };
 
         self.in_data.offset(read_data);
         self.in_fds.offset(read_fd);
+        self.stats.messages_received += 1;
 
         Ok(msg)
     }
+
+    /// Get the object id, the opcode and the size in bytes of the next message of the incoming
+    /// buffers
+    ///
+    /// Returns `None` until the whole message was received, unless it exceeds the maximum message
+    /// size. The size is the one given by the header of the message, and may be invalid.
+    pub fn peek_message_header(&self) -> Option<(u32, u16, usize)> {
+        let data = self.in_data.get_contents();
+        if data.len() < 2 {
+            return None;
+        }
+        let size = (data[1] >> 16) as usize;
+        // a message larger than the limit is not waited for
+        if size <= self.limits.max_size && data.len() * 4 < size {
+            return None;
+        }
+        Some((data[0], (data[1] & 0x0000_FFFF) as u16, size))
+    }
+
+    /// Drop the contents of the incoming buffers, closing the file descriptors they hold
+    pub fn discard_incoming(&mut self) {
+        for &fd in self.in_fds.get_contents() {
+            let _ = ::nix::unistd::close(fd);
+        }
+        self.in_data.clear();
+        self.in_fds.clear();
+        self.in_skip = 0;
+    }
+
+    /// Discard the next message of the incoming buffers, without parsing it
+    ///
+    /// The file descriptors the message may carry are not discarded. Fails with `MissingData` until
+    /// the whole message was received, and with `Malformed` if its size is invalid. The part of a
+    /// message exceeding the maximum size that was not received yet is dropped as it arrives.
+    pub fn skip_one_message(&mut self) -> Result<(), MessageParseError> {
+        let (_, _, size) = self.peek_message_header().ok_or(MessageParseError::MissingData)?;
+        if size < 8 || size % 4 != 0 {
+            return Err(MessageParseError::Malformed);
+        }
+        let available = self.in_data.get_contents().len().min(size / 4);
+        self.in_data.offset(available);
+        self.in_skip = size / 4 - available;
+        self.stats.messages_received += 1;
+        Ok(())
+    }
+}
+
+/// Size in bytes and number of file descriptors of a message on the wire
+fn wire_size(msg: &Message<u32, RawFd>) -> (usize, usize) {
+    // length prefix and padded contents
+    let array_size = |len: usize| 4 + (len + 3) / 4 * 4;
+    msg.args.iter().fold((8, 0), |(size, fds), arg| match arg {
+        Argument::Str(Some(s)) => (size + array_size(s.as_bytes_with_nul().len()), fds),
+        Argument::Array(a) => (size + array_size(a.len()), fds),
+        Argument::Fd(_) => (size, fds + 1),
+        _ => (size + 4, fds),
+    })
+}
+
+impl Drop for BufferedSocket {
+    fn drop(&mut self) {
+        // the buffers own the file descriptors that were received but not yet read as part of a
+        // message, and those that were queued but not yet sent
+        for &fd in self.in_fds.get_contents().iter().chain(self.out_fds.get_contents()) {
+            let _ = ::nix::unistd::close(fd);
+        }
+    }
 }
 
 impl AsRawFd for BufferedSocket {
@@ -319,6 +651,21 @@ impl<T: Copy + Default> Buffer<T> {
         &mut self.storage[(self.occupied)..]
     }
 
+    /// Enlarge the storage of the buffer to `size`, keeping its contents
+    fn grow_to(&mut self, size: usize) {
+        if size > self.storage.len() {
+            self.storage.resize(size, T::default());
+        }
+    }
+
+    /// Shrink the storage of the buffer back to `size`, if its contents fit
+    fn shrink_to(&mut self, size: usize) {
+        if self.storage.len() > size && self.occupied <= size {
+            self.storage.truncate(size);
+            self.storage.shrink_to_fit();
+        }
+    }
+
     /// Move the unread contents of the buffer to the front, to ensure
     /// maximal write space availability
     fn move_to_front(&mut self) {
@@ -519,6 +866,174 @@ mod tests {
         }
     }
 
+    #[test]
+    fn unread_fds_closed_on_drop() {
+        let (pipe_read, pipe_write) =
+            ::nix::unistd::pipe2(::nix::fcntl::OFlag::O_CLOEXEC | ::nix::fcntl::OFlag::O_NONBLOCK)
+                .unwrap();
+        let msg = Message { sender_id: 42, opcode: 7, args: smallvec![Argument::Fd(pipe_write)] };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(Socket::from(client));
+        let mut server = BufferedSocket::new(Socket::from(server));
+
+        client.write_message(&msg).unwrap();
+        client.flush().unwrap();
+        ::nix::unistd::close(pipe_write).unwrap();
+
+        // the server receives the fd, but never reads the message
+        server.fill_incoming_buffers().unwrap();
+        drop(server);
+
+        // all the write ends of the pipe are closed
+        let mut buffer = [0u8; 1];
+        assert_eq!(::nix::unistd::read(pipe_read, &mut buffer), Ok(0));
+        ::nix::unistd::close(pipe_read).unwrap();
+    }
+
+    #[test]
+    fn fds_in_flight() {
+        let (pipe_read, pipe_write) = ::nix::unistd::pipe2(::nix::fcntl::OFlag::O_CLOEXEC).unwrap();
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: smallvec![Argument::Fd(pipe_read), Argument::Fd(pipe_write)],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(Socket::from(client));
+        let mut server = BufferedSocket::new(Socket::from(server));
+
+        client.write_message(&msg).unwrap();
+        ::nix::unistd::close(pipe_read).unwrap();
+        ::nix::unistd::close(pipe_write).unwrap();
+        assert_eq!(client.stats().fds_in_flight, 2);
+        client.flush().unwrap();
+        assert_eq!(client.stats().fds_in_flight, 0);
+        assert_eq!(client.stats().fds_sent, 2);
+
+        static SIGNATURE: &[ArgumentType] = &[ArgumentType::Fd, ArgumentType::Fd];
+
+        server.fill_incoming_buffers().unwrap();
+        assert_eq!(server.stats().fds_in_flight, 2);
+        let ret_msg =
+            server
+                .read_one_message(|sender_id, opcode| {
+                    if sender_id == 42 && opcode == 7 {
+                        Some(SIGNATURE)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+        assert_eq!(ret_msg.args.len(), 2);
+        assert_eq!(server.stats().fds_in_flight, 0);
+        assert_eq!(server.stats().fds_received, 2);
+    }
+
+    #[test]
+    fn out_buffer_grows_up_to_max_size() {
+        // 1012 bytes on the wire
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: smallvec![Argument::Array(vec![0; 1000].into())],
+        };
+
+        let (client, _server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        client.set_nonblocking(true).unwrap();
+        let mut client = BufferedSocket::new(Socket::from(client));
+
+        // the peer never reads, until both the socket and the buffer are full
+        while client.write_message(&msg).is_ok() {}
+        assert_eq!(
+            client.write_message(&msg).unwrap_err().raw_os_error(),
+            Some(::nix::errno::Errno::E2BIG as i32)
+        );
+
+        client.set_max_buffer_size(16 * MAX_BYTES_OUT);
+        let mut queued = 0;
+        while client.write_message(&msg).is_ok() {
+            queued += 1;
+        }
+        assert!((60..=64).contains(&queued));
+    }
+
+    #[test]
+    fn out_buffer_grows_before_flushing() {
+        // 1012 bytes on the wire
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: smallvec![Argument::Array(vec![0; 1000].into())],
+        };
+
+        let (client, _server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        client.set_nonblocking(true).unwrap();
+        let mut client = BufferedSocket::new(Socket::from(client));
+        client.set_grow_before_flush(true);
+
+        // nothing is written until the buffer reaches its maximum size
+        for _ in 0..100 {
+            client.write_message(&msg).unwrap();
+        }
+        assert_eq!(client.pending_bytes(), 100 * 1012);
+        assert_eq!(client.stats().write_calls, 0);
+        while client.stats().write_calls == 0 {
+            client.write_message(&msg).unwrap();
+        }
+        // the part of the buffer the socket accepted was written
+        assert!(client.pending_bytes() < MANUAL_FLUSH_BYTES);
+        assert!(client.stats().bytes_written > 0);
+    }
+
+    #[test]
+    fn message_limits_apply_both_ways() {
+        // 1012 bytes on the wire
+        let msg = Message {
+            sender_id: 42,
+            opcode: 7,
+            args: smallvec![Argument::Array(vec![0; 1000].into())],
+        };
+        let limits = MessageLimits { max_size: 1000, max_fds: 1 };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(Socket::from(client));
+        let mut server = BufferedSocket::new(Socket::from(server));
+
+        client.set_message_limits(limits);
+        assert_eq!(
+            client.write_message(&msg).unwrap_err().raw_os_error(),
+            Some(::nix::errno::Errno::E2BIG as i32)
+        );
+        let fds = message!(42, 0, [Argument::Fd(0), Argument::Fd(0)]);
+        assert_eq!(
+            client.write_message(&fds).unwrap_err().raw_os_error(),
+            Some(::nix::errno::Errno::E2BIG as i32)
+        );
+
+        client.set_message_limits(MessageLimits::default());
+        client.write_message(&msg).unwrap();
+        client.write_message(&message!(42, 1, [])).unwrap();
+        client.flush().unwrap();
+
+        static SIGNATURE: &[ArgumentType] = &[ArgumentType::Array];
+        server.set_message_limits(limits);
+        server.fill_incoming_buffers().unwrap();
+        match server.read_one_message(|_, _| Some(SIGNATURE)) {
+            Err(ReadError::TooLarge(e)) => {
+                assert_eq!((e.object_id, e.opcode, e.size, e.fds), (42, 7, 1012, 0));
+                assert_eq!(e.limits, limits);
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        // the next message is intact once the oversized one is discarded
+        server.skip_one_message().unwrap();
+        let ret_msg = server.read_one_message(|_, _| Some(&[])).unwrap();
+        assert_eq!((ret_msg.sender_id, ret_msg.opcode), (42, 1));
+    }
+
     #[test]
     fn parse_with_string_len_multiple_of_4() {
         let msg = Message {
@@ -556,4 +1071,39 @@ mod tests {
 
         assert_eq_msgs(&msg.map_fd(|fd| fd.as_raw_fd()), &ret_msg.map_fd(IntoRawFd::into_raw_fd));
     }
+
+    #[test]
+    fn flush_after_partial_word() {
+        let msg = Message {
+            sender_id: 3,
+            opcode: 1,
+            args: smallvec![Argument::Uint(7), Argument::Int(-1), Argument::Uint(0x1234_5678)],
+        };
+
+        let (client, server) = ::std::os::unix::net::UnixStream::pair().unwrap();
+        let mut client = BufferedSocket::new(Socket::from(client));
+        let mut server = BufferedSocket::new(Socket::from(server));
+
+        client.write_message(&msg).unwrap();
+        assert_eq!(client.pending_bytes(), 20);
+
+        // the socket accepted the first 6 bytes, ending in the middle of the second word
+        let words = client.out_data.get_contents();
+        let bytes = unsafe { ::std::slice::from_raw_parts(words.as_ptr() as *const u8, 6) };
+        assert_eq!(client.socket.send_msg(bytes, &[]).unwrap(), 6);
+        client.out_data.offset(1);
+        client.out_partial = 2;
+        assert_eq!(client.pending_bytes(), 14);
+
+        client.flush().unwrap();
+        assert_eq!(client.pending_bytes(), 0);
+
+        static SIGNATURE: &[ArgumentType] =
+            &[ArgumentType::Uint, ArgumentType::Int, ArgumentType::Uint];
+
+        server.fill_incoming_buffers().unwrap();
+        let ret_msg = server.read_one_message(|_, _| Some(SIGNATURE)).unwrap();
+
+        assert_eq_msgs(&msg.map_fd(|fd| fd.as_raw_fd()), &ret_msg.map_fd(IntoRawFd::into_raw_fd));
+    }
 }
@@ -1,13 +1,13 @@
 //! Types and routines used to manipulate arguments from the wire format
+//!
+//! The byte layout is implemented by `wayland-wire`, this module handles the ownership of the file
+//! descriptors on top of it.
 
-use std::os::unix::io::{BorrowedFd, OwnedFd};
-use std::os::unix::io::{FromRawFd, RawFd};
-use std::ptr;
-use std::{ffi::CStr, os::unix::prelude::AsRawFd};
+use std::os::unix::io::{BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
 
-use crate::protocol::{Argument, ArgumentType, Message};
+use crate::protocol::{ArgumentType, Message};
 
-use smallvec::SmallVec;
+pub use wayland_wire::MessageParseError;
 
 /// Error generated when trying to serialize a message into buffers
 #[derive(Debug)]
@@ -38,32 +38,6 @@ impl std::fmt::Display for MessageWriteError {
     }
 }
 
-/// Error generated when trying to deserialize a message from buffers
-#[derive(Debug, Clone)]
-pub enum MessageParseError {
-    /// The message references a FD but the buffer FD is empty
-    MissingFD,
-    /// More data is needed to deserialize the message
-    MissingData,
-    /// The message is malformed and cannot be parsed
-    Malformed,
-}
-
-impl std::error::Error for MessageParseError {}
-
-impl std::fmt::Display for MessageParseError {
-    #[cfg_attr(coverage, coverage(off))]
-    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
-        match *self {
-            Self::MissingFD => {
-                f.write_str("The message references a FD but the buffer FD is empty.")
-            }
-            Self::MissingData => f.write_str("More data is needed to deserialize the message"),
-            Self::Malformed => f.write_str("The message is malformed and cannot be parsed"),
-        }
-    }
-}
-
 /// Serialize the contents of this message into provided buffers
 ///
 /// Returns the number of elements written in each buffer
@@ -72,91 +46,26 @@ impl std::fmt::Display for MessageParseError {
 pub fn write_to_buffers(
     msg: &Message<u32, RawFd>,
     payload: &mut [u32],
-    mut fds: &mut [RawFd],
+    fds: &mut [RawFd],
 ) -> Result<(usize, usize), MessageWriteError> {
-    let orig_payload_len = payload.len();
-    let orig_fds_len = fds.len();
-    // Helper function to write a u32 or a RawFd to its buffer
-    fn write_buf<T>(u: T, payload: &mut [T]) -> Result<&mut [T], MessageWriteError> {
-        if let Some((head, tail)) = payload.split_first_mut() {
-            *head = u;
-            Ok(tail)
-        } else {
-            Err(MessageWriteError::BufferTooSmall)
-        }
-    }
-
-    // Helper function to write byte arrays in payload
-    fn write_array_to_payload<'a>(
-        array: &[u8],
-        payload: &'a mut [u32],
-    ) -> Result<&'a mut [u32], MessageWriteError> {
-        let array_len = array.len();
-        let word_len = array_len / 4 + usize::from(array_len % 4 != 0);
-        // need enough space to store the whole array with padding and a size header
-        if payload.len() < 1 + word_len {
-            return Err(MessageWriteError::BufferTooSmall);
-        }
-        // size header
-        payload[0] = array_len as u32;
-        let (buffer_slice, rest) = payload[1..].split_at_mut(word_len);
-        unsafe {
-            ptr::copy(array.as_ptr(), buffer_slice.as_mut_ptr() as *mut u8, array_len);
-        }
-        Ok(rest)
-    }
-
-    let free_size = payload.len();
-    if free_size < 2 {
-        return Err(MessageWriteError::BufferTooSmall);
-    }
-
-    let (header, mut payload) = payload.split_at_mut(2);
-
-    let mut pending_fds = Vec::new();
-
-    // write the contents in the buffer
-    for arg in &msg.args {
-        // Just to make the borrow checker happy
-        let old_payload = payload;
-        match *arg {
-            Argument::Int(i) => payload = write_buf(i as u32, old_payload)?,
-            Argument::Uint(u) => payload = write_buf(u, old_payload)?,
-            Argument::Fixed(f) => payload = write_buf(f as u32, old_payload)?,
-            Argument::Str(Some(ref s)) => {
-                payload = write_array_to_payload(s.as_bytes_with_nul(), old_payload)?;
-            }
-            Argument::Str(None) => {
-                payload = write_array_to_payload(&[], old_payload)?;
-            }
-            Argument::Object(o) => payload = write_buf(o, old_payload)?,
-            Argument::NewId(n) => payload = write_buf(n, old_payload)?,
-            Argument::Array(ref a) => {
-                payload = write_array_to_payload(a, old_payload)?;
-            }
-            Argument::Fd(fd) => {
-                let old_fds = fds;
-                let dup_fd = unsafe { BorrowedFd::borrow_raw(fd) }
-                    .try_clone_to_owned()
-                    .map_err(MessageWriteError::DupFdFailed)?;
-                let raw_dup_fd = dup_fd.as_raw_fd();
-                pending_fds.push(dup_fd);
-                fds = write_buf(raw_dup_fd, old_fds)?;
-                payload = old_payload;
+    let (written, fd_count) = wayland_wire::write_to_buffers(msg, payload, fds)
+        .map_err(|wayland_wire::BufferTooSmall| MessageWriteError::BufferTooSmall)?;
+
+    // replace the written fds by their duplicates, sendmsg will take ownership of them
+    let fds = &mut fds[..fd_count];
+    for i in 0..fds.len() {
+        match unsafe { BorrowedFd::borrow_raw(fds[i]) }.try_clone_to_owned() {
+            Ok(dup_fd) => fds[i] = dup_fd.into_raw_fd(),
+            Err(e) => {
+                // close the duplicates made so far
+                for &fd in &fds[..i] {
+                    drop(unsafe { OwnedFd::from_raw_fd(fd) });
+                }
+                return Err(MessageWriteError::DupFdFailed(e));
             }
         }
     }
-
-    // if we reached here, the whole message was written successfully, we can drop the pending_fds they
-    // don't need to be closed, sendmsg will take ownership of them
-    for fd in pending_fds {
-        std::mem::forget(fd);
-    }
-
-    let wrote_size = (free_size - payload.len()) * 4;
-    header[0] = msg.sender_id;
-    header[1] = ((wrote_size as u32) << 16) | u32::from(msg.opcode);
-    Ok((orig_payload_len - payload.len(), orig_fds_len - fds.len()))
+    Ok((written, fd_count))
 }
 
 /// Attempts to parse a single wayland message with the given signature.
@@ -165,6 +74,8 @@ pub fn write_to_buffers(
 /// and the unused tail of the buffers is returned. If a single message was present,
 /// the returned slices should thus be empty.
 ///
+/// The file descriptors of the message are taken ownership of.
+///
 /// Errors if the message is malformed.
 #[allow(clippy::type_complexity)]
 pub fn parse_message<'a, 'b>(
@@ -172,98 +83,92 @@ pub fn parse_message<'a, 'b>(
     signature: &[ArgumentType],
     fds: &'b [RawFd],
 ) -> Result<(Message<u32, OwnedFd>, &'a [u32], &'b [RawFd]), MessageParseError> {
-    // helper function to read arrays
-    fn read_array_from_payload(
-        array_len: usize,
-        payload: &[u32],
-    ) -> Result<(&[u8], &[u32]), MessageParseError> {
-        let word_len = array_len / 4 + usize::from(array_len % 4 != 0);
-        if word_len > payload.len() {
-            return Err(MessageParseError::MissingData);
-        }
-        let (array_contents, rest) = payload.split_at(word_len);
-        let array = unsafe {
-            ::std::slice::from_raw_parts(array_contents.as_ptr() as *const u8, array_len)
-        };
-        Ok((array, rest))
-    }
-
-    if raw.len() < 2 {
-        return Err(MessageParseError::MissingData);
-    }
-
-    let sender_id = raw[0];
-    let word_2 = raw[1];
-    let opcode = (word_2 & 0x0000_FFFF) as u16;
-    let len = (word_2 >> 16) as usize / 4;
-
-    if len < 2 {
-        return Err(MessageParseError::Malformed);
-    } else if len > raw.len() {
-        return Err(MessageParseError::MissingData);
-    }
+    let (msg, rest, fds) = wayland_wire::parse_message(raw, signature, fds)?;
+    Ok((msg.map_fd(|fd| unsafe { OwnedFd::from_raw_fd(fd) }), rest, fds))
+}
 
-    let (mut payload, rest) = raw.split_at(len);
-    payload = &payload[2..];
-    let mut fds = fds;
+/// Generate a signature from arbitrary bytes, consuming them
+///
+/// Once `data` is exhausted, the generated values are all zeroes.
+#[cfg(any(test, feature = "fuzzing"))]
+pub(crate) fn arbitrary_signature(data: &mut &[u8]) -> Vec<ArgumentType> {
+    use crate::protocol::AllowNull;
+    let len = take_byte(data) % 8;
+    (0..len)
+        .map(|_| match take_byte(data) % 8 {
+            0 => ArgumentType::Int,
+            1 => ArgumentType::Uint,
+            2 => ArgumentType::Fixed,
+            3 => ArgumentType::Str(AllowNull::Yes),
+            4 => ArgumentType::Object(AllowNull::Yes),
+            5 => ArgumentType::NewId,
+            6 => ArgumentType::Array,
+            _ => ArgumentType::Fd,
+        })
+        .collect()
+}
 
-    let arguments = signature
+/// Generate a message respecting `signature` from arbitrary bytes, consuming them
+///
+/// All the file descriptor arguments are `fd`. Once `data` is exhausted, the generated values are all
+/// zeroes.
+#[cfg(any(test, feature = "fuzzing"))]
+pub(crate) fn arbitrary_message(
+    data: &mut &[u8],
+    signature: &[ArgumentType],
+    fd: RawFd,
+) -> Message<u32, RawFd> {
+    use crate::protocol::Argument;
+    use std::ffi::CString;
+    let sender_id = take_u32(data);
+    let opcode = take_u32(data) as u16;
+    let args = signature
         .iter()
-        .map(|argtype| {
-            if let ArgumentType::Fd = *argtype {
-                // don't consume input but fd
-                if let Some((&front, tail)) = fds.split_first() {
-                    fds = tail;
-                    Ok(Argument::Fd(unsafe { OwnedFd::from_raw_fd(front) }))
-                } else {
-                    Err(MessageParseError::MissingFD)
+        .map(|kind| match kind {
+            ArgumentType::Int => Argument::Int(take_u32(data) as i32),
+            ArgumentType::Uint => Argument::Uint(take_u32(data)),
+            ArgumentType::Fixed => Argument::Fixed(take_u32(data) as i32),
+            ArgumentType::Str(_) => match take_byte(data) {
+                0 => Argument::Str(None),
+                len => {
+                    let bytes = (0..len % 64).map(|_| take_byte(data).max(1)).collect::<Vec<_>>();
+                    Argument::Str(Some(Box::new(CString::new(bytes).unwrap())))
                 }
-            } else if let Some((&front, mut tail)) = payload.split_first() {
-                let arg = match *argtype {
-                    ArgumentType::Int => Ok(Argument::Int(front as i32)),
-                    ArgumentType::Uint => Ok(Argument::Uint(front)),
-                    ArgumentType::Fixed => Ok(Argument::Fixed(front as i32)),
-                    ArgumentType::Str(_) => {
-                        read_array_from_payload(front as usize, tail).and_then(|(v, rest)| {
-                            tail = rest;
-                            if !v.is_empty() {
-                                match CStr::from_bytes_with_nul(v) {
-                                    Ok(s) => Ok(Argument::Str(Some(Box::new(s.into())))),
-                                    Err(_) => Err(MessageParseError::Malformed),
-                                }
-                            } else {
-                                Ok(Argument::Str(None))
-                            }
-                        })
-                    }
-                    ArgumentType::Object(_) => Ok(Argument::Object(front)),
-                    ArgumentType::NewId => Ok(Argument::NewId(front)),
-                    ArgumentType::Array => {
-                        read_array_from_payload(front as usize, tail).map(|(v, rest)| {
-                            tail = rest;
-                            Argument::Array(Box::new(v.into()))
-                        })
-                    }
-                    ArgumentType::Fd => unreachable!(),
-                };
-                payload = tail;
-                arg
-            } else {
-                Err(MessageParseError::MissingData)
+            },
+            ArgumentType::Object(_) => Argument::Object(take_u32(data)),
+            ArgumentType::NewId => Argument::NewId(take_u32(data)),
+            ArgumentType::Array => {
+                let len = take_byte(data);
+                Argument::Array(Box::new((0..len).map(|_| take_byte(data)).collect()))
             }
+            ArgumentType::Fd => Argument::Fd(fd),
         })
-        .collect::<Result<SmallVec<_>, MessageParseError>>()?;
+        .collect();
+    Message { sender_id, opcode, args }
+}
 
-    let msg = Message { sender_id, opcode, args: arguments };
-    Ok((msg, rest, fds))
+#[cfg(any(test, feature = "fuzzing"))]
+fn take_byte(data: &mut &[u8]) -> u8 {
+    match data.split_first() {
+        Some((&byte, rest)) => {
+            *data = rest;
+            byte
+        }
+        None => 0,
+    }
+}
+
+#[cfg(any(test, feature = "fuzzing"))]
+fn take_u32(data: &mut &[u8]) -> u32 {
+    u32::from_le_bytes([take_byte(data), take_byte(data), take_byte(data), take_byte(data)])
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::protocol::AllowNull;
+    use crate::protocol::{AllowNull, Argument};
     use smallvec::smallvec;
-    use std::{ffi::CString, os::unix::io::IntoRawFd};
+    use std::ffi::CString;
 
     #[test]
     fn into_from_raw_cycle() {
@@ -302,4 +207,44 @@ mod tests {
         .unwrap();
         assert_eq!(rebuilt.map_fd(IntoRawFd::into_raw_fd), msg);
     }
+
+    // Random messages survive a round-trip through the wire format, along with the size of the
+    // message and the padding of strings and arrays
+    #[test]
+    fn random_messages_cycle() {
+        // xorshift, seeded for reproducibility
+        let mut state = 0x2545_f491_u32;
+        let mut random_bytes = |len: usize| {
+            (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut bytes_buffer = vec![0; 1024];
+        let mut fd_buffer = [0; 10];
+        for _ in 0..1000 {
+            let bytes = random_bytes(512);
+            let mut data = &bytes[..];
+            let signature = arbitrary_signature(&mut data);
+            let msg = arbitrary_message(&mut data, &signature, 0);
+
+            let (written, fd_count) =
+                write_to_buffers(&msg, &mut bytes_buffer[..], &mut fd_buffer[..]).unwrap();
+            let len = (bytes_buffer[1] >> 16) as usize;
+            assert_eq!(len, written * 4);
+
+            let (rebuilt, rest, fds) =
+                parse_message(&bytes_buffer[..written], &signature, &fd_buffer[..fd_count])
+                    .unwrap();
+            assert!(rest.is_empty());
+            assert!(fds.is_empty());
+            // the file descriptors were duplicated
+            assert_eq!(rebuilt.map_fd(|_| 0), msg);
+        }
+    }
 }
@@ -4,10 +4,18 @@ use std::{
     os::unix::io::{BorrowedFd, OwnedFd},
     os::unix::{io::RawFd, net::UnixStream},
     sync::Arc,
+    time::Duration,
 };
 
-use crate::protocol::{Interface, Message, ObjectInfo};
-pub use crate::types::server::{Credentials, DisconnectReason, GlobalInfo, InitError, InvalidId};
+use crate::clock::{Clock, SystemClock};
+use crate::protocol::{
+    Direction, IdReusePolicy, Interface, IoStats, Message, MessageLimits, Metrics, ObjectInfo,
+};
+use crate::types::server::DrainCallback;
+pub use crate::types::server::{
+    Credentials, DestroyListenerId, DisconnectReason, GlobalInfo, InitError, InvalidId, PauseError,
+};
+pub use crate::types::wakeup::WakeupHandle;
 
 use super::server_impl;
 
@@ -31,6 +39,12 @@ pub trait ObjectData<D>: downcast_rs::DowncastSync {
         msg: Message<ObjectId, OwnedFd>,
     ) -> Option<Arc<dyn ObjectData<D>>>;
     /// Notification that the object has been destroyed and is no longer active
+    ///
+    /// When a client is disconnected, the destructors of its remaining objects are invoked in the reverse
+    /// order of their creation, so that an object is always destroyed before the one it was created from
+    /// (a `wl_subsurface` before its `wl_surface`). The system backend can only approximate this order by
+    /// decreasing protocol id, and only if the client is disconnected by the compositor: libwayland
+    /// destroys the objects by increasing id if the client closed its connection.
     fn destroyed(
         self: Arc<Self>,
         handle: &Handle,
@@ -128,6 +142,258 @@ impl std::fmt::Debug for dyn ClientData {
 
 impl ClientData for () {}
 
+/// A hook receiving the messages exchanged with the clients
+///
+/// See [`Handle::set_protocol_logger()`]. It is implemented for the closures with the same
+/// arguments as [`log()`](ProtocolLogger::log).
+pub trait ProtocolLogger: Send + Sync {
+    /// A message was received from or is about to be sent to a client
+    ///
+    /// The message describes objects by their protocol id, and its file descriptors are only valid
+    /// during this call. The `interface` is the one of the object sending or receiving the message.
+    /// [`DisplayMessage`](crate::protocol::DisplayMessage) formats the message like the
+    /// `WAYLAND_DEBUG` output.
+    fn log(
+        &self,
+        client: ClientId,
+        direction: Direction,
+        interface: &'static Interface,
+        message: &Message<u32, RawFd>,
+    );
+}
+
+impl<F> ProtocolLogger for F
+where
+    F: Fn(ClientId, Direction, &'static Interface, &Message<u32, RawFd>) + Send + Sync,
+{
+    fn log(
+        &self,
+        client: ClientId,
+        direction: Direction,
+        interface: &'static Interface,
+        message: &Message<u32, RawFd>,
+    ) {
+        self(client, direction, interface, message)
+    }
+}
+
+impl std::fmt::Debug for dyn ProtocolLogger {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolLogger").finish_non_exhaustive()
+    }
+}
+
+/// What to do with an event that does not fit in the outgoing buffer of a client
+///
+/// When a client does not read its socket fast enough, the events sent to it pile up in its
+/// outgoing buffer, up to the size set with [`Handle::set_client_max_buffer_size()`]. Past it,
+/// the policy of the client decides whether the event is discarded or the client disconnected.
+///
+/// Only the events that neither create nor destroy an object and carry no file descriptor can be
+/// discarded without breaking the protocol state shared with the client: the others always
+/// disconnect it.
+#[derive(Clone, Default)]
+pub enum OverflowPolicy {
+    /// Disconnect the client, the default
+    #[default]
+    Kill,
+    /// Discard the events that can be, like the input events of a frame
+    DropFrameEvents,
+    /// Let a callback decide, given the interface of the object sending the event and its
+    /// opcode
+    ///
+    /// The event is discarded if it returns `true`, and the client disconnected otherwise. It is
+    /// called with an internal lock of the backend held, and must not call back into it.
+    Callback(Arc<dyn Fn(ClientId, &'static Interface, u16) -> bool + Send + Sync>),
+}
+
+impl fmt::Debug for OverflowPolicy {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverflowPolicy::Kill => f.write_str("Kill"),
+            OverflowPolicy::DropFrameEvents => f.write_str("DropFrameEvents"),
+            OverflowPolicy::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+type ExceededCallback = Arc<dyn Fn(&Handle, ClientId) + Send + Sync>;
+
+/// Limits on the requests dispatched for each client, against the clients flooding the server
+///
+/// At most [`max_requests`](RateLimit::max_requests) requests of a client are dispatched per
+/// dispatch cycle, that is per call to [`Backend::dispatch_all_clients()`] or
+/// [`Backend::dispatch_single_client()`]. The others are deferred to the next cycle, the
+/// [`poll_fd()`](Backend::poll_fd) of the backend remaining readable until they are dispatched,
+/// so that a single client cannot starve the others and the event loop of the server.
+///
+/// A client sending more than [`threshold`](RateLimit::threshold) requests within a
+/// [`window`](RateLimit::window) is additionally reported to the
+/// [`on_exceeded`](RateLimit::on_exceeded) callback, once per window, for the server to decide
+/// what to do with it. Only the rust backend enforces these limits, libwayland dispatches all the
+/// requests it reads with the system backend.
+#[derive(Clone)]
+pub struct RateLimit {
+    /// Number of requests dispatched for each client per dispatch cycle
+    pub max_requests: usize,
+    /// Number of requests within a window above which a client is reported
+    pub threshold: u64,
+    /// Length of the windows over which the requests are counted
+    pub window: Duration,
+    /// Invoked with the clients exceeding the threshold, after the dispatch cycle
+    pub on_exceeded: Option<ExceededCallback>,
+    /// The clock measuring the windows
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_requests: 1000,
+            threshold: 100_000,
+            window: Duration::from_secs(1),
+            on_exceeded: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl fmt::Debug for RateLimit {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("max_requests", &self.max_requests)
+            .field("threshold", &self.threshold)
+            .field("window", &self.window)
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why a request received from a client could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MalformedReason {
+    /// The client has no object with this id
+    UnknownObject,
+    /// The interface of the object has no request with this opcode
+    UnknownOpcode,
+    /// The content of the request does not match its signature
+    InvalidArguments,
+    /// The request exceeds the message limits of the server, see [`Handle::set_message_limits()`]
+    ///
+    /// The request is handled as soon as its header is received if its size exceeds the limit.
+    TooLarge,
+}
+
+/// A request received from a client that could not be decoded
+///
+/// See [`MalformedRequestPolicy`].
+#[derive(Debug, Clone)]
+pub struct MalformedRequest {
+    /// Protocol id of the object the request was sent to
+    pub object_id: u32,
+    /// Interface of this object, `None` if the client has no such object
+    pub interface: Option<&'static Interface>,
+    /// Opcode of the request
+    pub opcode: u16,
+    /// Size of the request in bytes, header included
+    pub size: usize,
+    /// Why the request could not be decoded
+    pub reason: MalformedReason,
+}
+
+type MalformedCallback = Arc<dyn Fn(ClientId, &MalformedRequest) -> bool + Send + Sync>;
+
+/// What to do with a request of a client that could not be decoded
+///
+/// By default the client is disconnected, with a `wl_display.error` describing the request like
+/// libwayland does. A callback can instead discard some of these requests, for example to count
+/// and log the requests of a client known to send harmless garbage without losing it.
+///
+/// The request is skipped according to the size given in its header. The file descriptors it may
+/// carry cannot be identified without its signature, and stay in the incoming buffer where the
+/// following requests will consume them: only the requests known not to carry any should be
+/// discarded. The requests whose header is invalid always disconnect the client.
+#[derive(Clone, Default)]
+pub enum MalformedRequestPolicy {
+    /// Disconnect the client, the default
+    #[default]
+    Kill,
+    /// Let a callback decide, given the client and the request
+    ///
+    /// The request is discarded if it returns `true`, and the client disconnected otherwise. It is
+    /// called with an internal lock of the backend held, and must not call back into it.
+    Callback(MalformedCallback),
+}
+
+impl fmt::Debug for MalformedRequestPolicy {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MalformedRequestPolicy::Kill => f.write_str("Kill"),
+            MalformedRequestPolicy::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+/// What to do with a client that the backend is about to disconnect
+///
+/// See [`KillPolicy`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KillDecision {
+    /// Disconnect the client right away
+    Kill,
+    /// Disconnect the client once the events already queued for it are written to its socket
+    ///
+    /// Its requests are ignored and the new events sent to it are dropped in the meantime. The
+    /// queued events are written as the backend is flushed.
+    Defer,
+    /// Keep the client connected
+    Keep,
+}
+
+type KillCallback = Arc<dyn Fn(ClientId, &DisconnectReason) -> KillDecision + Send + Sync>;
+
+/// Whether the backend disconnects the clients it runs into trouble with
+///
+/// The backend disconnects a client by itself for one of these reasons:
+///
+/// - [`DisconnectReason::ProtocolError`]: a protocol error was posted to the client. If it is kept,
+///   the error is not sent to it and the request that caused it is ignored.
+/// - [`DisconnectReason::InvalidMessage`]: a request of the client could not be decoded, and the
+///   [`MalformedRequestPolicy`] did not discard it. If it is kept, the request is discarded, unless its
+///   header is invalid, which disconnects the client anyway.
+/// - [`DisconnectReason::BufferOverflow`]: an event did not fit in the outgoing buffer of the client,
+///   and its [`OverflowPolicy`] did not discard it. If it is kept, the event is lost, which breaks the
+///   protocol state shared with the client if it creates or destroys an object.
+///
+/// By default the client is disconnected right away, like libwayland does. A callback can instead
+/// defer the disconnection, for example to make sure that the client receives the error it was sent,
+/// or keep the client. The other disconnections, like the ones requested with
+/// [`Handle::kill_client()`], do not go through this policy.
+#[derive(Clone, Default)]
+pub enum KillPolicy {
+    /// Disconnect the client right away, the default
+    #[default]
+    Kill,
+    /// Let a callback decide, given the client and the reason of the disconnection
+    ///
+    /// It is called with an internal lock of the backend held, and must not call back into it.
+    Callback(KillCallback),
+}
+
+impl fmt::Debug for KillPolicy {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KillPolicy::Kill => f.write_str("Kill"),
+            KillPolicy::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
 downcast_rs::impl_downcast!(sync ClientData);
 
 /// An ID representing a Wayland object
@@ -206,6 +472,13 @@ pub struct ClientId {
     pub(crate) id: server_impl::InnerClientId,
 }
 
+impl ClientId {
+    /// A number identifying the client among those connected, for the traces
+    pub(crate) fn as_u64(&self) -> u64 {
+        self.id.as_u64()
+    }
+}
+
 impl fmt::Debug for ClientId {
     #[cfg_attr(coverage, coverage(off))]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -298,6 +571,137 @@ impl Handle {
         self.handle.get_client_credentials(id.id)
     }
 
+    /// Retrieve the security label of a client
+    ///
+    /// This is the context given to the client process by the security module of the system, like
+    /// SELinux or AppArmor, as reported by `SO_PEERSEC`. It is `None` if no such module labels
+    /// sockets, or on platforms other than linux.
+    #[inline]
+    pub fn get_client_security_label(&self, id: ClientId) -> Result<Option<String>, InvalidId> {
+        self.handle.get_client_security_label(id.id)
+    }
+
+    /// Set the size in bytes up to which the outgoing buffer of a client can grow
+    ///
+    /// The buffer grows when the client does not read the events as fast as they are sent, and
+    /// the [`OverflowPolicy`] of the client applies to the events which do not fit once it reached
+    /// this size. The default and minimum size is 4096 bytes, the size of the largest message.
+    ///
+    /// It has no effect with the system backend, for which `libwayland-server.so` manages the
+    /// buffers.
+    #[inline]
+    pub fn set_client_max_buffer_size(&self, id: ClientId, size: usize) -> Result<(), InvalidId> {
+        self.handle.set_client_max_buffer_size(id.id, size)
+    }
+
+    /// Set the policy for the events not fitting in the outgoing buffer of a client
+    ///
+    /// It has no effect with the system backend, which always disconnects the client.
+    #[inline]
+    pub fn set_client_overflow_policy(
+        &self,
+        id: ClientId,
+        policy: OverflowPolicy,
+    ) -> Result<(), InvalidId> {
+        self.handle.set_client_overflow_policy(id.id, policy)
+    }
+
+    /// Start counting the messages sent to and received from each client, by interface
+    ///
+    /// This applies to the clients already connected, whose counters start from zero, as well as to the
+    /// clients that will connect later. Further calls have no effect.
+    #[inline]
+    pub fn enable_metrics(&self) {
+        self.handle.enable_metrics()
+    }
+
+    /// Set the policy for reusing the ids of the objects destroyed from now on
+    ///
+    /// This applies to the clients already connected as well as to the clients that will connect
+    /// later. It has no effect with the system backend, whose ids are allocated by
+    /// `libwayland-server.so`.
+    #[inline]
+    pub fn set_id_reuse_policy(&self, policy: IdReusePolicy) {
+        self.handle.set_id_reuse_policy(policy)
+    }
+
+    /// Limit the requests dispatched for each client, or remove the limits with `None`
+    ///
+    /// See [`RateLimit`]. It has no effect with the system backend.
+    #[inline]
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.handle.set_rate_limit(limit)
+    }
+
+    /// Set the hook receiving every message exchanged with the clients, or remove it with `None`
+    ///
+    /// The logger is given the requests before they are dispatched and the events before they are
+    /// sent, in the same spirit as `wl_display_add_protocol_logger()` of libwayland, from the thread
+    /// dispatching or sending them. It must not call back into the backend.
+    ///
+    /// With the system backend, the messages processed by `libwayland-server.so` itself, like those of
+    /// the `wl_display` and the `wl_registry`, are not given to the logger.
+    #[inline]
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.handle.set_protocol_logger(logger)
+    }
+
+    /// Set the policy for the requests of the clients that cannot be decoded
+    ///
+    /// See [`MalformedRequestPolicy`]. It applies to the clients already connected as well as to the
+    /// clients that will connect later. It has no effect with the system backend, for which
+    /// `libwayland-server.so` always disconnects the client.
+    #[inline]
+    pub fn set_malformed_request_policy(&self, policy: MalformedRequestPolicy) {
+        self.handle.set_malformed_request_policy(policy)
+    }
+
+    /// Set the policy for the disconnections of the clients decided by the backend
+    ///
+    /// See [`KillPolicy`]. It applies to the clients already connected as well as to the clients that
+    /// will connect later. It has no effect with the system backend, for which `libwayland-server.so`
+    /// always disconnects the client.
+    #[inline]
+    pub fn set_kill_policy(&self, policy: KillPolicy) {
+        self.handle.set_kill_policy(policy)
+    }
+
+    /// Set the limits on the size and file descriptors of the messages exchanged with the clients
+    ///
+    /// A request exceeding them is a malformed request with the [`MalformedReason::TooLarge`] reason,
+    /// handled by the [`MalformedRequestPolicy`]. Sending an event exceeding them fails like sending it
+    /// to a client whose buffer is full, according to the overflow policy of the client. The clients must
+    /// accept messages as large as the ones they are sent.
+    ///
+    /// This applies to the clients already connected as well as to the clients that will connect later.
+    /// It has no effect with the system backend, whose limits are fixed.
+    #[inline]
+    pub fn set_message_limits(&self, limits: MessageLimits) {
+        self.handle.set_message_limits(limits)
+    }
+
+    /// Get the current value of the message counters of a client
+    ///
+    /// The returned counters are empty if [`enable_metrics()`](Handle::enable_metrics) was not called.
+    #[inline]
+    pub fn get_client_metrics(&self, id: ClientId) -> Result<Metrics, InvalidId> {
+        self.handle.get_client_metrics(id.id)
+    }
+
+    /// Get the I/O counters of a client
+    ///
+    /// Unlike the message counters, these are always maintained.
+    #[inline]
+    pub fn get_client_io_stats(&self, id: ClientId) -> Result<IoStats, InvalidId> {
+        self.handle.get_client_io_stats(id.id)
+    }
+
+    /// Get the sum of the I/O counters of the clients currently connected
+    #[inline]
+    pub fn io_stats(&self) -> IoStats {
+        self.handle.io_stats()
+    }
+
     /// Invokes a closure for all clients connected to this server
     ///
     /// Note that while this method is running, an internal lock of the backend is held,
@@ -411,6 +815,36 @@ impl Handle {
         self.handle.post_error(object_id.id, error_code, message)
     }
 
+    /// Pause or resume the dispatch of the requests of an object
+    ///
+    /// While an object is paused, its requests are queued instead of being given to its [`ObjectData`].
+    /// They are dispatched in order once it is resumed, by the next dispatch of the clients. To keep the
+    /// order of the requests, the requests creating objects, or taking them as arguments, are queued as
+    /// well, until the queued requests they depend on are dispatched. This does not pause the
+    /// `wl_display` and `wl_registry` objects, nor the requests of the objects which don't involve a
+    /// paused object.
+    ///
+    /// At most 4096 requests of a client can be queued. A client exceeding this limit gets a `no_memory`
+    /// protocol error, and is disconnected according to the [`KillPolicy`].
+    ///
+    /// It is for example useful to hold the commits of a surface back while a transaction is pending.
+    ///
+    /// Pausing is not supported by the system backend, where the requests are always dispatched as they
+    /// are read: it returns [`PauseError::Unsupported`] for live objects.
+    #[inline]
+    pub fn set_object_paused(&self, id: ObjectId, paused: bool) -> Result<(), PauseError> {
+        self.handle.set_object_paused(id.id, paused)
+    }
+
+    /// Posts an implementation error on the `wl_display` of a client, disconnecting it.
+    ///
+    /// This is meant for errors that cannot be attributed to a specific object of the client, it is
+    /// the equivalent of `wl_client_post_implementation_error` in libwayland.
+    #[inline]
+    pub fn post_implementation_error(&self, client_id: ClientId, message: CString) {
+        self.handle.post_implementation_error(client_id.id, message)
+    }
+
     /// Kills the connection to a client.
     ///
     /// The disconnection reason determines the error message that is sent to the client (if any).
@@ -471,12 +905,82 @@ impl Handle {
         self.handle.remove_global::<D>(id.id)
     }
 
+    /// Disable a global now, and remove it once a grace period is over
+    ///
+    /// This is the combination of [`disable_global()`](Handle::disable_global) and
+    /// [`remove_global()`](Handle::remove_global) recommended to remove globals without racing
+    /// clients: the removal is signaled to the clients right away, but the global remains bindable
+    /// during the `grace` period. It is then removed by the first dispatch of the clients after the
+    /// grace period, which invokes `finalize` to free the associated resources. A timer of the event
+    /// loop of the server can ensure that a dispatch happens on time.
+    ///
+    /// **Panic:** This method will panic if the type parameter `D` is not same to the same type as the
+    /// one the backend was initialized with.
+    pub fn remove_global_after<D: 'static>(
+        &self,
+        id: GlobalId,
+        grace: Duration,
+        finalize: impl FnOnce(&Handle, &mut D) + Send + 'static,
+    ) {
+        self.handle.remove_global_after::<D>(id.id, grace, Box::new(finalize))
+    }
+
     /// Returns information about a global.
     #[inline]
     pub fn global_info(&self, id: GlobalId) -> Result<GlobalInfo, InvalidId> {
         self.handle.global_info(id.id)
     }
 
+    /// Register a callback invoked once a client has been destroyed
+    ///
+    /// Unlike [`ClientData::disconnected()`], the callback is invoked with the state of the
+    /// compositor, during the dispatch following the destruction of the client, and after the
+    /// destructors of all its objects. It can thus clean up the per-client state that those
+    /// destructors might still use. Several listeners of a client are invoked in the order they
+    /// were added, and are all given the reason of the disconnection.
+    ///
+    /// The returned id can be given to
+    /// [`remove_client_destroy_listener()`](Handle::remove_client_destroy_listener) to unregister
+    /// the callback. Fails if the client is no longer alive.
+    ///
+    /// **Panic:** This method will panic if the type parameter `D` is not same to the same type as the
+    /// one the backend was initialized with.
+    pub fn add_client_destroy_listener<D: 'static>(
+        &self,
+        client_id: ClientId,
+        listener: impl FnOnce(&Handle, &mut D, ClientId, DisconnectReason) + Send + 'static,
+    ) -> Result<DestroyListenerId, InvalidId> {
+        self.handle.add_client_destroy_listener::<D>(client_id.id, Box::new(listener))
+    }
+
+    /// Unregister a client destroy listener
+    ///
+    /// Returns `false` if the listener was already invoked or removed.
+    pub fn remove_client_destroy_listener(&self, id: DestroyListenerId) -> bool {
+        self.handle.remove_client_destroy_listener(id)
+    }
+
+    /// Accept new clients on a listening socket, until `close_fd` is hung up.
+    ///
+    /// This is [`Backend::add_listener`], usable from the callbacks of the backend. If `close_fd`
+    /// is given, the listener is removed and the socket closed once the other end of this file
+    /// descriptor is closed, like the `close_fd` of `wp_security_context_v1`. With the rust
+    /// backend, the listener is also removed if data can be read from `close_fd`.
+    ///
+    /// **Panic:** This method will panic if the type parameter `D` is not same to the same type as the
+    /// one the backend was initialized with.
+    pub fn add_listener<D: 'static, F>(
+        &self,
+        listener: OwnedFd,
+        close_fd: Option<OwnedFd>,
+        accept: F,
+    ) -> std::io::Result<()>
+    where
+        F: FnMut(&mut D, &UnixStream) -> Option<Arc<dyn ClientData>> + Send + 'static,
+    {
+        self.handle.add_listener::<D>(listener, close_fd, Box::new(accept))
+    }
+
     /// Returns the handler which manages the visibility and notifies when a client has bound the global.
     #[inline]
     pub fn get_global_handler<D: 'static>(
@@ -486,12 +990,67 @@ impl Handle {
         self.handle.get_global_handler(id.id)
     }
 
+    /// Replaces the handler of a global, without removing it
+    ///
+    /// The clients binding the global from now on are given to the new handler, which also decides which
+    /// clients can see the global. The clients are not notified, and the objects they already bound keep
+    /// their [`ObjectData`], which can be replaced with [`set_object_data()`](Self::set_object_data).
+    ///
+    /// **Panic:** This method will panic if the type parameter `D` is not same to the same type as the
+    /// one the backend was initialized with.
+    pub fn set_global_handler<D: 'static>(
+        &self,
+        id: GlobalId,
+        handler: Arc<dyn GlobalHandler<D>>,
+    ) -> Result<(), InvalidId> {
+        self.handle.set_global_handler(id.id, handler)
+    }
+
     /// Flushes pending events destined for a client.
     ///
     /// If no client is specified, all pending events are flushed to all clients.
     pub fn flush(&mut self, client: Option<ClientId>) -> std::io::Result<()> {
         self.handle.flush(client)
     }
+
+    /// Flushes the pending events of a client, and invokes `callback` once they left the process
+    ///
+    /// The callback is invoked when the events queued for the client until now have all been written to
+    /// its socket, from this flush or from a later one if the socket is full, once the backend is no
+    /// longer locked. The events sent afterwards are not waited for. If the client disconnects first,
+    /// the callback is dropped without being invoked.
+    ///
+    /// `libwayland-server.so` does not report whether the events were all written, so the system backend
+    /// invokes the callback right after flushing.
+    pub fn flush_with_callback(
+        &mut self,
+        client: ClientId,
+        callback: impl FnOnce() + Send + 'static,
+    ) -> Result<(), InvalidId> {
+        self.handle.flush_with_callback(client, DrainCallback(Box::new(callback)))
+    }
+
+    /// Flushes the pending events of a client, and invokes `callback` once the client read them
+    ///
+    /// The callback is invoked at a flush, once the events queued for the client until now have been
+    /// written to its socket and the client has read everything from it. The requests the client sends
+    /// after, like the acknowledgment of a configure, were thus sent knowing these events, and a
+    /// compositor can for example wait for the removal of a global to be seen before destroying it.
+    /// The readiness is checked at every flush, which a compositor usually does after dispatching the
+    /// requests of the client. If the client disconnects first, the callback is dropped without being
+    /// invoked.
+    ///
+    /// The client may have read the events without having processed them yet. Where the platform does
+    /// not tell whether the client read its socket (on other systems than Linux and Android), the callback
+    /// is invoked once the events are written, and the system backend invokes it right after flushing as
+    /// [`flush_with_callback()`](Self::flush_with_callback) does.
+    pub fn barrier(
+        &mut self,
+        client: ClientId,
+        callback: impl FnOnce() + Send + 'static,
+    ) -> Result<(), InvalidId> {
+        self.handle.barrier(client, DrainCallback(Box::new(callback)))
+    }
 }
 
 /// A backend object that represents the state of a wayland server.
@@ -539,6 +1098,31 @@ impl<D> Backend<D> {
         self.backend.poll_fd()
     }
 
+    /// Returns a handle to wake up the event loop monitoring [`Backend::poll_fd`] from other threads.
+    ///
+    /// All the handles of a backend share the same file descriptor, created by the first call.
+    #[inline]
+    pub fn wakeup_handle(&self) -> std::io::Result<WakeupHandle> {
+        self.backend.wakeup_handle()
+    }
+
+    /// Accept new clients on a listening socket
+    ///
+    /// The socket can be of any family accepting stream connections, like an abstract Unix socket
+    /// or a vsock socket for the guests of a virtual machine, and is monitored by
+    /// [`Backend::poll_fd`]. The connections are accepted during [`Backend::dispatch_all_clients`],
+    /// giving each of them to the `accept` callback, which returns the data of the new client or
+    /// `None` to refuse it and close the connection.
+    ///
+    /// The backend takes ownership of the socket and makes it non-blocking. Returns an error of
+    /// kind [`InvalidInput`](std::io::ErrorKind::InvalidInput) if it is not a listening socket.
+    pub fn add_listener<F>(&self, listener: OwnedFd, accept: F) -> std::io::Result<()>
+    where
+        F: FnMut(&mut D, &UnixStream) -> Option<Arc<dyn ClientData>> + Send + 'static,
+    {
+        self.backend.add_listener(listener, Box::new(accept))
+    }
+
     /// Dispatches all pending messages from the specified client.
     ///
     /// This method will not block if there are no pending messages.
@@ -574,6 +1158,62 @@ impl<D> Backend<D> {
     pub fn dispatch_all_clients(&mut self, data: &mut D) -> std::io::Result<usize> {
         self.backend.dispatch_all_clients(data)
     }
+
+    /// Invoke the pending destructors without dispatching the clients
+    ///
+    /// The destructors of the objects destroyed outside of a dispatch, for example because their client was
+    /// killed with [`Handle::kill_client()`], are normally invoked by the next call to
+    /// [`dispatch_all_clients()`](Backend::dispatch_all_clients). This invokes them now with `data`, along
+    /// with the destroy listeners of the destroyed clients.
+    pub fn flush_destructors(&mut self, data: &mut D) {
+        self.backend.flush_destructors(data)
+    }
+
+    /// Disconnect all the clients and stop accepting new ones
+    ///
+    /// The listeners added with [`Backend::add_listener()`] are closed, and the outgoing buffers are flushed.
+    /// Then all the clients are disconnected, after being sent an implementation error with the provided
+    /// `message` if any. Once they all are, the destructors of their objects are invoked with `data`, one
+    /// client after the other, and then their destroy listeners.
+    ///
+    /// Dropping the backend without calling this method does not invoke the destructors.
+    pub fn shutdown(&mut self, data: &mut D, message: Option<CString>) {
+        self.backend.shutdown(data, message)
+    }
+}
+
+#[cfg(feature = "leak_report")]
+impl<D> Drop for Backend<D> {
+    fn drop(&mut self) {
+        let handle = self.handle();
+        let mut clients = Vec::new();
+        handle.with_all_clients(|id| clients.push(id));
+        if clients.is_empty() {
+            return;
+        }
+
+        let mut report =
+            format!("Server backend dropped with {} connected clients:", clients.len());
+        for client in clients {
+            let mut objects = Vec::new();
+            // the wl_display of the client is not a leak
+            let _ = handle.with_all_objects_for(client.clone(), |id| {
+                if id.protocol_id() != 1 {
+                    objects.push(id)
+                }
+            });
+            objects.sort_by_key(ObjectId::protocol_id);
+            let objects = objects.iter().map(ToString::to_string).collect::<Vec<_>>();
+            let pid = handle.get_client_credentials(client).map(|creds| creds.pid).unwrap_or(0);
+            report += &format!(
+                "\n  client with pid {} has {} live objects: {}",
+                pid,
+                objects.len(),
+                objects.join(", ")
+            );
+        }
+        crate::log_warn!("{}", report);
+    }
 }
 
 pub(crate) struct DumbObjectData;
@@ -18,8 +18,9 @@ use std::{
 use crate::{
     core_interfaces::WL_DISPLAY_INTERFACE,
     protocol::{
-        check_for_signature, same_interface, AllowNull, Argument, ArgumentType, Interface, Message,
-        ObjectInfo, ProtocolError, ANONYMOUS_INTERFACE,
+        check_for_signature, same_interface, AllowNull, Argument, ArgumentType, Direction,
+        IdReusePolicy, Interface, IoStats, Message, MessageLimits, Metrics, ObjectInfo,
+        ProtocolError, ToLogged, ANONYMOUS_INTERFACE,
     },
 };
 use scoped_tls::scoped_thread_local;
@@ -173,6 +174,10 @@ struct ConnectionState {
     display_id: InnerObjectId,
     last_error: Option<WaylandError>,
     known_proxies: HashSet<*mut wl_proxy>,
+    metrics: Option<Metrics>,
+    logger: Option<Arc<dyn ProtocolLogger>>,
+    io_stats: IoStats,
+    flush_policy: FlushPolicy,
 }
 
 unsafe impl Send for ConnectionState {}
@@ -196,6 +201,22 @@ pub struct WeakInnerBackend {
     inner: Weak<Inner>,
 }
 
+impl PartialEq for InnerBackend {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for InnerBackend {}
+
+impl PartialEq for WeakInnerBackend {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for WeakInnerBackend {}
+
 impl InnerBackend {
     fn lock_state(&self) -> MutexGuard<ConnectionState> {
         self.inner.state.lock().unwrap()
@@ -253,15 +274,26 @@ impl InnerBackend {
                     },
                     last_error: None,
                     known_proxies: HashSet::new(),
+                    metrics: None,
+                    logger: None,
+                    io_stats: IoStats::default(),
+                    flush_policy: FlushPolicy::Dispatch,
                 }),
                 dispatch_lock: Mutex::new(Dispatcher),
             }),
         })
     }
 
-    pub unsafe fn from_foreign_display(display: *mut wl_display) -> Self {
-        let evq =
-            unsafe { ffi_dispatch!(wayland_client_handle(), wl_display_create_queue, display) };
+    pub unsafe fn from_foreign_display(display: *mut wl_display, queue_name: &CStr) -> Self {
+        // Named queues show up in the WAYLAND_DEBUG output of libwayland 1.23 and later
+        let evq = match wl_display_create_queue_with_name_option() {
+            Some(create_queue_with_name) => unsafe {
+                create_queue_with_name(display, queue_name.as_ptr())
+            },
+            None => unsafe {
+                ffi_dispatch!(wayland_client_handle(), wl_display_create_queue, display)
+            },
+        };
         Self {
             inner: Arc::new(Inner {
                 state: Mutex::new(ConnectionState {
@@ -275,6 +307,10 @@ impl InnerBackend {
                     },
                     last_error: None,
                     known_proxies: HashSet::new(),
+                    metrics: None,
+                    logger: None,
+                    io_stats: IoStats::default(),
+                    flush_policy: FlushPolicy::Dispatch,
                 }),
                 dispatch_lock: Mutex::new(Dispatcher),
             }),
@@ -478,6 +514,52 @@ impl InnerBackend {
         self.lock_state().last_error.clone()
     }
 
+    pub fn live_objects(&self) -> Vec<ObjectId> {
+        self.lock_state().live_objects().map(|id| ObjectId { id }).collect()
+    }
+
+    pub fn enable_metrics(&self) {
+        self.lock_state().metrics.get_or_insert_with(Metrics::default);
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.lock_state().metrics.clone().unwrap_or_default()
+    }
+
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.lock_state().logger = logger;
+    }
+
+    pub fn set_zombie_hook(&self, _hook: Option<Arc<dyn ZombieHook>>) {
+        // libwayland discards the events of its zombie proxies
+    }
+
+    pub fn set_id_reuse_policy(&self, _policy: IdReusePolicy) {
+        // libwayland allocates the ids
+    }
+
+    pub fn set_message_limits(&self, _limits: MessageLimits) {
+        // the limits of libwayland are fixed
+    }
+
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        // libwayland flushes its buffer by itself when it is full
+        self.lock_state().flush_policy = policy;
+    }
+
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.lock_state().flush_policy
+    }
+
+    pub fn pending_bytes(&self) -> usize {
+        0
+    }
+
+    pub fn io_stats(&self) -> IoStats {
+        let state = self.lock_state();
+        IoStats { live_objects: state.live_objects().count() as u64, ..state.io_stats }
+    }
+
     pub fn info(&self, ObjectId { id }: ObjectId) -> Result<ObjectInfo, InvalidId> {
         if !id.alive.as_ref().map(|a| a.load(Ordering::Acquire)).unwrap_or(true) || id.ptr.is_null()
         {
@@ -534,6 +616,10 @@ impl InnerBackend {
                 id.interface.name, id.id, message_desc.name, message_desc.signature, args
             );
         }
+        if let Some(ref mut metrics) = guard.metrics {
+            metrics.count_sent(id.interface);
+        }
+        guard.io_stats.messages_sent += 1;
 
         // Prepare the child object data
         let child_spec = if message_desc
@@ -714,6 +800,19 @@ impl InnerBackend {
             Self::null_id()
         };
 
+        // the id of the new object is only known once libwayland has marshalled the request
+        if let Some(ref logger) = guard.logger {
+            let args = args
+                .iter()
+                .map(|arg| match *arg {
+                    Argument::NewId(_) => Argument::NewId(child_id.protocol_id()),
+                    ref arg => arg.to_logged(ObjectId::protocol_id),
+                })
+                .collect();
+            let logged = Message { sender_id: id.id, opcode, args };
+            logger.log(Direction::Sent, id.interface, &logged);
+        }
+
         if message_desc.is_destructor {
             if let Some(ref alive) = id.alive {
                 let udata = unsafe {
@@ -955,17 +1054,24 @@ unsafe extern "C" fn dispatcher_func(
 
     let ret = BACKEND.with(|backend| {
         let mut guard = backend.backend.lock_state();
+        if let Some(ref mut metrics) = guard.metrics {
+            metrics.count_received(udata.interface);
+        }
+        guard.io_stats.messages_received += 1;
         if let Some((ref new_id, _)) = created {
             guard.known_proxies.insert(new_id.ptr);
         }
         if message_desc.is_destructor {
             guard.known_proxies.remove(&proxy);
         }
+        let logger = guard.logger.clone();
         std::mem::drop(guard);
-        udata.data.clone().event(
-            backend,
-            Message { sender_id: id.clone(), opcode: opcode as u16, args: parsed_args },
-        )
+        let message = Message { sender_id: id.clone(), opcode: opcode as u16, args: parsed_args };
+        if let Some(logger) = logger {
+            let logged = message.to_logged(ObjectId::protocol_id);
+            logger.log(Direction::Received, udata.interface, &logged);
+        }
+        udata.data.clone().event(backend, message)
     });
 
     if message_desc.is_destructor {
@@ -1001,8 +1107,39 @@ extern "C" {
     fn wl_log_trampoline_to_rust_client(fmt: *const std::os::raw::c_char, list: *const c_void);
 }
 
+impl ConnectionState {
+    fn live_objects(&self) -> impl Iterator<Item = InnerObjectId> + '_ {
+        self.known_proxies.iter().filter_map(|&ptr| {
+            let udata = unsafe {
+                &*(ffi_dispatch!(wayland_client_handle(), wl_proxy_get_user_data, ptr)
+                    as *mut ProxyUserData)
+            };
+            if !udata.alive.load(Ordering::Acquire) {
+                return None;
+            }
+            Some(InnerObjectId {
+                id: unsafe { ffi_dispatch!(wayland_client_handle(), wl_proxy_get_id, ptr) },
+                ptr,
+                alive: Some(udata.alive.clone()),
+                interface: udata.interface,
+            })
+        })
+    }
+}
+
 impl Drop for ConnectionState {
     fn drop(&mut self) {
+        #[cfg(feature = "leak_report")]
+        {
+            let leaked = self.live_objects().map(|id| id.to_string()).collect::<Vec<_>>();
+            if !leaked.is_empty() {
+                crate::log_warn!(
+                    "[wayland-backend-sys] Connection dropped with {} live objects: {}",
+                    leaked.len(),
+                    leaked.join(", ")
+                );
+            }
+        }
         // Cleanup the objects we know about, libwayland will discard any future message
         // they receive.
         for proxy_ptr in self.known_proxies.drain() {
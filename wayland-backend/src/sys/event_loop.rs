@@ -0,0 +1,392 @@
+//! Event sources of the `libwayland-server` event loop
+//!
+//! `libwayland-server` drives its clients with an internal event loop, which can also be used to
+//! monitor other event sources: file descriptors, timers, signals and idle callbacks. This module
+//! exposes them as methods on [`Backend`]. The callbacks of these sources are invoked from within
+//! [`Backend::dispatch_all_clients`], with the same `&mut D` as the request handlers.
+//!
+//! Sources are removed from the event loop when the value representing them is dropped. Dropping
+//! a source from within its own callback is supported.
+
+use std::{
+    cell::RefCell,
+    os::raw::{c_int, c_void},
+    os::unix::io::{AsRawFd, BorrowedFd},
+    rc::Rc,
+    time::Duration,
+};
+
+use wayland_sys::{ffi_dispatch, server::*};
+
+use super::server::{Backend, Handle, WeakHandle};
+use super::server_impl::with_dispatch_data;
+
+/// The kind of readiness a file descriptor source should monitor
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Interest {
+    /// Monitor the file descriptor for readability
+    pub readable: bool,
+    /// Monitor the file descriptor for writability
+    pub writable: bool,
+}
+
+impl Interest {
+    /// Only monitor readability
+    pub const READ: Interest = Interest { readable: true, writable: false };
+    /// Only monitor writability
+    pub const WRITE: Interest = Interest { readable: false, writable: true };
+    /// Monitor both readability and writability
+    pub const BOTH: Interest = Interest { readable: true, writable: true };
+
+    fn mask(self) -> u32 {
+        let mut mask = 0;
+        if self.readable {
+            mask |= WL_EVENT_READABLE;
+        }
+        if self.writable {
+            mask |= WL_EVENT_WRITABLE;
+        }
+        mask
+    }
+}
+
+/// The readiness of a file descriptor, as reported to the callback of a file descriptor source
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Readiness {
+    /// The file descriptor is readable
+    pub readable: bool,
+    /// The file descriptor is writable
+    pub writable: bool,
+    /// The other end of the file descriptor hung up
+    pub hangup: bool,
+    /// An error condition occurred on the file descriptor
+    pub error: bool,
+}
+
+impl Readiness {
+    fn from_mask(mask: u32) -> Readiness {
+        Readiness {
+            readable: mask & WL_EVENT_READABLE != 0,
+            writable: mask & WL_EVENT_WRITABLE != 0,
+            hangup: mask & WL_EVENT_HANGUP != 0,
+            error: mask & WL_EVENT_ERROR != 0,
+        }
+    }
+}
+
+// The callback of a source, shared between the source and its trampoline so that the source can
+// be dropped while its callback is running.
+type Callback<F> = RefCell<F>;
+
+struct SourceInner {
+    ptr: *mut wl_event_source,
+    callback: *const c_void,
+    drop_callback: unsafe fn(*const c_void),
+    handle: WeakHandle,
+}
+
+impl SourceInner {
+    fn new<F>(callback: F, handle: WeakHandle) -> SourceInner {
+        unsafe fn drop_callback<F>(ptr: *const c_void) {
+            drop(unsafe { Rc::from_raw(ptr as *const Callback<F>) });
+        }
+        SourceInner {
+            ptr: std::ptr::null_mut(),
+            callback: Rc::into_raw(Rc::new(RefCell::new(callback))) as *const c_void,
+            drop_callback: drop_callback::<F>,
+            handle,
+        }
+    }
+}
+
+impl Drop for SourceInner {
+    fn drop(&mut self) {
+        // If the backend is gone, the event loop was destroyed with it, and the source with it
+        if !self.ptr.is_null() && self.handle.upgrade().is_some() {
+            unsafe {
+                ffi_dispatch!(wayland_server_handle(), wl_event_source_remove, self.ptr);
+            }
+        }
+        unsafe { (self.drop_callback)(self.callback) }
+    }
+}
+
+impl std::fmt::Debug for SourceInner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSource").field("ptr", &self.ptr).finish_non_exhaustive()
+    }
+}
+
+/// Run the callback stored behind `data`, keeping it alive for the duration of the call
+unsafe fn with_callback<F, T>(data: *mut c_void, f: impl FnOnce(&mut F) -> T) -> Option<T> {
+    let data = data as *const Callback<F>;
+    let callback = unsafe {
+        Rc::increment_strong_count(data);
+        Rc::from_raw(data)
+    };
+    // a reentrant dispatch of the event loop cannot run the same callback twice
+    let mut guard = callback.try_borrow_mut().ok()?;
+    Some(f(&mut guard))
+}
+
+/// A signal event source
+///
+/// The source is removed from the event loop when this value is dropped.
+#[derive(Debug)]
+pub struct EventSource {
+    inner: SourceInner,
+}
+
+impl EventSource {
+    /// Access the underlying `*mut wl_event_source` pointer
+    pub fn as_ptr(&self) -> *mut wl_event_source {
+        self.inner.ptr
+    }
+}
+
+/// A timer event source
+///
+/// A freshly inserted timer is disarmed, use [`TimerSource::set_timeout`] to arm it. Once it fires,
+/// the timer is disarmed again until it is re-armed, which can be done from its callback.
+///
+/// The source is removed from the event loop when this value is dropped.
+#[derive(Debug)]
+pub struct TimerSource {
+    inner: SourceInner,
+}
+
+impl TimerSource {
+    /// Arm the timer to fire after `timeout`, or disarm it with `None`
+    ///
+    /// The timeout has a millisecond granularity and is rounded up, so that a non-zero duration
+    /// never disarms the timer.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        let ms = match timeout {
+            None => 0,
+            Some(timeout) => {
+                let ms = timeout.as_nanos().saturating_add(999_999) / 1_000_000;
+                ms.clamp(1, c_int::MAX as u128) as c_int
+            }
+        };
+        let ret = unsafe {
+            ffi_dispatch!(wayland_server_handle(), wl_event_source_timer_update, self.inner.ptr, ms)
+        };
+        if ret < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Access the underlying `*mut wl_event_source` pointer
+    pub fn as_ptr(&self) -> *mut wl_event_source {
+        self.inner.ptr
+    }
+}
+
+/// A file descriptor event source
+///
+/// The source is removed from the event loop when this value is dropped.
+#[derive(Debug)]
+pub struct FdSource {
+    inner: SourceInner,
+}
+
+impl FdSource {
+    /// Change the kind of readiness monitored by this source
+    pub fn set_interest(&self, interest: Interest) -> std::io::Result<()> {
+        let ret = unsafe {
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_event_source_fd_update,
+                self.inner.ptr,
+                interest.mask()
+            )
+        };
+        if ret < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Access the underlying `*mut wl_event_source` pointer
+    pub fn as_ptr(&self) -> *mut wl_event_source {
+        self.inner.ptr
+    }
+}
+
+impl<D: 'static> Backend<D> {
+    fn event_loop_ptr(&self) -> *mut wl_event_loop {
+        unsafe {
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_display_get_event_loop,
+                self.handle().display_ptr()
+            )
+        }
+    }
+
+    /// Insert a timer in the event loop of this backend
+    ///
+    /// The timer is initially disarmed, see [`TimerSource::set_timeout`].
+    pub fn insert_timer<F>(&mut self, callback: F) -> std::io::Result<TimerSource>
+    where
+        F: FnMut(&Handle, &mut D) + 'static,
+    {
+        unsafe extern "C" fn timer_trampoline<D: 'static, F>(data: *mut c_void) -> c_int
+        where
+            F: FnMut(&Handle, &mut D) + 'static,
+        {
+            unsafe {
+                with_callback::<(WeakHandle, F), _>(data, |(weak, callback)| {
+                    with_dispatch_data::<D, _>(&weak.handle, |handle, data| callback(handle, data))
+                });
+            }
+            0
+        }
+
+        let weak = self.handle().downgrade();
+        let mut inner = SourceInner::new::<(WeakHandle, F)>((weak.clone(), callback), weak);
+        inner.ptr = unsafe {
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_event_loop_add_timer,
+                self.event_loop_ptr(),
+                timer_trampoline::<D, F>,
+                inner.callback as *mut c_void
+            )
+        };
+        if inner.ptr.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(TimerSource { inner })
+    }
+
+    /// Insert a file descriptor in the event loop of this backend
+    ///
+    /// The file descriptor is duplicated by `libwayland-server`, so `fd` does not need to outlive the
+    /// source.
+    pub fn insert_fd<F>(
+        &mut self,
+        fd: BorrowedFd<'_>,
+        interest: Interest,
+        callback: F,
+    ) -> std::io::Result<FdSource>
+    where
+        F: FnMut(&Handle, &mut D, Readiness) + 'static,
+    {
+        unsafe extern "C" fn fd_trampoline<D: 'static, F>(
+            _fd: c_int,
+            mask: u32,
+            data: *mut c_void,
+        ) -> c_int
+        where
+            F: FnMut(&Handle, &mut D, Readiness) + 'static,
+        {
+            let readiness = Readiness::from_mask(mask);
+            unsafe {
+                with_callback::<(WeakHandle, F), _>(data, |(weak, callback)| {
+                    with_dispatch_data::<D, _>(&weak.handle, |handle, data| {
+                        callback(handle, data, readiness)
+                    })
+                });
+            }
+            0
+        }
+
+        let weak = self.handle().downgrade();
+        let mut inner = SourceInner::new::<(WeakHandle, F)>((weak.clone(), callback), weak);
+        inner.ptr = unsafe {
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_event_loop_add_fd,
+                self.event_loop_ptr(),
+                fd.as_raw_fd(),
+                interest.mask(),
+                fd_trampoline::<D, F>,
+                inner.callback as *mut c_void
+            )
+        };
+        if inner.ptr.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(FdSource { inner })
+    }
+
+    /// Insert a signal handler in the event loop of this backend
+    ///
+    /// The signal is received through a `signalfd`, and as such must be blocked in all threads of
+    /// the process for the callback to have any effect.
+    pub fn insert_signal<F>(&mut self, signal: c_int, callback: F) -> std::io::Result<EventSource>
+    where
+        F: FnMut(&Handle, &mut D) + 'static,
+    {
+        unsafe extern "C" fn signal_trampoline<D: 'static, F>(
+            _signal: c_int,
+            data: *mut c_void,
+        ) -> c_int
+        where
+            F: FnMut(&Handle, &mut D) + 'static,
+        {
+            unsafe {
+                with_callback::<(WeakHandle, F), _>(data, |(weak, callback)| {
+                    with_dispatch_data::<D, _>(&weak.handle, |handle, data| callback(handle, data))
+                });
+            }
+            0
+        }
+
+        let weak = self.handle().downgrade();
+        let mut inner = SourceInner::new::<(WeakHandle, F)>((weak.clone(), callback), weak);
+        inner.ptr = unsafe {
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_event_loop_add_signal,
+                self.event_loop_ptr(),
+                signal,
+                signal_trampoline::<D, F>,
+                inner.callback as *mut c_void
+            )
+        };
+        if inner.ptr.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(EventSource { inner })
+    }
+
+    /// Schedule a callback to be run the next time the event loop of this backend is dispatched
+    ///
+    /// The callback is invoked once, at the start of the next call to
+    /// [`Backend::dispatch_all_clients`]. If the backend is dropped before that, the callback is
+    /// never invoked and is leaked.
+    pub fn insert_idle<F>(&mut self, callback: F)
+    where
+        F: FnOnce(&Handle, &mut D) + 'static,
+    {
+        unsafe extern "C" fn idle_trampoline<D: 'static, F>(data: *mut c_void)
+        where
+            F: FnOnce(&Handle, &mut D) + 'static,
+        {
+            // libwayland removes idle sources once they have been dispatched
+            let (weak, callback) = *unsafe { Box::from_raw(data as *mut (WeakHandle, F)) };
+            unsafe {
+                with_dispatch_data::<D, _>(&weak.handle, |handle, data| callback(handle, data));
+            }
+        }
+
+        let data = Box::into_raw(Box::new((self.handle().downgrade(), callback)));
+        let ptr = unsafe {
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_event_loop_add_idle,
+                self.event_loop_ptr(),
+                idle_trampoline::<D, F>,
+                data as *mut c_void
+            )
+        };
+        if ptr.is_null() {
+            drop(unsafe { Box::from_raw(data) });
+        }
+    }
+}
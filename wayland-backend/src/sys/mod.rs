@@ -79,7 +79,30 @@ impl client::Backend {
     /// You need to ensure the `*mut wl_display` remains live as long as the  [`Backend`](client::Backend)
     /// (or its clones) exist.
     pub unsafe fn from_foreign_display(display: *mut wayland_sys::client::wl_display) -> Self {
-        Self { backend: unsafe { client_impl::InnerBackend::from_foreign_display(display) } }
+        let name = std::ffi::CStr::from_bytes_with_nul(b"wayland-rs\0").unwrap();
+        unsafe { Self::from_foreign_display_with_queue_name(display, name) }
+    }
+
+    /// Creates a Backend from a foreign `*mut wl_display`, naming its event queue.
+    ///
+    /// This is the same as [`Backend::from_foreign_display`](client::Backend::from_foreign_display),
+    /// except that the event queue used by the backend is given `queue_name`, which appears in the
+    /// `WAYLAND_DEBUG` output of libwayland. Queue names are only supported since libwayland 1.23,
+    /// with older versions the name is ignored.
+    ///
+    /// # Safety
+    ///
+    /// You need to ensure the `*mut wl_display` remains live as long as the  [`Backend`](client::Backend)
+    /// (or its clones) exist.
+    pub unsafe fn from_foreign_display_with_queue_name(
+        display: *mut wayland_sys::client::wl_display,
+        queue_name: &std::ffi::CStr,
+    ) -> Self {
+        Self {
+            backend: unsafe {
+                client_impl::InnerBackend::from_foreign_display(display, queue_name)
+            },
+        }
     }
 
     /// Returns the underlying `wl_display` pointer to this backend.
@@ -112,6 +135,9 @@ unsafe impl raw_window_handle::HasRawDisplayHandle for client::Backend {
 #[path = "../server_api.rs"]
 pub mod server;
 
+#[cfg(any(test, feature = "server_system"))]
+pub mod event_loop;
+
 #[cfg(any(test, feature = "server_system"))]
 impl server::ObjectId {
     /// Creates an object from a C pointer.
@@ -140,10 +166,109 @@ impl server::ObjectId {
     }
 }
 
+#[cfg(any(test, feature = "server_system"))]
+impl server::ClientId {
+    /// Retrieves the id of a client from a C pointer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an [`InvalidId`](server::InvalidId) error if the client is not known to
+    /// this backend, meaning it was neither inserted with
+    /// [`Handle::insert_client`](server::Handle::insert_client) nor adopted with
+    /// [`Handle::adopt_client`](server::Handle::adopt_client).
+    ///
+    /// # Safety
+    ///
+    /// The provided pointer must be a valid pointer to a `wl_client`.
+    pub unsafe fn from_ptr(
+        ptr: *mut wayland_sys::server::wl_client,
+    ) -> Result<Self, server::InvalidId> {
+        Ok(Self { id: unsafe { server_impl::InnerClientId::from_ptr(ptr) }? })
+    }
+
+    /// Returns the pointer that represents this client.
+    ///
+    /// The pointer may be used to interoperate with libwayland. It is null if the client is no
+    /// longer alive.
+    pub fn as_ptr(&self) -> *mut wayland_sys::server::wl_client {
+        self.id.as_ptr()
+    }
+}
+
 #[cfg(any(test, feature = "server_system"))]
 impl server::Handle {
     /// Access the underlying `*mut wl_display` pointer
     pub fn display_ptr(&self) -> *mut wayland_sys::server::wl_display {
         self.handle.display_ptr()
     }
+
+    /// Starts tracking a client that was created outside of this backend.
+    ///
+    /// This is meant for clients created directly through libwayland (for example by C code sharing
+    /// the same `wl_display`), so that they can be used with this backend. A destroy listener is
+    /// attached to the client to track its liveness, and `data` is associated with it. If the client
+    /// is already known to this backend, its existing id is returned and `data` is dropped.
+    ///
+    /// Resources belonging to such clients can be retrieved with
+    /// [`ObjectId::from_ptr`](server::ObjectId::from_ptr).
+    ///
+    /// Once the client owns objects managed by this backend, it must only be destroyed with
+    /// [`Handle::kill_client`](server::Handle::kill_client) or from within
+    /// [`Backend::dispatch_all_clients`](server::Backend::dispatch_all_clients), as the destructors of
+    /// these objects need access to the backend state.
+    ///
+    /// # Safety
+    ///
+    /// The provided pointer must be a valid pointer to a `wl_client` belonging to the `wl_display`
+    /// of this backend.
+    pub unsafe fn adopt_client(
+        &self,
+        ptr: *mut wayland_sys::server::wl_client,
+        data: std::sync::Arc<dyn server::ClientData>,
+    ) -> server::ClientId {
+        server::ClientId { id: unsafe { self.handle.adopt_client(ptr, data) } }
+    }
+
+    /// Iterate over all the resources of a client, including those not managed by this backend
+    ///
+    /// Unlike [`Handle::with_all_objects_for`](server::Handle::with_all_objects_for), this also
+    /// yields the resources created directly through libwayland, such as the `wl_display` object of
+    /// the client. The callback must not create or destroy resources of this client.
+    ///
+    /// Returns an error if the client is no longer alive.
+    pub fn with_all_resources_for(
+        &self,
+        client_id: &server::ClientId,
+        mut f: impl FnMut(*mut wayland_sys::server::wl_resource),
+    ) -> Result<(), server::InvalidId> {
+        self.handle.with_all_resources_for(client_id.id.clone(), &mut f)
+    }
+
+    /// Returns the file descriptor of the connection of a client
+    ///
+    /// The file descriptor is owned by libwayland and remains valid as long as the client is
+    /// alive. It should not be read from or written to.
+    ///
+    /// Returns an error if the client is no longer alive.
+    pub fn client_fd(
+        &self,
+        client_id: &server::ClientId,
+    ) -> Result<std::os::unix::io::RawFd, server::InvalidId> {
+        self.handle.client_fd(client_id.id.clone())
+    }
+}
+
+#[cfg(any(test, feature = "server_system"))]
+impl server::GlobalId {
+    /// Returns the pointer that represents this global.
+    ///
+    /// The pointer may be used to interoperate with libwayland, for example from a global filter
+    /// set with `wl_display_set_global_filter`. It is null if the global was removed.
+    ///
+    /// Note that this backend already installs a global filter to implement
+    /// [`GlobalHandler::can_view`](server::GlobalHandler::can_view), replacing it will break that
+    /// functionality.
+    pub fn as_ptr(&self) -> *mut wayland_sys::server::wl_global {
+        self.id.as_ptr()
+    }
 }
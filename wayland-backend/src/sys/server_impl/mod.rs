@@ -5,18 +5,20 @@ use std::{
     os::raw::{c_int, c_void},
     os::unix::io::{BorrowedFd, OwnedFd},
     os::unix::{
-        io::{FromRawFd, IntoRawFd, RawFd},
+        io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
         net::UnixStream,
     },
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex, Weak,
     },
+    time::{Duration, Instant},
 };
 
 use crate::protocol::{
-    check_for_signature, same_interface, AllowNull, Argument, ArgumentType, Interface, Message,
-    ObjectInfo, ANONYMOUS_INTERFACE,
+    check_for_signature, same_interface, AllowNull, Argument, ArgumentType, Direction,
+    IdReusePolicy, Interface, IoStats, Message, MessageLimits, Metrics, ObjectInfo, ToLogged,
+    ANONYMOUS_INTERFACE,
 };
 use scoped_tls::scoped_thread_local;
 use smallvec::SmallVec;
@@ -25,7 +27,12 @@ use wayland_sys::{common::*, ffi_dispatch, server::*};
 
 use super::{free_arrays, server::*, RUST_MANAGED};
 
+use crate::types::server::{
+    AcceptCallback, ClientDestroyCallback, DestroyListenerId, DestroyListeners, DrainCallback,
+    GlobalFinalizer, GlobalRemovals, Listener, PauseError,
+};
 pub use crate::types::server::{Credentials, DisconnectReason, GlobalInfo, InitError, InvalidId};
+use crate::types::wakeup::{self, Wakeup, WakeupHandle};
 
 scoped_thread_local! {
     // scoped_tls does not allow unsafe_op_in_unsafe_fn internally
@@ -35,6 +42,15 @@ scoped_thread_local! {
 
 type PendingDestructor<D> = (Arc<dyn ObjectData<D>>, ClientId, ObjectId);
 
+type ServerListener<D> = Listener<AcceptCallback<D, dyn ClientData>>;
+
+// A listener and its event sources, boxed as the sources point to it
+#[derive(Debug)]
+struct ListenerEntry<D: 'static> {
+    listener: ServerListener<D>,
+    sources: Vec<*mut wl_event_source>,
+}
+
 // Pointer is &mut Vec<PendingDestructor<D>>
 scoped_thread_local! {
     // scoped_tls does not allow unsafe_op_in_unsafe_fn internally
@@ -249,6 +265,25 @@ impl std::hash::Hash for InnerClientId {
     }
 }
 
+impl InnerClientId {
+    pub unsafe fn from_ptr(ptr: *mut wl_client) -> Result<Self, InvalidId> {
+        // Safety: the caller guarantees the pointer is a valid wl_client
+        unsafe { client_id_from_ptr(ptr) }.ok_or(InvalidId)
+    }
+
+    pub fn as_ptr(&self) -> *mut wl_client {
+        if self.alive.load(Ordering::Acquire) {
+            self.ptr
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+
+    pub(crate) fn as_u64(&self) -> u64 {
+        self.ptr as u64
+    }
+}
+
 /// The ID of a global
 #[derive(Debug, Clone)]
 pub struct InnerGlobalId {
@@ -256,6 +291,16 @@ pub struct InnerGlobalId {
     alive: Arc<AtomicBool>,
 }
 
+impl InnerGlobalId {
+    pub fn as_ptr(&self) -> *mut wl_global {
+        if self.alive.load(Ordering::Acquire) {
+            self.ptr
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+}
+
 unsafe impl Send for InnerGlobalId {}
 unsafe impl Sync for InnerGlobalId {}
 
@@ -283,8 +328,27 @@ struct ResourceUserData<D> {
 struct ClientUserData {
     data: Arc<dyn ClientData>,
     alive: Arc<AtomicBool>,
+    // set once libwayland destroyed the client, unlike `alive` which is unset when it is killed
+    destroyed: Arc<AtomicBool>,
+    // why the client was killed, for its destroy listeners
+    reason: Arc<Mutex<Option<DisconnectReason>>>,
+    metrics: Mutex<Option<Metrics>>,
+    io_stats: Mutex<IoStats>,
+    logger: Mutex<Option<Arc<dyn ProtocolLogger>>>,
+}
+
+impl ClientUserData {
+    /// Mark the client as dead and notify its data
+    fn disconnected(&self, client_id: InnerClientId, reason: DisconnectReason) {
+        self.alive.store(false, Ordering::Release);
+        *self.reason.lock().unwrap() = Some(reason.clone());
+        self.data.disconnected(ClientId { id: client_id }, reason);
+    }
 }
 
+/// A client with destroy listeners, whether it was destroyed and why
+type DestroyedClient = (ClientId, Arc<AtomicBool>, Arc<Mutex<Option<DisconnectReason>>>);
+
 struct GlobalUserData<D> {
     handler: Arc<dyn GlobalHandler<D>>,
     interface: &'static Interface,
@@ -301,6 +365,14 @@ pub struct State<D: 'static> {
     timer_source: *mut wl_event_source,
     _data: std::marker::PhantomData<fn(&mut D)>,
     known_globals: Vec<InnerGlobalId>,
+    pending_removals: GlobalRemovals<InnerGlobalId, Handle, D>,
+    destroy_listeners:
+        DestroyListeners<DestroyedClient, ClientDestroyCallback<Handle, ClientId, D>>,
+    metrics: bool,
+    logger: Option<Arc<dyn ProtocolLogger>>,
+    wakeup: Option<Wakeup>,
+    #[allow(clippy::vec_box)]
+    listeners: Vec<Box<ListenerEntry<D>>>,
 }
 
 unsafe impl<D> Send for State<D> {}
@@ -368,6 +440,12 @@ impl<D> InnerBackend<D> {
                 timer_source,
                 _data: std::marker::PhantomData,
                 known_globals: Vec::new(),
+                pending_removals: GlobalRemovals::new(),
+                destroy_listeners: DestroyListeners::new(),
+                metrics: false,
+                logger: None,
+                wakeup: None,
+                listeners: Vec::new(),
             })),
             display_ptr: display,
         })
@@ -393,6 +471,49 @@ impl<D> InnerBackend<D> {
         }
     }
 
+    pub fn wakeup_handle(&self) -> std::io::Result<WakeupHandle> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(ref wakeup) = state.wakeup {
+            return Ok(wakeup.handle());
+        }
+        let wakeup = Wakeup::new()?;
+
+        extern "C" fn wakeup_cb(fd: c_int, _: u32, _: *mut c_void) -> c_int {
+            wakeup::drain(fd);
+            0
+        }
+
+        // the source is destroyed along with the event loop
+        let source = unsafe {
+            let evl =
+                ffi_dispatch!(wayland_server_handle(), wl_display_get_event_loop, self.display_ptr);
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_event_loop_add_fd,
+                evl,
+                wakeup.fd().as_raw_fd(),
+                WL_EVENT_READABLE,
+                wakeup_cb,
+                std::ptr::null_mut()
+            )
+        };
+        if source.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let handle = wakeup.handle();
+        state.wakeup = Some(wakeup);
+        Ok(handle)
+    }
+
+    pub fn add_listener(
+        &self,
+        fd: OwnedFd,
+        accept: AcceptCallback<D, dyn ClientData>,
+    ) -> std::io::Result<()> {
+        self.handle().handle.add_listener::<D>(fd, None, accept)
+    }
+
     pub fn dispatch_client(
         &mut self,
         data: &mut D,
@@ -410,11 +531,14 @@ impl<D> InnerBackend<D> {
             ffi_dispatch!(wayland_server_handle(), wl_event_loop_dispatch, evl_ptr, 0)
         });
 
-        let pending_destructors =
-            std::mem::take(&mut self.state.lock().unwrap().pending_destructors);
-        for (object, client_id, object_id) in pending_destructors {
+        self.notify_destroyed(data);
+
+        let removed_globals =
+            self.state.lock().unwrap().pending_removals.take_expired(Instant::now());
+        for (id, finalize) in removed_globals {
             let handle = self.handle();
-            object.clone().destroyed(&handle, data, client_id, object_id);
+            handle.handle.remove_global::<D>(id);
+            finalize(&handle, data);
         }
 
         if ret < 0 {
@@ -423,6 +547,86 @@ impl<D> InnerBackend<D> {
             Ok(ret as usize)
         }
     }
+
+    pub fn flush_destructors(&mut self, data: &mut D) {
+        self.notify_destroyed(data);
+    }
+
+    pub fn shutdown(&mut self, data: &mut D, message: Option<CString>) {
+        let listeners = std::mem::take(&mut self.state.lock().unwrap().listeners);
+        for entry in listeners {
+            for source in entry.sources {
+                unsafe { ffi_dispatch!(wayland_server_handle(), wl_event_source_remove, source) };
+            }
+        }
+        let handle = self.handle();
+        let mut clients = Vec::new();
+        handle.with_all_clients(|id| clients.push(id));
+        for client in &clients {
+            if !client.id.alive.load(Ordering::Acquire) {
+                continue;
+            }
+            // Safety: the client is alive
+            if let Some(udata) = unsafe { client_user_data(client.id.ptr) } {
+                unsafe { &*udata }
+                    .disconnected(client.id.clone(), DisconnectReason::ServerShutdown);
+            }
+            let display = unsafe {
+                ffi_dispatch!(wayland_server_handle(), wl_client_get_object, client.id.ptr, 1)
+            };
+            if let (Some(message), false) = (message.as_ref(), display.is_null()) {
+                unsafe { post_error(display, WL_DISPLAY_ERROR_IMPLEMENTATION, message.clone()) }
+            }
+        }
+        let _ = self.flush(None);
+        {
+            let mut state = self.state.lock().unwrap();
+            for client in clients {
+                state.destroy_client(client.id.ptr);
+            }
+        }
+        self.notify_destroyed(data);
+    }
+
+    /// Run the destructors of the destroyed resources, then the destroy listeners of the destroyed clients
+    fn notify_destroyed(&self, data: &mut D) {
+        let pending_destructors =
+            std::mem::take(&mut self.state.lock().unwrap().pending_destructors);
+        for (object, client_id, object_id) in pending_destructors {
+            let handle = self.handle();
+            object.clone().destroyed(&handle, data, client_id, object_id);
+        }
+
+        let destroy_listeners = self
+            .state
+            .lock()
+            .unwrap()
+            .destroy_listeners
+            .take(|(_, destroyed, _)| destroyed.load(Ordering::Acquire));
+        for ((client_id, _, reason), listener) in destroy_listeners {
+            let reason = reason.lock().unwrap().clone();
+            listener(
+                &self.handle(),
+                data,
+                client_id,
+                reason.unwrap_or(DisconnectReason::ConnectionClosed),
+            );
+        }
+    }
+}
+
+impl<D> State<D> {
+    /// Destroy a client, queuing the destructors of its resources
+    fn destroy_client(&mut self, client: *mut wl_client) {
+        let start = self.pending_destructors.len();
+        // wl_client_destroy invokes destructors
+        PENDING_DESTRUCTORS.set(&(&mut self.pending_destructors as *mut _ as *mut _), || unsafe {
+            ffi_dispatch!(wayland_server_handle(), wl_client_destroy, client);
+        });
+        // libwayland destroys them by increasing id, the reverse order approximates the destruction of
+        // the objects before the ones they were created from
+        self.pending_destructors[start..].reverse();
+    }
 }
 
 impl<D> Drop for State<D> {
@@ -497,6 +701,24 @@ impl InnerHandle {
         self.state.lock().unwrap().insert_client(stream, data)
     }
 
+    pub unsafe fn adopt_client(
+        &self,
+        client: *mut wl_client,
+        data: Arc<dyn ClientData>,
+    ) -> InnerClientId {
+        // Keep this guard alive while the code is run to protect the C state
+        let state = self.state.lock().unwrap();
+        // Safety: the caller guarantees the pointer is a valid wl_client
+        if let Some(id) = unsafe { client_id_from_ptr(client) } {
+            return id;
+        }
+        let id = unsafe {
+            init_client(client, data.clone(), state.metrics_enabled(), state.protocol_logger())
+        };
+        data.initialized(ClientId { id: id.clone() });
+        id
+    }
+
     pub fn get_client(&self, id: InnerObjectId) -> Result<ClientId, InvalidId> {
         self.state.lock().unwrap().get_client(id)
     }
@@ -509,6 +731,77 @@ impl InnerHandle {
         self.state.lock().unwrap().get_client_credentials(id)
     }
 
+    pub fn get_client_security_label(
+        &self,
+        id: InnerClientId,
+    ) -> Result<Option<String>, InvalidId> {
+        self.state.lock().unwrap().get_client_security_label(id)
+    }
+
+    pub fn set_client_max_buffer_size(
+        &self,
+        id: InnerClientId,
+        _size: usize,
+    ) -> Result<(), InvalidId> {
+        // libwayland manages the buffers
+        if !id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+        Ok(())
+    }
+
+    pub fn set_client_overflow_policy(
+        &self,
+        id: InnerClientId,
+        _policy: OverflowPolicy,
+    ) -> Result<(), InvalidId> {
+        // libwayland always disconnects the clients whose buffer is full
+        if !id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+        Ok(())
+    }
+
+    pub fn enable_metrics(&self) {
+        self.state.lock().unwrap().enable_metrics()
+    }
+
+    pub fn set_id_reuse_policy(&self, _policy: IdReusePolicy) {
+        // libwayland allocates the ids
+    }
+
+    pub fn set_rate_limit(&self, _limit: Option<RateLimit>) {
+        // libwayland dispatches all the requests it reads
+    }
+
+    pub fn set_malformed_request_policy(&self, _policy: MalformedRequestPolicy) {
+        // libwayland always disconnects the client
+    }
+
+    pub fn set_kill_policy(&self, _policy: KillPolicy) {
+        // libwayland always disconnects the client
+    }
+
+    pub fn set_message_limits(&self, _limits: MessageLimits) {
+        // the limits of libwayland are fixed
+    }
+
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.state.lock().unwrap().set_protocol_logger(logger)
+    }
+
+    pub fn get_client_metrics(&self, id: InnerClientId) -> Result<Metrics, InvalidId> {
+        self.state.lock().unwrap().get_client_metrics(id)
+    }
+
+    pub fn get_client_io_stats(&self, id: InnerClientId) -> Result<IoStats, InvalidId> {
+        self.state.lock().unwrap().get_client_io_stats(id)
+    }
+
+    pub fn io_stats(&self) -> IoStats {
+        self.state.lock().unwrap().io_stats()
+    }
+
     pub fn with_all_clients(&self, mut f: impl FnMut(ClientId)) {
         self.state.lock().unwrap().with_all_clients(&mut f)
     }
@@ -667,10 +960,22 @@ impl InnerHandle {
         self.state.lock().unwrap().post_error(object_id, error_code, message)
     }
 
+    pub fn set_object_paused(&self, id: InnerObjectId, _paused: bool) -> Result<(), PauseError> {
+        // libwayland dispatches the requests as it reads them
+        if !id.alive.load(Ordering::Acquire) {
+            return Err(PauseError::InvalidId);
+        }
+        Err(PauseError::Unsupported)
+    }
+
     pub fn kill_client(&self, client_id: InnerClientId, reason: DisconnectReason) {
         self.state.lock().unwrap().kill_client(client_id, reason)
     }
 
+    pub fn post_implementation_error(&self, client_id: InnerClientId, message: CString) {
+        self.state.lock().unwrap().post_implementation_error(client_id, message)
+    }
+
     pub fn create_global<D: 'static>(
         &self,
         interface: &'static Interface,
@@ -733,6 +1038,46 @@ impl InnerHandle {
         id
     }
 
+    pub fn with_all_resources_for(
+        &self,
+        client_id: InnerClientId,
+        mut f: &mut dyn FnMut(*mut wl_resource),
+    ) -> Result<(), InvalidId> {
+        if !client_id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+
+        unsafe extern "C" fn iterator_func(
+            resource: *mut wl_resource,
+            user_data: *mut c_void,
+        ) -> c_int {
+            // the closure is passed by double-reference because we only have 1 pointer of user data
+            let closure = unsafe { &mut *(user_data as *mut &mut dyn FnMut(*mut wl_resource)) };
+            closure(resource);
+            // return WL_ITERATOR_CONTINUE
+            1
+        }
+
+        unsafe {
+            ffi_dispatch!(
+                wayland_server_handle(),
+                wl_client_for_each_resource,
+                client_id.ptr,
+                iterator_func,
+                &mut f as *mut _ as *mut c_void,
+            )
+        }
+
+        Ok(())
+    }
+
+    pub fn client_fd(&self, client_id: InnerClientId) -> Result<RawFd, InvalidId> {
+        if !client_id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+        Ok(unsafe { ffi_dispatch!(wayland_server_handle(), wl_client_get_fd, client_id.ptr) })
+    }
+
     pub fn disable_global<D: 'static>(&self, id: InnerGlobalId) {
         // check that `D` is correct
         {
@@ -787,10 +1132,100 @@ impl InnerHandle {
         });
     }
 
+    pub fn remove_global_after<D: 'static>(
+        &self,
+        id: InnerGlobalId,
+        grace: Duration,
+        finalize: GlobalFinalizer<Handle, D>,
+    ) {
+        self.disable_global::<D>(id.clone());
+
+        let mut state = self.state.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::remove_global_after().");
+        state.pending_removals.schedule(Instant::now() + grace, id, finalize);
+    }
+
     pub fn global_info(&self, id: InnerGlobalId) -> Result<GlobalInfo, InvalidId> {
         self.state.lock().unwrap().global_info(id)
     }
 
+    pub fn add_client_destroy_listener<D: 'static>(
+        &self,
+        client_id: InnerClientId,
+        listener: ClientDestroyCallback<Handle, ClientId, D>,
+    ) -> Result<DestroyListenerId, InvalidId> {
+        let mut state = self.state.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::add_client_destroy_listener().");
+
+        if !client_id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+        // Safety: the client is alive
+        let (destroyed, reason) = match unsafe { client_user_data(client_id.ptr) } {
+            Some(udata) => unsafe { ((*udata).destroyed.clone(), (*udata).reason.clone()) },
+            None => return Err(InvalidId),
+        };
+        Ok(state.destroy_listeners.add((ClientId { id: client_id }, destroyed, reason), listener))
+    }
+
+    pub fn remove_client_destroy_listener(&self, id: DestroyListenerId) -> bool {
+        self.state.lock().unwrap().remove_client_destroy_listener(id)
+    }
+
+    pub fn add_listener<D: 'static>(
+        &self,
+        fd: OwnedFd,
+        close_fd: Option<OwnedFd>,
+        accept: AcceptCallback<D, dyn ClientData>,
+    ) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::add_listener().");
+
+        let listener = Listener::new(fd, close_fd, accept)?;
+        let mut entry = Box::new(ListenerEntry { listener, sources: Vec::new() });
+        let entry_ptr = &mut *entry as *mut ListenerEntry<D> as *mut c_void;
+        let evl = unsafe {
+            ffi_dispatch!(wayland_server_handle(), wl_display_get_event_loop, state.display)
+        };
+        let mut fds = vec![(entry.listener.fd(), WL_EVENT_READABLE, listener_cb::<D> as _)];
+        if let Some(close_fd) = entry.listener.close_fd() {
+            // a hangup is always reported
+            fds.push((close_fd, 0, listener_close_cb::<D> as _));
+        }
+        for (fd, mask, cb) in fds {
+            let source = unsafe {
+                ffi_dispatch!(
+                    wayland_server_handle(),
+                    wl_event_loop_add_fd,
+                    evl,
+                    fd,
+                    mask,
+                    cb,
+                    entry_ptr
+                )
+            };
+            if source.is_null() {
+                let err = std::io::Error::last_os_error();
+                for source in entry.sources {
+                    unsafe {
+                        ffi_dispatch!(wayland_server_handle(), wl_event_source_remove, source)
+                    };
+                }
+                return Err(err);
+            }
+            entry.sources.push(source);
+        }
+
+        state.listeners.push(entry);
+        Ok(())
+    }
+
     /// Returns the handler which manages the visibility and notifies when a client has bound the global.
     pub fn get_global_handler<D: 'static>(
         &self,
@@ -807,16 +1242,58 @@ impl InnerHandle {
         }
 
         let udata = unsafe {
-            Box::from_raw(ffi_dispatch!(wayland_server_handle(), wl_global_get_user_data, id.ptr)
+            &*(ffi_dispatch!(wayland_server_handle(), wl_global_get_user_data, id.ptr)
                 as *mut GlobalUserData<D>)
         };
         Ok(udata.handler.clone())
     }
 
+    pub fn set_global_handler<D: 'static>(
+        &self,
+        id: InnerGlobalId,
+        handler: Arc<dyn GlobalHandler<D>>,
+    ) -> Result<(), InvalidId> {
+        let mut state = self.state.lock().unwrap();
+        // Keep this guard alive while the code is run to protect the C state
+        let _state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter passed to Handle::set_global_handler().");
+
+        if !id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+
+        let udata = unsafe {
+            &mut *(ffi_dispatch!(wayland_server_handle(), wl_global_get_user_data, id.ptr)
+                as *mut GlobalUserData<D>)
+        };
+        udata.handler = handler;
+        Ok(())
+    }
+
     pub fn flush(&mut self, client: Option<ClientId>) -> std::io::Result<()> {
         self.state.lock().unwrap().flush(client)
     }
 
+    pub fn flush_with_callback(
+        &mut self,
+        client: ClientId,
+        callback: DrainCallback,
+    ) -> Result<(), InvalidId> {
+        if !client.id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+        let _ = self.state.lock().unwrap().flush(Some(client));
+        // libwayland does not report whether the events were all written
+        callback.invoke();
+        Ok(())
+    }
+
+    pub fn barrier(&mut self, client: ClientId, callback: DrainCallback) -> Result<(), InvalidId> {
+        // nor whether the client read them
+        self.flush_with_callback(client, callback)
+    }
+
     pub fn display_ptr(&self) -> *mut wl_display {
         self.state.lock().unwrap().display_ptr()
     }
@@ -831,7 +1308,16 @@ pub(crate) trait ErasedState: downcast_rs::Downcast {
     ) -> std::io::Result<InnerClientId>;
     fn get_client(&self, id: InnerObjectId) -> Result<ClientId, InvalidId>;
     fn get_client_credentials(&self, id: InnerClientId) -> Result<Credentials, InvalidId>;
+    fn get_client_security_label(&self, id: InnerClientId) -> Result<Option<String>, InvalidId>;
+    fn remove_client_destroy_listener(&mut self, id: DestroyListenerId) -> bool;
     fn get_client_data(&self, id: InnerClientId) -> Result<Arc<dyn ClientData>, InvalidId>;
+    fn enable_metrics(&mut self);
+    fn metrics_enabled(&self) -> bool;
+    fn set_protocol_logger(&mut self, logger: Option<Arc<dyn ProtocolLogger>>);
+    fn protocol_logger(&self) -> Option<Arc<dyn ProtocolLogger>>;
+    fn get_client_metrics(&self, id: InnerClientId) -> Result<Metrics, InvalidId>;
+    fn get_client_io_stats(&self, id: InnerClientId) -> Result<IoStats, InvalidId>;
+    fn io_stats(&self) -> IoStats;
     fn with_all_clients(&self, f: &mut dyn FnMut(ClientId));
     fn with_all_objects_for(
         &self,
@@ -851,6 +1337,7 @@ pub(crate) trait ErasedState: downcast_rs::Downcast {
     fn send_event(&mut self, msg: Message<ObjectId, RawFd>) -> Result<(), InvalidId>;
     fn post_error(&mut self, object_id: InnerObjectId, error_code: u32, message: CString);
     fn kill_client(&mut self, client_id: InnerClientId, reason: DisconnectReason);
+    fn post_implementation_error(&mut self, client_id: InnerClientId, message: CString);
     fn global_info(&self, id: InnerGlobalId) -> Result<GlobalInfo, InvalidId>;
     fn is_known_global(&self, global_ptr: *const wl_global) -> bool;
     fn flush(&mut self, client: Option<ClientId>) -> std::io::Result<()>;
@@ -890,7 +1377,9 @@ impl<D: 'static> ErasedState for State<D> {
             return Err(std::io::Error::last_os_error());
         }
 
-        Ok(unsafe { init_client(ret, data) })
+        let id = unsafe { init_client(ret, data.clone(), self.metrics, self.logger.clone()) };
+        data.initialized(ClientId { id: id.clone() });
+        Ok(id)
     }
 
     fn get_client(&self, id: InnerObjectId) -> Result<ClientId, InvalidId> {
@@ -940,19 +1429,104 @@ impl<D: 'static> ErasedState for State<D> {
         Ok(creds)
     }
 
+    fn get_client_security_label(&self, id: InnerClientId) -> Result<Option<String>, InvalidId> {
+        if !id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+
+        let fd = unsafe { ffi_dispatch!(wayland_server_handle(), wl_client_get_fd, id.ptr) };
+        Ok(crate::types::server::peer_security_label(fd))
+    }
+
+    fn remove_client_destroy_listener(&mut self, id: DestroyListenerId) -> bool {
+        self.destroy_listeners.remove(id)
+    }
+
+    fn enable_metrics(&mut self) {
+        self.metrics = true;
+        self.with_all_clients(&mut |client| {
+            // Safety: the client was listed by libwayland and is thus alive
+            if let Some(udata) = unsafe { client_user_data(client.id.ptr) } {
+                unsafe { &*udata }.metrics.lock().unwrap().get_or_insert_with(Metrics::default);
+            }
+        });
+    }
+
+    fn metrics_enabled(&self) -> bool {
+        self.metrics
+    }
+
+    fn set_protocol_logger(&mut self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.with_all_clients(&mut |client| {
+            // Safety: the client was listed by libwayland and is thus alive
+            if let Some(udata) = unsafe { client_user_data(client.id.ptr) } {
+                *unsafe { &*udata }.logger.lock().unwrap() = logger.clone();
+            }
+        });
+        self.logger = logger;
+    }
+
+    fn protocol_logger(&self) -> Option<Arc<dyn ProtocolLogger>> {
+        self.logger.clone()
+    }
+
+    fn get_client_metrics(&self, id: InnerClientId) -> Result<Metrics, InvalidId> {
+        if !id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+
+        let data = unsafe {
+            match client_user_data(id.ptr) {
+                Some(ptr) => &*ptr,
+                None => return Err(InvalidId),
+            }
+        };
+
+        Ok(data.metrics.lock().unwrap().clone().unwrap_or_default())
+    }
+
+    fn get_client_io_stats(&self, id: InnerClientId) -> Result<IoStats, InvalidId> {
+        if !id.alive.load(Ordering::Acquire) {
+            return Err(InvalidId);
+        }
+
+        let data = unsafe {
+            match client_user_data(id.ptr) {
+                Some(ptr) => &*ptr,
+                None => return Err(InvalidId),
+            }
+        };
+
+        let mut live_objects = 0;
+        self.with_all_objects_for(id, &mut |_| live_objects += 1)?;
+        Ok(IoStats { live_objects, ..*data.io_stats.lock().unwrap() })
+    }
+
+    fn io_stats(&self) -> IoStats {
+        let mut total = IoStats::default();
+        self.with_all_clients(&mut |client| {
+            if let Ok(stats) = self.get_client_io_stats(client.id) {
+                total += stats;
+            }
+        });
+        total
+    }
+
     fn with_all_clients(&self, f: &mut dyn FnMut(ClientId)) {
-        let mut client_list = unsafe {
+        let client_list = unsafe {
             ffi_dispatch!(wayland_server_handle(), wl_display_get_client_list, self.display)
         };
         unsafe {
-            while (*client_list).next != client_list {
+            // the list head is not a client, iterate until we come back to it
+            let mut client_link = (*client_list).next;
+            while client_link != client_list {
                 let client =
-                    ffi_dispatch!(wayland_server_handle(), wl_client_from_link, client_list);
+                    ffi_dispatch!(wayland_server_handle(), wl_client_from_link, client_link);
                 if let Some(id) = client_id_from_ptr(client) {
                     f(ClientId { id })
                 }
 
-                client_list = (*client_list).next;
+                client_link = (*client_link).next;
             }
         }
     }
@@ -1048,7 +1622,7 @@ impl<D: 'static> ErasedState for State<D> {
 
     fn send_event(
         &mut self,
-        Message { sender_id: ObjectId { id }, opcode, args }: Message<ObjectId, RawFd>,
+        Message { sender_id: ObjectId { id }, opcode, mut args }: Message<ObjectId, RawFd>,
     ) -> Result<(), InvalidId> {
         if !id.alive.load(Ordering::Acquire) || id.ptr.is_null() {
             return Err(InvalidId);
@@ -1068,6 +1642,30 @@ impl<D: 'static> ErasedState for State<D> {
             );
         }
 
+        // Safety: the resource is alive, so its client is valid
+        let client =
+            unsafe { ffi_dispatch!(wayland_server_handle(), wl_resource_get_client, id.ptr) };
+        if let Some(udata) = unsafe { client_user_data(client) } {
+            let udata = unsafe { &*udata };
+            if let Some(ref mut metrics) = *udata.metrics.lock().unwrap() {
+                metrics.count_sent(id.interface);
+            }
+            udata.io_stats.lock().unwrap().messages_sent += 1;
+            let logger = udata.logger.lock().unwrap().clone();
+            if let Some(logger) = logger {
+                let client_id =
+                    ClientId { id: InnerClientId { ptr: client, alive: udata.alive.clone() } };
+                let msg = Message { sender_id: ObjectId { id: id.clone() }, opcode, args };
+                logger.log(
+                    client_id,
+                    Direction::Sent,
+                    id.interface,
+                    &msg.to_logged(ObjectId::protocol_id),
+                );
+                args = msg.args;
+            }
+        }
+
         let mut argument_list = SmallVec::<[wl_argument; 4]>::with_capacity(args.len());
         let mut arg_interfaces = message_desc.arg_interfaces.iter();
         for (i, arg) in args.iter().enumerate() {
@@ -1179,17 +1777,8 @@ impl<D: 'static> ErasedState for State<D> {
         let client =
             unsafe { ffi_dispatch!(wayland_server_handle(), wl_resource_get_client, id.ptr) };
         let client_id = unsafe { client_id_from_ptr(client) }.unwrap();
-        // mark the client as dead
-        client_id.alive.store(false, Ordering::Release);
-
         unsafe {
-            ffi_dispatch!(
-                wayland_server_handle(),
-                wl_resource_post_error,
-                id.ptr,
-                error_code,
-                message.as_ptr()
-            )
+            post_error_and_notify(client_id, id.ptr, id.id, id.interface, error_code, message)
         }
     }
 
@@ -1198,15 +1787,34 @@ impl<D: 'static> ErasedState for State<D> {
             return;
         }
         if let Some(udata) = unsafe { client_user_data(client_id.ptr) } {
-            let udata = unsafe { &*udata };
-            udata.alive.store(false, Ordering::Release);
-            udata.data.disconnected(ClientId { id: client_id.clone() }, reason);
+            unsafe { &*udata }.disconnected(client_id.clone(), reason);
         }
 
-        // wl_client_destroy invokes destructors
-        PENDING_DESTRUCTORS.set(&(&mut self.pending_destructors as *mut _ as *mut _), || unsafe {
-            ffi_dispatch!(wayland_server_handle(), wl_client_destroy, client_id.ptr);
-        });
+        self.destroy_client(client_id.ptr);
+    }
+
+    fn post_implementation_error(&mut self, client_id: InnerClientId, message: CString) {
+        if !client_id.alive.load(Ordering::Acquire) {
+            return;
+        }
+        // This is what wl_client_post_implementation_error does, but it is not available in all
+        // the versions of libwayland we support
+        let display = unsafe {
+            ffi_dispatch!(wayland_server_handle(), wl_client_get_object, client_id.ptr, 1)
+        };
+        if display.is_null() {
+            return;
+        }
+        unsafe {
+            post_error_and_notify(
+                client_id,
+                display,
+                1,
+                &crate::core_interfaces::WL_DISPLAY_INTERFACE,
+                WL_DISPLAY_ERROR_IMPLEMENTATION,
+                message,
+            )
+        }
     }
 
     fn global_info(&self, id: InnerGlobalId) -> Result<GlobalInfo, InvalidId> {
@@ -1243,7 +1851,9 @@ impl<D: 'static> ErasedState for State<D> {
                 },
             );
         }
-        if !self.pending_destructors.is_empty() {
+        let destroyed_clients =
+            self.destroy_listeners.any(|(_, destroyed, _)| destroyed.load(Ordering::Acquire));
+        if !self.pending_destructors.is_empty() || destroyed_clients {
             // Arm the timer to trigger a wakeup of the inner event loop in 1ms, so that the user
             // is indicated to call dispatch_clients() and have the destructors run
             unsafe {
@@ -1263,9 +1873,23 @@ impl<D: 'static> ErasedState for State<D> {
     }
 }
 
-unsafe fn init_client(client: *mut wl_client, data: Arc<dyn ClientData>) -> InnerClientId {
+unsafe fn init_client(
+    client: *mut wl_client,
+    data: Arc<dyn ClientData>,
+    metrics: bool,
+    logger: Option<Arc<dyn ProtocolLogger>>,
+) -> InnerClientId {
     let alive = Arc::new(AtomicBool::new(true));
-    let client_data = Box::into_raw(Box::new(ClientUserData { alive: alive.clone(), data }));
+    let metrics = Mutex::new(if metrics { Some(Metrics::default()) } else { None });
+    let client_data = Box::into_raw(Box::new(ClientUserData {
+        alive: alive.clone(),
+        destroyed: Arc::new(AtomicBool::new(false)),
+        reason: Arc::new(Mutex::new(None)),
+        data,
+        metrics,
+        io_stats: Mutex::new(IoStats::default()),
+        logger: Mutex::new(logger),
+    }));
 
     let listener = signal::rust_listener_create(client_destroy_notify);
     // Safety: we just created listener and client_data, they are valid
@@ -1304,6 +1928,55 @@ unsafe fn client_user_data(client: *mut wl_client) -> Option<*mut ClientUserData
     }
 }
 
+const WL_DISPLAY_ERROR_IMPLEMENTATION: u32 = 3;
+
+/// Post a protocol error on a resource and notify the client data of the disconnection
+///
+/// The client is destroyed by libwayland once the error has been flushed to it, at which point we
+/// have already notified its data.
+unsafe fn post_error_and_notify(
+    client_id: InnerClientId,
+    resource: *mut wl_resource,
+    object_id: u32,
+    interface: &'static Interface,
+    error_code: u32,
+    message: CString,
+) {
+    // mark the client as dead
+    client_id.alive.store(false, Ordering::Release);
+
+    if let Some(udata) = unsafe { client_user_data(client_id.ptr) } {
+        unsafe { &*udata }.disconnected(
+            client_id,
+            DisconnectReason::ProtocolError(crate::protocol::ProtocolError {
+                code: error_code,
+                object_id,
+                object_interface: interface.name.into(),
+                message: message.to_string_lossy().into(),
+            }),
+        );
+    }
+
+    unsafe { post_error(resource, error_code, message) }
+}
+
+/// Post a protocol error on a resource
+///
+/// Safety: the resource must be valid.
+unsafe fn post_error(resource: *mut wl_resource, error_code: u32, message: CString) {
+    // the message must not be interpreted as a format string
+    unsafe {
+        ffi_dispatch!(
+            wayland_server_handle(),
+            wl_resource_post_error,
+            resource,
+            error_code,
+            b"%s\0".as_ptr() as *const std::os::raw::c_char,
+            message.as_ptr()
+        )
+    }
+}
+
 unsafe extern "C" fn client_destroy_notify(listener: *mut wl_listener, client_ptr: *mut c_void) {
     // Safety: if this function is invoked by libwayland its arguments must be valid
     let data = unsafe {
@@ -1312,13 +1985,12 @@ unsafe extern "C" fn client_destroy_notify(listener: *mut wl_listener, client_pt
     unsafe {
         signal::rust_listener_destroy(listener);
     }
+    // the destroy listeners run after the destructors of the resources, at the end of the dispatch
+    data.destroyed.store(true, Ordering::Release);
     // only notify the killing if it was not already
     if data.alive.load(Ordering::Acquire) {
-        data.alive.store(false, Ordering::Release);
-        data.data.disconnected(
-            ClientId {
-                id: InnerClientId { ptr: client_ptr as *mut wl_client, alive: data.alive.clone() },
-            },
+        data.disconnected(
+            InnerClientId { ptr: client_ptr as *mut wl_client, alive: data.alive.clone() },
             DisconnectReason::ConnectionClosed,
         );
     }
@@ -1370,6 +2042,53 @@ unsafe extern "C" fn global_bind<D: 'static>(
     })
 }
 
+extern "C" fn listener_cb<D: 'static>(_: c_int, _: u32, entry: *mut c_void) -> c_int {
+    // Safety: the pointer is the listener entry of this source, kept alive by the state
+    let listener = unsafe { &mut (*(entry as *mut ListenerEntry<D>)).listener };
+    // the event loop is only dispatched by dispatch_all_clients(), which provides the data
+    HANDLE.with(|&(ref state_arc, data_ptr)| {
+        // Safety: the data_ptr is a valid pointer that live outside code put there
+        let data = unsafe { &mut *(data_ptr as *mut D) };
+        loop {
+            let stream = match listener.next_connection() {
+                Ok(Some(stream)) => stream,
+                Ok(None) => break,
+                Err(e) => {
+                    crate::log_warn!("Failed to accept a client connection: {}", e);
+                    break;
+                }
+            };
+            if let Some(client_data) = (listener.accept)(data, &stream) {
+                if let Err(e) = state_arc.lock().unwrap().insert_client(stream, client_data) {
+                    crate::log_warn!("Failed to insert an accepted client: {}", e);
+                }
+            }
+        }
+    });
+    0
+}
+
+extern "C" fn listener_close_cb<D: 'static>(_: c_int, _: u32, entry: *mut c_void) -> c_int {
+    HANDLE.with(|(state_arc, _)| {
+        let mut state = state_arc.lock().unwrap();
+        let state = (&mut *state as &mut dyn ErasedState)
+            .downcast_mut::<State<D>>()
+            .expect("Wrong type parameter for a listener.");
+        let index = state
+            .listeners
+            .iter()
+            .position(|listener| std::ptr::eq(&**listener, entry as *const ListenerEntry<D>));
+        if let Some(index) = index {
+            let entry = state.listeners.remove(index);
+            // libwayland skips the events of removed sources, the entry can be dropped right away
+            for source in entry.sources {
+                unsafe { ffi_dispatch!(wayland_server_handle(), wl_event_source_remove, source) };
+            }
+        }
+    });
+    0
+}
+
 unsafe extern "C" fn global_filter<D: 'static>(
     client: *const wl_client,
     global: *const wl_global,
@@ -1549,7 +2268,20 @@ unsafe extern "C" fn resource_dispatcher<D: 'static>(
     };
 
     // Safety: the client ptr is valid and provided by libwayland
-    let client_id = unsafe { client_id_from_ptr(client) }.unwrap();
+    let client_udata = unsafe { &*client_user_data(client).unwrap() };
+    if let Some(ref mut metrics) = *client_udata.metrics.lock().unwrap() {
+        metrics.count_received(interface);
+    }
+    client_udata.io_stats.lock().unwrap().messages_received += 1;
+    let client_id = InnerClientId { ptr: client, alive: client_udata.alive.clone() };
+
+    let message =
+        Message { sender_id: object_id.clone(), opcode: opcode as u16, args: parsed_args };
+    let logger = client_udata.logger.lock().unwrap().clone();
+    if let Some(logger) = logger {
+        let logged = message.to_logged(ObjectId::protocol_id);
+        logger.log(ClientId { id: client_id.clone() }, Direction::Received, interface, &logged);
+    }
 
     let ret = HANDLE.with(|&(ref state_arc, data_ptr)| {
         // Safety: the data pointer has been set by outside code and is valid
@@ -1558,7 +2290,7 @@ unsafe extern "C" fn resource_dispatcher<D: 'static>(
             &Handle { handle: InnerHandle { state: state_arc.clone() } },
             data,
             ClientId { id: client_id.clone() },
-            Message { sender_id: object_id.clone(), opcode: opcode as u16, args: parsed_args },
+            message,
         )
     });
 
@@ -1627,6 +2359,33 @@ unsafe extern "C" fn resource_destructor<D: 'static>(resource: *mut wl_resource)
     }
 }
 
+/// Run `f` with the handle and data of the ongoing dispatch of the backend `handle` refers to
+///
+/// Returns `None` if this backend is not currently being dispatched by
+/// [`Backend::dispatch_all_clients`].
+///
+/// # Safety
+///
+/// `handle` must have been obtained from a `Backend<D>`.
+pub(crate) unsafe fn with_dispatch_data<D: 'static, T>(
+    handle: &WeakInnerHandle,
+    f: impl FnOnce(&Handle, &mut D) -> T,
+) -> Option<T> {
+    if !HANDLE.is_set() {
+        return None;
+    }
+    HANDLE.with(|&(ref state_arc, data_ptr)| {
+        let same_backend =
+            handle.state.as_ptr() as *const () == Arc::as_ptr(state_arc) as *const ();
+        if data_ptr.is_null() || !same_backend {
+            return None;
+        }
+        // Safety: the data pointer has been set by dispatch_all_clients of this very backend
+        let data = unsafe { &mut *(data_ptr as *mut D) };
+        Some(f(&Handle { handle: InnerHandle { state: state_arc.clone() } }, data))
+    })
+}
+
 #[cfg(feature = "log")]
 extern "C" {
     fn wl_log_trampoline_to_rust_server(fmt: *const std::os::raw::c_char, list: *const c_void);
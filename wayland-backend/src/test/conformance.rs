@@ -0,0 +1,712 @@
+//! Core protocol semantics that all backend combinations must agree on
+
+use std::{
+    ffi::{CStr, CString},
+    os::unix::io::{AsRawFd, RawFd},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use super::*;
+use crate::protocol::{Interface, MessageCounts, Metrics};
+
+// Large enough for the messages to be close to the 4096 bytes limit. libwayland does not send
+// messages slightly smaller than the limit.
+const BIG_ARRAY_LEN: usize = 4000;
+
+fn big_array() -> Vec<u8> {
+    (0..BIG_ARRAY_LEN).map(|i| i as u8).collect()
+}
+
+struct ServerData<Id> {
+    object: Mutex<Option<Id>>,
+    send_big: bool,
+    received_big: AtomicBool,
+}
+
+impl<Id> ServerData<Id> {
+    fn new(send_big: bool) -> Self {
+        Self { object: Mutex::new(None), send_big, received_big: AtomicBool::new(false) }
+    }
+}
+
+macro_rules! impl_server_objectdata {
+    ($server_backend:tt) => {
+        impl $server_backend::ObjectData<()> for ServerData<$server_backend::ObjectId> {
+            fn request(
+                self: Arc<Self>,
+                _: &$server_backend::Handle,
+                _: &mut (),
+                _: $server_backend::ClientId,
+                msg: Message<$server_backend::ObjectId, OwnedFd>,
+            ) -> Option<Arc<dyn $server_backend::ObjectData<()>>> {
+                if msg.opcode == 0 {
+                    if let [.., Argument::Array(ref a), Argument::Str(Some(ref s)), Argument::Fd(ref fd)] =
+                        msg.args[..]
+                    {
+                        assert_eq!(**a, big_array());
+                        assert_eq!(&***s, CStr::from_bytes_with_nul(b"big\0").unwrap());
+                        assert!(fd.as_raw_fd() >= 0);
+                        self.received_big.store(true, Ordering::SeqCst);
+                    }
+                }
+                if msg.args.iter().any(|arg| matches!(arg, Argument::NewId(_))) {
+                    Some(Arc::new(DoNothingData))
+                } else {
+                    None
+                }
+            }
+
+            fn destroyed(
+                self: Arc<Self>,
+                _: &$server_backend::Handle,
+                _: &mut (),
+                _: $server_backend::ClientId,
+                _: $server_backend::ObjectId,
+            ) {
+            }
+        }
+
+        impl $server_backend::GlobalHandler<()> for ServerData<$server_backend::ObjectId> {
+            fn bind(
+                self: Arc<Self>,
+                handle: &$server_backend::Handle,
+                _: &mut (),
+                _: $server_backend::ClientId,
+                _: $server_backend::GlobalId,
+                object_id: $server_backend::ObjectId,
+            ) -> Arc<dyn $server_backend::ObjectData<()>> {
+                if self.send_big {
+                    handle
+                        .send_event(message!(
+                            object_id.clone(),
+                            0,
+                            [
+                                Argument::Uint(1),
+                                Argument::Int(2),
+                                Argument::Fixed(3),
+                                Argument::Array(Box::new(big_array())),
+                                Argument::Str(Some(Box::new(CString::new("big").unwrap()))),
+                                Argument::Fd(1), // stdout
+                            ],
+                        ))
+                        .unwrap();
+                }
+                *self.object.lock().unwrap() = Some(object_id);
+                self
+            }
+        }
+    };
+}
+
+impl_server_objectdata!(server_rs);
+impl_server_objectdata!(server_sys);
+
+#[derive(Default)]
+struct ClientData {
+    events: AtomicUsize,
+    received_big: AtomicBool,
+    destroyed: AtomicBool,
+}
+
+macro_rules! impl_client_objectdata {
+    ($client_backend:tt) => {
+        impl $client_backend::ObjectData for ClientData {
+            fn event(
+                self: Arc<Self>,
+                _: &$client_backend::Backend,
+                msg: Message<$client_backend::ObjectId, OwnedFd>,
+            ) -> Option<Arc<dyn $client_backend::ObjectData>> {
+                if let [.., Argument::Array(ref a), Argument::Str(Some(ref s)), Argument::Fd(ref fd)] =
+                    msg.args[..]
+                {
+                    assert_eq!(**a, big_array());
+                    assert_eq!(&***s, CStr::from_bytes_with_nul(b"big\0").unwrap());
+                    assert!(fd.as_raw_fd() >= 0);
+                    self.received_big.store(true, Ordering::SeqCst);
+                }
+                self.events.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+
+            fn destroyed(&self, _: $client_backend::ObjectId) {
+                self.destroyed.store(true, Ordering::SeqCst);
+            }
+        }
+    };
+}
+
+impl_client_objectdata!(client_rs);
+impl_client_objectdata!(client_sys);
+
+struct SyncData(AtomicBool);
+
+macro_rules! impl_sync_objectdata {
+    ($client_backend:tt) => {
+        impl $client_backend::ObjectData for SyncData {
+            fn event(
+                self: Arc<Self>,
+                _: &$client_backend::Backend,
+                _: Message<$client_backend::ObjectId, OwnedFd>,
+            ) -> Option<Arc<dyn $client_backend::ObjectData>> {
+                self.0.store(true, Ordering::SeqCst);
+                None
+            }
+
+            fn destroyed(&self, _: $client_backend::ObjectId) {}
+        }
+    };
+}
+
+impl_sync_objectdata!(client_rs);
+impl_sync_objectdata!(client_sys);
+
+// Bind the test global with the version 5 and the given client data
+macro_rules! bind_test_global {
+    ($client:expr, $client_data:expr) => {{
+        let registry_id = $client
+            .send_request(
+                message!(
+                    $client.display_id(),
+                    1,
+                    [Argument::NewId(client_backend::ObjectId::null())]
+                ),
+                Some(Arc::new(DoNothingData)),
+                Some((&interfaces::WL_REGISTRY_INTERFACE, 1)),
+            )
+            .unwrap();
+        $client
+            .send_request(
+                message!(
+                    registry_id,
+                    0,
+                    [
+                        Argument::Uint(1),
+                        Argument::Str(Some(Box::new(
+                            CString::new(interfaces::TEST_GLOBAL_INTERFACE.name.as_bytes())
+                                .unwrap(),
+                        ))),
+                        Argument::Uint(5),
+                        Argument::NewId(client_backend::ObjectId::null()),
+                    ],
+                ),
+                Some($client_data),
+                Some((&interfaces::TEST_GLOBAL_INTERFACE, 5)),
+            )
+            .unwrap()
+    }};
+}
+
+// Send a wl_display.sync, and process everything until its callback is done, returning the
+// protocol id the callback had
+macro_rules! roundtrip {
+    ($client:expr, $server:expr) => {{
+        let sync_data = Arc::new(SyncData(AtomicBool::new(false)));
+        let callback_id = $client
+            .send_request(
+                message!(
+                    $client.display_id(),
+                    0,
+                    [Argument::NewId(client_backend::ObjectId::null())]
+                ),
+                Some(sync_data.clone()),
+                Some((&interfaces::WL_CALLBACK_INTERFACE, 1)),
+            )
+            .unwrap();
+        $client.flush().unwrap();
+        $server.dispatch_all_clients(&mut ()).unwrap();
+        $server.flush(None).unwrap();
+        while !sync_data.0.load(Ordering::SeqCst) {
+            $client.prepare_read().unwrap().read().unwrap();
+        }
+        callback_id.protocol_id()
+    }};
+}
+
+// An id is only reused once the server acknowledged the destruction of its previous object
+expand_test!(id_reuse_after_delete_id, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(false)),
+    );
+    let test_global_id = bind_test_global!(client, Arc::new(ClientData::default()));
+
+    let create_secondary = || {
+        client
+            .send_request(
+                message!(
+                    test_global_id.clone(),
+                    1,
+                    [Argument::NewId(client_backend::ObjectId::null())]
+                ),
+                Some(Arc::new(DoNothingData)),
+                Some((&interfaces::SECONDARY_INTERFACE, 5)),
+            )
+            .unwrap()
+    };
+
+    let first = create_secondary();
+    client.send_request(message!(first.clone(), 0, []), None, None).unwrap();
+
+    // the server did not send delete_id yet
+    let second = create_secondary();
+    assert_ne!(second.protocol_id(), first.protocol_id());
+
+    let callback = roundtrip!(client, server);
+
+    // the ids of the first secondary and of the callback are now free, which one is reused first
+    // depends on the backend
+    let third = create_secondary();
+    assert!([first.protocol_id(), callback].contains(&third.protocol_id()));
+    assert_ne!(third, first);
+
+    roundtrip!(client, server);
+});
+
+// Events sent by the server to an object the client already destroyed are discarded, their file
+// descriptors included, without disturbing the following messages
+expand_test!(zombie_object_events, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    let server_data = Arc::new(ServerData::new(false));
+    let client_data = Arc::new(ClientData::default());
+    server.handle().create_global(&interfaces::TEST_GLOBAL_INTERFACE, 5, server_data.clone());
+    let test_global_id = bind_test_global!(client, client_data.clone());
+    roundtrip!(client, server);
+
+    // the client destroys the object while the server sends an event to it
+    client.send_request(message!(test_global_id, 4, []), None, None).unwrap();
+    assert!(client_data.destroyed.load(Ordering::SeqCst));
+    client.flush().unwrap();
+
+    let server_object = server_data.object.lock().unwrap().clone().unwrap();
+    server
+        .handle()
+        .send_event(message!(
+            server_object,
+            0,
+            [
+                Argument::Uint(1),
+                Argument::Int(2),
+                Argument::Fixed(3),
+                Argument::Array(Box::new(vec![4, 5, 6])),
+                Argument::Str(Some(Box::new(CString::new("zombie").unwrap()))),
+                Argument::Fd(1), // stdout
+            ],
+        ))
+        .unwrap();
+
+    roundtrip!(client, server);
+    assert_eq!(client_data.events.load(Ordering::SeqCst), 0);
+});
+
+// Messages close to the maximum size go through in both directions, along with a file descriptor
+expand_test!(big_messages, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    let server_data = Arc::new(ServerData::new(true));
+    let client_data = Arc::new(ClientData::default());
+    server.handle().create_global(&interfaces::TEST_GLOBAL_INTERFACE, 5, server_data.clone());
+    let test_global_id = bind_test_global!(client, client_data.clone());
+    roundtrip!(client, server);
+    assert!(client_data.received_big.load(Ordering::SeqCst));
+
+    client
+        .send_request(
+            message!(
+                test_global_id,
+                0,
+                [
+                    Argument::Uint(1),
+                    Argument::Int(2),
+                    Argument::Fixed(3),
+                    Argument::Array(Box::new(big_array())),
+                    Argument::Str(Some(Box::new(CString::new("big").unwrap()))),
+                    Argument::Fd(0), // stdin
+                ],
+            ),
+            None,
+            None,
+        )
+        .unwrap();
+    roundtrip!(client, server);
+    assert!(server_data.received_big.load(Ordering::SeqCst));
+});
+
+// The objects created by the client are listed as alive until they are destroyed
+expand_test!(live_objects, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+    assert!(client.live_objects().is_empty());
+
+    server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(false)),
+    );
+    let test_global_id = bind_test_global!(client, Arc::new(ClientData::default()));
+    roundtrip!(client, server);
+
+    let live = client.live_objects();
+    assert_eq!(live.len(), 2);
+    assert_eq!(live[0].interface().name, interfaces::WL_REGISTRY_INTERFACE.name);
+    assert_eq!(live[1], test_global_id);
+
+    client.send_request(message!(test_global_id, 4, []), None, None).unwrap();
+    let live = client.live_objects();
+    assert_eq!(live.len(), 1);
+    assert_eq!(live[0].interface().name, interfaces::WL_REGISTRY_INTERFACE.name);
+});
+
+expand_test!(message_metrics, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(false)),
+    );
+    let test_global_id = bind_test_global!(client, Arc::new(ClientData::default()));
+    roundtrip!(client, server);
+    // nothing is counted until the metrics are enabled
+    assert_eq!(client.metrics(), Metrics::default());
+    assert_eq!(server.handle().get_client_metrics(client_id.clone()).unwrap(), Metrics::default());
+
+    client.enable_metrics();
+    server.handle().enable_metrics();
+    client.send_request(message!(test_global_id, 4, []), None, None).unwrap();
+    roundtrip!(client, server);
+
+    let metrics = client.metrics();
+    assert_eq!(metrics.interface("test_global"), MessageCounts { sent: 1, received: 0 });
+    assert_eq!(metrics.interface("wl_callback"), MessageCounts { sent: 0, received: 1 });
+    assert_eq!(metrics.interface("wl_display").sent, 1);
+    assert_eq!(metrics.total().sent, 2);
+
+    let metrics = server.handle().get_client_metrics(client_id).unwrap();
+    assert_eq!(metrics.interface("test_global"), MessageCounts { sent: 0, received: 1 });
+});
+
+expand_test!(io_stats, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(false)),
+    );
+    let test_global_id = bind_test_global!(client, Arc::new(ClientData::default()));
+    roundtrip!(client, server);
+
+    let client_before = client.io_stats();
+    let server_before = server.handle().get_client_io_stats(client_id.clone()).unwrap();
+    // the registry and the test global, the callback of the roundtrip is gone
+    assert_eq!(client_before.live_objects, 2);
+    assert!(server_before.live_objects >= 1);
+
+    client.send_request(message!(test_global_id, 4, []), None, None).unwrap();
+    roundtrip!(client, server);
+
+    // the request and the sync of the roundtrip
+    let client_after = client.io_stats();
+    assert_eq!(client_after.messages_sent - client_before.messages_sent, 2);
+    assert!(client_after.messages_received > client_before.messages_received);
+    let server_after = server.handle().get_client_io_stats(client_id).unwrap();
+    assert!(server_after.messages_received > server_before.messages_received);
+    assert_eq!(server.handle().io_stats(), server_after);
+});
+
+expand_test!(server_protocol_logger, {
+    use crate::protocol::Direction;
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let log = logged.clone();
+    let logger = move |client: server_backend::ClientId,
+                       direction: Direction,
+                       interface: &'static Interface,
+                       message: &Message<u32, RawFd>| {
+        log.lock().unwrap().push((client, direction, interface.name, message.opcode));
+    };
+    server.handle().set_protocol_logger(Some(Arc::new(logger)));
+
+    server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(true)),
+    );
+    let test_global_id = bind_test_global!(client, Arc::new(ClientData::default()));
+    client.send_request(message!(test_global_id, 4, []), None, None).unwrap();
+    roundtrip!(client, server);
+
+    let log = std::mem::take(&mut *logged.lock().unwrap());
+    assert!(log.iter().all(|(id, ..)| *id == client_id));
+    // the event sent on bind and the request of the client
+    let sent = log.iter().position(|l| (l.1, l.2, l.3) == (Direction::Sent, "test_global", 0));
+    let received =
+        log.iter().position(|l| (l.1, l.2, l.3) == (Direction::Received, "test_global", 4));
+    assert!(sent.unwrap() < received.unwrap());
+
+    // nothing is logged once the logger is removed
+    server.handle().set_protocol_logger(None);
+    roundtrip!(client, server);
+    assert!(logged.lock().unwrap().is_empty());
+});
+
+expand_test!(client_protocol_logger, {
+    use crate::protocol::Direction;
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let log = logged.clone();
+    let logger = move |direction: Direction,
+                       interface: &'static Interface,
+                       message: &Message<u32, RawFd>| {
+        log.lock().unwrap().push((direction, interface.name, message.clone()));
+    };
+    client.set_protocol_logger(Some(Arc::new(logger)));
+
+    server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(true)),
+    );
+    let test_global_id = bind_test_global!(client, Arc::new(ClientData::default()));
+    roundtrip!(client, server);
+
+    let log = std::mem::take(&mut *logged.lock().unwrap());
+    // the new id of the bind request is the one of the created object
+    let bind = log
+        .iter()
+        .find(|(direction, interface, msg)| {
+            (*direction, *interface, msg.opcode) == (Direction::Sent, "wl_registry", 0)
+        })
+        .unwrap();
+    assert!(matches!(
+        bind.2.args[..],
+        [.., Argument::NewId(id)] if id == test_global_id.protocol_id()
+    ));
+    // the event sent by the server on bind, with its file descriptor
+    let event = log
+        .iter()
+        .find(|(direction, interface, _)| {
+            (*direction, *interface) == (Direction::Received, "test_global")
+        })
+        .unwrap();
+    assert_eq!(event.2.sender_id, test_global_id.protocol_id());
+    assert_eq!(event.2.args.len(), 6);
+
+    // nothing is logged once the logger is removed
+    client.set_protocol_logger(None);
+    roundtrip!(client, server);
+    assert!(logged.lock().unwrap().is_empty());
+});
+
+expand_test!(server_transcript, {
+    use crate::debug::{diff_lines, Transcript};
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    let transcript = Arc::new(Transcript::new());
+    server.handle().set_protocol_logger(Some(transcript.clone()));
+
+    server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(true)),
+    );
+    let test_global_id = bind_test_global!(client, Arc::new(ClientData::default()));
+    client.send_request(message!(test_global_id, 4, []), None, None).unwrap();
+    roundtrip!(client, server);
+
+    // libwayland-server does not log the messages of the objects it handles itself
+    let lines = transcript.take_lines();
+    let lines = lines.iter().filter(|line| line.contains("test_global@")).collect::<Vec<_>>();
+    let array = big_array().iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    let expected = [
+        format!(
+            "[client 0] -> test_global@3.many_args_evt(1, 2, 0.01171875, array[{}], \"big\", fd#0)",
+            array
+        ),
+        "[client 0] <- test_global@3.destroy()".into(),
+    ];
+    if let Some(diff) = diff_lines(&expected, &lines) {
+        panic!("unexpected transcript:\n{}", diff);
+    }
+});
+
+expand_test!(global_removal_grace_period, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::<()>::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    let kept = server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(false)),
+    );
+    let kept_finalized = Arc::new(AtomicBool::new(false));
+    let finalized = kept_finalized.clone();
+    server.handle().remove_global_after::<()>(
+        kept.clone(),
+        std::time::Duration::from_secs(3600),
+        move |_, _| finalized.store(true, Ordering::SeqCst),
+    );
+    // the global can still be bound during its grace period
+    bind_test_global!(client, Arc::new(ClientData::default()));
+    roundtrip!(client, server);
+    assert!(client.last_error().is_none());
+    assert!(server.handle().global_info(kept.clone()).unwrap().disabled);
+    assert!(!kept_finalized.load(Ordering::SeqCst));
+
+    let removed = server.handle().create_global(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(ServerData::new(false)),
+    );
+    let removed_finalized = Arc::new(AtomicBool::new(false));
+    let finalized = removed_finalized.clone();
+    server.handle().remove_global_after::<()>(
+        removed.clone(),
+        std::time::Duration::ZERO,
+        move |_, _| finalized.store(true, Ordering::SeqCst),
+    );
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(removed_finalized.load(Ordering::SeqCst));
+    assert!(server.handle().global_info(removed).is_err());
+    assert!(server.handle().global_info(kept).is_ok());
+    assert!(!kept_finalized.load(Ordering::SeqCst));
+});
+
+// a path for a listening socket, unique among the tests running in parallel
+fn socket_path() -> std::path::PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!("wayland-backend-test-{}-{}", std::process::id(), n))
+}
+
+expand_test!(listener_accepts_clients, {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let mut server = server_backend::Backend::<()>::new().unwrap();
+    let path = socket_path();
+    let listener = UnixListener::bind(&path).unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    server
+        .add_listener(OwnedFd::from(listener), move |_, _| {
+            // every other client is refused
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            (n % 2 == 0).then(|| Arc::new(()) as Arc<_>)
+        })
+        .unwrap();
+
+    let accepted_stream = UnixStream::connect(&path).unwrap();
+    let refused_stream = UnixStream::connect(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+    let client = client_backend::Backend::connect(accepted_stream).unwrap();
+    roundtrip!(client, server);
+    assert!(client.last_error().is_none());
+
+    // the connection of a refused client is closed
+    let mut buffer = [0u8; 4];
+    assert_eq!(std::io::Read::read(&mut &refused_stream, &mut buffer).unwrap(), 0);
+
+    // only listening sockets are accepted
+    let (stream, _) = UnixStream::pair().unwrap();
+    let err = server.add_listener(OwnedFd::from(stream), |_, _| None).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+});
+
+expand_test!(listener_close_fd, {
+    use std::os::unix::{
+        io::FromRawFd,
+        net::{UnixListener, UnixStream},
+    };
+
+    let mut server = server_backend::Backend::<()>::new().unwrap();
+    let path = socket_path();
+    let listener = UnixListener::bind(&path).unwrap();
+    let (close_fd, close_write) = nix::unistd::pipe().unwrap();
+    let (close_fd, close_write) =
+        unsafe { (OwnedFd::from_raw_fd(close_fd), OwnedFd::from_raw_fd(close_write)) };
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    server
+        .handle()
+        .add_listener::<(), _>(OwnedFd::from(listener), Some(close_fd), move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Some(Arc::new(()))
+        })
+        .unwrap();
+
+    let _stream = UnixStream::connect(&path).unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    // hanging up the close fd closes the listening socket
+    drop(close_write);
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(UnixStream::connect(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+});
+
+// the rust backends also count what goes through the socket
+#[test]
+fn io_stats_socket() {
+    use client_rs as client_backend;
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_rs::Backend::connect(tx).unwrap();
+    roundtrip!(client, server);
+
+    let client_stats = client.io_stats();
+    let server_stats = server.handle().get_client_io_stats(client_id).unwrap();
+    assert_eq!(client_stats.messages_sent, 1);
+    assert_eq!(server_stats.messages_received, 1);
+    // wl_callback.done and wl_display.delete_id
+    assert_eq!(server_stats.messages_sent, 2);
+    assert_eq!(client_stats.messages_received, 2);
+    assert_eq!(client_stats.bytes_written, 12);
+    assert_eq!(server_stats.bytes_read, client_stats.bytes_written);
+    assert_eq!(client_stats.bytes_read, server_stats.bytes_written);
+    assert!(client_stats.write_calls >= 1 && client_stats.read_calls >= 1);
+    assert!(server_stats.write_calls >= 1 && server_stats.read_calls >= 1);
+    assert_eq!(client_stats.live_objects, 0);
+}
@@ -222,3 +222,105 @@ expand_test!(destructor_client_cleanup, {
 
     assert!(client_data.0.load(Ordering::Acquire));
 });
+
+#[test]
+fn zombie_events_are_discarded() {
+    use std::os::unix::io::AsRawFd;
+    use std::sync::Mutex;
+
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::new().unwrap();
+    let client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_rs::Backend::connect(tx).unwrap();
+
+    let discarded = Arc::new(Mutex::new(Vec::new()));
+    let discarded2 = discarded.clone();
+    client.set_zombie_hook(Some(Arc::new(move |event: &client_rs::ZombieEvent| {
+        discarded2.lock().unwrap().push((event.interface.name, event.opcode, event.fds));
+    })));
+
+    server.handle().create_global::<()>(
+        &interfaces::TEST_GLOBAL_INTERFACE,
+        5,
+        Arc::new(DoNothingData),
+    );
+
+    let client_display = client.display_id();
+    let registry_id = client
+        .send_request(
+            message!(client_display, 1, [Argument::NewId(client_rs::ObjectId::null())],),
+            Some(Arc::new(DoNothingData)),
+            Some((&interfaces::WL_REGISTRY_INTERFACE, 1)),
+        )
+        .unwrap();
+    let client_data = Arc::new(ClientData(AtomicBool::new(false)));
+    let test_global_id = client
+        .send_request(
+            message!(
+                registry_id,
+                0,
+                [
+                    Argument::Uint(1),
+                    Argument::Str(Some(Box::new(
+                        CString::new(interfaces::TEST_GLOBAL_INTERFACE.name.as_bytes()).unwrap(),
+                    ))),
+                    Argument::Uint(5),
+                    Argument::NewId(client_rs::ObjectId::null()),
+                ],
+            ),
+            Some(client_data.clone()),
+            Some((&interfaces::TEST_GLOBAL_INTERFACE, 5)),
+        )
+        .unwrap();
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    let server_id = server
+        .handle()
+        .object_for_protocol_id(
+            client_id.clone(),
+            &interfaces::TEST_GLOBAL_INTERFACE,
+            test_global_id.protocol_id(),
+        )
+        .unwrap();
+
+    // the client destroys the object while the server sends it events
+    client.send_request(message!(test_global_id, 4, []), None, None).unwrap();
+    client.flush().unwrap();
+    let file = std::fs::File::open("/dev/null").unwrap();
+    server
+        .handle()
+        .send_event(message!(
+            server_id.clone(),
+            0,
+            [
+                Argument::Uint(1),
+                Argument::Int(2),
+                Argument::Fixed(3),
+                Argument::Array(Box::new(vec![1, 2, 3])),
+                Argument::Str(Some(Box::new(CString::new("zombie").unwrap()))),
+                Argument::Fd(file.as_raw_fd()),
+            ],
+        ))
+        .unwrap();
+    // an event creating an object, which is a zombie too
+    let quad_id = server
+        .handle()
+        .create_object::<()>(client_id, &interfaces::QUAD_INTERFACE, 5, Arc::new(DoNothingData))
+        .unwrap();
+    server
+        .handle()
+        .send_event(message!(
+            server_id,
+            2,
+            [Argument::NewId(quad_id), Argument::Object(server_rs::ObjectId::null())],
+        ))
+        .unwrap();
+    server.flush(None).unwrap();
+
+    client.prepare_read().unwrap().read().unwrap();
+    assert_eq!(*discarded.lock().unwrap(), [("test_global", 0, 1), ("test_global", 2, 0)]);
+    assert!(client.last_error().is_none());
+
+    server.dispatch_all_clients(&mut ()).unwrap();
+    server.flush(None).unwrap();
+}
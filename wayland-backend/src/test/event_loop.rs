@@ -0,0 +1,57 @@
+use std::io::Write;
+use std::os::unix::io::AsFd;
+use std::time::Duration;
+
+use crate::sys::event_loop::{Interest, Readiness};
+
+use super::*;
+
+#[derive(Default)]
+struct Fired {
+    idle: usize,
+    timer: usize,
+    fd: Vec<Readiness>,
+}
+
+// sources of the libwayland event loop are dispatched along with the clients
+#[test]
+fn event_loop_sources() {
+    let mut server = server_sys::Backend::<Fired>::new().unwrap();
+    let mut data = Fired::default();
+
+    server.insert_idle(|_, data: &mut Fired| data.idle += 1);
+
+    let timer = server.insert_timer(|_, data: &mut Fired| data.timer += 1).unwrap();
+    timer.set_timeout(Some(Duration::from_millis(1))).unwrap();
+
+    let (mut tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let fd_source = server
+        .insert_fd(rx.as_fd(), Interest::READ, |_, data: &mut Fired, readiness| {
+            data.fd.push(readiness)
+        })
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(10));
+    server.dispatch_all_clients(&mut data).unwrap();
+    assert_eq!(data.idle, 1);
+    assert_eq!(data.timer, 1);
+    assert!(data.fd.is_empty());
+
+    // the idle callback only runs once and the timer is not re-armed
+    tx.write_all(b"x").unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    server.dispatch_all_clients(&mut data).unwrap();
+    assert_eq!(data.idle, 1);
+    assert_eq!(data.timer, 1);
+    assert_eq!(data.fd.len(), 1);
+    assert!(data.fd[0].readable);
+
+    // dropped sources are no longer dispatched
+    drop(fd_source);
+    timer.set_timeout(Some(Duration::from_millis(1))).unwrap();
+    drop(timer);
+    std::thread::sleep(Duration::from_millis(10));
+    server.dispatch_all_clients(&mut data).unwrap();
+    assert_eq!(data.timer, 1);
+    assert_eq!(data.fd.len(), 1);
+}
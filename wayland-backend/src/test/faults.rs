@@ -0,0 +1,310 @@
+use std::ffi::CString;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::rs::{FaultConfig, LoopbackStream};
+use crate::types::client::WaylandError;
+
+use super::*;
+
+const MESSAGES: usize = 200;
+
+struct ServerData {
+    requests: AtomicUsize,
+    global: Mutex<Option<server_rs::ObjectId>>,
+}
+
+impl server_rs::GlobalHandler<()> for ServerData {
+    fn bind(
+        self: Arc<Self>,
+        _: &server_rs::Handle,
+        _: &mut (),
+        _: server_rs::ClientId,
+        _: server_rs::GlobalId,
+        object_id: server_rs::ObjectId,
+    ) -> Arc<dyn server_rs::ObjectData<()>> {
+        *self.global.lock().unwrap() = Some(object_id);
+        self
+    }
+}
+
+impl server_rs::ObjectData<()> for ServerData {
+    fn request(
+        self: Arc<Self>,
+        _: &server_rs::Handle,
+        _: &mut (),
+        _: server_rs::ClientId,
+        msg: Message<server_rs::ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn server_rs::ObjectData<()>>> {
+        if let [Argument::Uint(n), .., Argument::Fd(ref fd)] = msg.args[..] {
+            assert_eq!(n as usize, self.requests.load(Ordering::SeqCst));
+            assert!(fd.as_raw_fd() >= 0);
+            self.requests.fetch_add(1, Ordering::SeqCst);
+        }
+        None
+    }
+
+    fn destroyed(
+        self: Arc<Self>,
+        _: &server_rs::Handle,
+        _: &mut (),
+        _: server_rs::ClientId,
+        _: server_rs::ObjectId,
+    ) {
+    }
+}
+
+#[derive(Default)]
+struct ClientData {
+    events: AtomicUsize,
+}
+
+impl client_rs::ObjectData for ClientData {
+    fn event(
+        self: Arc<Self>,
+        _: &client_rs::Backend,
+        msg: Message<client_rs::ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn client_rs::ObjectData>> {
+        if let [Argument::Uint(n), .., Argument::Fd(ref fd)] = msg.args[..] {
+            assert_eq!(n as usize, self.events.load(Ordering::SeqCst));
+            assert!(fd.as_raw_fd() >= 0);
+            self.events.fetch_add(1, Ordering::SeqCst);
+        }
+        None
+    }
+
+    fn destroyed(&self, _: client_rs::ObjectId) {}
+}
+
+struct DisconnectData(AtomicBool);
+
+impl server_rs::ClientData for DisconnectData {
+    fn disconnected(&self, _: server_rs::ClientId, _: crate::types::server::DisconnectReason) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+fn many_args<Id>(sender_id: Id, n: usize, fd: i32) -> Message<Id, i32> {
+    message!(
+        sender_id,
+        0,
+        [
+            Argument::Uint(n as u32),
+            Argument::Int(-1),
+            Argument::Fixed(2),
+            Argument::Array(Box::new(vec![3; 37])),
+            Argument::Str(Some(Box::new(CString::new("faulty").unwrap()))),
+            Argument::Fd(fd),
+        ]
+    )
+}
+
+// Drive both ends until `done` returns true, retrying the operations that would block
+fn pump(client: &client_rs::Backend, server: &mut server_rs::Backend<()>, done: impl Fn() -> bool) {
+    for _ in 0..100_000 {
+        if done() {
+            return;
+        }
+        match client.flush() {
+            Ok(()) => {}
+            Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => panic!("{}", e),
+        }
+        server.dispatch_all_clients(&mut ()).unwrap();
+        let _ = server.flush(None);
+        match client.prepare_read() {
+            Some(guard) => match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("{}", e),
+            },
+            None => {
+                client.dispatch_inner_queue().unwrap();
+            }
+        }
+    }
+    panic!("The messages were not all delivered");
+}
+
+// Bind the test global, then send messages carrying file descriptors in both directions
+fn check_transfers(client: client_rs::Backend, mut server: server_rs::Backend<()>) {
+    let server_data =
+        Arc::new(ServerData { requests: AtomicUsize::new(0), global: Mutex::new(None) });
+    server.handle().create_global(&interfaces::TEST_GLOBAL_INTERFACE, 1, server_data.clone());
+    let client_data = Arc::new(ClientData::default());
+    let registry = client
+        .send_request(
+            message!(client.display_id(), 1, [Argument::NewId(client_rs::ObjectId::null())]),
+            Some(Arc::new(DoNothingData)),
+            Some((&interfaces::WL_REGISTRY_INTERFACE, 1)),
+        )
+        .unwrap();
+    let test_global = client
+        .send_request(
+            message!(
+                registry,
+                0,
+                [
+                    Argument::Uint(1),
+                    Argument::Str(Some(Box::new(
+                        CString::new(interfaces::TEST_GLOBAL_INTERFACE.name).unwrap()
+                    ))),
+                    Argument::Uint(1),
+                    Argument::NewId(client_rs::ObjectId::null()),
+                ]
+            ),
+            Some(client_data.clone()),
+            Some((&interfaces::TEST_GLOBAL_INTERFACE, 1)),
+        )
+        .unwrap();
+
+    let (fd, _other_end) = UnixStream::pair().unwrap();
+    for n in 0..MESSAGES {
+        client.send_request(many_args(test_global.clone(), n, fd.as_raw_fd()), None, None).unwrap();
+        // flush as we go, the outgoing buffer cannot hold all the messages
+        if n % 8 == 7 {
+            pump(&client, &mut server, || server_data.requests.load(Ordering::SeqCst) > n);
+        }
+    }
+    pump(&client, &mut server, || server_data.requests.load(Ordering::SeqCst) == MESSAGES);
+
+    let global = server_data.global.lock().unwrap().clone().unwrap();
+    for n in 0..MESSAGES {
+        server.handle().send_event(many_args(global.clone(), n, fd.as_raw_fd())).unwrap();
+        if n % 8 == 7 {
+            pump(&client, &mut server, || client_data.events.load(Ordering::SeqCst) > n);
+        }
+    }
+    pump(&client, &mut server, || client_data.events.load(Ordering::SeqCst) == MESSAGES);
+}
+
+#[test]
+fn transfers_survive_faults() {
+    let config = FaultConfig {
+        short_io: 0.3,
+        delayed_fds: 0.5,
+        eagain_storm: 0.05,
+        eagain_storm_len: 4,
+        ..FaultConfig::new(0x5eed)
+    };
+    let (tx, rx) = UnixStream::pair().unwrap();
+    let server = server_rs::Backend::<()>::new().unwrap();
+    server
+        .handle()
+        .insert_client_with_faults(rx, Arc::new(()), FaultConfig { seed: 1, ..config.clone() })
+        .unwrap();
+    let client = client_rs::Backend::connect_with_faults(tx, config);
+    check_transfers(client, server);
+}
+
+#[test]
+fn loopback_transfers_survive_faults() {
+    let config = FaultConfig {
+        short_io: 0.3,
+        delayed_fds: 0.5,
+        latency: 0.01,
+        latency_duration: Duration::from_micros(200),
+        ..FaultConfig::new(0x100b)
+    };
+    let (tx, rx) = LoopbackStream::pair().unwrap();
+    let server = server_rs::Backend::<()>::new().unwrap();
+    server
+        .handle()
+        .insert_loopback_client_with_faults(
+            rx,
+            Arc::new(()),
+            FaultConfig { seed: 2, ..config.clone() },
+        )
+        .unwrap();
+    let client = client_rs::Backend::connect_loopback_with_faults(tx, config);
+    check_transfers(client, server);
+}
+
+#[test]
+fn connection_reset() {
+    let (tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let disconnect = Arc::new(DisconnectData(AtomicBool::new(false)));
+    server.handle().insert_client(rx, disconnect.clone()).unwrap();
+    let client = client_rs::Backend::connect_with_faults(
+        tx,
+        FaultConfig { reset: 1., ..FaultConfig::new(0) },
+    );
+
+    client
+        .send_request(
+            message!(client.display_id(), 1, [Argument::NewId(client_rs::ObjectId::null())]),
+            Some(Arc::new(DoNothingData)),
+            Some((&interfaces::WL_REGISTRY_INTERFACE, 1)),
+        )
+        .unwrap();
+    assert!(matches!(
+        client.flush(),
+        Err(WaylandError::Io(e)) if e.raw_os_error() == Some(nix::libc::ECONNRESET)
+    ));
+    // the error is persistent
+    assert!(client.flush().is_err());
+
+    // the server sees the connection closing
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(disconnect.0.load(Ordering::SeqCst));
+}
+
+#[test]
+fn fd_exhaustion() {
+    let (tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_rs::Backend::connect_with_faults(
+        tx,
+        FaultConfig { fd_exhaustion: 1., ..FaultConfig::new(0) },
+    );
+
+    let server_data =
+        Arc::new(ServerData { requests: AtomicUsize::new(0), global: Mutex::new(None) });
+    server.handle().create_global(&interfaces::TEST_GLOBAL_INTERFACE, 1, server_data.clone());
+    let registry = client
+        .send_request(
+            message!(client.display_id(), 1, [Argument::NewId(client_rs::ObjectId::null())]),
+            Some(Arc::new(DoNothingData)),
+            Some((&interfaces::WL_REGISTRY_INTERFACE, 1)),
+        )
+        .unwrap();
+    client
+        .send_request(
+            message!(
+                registry,
+                0,
+                [
+                    Argument::Uint(1),
+                    Argument::Str(Some(Box::new(
+                        CString::new(interfaces::TEST_GLOBAL_INTERFACE.name).unwrap()
+                    ))),
+                    Argument::Uint(1),
+                    Argument::NewId(client_rs::ObjectId::null()),
+                ]
+            ),
+            Some(Arc::new(ClientData::default())),
+            Some((&interfaces::TEST_GLOBAL_INTERFACE, 1)),
+        )
+        .unwrap();
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+
+    // messages without fds go through
+    server.flush(None).unwrap();
+    client.prepare_read().unwrap().read().unwrap();
+
+    let (fd, _other_end) = UnixStream::pair().unwrap();
+    let global = server_data.global.lock().unwrap().clone().unwrap();
+    server.handle().send_event(many_args(global, 0, fd.as_raw_fd())).unwrap();
+    server.flush(None).unwrap();
+    assert!(matches!(
+        client.prepare_read().unwrap().read(),
+        Err(WaylandError::Io(e)) if e.raw_os_error() == Some(nix::libc::EOVERFLOW)
+    ));
+    assert!(client.last_error().is_some());
+}
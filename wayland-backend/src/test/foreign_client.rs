@@ -0,0 +1,91 @@
+use std::os::unix::io::IntoRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use wayland_sys::{ffi_dispatch, server::*};
+
+use super::*;
+
+#[derive(Default)]
+struct TrackingData {
+    initialized: AtomicBool,
+    disconnected: AtomicBool,
+}
+
+impl server_sys::ClientData for TrackingData {
+    fn initialized(&self, _: server_sys::ClientId) {
+        self.initialized.store(true, Ordering::SeqCst);
+    }
+
+    fn disconnected(&self, _: server_sys::ClientId, _: server_sys::DisconnectReason) {
+        self.disconnected.store(true, Ordering::SeqCst);
+    }
+}
+
+// a client created directly through libwayland can be adopted by the backend
+#[test]
+fn adopt_client() {
+    let (_tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let server = server_sys::Backend::<()>::new().unwrap();
+    let handle = server.handle();
+
+    let ptr = unsafe {
+        ffi_dispatch!(
+            wayland_server_handle(),
+            wl_client_create,
+            handle.display_ptr(),
+            rx.into_raw_fd()
+        )
+    };
+    assert!(!ptr.is_null());
+    assert!(unsafe { server_sys::ClientId::from_ptr(ptr) }.is_err());
+
+    let data = Arc::new(TrackingData::default());
+    let client_id = unsafe { handle.adopt_client(ptr, data.clone()) };
+    assert!(data.initialized.load(Ordering::SeqCst));
+    assert_eq!(client_id.as_ptr(), ptr);
+    assert_eq!(unsafe { server_sys::ClientId::from_ptr(ptr) }.unwrap(), client_id);
+    // adopting it a second time is a no-op
+    assert_eq!(unsafe { handle.adopt_client(ptr, Arc::new(())) }, client_id);
+
+    // the client can be used like any other
+    assert!(handle.get_client_credentials(client_id.clone()).is_ok());
+    let mut clients = Vec::new();
+    handle.with_all_clients(|id| clients.push(id));
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0], client_id);
+
+    // its destruction from C is tracked as well
+    unsafe { ffi_dispatch!(wayland_server_handle(), wl_client_destroy, ptr) };
+    assert!(data.disconnected.load(Ordering::SeqCst));
+    assert!(client_id.as_ptr().is_null());
+    assert!(handle.get_client_data(client_id).is_err());
+}
+
+// resources created by libwayland itself are visible through the raw iteration
+#[test]
+fn raw_client_accessors() {
+    let (_tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let server = server_sys::Backend::<()>::new().unwrap();
+    let mut handle = server.handle();
+    let client_id = handle.insert_client(rx, Arc::new(())).unwrap();
+
+    assert!(handle.client_fd(&client_id).unwrap() >= 0);
+
+    let mut resources = Vec::new();
+    handle.with_all_resources_for(&client_id, |resource| resources.push(resource)).unwrap();
+    // the wl_display object of the client
+    assert_eq!(resources.len(), 1);
+    let mut objects = 0;
+    handle.with_all_objects_for(client_id.clone(), |_| objects += 1).unwrap();
+    assert_eq!(objects, 0);
+
+    let global =
+        handle.create_global::<()>(&interfaces::WL_CALLBACK_INTERFACE, 1, Arc::new(DoNothingData));
+    assert!(!global.as_ptr().is_null());
+    handle.remove_global::<()>(global.clone());
+    assert!(global.as_ptr().is_null());
+
+    handle.kill_client(client_id.clone(), server_sys::DisconnectReason::ConnectionClosed);
+    assert!(handle.client_fd(&client_id).is_err());
+    assert!(handle.with_all_resources_for(&client_id, |_| ()).is_err());
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::*;
+
+struct SyncData(AtomicBool);
+
+impl client_sys::ObjectData for SyncData {
+    fn event(
+        self: Arc<Self>,
+        _: &client_sys::Backend,
+        _: Message<client_sys::ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn client_sys::ObjectData>> {
+        self.0.store(true, Ordering::SeqCst);
+        None
+    }
+
+    fn destroyed(&self, _: client_sys::ObjectId) {}
+}
+
+fn send_sync(client: &client_sys::Backend) -> Arc<SyncData> {
+    let data = Arc::new(SyncData(AtomicBool::new(false)));
+    client
+        .send_request(
+            message!(client.display_id(), 0, [Argument::NewId(client_sys::ObjectId::null())]),
+            Some(data.clone()),
+            Some((&interfaces::WL_CALLBACK_INTERFACE, 1)),
+        )
+        .unwrap();
+    client.flush().unwrap();
+    data
+}
+
+fn answer(server: &mut server_rs::Backend<()>) {
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    server.dispatch_all_clients(&mut ()).unwrap();
+    server.flush(None).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+}
+
+// a guest backend gets its own event queue, and leaves the host connection alive when dropped
+#[test]
+fn guest_backend() {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let host = client_sys::Backend::connect(tx).unwrap();
+    let guest = unsafe { client_sys::Backend::from_foreign_display(host.display_ptr()) };
+    assert_eq!(guest.display_ptr(), host.display_ptr());
+
+    let host_sync = send_sync(&host);
+    let guest_sync = send_sync(&guest);
+    answer(&mut server);
+
+    // reading from the guest only dispatches the guest queue
+    guest.prepare_read().unwrap().read().unwrap();
+    assert!(guest_sync.0.load(Ordering::SeqCst));
+    assert!(!host_sync.0.load(Ordering::SeqCst));
+    host.dispatch_inner_queue().unwrap();
+    assert!(host_sync.0.load(Ordering::SeqCst));
+
+    // the connection survives the guest
+    drop(guest);
+    let host_sync = send_sync(&host);
+    answer(&mut server);
+    host.prepare_read().unwrap().read().unwrap();
+    assert!(host_sync.0.load(Ordering::SeqCst));
+}
+
+// naming the queue of a guest does not change its behavior, whether or not libwayland supports it
+#[test]
+fn named_guest_queue() {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::new().unwrap();
+    let _client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let host = client_sys::Backend::connect(tx).unwrap();
+    let name = std::ffi::CStr::from_bytes_with_nul(b"guest\0").unwrap();
+    let guest = unsafe {
+        client_sys::Backend::from_foreign_display_with_queue_name(host.display_ptr(), name)
+    };
+
+    let guest_sync = send_sync(&guest);
+    answer(&mut server);
+    guest.prepare_read().unwrap().read().unwrap();
+    assert!(guest_sync.0.load(Ordering::SeqCst));
+}
@@ -0,0 +1,105 @@
+use std::ffi::CString;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use super::*;
+use crate::types::server::DisconnectReason;
+
+#[derive(Default)]
+struct RecordDisconnect(Mutex<Option<DisconnectReason>>);
+
+impl server_rs::ClientData for RecordDisconnect {
+    fn disconnected(&self, _: server_rs::ClientId, reason: DisconnectReason) {
+        *self.0.lock().unwrap() = Some(reason);
+    }
+}
+
+// keep the clients for the reasons matching `keep`, and defer the other disconnections
+fn policy(keep: fn(&DisconnectReason) -> bool) -> server_rs::KillPolicy {
+    server_rs::KillPolicy::Callback(Arc::new(move |_, reason| {
+        if keep(reason) {
+            server_rs::KillDecision::Keep
+        } else {
+            server_rs::KillDecision::Defer
+        }
+    }))
+}
+
+#[test]
+fn malformed_request_kept() {
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    server.handle().insert_client(rx, client_data.clone()).unwrap();
+    server
+        .handle()
+        .set_kill_policy(policy(|reason| matches!(reason, DisconnectReason::InvalidMessage(_))));
+
+    // a request with the unknown opcode 7 on the wl_display, followed by a wl_display.sync
+    let words: [u32; 5] = [1, 8 << 16 | 7, 1, 12 << 16, 2];
+    let bytes = words.iter().flat_map(|word| word.to_ne_bytes()).collect::<Vec<_>>();
+    tx.write_all(&bytes).unwrap();
+    // the unknown request is skipped
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 1);
+    assert!(client_data.0.lock().unwrap().is_none());
+}
+
+#[test]
+fn protocol_error_kept() {
+    let (_tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    let client_id = server.handle().insert_client(rx, client_data.clone()).unwrap();
+    server
+        .handle()
+        .set_kill_policy(policy(|reason| matches!(reason, DisconnectReason::ProtocolError(_))));
+
+    server.handle().post_implementation_error(client_id.clone(), CString::new("ignored").unwrap());
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(client_data.0.lock().unwrap().is_none());
+    assert!(server.handle().get_client_data(client_id).is_ok());
+}
+
+#[test]
+fn buffer_overflow_deferred() {
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    let client_id = server.handle().insert_client(rx, client_data.clone()).unwrap();
+    let deferred = Arc::new(AtomicBool::new(false));
+    let deferred2 = deferred.clone();
+    server.handle().set_kill_policy(server_rs::KillPolicy::Callback(Arc::new(move |_, reason| {
+        assert!(matches!(reason, DisconnectReason::BufferOverflow));
+        deferred2.store(true, Ordering::SeqCst);
+        server_rs::KillDecision::Defer
+    })));
+    server.handle().set_client_max_buffer_size(client_id.clone(), 4096).unwrap();
+
+    // the client does not read the wl_display.delete_id events it is sent
+    let display = server
+        .handle()
+        .object_for_protocol_id(client_id, &interfaces::WL_DISPLAY_INTERFACE, 1)
+        .unwrap();
+    while !deferred.load(Ordering::SeqCst) {
+        server.handle().send_event(message!(display.clone(), 1, [Argument::Uint(3)])).unwrap();
+    }
+    // the client stays connected until the queued events are written
+    let _ = server.flush(None);
+    assert!(client_data.0.lock().unwrap().is_none());
+
+    tx.set_nonblocking(true).unwrap();
+    let mut buffer = [0; 4096];
+    while client_data.0.lock().unwrap().is_none() {
+        loop {
+            match tx.read(&mut buffer) {
+                Ok(_) => continue,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        let _ = server.flush(None);
+    }
+    assert!(matches!(*client_data.0.lock().unwrap(), Some(DisconnectReason::BufferOverflow)));
+}
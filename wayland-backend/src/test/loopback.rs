@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::rs::LoopbackStream;
+
+use super::*;
+
+struct SyncData(AtomicBool);
+
+impl client_rs::ObjectData for SyncData {
+    fn event(
+        self: Arc<Self>,
+        _: &client_rs::Backend,
+        msg: Message<client_rs::ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn client_rs::ObjectData>> {
+        assert_eq!(msg.opcode, 0);
+        self.0.store(true, Ordering::SeqCst);
+        None
+    }
+
+    fn destroyed(&self, _: client_rs::ObjectId) {}
+}
+
+struct DisconnectData(AtomicBool);
+
+impl server_rs::ClientData for DisconnectData {
+    fn disconnected(&self, _: server_rs::ClientId, _: crate::types::server::DisconnectReason) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn loopback_sync() {
+    let (client_end, server_end) = LoopbackStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let disconnect = Arc::new(DisconnectData(AtomicBool::new(false)));
+    let client_id = server.handle().insert_loopback_client(server_end, disconnect.clone()).unwrap();
+    let client = client_rs::Backend::connect_loopback(client_end);
+
+    let credentials = server.handle().get_client_credentials(client_id).unwrap();
+    assert_eq!(credentials.pid, std::process::id() as i32);
+
+    // nothing was sent yet
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 0);
+
+    let sync_data = Arc::new(SyncData(AtomicBool::new(false)));
+    client
+        .send_request(
+            message!(client.display_id(), 0, [Argument::NewId(client_rs::ObjectId::null())]),
+            Some(sync_data.clone()),
+            Some((&interfaces::WL_CALLBACK_INTERFACE, 1)),
+        )
+        .unwrap();
+    client.flush().unwrap();
+
+    server.dispatch_all_clients(&mut ()).unwrap();
+    server.flush(None).unwrap();
+
+    client.prepare_read().unwrap().read().unwrap();
+    assert!(sync_data.0.load(Ordering::SeqCst));
+
+    // dropping the client disconnects it
+    drop(client);
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(disconnect.0.load(Ordering::SeqCst));
+}
+
+#[test]
+fn loopback_kill_client() {
+    let (client_end, server_end) = LoopbackStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let disconnect = Arc::new(DisconnectData(AtomicBool::new(false)));
+    let client_id = server.handle().insert_loopback_client(server_end, disconnect.clone()).unwrap();
+    let client = client_rs::Backend::connect_loopback(client_end);
+
+    // the client stays alive while the server drops its end
+    server
+        .handle()
+        .kill_client(client_id, crate::types::server::DisconnectReason::ConnectionClosed);
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(disconnect.0.load(Ordering::SeqCst));
+
+    // more requests from the client must not wake the server up any more
+    client
+        .send_request(
+            message!(client.display_id(), 0, [Argument::NewId(client_rs::ObjectId::null())]),
+            Some(Arc::new(SyncData(AtomicBool::new(false)))),
+            Some((&interfaces::WL_CALLBACK_INTERFACE, 1)),
+        )
+        .unwrap();
+    let _ = client.flush();
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 0);
+}
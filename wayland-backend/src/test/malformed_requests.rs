@@ -0,0 +1,130 @@
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::*;
+use crate::protocol::MessageLimits;
+use crate::types::server::DisconnectReason;
+
+#[derive(Default)]
+struct RecordDisconnect(Mutex<Option<DisconnectReason>>);
+
+impl server_rs::ClientData for RecordDisconnect {
+    fn disconnected(&self, _: server_rs::ClientId, reason: DisconnectReason) {
+        *self.0.lock().unwrap() = Some(reason);
+    }
+}
+
+// a request with the unknown opcode 7 on the wl_display, followed by a wl_display.sync
+fn send_unknown_then_sync(stream: &mut UnixStream) {
+    let words: [u32; 5] = [1, 8 << 16 | 7, 1, 12 << 16, 2];
+    let bytes = words.iter().flat_map(|word| word.to_ne_bytes()).collect::<Vec<_>>();
+    stream.write_all(&bytes).unwrap();
+}
+
+#[test]
+fn malformed_request_kills_client() {
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    server.handle().insert_client(rx, client_data.clone()).unwrap();
+
+    send_unknown_then_sync(&mut tx);
+    // the client is killed before dispatching the sync
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 0);
+
+    let reason = client_data.0.lock().unwrap().take();
+    match reason {
+        Some(DisconnectReason::InvalidMessage(error)) => {
+            assert_eq!(error.code, 1);
+            assert_eq!(error.object_interface, "wl_display");
+            assert_eq!(error.message, "invalid method 7, object wl_display@1");
+        }
+        reason => panic!("Unexpected disconnect reason: {:?}", reason),
+    }
+}
+
+#[test]
+fn malformed_request_discarded_by_policy() {
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    server.handle().insert_client(rx, client_data.clone()).unwrap();
+
+    let discarded = Arc::new(AtomicUsize::new(0));
+    let discarded2 = discarded.clone();
+    server.handle().set_malformed_request_policy(server_rs::MalformedRequestPolicy::Callback(
+        Arc::new(move |_, request| {
+            assert_eq!(request.object_id, 1);
+            assert_eq!(request.interface.map(|interface| interface.name), Some("wl_display"));
+            assert_eq!(request.opcode, 7);
+            assert_eq!(request.size, 8);
+            assert_eq!(request.reason, server_rs::MalformedReason::UnknownOpcode);
+            discarded2.fetch_add(1, Ordering::SeqCst);
+            true
+        }),
+    ));
+
+    // the unknown request is skipped, and the sync still dispatched
+    send_unknown_then_sync(&mut tx);
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 1);
+    assert_eq!(discarded.load(Ordering::SeqCst), 1);
+    assert!(client_data.0.lock().unwrap().is_none());
+}
+
+// a request of `size` bytes on the wl_display whose contents are never read, followed by a
+// wl_display.sync
+fn send_oversized_then_sync(stream: &mut UnixStream, size: u32) {
+    let mut words = vec![0; size as usize / 4];
+    words[0] = 1;
+    words[1] = size << 16;
+    words.extend_from_slice(&[1, 12 << 16, 2]);
+    let bytes = words.iter().flat_map(|word| word.to_ne_bytes()).collect::<Vec<_>>();
+    stream.write_all(&bytes).unwrap();
+}
+
+#[test]
+fn oversized_request_kills_client() {
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    server.handle().insert_client(rx, client_data.clone()).unwrap();
+    server.handle().set_message_limits(MessageLimits { max_size: 512, ..Default::default() });
+
+    send_oversized_then_sync(&mut tx, 1024);
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 0);
+
+    let reason = client_data.0.lock().unwrap().take();
+    match reason {
+        Some(DisconnectReason::InvalidMessage(error)) => {
+            assert_eq!(error.code, 1);
+            assert_eq!(
+                error.message,
+                "Message 0 of object 1 is 1024 bytes long, more than the limit of 512 bytes."
+            );
+        }
+        reason => panic!("Unexpected disconnect reason: {:?}", reason),
+    }
+}
+
+#[test]
+fn oversized_request_discarded_by_policy() {
+    let (mut tx, rx) = UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    server.handle().insert_client(rx, client_data.clone()).unwrap();
+
+    server.handle().set_malformed_request_policy(server_rs::MalformedRequestPolicy::Callback(
+        Arc::new(move |_, request| {
+            assert_eq!(request.size, 40_000);
+            assert_eq!(request.reason, server_rs::MalformedReason::TooLarge);
+            true
+        }),
+    ));
+
+    // the request is larger than the incoming buffer, and dropped as it is received
+    send_oversized_then_sync(&mut tx, 40_000);
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 1);
+    assert!(client_data.0.lock().unwrap().is_none());
+}
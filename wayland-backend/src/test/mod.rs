@@ -55,12 +55,23 @@ mod interfaces {
     );
 }
 
+mod conformance;
 mod destructors;
+mod event_loop;
+mod faults;
+mod foreign_client;
+mod foreign_display;
+mod kill_policy;
+mod loopback;
+mod malformed_requests;
 mod many_args;
 mod object_args;
+mod paused_objects;
 mod protocol_error;
+mod rate_limit;
 mod server_created_objects;
 mod sync;
+mod wakeup;
 
 /*
  * Assertion of Send/Sync for all relevant objects
@@ -86,6 +97,7 @@ fn send_sync_server_rs() {
     ensure_both::<server_rs::ObjectId>();
     ensure_both::<server_rs::GlobalId>();
     ensure_both::<server_rs::ClientId>();
+    ensure_both::<server_rs::WakeupHandle>();
 }
 
 #[allow(dead_code)]
@@ -94,6 +106,7 @@ fn send_sync_server_sys() {
     ensure_both::<server_sys::ObjectId>();
     ensure_both::<server_sys::GlobalId>();
     ensure_both::<server_sys::ClientId>();
+    ensure_both::<server_sys::WakeupHandle>();
 }
 
 /*
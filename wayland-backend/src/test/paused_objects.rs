@@ -0,0 +1,193 @@
+use std::ffi::CString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::*;
+use crate::protocol::{ArgumentType, Interface, MessageDesc};
+use crate::types::server::DisconnectReason;
+
+// an object creating objects of its own interface, which the server can destroy with an event
+static NODE_INTERFACE: Interface = Interface {
+    name: "node",
+    version: 1,
+    requests: &[MessageDesc {
+        name: "create",
+        since: 1,
+        is_destructor: false,
+        signature: &[ArgumentType::NewId],
+        child_interface: Some(&NODE_INTERFACE),
+        arg_interfaces: &[],
+    }],
+    events: &[MessageDesc {
+        name: "gone",
+        since: 1,
+        is_destructor: true,
+        signature: &[],
+        child_interface: None,
+        arg_interfaces: &[],
+    }],
+    c_ptr: None,
+};
+
+#[derive(Default)]
+struct ServerData {
+    bound: Mutex<Option<server_rs::ObjectId>>,
+    requests: AtomicUsize,
+}
+
+impl server_rs::GlobalHandler<()> for ServerData {
+    fn bind(
+        self: Arc<Self>,
+        _: &server_rs::Handle,
+        _: &mut (),
+        _: server_rs::ClientId,
+        _: server_rs::GlobalId,
+        object_id: server_rs::ObjectId,
+    ) -> Arc<dyn server_rs::ObjectData<()>> {
+        *self.bound.lock().unwrap() = Some(object_id);
+        self
+    }
+}
+
+impl server_rs::ObjectData<()> for ServerData {
+    fn request(
+        self: Arc<Self>,
+        _: &server_rs::Handle,
+        _: &mut (),
+        _: server_rs::ClientId,
+        _: Message<server_rs::ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn server_rs::ObjectData<()>>> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        Some(self)
+    }
+
+    fn destroyed(
+        self: Arc<Self>,
+        _: &server_rs::Handle,
+        _: &mut (),
+        _: server_rs::ClientId,
+        _: server_rs::ObjectId,
+    ) {
+    }
+}
+
+fn bind_node(client: &client_rs::Backend) -> client_rs::ObjectId {
+    let registry_id = client
+        .send_request(
+            message!(client.display_id(), 1, [Argument::NewId(client_rs::ObjectId::null())]),
+            Some(Arc::new(DoNothingData)),
+            Some((&interfaces::WL_REGISTRY_INTERFACE, 1)),
+        )
+        .unwrap();
+    client
+        .send_request(
+            message!(
+                registry_id,
+                0,
+                [
+                    Argument::Uint(1),
+                    Argument::Str(Some(Box::new(
+                        CString::new(NODE_INTERFACE.name.as_bytes()).unwrap()
+                    ))),
+                    Argument::Uint(1),
+                    Argument::NewId(client_rs::ObjectId::null()),
+                ],
+            ),
+            Some(Arc::new(DoNothingData)),
+            Some((&NODE_INTERFACE, 1)),
+        )
+        .unwrap()
+}
+
+fn create_node(client: &client_rs::Backend, parent: client_rs::ObjectId) -> client_rs::ObjectId {
+    client
+        .send_request(
+            message!(parent, 0, [Argument::NewId(client_rs::ObjectId::null())]),
+            Some(Arc::new(DoNothingData)),
+            Some((&NODE_INTERFACE, 1)),
+        )
+        .unwrap()
+}
+
+// the object created by a deferred request whose object died is a zombie
+#[test]
+fn deferred_request_of_dead_object() {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_rs::Backend::connect(tx).unwrap();
+
+    let server_data = Arc::new(ServerData::default());
+    server.handle().create_global(&NODE_INTERFACE, 1, server_data.clone());
+
+    let node = bind_node(&client);
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    let server_node = server_data.bound.lock().unwrap().clone().unwrap();
+    server.handle().set_object_paused(server_node.clone(), true).unwrap();
+
+    let child = create_node(&client, node);
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert_eq!(server_data.requests.load(Ordering::SeqCst), 0);
+
+    // the server destroys the paused object, its deferred request is discarded
+    server.handle().send_event(message!(server_node, 0, [])).unwrap();
+
+    // the requests of its child, and of the objects the child creates, are ignored
+    let grandchild = create_node(&client, child);
+    create_node(&client, grandchild);
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert_eq!(server_data.requests.load(Ordering::SeqCst), 0);
+    assert!(server.handle().get_client_data(client_id).is_ok());
+}
+
+#[derive(Default)]
+struct RecordDisconnect(Mutex<Option<DisconnectReason>>);
+
+impl server_rs::ClientData for RecordDisconnect {
+    fn disconnected(&self, _: server_rs::ClientId, reason: DisconnectReason) {
+        *self.0.lock().unwrap() = Some(reason);
+    }
+}
+
+// a client deferring too many requests gets a no_memory error
+#[test]
+fn deferred_requests_limit() {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(RecordDisconnect::default());
+    server.handle().insert_client(rx, client_data.clone()).unwrap();
+    let client = client_rs::Backend::connect(tx).unwrap();
+
+    let server_data = Arc::new(ServerData::default());
+    server.handle().create_global(&NODE_INTERFACE, 1, server_data.clone());
+
+    let node = bind_node(&client);
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    let server_node = server_data.bound.lock().unwrap().clone().unwrap();
+    server.handle().set_object_paused(server_node, true).unwrap();
+
+    // the limit is 4096 requests
+    for i in 0..4096 {
+        create_node(&client, node.clone());
+        if i % 256 == 0 {
+            client.flush().unwrap();
+            server.dispatch_all_clients(&mut ()).unwrap();
+        }
+    }
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(client_data.0.lock().unwrap().is_none());
+
+    create_node(&client, node);
+    client.flush().unwrap();
+    server.dispatch_all_clients(&mut ()).unwrap();
+    match *client_data.0.lock().unwrap() {
+        Some(DisconnectReason::ProtocolError(ref error)) => assert_eq!(error.code, 2),
+        ref reason => panic!("Unexpected disconnection: {:?}", reason),
+    }
+    assert_eq!(server_data.requests.load(Ordering::SeqCst), 0);
+}
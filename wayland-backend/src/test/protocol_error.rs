@@ -317,3 +317,47 @@ expand_test!(protocol_error_in_request_without_object_init, {
     // the server should not panic, and gracefull accept that the user did not provide any object data for
     // the already destroyed object
 });
+
+#[derive(Default)]
+struct DisconnectData(Mutex<Option<crate::types::server::DisconnectReason>>);
+
+impl server_rs::ClientData for DisconnectData {
+    fn disconnected(&self, _: server_rs::ClientId, reason: server_rs::DisconnectReason) {
+        *self.0.lock().unwrap() = Some(reason);
+    }
+}
+
+impl server_sys::ClientData for DisconnectData {
+    fn disconnected(&self, _: server_sys::ClientId, reason: server_sys::DisconnectReason) {
+        *self.0.lock().unwrap() = Some(reason);
+    }
+}
+
+expand_test!(implementation_error, {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_backend::Backend::<()>::new().unwrap();
+    let client_data = Arc::new(DisconnectData::default());
+    let client_id = server.handle().insert_client(rx, client_data.clone()).unwrap();
+    let client = client_backend::Backend::connect(tx).unwrap();
+
+    server.handle().post_implementation_error(client_id, CString::new("Oops.").unwrap());
+    server.flush(None).unwrap();
+
+    match client.prepare_read().unwrap().read() {
+        Err(client_backend::WaylandError::Protocol(err)) => {
+            assert_eq!(err.code, 3);
+            assert_eq!(err.object_id, 1);
+            assert_eq!(err.object_interface, "wl_display");
+        }
+        ret => panic!("Bad ret: {:?}", ret),
+    }
+
+    let reason = client_data.0.lock().unwrap().take();
+    match reason {
+        Some(crate::types::server::DisconnectReason::ProtocolError(err)) => {
+            assert_eq!(err.code, 3);
+            assert_eq!(err.message, "Oops.");
+        }
+        reason => panic!("Bad disconnect reason: {:?}", reason),
+    }
+});
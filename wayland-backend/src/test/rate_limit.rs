@@ -0,0 +1,80 @@
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use nix::poll::{poll, PollFd, PollFlags};
+
+use super::*;
+use crate::clock::VirtualClock;
+
+fn readable(fd: std::os::unix::io::RawFd) -> bool {
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    poll(&mut fds, 0).unwrap() == 1
+}
+
+fn send_syncs(client: &client_rs::Backend, count: usize) {
+    for _ in 0..count {
+        client
+            .send_request(
+                message!(client.display_id(), 0, [Argument::NewId(client_rs::ObjectId::null())]),
+                Some(Arc::new(DoNothingData)),
+                Some((&interfaces::WL_CALLBACK_INTERFACE, 1)),
+            )
+            .unwrap();
+    }
+    client.flush().unwrap();
+}
+
+// the requests above the limit are dispatched by the following cycles
+#[test]
+fn requests_deferred_to_next_cycle() {
+    let (tx, rx) = std::os::unix::net::UnixStream::pair().unwrap();
+    let mut server = server_rs::Backend::<()>::new().unwrap();
+    let client_id = server.handle().insert_client(rx, Arc::new(())).unwrap();
+    let client = client_rs::Backend::connect(tx).unwrap();
+
+    let clock = VirtualClock::new();
+    let exceeded = Arc::new(AtomicUsize::new(0));
+    let exceeded2 = exceeded.clone();
+    server.handle().set_rate_limit(Some(server_rs::RateLimit {
+        max_requests: 10,
+        threshold: 25,
+        window: Duration::from_secs(1),
+        on_exceeded: Some(Arc::new(move |_, id| {
+            assert_eq!(id, client_id);
+            exceeded2.fetch_add(1, Ordering::SeqCst);
+        })),
+        clock: Arc::new(clock.clone()),
+    }));
+    let poll_fd = server.poll_fd().as_raw_fd();
+
+    send_syncs(&client, 30);
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 10);
+    // the poll fd remains readable for the deferred requests
+    assert!(readable(poll_fd));
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 10);
+    assert_eq!(exceeded.load(Ordering::SeqCst), 0);
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 10);
+    // the client is reported once per window
+    assert_eq!(exceeded.load(Ordering::SeqCst), 1);
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 0);
+    assert!(!readable(poll_fd));
+
+    send_syncs(&client, 30);
+    for _ in 0..3 {
+        server.dispatch_all_clients(&mut ()).unwrap();
+    }
+    assert_eq!(exceeded.load(Ordering::SeqCst), 1);
+
+    clock.advance(Duration::from_secs(1));
+    send_syncs(&client, 30);
+    for _ in 0..3 {
+        server.dispatch_all_clients(&mut ()).unwrap();
+    }
+    assert_eq!(exceeded.load(Ordering::SeqCst), 2);
+
+    // without limits, all the requests are dispatched at once
+    server.handle().set_rate_limit(None);
+    send_syncs(&client, 30);
+    assert_eq!(server.dispatch_all_clients(&mut ()).unwrap(), 30);
+}
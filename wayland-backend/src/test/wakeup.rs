@@ -0,0 +1,34 @@
+use std::os::unix::io::AsRawFd;
+
+use nix::poll::{poll, PollFd, PollFlags};
+
+use super::*;
+
+fn readable(fd: std::os::unix::io::RawFd, timeout_ms: i32) -> bool {
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    poll(&mut fds, timeout_ms).unwrap() == 1
+}
+
+// waking up from another thread makes the poll fd readable until the next dispatch
+expand_test!(wakeup_from_thread, {
+    let mut server = server_backend::Backend::<()>::new().unwrap();
+    let poll_fd = server.poll_fd().as_raw_fd();
+    let handle = server.wakeup_handle().unwrap();
+    assert!(!readable(poll_fd, 0));
+
+    let thread = std::thread::spawn(move || {
+        handle.wake();
+        handle.wake();
+    });
+    assert!(readable(poll_fd, 1000));
+    thread.join().unwrap();
+
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(!readable(poll_fd, 0));
+
+    // all the handles share the same file descriptor
+    server.wakeup_handle().unwrap().wake();
+    assert!(readable(poll_fd, 1000));
+    server.dispatch_all_clients(&mut ()).unwrap();
+    assert!(!readable(poll_fd, 0));
+});
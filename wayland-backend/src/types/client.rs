@@ -48,6 +48,11 @@ impl Clone for WaylandError {
             Self::Io(e) => {
                 if let Some(code) = e.raw_os_error() {
                     Self::Io(std::io::Error::from_raw_os_error(code))
+                } else if let Some(&inner) = e
+                    .get_ref()
+                    .and_then(|inner| inner.downcast_ref::<crate::protocol::MessageTooLarge>())
+                {
+                    Self::Io(std::io::Error::new(e.kind(), inner))
                 } else {
                     Self::Io(std::io::Error::new(e.kind(), ""))
                 }
@@ -82,3 +87,23 @@ impl std::fmt::Display for InvalidId {
         write!(f, "Invalid ObjectId")
     }
 }
+
+/// When the requests sent on a connection are written to its socket
+///
+/// The requests are first stored in the outgoing buffer of the connection, and written to the socket by
+/// [`Backend::flush()`](crate::client::Backend::flush) or when the buffer cannot hold more of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// The buffer is written when it is full, and by the dispatching methods of `wayland-client`
+    /// before they wait for the server
+    #[default]
+    Dispatch,
+    /// The buffer is only written when it is full, or when flushing explicitly
+    OnFull,
+    /// The buffer is only written when flushing explicitly
+    ///
+    /// It grows to hold the requests sent in the meantime, up to 256 kB or the 28 file descriptors a
+    /// message can carry, at which point it is written anyway. With the system backend, which cannot
+    /// grow the buffer of `libwayland-client.so`, this is the same as [`OnFull`](FlushPolicy::OnFull).
+    Manual,
+}
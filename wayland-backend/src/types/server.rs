@@ -1,3 +1,12 @@
+use std::{
+    os::unix::{
+        io::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        net::UnixStream,
+    },
+    sync::Arc,
+    time::Instant,
+};
+
 use crate::protocol::Interface;
 
 /// Description of a global advertised to some clients.
@@ -53,13 +62,48 @@ impl std::fmt::Display for InvalidId {
     }
 }
 
+/// An error generated when trying to pause or resume the requests of an object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PauseError {
+    /// The object is not valid
+    InvalidId,
+    /// The backend does not support pausing objects
+    Unsupported,
+}
+
+impl std::error::Error for PauseError {}
+
+impl std::fmt::Display for PauseError {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        match self {
+            PauseError::InvalidId => write!(f, "Invalid Id"),
+            PauseError::Unsupported => {
+                f.write_str("pausing objects is not supported by this backend")
+            }
+        }
+    }
+}
+
+impl From<InvalidId> for PauseError {
+    fn from(_: InvalidId) -> Self {
+        PauseError::InvalidId
+    }
+}
+
 /// Describes why a client has been disconnected from the server.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DisconnectReason {
     /// The connection has been closed by the server or client.
     ConnectionClosed,
     /// The server has sent the client a protocol error, terminating the connection.
     ProtocolError(crate::protocol::ProtocolError),
+    /// An event did not fit in the outgoing buffer of the client.
+    BufferOverflow,
+    /// The client sent a request that could not be decoded, and was sent this error.
+    InvalidMessage(crate::protocol::ProtocolError),
+    /// The server is shutting down.
+    ServerShutdown,
 }
 
 /// Holds the client credentials
@@ -72,3 +116,252 @@ pub struct Credentials {
     /// gid of the client
     pub gid: nix::libc::gid_t,
 }
+
+impl Credentials {
+    /// Read the credentials of the peer of a connected unix socket
+    ///
+    /// This gives the credentials of a client before it is inserted in the backend, for example to refuse
+    /// it when accepting its connection. The credentials are only available on linux, they are all 0 on
+    /// the other systems.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn from_peer(socket: BorrowedFd<'_>) -> std::io::Result<Credentials> {
+        let creds = nix::sys::socket::getsockopt(
+            socket.as_raw_fd(),
+            nix::sys::socket::sockopt::PeerCredentials,
+        )?;
+        Ok(Credentials { pid: creds.pid(), uid: creds.uid(), gid: creds.gid() })
+    }
+
+    /// Read the credentials of the peer of a connected unix socket
+    ///
+    /// This gives the credentials of a client before it is inserted in the backend, for example to refuse
+    /// it when accepting its connection. The credentials are only available on linux, they are all 0 on
+    /// the other systems.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn from_peer(_socket: BorrowedFd<'_>) -> std::io::Result<Credentials> {
+        Ok(Credentials { pid: 0, uid: 0, gid: 0 })
+    }
+}
+
+/// Read the security label of the peer of a unix socket
+///
+/// The label is set by the linux security module enforcing the policy of the system (SELinux,
+/// AppArmor, Smack...), there is none if no such module is active.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn peer_security_label(fd: RawFd) -> Option<String> {
+    use nix::{errno::Errno, libc};
+    let mut label = vec![0u8; 256];
+    loop {
+        let mut len = label.len() as libc::socklen_t;
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PEERSEC,
+                label.as_mut_ptr().cast(),
+                &mut len,
+            )
+        };
+        if ret == 0 {
+            label.truncate(len as usize);
+            break;
+        }
+        match Errno::last() {
+            // the kernel gave the size of the label
+            Errno::ERANGE if len as usize > label.len() => label.resize(len as usize, 0),
+            // ENOPROTOOPT when no module attaches labels to sockets
+            _ => return None,
+        }
+    }
+    // the label may or may not be nul-terminated depending on the module
+    while label.last() == Some(&0) {
+        label.pop();
+    }
+    (!label.is_empty()).then(|| String::from_utf8_lossy(&label).into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn peer_security_label(_fd: RawFd) -> Option<String> {
+    None
+}
+
+/// A callback invoked once a global has been removed after its grace period
+pub(crate) type GlobalFinalizer<H, D> = Box<dyn FnOnce(&H, &mut D) + Send>;
+
+/// The disabled globals waiting for the end of their grace period to be removed
+pub(crate) struct GlobalRemovals<G, H, D> {
+    pending: Vec<(Instant, G, GlobalFinalizer<H, D>)>,
+}
+
+impl<G, H, D> GlobalRemovals<G, H, D> {
+    pub(crate) fn new() -> Self {
+        Self { pending: Vec::new() }
+    }
+
+    pub(crate) fn schedule(&mut self, deadline: Instant, id: G, finalize: GlobalFinalizer<H, D>) {
+        self.pending.push((deadline, id, finalize));
+    }
+
+    /// Take the globals whose grace period is over
+    pub(crate) fn take_expired(&mut self, now: Instant) -> Vec<(G, GlobalFinalizer<H, D>)> {
+        if self.pending.iter().all(|(deadline, ..)| *deadline > now) {
+            return Vec::new();
+        }
+        let (expired, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(deadline, ..)| *deadline <= now);
+        self.pending = pending;
+        expired.into_iter().map(|(_, id, finalize)| (id, finalize)).collect()
+    }
+}
+
+impl<G, H, D> std::fmt::Debug for GlobalRemovals<G, H, D> {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GlobalRemovals").field("pending", &self.pending.len()).finish()
+    }
+}
+
+/// An identifier of a client destroy listener, to remove it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DestroyListenerId(u64);
+
+/// The destroy listeners of the clients
+///
+/// Each listener is stored along with a key `K` identifying its client.
+pub(crate) struct DestroyListeners<K, F> {
+    last_id: u64,
+    listeners: Vec<(DestroyListenerId, K, F)>,
+}
+
+impl<K, F> DestroyListeners<K, F> {
+    pub(crate) fn new() -> Self {
+        Self { last_id: 0, listeners: Vec::new() }
+    }
+
+    pub(crate) fn add(&mut self, client: K, listener: F) -> DestroyListenerId {
+        self.last_id += 1;
+        let id = DestroyListenerId(self.last_id);
+        self.listeners.push((id, client, listener));
+        id
+    }
+
+    /// Remove a listener, returns whether it was still registered
+    pub(crate) fn remove(&mut self, id: DestroyListenerId) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|(listener_id, ..)| *listener_id != id);
+        self.listeners.len() != len
+    }
+
+    pub(crate) fn any(&self, mut dead: impl FnMut(&K) -> bool) -> bool {
+        self.listeners.iter().any(|(_, client, _)| dead(client))
+    }
+
+    /// Take the listeners of the clients for which `dead` returns true, in registration order
+    pub(crate) fn take(&mut self, mut dead: impl FnMut(&K) -> bool) -> Vec<(K, F)> {
+        if !self.any(&mut dead) {
+            return Vec::new();
+        }
+        let (taken, listeners) = std::mem::take(&mut self.listeners)
+            .into_iter()
+            .partition(|(_, client, _)| dead(client));
+        self.listeners = listeners;
+        taken.into_iter().map(|(_, client, listener)| (client, listener)).collect()
+    }
+}
+
+impl<K, F> std::fmt::Debug for DestroyListeners<K, F> {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DestroyListeners").field("listeners", &self.listeners.len()).finish()
+    }
+}
+
+/// A callback invoked once a client has been destroyed, after the destructors of its objects
+pub(crate) type ClientDestroyCallback<H, C, D> =
+    Box<dyn FnOnce(&H, &mut D, C, DisconnectReason) + Send>;
+
+/// A callback invoked once the events queued for a client have been written to its socket
+pub(crate) struct DrainCallback(pub(crate) Box<dyn FnOnce() + Send>);
+
+impl DrainCallback {
+    pub(crate) fn invoke(self) {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for DrainCallback {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DrainCallback")
+    }
+}
+
+/// A callback choosing the data of the clients connecting to a listener, or refusing them
+pub(crate) type AcceptCallback<D, C> = Box<dyn FnMut(&mut D, &UnixStream) -> Option<Arc<C>> + Send>;
+
+/// A listening socket on which a backend accepts new clients
+pub(crate) struct Listener<A> {
+    fd: OwnedFd,
+    close_fd: Option<OwnedFd>,
+    pub(crate) accept: A,
+}
+
+impl<A> Listener<A> {
+    pub(crate) fn new(fd: OwnedFd, close_fd: Option<OwnedFd>, accept: A) -> std::io::Result<Self> {
+        use nix::{
+            fcntl::{fcntl, FcntlArg, OFlag},
+            sys::socket::{getsockopt, sockopt},
+        };
+        if !getsockopt(fd.as_raw_fd(), sockopt::AcceptConn)? {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the file descriptor is not a listening socket",
+            ));
+        }
+        // pending connections are accepted until none is left
+        let flags = OFlag::from_bits_truncate(fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)?);
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags | OFlag::O_NONBLOCK))?;
+        Ok(Self { fd, close_fd, accept })
+    }
+
+    pub(crate) fn fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// The file descriptor whose hangup stops the listener
+    pub(crate) fn close_fd(&self) -> Option<RawFd> {
+        self.close_fd.as_ref().map(AsRawFd::as_raw_fd)
+    }
+
+    /// Accept the next pending connection, if any
+    ///
+    /// Unlike `UnixListener::accept()`, this works for sockets of any family, like vsock.
+    pub(crate) fn next_connection(&self) -> std::io::Result<Option<UnixStream>> {
+        use nix::{
+            errno::Errno,
+            sys::socket::{accept4, SockFlag},
+        };
+        loop {
+            match accept4(self.fd.as_raw_fd(), SockFlag::SOCK_CLOEXEC) {
+                // Safety: the file descriptor was just created by accept
+                Ok(fd) => return Ok(Some(unsafe { UnixStream::from_raw_fd(fd) })),
+                Err(Errno::EAGAIN) => return Ok(None),
+                Err(Errno::EINTR) => continue,
+                // the client gave up before its connection was accepted
+                Err(Errno::ECONNABORTED) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl<A> std::fmt::Debug for Listener<A> {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Listener")
+            .field("fd", &self.fd)
+            .field("close_fd", &self.close_fd)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    os::unix::io::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    sync::Arc,
+};
+
+/// A handle to wake up the event loop of a server backend from any thread
+///
+/// Calling [`wake()`](WakeupHandle::wake) makes the [`poll_fd()`](crate::server::Backend::poll_fd)
+/// of the backend readable, so that an event loop monitoring it returns, until the next call to
+/// [`dispatch_all_clients()`](crate::server::Backend::dispatch_all_clients). Several wakeups before
+/// the dispatch are coalesced into a single one.
+///
+/// The handle is cheap to clone, and remains valid after the backend is dropped, waking up nothing.
+#[derive(Debug, Clone)]
+pub struct WakeupHandle {
+    fd: Arc<OwnedFd>,
+}
+
+impl WakeupHandle {
+    /// Wake up the event loop of the backend
+    pub fn wake(&self) {
+        // a full counter or pipe means that a wakeup is already pending
+        let _ = nix::unistd::write(self.fd.as_raw_fd(), &1u64.to_ne_bytes());
+    }
+}
+
+/// The file descriptors behind the wakeup handles of a backend
+#[derive(Debug)]
+pub(crate) struct Wakeup {
+    read: Arc<OwnedFd>,
+    write: Arc<OwnedFd>,
+}
+
+impl Wakeup {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) fn new() -> std::io::Result<Self> {
+        use nix::sys::eventfd::{eventfd, EfdFlags};
+        let fd = eventfd(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+        let fd = Arc::new(unsafe { OwnedFd::from_raw_fd(fd) });
+        Ok(Self { read: fd.clone(), write: fd })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(crate) fn new() -> std::io::Result<Self> {
+        use nix::fcntl::OFlag;
+        let (read, write) = nix::unistd::pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?;
+        unsafe {
+            Ok(Self {
+                read: Arc::new(OwnedFd::from_raw_fd(read)),
+                write: Arc::new(OwnedFd::from_raw_fd(write)),
+            })
+        }
+    }
+
+    /// The file descriptor to monitor for readability
+    pub(crate) fn fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.read.as_raw_fd()) }
+    }
+
+    pub(crate) fn handle(&self) -> WakeupHandle {
+        WakeupHandle { fd: self.write.clone() }
+    }
+}
+
+/// Consume the pending wakeups of a wakeup file descriptor
+pub(crate) fn drain(fd: RawFd) {
+    let mut buffer = [0u8; 64];
+    // an eventfd is reset by a single read, a pipe needs to be emptied
+    while let Ok(n) = nix::unistd::read(fd, &mut buffer) {
+        if n < buffer.len() {
+            break;
+        }
+    }
+}
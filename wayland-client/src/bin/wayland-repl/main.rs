@@ -0,0 +1,476 @@
+//! Interactive client sending requests to a compositor, to debug it by hand
+//!
+//! Connects to the compositor given by the environment, lists its globals, then sends the requests
+//! typed on stdin while printing the events received as they arrive. Requests are written as in the
+//! `WAYLAND_DEBUG` output, their arguments being converted according to the signature of the request:
+//!
+//! ```text
+//! bind wl_compositor
+//! -> wl_registry@2.bind(1, "wl_compositor", 6, new id wl_compositor@3)
+//! @3.create_surface(new)
+//! -> wl_compositor@3.create_surface(new id wl_surface@4)
+//! ```
+//!
+//! Only the interfaces of the core protocol are known. This binary is only built with the `tools` cargo
+//! feature.
+
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::io::{self, BufRead, Read, Write};
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::process::exit;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+use wayland_backend::client::{Backend, ObjectData, ObjectId, WaylandError};
+use wayland_backend::protocol::{
+    AllowNull, Argument, ArgumentType, Interface, Message, ANONYMOUS_INTERFACE,
+};
+use wayland_backend::smallvec::SmallVec;
+use wayland_client::protocol::__interfaces::*;
+use wayland_client::Connection;
+
+use parse::{parse_command, Command, Target, Value};
+
+mod parse;
+
+const HELP: &str = "\
+Commands:
+  globals                           List the globals advertised by the compositor
+  bind <NAME | INTERFACE> [VERSION] Bind a global, at the advertised version by default
+  objects                           List the objects alive on the connection
+  sync                              Wait for the compositor to process the requests sent
+  <OBJECT>.<REQUEST>(<ARGS>)        Send a request, as in `@3.create_surface(new)`
+  help                              Print this help
+  quit                              Disconnect and exit
+
+Objects are written `@ID` or `INTERFACE@ID`. Arguments are integers, decimal numbers, \"strings\",
+objects, `nil`, `new` for the object created by the request, arrays of hexadecimal bytes as
+`[01 ff]`, and file descriptors of this process given by number.";
+
+static INTERFACES: &[&Interface] = &[
+    &WL_DISPLAY_INTERFACE,
+    &WL_REGISTRY_INTERFACE,
+    &WL_CALLBACK_INTERFACE,
+    &WL_COMPOSITOR_INTERFACE,
+    &WL_SHM_POOL_INTERFACE,
+    &WL_SHM_INTERFACE,
+    &WL_BUFFER_INTERFACE,
+    &WL_DATA_OFFER_INTERFACE,
+    &WL_DATA_SOURCE_INTERFACE,
+    &WL_DATA_DEVICE_INTERFACE,
+    &WL_DATA_DEVICE_MANAGER_INTERFACE,
+    &WL_SHELL_INTERFACE,
+    &WL_SHELL_SURFACE_INTERFACE,
+    &WL_SURFACE_INTERFACE,
+    &WL_SEAT_INTERFACE,
+    &WL_POINTER_INTERFACE,
+    &WL_KEYBOARD_INTERFACE,
+    &WL_TOUCH_INTERFACE,
+    &WL_OUTPUT_INTERFACE,
+    &WL_REGION_INTERFACE,
+    &WL_SUBCOMPOSITOR_INTERFACE,
+    &WL_SUBSURFACE_INTERFACE,
+];
+
+#[derive(Debug, Clone)]
+struct Global {
+    name: u32,
+    interface: String,
+    version: u32,
+}
+
+/// Prints the events of all the objects, and keeps track of the globals
+#[derive(Default)]
+struct Printer {
+    globals: Mutex<Vec<Global>>,
+}
+
+impl ObjectData for Printer {
+    fn event(
+        self: Arc<Self>,
+        _: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        let interface = msg.sender_id.interface();
+        let name = interface.events.get(msg.opcode as usize).map(|desc| desc.name).unwrap_or("?");
+        println!("<- {}.{}({})", msg.sender_id, name, format_args(&msg.args));
+
+        if std::ptr::eq(interface, &WL_REGISTRY_INTERFACE) {
+            let mut globals = self.globals.lock().unwrap();
+            match (msg.opcode, &msg.args[..]) {
+                (
+                    0,
+                    [Argument::Uint(name), Argument::Str(Some(interface)), Argument::Uint(version)],
+                ) => {
+                    globals.push(Global {
+                        name: *name,
+                        interface: interface.to_string_lossy().into_owned(),
+                        version: *version,
+                    });
+                }
+                (1, [Argument::Uint(name)]) => globals.retain(|global| global.name != *name),
+                _ => {}
+            }
+        }
+
+        if msg.args.iter().any(|arg| matches!(arg, Argument::NewId(_))) {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
+
+fn format_args<Fd: AsRawFd>(args: &[Argument<ObjectId, Fd>]) -> String {
+    let args = args.iter().map(|arg| match arg {
+        Argument::Int(value) => value.to_string(),
+        Argument::Uint(value) => value.to_string(),
+        Argument::Fixed(value) => (*value as f64 / 256.).to_string(),
+        Argument::Str(Some(value)) => format!("{:?}", value.to_string_lossy()),
+        Argument::Str(None) => "nil".into(),
+        Argument::Object(id) if id.is_null() => "nil".into(),
+        Argument::Object(id) => id.to_string(),
+        Argument::NewId(id) => format!("new id {}", id),
+        Argument::Array(bytes) => {
+            let bytes = bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>();
+            format!("[{}]", bytes.join(" "))
+        }
+        Argument::Fd(fd) => format!("fd {}", fd.as_raw_fd()),
+    });
+    args.collect::<Vec<_>>().join(", ")
+}
+
+struct Repl {
+    conn: Connection,
+    backend: Backend,
+    registry: ObjectId,
+    printer: Arc<Printer>,
+}
+
+impl Repl {
+    fn new(conn: Connection) -> Self {
+        let backend = conn.backend();
+        let printer = Arc::new(Printer::default());
+        let registry = Self::send(
+            &backend,
+            message(backend.display_id(), 1, vec![Argument::NewId(ObjectId::null())]),
+            Some(printer.clone()),
+            Some((&WL_REGISTRY_INTERFACE, 1)),
+        );
+        Self { conn, backend, registry, printer }
+    }
+
+    fn send(
+        backend: &Backend,
+        msg: Message<ObjectId, RawFd>,
+        data: Option<Arc<dyn ObjectData>>,
+        child_spec: Option<(&'static Interface, u32)>,
+    ) -> ObjectId {
+        let sender = msg.sender_id.clone();
+        let name = sender.interface().requests[msg.opcode as usize].name;
+        let mut args = msg.args.clone();
+        let created = backend.send_request(msg, data, child_spec).unwrap();
+        for arg in args.iter_mut() {
+            if let Argument::NewId(ref mut id) = arg {
+                *id = created.clone();
+            }
+        }
+        println!("-> {}.{}({})", sender, name, format_args(&args));
+        created
+    }
+
+    fn run(&mut self, command: Command) -> Result<(), String> {
+        match command {
+            Command::Help => println!("{}", HELP),
+            Command::Quit => {
+                let _ = self.conn.flush();
+                exit(0)
+            }
+            Command::Globals => {
+                for global in self.printer.globals.lock().unwrap().iter() {
+                    println!(
+                        "{:>4}  {} (version {})",
+                        global.name, global.interface, global.version
+                    );
+                }
+            }
+            Command::Objects => {
+                for id in self.backend.live_objects() {
+                    let version =
+                        self.backend.info(id.clone()).map(|info| info.version).unwrap_or(0);
+                    println!("{} (version {})", id, version);
+                }
+            }
+            Command::Sync => {
+                self.conn.roundtrip().map_err(|e| e.to_string())?;
+            }
+            Command::Bind { global, version } => self.bind(&global, version)?,
+            Command::Request { target, request, args } => self.request(target, &request, args)?,
+        }
+        Ok(())
+    }
+
+    fn bind(&self, global: &str, version: Option<u32>) -> Result<(), String> {
+        let global = {
+            let globals = self.printer.globals.lock().unwrap();
+            globals
+                .iter()
+                .find(|g| g.name.to_string() == global || g.interface == global)
+                .cloned()
+                .ok_or_else(|| format!("Unknown global: {}", global))?
+        };
+        let interface = INTERFACES
+            .iter()
+            .find(|interface| interface.name == global.interface)
+            .ok_or_else(|| format!("The interface {} is not known", global.interface))?;
+        let version = version.unwrap_or_else(|| global.version.min(interface.version));
+        if version == 0 || version > global.version {
+            return Err(format!(
+                "{} is advertised at version {}",
+                global.interface, global.version
+            ));
+        }
+
+        let name = CString::new(global.interface).unwrap();
+        let args = vec![
+            Argument::Uint(global.name),
+            Argument::Str(Some(Box::new(name))),
+            Argument::Uint(version),
+            Argument::NewId(ObjectId::null()),
+        ];
+        Self::send(
+            &self.backend,
+            message(self.registry.clone(), 0, args),
+            Some(self.printer.clone()),
+            Some((interface, version)),
+        );
+        Ok(())
+    }
+
+    fn find(&self, target: &Target) -> Result<ObjectId, String> {
+        let id = if target.id == 1 {
+            Some(self.backend.display_id())
+        } else {
+            self.backend.live_objects().into_iter().find(|id| id.protocol_id() == target.id)
+        };
+        let id = id.ok_or_else(|| format!("No object with id {}", target.id))?;
+        match target.interface {
+            Some(ref interface) if interface != id.interface().name => {
+                Err(format!("Object {} is not a {}", id, interface))
+            }
+            _ => Ok(id),
+        }
+    }
+
+    fn request(&self, target: Target, request: &str, values: Vec<Value>) -> Result<(), String> {
+        let id = self.find(&target)?;
+        let interface = id.interface();
+        let (opcode, desc) = interface
+            .requests
+            .iter()
+            .enumerate()
+            .find(|(_, desc)| desc.name == request)
+            .ok_or_else(|| format!("{} has no request {}", interface.name, request))?;
+        let version = self.backend.info(id.clone()).map_err(|e| e.to_string())?.version;
+        if desc.since > version {
+            return Err(format!("{}.{} requires version {}", interface.name, request, desc.since));
+        }
+        if values.len() != desc.signature.len() {
+            return Err(format!(
+                "{}.{} expects {} arguments: {:?}",
+                interface.name,
+                request,
+                desc.signature.len(),
+                desc.signature
+            ));
+        }
+
+        let mut arg_interfaces = desc.arg_interfaces.iter();
+        let mut args = Vec::with_capacity(values.len());
+        for (i, (value, signature)) in values.into_iter().zip(desc.signature).enumerate() {
+            let expected = match signature {
+                ArgumentType::Object(_) => arg_interfaces.next().copied(),
+                _ => None,
+            };
+            let arg = self
+                .convert(value, *signature, expected)
+                .map_err(|e| format!("Argument {}: {}", i + 1, e))?;
+            args.push(arg);
+        }
+
+        let child_spec = match desc.child_interface {
+            Some(child) => Some((child, version)),
+            None if desc.signature.contains(&ArgumentType::NewId) => {
+                return Err("This request creates an object of any interface, use bind".into())
+            }
+            None => None,
+        };
+        let data = child_spec.map(|_| self.printer.clone() as Arc<dyn ObjectData>);
+        Self::send(&self.backend, message(id, opcode as u16, args), data, child_spec);
+        Ok(())
+    }
+
+    fn convert(
+        &self,
+        value: Value,
+        signature: ArgumentType,
+        expected: Option<&'static Interface>,
+    ) -> Result<Argument<ObjectId, RawFd>, String> {
+        let arg = match (signature, value) {
+            (ArgumentType::Int, Value::Int(v)) => {
+                Argument::Int(i32::try_from(v).map_err(|_| "Out of range for an int")?)
+            }
+            (ArgumentType::Uint, Value::Int(v)) => {
+                Argument::Uint(u32::try_from(v).map_err(|_| "Out of range for an uint")?)
+            }
+            (ArgumentType::Fixed, Value::Int(v)) => Argument::Fixed((v * 256) as i32),
+            (ArgumentType::Fixed, Value::Float(v)) => Argument::Fixed((v * 256.).round() as i32),
+            (ArgumentType::Str(_), Value::Str(s)) => Argument::Str(Some(Box::new(
+                CString::new(s).map_err(|_| "Strings cannot contain a nul byte")?,
+            ))),
+            (ArgumentType::Str(AllowNull::Yes), Value::Nil) => Argument::Str(None),
+            (ArgumentType::Object(AllowNull::Yes), Value::Nil) => {
+                Argument::Object(ObjectId::null())
+            }
+            (ArgumentType::Object(_), Value::Object(target)) => {
+                let id = self.find(&target)?;
+                let expected = expected.unwrap_or(&ANONYMOUS_INTERFACE);
+                if !std::ptr::eq(expected, &ANONYMOUS_INTERFACE)
+                    && expected.name != id.interface().name
+                {
+                    return Err(format!("Expected a {}, got {}", expected.name, id));
+                }
+                Argument::Object(id)
+            }
+            (ArgumentType::NewId, Value::New) => Argument::NewId(ObjectId::null()),
+            (ArgumentType::Array, Value::Array(bytes)) => Argument::Array(Box::new(bytes)),
+            (ArgumentType::Fd, Value::Int(fd)) => {
+                let fd = RawFd::try_from(fd).map_err(|_| "Invalid file descriptor")?;
+                nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFD)
+                    .map_err(|e| format!("Invalid file descriptor {}: {}", fd, e))?;
+                Argument::Fd(fd)
+            }
+            (signature, value) => return Err(format!("Expected {:?}, got {:?}", signature, value)),
+        };
+        Ok(arg)
+    }
+}
+
+fn message(
+    sender_id: ObjectId,
+    opcode: u16,
+    args: Vec<Argument<ObjectId, RawFd>>,
+) -> Message<ObjectId, RawFd> {
+    Message { sender_id, opcode, args: SmallVec::from_vec(args) }
+}
+
+/// Read the lines of stdin in a thread, waking the main loop up through a socket for each of them
+fn spawn_stdin_reader() -> (Receiver<String>, UnixStream) {
+    let (tx, rx) = mpsc::channel();
+    let (mut waker, wake) = UnixStream::pair().unwrap();
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if tx.send(line).is_err() {
+                return;
+            }
+            let _ = waker.write_all(&[0]);
+        }
+        // dropping the sender and the waker signals the end of the input
+    });
+    (rx, wake)
+}
+
+fn main() {
+    if std::env::args().skip(1).any(|arg| arg == "-h" || arg == "--help") {
+        println!(
+            "Usage: wayland-repl\n\nConnect to the compositor of WAYLAND_DISPLAY.\n\n{}",
+            HELP
+        );
+        return;
+    }
+
+    let conn = match Connection::connect_to_env() {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("Failed to connect to the compositor: {}", e);
+            exit(1);
+        }
+    };
+    let mut repl = Repl::new(conn);
+    if let Err(e) = repl.conn.roundtrip() {
+        eprintln!("{}", e);
+        exit(1);
+    }
+
+    let (lines, mut wake) = spawn_stdin_reader();
+    loop {
+        if let Err(e) = repl.conn.flush() {
+            eprintln!("{}", e);
+            exit(1);
+        }
+
+        let guard = match repl.conn.prepare_read() {
+            Some(guard) => guard,
+            None => {
+                // events are already waiting to be dispatched
+                let _ = repl.backend.dispatch_inner_queue();
+                continue;
+            }
+        };
+        let flags = nix::poll::PollFlags::POLLIN | nix::poll::PollFlags::POLLERR;
+        let mut fds = [
+            nix::poll::PollFd::new(guard.connection_fd().as_raw_fd(), flags),
+            nix::poll::PollFd::new(wake.as_raw_fd(), flags),
+        ];
+        match nix::poll::poll(&mut fds, -1) {
+            Ok(_) | Err(nix::errno::Errno::EINTR) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+
+        if fds[0].revents().map_or(false, |revents| !revents.is_empty()) {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+            }
+        } else {
+            drop(guard);
+        }
+
+        if fds[1].revents().map_or(false, |revents| !revents.is_empty()) {
+            let _ = wake.read(&mut [0; 64]);
+        }
+        loop {
+            match lines.try_recv() {
+                Ok(line) => match parse_command(&line) {
+                    Ok(Some(command)) => {
+                        if let Err(e) = repl.run(command) {
+                            println!("error: {}", e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => println!("error: {}", e),
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    let _ = repl.conn.flush();
+                    return;
+                }
+            }
+        }
+    }
+}
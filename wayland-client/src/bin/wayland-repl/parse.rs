@@ -0,0 +1,279 @@
+//! Parsing of the commands typed in the REPL
+//!
+//! Requests are written like in the `WAYLAND_DEBUG` output, the interface being optional:
+//!
+//! ```text
+//! wl_compositor@4.create_surface(new)
+//! @5.attach(@7, 0, 0)
+//! @9.set_title("hello")
+//! ```
+
+/// A command typed by the user
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Quit,
+    Globals,
+    Objects,
+    Sync,
+    /// Bind a global, given by name or by interface, at the given version or the advertised one
+    Bind {
+        global: String,
+        version: Option<u32>,
+    },
+    Request {
+        target: Target,
+        request: String,
+        args: Vec<Value>,
+    },
+}
+
+/// An object, given by its protocol id and optionally its interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub interface: Option<String>,
+    pub id: u32,
+}
+
+/// An argument of a request, converted according to the signature of the request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// An integer, used for `int`, `uint`, `fixed` and `fd` arguments
+    Int(i64),
+    /// A decimal number, for `fixed` arguments
+    Float(f64),
+    Str(String),
+    Object(Target),
+    /// A null object or string
+    Nil,
+    /// The object created by the request
+    New,
+    /// An array of bytes, written in hexadecimal
+    Array(Vec<u8>),
+}
+
+/// Parse a line typed by the user, returning `None` for an empty line
+pub fn parse_command(line: &str) -> Result<Option<Command>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some("help") => Command::Help,
+        Some("quit") | Some("exit") => Command::Quit,
+        Some("globals") => Command::Globals,
+        Some("objects") => Command::Objects,
+        Some("sync") => Command::Sync,
+        Some("bind") => {
+            let global = words.next().ok_or("Usage: bind <name or interface> [version]")?;
+            let version = match words.next() {
+                Some(version) => {
+                    Some(version.parse().map_err(|_| format!("Invalid version: {}", version))?)
+                }
+                None => None,
+            };
+            Command::Bind { global: global.to_owned(), version }
+        }
+        _ => return parse_request(line).map(Some),
+    };
+    match words.next() {
+        Some(extra) => Err(format!("Unexpected argument: {}", extra)),
+        None => Ok(Some(command)),
+    }
+}
+
+fn parse_request(line: &str) -> Result<Command, String> {
+    let (head, args) = line.split_once('(').ok_or_else(|| format!("Unknown command: {}", line))?;
+    let args = args.trim_end().strip_suffix(')').ok_or("Missing closing parenthesis")?;
+    let (target, request) = head.trim().split_once('.').ok_or("Expected object.request(...)")?;
+    let target = parse_target(target).ok_or_else(|| format!("Invalid object: {}", target))?;
+    if request.is_empty() || !request.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("Invalid request name: {}", request));
+    }
+    let args = split_args(args)?.into_iter().map(parse_value).collect::<Result<_, _>>()?;
+    Ok(Command::Request { target, request: request.to_owned(), args })
+}
+
+// `interface@id` or `@id`
+fn parse_target(text: &str) -> Option<Target> {
+    let (interface, id) = text.split_once('@')?;
+    if !interface.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let interface = if interface.is_empty() { None } else { Some(interface.to_owned()) };
+    Some(Target { interface, id: id.parse().ok()? })
+}
+
+// Split the arguments on the commas that are not part of a string or an array
+fn split_args(args: &str) -> Result<Vec<&str>, String> {
+    let mut result = Vec::new();
+    let mut in_string = false;
+    let mut in_array = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' => in_array = true,
+            ']' => in_array = false,
+            ',' if !in_array => {
+                result.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if in_string {
+        return Err("Unterminated string".into());
+    }
+    let last = args[start..].trim();
+    if !last.is_empty() || !result.is_empty() {
+        result.push(last);
+    }
+    Ok(result)
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    match text {
+        "" => return Err("Empty argument".into()),
+        "nil" => return Ok(Value::Nil),
+        "new" => return Ok(Value::New),
+        _ => {}
+    }
+    if let Some(string) = text.strip_prefix('"') {
+        let string = string.strip_suffix('"').ok_or("Unterminated string")?;
+        return unescape(string).map(Value::Str);
+    }
+    if let Some(bytes) = text.strip_prefix('[') {
+        let bytes = bytes.strip_suffix(']').ok_or("Unterminated array")?;
+        return bytes
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|byte| !byte.is_empty())
+            .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| format!("Invalid byte: {}", byte)))
+            .collect::<Result<_, _>>()
+            .map(Value::Array);
+    }
+    if text.contains('@') {
+        return parse_target(text)
+            .map(Value::Object)
+            .ok_or_else(|| format!("Invalid object: {}", text));
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => digits.parse::<i64>().ok(),
+    };
+    match value {
+        Some(value) => Ok(Value::Int(if negative { -value } else { value })),
+        None => text.parse().map(Value::Float).map_err(|_| format!("Invalid argument: {}", text)),
+    }
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(c @ ('\\' | '"')) => result.push(c),
+            Some(c) => return Err(format!("Invalid escape sequence: \\{}", c)),
+            None => return Err("Unterminated string".into()),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(interface: Option<&str>, id: u32) -> Target {
+        Target { interface: interface.map(Into::into), id }
+    }
+
+    #[test]
+    fn requests() {
+        assert_eq!(
+            parse_command("wl_compositor@4.create_surface(new)").unwrap(),
+            Some(Command::Request {
+                target: target(Some("wl_compositor"), 4),
+                request: "create_surface".into(),
+                args: vec![Value::New],
+            })
+        );
+        assert_eq!(
+            parse_command(" @5.attach(@7, -1, 0x10) ").unwrap(),
+            Some(Command::Request {
+                target: target(None, 5),
+                request: "attach".into(),
+                args: vec![Value::Object(target(None, 7)), Value::Int(-1), Value::Int(16)],
+            })
+        );
+        assert_eq!(
+            parse_command(r#"@9.set_title("a, \"b\"", nil, [01 ff, 2a], 1.5)"#).unwrap(),
+            Some(Command::Request {
+                target: target(None, 9),
+                request: "set_title".into(),
+                args: vec![
+                    Value::Str("a, \"b\"".into()),
+                    Value::Nil,
+                    Value::Array(vec![0x01, 0xff, 0x2a]),
+                    Value::Float(1.5),
+                ],
+            })
+        );
+        assert_eq!(
+            parse_command("@3.commit()").unwrap(),
+            Some(Command::Request {
+                target: target(None, 3),
+                request: "commit".into(),
+                args: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command("").unwrap(), None);
+        assert_eq!(parse_command("# comment").unwrap(), None);
+        assert_eq!(parse_command("globals").unwrap(), Some(Command::Globals));
+        assert_eq!(
+            parse_command("bind wl_seat 7").unwrap(),
+            Some(Command::Bind { global: "wl_seat".into(), version: Some(7) })
+        );
+        assert_eq!(
+            parse_command("bind 3").unwrap(),
+            Some(Command::Bind { global: "3".into(), version: None })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse_command("frobnicate").is_err());
+        assert!(parse_command("bind").is_err());
+        assert!(parse_command("sync now").is_err());
+        assert!(parse_command("@3.commit(").is_err());
+        assert!(parse_command("wl_surface.commit()").is_err());
+        assert!(parse_command("@3.set_title(\"unterminated)").is_err());
+        assert!(parse_command("@3.damage(1,,2)").is_err());
+    }
+}
@@ -0,0 +1,564 @@
+//! Helpers for copying and pasting through the selection of a seat
+//!
+//! A [`Clipboard`] owns the `wl_data_device` of a seat and keeps track of the data offers the compositor
+//! sends it, to always know the current selection and its mime types. [`Clipboard::copy()`] sets a new
+//! selection provided by the app and [`Clipboard::paste()`] asks the owner of the current selection to
+//! write its contents into a pipe.
+//!
+//! The compositor only sends the selection to the client having the keyboard focus, and only accepts a
+//! new selection from this client. The serial given to [`Clipboard::copy()`] must be the one of the input
+//! event which triggered the copy, usually a `wl_keyboard.key` or `wl_pointer.button` event.
+//!
+//! With the `primary-selection` cargo feature, [`Clipboard::new_primary()`] creates a clipboard for the
+//! primary selection instead, which is the selection pasted with the middle button of the mouse. It takes
+//! the `zwp_primary_selection_device_manager_v1` bound by the app, for example with the types generated by
+//! the `wayland-protocols` crate.
+//!
+//! The events of the objects of the clipboard are processed as soon as they are read from the
+//! connection, independently of any event queue. The drag-and-drop offers received by the clipboard are
+//! ignored.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use std::io::{Read, Write};
+//! use wayland_client::{
+//!     clipboard::Clipboard,
+//!     protocol::{wl_data_device_manager, wl_seat},
+//! };
+//! # fn copy_paste(
+//! #     manager: &wl_data_device_manager::WlDataDeviceManager,
+//! #     seat: &wl_seat::WlSeat,
+//! #     conn: &wayland_client::Connection,
+//! #     serial: u32,
+//! # ) -> Result<(), wayland_client::clipboard::ClipboardError> {
+//! let clipboard = Clipboard::new(manager, seat)?;
+//!
+//! // the callback writes the data when another client pastes it
+//! clipboard.copy(vec!["text/plain;charset=utf-8".into()], serial, |_mime_type, fd| {
+//!     let _ = std::fs::File::from(fd).write_all(b"Hello World!");
+//! })?;
+//!
+//! // later, once the compositor sent the selection
+//! let (_mime_type, fd) = clipboard.paste(&["text/plain;charset=utf-8", "text/plain"])?;
+//! conn.flush().unwrap();
+//! // the owner of the selection, which may be this app, writes the data while it processes its events:
+//! // read the pipe from another thread, and keep processing the events until it is done
+//! let reader = std::thread::spawn(move || {
+//!     let mut text = String::new();
+//!     std::fs::File::from(fd).read_to_string(&mut text).map(|_| text)
+//! });
+//! while !reader.is_finished() {
+//!     conn.roundtrip().unwrap();
+//! }
+//! let text = reader.join().unwrap()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt, io,
+    os::unix::io::{AsFd, FromRawFd, OwnedFd},
+    sync::{Arc, Mutex},
+};
+
+use nix::{errno::Errno, fcntl::OFlag, unistd};
+use wayland_backend::{
+    client::{Backend, InvalidId, ObjectData, ObjectId},
+    protocol::{same_interface, Message},
+};
+
+use crate::{
+    protocol::{wl_data_device, wl_data_device_manager, wl_data_offer, wl_data_source, wl_seat},
+    Connection, Proxy,
+};
+
+#[cfg(feature = "primary-selection")]
+#[allow(missing_docs, dead_code, non_upper_case_globals, non_camel_case_types, unused_imports)]
+mod primary {
+    use crate as wayland_client;
+    use crate::protocol::*;
+
+    pub mod __interfaces {
+        use crate::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("primary-selection-unstable-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("primary-selection-unstable-v1.xml");
+}
+
+#[cfg(feature = "primary-selection")]
+use primary::{
+    zwp_primary_selection_device_manager_v1 as zwp_manager,
+    zwp_primary_selection_device_v1 as zwp_device, zwp_primary_selection_offer_v1 as zwp_offer,
+    zwp_primary_selection_source_v1 as zwp_source,
+};
+
+/// An error when using a [`Clipboard`]
+#[derive(Debug)]
+pub enum ClipboardError {
+    /// The seat has no selection, or the client does not have the keyboard focus
+    NoSelection,
+    /// The selection is not available in any of the requested mime types
+    NoMatchingMimeType,
+    /// The pipe for the transfer could not be created
+    Io(io::Error),
+    /// The objects of the clipboard are no longer alive
+    InvalidId(InvalidId),
+    /// The manager given to [`Clipboard::new_primary()`] is not a `zwp_primary_selection_device_manager_v1`
+    #[cfg(feature = "primary-selection")]
+    NotPrimarySelection,
+}
+
+impl std::error::Error for ClipboardError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClipboardError::Io(e) => Some(e),
+            ClipboardError::InvalidId(e) => Some(e),
+            ClipboardError::NoSelection | ClipboardError::NoMatchingMimeType => None,
+            #[cfg(feature = "primary-selection")]
+            ClipboardError::NotPrimarySelection => None,
+        }
+    }
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipboardError::NoSelection => write!(f, "The seat has no selection"),
+            ClipboardError::NoMatchingMimeType => {
+                write!(f, "The selection is not available in the requested mime types")
+            }
+            ClipboardError::Io(e) => write!(f, "Pipe error: {}", e),
+            ClipboardError::InvalidId(e) => write!(f, "{}", e),
+            #[cfg(feature = "primary-selection")]
+            ClipboardError::NotPrimarySelection => {
+                write!(f, "The manager is not a primary selection device manager")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ClipboardError {
+    fn from(e: io::Error) -> Self {
+        ClipboardError::Io(e)
+    }
+}
+
+impl From<Errno> for ClipboardError {
+    fn from(e: Errno) -> Self {
+        ClipboardError::Io(e.into())
+    }
+}
+
+impl From<InvalidId> for ClipboardError {
+    fn from(e: InvalidId) -> Self {
+        ClipboardError::InvalidId(e)
+    }
+}
+
+type SendCallback = Box<dyn FnMut(String, OwnedFd) + Send>;
+
+/// A clipboard of a seat
+///
+/// See [the module level documentation](self).
+#[derive(Debug)]
+pub struct Clipboard {
+    manager: Manager,
+    device: Device,
+    state: Arc<Mutex<ClipboardState>>,
+}
+
+#[derive(Debug)]
+enum Manager {
+    Data(wl_data_device_manager::WlDataDeviceManager),
+    #[cfg(feature = "primary-selection")]
+    Primary(zwp_manager::ZwpPrimarySelectionDeviceManagerV1),
+}
+
+#[derive(Debug)]
+enum Device {
+    Data(wl_data_device::WlDataDevice),
+    #[cfg(feature = "primary-selection")]
+    Primary(zwp_device::ZwpPrimarySelectionDeviceV1),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Offer {
+    Data(wl_data_offer::WlDataOffer),
+    #[cfg(feature = "primary-selection")]
+    Primary(zwp_offer::ZwpPrimarySelectionOfferV1),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Data(wl_data_source::WlDataSource),
+    #[cfg(feature = "primary-selection")]
+    Primary(zwp_source::ZwpPrimarySelectionSourceV1),
+}
+
+impl Offer {
+    fn id(&self) -> ObjectId {
+        match self {
+            Offer::Data(offer) => offer.id(),
+            #[cfg(feature = "primary-selection")]
+            Offer::Primary(offer) => offer.id(),
+        }
+    }
+
+    fn receive(&self, mime_type: String, fd: &OwnedFd) {
+        match self {
+            Offer::Data(offer) => offer.receive(mime_type, fd.as_fd()),
+            #[cfg(feature = "primary-selection")]
+            Offer::Primary(offer) => offer.receive(mime_type, fd.as_fd()),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Offer::Data(offer) => offer.destroy(),
+            #[cfg(feature = "primary-selection")]
+            Offer::Primary(offer) => offer.destroy(),
+        }
+    }
+}
+
+impl Source {
+    fn id(&self) -> ObjectId {
+        match self {
+            Source::Data(source) => source.id(),
+            #[cfg(feature = "primary-selection")]
+            Source::Primary(source) => source.id(),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Source::Data(source) => source.destroy(),
+            #[cfg(feature = "primary-selection")]
+            Source::Primary(source) => source.destroy(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClipboardState {
+    // the offers announced by the compositor, until they become the selection
+    offers: Vec<(Offer, Vec<String>)>,
+    selection: Option<(Offer, Vec<String>)>,
+    source: Option<Source>,
+}
+
+impl ClipboardState {
+    fn new_selection(&mut self, id: Option<ObjectId>) {
+        if let Some((offer, _)) = self.selection.take() {
+            offer.destroy();
+        }
+        if let Some(id) = id {
+            if let Some(index) = self.offers.iter().position(|(offer, _)| offer.id() == id) {
+                self.selection = Some(self.offers.remove(index));
+            }
+        }
+    }
+
+    // The offers which will not become the selection, like the ones of a drag-and-drop
+    fn discard_offer(&mut self, id: ObjectId) {
+        if let Some(index) = self.offers.iter().position(|(offer, _)| offer.id() == id) {
+            self.offers.remove(index).0.destroy();
+        }
+    }
+
+    fn add_mime_type(&mut self, id: ObjectId, mime_type: String) {
+        let offer =
+            self.offers.iter_mut().chain(self.selection.as_mut()).find(|(o, _)| o.id() == id);
+        if let Some((_, mime_types)) = offer {
+            mime_types.push(mime_type);
+        }
+    }
+}
+
+impl Clipboard {
+    /// Create a clipboard for the selection of a seat
+    pub fn new(
+        manager: &wl_data_device_manager::WlDataDeviceManager,
+        seat: &wl_seat::WlSeat,
+    ) -> Result<Clipboard, ClipboardError> {
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let device = manager.send_constructor(
+            wl_data_device_manager::Request::GetDataDevice { seat: seat.clone() },
+            Arc::new(DeviceData { state: state.clone() }),
+        )?;
+        Ok(Clipboard {
+            manager: Manager::Data(manager.clone()),
+            device: Device::Data(device),
+            state,
+        })
+    }
+
+    /// Create a clipboard for the primary selection of a seat
+    ///
+    /// The manager is a `zwp_primary_selection_device_manager_v1` bound by the app, like the one generated
+    /// by the `wayland-protocols` crate. It stays owned by the app, which must not destroy it before the
+    /// clipboard: [`Clipboard::copy()`] creates its sources from the manager.
+    #[cfg(feature = "primary-selection")]
+    pub fn new_primary<M: Proxy>(
+        manager: &M,
+        seat: &wl_seat::WlSeat,
+    ) -> Result<Clipboard, ClipboardError> {
+        let conn = Connection::from_backend(manager.backend().upgrade().ok_or(InvalidId)?);
+        let manager = zwp_manager::ZwpPrimarySelectionDeviceManagerV1::from_id(&conn, manager.id())
+            .map_err(|_| ClipboardError::NotPrimarySelection)?;
+        let state = Arc::new(Mutex::new(ClipboardState::default()));
+        let device = manager.send_constructor(
+            zwp_manager::Request::GetDevice { seat: seat.clone() },
+            Arc::new(DeviceData { state: state.clone() }),
+        )?;
+        Ok(Clipboard { manager: Manager::Primary(manager), device: Device::Primary(device), state })
+    }
+
+    /// Set the selection of the seat to data provided by the app
+    ///
+    /// The callback is invoked with a mime type and a file descriptor every time another client pastes
+    /// the data; it should write the data in this format to the file descriptor and close it. The
+    /// selection stays available until another client sets the selection, or [`Clipboard::clear()`] is
+    /// called.
+    pub fn copy<F>(
+        &self,
+        mime_types: Vec<String>,
+        serial: u32,
+        callback: F,
+    ) -> Result<(), ClipboardError>
+    where
+        F: FnMut(String, OwnedFd) + Send + 'static,
+    {
+        let data = Arc::new(SourceData {
+            state: self.state.clone(),
+            callback: Mutex::new(Box::new(callback)),
+        });
+        let source = match self.manager {
+            Manager::Data(ref manager) => Source::Data(
+                manager
+                    .send_constructor(wl_data_device_manager::Request::CreateDataSource {}, data)?,
+            ),
+            #[cfg(feature = "primary-selection")]
+            Manager::Primary(ref manager) => Source::Primary(
+                manager.send_constructor(zwp_manager::Request::CreateSource {}, data)?,
+            ),
+        };
+        match (&self.device, &source) {
+            (Device::Data(device), Source::Data(source)) => {
+                for mime_type in mime_types {
+                    source.offer(mime_type);
+                }
+                device.set_selection(Some(source), serial);
+            }
+            #[cfg(feature = "primary-selection")]
+            (Device::Primary(device), Source::Primary(source)) => {
+                for mime_type in mime_types {
+                    source.offer(mime_type);
+                }
+                device.set_selection(Some(source), serial);
+            }
+            #[cfg(feature = "primary-selection")]
+            _ => unreachable!(),
+        }
+        if let Some(old) = self.state.lock().unwrap().source.replace(source) {
+            old.destroy();
+        }
+        Ok(())
+    }
+
+    /// Clear the selection of the seat, if it was set by this clipboard
+    pub fn clear(&self, serial: u32) {
+        let source = match self.state.lock().unwrap().source.take() {
+            Some(source) => source,
+            None => return,
+        };
+        match self.device {
+            Device::Data(ref device) => device.set_selection(None, serial),
+            #[cfg(feature = "primary-selection")]
+            Device::Primary(ref device) => device.set_selection(None, serial),
+        }
+        source.destroy();
+    }
+
+    /// The mime types of the current selection, `None` if the seat has no selection
+    pub fn mime_types(&self) -> Option<Vec<String>> {
+        self.state.lock().unwrap().selection.as_ref().map(|(_, mime_types)| mime_types.clone())
+    }
+
+    /// Whether the current selection was set by this clipboard
+    pub fn owns_selection(&self) -> bool {
+        self.state.lock().unwrap().source.is_some()
+    }
+
+    /// Paste the current selection
+    ///
+    /// The first of the preferred mime types which is offered by the selection is requested, any mime
+    /// type is accepted if no preference is given. The chosen mime type and the read end of a pipe are
+    /// returned; the data can be read from the pipe once the request is flushed to the compositor, until
+    /// the owner of the selection closes it.
+    ///
+    /// If the selection was set by this app, the data is written by the callback given to
+    /// [`Clipboard::copy()`], which is only invoked when the events of the connection are read: reading
+    /// the pipe from the same thread would block forever.
+    pub fn paste(&self, preferred: &[&str]) -> Result<(String, OwnedFd), ClipboardError> {
+        let state = self.state.lock().unwrap();
+        let (offer, mime_types) = state.selection.as_ref().ok_or(ClipboardError::NoSelection)?;
+        let mime_type = if preferred.is_empty() {
+            mime_types.first().cloned()
+        } else {
+            preferred
+                .iter()
+                .find(|&&mime| mime_types.iter().any(|m| m == mime))
+                .map(|m| m.to_string())
+        }
+        .ok_or(ClipboardError::NoMatchingMimeType)?;
+
+        let (read, write) = unistd::pipe2(OFlag::O_CLOEXEC)?;
+        let (read, write) = unsafe { (OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)) };
+        offer.receive(mime_type.clone(), &write);
+        Ok((mime_type, read))
+    }
+}
+
+impl Drop for Clipboard {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        let selection = state.selection.take();
+        for (offer, _) in state.offers.drain(..).chain(selection) {
+            offer.destroy();
+        }
+        if let Some(source) = state.source.take() {
+            source.destroy();
+        }
+        match self.device {
+            Device::Data(ref device) if device.version() >= 2 => device.release(),
+            Device::Data(_) => {}
+            #[cfg(feature = "primary-selection")]
+            Device::Primary(ref device) => device.destroy(),
+        }
+    }
+}
+
+struct DeviceData {
+    state: Arc<Mutex<ClipboardState>>,
+}
+
+impl ObjectData for DeviceData {
+    fn event(
+        self: Arc<Self>,
+        backend: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        let conn = Connection::from_backend(backend.clone());
+        let mut state = self.state.lock().unwrap();
+        if same_interface(msg.sender_id.interface(), wl_data_device::WlDataDevice::interface()) {
+            match wl_data_device::WlDataDevice::parse_event(&conn, msg).ok()?.1 {
+                wl_data_device::Event::DataOffer { id } => {
+                    state.offers.push((Offer::Data(id), Vec::new()));
+                    return Some(Arc::new(OfferData { state: self.state.clone() }));
+                }
+                wl_data_device::Event::Selection { id } => {
+                    state.new_selection(id.map(|offer| offer.id()))
+                }
+                wl_data_device::Event::Enter { id: Some(offer), .. } => {
+                    state.discard_offer(offer.id())
+                }
+                _ => {}
+            }
+        } else {
+            #[cfg(feature = "primary-selection")]
+            match zwp_device::ZwpPrimarySelectionDeviceV1::parse_event(&conn, msg).ok()?.1 {
+                zwp_device::Event::DataOffer { offer } => {
+                    state.offers.push((Offer::Primary(offer), Vec::new()));
+                    return Some(Arc::new(OfferData { state: self.state.clone() }));
+                }
+                zwp_device::Event::Selection { id } => {
+                    state.new_selection(id.map(|offer| offer.id()))
+                }
+            }
+        }
+        None
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
+
+struct OfferData {
+    state: Arc<Mutex<ClipboardState>>,
+}
+
+impl ObjectData for OfferData {
+    fn event(
+        self: Arc<Self>,
+        backend: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        let conn = Connection::from_backend(backend.clone());
+        let id = msg.sender_id.clone();
+        let mime_type = if same_interface(id.interface(), wl_data_offer::WlDataOffer::interface()) {
+            match wl_data_offer::WlDataOffer::parse_event(&conn, msg).ok()?.1 {
+                wl_data_offer::Event::Offer { mime_type } => mime_type,
+                // the actions are only useful for drag-and-drop
+                _ => return None,
+            }
+        } else {
+            #[cfg(feature = "primary-selection")]
+            match zwp_offer::ZwpPrimarySelectionOfferV1::parse_event(&conn, msg).ok()?.1 {
+                zwp_offer::Event::Offer { mime_type } => mime_type,
+            }
+            #[cfg(not(feature = "primary-selection"))]
+            return None;
+        };
+        self.state.lock().unwrap().add_mime_type(id, mime_type);
+        None
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
+
+struct SourceData {
+    state: Arc<Mutex<ClipboardState>>,
+    callback: Mutex<SendCallback>,
+}
+
+impl ObjectData for SourceData {
+    fn event(
+        self: Arc<Self>,
+        backend: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        let conn = Connection::from_backend(backend.clone());
+        let id = msg.sender_id.clone();
+        let send = if same_interface(id.interface(), wl_data_source::WlDataSource::interface()) {
+            match wl_data_source::WlDataSource::parse_event(&conn, msg).ok()?.1 {
+                wl_data_source::Event::Send { mime_type, fd } => Some((mime_type, fd)),
+                wl_data_source::Event::Cancelled => None,
+                _ => return None,
+            }
+        } else {
+            #[cfg(feature = "primary-selection")]
+            match zwp_source::ZwpPrimarySelectionSourceV1::parse_event(&conn, msg).ok()?.1 {
+                zwp_source::Event::Send { mime_type, fd } => Some((mime_type, fd)),
+                zwp_source::Event::Cancelled => None,
+            }
+            #[cfg(not(feature = "primary-selection"))]
+            return None;
+        };
+        match send {
+            Some((mime_type, fd)) => (self.callback.lock().unwrap())(mime_type, fd),
+            // another client took the selection, the source is useless now
+            None => {
+                let mut state = self.state.lock().unwrap();
+                if state.source.as_ref().map(Source::id) == Some(id) {
+                    state.source.take().unwrap().destroy();
+                }
+            }
+        }
+        None
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
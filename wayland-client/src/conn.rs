@@ -9,16 +9,20 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Instant,
 };
 
 use wayland_backend::{
-    client::{Backend, InvalidId, ObjectData, ObjectId, ReadEventsGuard, WaylandError},
-    protocol::{ObjectInfo, ProtocolError},
+    client::{
+        Backend, FlushPolicy, InvalidId, ObjectData, ObjectId, ProtocolLogger, ReadEventsGuard,
+        WaylandError, ZombieHook,
+    },
+    protocol::{IoStats, MessageLimits, Metrics, ObjectInfo, ProtocolError},
 };
 
 use nix::{fcntl, Error};
 
-use crate::{protocol::wl_display::WlDisplay, EventQueue, Proxy};
+use crate::{event_queue::Wakeup, protocol::wl_display::WlDisplay, EventQueue, Proxy};
 
 /// The Wayland connection
 ///
@@ -37,7 +41,14 @@ use crate::{protocol::wl_display::WlDisplay, EventQueue, Proxy};
 /// In case you need to plug yourself into an external Wayland connection that you don't control, you'll
 /// likely get access to it as a [`Backend`], in which case you can create a [`Connection`] from it using
 /// the [`from_backend`](Connection::from_backend) method.
-#[derive(Debug, Clone)]
+///
+/// If the external connection is instead given to you as a raw `*mut wl_display` (for example by SDL or
+/// GLFW), enable the `client_system` cargo feature of `wayland-backend` and wrap it with
+/// `Backend::from_foreign_display()`. The resulting [`Connection`] uses its own event queue and never
+/// closes the foreign connection, so it can speak additional protocols alongside the host library.
+///
+/// Two [`Connection`]s compare equal if they refer to the same Wayland connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Connection {
     pub(crate) backend: Backend,
 }
@@ -99,6 +110,20 @@ impl Connection {
         Ok(Self { backend })
     }
 
+    /// Initialize a Wayland connection from the file descriptor of a connected Unix stream socket
+    ///
+    /// This is the owned counterpart of the `WAYLAND_SOCKET` environment variable, for sandboxes and
+    /// test harnesses handing over a socket by other means. The connection takes ownership of the
+    /// file descriptor and closes it when dropped. Returns [`ConnectError::InvalidFd`] if the file
+    /// descriptor is not a stream socket.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, ConnectError> {
+        use nix::sys::socket::{getsockopt, sockopt, SockType};
+        match getsockopt(fd.as_raw_fd(), sockopt::SockType) {
+            Ok(SockType::Stream) => Self::from_socket(UnixStream::from(fd)),
+            _ => Err(ConnectError::InvalidFd),
+        }
+    }
+
     /// Get the `WlDisplay` associated with this connection
     pub fn display(&self) -> WlDisplay {
         let display_id = self.backend.display_id();
@@ -123,11 +148,51 @@ impl Connection {
     /// Flush pending outgoing events to the server
     ///
     /// This needs to be done regularly to ensure the server receives all your requests, though several
-    /// dispatching methods do it implicitly (this is stated in their documentation when they do).
+    /// dispatching methods do it implicitly (this is stated in their documentation when they do), unless
+    /// the [flush policy](Connection::set_flush_policy) says otherwise.
     pub fn flush(&self) -> Result<(), WaylandError> {
         self.backend.flush()
     }
 
+    /// Set when the requests are written to the socket
+    ///
+    /// With the default [`FlushPolicy::Dispatch`], the dispatching methods which wait for the server
+    /// flush the connection first. Latency-sensitive apps can instead batch the requests of a frame
+    /// with [`FlushPolicy::OnFull`] or [`FlushPolicy::Manual`], and call [`flush()`](Connection::flush)
+    /// exactly once before waiting for the frame callback. The roundtrip methods always flush, as they
+    /// could not complete otherwise.
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        self.backend.set_flush_policy(policy)
+    }
+
+    /// Set the maximum size and number of file descriptors of the messages of the connection
+    ///
+    /// Receiving an event exceeding them is a fatal error of the connection, see
+    /// [`Backend::set_message_limits()`] for details. Only the rust backend applies these limits.
+    pub fn set_message_limits(&self, limits: MessageLimits) {
+        self.backend.set_message_limits(limits)
+    }
+
+    /// The current flush policy of the connection
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.backend.flush_policy()
+    }
+
+    /// Number of bytes of requests waiting to be flushed
+    ///
+    /// This is always 0 with the system backend, whose buffer is managed by `libwayland-client.so`.
+    pub fn pending_bytes(&self) -> usize {
+        self.backend.pending_bytes()
+    }
+
+    /// Flush before waiting for the server, if the flush policy asks for it
+    pub(crate) fn implicit_flush(&self) -> Result<(), WaylandError> {
+        match self.backend.flush_policy() {
+            FlushPolicy::Dispatch => self.backend.flush(),
+            FlushPolicy::OnFull | FlushPolicy::Manual => Ok(()),
+        }
+    }
+
     /// Start a synchronized read from the socket
     ///
     /// This is needed if you plan to wait on readiness of the Wayland socket using an event loop. See
@@ -164,7 +229,7 @@ impl Connection {
             self.backend.flush()?;
 
             if let Some(guard) = self.backend.prepare_read() {
-                dispatched += blocking_read(guard)?;
+                dispatched += blocking_read(guard, None, None)?;
             } else {
                 dispatched += self.backend.dispatch_inner_queue()?;
             }
@@ -209,6 +274,38 @@ impl Connection {
         self.backend.info(id)
     }
 
+    /// Start counting the requests sent and the events received on this connection, by interface
+    pub fn enable_metrics(&self) {
+        self.backend.enable_metrics()
+    }
+
+    /// Get the counters of the messages exchanged on this connection
+    ///
+    /// The counters are empty unless [`enable_metrics()`](Connection::enable_metrics) was called.
+    pub fn metrics(&self) -> Metrics {
+        self.backend.metrics()
+    }
+
+    /// Get the counters of the bytes, messages and file descriptors exchanged on this connection
+    pub fn io_stats(&self) -> IoStats {
+        self.backend.io_stats()
+    }
+
+    /// Set a hook receiving every request sent and every event received on this connection
+    ///
+    /// This allows an app to capture its own protocol traffic, see
+    /// [`Backend::set_protocol_logger()`] for details. Passing `None` removes the logger.
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.backend.set_protocol_logger(logger)
+    }
+
+    /// Set a hook notified of the events discarded because their object was already destroyed
+    ///
+    /// See [`Backend::set_zombie_hook()`]. Passing `None` removes the hook.
+    pub fn set_zombie_hook(&self, hook: Option<Arc<dyn ZombieHook>>) {
+        self.backend.set_zombie_hook(hook)
+    }
+
     /// Get the object data for a given object ID
     ///
     /// This is a low-level interface used by the code generated by `wayland-scanner`, a higher-level
@@ -219,20 +316,54 @@ impl Connection {
     }
 }
 
-pub(crate) fn blocking_read(guard: ReadEventsGuard) -> Result<usize, WaylandError> {
-    let mut fds = [nix::poll::PollFd::new(
-        guard.connection_fd().as_raw_fd(),
-        nix::poll::PollFlags::POLLIN | nix::poll::PollFlags::POLLERR,
-    )];
+/// Block until events can be read, the wakeup is triggered, or the deadline is reached
+pub(crate) fn blocking_read(
+    guard: ReadEventsGuard,
+    wakeup: Option<&Wakeup>,
+    deadline: Option<Instant>,
+) -> Result<usize, WaylandError> {
+    let mut fds = [
+        nix::poll::PollFd::new(
+            guard.connection_fd().as_raw_fd(),
+            nix::poll::PollFlags::POLLIN | nix::poll::PollFlags::POLLERR,
+        ),
+        // poll ignores negative file descriptors
+        nix::poll::PollFd::new(
+            wakeup.map_or(-1, |wakeup| wakeup.fd().as_raw_fd()),
+            nix::poll::PollFlags::POLLIN,
+        ),
+    ];
 
     loop {
-        match nix::poll::poll(&mut fds, -1) {
+        let timeout = match deadline {
+            // round up, not to wake up before the deadline
+            Some(deadline) => {
+                let nanos = deadline.saturating_duration_since(Instant::now()).as_nanos();
+                ((nanos + 999_999) / 1_000_000).min(i32::MAX as u128) as i32
+            }
+            None => -1,
+        };
+        match nix::poll::poll(&mut fds, timeout) {
+            Ok(0) if deadline.is_some() => {
+                return Err(WaylandError::Io(ErrorKind::TimedOut.into()));
+            }
             Ok(_) => break,
             Err(nix::errno::Errno::EINTR) => continue,
             Err(e) => return Err(WaylandError::Io(e.into())),
         }
     }
 
+    if let Some(wakeup) = wakeup {
+        let ready = |fd: &nix::poll::PollFd| fd.revents().map_or(false, |r| !r.is_empty());
+        if ready(&fds[1]) {
+            wakeup.drain();
+            if !ready(&fds[0]) {
+                // dropping the guard cancels the read
+                return Ok(0);
+            }
+        }
+    }
+
     // at this point the fd is ready
     match guard.read() {
         Ok(n) => Ok(n),
@@ -251,7 +382,8 @@ pub enum ConnectError {
     /// Could not find wayland compositor
     NoCompositor,
 
-    /// `WAYLAND_SOCKET` was set but contained garbage
+    /// `WAYLAND_SOCKET` was set but contained garbage, or the file descriptor given to
+    /// [`Connection::from_fd()`] is not a stream socket
     InvalidFd,
 }
 
@@ -267,7 +399,7 @@ impl fmt::Display for ConnectError {
                 write!(f, "Could not find wayland compositor")
             }
             ConnectError::InvalidFd => {
-                write!(f, "WAYLAND_SOCKET was set but contained garbage")
+                write!(f, "WAYLAND_SOCKET or the provided file descriptor is not a valid socket")
             }
         }
     }
@@ -0,0 +1,280 @@
+//! Helpers for clients connected to several Wayland servers at once
+//!
+//! An app may need to talk to several compositors simultaneously, for example to display its
+//! windows on the host compositor while running a nested compositor for an authentication
+//! dialog. A [`ConnectionSet`] groups the event queues of all these connections, identified by keys
+//! of your choosing, so that a single event loop can wait on all of them:
+//!
+//! ```no_run
+//! # use wayland_client::{Connection, connections::ConnectionSet};
+//! # use std::os::unix::net::UnixStream;
+//! # fn connect(path: &str) -> Connection {
+//! #     Connection::from_socket(UnixStream::connect(path).unwrap()).unwrap()
+//! # }
+//! #[derive(Debug, Clone, PartialEq, Eq)]
+//! enum Server {
+//!     Host,
+//!     Nested,
+//! }
+//!
+//! struct State;
+//!
+//! let mut connections = ConnectionSet::<Server, State>::new();
+//! connections.insert(Server::Host, Connection::connect_to_env().unwrap().new_event_queue());
+//! connections.insert(Server::Nested, connect("/run/user/1000/nested-0").new_event_queue());
+//!
+//! let mut state = State;
+//! loop {
+//!     if let Err(err) = connections.blocking_dispatch(&mut state) {
+//!         eprintln!("Lost the connection to {:?}: {}", err.key, err.error);
+//!         connections.remove(&err.key);
+//!     }
+//! #   break;
+//! }
+//! ```
+//!
+//! The events of each connection are dispatched to the same `State`, using the
+//! [`Dispatch`](crate::Dispatch) implementations of its event queue. When handling them,
+//! [`key_of()`](ConnectionSet::key_of) and [`handle_of()`](ConnectionSet::handle_of) tell to
+//! which connection an object belongs, so that new objects are created on the right one.
+
+use std::{
+    fmt,
+    io::ErrorKind,
+    os::unix::io::{AsRawFd, BorrowedFd},
+};
+
+use wayland_backend::client::WaylandError;
+
+use crate::{Connection, DispatchError, EventQueue, Proxy, QueueHandle};
+
+/// A set of event queues on different connections
+///
+/// Each connection registered in the set is identified by a key of type `K`, and has its own
+/// [`EventQueue`] dispatching events to a `State` shared by all connections.
+///
+/// If you integrate the connections in your own event loop, [`fds()`](ConnectionSet::fds) gives the
+/// file descriptors to monitor, and the usual [`Connection::prepare_read()`] and
+/// [`EventQueue::dispatch_pending()`] methods are available through
+/// [`connection()`](ConnectionSet::connection) and [`queue_mut()`](ConnectionSet::queue_mut).
+pub struct ConnectionSet<K, State> {
+    entries: Vec<(K, EventQueue<State>)>,
+}
+
+impl<K: PartialEq + Clone, State> ConnectionSet<K, State> {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    /// Register the event queue of a connection with the given key
+    ///
+    /// The queue replaces the one previously registered with this key, which is returned.
+    pub fn insert(&mut self, key: K, queue: EventQueue<State>) -> Option<EventQueue<State>> {
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, old)) => Some(std::mem::replace(old, queue)),
+            None => {
+                self.entries.push((key, queue));
+                None
+            }
+        }
+    }
+
+    /// Remove the connection registered with the given key, returning its event queue
+    ///
+    /// The connection is closed once the queue and all other handles to it are dropped.
+    pub fn remove(&mut self, key: &K) -> Option<EventQueue<State>> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// The number of registered connections
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no connection is registered
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the keys of the registered connections, in insertion order
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    /// The connection registered with the given key
+    pub fn connection(&self, key: &K) -> Option<&Connection> {
+        self.queue(key).map(|queue| &queue.conn)
+    }
+
+    /// The event queue registered with the given key
+    pub fn queue(&self, key: &K) -> Option<&EventQueue<State>> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, queue)| queue)
+    }
+
+    /// The event queue registered with the given key, mutably
+    pub fn queue_mut(&mut self, key: &K) -> Option<&mut EventQueue<State>> {
+        self.entries.iter_mut().find(|(k, _)| k == key).map(|(_, queue)| queue)
+    }
+
+    /// A [`QueueHandle`] to the event queue registered with the given key
+    pub fn handle(&self, key: &K) -> Option<QueueHandle<State>> {
+        self.queue(key).map(EventQueue::handle)
+    }
+
+    /// The key of the connection an object belongs to
+    ///
+    /// Returns `None` if the connection of this object is not in the set, or if the object is dead.
+    pub fn key_of<I: Proxy>(&self, proxy: &I) -> Option<&K> {
+        let backend = proxy.backend().upgrade()?;
+        self.entries.iter().find(|(_, queue)| queue.conn.backend == backend).map(|(k, _)| k)
+    }
+
+    /// A [`QueueHandle`] to the event queue of the connection an object belongs to
+    ///
+    /// This is the handle to use to create the child objects of this object.
+    pub fn handle_of<I: Proxy>(&self, proxy: &I) -> Option<QueueHandle<State>> {
+        let key = self.key_of(proxy)?;
+        self.handle(key)
+    }
+
+    /// The file descriptors of all the connections, to be monitored for readability
+    pub fn fds(&self) -> impl Iterator<Item = (&K, BorrowedFd<'_>)> {
+        self.entries.iter().map(|(k, queue)| (k, queue.conn.backend.poll_fd()))
+    }
+
+    /// Flush the pending requests of all the connections
+    ///
+    /// This stops at the first connection returning an error.
+    pub fn flush(&self) -> Result<(), ConnectionSetError<K>> {
+        for (key, queue) in &self.entries {
+            queue.flush().map_err(|error| ConnectionSetError::new(key, error.into()))?;
+        }
+        Ok(())
+    }
+
+    /// Dispatch the pending events of all the connections
+    ///
+    /// See [`EventQueue::dispatch_pending()`]. This stops at the first connection returning an
+    /// error, the events of the connections after it remain pending.
+    pub fn dispatch_pending(&mut self, data: &mut State) -> Result<usize, ConnectionSetError<K>> {
+        let mut dispatched = 0;
+        for (key, queue) in &mut self.entries {
+            dispatched += queue
+                .dispatch_pending(data)
+                .map_err(|error| ConnectionSetError::new(key, error))?;
+        }
+        Ok(dispatched)
+    }
+
+    /// Block waiting for events on any of the connections and dispatch them
+    ///
+    /// This is the equivalent of [`EventQueue::blocking_dispatch()`] for all the connections at once:
+    /// if no event is pending, it flushes all the connections, waits until at least one of them
+    /// receives events, and dispatches them.
+    ///
+    /// An error is returned along with the key of the connection it comes from, which most likely is
+    /// no longer usable and needs to be removed from the set.
+    pub fn blocking_dispatch(&mut self, data: &mut State) -> Result<usize, ConnectionSetError<K>> {
+        let dispatched = self.dispatch_pending(data)?;
+        if dispatched > 0 {
+            return Ok(dispatched);
+        }
+
+        for (key, queue) in &self.entries {
+            queue
+                .conn
+                .implicit_flush()
+                .map_err(|error| ConnectionSetError::new(key, error.into()))?;
+        }
+
+        let mut guards = Vec::with_capacity(self.entries.len());
+        for (_, queue) in &self.entries {
+            match queue.prepare_read() {
+                Some(guard) => guards.push(guard),
+                // events were enqueued in the meantime, dropping the guards cancels the reads
+                None => {
+                    drop(guards);
+                    return self.dispatch_pending(data);
+                }
+            }
+        }
+        if guards.is_empty() {
+            return Ok(0);
+        }
+
+        let mut fds = guards
+            .iter()
+            .map(|guard| {
+                nix::poll::PollFd::new(
+                    guard.connection_fd().as_raw_fd(),
+                    nix::poll::PollFlags::POLLIN | nix::poll::PollFlags::POLLERR,
+                )
+            })
+            .collect::<Vec<_>>();
+        loop {
+            match nix::poll::poll(&mut fds, -1) {
+                Ok(_) => break,
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(e) => {
+                    // a failing poll is not specific to a connection, report it on the first one
+                    let error = WaylandError::Io(e.into());
+                    return Err(ConnectionSetError::new(&self.entries[0].0, error.into()));
+                }
+            }
+        }
+
+        for ((guard, fd), (key, _)) in guards.into_iter().zip(&fds).zip(&self.entries) {
+            if fd.revents().map_or(true, |r| r.is_empty()) {
+                continue;
+            }
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(error) => return Err(ConnectionSetError::new(key, error.into())),
+            }
+        }
+
+        self.dispatch_pending(data)
+    }
+}
+
+impl<K: PartialEq + Clone, State> Default for ConnectionSet<K, State> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: fmt::Debug, State> fmt::Debug for ConnectionSet<K, State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.entries.iter().map(|(k, queue)| (k, queue))).finish()
+    }
+}
+
+/// An error of one of the connections of a [`ConnectionSet`]
+#[derive(Debug)]
+pub struct ConnectionSetError<K> {
+    /// The key of the connection
+    pub key: K,
+    /// The error of this connection
+    pub error: DispatchError,
+}
+
+impl<K: Clone> ConnectionSetError<K> {
+    fn new(key: &K, error: DispatchError) -> Self {
+        Self { key: key.clone(), error }
+    }
+}
+
+impl<K: fmt::Debug> std::error::Error for ConnectionSetError<K> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<K: fmt::Debug> fmt::Display for ConnectionSetError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Connection {:?}: {}", self.key, self.error)
+    }
+}
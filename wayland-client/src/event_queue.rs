@@ -1,10 +1,12 @@
 use std::any::Any;
 use std::collections::VecDeque;
 use std::convert::Infallible;
+use std::future::Future;
 use std::marker::PhantomData;
-use std::os::unix::io::{AsFd, BorrowedFd, OwnedFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
 use std::sync::{atomic::Ordering, Arc, Condvar, Mutex};
 use std::task;
+use std::time::{Duration, Instant};
 
 use nix::Error;
 use wayland_backend::{
@@ -314,19 +316,43 @@ impl<State> std::fmt::Debug for QueueEvent<State> {
 /// ```
 pub struct EventQueue<State> {
     handle: QueueHandle<State>,
-    conn: Connection,
+    pub(crate) conn: Connection,
+}
+
+/// The priority of the events of the objects within their [`EventQueue`]
+///
+/// The pending events of higher priority are dispatched first, for example to handle input
+/// before the bulk of the other events during a busy frame. The events of a same priority are
+/// dispatched in the order they were received, but the order of events of different priorities is
+/// not preserved: an object should only be given a different priority from the objects whose events
+/// it relates to when this doesn't matter.
+///
+/// The priority of an object is the one of the [`QueueHandle`] it was created with, see
+/// [`QueueHandle::with_priority()`]. The objects created by its events inherit it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueuePriority {
+    /// Dispatched before the other events
+    High,
+    /// The default priority
+    #[default]
+    Normal,
+    /// Dispatched after the other events
+    Low,
 }
 
 #[derive(Debug)]
 pub(crate) struct EventQueueInner<State> {
-    queue: VecDeque<QueueEvent<State>>,
+    // the pending events of each priority, from the highest
+    queues: [VecDeque<QueueEvent<State>>; 3],
     freeze_count: usize,
     waker: Option<task::Waker>,
+    wakeup: Option<Arc<Wakeup>>,
 }
 
 impl<State> EventQueueInner<State> {
     pub(crate) fn enqueue_event<I, U>(
         &mut self,
+        priority: QueuePriority,
         msg: Message<ObjectId, OwnedFd>,
         odata: Arc<dyn ObjectData>,
     ) where
@@ -335,13 +361,21 @@ impl<State> EventQueueInner<State> {
         I: Proxy + 'static,
     {
         let func = queue_callback::<I, U, State>;
-        self.queue.push_back(QueueEvent(func, msg, odata));
+        self.queues[priority as usize].push_back(QueueEvent(func, msg, odata));
         if self.freeze_count == 0 {
             if let Some(waker) = self.waker.take() {
                 waker.wake();
             }
         }
     }
+
+    fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    fn pop_event(&mut self) -> Option<QueueEvent<State>> {
+        self.queues.iter_mut().find_map(VecDeque::pop_front)
+    }
 }
 
 impl<State> std::fmt::Debug for EventQueue<State> {
@@ -361,11 +395,12 @@ impl<State> AsFd for EventQueue<State> {
 impl<State> EventQueue<State> {
     pub(crate) fn new(conn: Connection) -> Self {
         let inner = Arc::new(Mutex::new(EventQueueInner {
-            queue: VecDeque::new(),
+            queues: Default::default(),
             freeze_count: 0,
             waker: None,
+            wakeup: None,
         }));
-        Self { handle: QueueHandle { inner }, conn }
+        Self { handle: QueueHandle { inner, priority: QueuePriority::Normal }, conn }
     }
 
     /// Get a [`QueueHandle`] for this event queue
@@ -392,16 +427,28 @@ impl<State> EventQueue<State> {
     /// event.
     ///
     /// A simple app event loop can consist of invoking this method in a loop.
+    ///
+    /// The wait for the server is interrupted by the [`QueueWaker`]s of this event queue, in which case
+    /// this method may return without having dispatched any event.
     pub fn blocking_dispatch(&mut self, data: &mut State) -> Result<usize, DispatchError> {
+        self.blocking_dispatch_until(data, None)
+    }
+
+    fn blocking_dispatch_until(
+        &mut self,
+        data: &mut State,
+        deadline: Option<Instant>,
+    ) -> Result<usize, DispatchError> {
         let dispatched = self.dispatch_pending(data)?;
         if dispatched > 0 {
             return Ok(dispatched);
         }
 
-        self.conn.flush()?;
+        self.conn.implicit_flush()?;
 
+        let wakeup = self.handle.inner.lock().unwrap().wakeup.clone();
         if let Some(guard) = self.conn.prepare_read() {
-            crate::conn::blocking_read(guard)?;
+            crate::conn::blocking_read(guard, wakeup.as_deref(), deadline)?;
         }
 
         self.dispatch_pending(data)
@@ -415,6 +462,28 @@ impl<State> EventQueue<State> {
     /// This function may be useful during initial setup of your app. This function may also be useful
     /// where you need to guarantee all requests prior to calling this function are completed.
     pub fn roundtrip(&mut self, data: &mut State) -> Result<usize, DispatchError> {
+        self.roundtrip_until(data, None)
+    }
+
+    /// Synchronous roundtrip, giving up after `timeout`
+    ///
+    /// This is the same as [`roundtrip()`](EventQueue::roundtrip), but fails with an I/O error of
+    /// kind [`TimedOut`](std::io::ErrorKind::TimedOut) if the server did not answer in time, so that
+    /// a hung server does not block the client forever. The connection remains usable: the events
+    /// the server sends later are dispatched as usual.
+    pub fn roundtrip_timeout(
+        &mut self,
+        data: &mut State,
+        timeout: Duration,
+    ) -> Result<usize, DispatchError> {
+        self.roundtrip_until(data, Some(Instant::now() + timeout))
+    }
+
+    fn roundtrip_until(
+        &mut self,
+        data: &mut State,
+        deadline: Option<Instant>,
+    ) -> Result<usize, DispatchError> {
         let done = Arc::new(SyncData::default());
 
         let display = self.conn.display();
@@ -425,14 +494,35 @@ impl<State> EventQueue<State> {
                 Some(done.clone()),
             )
             .map_err(|_| WaylandError::Io(Error::EPIPE.into()))?;
+        // whatever the flush policy, the server needs the request to answer it
+        self.conn.flush()?;
 
         let mut dispatched = 0;
 
-        while !done.done.load(Ordering::Relaxed) {
-            dispatched += self.blocking_dispatch(data)?;
+        loop {
+            dispatched += self.blocking_dispatch_until(data, deadline)?;
+            if done.done.load(Ordering::Relaxed) {
+                return Ok(dispatched);
+            }
+            // the wait is skipped as long as there are pending events, so a server streaming them would
+            // otherwise never let the deadline expire
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Err(WaylandError::Io(std::io::ErrorKind::TimedOut.into()).into());
+            }
         }
+    }
 
-        Ok(dispatched)
+    /// Get a [`QueueWaker`] to interrupt [`blocking_dispatch()`](EventQueue::blocking_dispatch) from
+    /// other threads
+    ///
+    /// All the wakers of an event queue share the same file descriptor, created by the first call.
+    pub fn waker(&self) -> std::io::Result<QueueWaker> {
+        let mut inner = self.handle.inner.lock().unwrap();
+        let wakeup = match inner.wakeup {
+            Some(ref wakeup) => wakeup.clone(),
+            None => inner.wakeup.insert(Arc::new(Wakeup::new()?)).clone(),
+        };
+        Ok(QueueWaker { wakeup })
     }
 
     /// Start a synchronized read from the socket
@@ -485,14 +575,14 @@ impl<State> EventQueue<State> {
 
     fn try_next(inner: &Mutex<EventQueueInner<State>>) -> Option<QueueEvent<State>> {
         let mut lock = inner.lock().unwrap();
-        if lock.freeze_count != 0 && !lock.queue.is_empty() {
+        if lock.freeze_count != 0 && !lock.is_empty() {
             let waker = Arc::new(DispatchWaker { cond: Condvar::new() });
             while lock.freeze_count != 0 {
                 lock.waker = Some(waker.clone().into());
                 lock = waker.cond.wait(lock).unwrap();
             }
         }
-        lock.queue.pop_front()
+        lock.pop_event()
     }
 
     /// Attempt to dispatch events from this queue, registering the current task for wakeup if no
@@ -554,7 +644,7 @@ impl<State> EventQueue<State> {
                 lock.waker = Some(cx.waker().clone());
                 return task::Poll::Pending;
             }
-            let QueueEvent(cb, msg, odata) = if let Some(elt) = lock.queue.pop_front() {
+            let QueueEvent(cb, msg, odata) = if let Some(elt) = lock.pop_event() {
                 elt
             } else {
                 lock.waker = Some(cx.waker().clone());
@@ -564,6 +654,71 @@ impl<State> EventQueue<State> {
             cb(&self.conn, msg, data, odata, &self.handle)?
         }
     }
+
+    /// Read events from the socket once it is readable, without blocking the thread
+    ///
+    /// This is the async equivalent of reading the socket with
+    /// [`prepare_read()`](EventQueue::prepare_read). It first flushes the connection, then awaits
+    /// the futures returned by `readable` until a read succeeds. These futures must resolve once the
+    /// socket given by [`AsFd`] is readable, and are provided by the runtime driving the task, for
+    /// example with tokio:
+    ///
+    /// ```ignore
+    /// let fd = tokio::io::unix::AsyncFd::new(queue.as_fd().as_raw_fd())?;
+    /// queue
+    ///     .read_events_async(|| async {
+    ///         fd.readable().await?.clear_ready();
+    ///         Ok(())
+    ///     })
+    ///     .await?;
+    /// ```
+    ///
+    /// The read is only prepared once the socket is readable, and no read guard is held across an
+    /// await point, so that several tasks of a single-threaded runtime can wait on the same
+    /// connection. This returns `Ok(0)` if another task or thread read the socket first.
+    pub async fn read_events_async<R, Fut>(&self, mut readable: R) -> Result<usize, WaylandError>
+    where
+        R: FnMut() -> Fut,
+        Fut: Future<Output = std::io::Result<()>>,
+    {
+        self.conn.implicit_flush()?;
+        loop {
+            readable().await?;
+            let guard = match self.conn.prepare_read() {
+                Some(guard) => guard,
+                // events are already waiting in an event queue
+                None => return Ok(0),
+            };
+            match guard.read() {
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                ret => return ret,
+            }
+        }
+    }
+
+    /// Wait for events without blocking the thread and dispatch them
+    ///
+    /// This is the async equivalent of [`blocking_dispatch()`](EventQueue::blocking_dispatch): if no
+    /// event is pending, it reads the socket with
+    /// [`read_events_async()`](EventQueue::read_events_async), and then dispatches the pending events.
+    pub async fn dispatch_async<R, Fut>(
+        &mut self,
+        data: &mut State,
+        readable: R,
+    ) -> Result<usize, DispatchError>
+    where
+        R: FnMut() -> Fut,
+        Fut: Future<Output = std::io::Result<()>>,
+    {
+        let dispatched = self.dispatch_pending(data)?;
+        if dispatched > 0 {
+            return Ok(dispatched);
+        }
+
+        self.read_events_async(readable).await?;
+
+        self.dispatch_pending(data)
+    }
 }
 
 struct DispatchWaker {
@@ -579,6 +734,69 @@ impl task::Wake for DispatchWaker {
 /// A handle representing an [`EventQueue`], used to assign objects upon creation.
 pub struct QueueHandle<State> {
     pub(crate) inner: Arc<Mutex<EventQueueInner<State>>>,
+    pub(crate) priority: QueuePriority,
+}
+
+/// A handle to interrupt the blocking dispatch of an [`EventQueue`] from other threads
+///
+/// Obtained with [`EventQueue::waker()`]. Calling [`wake()`](QueueWaker::wake) makes the
+/// [`blocking_dispatch()`](EventQueue::blocking_dispatch) currently waiting for the server return,
+/// cancelling its read of the socket, or the next one return immediately if the queue is not waiting.
+/// This allows a thread receiving application events to have the dispatching thread send requests
+/// promptly. Several wakeups before the dispatch are coalesced into a single one.
+///
+/// [`EventQueue::roundtrip()`] is not interrupted, and consumes the wakeups happening during it.
+#[derive(Debug, Clone)]
+pub struct QueueWaker {
+    wakeup: Arc<Wakeup>,
+}
+
+impl QueueWaker {
+    /// Wake up the thread dispatching the event queue
+    pub fn wake(&self) {
+        // a full counter or pipe means that a wakeup is already pending
+        let _ = nix::unistd::write(self.wakeup.write.as_raw_fd(), &1u64.to_ne_bytes());
+    }
+}
+
+// The file descriptors behind the wakers of an event queue, an eventfd or a pipe
+#[derive(Debug)]
+pub(crate) struct Wakeup {
+    read: OwnedFd,
+    write: OwnedFd,
+}
+
+impl Wakeup {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn new() -> std::io::Result<Self> {
+        use nix::sys::eventfd::{eventfd, EfdFlags};
+        let read = unsafe {
+            OwnedFd::from_raw_fd(eventfd(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?)
+        };
+        let write = read.try_clone()?;
+        Ok(Self { read, write })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn new() -> std::io::Result<Self> {
+        use nix::fcntl::OFlag;
+        let (read, write) = nix::unistd::pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?;
+        unsafe { Ok(Self { read: OwnedFd::from_raw_fd(read), write: OwnedFd::from_raw_fd(write) }) }
+    }
+
+    pub(crate) fn fd(&self) -> BorrowedFd<'_> {
+        self.read.as_fd()
+    }
+
+    pub(crate) fn drain(&self) {
+        let mut buffer = [0u8; 64];
+        // an eventfd is reset by a single read, a pipe needs to be emptied
+        while let Ok(n) = nix::unistd::read(self.read.as_raw_fd(), &mut buffer) {
+            if n < buffer.len() {
+                break;
+            }
+        }
+    }
 }
 
 /// A handle that temporarily pauses event processing on an [`EventQueue`].
@@ -590,13 +808,16 @@ pub struct QueueFreezeGuard<'a, State> {
 impl<State> std::fmt::Debug for QueueHandle<State> {
     #[cfg_attr(coverage, coverage(off))]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("QueueHandle").field("inner", &Arc::as_ptr(&self.inner)).finish()
+        f.debug_struct("QueueHandle")
+            .field("inner", &Arc::as_ptr(&self.inner))
+            .field("priority", &self.priority)
+            .finish()
     }
 }
 
 impl<State> Clone for QueueHandle<State> {
     fn clone(&self) -> Self {
-        Self { inner: self.inner.clone() }
+        Self { inner: self.inner.clone(), priority: self.priority }
     }
 }
 
@@ -620,6 +841,14 @@ impl<State: 'static> QueueHandle<State> {
         })
     }
 
+    /// Get a handle to this queue whose objects have the given priority
+    ///
+    /// The events of the objects created with it are dispatched according to their
+    /// [`QueuePriority`], for example ahead of the others for the input devices.
+    pub fn with_priority(&self, priority: QueuePriority) -> QueueHandle<State> {
+        Self { inner: self.inner.clone(), priority }
+    }
+
     /// Temporarily block processing on this queue.
     ///
     /// This will cause the associated queue to block (or return `NotReady` to poll) until all
@@ -634,7 +863,7 @@ impl<'a, State> Drop for QueueFreezeGuard<'a, State> {
     fn drop(&mut self) {
         let mut lock = self.qh.inner.lock().unwrap();
         lock.freeze_count -= 1;
-        if lock.freeze_count == 0 && !lock.queue.is_empty() {
+        if lock.freeze_count == 0 && !lock.is_empty() {
             if let Some(waker) = lock.waker.take() {
                 waker.wake();
             }
@@ -653,6 +882,12 @@ fn queue_callback<
     odata: Arc<dyn ObjectData>,
     qhandle: &QueueHandle<State>,
 ) -> Result<(), DispatchError> {
+    crate::trace_span!(
+        "dispatch",
+        interface = I::interface().name,
+        id = msg.sender_id.protocol_id(),
+        opcode = msg.opcode
+    );
     let (proxy, event) = I::parse_event(handle, msg)?;
     let udata = odata.data_as_any().downcast_ref().expect("Wrong user_data value for object");
     <State as Dispatch<I, U, State>>::event(data, &proxy, event, udata, handle, qhandle);
@@ -682,7 +917,8 @@ where
             .any(|arg| matches!(arg, Argument::NewId(id) if !id.is_null()))
             .then(|| State::event_created_child(msg.opcode, &self.handle));
 
-        self.handle.inner.lock().unwrap().enqueue_event::<I, U>(msg, self.clone());
+        let priority = self.handle.priority;
+        self.handle.inner.lock().unwrap().enqueue_event::<I, U>(priority, msg, self.clone());
 
         new_data
     }
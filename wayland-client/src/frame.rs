@@ -0,0 +1,296 @@
+//! Scheduling the redraws of surfaces on their frame callbacks
+//!
+//! A `wl_surface.frame` request asks the compositor for a `wl_callback` which is done when it is a good
+//! time to draw a new frame of the surface. Handling these callbacks by hand is error-prone: a client
+//! requesting a callback each time something changes accumulates callbacks, and one forgetting to request
+//! one before committing stops redrawing.
+//!
+//! A [`FrameScheduler`] owns the frame callbacks of the surfaces of a client, and processes their events
+//! as soon as they are read from the connection, independently of any event queue:
+//!
+//! - [`FrameScheduler::request_frame()`] returns a future completed with the timestamp of the next
+//!   frame of the surface. All the requests made before it share the same callback.
+//! - [`FrameScheduler::request_redraw()`] marks a surface as needing to be drawn, and the surfaces
+//!   returned by [`FrameScheduler::take_redraws()`] or [`FrameScheduler::redraws()`] are those to draw
+//!   now. A surface is drawn at most once per frame, however many redraws were requested.
+//!
+//! As for any `wl_surface.frame` request, the callbacks only take effect with the next commit of the
+//! surface.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! # async fn render_loop(
+//! #     conn: wayland_client::Connection,
+//! #     scheduler: wayland_client::frame::FrameScheduler,
+//! #     draw: impl Fn(&wayland_client::protocol::wl_surface::WlSurface),
+//! # ) {
+//! loop {
+//!     for surface in scheduler.redraws().await {
+//!         draw(&surface);
+//!         surface.commit();
+//!     }
+//!     conn.flush().unwrap();
+//! }
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    os::unix::io::OwnedFd,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use wayland_backend::{
+    client::{Backend, InvalidId, ObjectData, ObjectId},
+    protocol::{Argument, Message},
+};
+
+use crate::{
+    protocol::{wl_callback, wl_surface},
+    Proxy,
+};
+
+/// The frame callbacks and redraws of a set of surfaces
+///
+/// The scheduler can be cloned, the clones sharing the same state.
+#[derive(Debug, Clone, Default)]
+pub struct FrameScheduler {
+    inner: Arc<Mutex<SchedulerInner>>,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerInner {
+    surfaces: HashMap<ObjectId, SurfaceState>,
+    // the task waiting on `redraws()`
+    waker: Option<Waker>,
+}
+
+#[derive(Debug)]
+struct SurfaceState {
+    surface: wl_surface::WlSurface,
+    // the frame callback requested and not done yet
+    pending: Option<Arc<FrameSlot>>,
+    dirty: bool,
+}
+
+impl SchedulerInner {
+    fn has_redraws(&self) -> bool {
+        self.surfaces.values().any(|state| state.dirty && state.pending.is_none())
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl FrameScheduler {
+    /// Create a scheduler without any surface
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a future completed with the timestamp of the next frame of the surface
+    ///
+    /// The timestamp is the one of the `wl_callback.done` event, in milliseconds. A frame callback is
+    /// only requested if the surface has none pending, so the surface must be committed for the future
+    /// to complete.
+    pub fn request_frame(&self, surface: &wl_surface::WlSurface) -> Result<FrameFuture, InvalidId> {
+        let mut inner = self.inner.lock().unwrap();
+        let state = inner.surfaces.entry(surface.id()).or_insert_with(|| SurfaceState {
+            surface: surface.clone(),
+            pending: None,
+            dirty: false,
+        });
+        let slot = match state.pending {
+            Some(ref slot) => slot.clone(),
+            None => {
+                let slot = self.send_frame(surface)?;
+                state.pending = Some(slot.clone());
+                slot
+            }
+        };
+        Ok(FrameFuture { slot })
+    }
+
+    /// Mark the surface as needing to be drawn
+    ///
+    /// The surface is returned by the next call to [`take_redraws()`](Self::take_redraws) once its
+    /// pending frame callback, if any, is done.
+    pub fn request_redraw(&self, surface: &wl_surface::WlSurface) {
+        let mut inner = self.inner.lock().unwrap();
+        let state = inner.surfaces.entry(surface.id()).or_insert_with(|| SurfaceState {
+            surface: surface.clone(),
+            pending: None,
+            dirty: false,
+        });
+        state.dirty = true;
+        if state.pending.is_none() {
+            inner.wake();
+        }
+    }
+
+    /// Take the surfaces to draw now
+    ///
+    /// These are the surfaces whose redraw was requested and which have no pending frame callback. A
+    /// frame callback is requested for each of them, so that their next redraw waits for the frame
+    /// following the one drawn now: each surface must be committed after being drawn.
+    pub fn take_redraws(&self) -> Vec<wl_surface::WlSurface> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut redraws = Vec::new();
+        for state in inner.surfaces.values_mut() {
+            if !state.dirty || state.pending.is_some() {
+                continue;
+            }
+            state.dirty = false;
+            // a dead surface is not drawn anymore
+            if let Ok(slot) = self.send_frame(&state.surface) {
+                state.pending = Some(slot);
+                redraws.push(state.surface.clone());
+            }
+        }
+        redraws
+    }
+
+    /// Get a future completed with the surfaces to draw, once there are some
+    ///
+    /// This is the asynchronous version of [`take_redraws()`](Self::take_redraws). Only one task should
+    /// be waiting on this future at a time.
+    pub fn redraws(&self) -> Redraws {
+        Redraws { scheduler: self.clone() }
+    }
+
+    /// Stop tracking a surface, before destroying it
+    ///
+    /// The futures of its pending frame callback can only complete if the compositor still sends its
+    /// `done` event.
+    pub fn forget(&self, surface: &wl_surface::WlSurface) {
+        self.inner.lock().unwrap().surfaces.remove(&surface.id());
+    }
+
+    fn send_frame(&self, surface: &wl_surface::WlSurface) -> Result<Arc<FrameSlot>, InvalidId> {
+        let slot = Arc::new(FrameSlot::default());
+        let data = Arc::new(CallbackData {
+            slot: slot.clone(),
+            scheduler: Arc::downgrade(&self.inner),
+            surface: surface.id(),
+        });
+        surface.send_constructor::<wl_callback::WlCallback>(wl_surface::Request::Frame {}, data)?;
+        Ok(slot)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FrameSlot {
+    state: Mutex<SlotState>,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    time: Option<u32>,
+    wakers: Vec<Waker>,
+}
+
+/// A future completed with the timestamp of the next frame of a surface
+///
+/// See [`FrameScheduler::request_frame()`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct FrameFuture {
+    slot: Arc<FrameSlot>,
+}
+
+impl Future for FrameFuture {
+    type Output = u32;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+        let mut state = self.slot.state.lock().unwrap();
+        if let Some(time) = state.time {
+            return Poll::Ready(time);
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// A future completed with the surfaces to draw
+///
+/// See [`FrameScheduler::redraws()`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Redraws {
+    scheduler: FrameScheduler,
+}
+
+impl Future for Redraws {
+    type Output = Vec<wl_surface::WlSurface>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        {
+            let mut inner = self.scheduler.inner.lock().unwrap();
+            if !inner.has_redraws() {
+                inner.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(self.scheduler.take_redraws())
+    }
+}
+
+struct CallbackData {
+    slot: Arc<FrameSlot>,
+    scheduler: Weak<Mutex<SchedulerInner>>,
+    surface: ObjectId,
+}
+
+impl ObjectData for CallbackData {
+    fn event(
+        self: Arc<Self>,
+        _: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        // wl_callback.done is the only event of a wl_callback
+        let time = match msg.args.first() {
+            Some(Argument::Uint(time)) => *time,
+            _ => 0,
+        };
+        let wakers = {
+            let mut state = self.slot.state.lock().unwrap();
+            state.time = Some(time);
+            std::mem::take(&mut state.wakers)
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+
+        if let Some(scheduler) = self.scheduler.upgrade() {
+            let mut inner = scheduler.lock().unwrap();
+            let mut redraw = false;
+            if let Some(state) = inner.surfaces.get_mut(&self.surface) {
+                if state.pending.as_ref().map_or(false, |slot| Arc::ptr_eq(slot, &self.slot)) {
+                    state.pending = None;
+                    redraw = state.dirty;
+                }
+            }
+            if redraw {
+                inner.wake();
+            }
+        }
+        None
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
+
+impl std::fmt::Debug for CallbackData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackData").field("surface", &self.surface).finish_non_exhaustive()
+    }
+}
@@ -54,6 +54,7 @@
 
 use std::{
     fmt,
+    future::Future,
     ops::RangeInclusive,
     os::unix::io::OwnedFd,
     sync::{
@@ -69,7 +70,7 @@ use wayland_backend::{
 
 use crate::{
     protocol::{wl_display, wl_registry},
-    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+    Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle,
 };
 
 /// Initialize a new event queue with its associated registry and retrieve the initial list of globals
@@ -178,6 +179,226 @@ impl GlobalList {
     pub fn registry(&self) -> &wl_registry::WlRegistry {
         &self.registry
     }
+
+    /// Dispatch the event queue until a global of interface `I` is advertised
+    ///
+    /// This returns the first global of this interface whose version is at least the lower bound
+    /// of `version`, immediately if it is already in the list. It is then ready to be bound, with
+    /// [`bind()`](GlobalList::bind) or for globals with multiple instances with
+    /// [`WlRegistry::bind()`](wl_registry::WlRegistry::bind) on its name.
+    ///
+    /// The `event_queue` must be the one returned by [`registry_queue_init()`] along with this list.
+    pub fn wait_for<I, State>(
+        &self,
+        event_queue: &mut EventQueue<State>,
+        state: &mut State,
+        version: RangeInclusive<u32>,
+    ) -> Result<Global, DispatchError>
+    where
+        I: Proxy + 'static,
+    {
+        loop {
+            if let Some(global) = self.find::<I>(&version) {
+                return Ok(global);
+            }
+            event_queue.blocking_dispatch(state)?;
+        }
+    }
+
+    /// Dispatch the event queue until a global of interface `I` is advertised, without blocking
+    /// the thread
+    ///
+    /// This is the async equivalent of [`wait_for()`](GlobalList::wait_for), dispatching with
+    /// [`EventQueue::dispatch_async()`] where `readable` is documented.
+    pub async fn wait_for_async<I, State, R, Fut>(
+        &self,
+        event_queue: &mut EventQueue<State>,
+        state: &mut State,
+        version: RangeInclusive<u32>,
+        mut readable: R,
+    ) -> Result<Global, DispatchError>
+    where
+        I: Proxy + 'static,
+        R: FnMut() -> Fut,
+        Fut: Future<Output = std::io::Result<()>>,
+    {
+        loop {
+            if let Some(global) = self.find::<I>(&version) {
+                return Ok(global);
+            }
+            event_queue.dispatch_async(state, &mut readable).await?;
+        }
+    }
+
+    fn find<I: Proxy>(&self, version: &RangeInclusive<u32>) -> Option<Global> {
+        let interface = I::interface().name;
+        self.contents().with_list(|list| {
+            list.iter()
+                .find(|global| global.interface == interface && global.version >= *version.start())
+                .cloned()
+        })
+    }
+}
+
+/// A tracker of all the instances of a multi-instance global, like `wl_output` or `wl_seat`
+///
+/// It binds every instance advertised by the server, and forgets the ones which are removed. The
+/// instances advertised at startup are bound with [`init()`](GlobalTracker::init), the following
+/// ones by feeding the events of the registry to [`handle_event()`](GlobalTracker::handle_event)
+/// from your `Dispatch<WlRegistry, GlobalListContents>` implementation:
+///
+/// ```no_run
+/// use wayland_client::{
+///     globals::{registry_queue_init, GlobalListContents, GlobalTracker, TrackerEvent},
+///     protocol::{wl_output, wl_registry},
+///     Connection, Dispatch, QueueHandle,
+/// };
+///
+/// struct State {
+///     outputs: GlobalTracker<wl_output::WlOutput>,
+/// }
+///
+/// impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+///     fn event(
+///         state: &mut State,
+///         registry: &wl_registry::WlRegistry,
+///         event: wl_registry::Event,
+///         _: &GlobalListContents,
+///         _: &Connection,
+///         qh: &QueueHandle<State>,
+///     ) {
+///         match state.outputs.handle_event(registry, &event, qh, || ()) {
+///             Some(TrackerEvent::Added(output)) => { /* a new output was bound */ }
+///             Some(TrackerEvent::Removed(_, output)) => output.release(),
+///             None => {}
+///         }
+///     }
+/// }
+/// # impl Dispatch<wl_output::WlOutput, ()> for State {
+/// #     fn event(_: &mut State, _: &wl_output::WlOutput, _: wl_output::Event, _: &(),
+/// #         _: &Connection, _: &QueueHandle<State>) {}
+/// # }
+///
+/// let conn = Connection::connect_to_env().unwrap();
+/// let (globals, queue) = registry_queue_init::<State>(&conn).unwrap();
+/// let mut state = State { outputs: GlobalTracker::new(1..=4) };
+/// state.outputs.init(&globals, &queue.handle(), || ());
+/// ```
+///
+/// The proxies of the removed instances are given back in [`TrackerEvent::Removed`], for you to
+/// send their destructor request if the interface has one.
+#[derive(Debug)]
+pub struct GlobalTracker<I> {
+    version: RangeInclusive<u32>,
+    instances: Vec<(u32, I)>,
+}
+
+impl<I: Proxy + 'static> GlobalTracker<I> {
+    /// Create a tracker binding the instances in the given range of versions
+    ///
+    /// The instances advertised with a version lower than the range are ignored.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the maximum requested version is greater than the known maximum version of
+    /// the interface, as with [`GlobalList::bind()`].
+    pub fn new(version: RangeInclusive<u32>) -> Self {
+        let interface = I::interface();
+        if *version.end() > interface.version {
+            panic!("Maximum version ({}) of {} was higher than the proxy's maximum version ({}); outdated wayland XML files?",
+                version.end(), interface.name, interface.version);
+        }
+        Self { version, instances: Vec::new() }
+    }
+
+    /// Bind the instances already in the list of globals
+    ///
+    /// The user data of each new object is created by `udata`. The newly bound objects are returned.
+    pub fn init<State, U>(
+        &mut self,
+        globals: &GlobalList,
+        qh: &QueueHandle<State>,
+        mut udata: impl FnMut() -> U,
+    ) -> Vec<I>
+    where
+        State: Dispatch<I, U> + 'static,
+        U: Send + Sync + 'static,
+    {
+        globals
+            .contents()
+            .clone_list()
+            .into_iter()
+            .filter_map(|Global { name, interface, version }| {
+                self.bind(globals.registry(), name, &interface, version, qh, &mut udata)
+            })
+            .collect()
+    }
+
+    /// Process an event of the registry
+    ///
+    /// A new instance is bound, with the user data created by `udata`, and returned as
+    /// [`TrackerEvent::Added`]. A removed instance is forgotten and returned as
+    /// [`TrackerEvent::Removed`]. The events about other globals return `None`.
+    pub fn handle_event<State, U>(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        event: &wl_registry::Event,
+        qh: &QueueHandle<State>,
+        udata: impl FnOnce() -> U,
+    ) -> Option<TrackerEvent<I>>
+    where
+        State: Dispatch<I, U> + 'static,
+        U: Send + Sync + 'static,
+    {
+        match *event {
+            wl_registry::Event::Global { name, ref interface, version } => {
+                self.bind(registry, name, interface, version, qh, udata).map(TrackerEvent::Added)
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                let index = self.instances.iter().position(|&(n, _)| n == name)?;
+                let (name, proxy) = self.instances.remove(index);
+                Some(TrackerEvent::Removed(name, proxy))
+            }
+        }
+    }
+
+    /// The instances currently bound, with the names of their globals
+    pub fn instances(&self) -> &[(u32, I)] {
+        &self.instances
+    }
+
+    fn bind<State, U>(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        interface: &str,
+        version: u32,
+        qh: &QueueHandle<State>,
+        udata: impl FnOnce() -> U,
+    ) -> Option<I>
+    where
+        State: Dispatch<I, U> + 'static,
+        U: Send + Sync + 'static,
+    {
+        if interface != I::interface().name || version < *self.version.start() {
+            return None;
+        }
+        if self.instances.iter().any(|&(n, _)| n == name) {
+            return None;
+        }
+        let proxy: I = registry.bind(name, version.min(*self.version.end()), qh, udata());
+        self.instances.push((name, proxy.clone()));
+        Some(proxy)
+    }
+}
+
+/// A change of the instances of a [`GlobalTracker`]
+#[derive(Debug, Clone)]
+pub enum TrackerEvent<I> {
+    /// A new instance was bound
+    Added(I),
+    /// The global of an instance was removed, with its name
+    Removed(u32, I),
 }
 
 /// An error that may occur when initializing the global list.
@@ -338,7 +559,11 @@ where
                 .inner
                 .lock()
                 .unwrap()
-                .enqueue_event::<wl_registry::WlRegistry, GlobalListContents>(msg, self.clone())
+                .enqueue_event::<wl_registry::WlRegistry, GlobalListContents>(
+                    self.handle.priority,
+                    msg,
+                    self.clone(),
+                )
         }
 
         // We do not create any objects in this event handler.
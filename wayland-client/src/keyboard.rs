@@ -0,0 +1,496 @@
+//! Decoding of keyboard input with xkbcommon
+//!
+//! The compositor sends raw keycodes through `wl_keyboard`, along with the keymap needed to interpret
+//! them. A [`Keyboard`] compiles this keymap with `libxkbcommon`, tracks the state of the modifiers, and
+//! turns the events of a `wl_keyboard` into [`KeyboardEvent`]s carrying the keysym and text of each key.
+//! The [`Keyboard`] does not receive events by itself: feed it the events given to your
+//! `Dispatch<WlKeyboard, _>` implementation with [`Keyboard::handle_event()`].
+//!
+//! Key repetition is left to the client by the Wayland protocol. The [`Keyboard`] follows the
+//! `wl_keyboard.repeat_info` event: while a repeating key is held, [`Keyboard::repeat_deadline()`] gives
+//! the instant of the next repetition, and [`Keyboard::repeat()`] produces the repetitions which are due.
+//! Apps usually use this deadline as the timeout of their event loop.
+//!
+//! This module requires the `xkbcommon` cargo feature. Unless the `dlopen` feature of `wayland-backend`
+//! is enabled, `libxkbcommon.so` is then linked to the program.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use wayland_client::{
+//!     keyboard::{Keyboard, KeyboardEvent},
+//!     protocol::wl_keyboard,
+//!     Connection, Dispatch, QueueHandle,
+//! };
+//!
+//! struct State {
+//!     keyboard: Keyboard,
+//! }
+//!
+//! impl Dispatch<wl_keyboard::WlKeyboard, ()> for State {
+//!     fn event(
+//!         state: &mut Self,
+//!         _: &wl_keyboard::WlKeyboard,
+//!         event: wl_keyboard::Event,
+//!         _: &(),
+//!         _: &Connection,
+//!         _: &QueueHandle<Self>,
+//!     ) {
+//!         if let Some(KeyboardEvent::Press { event, .. }) = state.keyboard.handle_event(event) {
+//!             if let Some(text) = event.utf8 {
+//!                 println!("Typed {:?}", text);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    os::{
+        raw::c_char,
+        unix::io::{AsRawFd, OwnedFd},
+    },
+    ptr,
+    time::{Duration, Instant},
+};
+
+use nix::sys::mman;
+use wayland_sys::{ffi_dispatch, xkbcommon::*};
+
+use crate::{
+    protocol::{wl_keyboard, wl_surface::WlSurface},
+    WEnum,
+};
+
+/// An error creating a [`Keyboard`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardError {
+    /// `libxkbcommon.so` could not be loaded
+    NoXkbCommonLib,
+    /// The xkbcommon context could not be created
+    ContextCreation,
+}
+
+impl std::error::Error for KeyboardError {}
+
+impl fmt::Display for KeyboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyboardError::NoXkbCommonLib => write!(f, "libxkbcommon.so could not be loaded"),
+            KeyboardError::ContextCreation => {
+                write!(f, "The xkbcommon context could not be created")
+            }
+        }
+    }
+}
+
+/// The state of the usual modifiers
+///
+/// A modifier is active if it is held, latched or locked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    /// The Control key
+    pub ctrl: bool,
+    /// The Alt key
+    pub alt: bool,
+    /// The Shift key
+    pub shift: bool,
+    /// Caps Lock
+    pub caps_lock: bool,
+    /// The Super, or Windows, key
+    pub logo: bool,
+    /// Num Lock
+    pub num_lock: bool,
+}
+
+/// A key, decoded through the keymap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The timestamp of the event, in milliseconds
+    ///
+    /// The timestamp of a repetition is computed from the one of the key press.
+    pub time: u32,
+    /// The raw keycode of the key, as sent by the compositor
+    pub raw_code: u32,
+    /// The keysym of the key, as defined in `xkbcommon-keysyms.h`
+    ///
+    /// It is `0` (`XKB_KEY_NoSymbol`) if the key has no symbol or if no keymap was received yet.
+    pub keysym: u32,
+    /// The text produced by the key, if any
+    pub utf8: Option<String>,
+}
+
+/// An event of a [`Keyboard`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardEvent {
+    /// The keyboard focus entered a surface, with some keys already pressed
+    Enter {
+        /// The serial of the event
+        serial: u32,
+        /// The surface which gained the focus
+        surface: WlSurface,
+        /// The keys pressed when the surface gained the focus
+        keys: Vec<KeyEvent>,
+    },
+    /// The keyboard focus left a surface
+    Leave {
+        /// The serial of the event
+        serial: u32,
+        /// The surface which lost the focus
+        surface: WlSurface,
+    },
+    /// A key was pressed
+    Press {
+        /// The serial of the event
+        serial: u32,
+        /// The pressed key
+        event: KeyEvent,
+    },
+    /// A key was released
+    Release {
+        /// The serial of the event
+        serial: u32,
+        /// The released key
+        event: KeyEvent,
+    },
+    /// A held key is repeated, produced by [`Keyboard::repeat()`]
+    Repeat(KeyEvent),
+    /// The state of the modifiers changed
+    Modifiers {
+        /// The serial of the event
+        serial: u32,
+        /// The new state of the modifiers
+        modifiers: Modifiers,
+    },
+}
+
+/// The key repetition settings of the compositor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatInfo {
+    /// The number of repetitions per second, `0` disabling the repetition
+    pub rate: u32,
+    /// The delay between the key press and its first repetition
+    pub delay: Duration,
+}
+
+impl Default for RepeatInfo {
+    /// The settings used until `wl_keyboard.repeat_info` is received, which are the ones of
+    /// most compositors
+    fn default() -> RepeatInfo {
+        RepeatInfo { rate: 25, delay: Duration::from_millis(600) }
+    }
+}
+
+#[derive(Debug)]
+struct Repeat {
+    raw_code: u32,
+    time: u32,
+    pressed_at: Instant,
+    next: Instant,
+}
+
+#[derive(Debug)]
+struct Keymap {
+    keymap: *mut xkb_keymap,
+    state: *mut xkb_state,
+}
+
+impl Drop for Keymap {
+    fn drop(&mut self) {
+        unsafe {
+            ffi_dispatch!(xkbcommon_handle(), xkb_state_unref, self.state);
+            ffi_dispatch!(xkbcommon_handle(), xkb_keymap_unref, self.keymap);
+        }
+    }
+}
+
+/// The decoding state of a `wl_keyboard`
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct Keyboard {
+    context: *mut xkb_context,
+    keymap: Option<Keymap>,
+    modifiers: Modifiers,
+    repeat_info: RepeatInfo,
+    repeat: Option<Repeat>,
+}
+
+// The xkbcommon objects are owned by the `Keyboard` and only used through `&mut self` methods, and
+// xkbcommon does not tie them to the thread which created them.
+unsafe impl Send for Keyboard {}
+unsafe impl Sync for Keyboard {}
+
+impl Keyboard {
+    /// Create a new keyboard
+    ///
+    /// Keys are decoded once the `wl_keyboard.keymap` event has been given to
+    /// [`handle_event()`](Keyboard::handle_event).
+    pub fn new() -> Result<Keyboard, KeyboardError> {
+        if !is_lib_available() {
+            return Err(KeyboardError::NoXkbCommonLib);
+        }
+        let context =
+            unsafe { ffi_dispatch!(xkbcommon_handle(), xkb_context_new, XKB_CONTEXT_NO_FLAGS) };
+        if context.is_null() {
+            return Err(KeyboardError::ContextCreation);
+        }
+        Ok(Keyboard {
+            context,
+            keymap: None,
+            modifiers: Modifiers::default(),
+            repeat_info: RepeatInfo::default(),
+            repeat: None,
+        })
+    }
+
+    /// Whether a keymap has been received and compiled
+    pub fn has_keymap(&self) -> bool {
+        self.keymap.is_some()
+    }
+
+    /// The current state of the modifiers
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// The key repetition settings of the compositor
+    pub fn repeat_info(&self) -> RepeatInfo {
+        self.repeat_info
+    }
+
+    /// Process an event of the `wl_keyboard`
+    ///
+    /// The `keymap` and `repeat_info` events only update the state of the keyboard and return `None`.
+    pub fn handle_event(&mut self, event: wl_keyboard::Event) -> Option<KeyboardEvent> {
+        match event {
+            wl_keyboard::Event::Keymap { format, fd, size } => {
+                self.keymap = None;
+                if format == WEnum::Value(wl_keyboard::KeymapFormat::XkbV1) {
+                    self.keymap = self.compile_keymap(fd, size as usize);
+                }
+                None
+            }
+            wl_keyboard::Event::Enter { serial, surface, keys } => {
+                let keys = keys
+                    .chunks_exact(4)
+                    .map(|key| {
+                        self.key_event(0, u32::from_ne_bytes([key[0], key[1], key[2], key[3]]))
+                    })
+                    .collect();
+                Some(KeyboardEvent::Enter { serial, surface, keys })
+            }
+            wl_keyboard::Event::Leave { serial, surface } => {
+                self.repeat = None;
+                Some(KeyboardEvent::Leave { serial, surface })
+            }
+            wl_keyboard::Event::Key { serial, time, key, state } => {
+                let event = self.key_event(time, key);
+                match state {
+                    WEnum::Value(wl_keyboard::KeyState::Pressed) => {
+                        self.repeat = None;
+                        if self.repeat_info.rate > 0 && self.key_repeats(key) {
+                            let now = Instant::now();
+                            self.repeat = Some(Repeat {
+                                raw_code: key,
+                                time,
+                                pressed_at: now,
+                                next: now + self.repeat_info.delay,
+                            });
+                        }
+                        Some(KeyboardEvent::Press { serial, event })
+                    }
+                    _ => {
+                        if self.repeat.as_ref().map(|r| r.raw_code) == Some(key) {
+                            self.repeat = None;
+                        }
+                        Some(KeyboardEvent::Release { serial, event })
+                    }
+                }
+            }
+            wl_keyboard::Event::Modifiers {
+                serial,
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+            } => {
+                if let Some(ref keymap) = self.keymap {
+                    unsafe {
+                        ffi_dispatch!(
+                            xkbcommon_handle(),
+                            xkb_state_update_mask,
+                            keymap.state,
+                            mods_depressed,
+                            mods_latched,
+                            mods_locked,
+                            0,
+                            0,
+                            group
+                        );
+                    }
+                    self.modifiers = Modifiers {
+                        ctrl: keymap.mod_is_active(XKB_MOD_NAME_CTRL),
+                        alt: keymap.mod_is_active(XKB_MOD_NAME_ALT),
+                        shift: keymap.mod_is_active(XKB_MOD_NAME_SHIFT),
+                        caps_lock: keymap.mod_is_active(XKB_MOD_NAME_CAPS),
+                        logo: keymap.mod_is_active(XKB_MOD_NAME_LOGO),
+                        num_lock: keymap.mod_is_active(XKB_MOD_NAME_NUM),
+                    };
+                }
+                Some(KeyboardEvent::Modifiers { serial, modifiers: self.modifiers })
+            }
+            wl_keyboard::Event::RepeatInfo { rate, delay } => {
+                self.repeat_info = RepeatInfo {
+                    rate: rate.max(0) as u32,
+                    delay: Duration::from_millis(delay.max(0) as u64),
+                };
+                if self.repeat_info.rate == 0 {
+                    self.repeat = None;
+                } else if let Some(ref mut repeat) = self.repeat {
+                    repeat.next = repeat.pressed_at + self.repeat_info.delay;
+                }
+                None
+            }
+        }
+    }
+
+    /// The instant of the next repetition of the held key, if any
+    pub fn repeat_deadline(&self) -> Option<Instant> {
+        self.repeat.as_ref().map(|r| r.next)
+    }
+
+    /// The repetitions of the held key which are due at `now`
+    ///
+    /// Several repetitions are returned if this method was not called for longer than the repetition
+    /// interval. The keysym and text of the repetitions follow the current state of the modifiers.
+    pub fn repeat(&mut self, now: Instant) -> Vec<KeyboardEvent> {
+        let mut events = Vec::new();
+        let interval = Duration::from_secs(1) / self.repeat_info.rate.max(1);
+        while let Some((raw_code, time)) = self.repeat.as_mut().filter(|r| r.next <= now).map(|r| {
+            let time = r.time.wrapping_add((r.next - r.pressed_at).as_millis() as u32);
+            r.next += interval;
+            (r.raw_code, time)
+        }) {
+            events.push(KeyboardEvent::Repeat(self.key_event(time, raw_code)));
+        }
+        events
+    }
+
+    fn compile_keymap(&self, fd: OwnedFd, size: usize) -> Option<Keymap> {
+        let len = NonZeroUsize::new(size)?;
+        let ptr = match unsafe {
+            mman::mmap(
+                None,
+                len,
+                mman::ProtFlags::PROT_READ,
+                mman::MapFlags::MAP_PRIVATE,
+                fd.as_raw_fd(),
+                0,
+            )
+        } {
+            Ok(ptr) => ptr,
+            Err(e) => {
+                crate::log_warn!("Failed to map the keymap: {}", e);
+                return None;
+            }
+        };
+        // the keymap is a nul-terminated string, which older versions of xkbcommon do not accept
+        // as a buffer
+        let text = unsafe { std::slice::from_raw_parts(ptr as *const u8, size) };
+        let len = text.iter().position(|&b| b == 0).unwrap_or(size);
+        let keymap = unsafe {
+            ffi_dispatch!(
+                xkbcommon_handle(),
+                xkb_keymap_new_from_buffer,
+                self.context,
+                ptr as *const c_char,
+                len,
+                XKB_KEYMAP_FORMAT_TEXT_V1,
+                XKB_KEYMAP_COMPILE_NO_FLAGS
+            )
+        };
+        let _ = unsafe { mman::munmap(ptr, size) };
+        if keymap.is_null() {
+            crate::log_warn!("Failed to compile the keymap of the compositor");
+            return None;
+        }
+        let state = unsafe { ffi_dispatch!(xkbcommon_handle(), xkb_state_new, keymap) };
+        if state.is_null() {
+            unsafe { ffi_dispatch!(xkbcommon_handle(), xkb_keymap_unref, keymap) };
+            return None;
+        }
+        Some(Keymap { keymap, state })
+    }
+
+    fn key_repeats(&self, raw_code: u32) -> bool {
+        self.keymap.as_ref().map_or(false, |keymap| unsafe {
+            ffi_dispatch!(xkbcommon_handle(), xkb_keymap_key_repeats, keymap.keymap, raw_code + 8)
+                != 0
+        })
+    }
+
+    fn key_event(&self, time: u32, raw_code: u32) -> KeyEvent {
+        let (keysym, utf8) = match self.keymap {
+            Some(ref keymap) => keymap.decode(raw_code + 8),
+            None => (0, None),
+        };
+        KeyEvent { time, raw_code, keysym, utf8 }
+    }
+}
+
+impl Keymap {
+    fn mod_is_active(&self, name: &[u8]) -> bool {
+        unsafe {
+            ffi_dispatch!(
+                xkbcommon_handle(),
+                xkb_state_mod_name_is_active,
+                self.state,
+                name.as_ptr() as *const c_char,
+                XKB_STATE_MODS_EFFECTIVE
+            ) > 0
+        }
+    }
+
+    // xkbcommon keycodes are the evdev keycodes sent by the compositor, offset by 8
+    fn decode(&self, keycode: u32) -> (u32, Option<String>) {
+        let keysym = unsafe {
+            ffi_dispatch!(xkbcommon_handle(), xkb_state_key_get_one_sym, self.state, keycode)
+        };
+        let len = unsafe {
+            ffi_dispatch!(
+                xkbcommon_handle(),
+                xkb_state_key_get_utf8,
+                self.state,
+                keycode,
+                ptr::null_mut(),
+                0
+            )
+        };
+        if len <= 0 {
+            return (keysym, None);
+        }
+        let mut buffer = vec![0u8; len as usize + 1];
+        unsafe {
+            ffi_dispatch!(
+                xkbcommon_handle(),
+                xkb_state_key_get_utf8,
+                self.state,
+                keycode,
+                buffer.as_mut_ptr() as *mut c_char,
+                buffer.len()
+            );
+        }
+        buffer.truncate(len as usize);
+        (keysym, Some(String::from_utf8_lossy(&buffer).into_owned()))
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        self.keymap = None;
+        unsafe { ffi_dispatch!(xkbcommon_handle(), xkb_context_unref, self.context) };
+    }
+}
@@ -47,6 +47,12 @@
 //! can reuse in your own app by delegating those objects to that provided implementation. See the
 //! documentation of those traits and macro for details.
 //!
+//! ### Tracing
+//!
+//! With the `tracing` cargo feature, each [`Dispatch::event()`] invocation happens in a `dispatch` span
+//! of the `tracing` crate, with the `interface`, `id` and `opcode` of the event. The feature enables the
+//! spans of the rust backend as well, about which see the documentation of `wayland-backend`.
+//!
 //! ## Getting started example
 //!
 //! As an overview of how this crate is used, here is a commented example of a program that connects to the
@@ -158,6 +164,12 @@
 //!   method (see `wayland-backend` docs), and then make it into a [`Connection`] using
 //!   [`Connection::from_backend()`]. Similarly, you can make [`ObjectId`]s from the `*mut wl_proxy` pointers
 //!   using `ObjectId::from_ptr()`, and then make the proxies using [`Proxy::from_id`].
+//!
+//! For the common case of `raw-window-handle`, the `raw-window-handle` cargo feature of this crate does this
+//! for you: it enables the `client_system` feature of `wayland-backend`, and implements `HasRawDisplayHandle`
+//! for [`Connection`] and `HasRawWindowHandle` for
+//! [`WlSurface`](crate::protocol::wl_surface::WlSurface). Graphics libraries usually expect both handles
+//! from a single object, which `SurfaceHandle` provides.
 
 #![allow(clippy::needless_doctest_main)]
 #![warn(missing_docs, missing_debug_implementations)]
@@ -169,7 +181,7 @@
 use std::{
     fmt,
     hash::{Hash, Hasher},
-    os::unix::io::{BorrowedFd, OwnedFd},
+    os::unix::io::{AsRawFd, BorrowedFd, OwnedFd},
     sync::Arc,
 };
 use wayland_backend::{
@@ -177,16 +189,33 @@ use wayland_backend::{
     protocol::{Interface, Message},
 };
 
+pub mod clipboard;
 mod conn;
+pub mod connections;
 mod event_queue;
+pub mod frame;
 pub mod globals;
+#[cfg(feature = "xkbcommon")]
+pub mod keyboard;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod reconnect;
+#[cfg(feature = "raw-window-handle")]
+mod rwh;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod shm;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod touch;
 
 /// Backend reexports
 pub mod backend {
     pub use wayland_backend::client::{
-        Backend, InvalidId, NoWaylandLib, ObjectData, ObjectId, ReadEventsGuard, WaylandError,
-        WeakBackend,
+        Backend, FlushPolicy, InvalidId, NoWaylandLib, ObjectData, ObjectId, ProtocolLogger,
+        ReadEventsGuard, WaylandError, WeakBackend, ZombieEvent, ZombieHook,
     };
+    pub use wayland_backend::debug;
     pub use wayland_backend::protocol;
     pub use wayland_backend::smallvec;
 }
@@ -194,7 +223,11 @@ pub mod backend {
 pub use wayland_backend::protocol::WEnum;
 
 pub use conn::{ConnectError, Connection};
-pub use event_queue::{Dispatch, EventQueue, QueueFreezeGuard, QueueHandle, QueueProxyData};
+pub use event_queue::{
+    Dispatch, EventQueue, QueueFreezeGuard, QueueHandle, QueuePriority, QueueProxyData, QueueWaker,
+};
+#[cfg(feature = "raw-window-handle")]
+pub use rwh::SurfaceHandle;
 
 // internal imports for dispatching logging depending on the `log` feature
 #[cfg(feature = "log")]
@@ -206,6 +239,20 @@ use std::{
     eprintln as log_error, eprintln as log_warn, eprintln as log_info, eprintln as log_debug,
 };
 
+// internal spans of the `tracing` crate, which expand to nothing without the `tracing` feature
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($name: literal $(, $($fields: tt)*)?) => {
+        let _span = tracing::trace_span!($name $(, $($fields)*)?).entered();
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($args: tt)*) => {};
+}
+#[allow(unused_imports)]
+use trace_span;
+
 /// Generated protocol definitions
 ///
 /// This module is automatically generated from the `wayland.xml` protocol specification,
@@ -217,7 +264,10 @@ pub mod protocol {
     pub mod __interfaces {
         wayland_scanner::generate_interfaces!("wayland.xml");
     }
+    #[cfg(not(feature = "serde"))]
     wayland_scanner::generate_client_code!("wayland.xml");
+    #[cfg(feature = "serde")]
+    wayland_scanner::generate_client_code!("wayland.xml", serde);
 }
 
 /// Trait representing a Wayland interface
@@ -236,6 +286,17 @@ pub trait Proxy: Clone + std::fmt::Debug + Sized {
     /// The version of this object
     fn version(&self) -> u32;
 
+    /// Checks if the version of this object supports the request with the given opcode
+    ///
+    /// The opcodes are given by the `REQ_*_OPCODE` constants of the interface modules. Sending a
+    /// request which the version of the object does not support is a protocol error.
+    fn version_supports(&self, opcode: u16) -> bool {
+        Self::interface()
+            .requests
+            .get(opcode as usize)
+            .map_or(false, |request| self.version() >= request.since)
+    }
+
     /// Checks if the Wayland object associated with this proxy is still alive
     fn is_alive(&self) -> bool {
         if let Some(backend) = self.backend().upgrade() {
@@ -288,6 +349,27 @@ pub trait Proxy: Clone + std::fmt::Debug + Sized {
         data: Arc<dyn ObjectData>,
     ) -> Result<I, InvalidId>;
 
+    /// Send a request for this object that creates another object of the given version.
+    ///
+    /// This is the same as [Proxy::send_constructor], except that the created object gets `version`
+    /// rather than the version decided by the request, which is the version of this object unless the
+    /// request specifies it like `wl_registry.bind`. This allows bindings which are not generated by
+    /// wayland-scanner, or generated at runtime, to create their objects with the version the server
+    /// gives them.
+    fn send_constructor_with_version<I: Proxy>(
+        &self,
+        req: Self::Request<'_>,
+        version: u32,
+        data: Arc<dyn ObjectData>,
+    ) -> Result<I, InvalidId> {
+        let conn = Connection::from_backend(self.backend().upgrade().ok_or(InvalidId)?);
+        let (msg, child_spec) = self.write_request(&conn, req)?;
+        let child_spec = child_spec.map(|(interface, _)| (interface, version));
+        let msg = msg.map_fd(|fd| fd.as_raw_fd());
+        let id = conn.backend().send_request(msg, Some(data), child_spec)?;
+        I::from_id(&conn, id)
+    }
+
     /// Parse a event for this object
     ///
     /// **Note:** This method is mostly meant as an implementation detail to be
@@ -318,6 +400,33 @@ pub trait Proxy: Clone + std::fmt::Debug + Sized {
     fn downgrade(&self) -> Weak<Self> {
         Weak { backend: self.backend().clone(), id: self.id(), _iface: std::marker::PhantomData }
     }
+
+    /// Deliver the events of this object to a stream
+    ///
+    /// The events are no longer dispatched to the event queue of the object, and the stream
+    /// buffers them until they are consumed. Calling this method again moves the delivery of the
+    /// events to the new stream, ending the previous one. See the [`stream`] module for details.
+    #[cfg(feature = "async")]
+    fn event_stream(&self) -> stream::EventStream<Self>
+    where
+        Self: 'static,
+        Self::Event: Send,
+    {
+        stream::EventStream::attach(self, None)
+    }
+
+    /// Deliver the events of this object to a stream only keeping the `capacity` most recent events
+    ///
+    /// This is similar to [`event_stream()`](Proxy::event_stream), but older events are discarded
+    /// when the stream is not consumed fast enough.
+    #[cfg(feature = "async")]
+    fn event_stream_bounded(&self, capacity: usize) -> stream::EventStream<Self>
+    where
+        Self: 'static,
+        Self::Event: Send,
+    {
+        stream::EventStream::attach(self, Some(capacity))
+    }
 }
 
 /// Wayland dispatching error
@@ -0,0 +1,489 @@
+//! A scripted client, for testing compositors
+//!
+//! The [`MockClient`] connects to a compositor and works at the level of raw messages: it binds the
+//! globals you ask for, sends requests by name, and records every event it receives along with the
+//! time it was received. This allows writing tests for a headless compositor from `#[test]` functions,
+//! without writing a full client:
+//!
+//! ```no_run
+//! use wayland_client::backend::{protocol::Argument, ObjectId};
+//! use wayland_client::mock::MockClient;
+//! use wayland_client::protocol::wl_compositor;
+//! use wayland_client::{Connection, Proxy};
+//!
+//! let conn = Connection::connect_to_env().unwrap();
+//! let client = MockClient::new(conn).unwrap();
+//! client.roundtrip().unwrap();
+//!
+//! let compositor = client.bind(wl_compositor::WlCompositor::interface(), 4);
+//! let surface = client.send(&compositor, "create_surface", vec![Argument::NewId(ObjectId::null())])
+//!     .unwrap()
+//!     .unwrap();
+//! client.roundtrip().unwrap();
+//! # let _ = surface;
+//! ```
+//!
+//! Beyond checking events one at a time, [`MockClient::wait_for()`] waits for a set of
+//! [`expect()`]ed events, possibly ordered relative to others, while the compositor runs:
+//!
+//! ```no_run
+//! # use wayland_client::mock::MockClient;
+//! # let client = MockClient::new(wayland_client::Connection::connect_to_env().unwrap()).unwrap();
+//! use std::time::Duration;
+//! use wayland_client::mock::expect;
+//! use wayland_client::protocol::{wl_keyboard::WlKeyboard, wl_seat::WlSeat};
+//!
+//! client.wait_for(
+//!     &[expect::<WlKeyboard>("keymap"), expect::<WlKeyboard>("enter").after::<WlSeat>("name")],
+//!     Duration::from_secs(1),
+//! );
+//! ```
+//!
+//! This module is only available with the `mock` cargo feature.
+
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::fmt;
+use std::io::ErrorKind;
+use std::os::unix::io::{OwnedFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use wayland_backend::client::{InvalidId, ObjectData, ObjectId, WaylandError};
+use wayland_backend::protocol::{Argument, Interface, Message};
+
+use crate::protocol::{wl_display, wl_registry};
+use crate::{Connection, Proxy};
+
+/// A scripted Wayland client
+///
+/// See the [module-level documentation](self) for an overview.
+#[derive(Debug)]
+pub struct MockClient {
+    conn: Connection,
+    registry: ObjectId,
+    data: Arc<MockObjectData>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    start: Instant,
+    globals: Vec<MockGlobal>,
+    events: VecDeque<MockEvent>,
+}
+
+/// A global advertised to the [`MockClient`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockGlobal {
+    /// The name of the global
+    pub name: u32,
+    /// The interface of the global
+    pub interface: String,
+    /// The version of the global
+    pub version: u32,
+}
+
+/// An event received by the [`MockClient`]
+#[derive(Debug)]
+pub struct MockEvent {
+    /// Time at which the event was received, since the creation of the client
+    pub time: Duration,
+    /// The content of the event
+    pub message: Message<ObjectId, OwnedFd>,
+}
+
+impl fmt::Display for MockEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}(", self.message.sender_id, self.name())?;
+        for (i, arg) in self.message.args.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            write!(f, "{}{}", sep, arg)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl MockEvent {
+    /// Interface of the object the event was sent to
+    pub fn interface(&self) -> &'static Interface {
+        self.message.sender_id.interface()
+    }
+
+    /// Name of the event
+    pub fn name(&self) -> &'static str {
+        self.interface().events[self.message.opcode as usize].name
+    }
+
+    /// The object created by this event, if any
+    pub fn new_id(&self) -> Option<ObjectId> {
+        self.message.args.iter().find_map(|arg| match arg {
+            Argument::NewId(id) => Some(id.clone()),
+            _ => None,
+        })
+    }
+}
+
+impl MockClient {
+    /// Create a mock client on an existing connection
+    ///
+    /// This creates a registry to track the globals of the compositor. Its events are handled
+    /// internally and are not recorded.
+    pub fn new(conn: Connection) -> Result<Self, InvalidId> {
+        let data = Arc::new(MockObjectData {
+            state: Mutex::new(MockState {
+                start: Instant::now(),
+                globals: Vec::new(),
+                events: VecDeque::new(),
+            }),
+        });
+        let registry = conn.send_request(
+            &conn.display(),
+            wl_display::Request::GetRegistry {},
+            Some(data.clone()),
+        )?;
+        Ok(Self { conn, registry, data })
+    }
+
+    /// The connection used by this client
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The globals currently advertised by the compositor
+    pub fn globals(&self) -> Vec<MockGlobal> {
+        self.data.state.lock().unwrap().globals.clone()
+    }
+
+    /// Bind a global of given interface
+    ///
+    /// The bound version is the smallest of `version` and the version advertised by the compositor.
+    ///
+    /// **Panic:** this method panics if no global of this interface was advertised, which is the case
+    /// until a roundtrip has been done.
+    pub fn bind(&self, interface: &'static Interface, version: u32) -> ObjectId {
+        let global = self
+            .globals()
+            .into_iter()
+            .find(|global| global.interface == interface.name)
+            .unwrap_or_else(|| panic!("No {} global was advertised", interface.name));
+        let version = version.min(global.version);
+
+        self.conn
+            .backend()
+            .send_request(
+                Message {
+                    sender_id: self.registry.clone(),
+                    opcode: wl_registry::REQ_BIND_OPCODE,
+                    args: vec![
+                        Argument::Uint(global.name),
+                        Argument::Str(Some(Box::new(CString::new(interface.name).unwrap()))),
+                        Argument::Uint(version),
+                        Argument::NewId(ObjectId::null()),
+                    ]
+                    .into(),
+                },
+                Some(self.data.clone()),
+                Some((interface, version)),
+            )
+            .expect("The registry of a MockClient cannot be dead")
+    }
+
+    /// Send a request by name
+    ///
+    /// If the request creates an object, a [`ObjectId::null()`] must be given in the argument list at
+    /// the appropriate place, and the created object is returned. Its events will then be recorded too.
+    ///
+    /// **Panic:** this method panics if the interface of the object has no request named `request`, or if
+    /// the arguments do not match its signature.
+    pub fn send(
+        &self,
+        object: &ObjectId,
+        request: &str,
+        args: Vec<Argument<ObjectId, RawFd>>,
+    ) -> Result<Option<ObjectId>, InvalidId> {
+        let interface = object.interface();
+        let opcode =
+            interface.requests.iter().position(|desc| desc.name == request).unwrap_or_else(|| {
+                panic!("Interface {} has no request {}", interface.name, request)
+            });
+        let id = self.conn.backend().send_request(
+            Message { sender_id: object.clone(), opcode: opcode as u16, args: args.into() },
+            Some(self.data.clone()),
+            None,
+        )?;
+        Ok(if id.is_null() { None } else { Some(id) })
+    }
+
+    /// Flush the requests and wait until the compositor processed all of them
+    ///
+    /// The compositor must be dispatched by another thread while this method blocks.
+    pub fn roundtrip(&self) -> Result<usize, WaylandError> {
+        self.conn.roundtrip()
+    }
+
+    /// Flush the requests and process the events already received, without blocking
+    ///
+    /// This allows driving the client and the compositor alternately from a single thread.
+    pub fn dispatch(&self) -> Result<usize, WaylandError> {
+        let backend = self.conn.backend();
+        backend.flush()?;
+        match backend.prepare_read() {
+            Some(guard) => match guard.read() {
+                Ok(n) => Ok(n),
+                Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(0),
+                Err(e) => Err(e),
+            },
+            None => backend.dispatch_inner_queue(),
+        }
+    }
+
+    /// Retrieve all the events that were received and not yet retrieved, in order
+    pub fn take_events(&self) -> Vec<MockEvent> {
+        self.data.state.lock().unwrap().events.drain(..).collect()
+    }
+
+    /// Retrieve the oldest received event, asserting its interface and name
+    ///
+    /// **Panic:** this method panics if no event was received, or if it does not match.
+    pub fn expect_event(&self, interface: &'static Interface, event: &str) -> MockEvent {
+        let next = self.data.state.lock().unwrap().events.pop_front();
+        match next {
+            Some(next) if next.interface().name == interface.name && next.name() == event => next,
+            Some(next) => panic!(
+                "Expected event {}.{}, got {}.{}",
+                interface.name,
+                event,
+                next.interface().name,
+                next.name()
+            ),
+            None => panic!("Expected event {}.{}, got nothing", interface.name, event),
+        }
+    }
+
+    /// Assert that all received events have been retrieved
+    ///
+    /// **Panic:** this method panics if there are events that were not retrieved.
+    pub fn expect_no_event(&self) {
+        let state = self.data.state.lock().unwrap();
+        if let Some(next) = state.events.front() {
+            panic!("Expected no event, got {}.{}", next.interface().name, next.name());
+        }
+    }
+
+    /// Dispatch the events until a distinct event matching each of the expectations is received
+    ///
+    /// The events may arrive in any order, and other events may be received in between. The matched
+    /// events are retrieved and returned in the order of the expectations, the others stay recorded.
+    ///
+    /// **Panic:** this method panics if the expectations are not met within `timeout`, listing the
+    /// unmet expectations and the events received.
+    pub fn wait_for(&self, expectations: &[Expectation], timeout: Duration) -> Vec<MockEvent> {
+        self.wait(expectations, false, timeout)
+    }
+
+    /// Dispatch the events until events matching the expectations are received in order
+    ///
+    /// Other events may be received in between. As with [`wait_for()`](Self::wait_for), the matched
+    /// events are retrieved and returned, the others stay recorded.
+    ///
+    /// **Panic:** this method panics if the expectations are not met within `timeout`.
+    pub fn wait_for_sequence(
+        &self,
+        expectations: &[Expectation],
+        timeout: Duration,
+    ) -> Vec<MockEvent> {
+        self.wait(expectations, true, timeout)
+    }
+
+    fn wait(
+        &self,
+        expectations: &[Expectation],
+        ordered: bool,
+        timeout: Duration,
+    ) -> Vec<MockEvent> {
+        let deadline = Instant::now() + timeout;
+        let mut received = Vec::new();
+        loop {
+            if let Err(e) = self.dispatch() {
+                panic!("Failed to dispatch the events: {}", e);
+            }
+            received.extend(self.take_events());
+            let keys = received.iter().map(|e| (e.interface().name, e.name())).collect::<Vec<_>>();
+            let matches = match_expectations(expectations, &keys, ordered);
+
+            if matches.iter().all(Option::is_some) {
+                let mut received = received.into_iter().map(Some).collect::<Vec<_>>();
+                let matched =
+                    matches.iter().map(|&i| received[i.unwrap()].take().unwrap()).collect();
+                // put the other events back
+                let mut state = self.data.state.lock().unwrap();
+                for event in received.into_iter().flatten().rev() {
+                    state.events.push_front(event);
+                }
+                return matched;
+            }
+
+            if Instant::now() >= deadline {
+                let unmet = expectations.iter().zip(&matches).filter(|(_, m)| m.is_none());
+                let mut text = format!("Unmet expectations after {:?}:\n", timeout);
+                for (expectation, _) in unmet {
+                    text += &format!("  {}\n", expectation);
+                }
+                text += "Received events:\n";
+                for event in &received {
+                    text += &format!("  {}\n", event);
+                }
+                panic!("{}", text);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Expect an event sent by an object of interface `I`, see [`MockClient::wait_for()`]
+///
+/// **Panic:** this function panics if the interface has no event named `event`.
+pub fn expect<I: Proxy>(event: &str) -> Expectation {
+    let (interface, event) = event_desc(I::interface(), event);
+    Expectation { interface, event, after: Vec::new() }
+}
+
+/// An event expected by the [`MockClient`], created with [`expect()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    interface: &'static str,
+    event: &'static str,
+    after: Vec<(&'static str, &'static str)>,
+}
+
+impl Expectation {
+    /// Only match the event if it was received after the given event from an object of interface `I`
+    ///
+    /// **Panic:** this method panics if the interface has no event named `event`.
+    pub fn after<I: Proxy>(mut self, event: &str) -> Self {
+        self.after.push(event_desc(I::interface(), event));
+        self
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.interface, self.event)?;
+        for (i, (interface, event)) in self.after.iter().enumerate() {
+            let sep = if i == 0 { "after" } else { "and" };
+            write!(f, " {} {}.{}", sep, interface, event)?;
+        }
+        Ok(())
+    }
+}
+
+fn event_desc(interface: &'static Interface, event: &str) -> (&'static str, &'static str) {
+    match interface.events.iter().find(|desc| desc.name == event) {
+        Some(desc) => (interface.name, desc.name),
+        None => panic!("Interface {} has no event {}", interface.name, event),
+    }
+}
+
+// Assign a distinct received message to each expectation, returning the index of the message
+// matched by each of them
+fn match_expectations(
+    expectations: &[Expectation],
+    received: &[(&str, &str)],
+    ordered: bool,
+) -> Vec<Option<usize>> {
+    let candidates = expectations
+        .iter()
+        .map(|expectation| {
+            (0..received.len())
+                .filter(|&i| received[i] == (expectation.interface, expectation.event))
+                .filter(|&i| expectation.after.iter().all(|after| received[..i].contains(after)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    if ordered {
+        // the earliest match of each expectation leaves the most room to the next ones
+        let mut next = 0;
+        return candidates
+            .iter()
+            .map(|candidates| {
+                let found = candidates.iter().copied().find(|&i| i >= next);
+                next = found.map_or(usize::MAX, |i| i + 1);
+                found
+            })
+            .collect();
+    }
+
+    // bipartite matching, `owner` giving the expectation each message is assigned to
+    let mut owner = vec![None; received.len()];
+    for expectation in 0..expectations.len() {
+        augment(expectation, &candidates, &mut owner, &mut vec![false; received.len()]);
+    }
+    let mut matches = vec![None; expectations.len()];
+    for (i, expectation) in owner.into_iter().enumerate() {
+        if let Some(expectation) = expectation {
+            matches[expectation] = Some(i);
+        }
+    }
+    matches
+}
+
+// Find an assignment for `expectation`, moving the other expectations to their next candidates
+fn augment(
+    expectation: usize,
+    candidates: &[Vec<usize>],
+    owner: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for &i in &candidates[expectation] {
+        if !visited[i] {
+            visited[i] = true;
+            if owner[i].map_or(true, |other| augment(other, candidates, owner, visited)) {
+                owner[i] = Some(expectation);
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// A request received by the [`MockCompositor`]
+#[derive(Debug)]
+struct MockObjectData {
+    state: Mutex<MockState>,
+}
+
+impl ObjectData for MockObjectData {
+    fn event(
+        self: Arc<Self>,
+        _: &wayland_backend::client::Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        let mut state = self.state.lock().unwrap();
+
+        if msg.sender_id.interface().name == wl_registry::WlRegistry::interface().name {
+            match (msg.opcode, &msg.args[..]) {
+                (
+                    wl_registry::EVT_GLOBAL_OPCODE,
+                    [Argument::Uint(name), Argument::Str(Some(interface)), Argument::Uint(version)],
+                ) => state.globals.push(MockGlobal {
+                    name: *name,
+                    interface: interface.to_string_lossy().into_owned(),
+                    version: *version,
+                }),
+                (wl_registry::EVT_GLOBAL_REMOVE_OPCODE, [Argument::Uint(name)]) => {
+                    state.globals.retain(|global| global.name != *name)
+                }
+                _ => {}
+            }
+            return None;
+        }
+
+        let event = MockEvent { time: state.start.elapsed(), message: msg };
+        let child = event.new_id().map(|_| self.clone() as Arc<dyn ObjectData>);
+        state.events.push_back(event);
+        child
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
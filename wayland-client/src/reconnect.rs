@@ -0,0 +1,252 @@
+//! Surviving the restart of the compositor
+//!
+//! When the compositor exits or crashes, the connection to it is lost along with all the objects
+//! created on it. Long-running clients like panels or kiosk apps may instead want to wait for the
+//! compositor to come back, and start over on a new connection. [`Reconnect`] automates this: it
+//! connects to the compositor, retrying until it is available, initializes the registry and gives
+//! the globals to your setup callback. When the connection is lost while dispatching, it notifies
+//! your state and connects again, invoking the setup callback anew so that the globals are bound
+//! again and the surfaces recreated:
+//!
+//! ```no_run
+//! use wayland_client::{globals::GlobalListContents, protocol::wl_registry, reconnect::Reconnect};
+//! # use wayland_client::{Connection, Dispatch, QueueHandle};
+//!
+//! struct State {
+//!     // the proxies of the current connection
+//!     outputs: usize,
+//! }
+//!
+//! # impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+//! #     fn event(_: &mut State, _: &wl_registry::WlRegistry, _: wl_registry::Event,
+//! #         _: &GlobalListContents, _: &Connection, _: &QueueHandle<State>) {}
+//! # }
+//! let mut reconnect = Reconnect::from_env(|state: &mut State, _conn, globals, _qh| {
+//!     // bind the globals and create the objects of the app
+//!     state.outputs = globals.contents().with_list(|list| {
+//!         list.iter().filter(|global| global.interface == "wl_output").count()
+//!     });
+//! });
+//! reconnect.on_connection_lost(|state, error| {
+//!     eprintln!("Lost the connection to the compositor: {}", error);
+//!     // the proxies of the lost connection are dead
+//!     state.outputs = 0;
+//! });
+//!
+//! let mut state = State { outputs: 0 };
+//! loop {
+//!     reconnect.blocking_dispatch(&mut state).unwrap();
+//! #   break;
+//! }
+//! ```
+//!
+//! Only the loss of the connection triggers a reconnection. The protocol errors, which are bugs of
+//! either the client or the compositor, are returned to the caller.
+
+use std::{fmt, time::Duration};
+
+use wayland_backend::client::WaylandError;
+
+use crate::{
+    globals::{registry_queue_init, GlobalError, GlobalList, GlobalListContents},
+    protocol::wl_registry,
+    ConnectError, Connection, Dispatch, DispatchError, EventQueue, QueueHandle,
+};
+
+type ConnectFn = Box<dyn FnMut() -> Result<Connection, ConnectError>>;
+type SetupFn<State> = Box<dyn FnMut(&mut State, &Connection, &GlobalList, &QueueHandle<State>)>;
+type LostFn<State> = Box<dyn FnMut(&mut State, &WaylandError)>;
+
+/// A connection to the compositor, reestablished when it is lost
+///
+/// See the [module documentation](self) for details.
+pub struct Reconnect<State> {
+    connect: ConnectFn,
+    setup: SetupFn<State>,
+    lost: Option<LostFn<State>>,
+    retry_delay: Duration,
+    max_attempts: Option<usize>,
+    current: Option<(Connection, GlobalList, EventQueue<State>)>,
+}
+
+impl<State> Reconnect<State>
+where
+    State: Dispatch<wl_registry::WlRegistry, GlobalListContents> + 'static,
+{
+    /// Reconnect with the given connection function
+    ///
+    /// The connection is not established before the first dispatch, or a call to
+    /// [`connect()`](Reconnect::connect). `setup` is then invoked on each new connection with its
+    /// globals, once the initial roundtrip of the registry is done.
+    pub fn new<C, F>(connect: C, setup: F) -> Self
+    where
+        C: FnMut() -> Result<Connection, ConnectError> + 'static,
+        F: FnMut(&mut State, &Connection, &GlobalList, &QueueHandle<State>) + 'static,
+    {
+        Self {
+            connect: Box::new(connect),
+            setup: Box::new(setup),
+            lost: None,
+            retry_delay: Duration::from_millis(500),
+            max_attempts: None,
+            current: None,
+        }
+    }
+
+    /// Reconnect to the compositor given by the environment, with [`Connection::connect_to_env()`]
+    pub fn from_env<F>(setup: F) -> Self
+    where
+        F: FnMut(&mut State, &Connection, &GlobalList, &QueueHandle<State>) + 'static,
+    {
+        Self::new(Connection::connect_to_env, setup)
+    }
+
+    /// Set the delay between two attempts to connect, 500ms by default
+    pub fn set_retry_delay(&mut self, delay: Duration) {
+        self.retry_delay = delay;
+    }
+
+    /// Set the number of attempts to connect before giving up
+    ///
+    /// This is unlimited by default, waiting for the compositor for as long as necessary.
+    pub fn set_max_attempts(&mut self, max_attempts: Option<usize>) {
+        self.max_attempts = max_attempts;
+    }
+
+    /// Set the callback notified of the loss of the connection
+    ///
+    /// It is invoked before connecting again, for your state to drop the objects of the lost
+    /// connection.
+    pub fn on_connection_lost(&mut self, lost: impl FnMut(&mut State, &WaylandError) + 'static) {
+        self.lost = Some(Box::new(lost));
+    }
+
+    /// The current connection, if connected
+    pub fn connection(&self) -> Option<&Connection> {
+        self.current.as_ref().map(|(conn, _, _)| conn)
+    }
+
+    /// The globals of the current connection, if connected
+    pub fn globals(&self) -> Option<&GlobalList> {
+        self.current.as_ref().map(|(_, globals, _)| globals)
+    }
+
+    /// The event queue of the current connection, if connected
+    pub fn queue_mut(&mut self) -> Option<&mut EventQueue<State>> {
+        self.current.as_mut().map(|(_, _, queue)| queue)
+    }
+
+    /// Connect if not already connected
+    ///
+    /// The attempts to connect are retried while no compositor is found, or the connection is
+    /// lost during the initial roundtrip, until the maximum number of attempts is reached.
+    pub fn connect(&mut self, state: &mut State) -> Result<(), ReconnectError> {
+        if self.current.is_some() {
+            return Ok(());
+        }
+        let mut attempts = 0;
+        let (conn, globals, queue) = loop {
+            attempts += 1;
+            let error = match (self.connect)() {
+                Ok(conn) => match registry_queue_init::<State>(&conn) {
+                    Ok((globals, queue)) => break (conn, globals, queue),
+                    Err(GlobalError::Backend(WaylandError::Io(err))) => {
+                        ReconnectError::Globals(GlobalError::Backend(WaylandError::Io(err)))
+                    }
+                    Err(err) => return Err(ReconnectError::Globals(err)),
+                },
+                Err(ConnectError::NoCompositor) => {
+                    ReconnectError::Connect(ConnectError::NoCompositor)
+                }
+                Err(err) => return Err(ReconnectError::Connect(err)),
+            };
+            if self.max_attempts.map_or(false, |max| attempts >= max) {
+                return Err(error);
+            }
+            std::thread::sleep(self.retry_delay);
+        };
+        (self.setup)(state, &conn, &globals, &queue.handle());
+        self.current = Some((conn, globals, queue));
+        Ok(())
+    }
+
+    /// Dispatch the pending events of the current connection, connecting first if needed
+    ///
+    /// See [`EventQueue::dispatch_pending()`]. If the connection was lost, the callback given to
+    /// [`on_connection_lost()`](Reconnect::on_connection_lost) is invoked and a new connection is
+    /// established.
+    pub fn dispatch_pending(&mut self, state: &mut State) -> Result<usize, ReconnectError> {
+        self.dispatch_with(state, EventQueue::dispatch_pending)
+    }
+
+    /// Block waiting for events and dispatch them, connecting first if needed
+    ///
+    /// See [`EventQueue::blocking_dispatch()`]. If the connection was lost, the callback given to
+    /// [`on_connection_lost()`](Reconnect::on_connection_lost) is invoked and a new connection is
+    /// established before returning.
+    pub fn blocking_dispatch(&mut self, state: &mut State) -> Result<usize, ReconnectError> {
+        self.dispatch_with(state, EventQueue::blocking_dispatch)
+    }
+
+    fn dispatch_with(
+        &mut self,
+        state: &mut State,
+        dispatch: impl FnOnce(&mut EventQueue<State>, &mut State) -> Result<usize, DispatchError>,
+    ) -> Result<usize, ReconnectError> {
+        self.connect(state)?;
+        let (_, _, queue) = self.current.as_mut().unwrap();
+        match dispatch(queue, state) {
+            Ok(dispatched) => Ok(dispatched),
+            Err(DispatchError::Backend(WaylandError::Io(err))) => {
+                self.current = None;
+                if let Some(ref mut lost) = self.lost {
+                    lost(state, &WaylandError::Io(err));
+                }
+                self.connect(state)?;
+                Ok(0)
+            }
+            Err(err) => Err(ReconnectError::Dispatch(err)),
+        }
+    }
+}
+
+impl<State> fmt::Debug for Reconnect<State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reconnect")
+            .field("retry_delay", &self.retry_delay)
+            .field("max_attempts", &self.max_attempts)
+            .field("connected", &self.current.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An error preventing a [`Reconnect`] from connecting or dispatching
+#[derive(Debug)]
+pub enum ReconnectError {
+    /// No connection could be established
+    Connect(ConnectError),
+    /// The initial roundtrip of the registry failed
+    Globals(GlobalError),
+    /// Dispatching failed for another reason than the loss of the connection
+    Dispatch(DispatchError),
+}
+
+impl std::error::Error for ReconnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReconnectError::Connect(source) => Some(source),
+            ReconnectError::Globals(source) => Some(source),
+            ReconnectError::Dispatch(source) => Some(source),
+        }
+    }
+}
+
+impl fmt::Display for ReconnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReconnectError::Connect(source) => write!(f, "Could not connect: {}", source),
+            ReconnectError::Globals(source) => write!(f, "Could not list the globals: {}", source),
+            ReconnectError::Dispatch(source) => write!(f, "{}", source),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Implementations of the raw-window-handle traits
+
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, WaylandWindowHandle,
+};
+
+use crate::{protocol::wl_surface::WlSurface, Connection, Proxy};
+
+// SAFETY: the display pointer is valid as long as the backend is alive, and the connection keeps it
+// alive.
+unsafe impl HasRawDisplayHandle for Connection {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        self.backend().raw_display_handle()
+    }
+}
+
+// SAFETY: the proxy pointer is valid until the surface is destroyed, and is null afterwards.
+unsafe impl HasRawWindowHandle for WlSurface {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        let mut handle = WaylandWindowHandle::empty();
+        handle.surface = self.id().as_ptr().cast();
+        RawWindowHandle::Wayland(handle)
+    }
+}
+
+/// A `wl_surface` along with its connection, providing both raw handles
+///
+/// Graphics libraries such as wgpu or softbuffer expect a single object implementing both
+/// [`HasRawWindowHandle`] and [`HasRawDisplayHandle`]. The connection is kept alive by this handle,
+/// but the surface must not be destroyed while the graphics library uses it.
+#[derive(Debug, Clone)]
+pub struct SurfaceHandle {
+    connection: Connection,
+    surface: WlSurface,
+}
+
+impl SurfaceHandle {
+    /// Gather a surface and the connection it was created on
+    pub fn new(connection: &Connection, surface: &WlSurface) -> SurfaceHandle {
+        SurfaceHandle { connection: connection.clone(), surface: surface.clone() }
+    }
+
+    /// The connection of the surface
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// The surface
+    pub fn surface(&self) -> &WlSurface {
+        &self.surface
+    }
+}
+
+// SAFETY: see the implementations for `Connection` and `WlSurface`
+unsafe impl HasRawDisplayHandle for SurfaceHandle {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        self.connection.raw_display_handle()
+    }
+}
+
+// SAFETY: see the implementations for `Connection` and `WlSurface`
+unsafe impl HasRawWindowHandle for SurfaceHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.surface.raw_window_handle()
+    }
+}
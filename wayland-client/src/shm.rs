@@ -0,0 +1,443 @@
+//! Helpers for sharing memory buffers with the compositor
+//!
+//! Software-rendered apps draw into a shared memory file, and describe the images stored in it to the
+//! compositor as `wl_buffer`s created from a `wl_shm_pool`. This module takes care of the unsafe parts of
+//! this process: the [`MemPool`] creates the file, maps it into memory and resizes it, and the [`Buffer`]s
+//! created from it keep track of whether the compositor is still reading them, so that the app does not
+//! draw into an image that is being displayed.
+//!
+//! A buffer is considered busy from the moment it is attached to a surface with [`Buffer::attach_to()`]
+//! until the compositor sends its `wl_buffer.release` event. While it is busy, [`MemPool::buffer_contents()`]
+//! refuses to give access to its contents.
+//!
+//! Apps redrawing continuously usually need two pools, one being displayed while the other is drawn into.
+//! The [`DoubleMemPool`] alternates between two pools in that way.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use wayland_client::{
+//!     protocol::{wl_shm, wl_surface},
+//!     shm::MemPool,
+//! };
+//! # fn draw(shm: &wl_shm::WlShm, surface: &wl_surface::WlSurface) -> Result<(), wayland_client::shm::ShmError> {
+//! let (width, height) = (320, 240);
+//! let mut pool = MemPool::new(shm, width * height * 4)?;
+//! let buffer =
+//!     pool.create_buffer(0, width as i32, height as i32, width as i32 * 4, wl_shm::Format::Argb8888)?;
+//!
+//! // fill the image with opaque white
+//! pool.buffer_contents(&buffer).unwrap().fill(0xff);
+//! buffer.attach_to(surface);
+//! surface.commit();
+//!
+//! // the contents of the buffer are not accessible until the compositor releases it
+//! assert!(pool.buffer_contents(&buffer).is_none());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    fs::File,
+    io,
+    num::NonZeroUsize,
+    os::unix::io::{AsFd, FromRawFd, OwnedFd, RawFd},
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use nix::{
+    errno::Errno,
+    fcntl,
+    sys::{mman, stat},
+};
+use wayland_backend::{
+    client::{Backend, InvalidId, ObjectData, ObjectId},
+    protocol::Message,
+};
+
+use crate::{
+    protocol::{wl_buffer, wl_shm, wl_shm_pool, wl_surface},
+    Proxy, WEnum,
+};
+
+/// An error when creating or resizing a [`MemPool`], or when creating a buffer from it
+#[derive(Debug)]
+pub enum ShmError {
+    /// The shared memory file could not be created, resized or mapped
+    Io(io::Error),
+    /// The `wl_shm` or `wl_shm_pool` object is no longer alive
+    InvalidId(InvalidId),
+    /// The requested size does not fit in a `wl_shm_pool`, or the buffer does not fit in the pool
+    OutOfBounds,
+}
+
+impl std::error::Error for ShmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShmError::Io(e) => Some(e),
+            ShmError::InvalidId(e) => Some(e),
+            ShmError::OutOfBounds => None,
+        }
+    }
+}
+
+impl fmt::Display for ShmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShmError::Io(e) => write!(f, "Shared memory file error: {}", e),
+            ShmError::InvalidId(e) => write!(f, "{}", e),
+            ShmError::OutOfBounds => write!(f, "The requested size exceeds the bounds of the pool"),
+        }
+    }
+}
+
+impl From<io::Error> for ShmError {
+    fn from(e: io::Error) -> Self {
+        ShmError::Io(e)
+    }
+}
+
+impl From<Errno> for ShmError {
+    fn from(e: Errno) -> Self {
+        ShmError::Io(e.into())
+    }
+}
+
+impl From<InvalidId> for ShmError {
+    fn from(e: InvalidId) -> Self {
+        ShmError::InvalidId(e)
+    }
+}
+
+/// A `wl_shm_pool` and the shared memory file backing it
+///
+/// The file is mapped in memory for as long as the pool exists. The pool can only grow, as required by
+/// the protocol, and destroying it does not invalidate the buffers created from it.
+#[derive(Debug)]
+pub struct MemPool {
+    pool: wl_shm_pool::WlShmPool,
+    file: File,
+    map: NonNull<u8>,
+    len: usize,
+    busy: Arc<AtomicUsize>,
+}
+
+// The mapping is owned by the pool, and only accessed through `&mut self`
+unsafe impl Send for MemPool {}
+unsafe impl Sync for MemPool {}
+
+impl MemPool {
+    /// Create a new pool of `size` bytes
+    ///
+    /// The size must be greater than zero.
+    pub fn new(shm: &wl_shm::WlShm, size: usize) -> Result<MemPool, ShmError> {
+        let len = NonZeroUsize::new(size).ok_or(ShmError::OutOfBounds)?;
+        let size = i32::try_from(size).map_err(|_| ShmError::OutOfBounds)?;
+        let file = unsafe { File::from_raw_fd(create_shm_fd()?) };
+        file.set_len(len.get() as u64)?;
+        let map = map_file(&file, len)?;
+
+        let pool = match shm.send_constructor(
+            wl_shm::Request::CreatePool { fd: file.as_fd(), size },
+            Arc::new(PoolData),
+        ) {
+            Ok(pool) => pool,
+            Err(e) => {
+                let _ = unsafe { mman::munmap(map.as_ptr().cast(), len.get()) };
+                return Err(e.into());
+            }
+        };
+
+        Ok(MemPool { pool, file, map, len: len.get(), busy: Arc::new(AtomicUsize::new(0)) })
+    }
+
+    /// The `wl_shm_pool` of this pool
+    pub fn pool(&self) -> &wl_shm_pool::WlShmPool {
+        &self.pool
+    }
+
+    /// Current size of the pool, in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the pool is empty, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the compositor holds some of the buffers of this pool
+    ///
+    /// This is the case from the moment a buffer is attached to a surface with [`Buffer::attach_to()`]
+    /// until the compositor releases it.
+    pub fn is_used(&self) -> bool {
+        self.busy.load(Ordering::Acquire) > 0
+    }
+
+    /// Grow the pool to `size` bytes
+    ///
+    /// Sizes smaller than the current one are ignored, as a `wl_shm_pool` cannot shrink.
+    pub fn resize(&mut self, size: usize) -> Result<(), ShmError> {
+        if size <= self.len {
+            return Ok(());
+        }
+        let new_size = i32::try_from(size).map_err(|_| ShmError::OutOfBounds)?;
+        self.file.set_len(size as u64)?;
+        // the new mapping is created first, so that the pool stays valid if it fails
+        let map = map_file(&self.file, NonZeroUsize::new(size).unwrap())?;
+        let _ = unsafe { mman::munmap(self.map.as_ptr().cast(), self.len) };
+        self.map = map;
+        self.len = size;
+        self.pool.resize(new_size);
+        Ok(())
+    }
+
+    /// Access the whole contents of the pool
+    ///
+    /// This gives access to the memory of all the buffers, including the ones the compositor is
+    /// currently reading. Prefer [`MemPool::buffer_contents()`] to draw into a single buffer.
+    pub fn mmap(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_ptr(), self.len) }
+    }
+
+    /// Create a buffer from the memory of this pool, starting at `offset` bytes
+    ///
+    /// Fails with [`ShmError::OutOfBounds`] if the image does not fit in the pool.
+    pub fn create_buffer(
+        &mut self,
+        offset: usize,
+        width: i32,
+        height: i32,
+        stride: i32,
+        format: wl_shm::Format,
+    ) -> Result<Buffer, ShmError> {
+        if width <= 0 || height <= 0 || stride < width {
+            return Err(ShmError::OutOfBounds);
+        }
+        let len = (stride as usize) * (height as usize);
+        if offset.checked_add(len).map_or(true, |end| end > self.len) {
+            return Err(ShmError::OutOfBounds);
+        }
+
+        let data =
+            Arc::new(BufferData { busy: AtomicBool::new(false), pool_busy: self.busy.clone() });
+        let buffer = self.pool.send_constructor(
+            wl_shm_pool::Request::CreateBuffer {
+                offset: offset as i32,
+                width,
+                height,
+                stride,
+                format: WEnum::Value(format),
+            },
+            data.clone(),
+        )?;
+        Ok(Buffer { buffer, offset, len, data })
+    }
+
+    /// Access the contents of a buffer created from this pool
+    ///
+    /// Returns `None` if the buffer belongs to another pool, or if the compositor has not released it
+    /// yet.
+    pub fn buffer_contents(&mut self, buffer: &Buffer) -> Option<&mut [u8]> {
+        if !Arc::ptr_eq(&self.busy, &buffer.data.pool_busy) || buffer.is_busy() {
+            return None;
+        }
+        Some(&mut self.mmap()[buffer.offset..buffer.offset + buffer.len])
+    }
+}
+
+impl Drop for MemPool {
+    fn drop(&mut self) {
+        self.pool.destroy();
+        let _ = unsafe { mman::munmap(self.map.as_ptr().cast(), self.len) };
+    }
+}
+
+/// A `wl_buffer` created from a [`MemPool`]
+///
+/// The `wl_buffer` is destroyed when this value is dropped.
+#[derive(Debug)]
+pub struct Buffer {
+    buffer: wl_buffer::WlBuffer,
+    offset: usize,
+    len: usize,
+    data: Arc<BufferData>,
+}
+
+impl Buffer {
+    /// The `wl_buffer` of this buffer
+    pub fn wl_buffer(&self) -> &wl_buffer::WlBuffer {
+        &self.buffer
+    }
+
+    /// Offset of the buffer in its pool, in bytes
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Size of the buffer, in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the size of the buffer is zero, which is never the case
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the compositor holds this buffer
+    pub fn is_busy(&self) -> bool {
+        self.data.busy.load(Ordering::Acquire)
+    }
+
+    /// Attach this buffer to a surface, and mark it as busy until the compositor releases it
+    ///
+    /// The new contents of the surface still need to be committed.
+    pub fn attach_to(&self, surface: &wl_surface::WlSurface) {
+        self.data.set_busy();
+        surface.attach(Some(&self.buffer), 0, 0);
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
+
+/// Two pools used in turn, one being drawn into while the compositor reads the other
+#[derive(Debug)]
+pub struct DoubleMemPool {
+    pools: [MemPool; 2],
+    next: usize,
+}
+
+impl DoubleMemPool {
+    /// Create two pools of `size` bytes each
+    pub fn new(shm: &wl_shm::WlShm, size: usize) -> Result<DoubleMemPool, ShmError> {
+        Ok(DoubleMemPool { pools: [MemPool::new(shm, size)?, MemPool::new(shm, size)?], next: 0 })
+    }
+
+    /// Get a pool none of whose buffers are held by the compositor
+    ///
+    /// The two pools are returned alternately while they are both free. Returns `None` if the
+    /// compositor holds buffers of both pools.
+    pub fn pool(&mut self) -> Option<&mut MemPool> {
+        let index =
+            [self.next, 1 - self.next].iter().copied().find(|&i| !self.pools[i].is_used())?;
+        self.next = 1 - index;
+        Some(&mut self.pools[index])
+    }
+}
+
+#[derive(Debug)]
+struct BufferData {
+    busy: AtomicBool,
+    pool_busy: Arc<AtomicUsize>,
+}
+
+impl BufferData {
+    fn set_busy(&self) {
+        if !self.busy.swap(true, Ordering::AcqRel) {
+            self.pool_busy.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    fn set_released(&self) {
+        if self.busy.swap(false, Ordering::AcqRel) {
+            self.pool_busy.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl ObjectData for BufferData {
+    fn event(
+        self: Arc<Self>,
+        _: &Backend,
+        _: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        // wl_buffer.release is the only event of a wl_buffer
+        self.set_released();
+        None
+    }
+
+    fn destroyed(&self, _: ObjectId) {
+        self.set_released();
+    }
+}
+
+// wl_shm_pool has no events
+struct PoolData;
+
+impl ObjectData for PoolData {
+    fn event(
+        self: Arc<Self>,
+        _: &Backend,
+        _: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        None
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
+
+fn map_file(file: &File, len: NonZeroUsize) -> Result<NonNull<u8>, ShmError> {
+    use std::os::unix::io::AsRawFd;
+    let ptr = unsafe {
+        mman::mmap(
+            None,
+            len,
+            mman::ProtFlags::PROT_READ | mman::ProtFlags::PROT_WRITE,
+            mman::MapFlags::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )?
+    };
+    Ok(NonNull::new(ptr.cast()).expect("mmap returned a null pointer"))
+}
+
+fn create_shm_fd() -> io::Result<RawFd> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    loop {
+        match nix::sys::memfd::memfd_create(
+            std::ffi::CStr::from_bytes_with_nul(b"wayland-client-rs\0").unwrap(),
+            nix::sys::memfd::MemFdCreateFlag::MFD_CLOEXEC,
+        ) {
+            Ok(fd) => return Ok(fd),
+            Err(Errno::EINTR) => continue,
+            Err(Errno::ENOSYS) => break,
+            Err(errno) => return Err(errno.into()),
+        }
+    }
+
+    // Fallback to an unlinked POSIX shared memory object
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let name = format!(
+            "/wayland-client-rs-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        match mman::shm_open(
+            name.as_str(),
+            fcntl::OFlag::O_CREAT
+                | fcntl::OFlag::O_EXCL
+                | fcntl::OFlag::O_RDWR
+                | fcntl::OFlag::O_CLOEXEC,
+            stat::Mode::S_IRUSR | stat::Mode::S_IWUSR,
+        ) {
+            Ok(fd) => {
+                let _ = mman::shm_unlink(name.as_str());
+                return Ok(fd);
+            }
+            Err(Errno::EEXIST) | Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno.into()),
+        }
+    }
+}
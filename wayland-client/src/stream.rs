@@ -0,0 +1,279 @@
+//! Streams of the events of objects
+//!
+//! Rather than having its events dispatched to the [`Dispatch`](crate::Dispatch) implementations
+//! of an event queue, an object can deliver them to an [`EventStream`], obtained with
+//! [`Proxy::event_stream()`]. The events are then consumed from an async task, along with the
+//! combinators of the `futures` crates:
+//!
+//! ```no_run
+//! # async fn run(conn: wayland_client::Connection, surface: wayland_client::protocol::wl_surface::WlSurface) {
+//! use futures_util::StreamExt;
+//! use wayland_client::{protocol::wl_surface, Proxy};
+//!
+//! let mut events = surface.event_stream();
+//! while let Some(event) = events.next().await {
+//!     if let wl_surface::Event::Enter { output } = event {
+//!         println!("Entered {}", output.id());
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! The events are pushed to the stream as soon as they are read from the socket, by whichever
+//! thread or task reads it, for example with
+//! [`EventQueue::read_events_async()`](crate::EventQueue::read_events_async). Event queues are
+//! not involved, so that the stream receives events even if its object's queue is never dispatched.
+//!
+//! The objects created by the events of an object with an event stream, like the `wl_data_offer`
+//! of a `wl_data_device`, keep their own events until they are given an event stream as well.
+//!
+//! This module is only available with the `async` cargo feature.
+
+use std::{
+    collections::VecDeque,
+    os::unix::io::OwnedFd,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+use wayland_backend::{
+    client::{Backend, ObjectData, ObjectId},
+    protocol::{Argument, Message},
+};
+
+use crate::{Connection, Proxy};
+
+/// A stream of the events of an object
+///
+/// The stream ends once the object is destroyed and all its events were consumed. Dropping the
+/// stream discards the later events of the object.
+///
+/// A stream created by [`Proxy::event_stream()`] buffers all the events until they are consumed,
+/// while one created by [`Proxy::event_stream_bounded()`] only keeps the most recent ones, as the
+/// server cannot be made to wait: older events are discarded and counted by
+/// [`lagged()`](EventStream::lagged).
+#[derive(Debug)]
+pub struct EventStream<I: Proxy> {
+    data: Arc<StreamData<I>>,
+}
+
+impl<I: Proxy + 'static> EventStream<I>
+where
+    I::Event: Send,
+{
+    pub(crate) fn attach(proxy: &I, capacity: Option<usize>) -> Self {
+        let data = Arc::new(StreamData {
+            inner: Mutex::new(StreamInner {
+                events: VecDeque::new(),
+                capacity,
+                lagged: 0,
+                waker: None,
+                closed: false,
+                dropped: false,
+            }),
+        });
+        let stream = Self { data: data.clone() };
+
+        let backend = match proxy.backend().upgrade() {
+            Some(backend) => backend,
+            None => {
+                data.close();
+                return stream;
+            }
+        };
+        let old_data = match backend.get_data(proxy.id()) {
+            Ok(old_data) => old_data,
+            Err(_) => {
+                data.close();
+                return stream;
+            }
+        };
+        // take over the events kept by a child object, forwarding the later ones until the
+        // backend delivers them to the stream directly
+        if let Some(child) = old_data.downcast_ref::<ChildData>() {
+            let forward = ChildState::Forward(data.clone());
+            if let ChildState::Pending { messages, destroyed } =
+                std::mem::replace(&mut *child.state.lock().unwrap(), forward)
+            {
+                let conn = Connection::from_backend(backend.clone());
+                for msg in messages {
+                    data.push(&conn, msg);
+                }
+                if destroyed {
+                    data.close();
+                }
+            }
+        }
+        if backend.set_data(proxy.id(), data.clone()).is_err() {
+            data.close();
+        }
+        stream
+    }
+}
+
+impl<I: Proxy> EventStream<I> {
+    /// The number of events discarded because the buffer of the stream was full
+    pub fn lagged(&self) -> u64 {
+        self.data.inner.lock().unwrap().lagged
+    }
+}
+
+impl<I: Proxy> Stream for EventStream<I> {
+    type Item = I::Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<I::Event>> {
+        let mut inner = self.data.inner.lock().unwrap();
+        if let Some(event) = inner.events.pop_front() {
+            Poll::Ready(Some(event))
+        } else if inner.closed {
+            Poll::Ready(None)
+        } else {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<I: Proxy> Drop for EventStream<I> {
+    fn drop(&mut self) {
+        let mut inner = self.data.inner.lock().unwrap();
+        inner.dropped = true;
+        inner.events.clear();
+    }
+}
+
+struct StreamData<I: Proxy> {
+    inner: Mutex<StreamInner<I>>,
+}
+
+struct StreamInner<I: Proxy> {
+    events: VecDeque<I::Event>,
+    capacity: Option<usize>,
+    lagged: u64,
+    waker: Option<Waker>,
+    closed: bool,
+    dropped: bool,
+}
+
+impl<I: Proxy> StreamData<I> {
+    fn push(&self, conn: &Connection, msg: Message<ObjectId, OwnedFd>) {
+        let event = match I::parse_event(conn, msg) {
+            Ok((_, event)) => event,
+            Err(err) => {
+                crate::log_warn!("Dropping an event of an event stream: {}", err);
+                return;
+            }
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if inner.dropped {
+            return;
+        }
+        if inner.capacity == Some(inner.events.len()) {
+            inner.lagged += 1;
+            if inner.events.pop_front().is_none() {
+                // a capacity of zero keeps no event at all
+                return;
+            }
+        }
+        inner.events.push_back(event);
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<I: Proxy + 'static> ObjectData for StreamData<I>
+where
+    I::Event: Send,
+{
+    fn event(
+        self: Arc<Self>,
+        backend: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        let child = creates_child(&msg).then(ChildData::pending);
+        self.push(&Connection::from_backend(backend.clone()), msg);
+        child
+    }
+
+    fn destroyed(&self, _: ObjectId) {
+        self.close();
+    }
+}
+
+impl<I: Proxy> std::fmt::Debug for StreamData<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("StreamData")
+            .field("pending", &inner.events.len())
+            .field("capacity", &inner.capacity)
+            .field("lagged", &inner.lagged)
+            .field("closed", &inner.closed)
+            .finish()
+    }
+}
+
+fn creates_child(msg: &Message<ObjectId, OwnedFd>) -> bool {
+    msg.args.iter().any(|arg| matches!(arg, Argument::NewId(id) if !id.is_null()))
+}
+
+// The data of the objects created by the events of an object with an event stream, keeping their
+// events until they get a stream of their own
+struct ChildData {
+    state: Mutex<ChildState>,
+}
+
+enum ChildState {
+    Pending { messages: Vec<Message<ObjectId, OwnedFd>>, destroyed: bool },
+    Forward(Arc<dyn ObjectData>),
+}
+
+impl ChildData {
+    fn pending() -> Arc<dyn ObjectData> {
+        Arc::new(Self {
+            state: Mutex::new(ChildState::Pending { messages: Vec::new(), destroyed: false }),
+        })
+    }
+}
+
+impl ObjectData for ChildData {
+    fn event(
+        self: Arc<Self>,
+        backend: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        // the lock is held while forwarding to keep the events in order with those taken over
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            ChildState::Pending { ref mut messages, .. } => {
+                let child = creates_child(&msg).then(ChildData::pending);
+                messages.push(msg);
+                child
+            }
+            ChildState::Forward(ref data) => data.clone().event(backend, msg),
+        }
+    }
+
+    fn destroyed(&self, object_id: ObjectId) {
+        match *self.state.lock().unwrap() {
+            ChildState::Pending { ref mut destroyed, .. } => *destroyed = true,
+            ChildState::Forward(ref data) => data.destroyed(object_id),
+        }
+    }
+}
+
+impl std::fmt::Debug for ChildData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChildData").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,206 @@
+//! Batching of touch input into frames
+//!
+//! The events of a `wl_touch` come in groups ended by a `frame` event: the touch points going down,
+//! moving and going up at the same time are sent one event after the other, and only make sense
+//! together. A [`Touch`] tracks the touch points of a `wl_touch` and gathers the events of each group
+//! into a [`TouchFrame`], which describes the changes of all the points at once. The [`Touch`] does not
+//! receive events by itself: feed it the events given to your `Dispatch<WlTouch, _>` implementation
+//! with [`Touch::handle_event()`].
+//!
+//! When the compositor takes over the touch sequence, for example for a global gesture, it cancels
+//! all the touch points instead. The client must then forget them, as they will neither move nor go up.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use wayland_client::{
+//!     protocol::wl_touch,
+//!     touch::{Touch, TouchEvent},
+//!     Connection, Dispatch, QueueHandle,
+//! };
+//!
+//! struct State {
+//!     touch: Touch,
+//! }
+//!
+//! impl Dispatch<wl_touch::WlTouch, ()> for State {
+//!     fn event(
+//!         state: &mut Self,
+//!         _: &wl_touch::WlTouch,
+//!         event: wl_touch::Event,
+//!         _: &(),
+//!         _: &Connection,
+//!         _: &QueueHandle<Self>,
+//!     ) {
+//!         match state.touch.handle_event(event) {
+//!             Some(TouchEvent::Frame(frame)) => {
+//!                 for update in frame.updates {
+//!                     println!("Point {} moved by {:?}", update.point.id, update.delta);
+//!                 }
+//!             }
+//!             Some(TouchEvent::Cancel(points)) => println!("{} points cancelled", points.len()),
+//!             None => {}
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::protocol::{wl_surface::WlSurface, wl_touch};
+
+/// A touch point
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchPoint {
+    /// The id of the point, unique among the points down
+    pub id: i32,
+    /// The surface the point went down on, which receives all its events
+    pub surface: WlSurface,
+    /// The position of the point, in surface-local coordinates
+    pub position: (f64, f64),
+    /// The length of the major and minor axes of the contact ellipse, if the compositor sent them
+    pub shape: Option<(f64, f64)>,
+    /// The angle between the major axis of the contact ellipse and the y axis, in degrees, if the
+    /// compositor sent it
+    pub orientation: Option<f64>,
+}
+
+/// The changes of a touch point during a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchUpdate {
+    /// The state of the point at the end of the frame
+    pub point: TouchPoint,
+    /// The serial of the `down` event, if the point went down during the frame
+    pub down: Option<u32>,
+    /// The serial of the `up` event, if the point went up during the frame
+    pub up: Option<u32>,
+    /// The motion of the point during the frame
+    pub delta: (f64, f64),
+    /// The timestamp of the last event of the point, in milliseconds, or 0 if only its shape or
+    /// orientation changed
+    pub time: u32,
+}
+
+/// The changes of the touch points between two `frame` events
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TouchFrame {
+    /// The points which changed, in the order of their first event
+    pub updates: Vec<TouchUpdate>,
+}
+
+/// A batch of touch input, see [`Touch::handle_event()`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TouchEvent {
+    /// The events of a frame
+    Frame(TouchFrame),
+    /// The compositor cancelled the touch points, which are given as of the last frame
+    Cancel(Vec<TouchPoint>),
+}
+
+/// The touch points of a `wl_touch`
+#[derive(Debug, Default)]
+pub struct Touch {
+    // the points down as of the last frame
+    points: Vec<TouchPoint>,
+    // the changes of the frame being received
+    pending: Vec<TouchUpdate>,
+}
+
+impl Touch {
+    /// Create the state of a `wl_touch` without any touch point
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The touch points down as of the last frame
+    pub fn points(&self) -> &[TouchPoint] {
+        &self.points
+    }
+
+    /// Process an event of the `wl_touch`
+    ///
+    /// The events are accumulated until the next `frame` or `cancel` event, which returns them. The
+    /// events of the points which are not down are ignored.
+    pub fn handle_event(&mut self, event: wl_touch::Event) -> Option<TouchEvent> {
+        match event {
+            wl_touch::Event::Down { serial, time, surface, id, x, y } => {
+                self.pending.push(TouchUpdate {
+                    point: TouchPoint {
+                        id,
+                        surface,
+                        position: (x, y),
+                        shape: None,
+                        orientation: None,
+                    },
+                    down: Some(serial),
+                    up: None,
+                    delta: (0., 0.),
+                    time,
+                });
+            }
+            wl_touch::Event::Up { serial, time, id } => {
+                if let Some(update) = self.update(id) {
+                    update.up = Some(serial);
+                    update.time = time;
+                }
+            }
+            wl_touch::Event::Motion { time, id, x, y } => {
+                if let Some(update) = self.update(id) {
+                    let (old_x, old_y) = update.point.position;
+                    update.delta.0 += x - old_x;
+                    update.delta.1 += y - old_y;
+                    update.point.position = (x, y);
+                    update.time = time;
+                }
+            }
+            wl_touch::Event::Shape { id, major, minor } => {
+                if let Some(update) = self.update(id) {
+                    update.point.shape = Some((major, minor));
+                }
+            }
+            wl_touch::Event::Orientation { id, orientation } => {
+                if let Some(update) = self.update(id) {
+                    update.point.orientation = Some(orientation);
+                }
+            }
+            wl_touch::Event::Frame => {
+                let updates = std::mem::take(&mut self.pending);
+                for update in &updates {
+                    let id = update.point.id;
+                    self.points.retain(|point| point.id != id);
+                    if update.up.is_none() {
+                        self.points.push(update.point.clone());
+                    }
+                }
+                return Some(TouchEvent::Frame(TouchFrame { updates }));
+            }
+            wl_touch::Event::Cancel => {
+                self.pending.clear();
+                return Some(TouchEvent::Cancel(std::mem::take(&mut self.points)));
+            }
+        }
+        None
+    }
+
+    // the pending changes of a point down, created from its last state if it has none yet
+    fn update(&mut self, id: i32) -> Option<&mut TouchUpdate> {
+        // an id may be reused once its point went up, even during the same frame
+        let index = match self.pending.iter().rposition(|u| u.point.id == id && u.up.is_none()) {
+            Some(index) => index,
+            None => {
+                let point = self.points.iter().find(|point| point.id == id)?.clone();
+                if self.pending.iter().any(|u| u.point.id == id) {
+                    // the point already went up during this frame
+                    return None;
+                }
+                self.pending.push(TouchUpdate {
+                    point,
+                    down: None,
+                    up: None,
+                    delta: (0., 0.),
+                    time: 0,
+                });
+                self.pending.len() - 1
+            }
+        };
+        Some(&mut self.pending[index])
+    }
+}
@@ -0,0 +1,70 @@
+//! A minimal built-in cursor, used when no theme provides the requested one
+
+use xcursor::parser::Image;
+
+/// A classic arrow, `#` is the outline, `.` the fill and spaces are transparent
+const ARROW: [&str; 19] = [
+    "#           ",
+    "##          ",
+    "#.#         ",
+    "#..#        ",
+    "#...#       ",
+    "#....#      ",
+    "#.....#     ",
+    "#......#    ",
+    "#.......#   ",
+    "#........#  ",
+    "#.........# ",
+    "#......#####",
+    "#...#..#    ",
+    "#..##..#    ",
+    "#.#  #..#   ",
+    "##   #..#   ",
+    "#     #..#  ",
+    "      #..#  ",
+    "       ##   ",
+];
+
+/// The nominal size the arrow was drawn for
+const ARROW_SIZE: u32 = 24;
+
+/// Render the fallback arrow for the given nominal size
+///
+/// The arrow is scaled by an integer factor, so that its dimensions stay divisible by the buffer
+/// scale of HiDPI themes.
+pub(crate) fn images(size: u32) -> Vec<Image> {
+    let factor = ((size + ARROW_SIZE / 2) / ARROW_SIZE).max(1);
+    let width = ARROW[0].len() as u32 * factor;
+    let height = ARROW.len() as u32 * factor;
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in ARROW.iter() {
+        let mut line = Vec::with_capacity((width * 4) as usize);
+        for c in row.bytes() {
+            // Pixels are stored in the byte order of the little-endian ARGB8888 shm format
+            let pixel = match c {
+                b'#' => [0x00, 0x00, 0x00, 0xff],
+                b'.' => [0xff, 0xff, 0xff, 0xff],
+                _ => [0x00, 0x00, 0x00, 0x00],
+            };
+            for _ in 0..factor {
+                line.extend_from_slice(&pixel);
+            }
+        }
+        for _ in 0..factor {
+            pixels.extend_from_slice(&line);
+        }
+    }
+
+    let pixels_argb = pixels.chunks(4).flat_map(|p| [p[3], p[0], p[1], p[2]]).collect();
+    vec![Image {
+        size: ARROW_SIZE * factor,
+        width,
+        height,
+        xhot: 0,
+        yhot: 0,
+        delay: 0,
+        pixels_rgba: pixels,
+        pixels_argb,
+    }]
+}
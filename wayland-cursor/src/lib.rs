@@ -14,6 +14,9 @@
 //! you with the means of querying which frame of the animation should be displayed at what time, as
 //! well as handles to the buffers containing these frames, to attach them to a wayland surface.
 //!
+//! Alternatively, the [`CursorManager`] sets the cursor of pointers by name, using the cursor-shape
+//! protocol when the compositor supports it and a theme loaded by this crate otherwise.
+//!
 //! # Example
 //!
 //! ```
@@ -46,12 +49,12 @@
 //! ```
 
 use std::env;
-use std::fs::File;
-use std::io::{Error as IoError, Read, Result as IoResult, Seek, SeekFrom, Write};
-use std::ops::{Deref, Index};
-use std::os::unix::io::{AsFd, FromRawFd, OwnedFd, RawFd};
+use std::io::{Error as IoError, Result as IoResult};
+use std::ops::{Deref, Index, Range};
+use std::os::unix::io::{OwnedFd, RawFd};
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use nix::errno::Errno;
 use nix::fcntl;
@@ -62,23 +65,36 @@ use {nix::sys::memfd, std::ffi::CStr};
 
 use wayland_client::backend::{InvalidId, ObjectData, WeakBackend};
 use wayland_client::protocol::wl_buffer::WlBuffer;
-use wayland_client::protocol::wl_shm::{self, Format, WlShm};
-use wayland_client::protocol::wl_shm_pool::{self, WlShmPool};
-use wayland_client::{Connection, Proxy, WEnum};
+use wayland_client::protocol::wl_shm::WlShm;
+use wayland_client::Connection;
 
 use xcursor::parser as xparser;
-use xcursor::CursorTheme as XCursorTheme;
 use xparser::Image as XCursorImage;
 
+#[cfg(feature = "fallback_theme")]
+mod fallback;
+mod loader;
+mod manager;
+mod pool;
+mod theme;
+
+use theme::Source;
+
+pub use loader::{CursorLoader, DecodedCursor};
+pub use manager::CursorManager;
+pub use pool::CursorPool;
+/// The cursor shapes of the cursor-shape protocol, used by the [`CursorManager`]
+pub use wayland_protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape as CursorShape;
+
 /// Represents a cursor theme loaded from the system.
 #[derive(Debug)]
 pub struct CursorTheme {
     name: String,
+    source: Source,
     cursors: Vec<Cursor>,
     size: u32,
-    pool: WlShmPool,
-    pool_size: i32,
-    file: File,
+    scale: u32,
+    pool: CursorPool,
     backend: WeakBackend,
 }
 
@@ -120,6 +136,64 @@ impl CursorTheme {
         Self::load_from_name(conn, shm, name, size)
     }
 
+    /// Load a cursor theme for an output with the given scale factor, ignoring the system defaults.
+    ///
+    /// `size` is the nominal cursor size in surface-local coordinates. The cursor images are loaded
+    /// for `size * scale` pixels, picking the nearest size available in the theme. The buffers of
+    /// this theme must then be attached to a surface whose buffer scale is [`scale()`](Self::scale),
+    /// so that the cursor appears with the expected size and stays crisp.
+    pub fn load_for_scale(
+        conn: &Connection,
+        shm: WlShm,
+        name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Result<Self, InvalidId> {
+        let scale = scale.max(1);
+        let mut theme = Self::load_from_name(conn, shm, name, size.saturating_mul(scale))?;
+        theme.scale = scale;
+        Ok(theme)
+    }
+
+    /// Create a new cursor theme from explicit search directories, ignoring the system defaults.
+    ///
+    /// The theme `name` and the themes it inherits from are only looked up in `search_paths`, in
+    /// order, rather than in the directories given by the `XCURSOR_PATH` environment variable. Each
+    /// search path is expected to contain theme directories, for example `/usr/share/icons`.
+    ///
+    /// This is useful to load themes bundled with an application, or to get deterministic results
+    /// regardless of the environment.
+    pub fn load_from_search_paths(
+        conn: &Connection,
+        shm: WlShm,
+        name: &str,
+        size: u32,
+        search_paths: Vec<PathBuf>,
+    ) -> Result<Self, InvalidId> {
+        let mut theme = Self::load_from_name(conn, shm, name, size)?;
+        theme.source = Source::SearchPaths(search_paths);
+        Ok(theme)
+    }
+
+    /// Create a new cursor theme from the contents of Xcursor files, without reading the filesystem
+    ///
+    /// `files` associates the name of each cursor with the contents of its Xcursor file. This is
+    /// useful on embedded or immutable systems where no theme is installed, or to bundle a theme in
+    /// the binary with `include_bytes!()`. The files are only decoded when the cursors are requested,
+    /// so that cursors which are not provided or fail to decode are reported as missing by
+    /// [`get_cursor()`](Self::get_cursor).
+    pub fn load_from_memory<N: Into<String>>(
+        conn: &Connection,
+        shm: WlShm,
+        files: impl IntoIterator<Item = (N, Vec<u8>)>,
+        size: u32,
+    ) -> Result<Self, InvalidId> {
+        let files = files.into_iter().map(|(name, data)| (name.into(), data)).collect();
+        let mut theme = Self::load_from_name(conn, shm, "", size)?;
+        theme.source = Source::Memory(Arc::new(files));
+        Ok(theme)
+    }
+
     /// Create a new cursor theme, ignoring the system defaults.
     pub fn load_from_name(
         conn: &Connection,
@@ -127,88 +201,161 @@ impl CursorTheme {
         name: &str,
         size: u32,
     ) -> Result<Self, InvalidId> {
-        // Set some minimal cursor size to hold it. We're not using `size` argument for that,
-        // because the actual size that we'll use depends on theme sizes available on a system.
-        // The minimal size covers most common minimal theme size, which is 16.
-        const INITIAL_POOL_SIZE: i32 = 16 * 16 * 4;
-
-        //  Create shm.
-        let mem_fd = create_shm_fd().expect("Shm fd allocation failed");
-        let mut file = unsafe { File::from_raw_fd(mem_fd) };
-        file.set_len(INITIAL_POOL_SIZE as u64).expect("Failed to set buffer length");
-
-        // Ensure that we have the same we requested.
-        file.write_all(&[0; INITIAL_POOL_SIZE as usize]).expect("Write to shm fd failed");
-        // Flush to ensure the compositor has access to the buffer when it tries to map it.
-        file.flush().expect("Flush on shm fd failed");
-
-        let pool_id = conn.send_request(
-            &shm,
-            wl_shm::Request::CreatePool { fd: file.as_fd(), size: INITIAL_POOL_SIZE },
-            Some(Arc::new(IgnoreObjectData)),
-        )?;
-        let pool = WlShmPool::from_id(conn, pool_id)?;
-
-        let name = String::from(name);
-
-        Ok(Self {
-            name,
-            file,
+        let pool = CursorPool::new(conn, &shm)?;
+        Ok(Self::load_with_pool(conn, pool, name, size))
+    }
+
+    /// Create a new cursor theme storing its images in an existing pool, ignoring the system defaults.
+    ///
+    /// This allows several themes, typically the same theme loaded at different sizes for each
+    /// output scale, to share a single `wl_shm_pool`. The pool of an existing theme can be retrieved
+    /// with [`pool()`](Self::pool).
+    ///
+    /// As with the other constructors, cursors are only read from disk and written into the pool
+    /// the first time they are requested with [`get_cursor()`](Self::get_cursor).
+    pub fn load_with_pool(conn: &Connection, pool: CursorPool, name: &str, size: u32) -> Self {
+        Self {
+            name: String::from(name),
+            source: Source::System,
             size,
+            scale: 1,
             pool,
-            pool_size: INITIAL_POOL_SIZE,
             cursors: Vec::new(),
             backend: conn.backend().downgrade(),
-        })
+        }
+    }
+
+    /// The pool storing the images of this theme
+    pub fn pool(&self) -> &CursorPool {
+        &self.pool
+    }
+
+    /// The scale factor this theme was loaded for
+    ///
+    /// This is the buffer scale to set on the surfaces displaying cursors of this theme. It is `1`
+    /// unless the theme was loaded with [`load_for_scale()`](Self::load_for_scale).
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// The size, in pixels, of the cursor images this theme loads
+    ///
+    /// The actual size of the images is the nearest one available in the theme, see
+    /// [`CursorImageBuffer::dimensions()`].
+    pub fn pixel_size(&self) -> u32 {
+        self.size
     }
 
     /// Retrieve a cursor from the theme.
     ///
     /// This method returns [`None`] if this cursor is not provided either by the theme, or by one of its parents.
+    ///
+    /// With the `fallback_theme` cargo feature, a built-in arrow is returned instead, so that a visible
+    /// cursor can be displayed even when no cursor theme is installed.
     pub fn get_cursor(&mut self, name: &str) -> Option<&Cursor> {
-        match self.cursors.iter().position(|cursor| cursor.name == name) {
+        self.get_cursor_with_size(name, self.size)
+    }
+
+    /// Retrieve a cursor from the theme, with the best images for an output scale factor
+    ///
+    /// The images are chosen for `scale` times the nominal size of the theme, that is the size it was
+    /// loaded with divided by its [`scale()`](Self::scale). The same theme can thus provide the
+    /// cursors of all the outputs: the buffers of the returned cursor must be attached to a surface
+    /// whose buffer scale is `scale`. A cursor is loaded only once for each size, in the pool of the
+    /// theme.
+    pub fn get_cursor_for_scale(&mut self, name: &str, scale: u32) -> Option<&Cursor> {
+        let size = (self.size / self.scale).saturating_mul(scale.max(1));
+        self.get_cursor_with_size(name, size)
+    }
+
+    fn get_cursor_with_size(&mut self, name: &str, size: u32) -> Option<&Cursor> {
+        match self.cursors.iter().position(|cursor| cursor.name == name && cursor.size == size) {
             Some(i) => Some(&self.cursors[i]),
             None => {
-                let cursor = self.load_cursor(name, self.size)?;
-                self.cursors.push(cursor);
-                self.cursors.iter().last()
+                let loader = CursorLoader::new(&self.name, self.source.clone(), name, size);
+                self.insert_cursor(loader.decode()?)
             }
         }
     }
 
-    /// This function loads a cursor, parses it and pushes the images onto the shm pool.
+    /// Prepare the loading of a cursor, without reading it from disk
     ///
-    /// Keep in mind that if the cursor is already loaded, the function will make a duplicate.
-    fn load_cursor(&mut self, name: &str, size: u32) -> Option<Cursor> {
-        let conn = Connection::from_backend(self.backend.upgrade()?);
-        let icon_path = XCursorTheme::load(&self.name).load_icon(name)?;
-        let mut icon_file = File::open(icon_path).ok()?;
+    /// The cursor can then be decoded on any thread using [`CursorLoader::decode()`], and added to the
+    /// theme with [`insert_cursor()`](Self::insert_cursor). This splits the work of
+    /// [`get_cursor()`](Self::get_cursor), so that the main thread is not blocked while the cursor
+    /// files are read and parsed.
+    pub fn loader(&self, name: &str) -> CursorLoader {
+        CursorLoader::new(&self.name, self.source.clone(), name, self.size)
+    }
 
-        let mut buf = Vec::new();
-        let images = {
-            icon_file.read_to_end(&mut buf).ok()?;
-            xparser::parse_xcursor(&buf)?
-        };
+    /// Add a decoded cursor to the theme, writing its images in the pool
+    ///
+    /// If a cursor with the same name and size is already loaded, it is returned and `cursor` is
+    /// discarded. This method returns [`None`] if the connection to the compositor was closed.
+    pub fn insert_cursor(&mut self, cursor: DecodedCursor) -> Option<&Cursor> {
+        if let Some(i) = self
+            .cursors
+            .iter()
+            .position(|loaded| loaded.name == cursor.name && loaded.size == cursor.size)
+        {
+            return Some(&self.cursors[i]);
+        }
+
+        let conn = Connection::from_backend(self.backend.upgrade()?);
+        let cursor = Cursor::new(&conn, &cursor.name, self, &cursor.images, cursor.size);
+        self.cursors.push(cursor);
+        self.cursors.iter().last()
+    }
 
-        Some(Cursor::new(&conn, name, self, &images, size))
+    /// Remove a cursor from the theme, releasing its images
+    ///
+    /// The buffers of the cursor, at all the sizes it was loaded with, are destroyed and their space in
+    /// the pool is reused for cursors loaded later on. Calling [`get_cursor()`](Self::get_cursor) with
+    /// the same name loads it again.
+    ///
+    /// Any clone of the evicted [`Cursor`] must not be used afterwards, and its buffers should not be
+    /// attached to a surface anymore. Returns `false` if the cursor was not loaded.
+    pub fn evict(&mut self, name: &str) -> bool {
+        let mut evicted = false;
+        while let Some(i) = self.cursors.iter().position(|cursor| cursor.name == name) {
+            let cursor = self.cursors.remove(i);
+            self.release(cursor);
+            evicted = true;
+        }
+        evicted
     }
 
-    /// Grow the wl_shm_pool this theme is stored on.
+    /// Remove all loaded cursors from the theme
     ///
-    /// This method does nothing if the provided size is smaller or equal to the pool's current size.
-    fn grow(&mut self, size: i32) {
-        if size > self.pool_size {
-            self.file.set_len(size as u64).expect("Failed to set new buffer length");
-            self.pool.resize(size);
-            self.pool_size = size;
+    /// See [`evict()`](Self::evict).
+    pub fn evict_all(&mut self) {
+        for cursor in std::mem::take(&mut self.cursors) {
+            self.release(cursor);
+        }
+    }
+
+    fn release(&self, cursor: Cursor) {
+        for image in cursor.images {
+            image.buffer.destroy();
+            self.pool.release(image.chunk);
         }
     }
+
+    /// Names of the cursors currently loaded in this theme
+    ///
+    /// A cursor loaded at several sizes with [`get_cursor_for_scale()`](Self::get_cursor_for_scale)
+    /// appears once for each size.
+    pub fn loaded_cursors(&self) -> impl Iterator<Item = &str> {
+        self.cursors.iter().map(|cursor| cursor.name.as_str())
+    }
 }
 
 /// A cursor from a theme. Can contain several images if animated.
 #[derive(Debug, Clone)]
 pub struct Cursor {
     name: String,
+    /// The nominal size the images were chosen for
+    size: u32,
     images: Vec<CursorImageBuffer>,
     total_duration: u32,
 }
@@ -216,12 +363,11 @@ pub struct Cursor {
 impl Cursor {
     /// Construct a new Cursor.
     ///
-    /// Each of the provided images will be written into `theme`.
-    /// This will also grow `theme.pool` if necessary.
+    /// Each of the provided images will be written into the pool of `theme`.
     fn new(
         conn: &Connection,
         name: &str,
-        theme: &mut CursorTheme,
+        theme: &CursorTheme,
         images: &[XCursorImage],
         size: u32,
     ) -> Self {
@@ -235,7 +381,7 @@ impl Cursor {
             })
             .collect();
 
-        Self { total_duration, name: String::from(name), images }
+        Self { total_duration, name: String::from(name), size, images }
     }
 
     fn nearest_images(size: u32, images: &[XCursorImage]) -> impl Iterator<Item = &XCursorImage> {
@@ -252,8 +398,13 @@ impl Cursor {
     ///
     /// Time will wrap, so if for instance the cursor has an animation lasting 100ms,
     /// then calling this function with 5ms and 105ms as input gives the same output.
-    pub fn frame_and_duration(&self, mut millis: u32) -> FrameAndDuration {
-        millis %= self.total_duration;
+    ///
+    /// For cursors that are not animated, the first frame is returned with a duration of 0.
+    pub fn frame_and_duration(&self, millis: u32) -> FrameAndDuration {
+        if self.total_duration == 0 {
+            return FrameAndDuration { frame_index: 0, frame_duration: 0 };
+        }
+        let mut millis = millis % self.total_duration;
 
         let mut res = 0;
         for (i, img) in self.images.iter().enumerate() {
@@ -264,7 +415,38 @@ impl Cursor {
             millis -= img.delay;
         }
 
-        FrameAndDuration { frame_index: res, frame_duration: millis }
+        FrameAndDuration { frame_index: res, frame_duration: self.images[res].delay - millis }
+    }
+
+    /// The frame to show after `elapsed` time since the start of the animation
+    ///
+    /// This returns the image to display, along with the time remaining until the next frame
+    /// should be shown. The animation loops, and the remaining time is zero for cursors that are
+    /// not animated.
+    pub fn frame_at(&self, elapsed: Duration) -> (&CursorImageBuffer, Duration) {
+        let millis = match self.total_duration {
+            0 => 0,
+            total => (elapsed.as_millis() % u128::from(total)) as u32,
+        };
+        let frame = self.frame_and_duration(millis);
+        (&self.images[frame.frame_index], Duration::from_millis(frame.frame_duration.into()))
+    }
+
+    /// Iterate over the frames of this cursor
+    ///
+    /// Each frame is given as its image, its hotspot and the time for which it should be
+    /// displayed.
+    pub fn frames(&self) -> impl Iterator<Item = (&CursorImageBuffer, (u32, u32), Duration)> {
+        self.images
+            .iter()
+            .map(|image| (image, image.hotspot(), Duration::from_millis(image.delay.into())))
+    }
+
+    /// Total duration of the animation of this cursor
+    ///
+    /// This is zero for cursors that are not animated.
+    pub fn total_duration(&self) -> Duration {
+        Duration::from_millis(self.total_duration.into())
     }
 
     /// Total number of images forming this cursor animation
@@ -290,6 +472,7 @@ impl Index<usize> for Cursor {
 #[derive(Debug, Clone)]
 pub struct CursorImageBuffer {
     buffer: WlBuffer,
+    chunk: Range<i32>,
     delay: u32,
     xhot: u32,
     yhot: u32,
@@ -300,36 +483,15 @@ pub struct CursorImageBuffer {
 impl CursorImageBuffer {
     /// Construct a new CursorImageBuffer
     ///
-    /// This function appends the pixels of the image to the provided file,
+    /// This function writes the pixels of the image to the pool of the theme,
     /// and constructs a wl_buffer on that data.
-    fn new(conn: &Connection, theme: &mut CursorTheme, image: &XCursorImage) -> Self {
-        let buf = &image.pixels_rgba;
-        let offset = theme.file.seek(SeekFrom::End(0)).unwrap();
-
-        // Resize memory before writing to it to handle shm correctly.
-        let new_size = offset + buf.len() as u64;
-        theme.grow(new_size as i32);
-
-        theme.file.write_all(buf).unwrap();
-
-        let buffer_id = conn
-            .send_request(
-                &theme.pool,
-                wl_shm_pool::Request::CreateBuffer {
-                    offset: offset as i32,
-                    width: image.width as i32,
-                    height: image.height as i32,
-                    stride: (image.width * 4) as i32,
-                    format: WEnum::Value(Format::Argb8888),
-                },
-                Some(Arc::new(IgnoreObjectData)),
-            )
-            .unwrap();
-
-        let buffer = WlBuffer::from_id(conn, buffer_id).unwrap();
+    fn new(conn: &Connection, theme: &CursorTheme, image: &XCursorImage) -> Self {
+        let (buffer, chunk) =
+            theme.pool.create_buffer(conn, &image.pixels_rgba, image.width, image.height);
 
         Self {
             buffer,
+            chunk,
             delay: image.delay,
             xhot: image.xhot,
             yhot: image.yhot,
@@ -0,0 +1,100 @@
+//! Deferred decoding of cursors
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use xcursor::parser as xparser;
+use xcursor::CursorTheme as XCursorTheme;
+use xparser::Image as XCursorImage;
+
+#[cfg(feature = "fallback_theme")]
+use crate::fallback;
+use crate::theme::{self, Source};
+
+/// A cursor of a theme, not yet read from disk
+///
+/// This is obtained with [`CursorTheme::loader()`](crate::CursorTheme::loader) and holds everything
+/// needed to find and decode the cursor images, without borrowing the theme. It can be sent to a
+/// worker thread or to the executor of your choice, so that loading a large theme does not block the
+/// main thread. The resulting [`DecodedCursor`] is then given back to
+/// [`CursorTheme::insert_cursor()`](crate::CursorTheme::insert_cursor).
+#[derive(Debug, Clone)]
+pub struct CursorLoader {
+    theme: String,
+    source: Source,
+    name: String,
+    size: u32,
+}
+
+impl CursorLoader {
+    pub(crate) fn new(theme: &str, source: Source, name: &str, size: u32) -> Self {
+        Self { theme: theme.into(), source, name: name.into(), size }
+    }
+
+    /// Name of the cursor to load
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Nominal size, in pixels, of the images to load
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Path of the file containing the cursor, if it is provided by the theme or one of its parents
+    ///
+    /// This only looks the file up, without reading it. It is always [`None`] for the themes created
+    /// with [`CursorTheme::load_from_memory()`](crate::CursorTheme::load_from_memory).
+    pub fn path(&self) -> Option<PathBuf> {
+        match self.source {
+            Source::System => XCursorTheme::load(&self.theme).load_icon(&self.name),
+            Source::SearchPaths(ref search_paths) => {
+                theme::find_icon(search_paths, &self.theme, &self.name)
+            }
+            Source::Memory(_) => None,
+        }
+    }
+
+    /// Read and decode the images of the cursor
+    ///
+    /// This method returns [`None`] in the same cases as
+    /// [`CursorTheme::get_cursor()`](crate::CursorTheme::get_cursor).
+    pub fn decode(self) -> Option<DecodedCursor> {
+        let images = match self.decode_images() {
+            Some(images) => images,
+            #[cfg(feature = "fallback_theme")]
+            None => fallback::images(self.size),
+            #[cfg(not(feature = "fallback_theme"))]
+            None => return None,
+        };
+        Some(DecodedCursor { name: self.name, size: self.size, images })
+    }
+
+    fn decode_images(&self) -> Option<Vec<XCursorImage>> {
+        if let Source::Memory(ref files) = self.source {
+            return xparser::parse_xcursor(files.get(&self.name)?);
+        }
+
+        let mut icon_file = File::open(self.path()?).ok()?;
+
+        let mut buf = Vec::new();
+        icon_file.read_to_end(&mut buf).ok()?;
+        xparser::parse_xcursor(&buf)
+    }
+}
+
+/// The decoded images of a cursor, ready to be inserted in a theme
+#[derive(Debug, Clone)]
+pub struct DecodedCursor {
+    pub(crate) name: String,
+    pub(crate) size: u32,
+    pub(crate) images: Vec<XCursorImage>,
+}
+
+impl DecodedCursor {
+    /// Name of the cursor
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
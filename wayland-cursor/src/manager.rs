@@ -0,0 +1,336 @@
+//! Setting the cursor of pointers, through cursor-shape or a cursor theme
+
+use std::env;
+use std::sync::Arc;
+
+use wayland_client::backend::WeakBackend;
+use wayland_client::protocol::wl_compositor::{self, WlCompositor};
+use wayland_client::protocol::wl_pointer::WlPointer;
+use wayland_client::protocol::wl_shm::WlShm;
+use wayland_client::protocol::wl_surface::WlSurface;
+use wayland_client::{Connection, Proxy};
+use wayland_protocols::wp::cursor_shape::v1::client::{
+    wp_cursor_shape_device_v1::{Shape, WpCursorShapeDeviceV1},
+    wp_cursor_shape_manager_v1::{self, WpCursorShapeManagerV1},
+};
+
+use crate::{CursorTheme, IgnoreObjectData};
+
+/// Sets the cursor of pointers, preferring the cursor-shape protocol
+///
+/// When the compositor supports `wp_cursor_shape_manager_v1`, the cursors are only given by their
+/// [`Shape`] and the compositor draws them itself, with its own theme and at the right scale. Otherwise
+/// the manager loads a [`CursorTheme`] and displays the cursor images on a surface of its own, for each
+/// pointer.
+///
+/// The cursor of a pointer can only be set with the serial of its last `wl_pointer.enter` event, and
+/// must be set again every time the pointer enters a surface of the app. The manager takes care of it:
+/// give it the `enter` and `leave` events of the pointers with [`pointer_enter()`](Self::pointer_enter)
+/// and [`pointer_leave()`](Self::pointer_leave), and the cursor chosen with
+/// [`set_cursor()`](Self::set_cursor) is applied whenever the pointer is over the app.
+///
+/// The fallback theme is read from the `XCURSOR_THEME` and `XCURSOR_SIZE` environment variables,
+/// or is the `default` theme at size 24. Animated cursors are displayed with their first image.
+#[derive(Debug)]
+pub struct CursorManager {
+    backend: WeakBackend,
+    compositor: WlCompositor,
+    shm: WlShm,
+    shape_manager: Option<WpCursorShapeManagerV1>,
+    theme_name: String,
+    size: u32,
+    scale: u32,
+    theme: Option<CursorTheme>,
+    pointers: Vec<PointerCursor>,
+}
+
+#[derive(Debug)]
+struct PointerCursor {
+    pointer: WlPointer,
+    /// Serial of the last enter event, while the pointer is over a surface of the app
+    serial: Option<u32>,
+    /// `None` if the cursor is hidden
+    shape: Option<Shape>,
+    device: Option<WpCursorShapeDeviceV1>,
+    surface: Option<WlSurface>,
+}
+
+impl CursorManager {
+    /// Create a cursor manager
+    ///
+    /// `shape_manager` is the `wp_cursor_shape_manager_v1` global, if the compositor advertises it.
+    /// The compositor and shm globals are used for the fallback theme.
+    pub fn new(
+        conn: &Connection,
+        compositor: &WlCompositor,
+        shm: &WlShm,
+        shape_manager: Option<&WpCursorShapeManagerV1>,
+    ) -> Self {
+        let theme_name = env::var("XCURSOR_THEME").unwrap_or_else(|_| String::from("default"));
+        let size = env::var("XCURSOR_SIZE").ok().and_then(|var| var.parse().ok()).unwrap_or(24);
+        Self {
+            backend: conn.backend().downgrade(),
+            compositor: compositor.clone(),
+            shm: shm.clone(),
+            shape_manager: shape_manager.cloned(),
+            theme_name,
+            size,
+            scale: 1,
+            theme: None,
+            pointers: Vec::new(),
+        }
+    }
+
+    /// Whether the cursors are set with the cursor-shape protocol
+    pub fn uses_shape_protocol(&self) -> bool {
+        self.shape_manager.is_some()
+    }
+
+    /// Change the theme used when the compositor does not support cursor-shape
+    ///
+    /// `size` is the nominal cursor size, in surface-local coordinates.
+    pub fn set_theme(&mut self, name: &str, size: u32) {
+        self.theme_name = String::from(name);
+        self.size = size;
+        self.reload_theme();
+    }
+
+    /// Change the scale factor of the theme cursors
+    ///
+    /// This is typically the largest scale of the outputs the surfaces of the app are displayed on.
+    /// The cursor images are loaded from the same theme for the new scale when needed, and the images
+    /// loaded for the previous scales are kept for when it changes back. It has no effect with the
+    /// cursor-shape protocol, as the compositor scales the cursors itself, nor with a `wl_compositor`
+    /// older than version 3, which cannot scale surfaces.
+    pub fn set_scale(&mut self, scale: u32) {
+        let scale = if self.compositor.version() >= 3 { scale.max(1) } else { 1 };
+        if scale != self.scale {
+            self.scale = scale;
+            if self.shape_manager.is_none() {
+                for index in 0..self.pointers.len() {
+                    self.apply(index);
+                }
+            }
+        }
+    }
+
+    /// The scale factor of the theme cursors
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Process a `wl_pointer.enter` event
+    ///
+    /// The cursor previously chosen for this pointer is applied, or the default cursor for a new
+    /// pointer.
+    pub fn pointer_enter(&mut self, pointer: &WlPointer, serial: u32) {
+        let index = self.pointer_index(pointer);
+        self.pointers[index].serial = Some(serial);
+        self.apply(index);
+    }
+
+    /// Process a `wl_pointer.leave` event
+    pub fn pointer_leave(&mut self, pointer: &WlPointer) {
+        if let Some(cursor) = self.pointers.iter_mut().find(|cursor| &cursor.pointer == pointer) {
+            cursor.serial = None;
+        }
+    }
+
+    /// Set the cursor of a pointer, `None` hiding it
+    ///
+    /// The cursor is applied right away if the pointer is over a surface of the app, and each time it
+    /// enters one afterwards. With a theme, the Xcursor names of the shape are looked up in order, and
+    /// this method returns `false` if none of them is provided by the theme. The previous cursor is
+    /// then kept.
+    pub fn set_cursor(&mut self, pointer: &WlPointer, shape: Option<Shape>) -> bool {
+        let index = self.pointer_index(pointer);
+        let previous = std::mem::replace(&mut self.pointers[index].shape, shape);
+        if self.apply(index) {
+            true
+        } else {
+            self.pointers[index].shape = previous;
+            false
+        }
+    }
+
+    /// The cursor currently chosen for a pointer
+    pub fn cursor(&self, pointer: &WlPointer) -> Option<Shape> {
+        match self.pointers.iter().find(|cursor| &cursor.pointer == pointer) {
+            Some(cursor) => cursor.shape,
+            None => Some(Shape::Default),
+        }
+    }
+
+    /// Forget a pointer, typically before releasing it
+    ///
+    /// The objects created for this pointer are destroyed.
+    pub fn remove_pointer(&mut self, pointer: &WlPointer) {
+        if let Some(index) = self.pointers.iter().position(|cursor| &cursor.pointer == pointer) {
+            self.pointers.remove(index).destroy();
+        }
+    }
+
+    fn pointer_index(&mut self, pointer: &WlPointer) -> usize {
+        // drop the pointers destroyed without calling `remove_pointer()`
+        while let Some(index) = self.pointers.iter().position(|cursor| !cursor.pointer.is_alive()) {
+            self.pointers.remove(index).destroy();
+        }
+        match self.pointers.iter().position(|cursor| &cursor.pointer == pointer) {
+            Some(index) => index,
+            None => {
+                self.pointers.push(PointerCursor {
+                    pointer: pointer.clone(),
+                    serial: None,
+                    shape: Some(Shape::Default),
+                    device: None,
+                    surface: None,
+                });
+                self.pointers.len() - 1
+            }
+        }
+    }
+
+    fn reload_theme(&mut self) {
+        let mut old = match self.theme.take() {
+            Some(theme) => theme,
+            None => return,
+        };
+        if let Some(backend) = self.backend.upgrade() {
+            // reuse the pool of the previous theme
+            let conn = Connection::from_backend(backend);
+            self.theme = Some(CursorTheme::load_with_pool(
+                &conn,
+                old.pool().clone(),
+                &self.theme_name,
+                self.size,
+            ));
+        }
+        for index in 0..self.pointers.len() {
+            self.apply(index);
+        }
+        // the images of the previous theme are no longer attached to the cursor surfaces
+        old.evict_all();
+    }
+
+    /// Apply the cursor of a pointer if it is over the app, returns false if it is not in the theme
+    fn apply(&mut self, index: usize) -> bool {
+        let conn = match self.backend.upgrade() {
+            Some(backend) => Connection::from_backend(backend),
+            None => return true,
+        };
+        let cursor = &mut self.pointers[index];
+
+        let shape = match cursor.shape {
+            Some(shape) => shape,
+            None => {
+                if let Some(serial) = cursor.serial {
+                    cursor.pointer.set_cursor(serial, None, 0, 0);
+                }
+                return true;
+            }
+        };
+
+        if let Some(ref shape_manager) = self.shape_manager {
+            if let Some(serial) = cursor.serial {
+                if cursor.device.is_none() {
+                    cursor.device = conn
+                        .send_request(
+                            shape_manager,
+                            wp_cursor_shape_manager_v1::Request::GetPointer {
+                                pointer: cursor.pointer.clone(),
+                            },
+                            Some(Arc::new(IgnoreObjectData)),
+                        )
+                        .and_then(|id| WpCursorShapeDeviceV1::from_id(&conn, id))
+                        .ok();
+                }
+                if let Some(ref device) = cursor.device {
+                    device.set_shape(serial, shape);
+                }
+            }
+            return true;
+        }
+
+        let theme = match self.theme {
+            Some(ref mut theme) => theme,
+            None => match CursorTheme::load_from_name(
+                &conn,
+                self.shm.clone(),
+                &self.theme_name,
+                self.size,
+            ) {
+                Ok(theme) => self.theme.insert(theme),
+                Err(_) => return true,
+            },
+        };
+        let scale = self.scale;
+        let name = match shape
+            .xcursor_names()
+            .iter()
+            .find(|name| theme.get_cursor_for_scale(name, scale).is_some())
+        {
+            Some(name) => name,
+            None => return false,
+        };
+        // the cursor was loaded by the lookup above
+        let image = theme.get_cursor_for_scale(name, scale).unwrap()[0].clone();
+
+        let serial = match cursor.serial {
+            Some(serial) => serial,
+            None => return true,
+        };
+        if cursor.surface.is_none() {
+            cursor.surface = conn
+                .send_request(
+                    &self.compositor,
+                    wl_compositor::Request::CreateSurface {},
+                    Some(Arc::new(IgnoreObjectData)),
+                )
+                .and_then(|id| WlSurface::from_id(&conn, id))
+                .ok();
+        }
+        let surface = match cursor.surface {
+            Some(ref surface) => surface,
+            None => return true,
+        };
+
+        let (width, height) = image.dimensions();
+        let (hotspot_x, hotspot_y) = image.hotspot();
+        if surface.version() >= 3 {
+            surface.set_buffer_scale(scale as i32);
+        }
+        surface.attach(Some(&image), 0, 0);
+        if surface.version() >= 4 {
+            surface.damage_buffer(0, 0, width as i32, height as i32);
+        } else {
+            surface.damage(0, 0, (width / scale) as i32, (height / scale) as i32);
+        }
+        surface.commit();
+        cursor.pointer.set_cursor(
+            serial,
+            Some(surface),
+            (hotspot_x / scale) as i32,
+            (hotspot_y / scale) as i32,
+        );
+        true
+    }
+}
+
+impl PointerCursor {
+    fn destroy(self) {
+        if let Some(device) = self.device {
+            device.destroy();
+        }
+        if let Some(surface) = self.surface {
+            surface.destroy();
+        }
+    }
+}
+
+impl Drop for CursorManager {
+    fn drop(&mut self) {
+        for cursor in self.pointers.drain(..) {
+            cursor.destroy();
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Shared storage of the cursor images
+
+use std::fs::File;
+use std::io::Write;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsFd, FromRawFd};
+use std::sync::{Arc, Mutex};
+
+use wayland_client::backend::InvalidId;
+use wayland_client::protocol::wl_buffer::WlBuffer;
+use wayland_client::protocol::wl_shm::{self, Format, WlShm};
+use wayland_client::protocol::wl_shm_pool::{self, WlShmPool};
+use wayland_client::{Connection, Proxy, WEnum};
+
+use crate::{create_shm_fd, IgnoreObjectData};
+
+// Set some minimal cursor size to hold it. We're not using the theme size for that, because the
+// actual size that we'll use depends on theme sizes available on a system. The minimal size
+// covers most common minimal theme size, which is 16.
+const INITIAL_POOL_SIZE: i32 = 16 * 16 * 4;
+
+/// A `wl_shm_pool` holding the images of cursors
+///
+/// Every [`CursorTheme`](crate::CursorTheme) stores its images in such a pool. By default each
+/// theme creates its own, but a pool can be shared by several themes (for example the same theme
+/// loaded at different sizes for outputs with different scales) using
+/// [`CursorTheme::load_with_pool()`](crate::CursorTheme::load_with_pool), so that they all use a
+/// single shared memory file.
+///
+/// The pool grows as cursors are loaded. The space of cursors removed with
+/// [`CursorTheme::evict()`](crate::CursorTheme::evict) is reused for the cursors loaded afterwards.
+///
+/// This is a handle to a reference-counted pool, cloning it does not create a new pool.
+#[derive(Debug, Clone)]
+pub struct CursorPool {
+    inner: Arc<Mutex<PoolInner>>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    pool: WlShmPool,
+    file: File,
+    size: i32,
+    /// End of the last allocated chunk, everything after it is free
+    end: i32,
+    /// Free chunks before `end`, sorted by offset and never adjacent to each other
+    free: Vec<Range<i32>>,
+}
+
+impl CursorPool {
+    /// Create a new, empty, pool
+    pub fn new(conn: &Connection, shm: &WlShm) -> Result<Self, InvalidId> {
+        let mem_fd = create_shm_fd().expect("Shm fd allocation failed");
+        let mut file = unsafe { File::from_raw_fd(mem_fd) };
+        file.set_len(INITIAL_POOL_SIZE as u64).expect("Failed to set buffer length");
+
+        // Ensure that we have the same we requested.
+        file.write_all(&[0; INITIAL_POOL_SIZE as usize]).expect("Write to shm fd failed");
+        // Flush to ensure the compositor has access to the buffer when it tries to map it.
+        file.flush().expect("Flush on shm fd failed");
+
+        let pool_id = conn.send_request(
+            shm,
+            wl_shm::Request::CreatePool { fd: file.as_fd(), size: INITIAL_POOL_SIZE },
+            Some(Arc::new(IgnoreObjectData)),
+        )?;
+        let pool = WlShmPool::from_id(conn, pool_id)?;
+
+        let inner = PoolInner { pool, file, size: INITIAL_POOL_SIZE, end: 0, free: Vec::new() };
+        Ok(Self { inner: Arc::new(Mutex::new(inner)) })
+    }
+
+    /// Current size of the pool, in bytes
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size as usize
+    }
+
+    /// Number of bytes of the pool currently holding cursor images
+    pub fn used(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        let free: i32 = inner.free.iter().map(|chunk| chunk.end - chunk.start).sum();
+        (inner.end - free) as usize
+    }
+
+    /// Write an ARGB8888 image in the pool and create a buffer over it
+    ///
+    /// Returns the buffer along with the chunk of the pool it uses.
+    pub(crate) fn create_buffer(
+        &self,
+        conn: &Connection,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+    ) -> (WlBuffer, Range<i32>) {
+        let mut inner = self.inner.lock().unwrap();
+        let chunk = inner.alloc(pixels.len() as i32);
+        inner.file.write_all_at(pixels, chunk.start as u64).unwrap();
+
+        let buffer_id = conn
+            .send_request(
+                &inner.pool,
+                wl_shm_pool::Request::CreateBuffer {
+                    offset: chunk.start,
+                    width: width as i32,
+                    height: height as i32,
+                    stride: (width * 4) as i32,
+                    format: WEnum::Value(Format::Argb8888),
+                },
+                Some(Arc::new(IgnoreObjectData)),
+            )
+            .unwrap();
+
+        (WlBuffer::from_id(conn, buffer_id).unwrap(), chunk)
+    }
+
+    /// Mark a chunk previously returned by `create_buffer()` as free
+    pub(crate) fn release(&self, chunk: Range<i32>) {
+        self.inner.lock().unwrap().release(chunk);
+    }
+}
+
+impl PoolInner {
+    fn alloc(&mut self, len: i32) -> Range<i32> {
+        if let Some(i) = self.free.iter().position(|chunk| chunk.end - chunk.start >= len) {
+            let start = self.free[i].start;
+            self.free[i].start += len;
+            if self.free[i].is_empty() {
+                self.free.remove(i);
+            }
+            return start..start + len;
+        }
+
+        let start = self.end;
+        self.end += len;
+        // Resize memory before writing to it to handle shm correctly.
+        self.grow(self.end);
+        start..self.end
+    }
+
+    fn release(&mut self, chunk: Range<i32>) {
+        let i = self.free.partition_point(|free| free.start < chunk.start);
+        self.free.insert(i, chunk);
+
+        // Merge with the following chunk, then with the previous one
+        if i + 1 < self.free.len() && self.free[i].end == self.free[i + 1].start {
+            self.free[i].end = self.free.remove(i + 1).end;
+        }
+        let i = if i > 0 && self.free[i - 1].end == self.free[i].start {
+            self.free[i - 1].end = self.free.remove(i).end;
+            i - 1
+        } else {
+            i
+        };
+
+        if self.free[i].end == self.end {
+            self.end = self.free.remove(i).start;
+        }
+    }
+
+    /// Grow the wl_shm_pool.
+    ///
+    /// This method does nothing if the provided size is smaller or equal to the pool's current size.
+    fn grow(&mut self, size: i32) {
+        if size > self.size {
+            self.file.set_len(size as u64).expect("Failed to set new buffer length");
+            self.pool.resize(size);
+            self.size = size;
+        }
+    }
+}
+
+impl Drop for PoolInner {
+    fn drop(&mut self) {
+        // The buffers created from the pool remain valid after it is destroyed
+        self.pool.destroy();
+    }
+}
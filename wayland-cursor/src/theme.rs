@@ -0,0 +1,80 @@
+//! Lookup of cursor files in explicitly provided theme directories
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where the cursor files of a theme are read from
+#[derive(Debug, Clone)]
+pub(crate) enum Source {
+    /// The directories of the `XCURSOR_PATH` environment variable, or the default ones
+    System,
+    /// Explicit theme directories
+    SearchPaths(Vec<PathBuf>),
+    /// Contents of Xcursor files provided by the app, by cursor name
+    Memory(Arc<HashMap<String, Vec<u8>>>),
+}
+
+/// Find the file of cursor `icon` in `theme` or the themes it inherits from
+///
+/// Each theme is searched for in all `search_paths`, in order. The inherited themes are read
+/// from the `Inherits` key of the `index.theme` file of each theme directory.
+pub(crate) fn find_icon(search_paths: &[PathBuf], theme: &str, icon: &str) -> Option<PathBuf> {
+    find_icon_inner(search_paths, theme, icon, &mut HashSet::new())
+}
+
+fn find_icon_inner(
+    search_paths: &[PathBuf],
+    theme: &str,
+    icon: &str,
+    walked: &mut HashSet<String>,
+) -> Option<PathBuf> {
+    // Guard against inheritance loops
+    if !walked.insert(theme.to_owned()) {
+        return None;
+    }
+
+    let theme_dirs: Vec<PathBuf> =
+        search_paths.iter().map(|path| path.join(theme)).filter(|dir| dir.is_dir()).collect();
+
+    for dir in &theme_dirs {
+        let candidate = dir.join("cursors").join(icon);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+
+    for dir in &theme_dirs {
+        for parent in theme_inherits(&dir.join("index.theme")) {
+            if let Some(path) = find_icon_inner(search_paths, &parent, icon, walked) {
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+/// Parse the names of the themes inherited by an `index.theme` file
+fn theme_inherits(index: &Path) -> Vec<String> {
+    let content = match fs::read_to_string(index) {
+        Ok(content) => content,
+        Err(_) => return Vec::new(),
+    };
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            if key.trim() == "Inherits" {
+                Some(value)
+            } else {
+                None
+            }
+        })
+        .flat_map(|value| value.split(|c: char| c == ',' || c == ';' || c.is_whitespace()))
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
@@ -9,18 +9,61 @@
 //! EGL surfaces from a wayland surface.
 //!
 //! See [`WlEglSurface`] documentation for details.
+//!
+//! ## Loading the library at runtime
+//!
+//! By default `libwayland-egl.so` is linked to the final binary. With the `dlopen` cargo feature, it is
+//! instead loaded at runtime, along with the other wayland libraries, so that the binary can start on
+//! systems where it is not installed. In that case, [`is_available()`] must be checked before creating any
+//! [`WlEglSurface`].
+//!
+//! ## Without `libwayland-egl.so`
+//!
+//! The `rust_egl_window` cargo feature replaces `libwayland-egl.so` with a Rust implementation of the
+//! `wl_egl_window` structure, following the layout Mesa expects. `libwayland-client.so` is still required,
+//! as the EGL implementation needs a `wl_surface` from it. As the layout cannot be checked when the
+//! window is created, [`WlEglSurface::has_driver()`] allows to verify that the EGL implementation
+//! recognized it once the EGL surface has been created.
+//!
+//! ## raw-window-handle
+//!
+//! With the `raw-window-handle` cargo feature, a [`WlEglSurface`] can be created from a
+//! [`RawWindowHandle`](raw_window_handle::RawWindowHandle) using
+//! [`WlEglSurface::from_raw_window_handle()`], and it implements
+//! [`HasRawWindowHandle`](raw_window_handle::HasRawWindowHandle), giving the handle of its `wl_surface`.
 
-use std::{fmt, os::raw::c_void};
+use std::{
+    fmt,
+    os::raw::c_void,
+    sync::{Arc, Mutex},
+};
 
 use wayland_backend::client::ObjectId;
-use wayland_sys::{client::wl_proxy, egl::*, ffi_dispatch};
+use wayland_sys::{client::wl_proxy, egl::*};
+
+#[cfg(feature = "rust_egl_window")]
+mod rust_window;
+
+#[cfg(not(feature = "rust_egl_window"))]
+macro_rules! egl_call {
+    ($func:ident, $($arg:expr),*) => {
+        wayland_sys::ffi_dispatch!(wayland_egl_handle(), $func, $($arg),*)
+    };
+}
+
+#[cfg(feature = "rust_egl_window")]
+macro_rules! egl_call {
+    ($func:ident, $($arg:expr),*) => {
+        rust_window::$func($($arg),*)
+    };
+}
 
 /// Checks if the wayland-egl lib is available and can be used
 ///
 /// Trying to create an [`WlEglSurface`] while this function returns
 /// `false` will result in a panic.
 pub fn is_available() -> bool {
-    is_lib_available()
+    cfg!(feature = "rust_egl_window") || is_lib_available()
 }
 
 /// EGL surface
@@ -30,9 +73,17 @@ pub fn is_available() -> bool {
 /// is created to get the window pointer your OpenGL library is needing to initialize
 /// the EGL context (you'll most likely need the display ptr as well, that you can
 /// get via the [`ObjectId::as_ptr()`] method on of the `wl_display` ID).
+///
+/// This type can be sent to another thread but is not `Sync`. If the surface is resized from a thread
+/// other than the one using it for rendering, use a [`ResizeHandle`] to queue the resize, and apply it
+/// from the rendering thread with [`apply_pending_resize()`](WlEglSurface::apply_pending_resize).
 #[derive(Debug)]
 pub struct WlEglSurface {
     ptr: *mut wl_egl_window,
+    // Only used to provide the raw window handle
+    #[cfg_attr(not(feature = "raw-window-handle"), allow(dead_code))]
+    surface: *mut wl_proxy,
+    pending: Arc<Mutex<Option<PendingResize>>>,
 }
 
 impl WlEglSurface {
@@ -71,11 +122,57 @@ impl WlEglSurface {
         if width <= 0 || height <= 0 {
             return Err(Error::InvalidSize);
         }
-        let ptr = ffi_dispatch!(wayland_egl_handle(), wl_egl_window_create, surface, width, height);
+        let ptr = unsafe { egl_call!(wl_egl_window_create, surface, width, height) };
         if ptr.is_null() {
             panic!("egl window allocation failed");
         }
-        Ok(Self { ptr })
+        Ok(Self { ptr, surface, pending: Arc::new(Mutex::new(None)) })
+    }
+
+    /// Take ownership of a `wl_egl_window` created by other code
+    ///
+    /// This allows to drive from Rust a native window that C code created with `wl_egl_window_create()`.
+    /// The returned [`WlEglSurface`] destroys the window when dropped, so the C code must not destroy it.
+    /// Returns [`Error::InvalidId`] if `ptr` is null.
+    ///
+    /// This constructor is not available with the `rust_egl_window` feature, as the window would then be
+    /// destroyed by a different implementation than the one which created it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a valid `wl_egl_window` pointer from `libwayland-egl`, not owned by anything else,
+    /// and `surface` the `wl_surface` pointer from `libwayland-client` it was created for.
+    #[cfg(not(feature = "rust_egl_window"))]
+    pub unsafe fn from_raw_egl_window(
+        ptr: *mut wl_egl_window,
+        surface: *mut wl_proxy,
+    ) -> Result<Self, Error> {
+        if ptr.is_null() {
+            return Err(Error::InvalidId);
+        }
+        Ok(Self { ptr, surface, pending: Arc::new(Mutex::new(None)) })
+    }
+
+    /// Create an EGL surface from the raw window handle of a wayland surface.
+    ///
+    /// Returns [`Error::InvalidId`] if the handle is not a Wayland handle, or has no surface.
+    ///
+    /// # Safety
+    ///
+    /// The surface of the handle must be a valid `wl_surface` pointer from `libwayland-client`.
+    #[cfg(feature = "raw-window-handle")]
+    pub unsafe fn from_raw_window_handle(
+        handle: raw_window_handle::RawWindowHandle,
+        width: i32,
+        height: i32,
+    ) -> Result<Self, Error> {
+        match handle {
+            raw_window_handle::RawWindowHandle::Wayland(handle) if !handle.surface.is_null() => {
+                // SAFETY: The caller guarantees the surface pointer is valid.
+                unsafe { Self::new_from_raw(handle.surface.cast(), width, height) }
+            }
+            _ => Err(Error::InvalidId),
+        }
     }
 
     /// Fetch current size of the EGL surface
@@ -83,8 +180,7 @@ impl WlEglSurface {
         let mut w = 0i32;
         let mut h = 0i32;
         unsafe {
-            ffi_dispatch!(
-                wayland_egl_handle(),
+            egl_call!(
                 wl_egl_window_get_attached_size,
                 self.ptr,
                 &mut w as *mut i32,
@@ -101,19 +197,41 @@ impl WlEglSurface {
     /// of the top-left corner of the surface. It allows you to control the
     /// direction of the resizing if necessary.
     pub fn resize(&self, width: i32, height: i32, dx: i32, dy: i32) {
-        unsafe {
-            ffi_dispatch!(
-                wayland_egl_handle(),
-                wl_egl_window_resize,
-                self.ptr,
-                width,
-                height,
-                dx,
-                dy
-            )
+        unsafe { egl_call!(wl_egl_window_resize, self.ptr, width, height, dx, dy) }
+    }
+
+    /// Get a handle to queue resizes of this surface from other threads
+    pub fn resize_handle(&self) -> ResizeHandle {
+        ResizeHandle { pending: self.pending.clone() }
+    }
+
+    /// Apply the resize queued with a [`ResizeHandle`], if any
+    ///
+    /// This should be called by the thread rendering to the surface, before drawing a new frame, so that
+    /// the resize never happens concurrently with the use of the surface by the EGL implementation.
+    ///
+    /// Returns `true` if a resize was applied.
+    pub fn apply_pending_resize(&self) -> bool {
+        match self.pending.lock().unwrap().take() {
+            Some(PendingResize { width, height, dx, dy }) => {
+                self.resize(width, height, dx, dy);
+                true
+            }
+            None => false,
         }
     }
 
+    /// Whether the EGL implementation registered itself on this surface
+    ///
+    /// This is only meaningful after an EGL window surface has been created from
+    /// [`ptr()`](WlEglSurface::ptr). If it returns `false` at that point, the EGL implementation did not
+    /// recognize the `wl_egl_window` layout of the `rust_egl_window` feature, and the surface will not be
+    /// resized correctly.
+    #[cfg(feature = "rust_egl_window")]
+    pub fn has_driver(&self) -> bool {
+        unsafe { rust_window::has_driver(self.ptr) }
+    }
+
     /// Raw pointer to the EGL surface
     ///
     /// You'll need this pointer to initialize the EGL context in your
@@ -127,14 +245,62 @@ impl WlEglSurface {
 unsafe impl Send for WlEglSurface {}
 // Note that WlEglSurface is !Sync. This is because the pointer performs no internal synchronization.
 
+// SAFETY: The surface pointer was valid when the EGL surface was created, and the wl_surface must
+// outlive it.
+#[cfg(feature = "raw-window-handle")]
+unsafe impl raw_window_handle::HasRawWindowHandle for WlEglSurface {
+    fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
+        let mut handle = raw_window_handle::WaylandWindowHandle::empty();
+        handle.surface = self.surface.cast();
+        raw_window_handle::RawWindowHandle::Wayland(handle)
+    }
+}
+
 impl Drop for WlEglSurface {
     fn drop(&mut self) {
         unsafe {
-            ffi_dispatch!(wayland_egl_handle(), wl_egl_window_destroy, self.ptr);
+            egl_call!(wl_egl_window_destroy, self.ptr);
         }
     }
 }
 
+/// A handle to queue resizes of a [`WlEglSurface`]
+///
+/// Unlike the surface itself, this handle can be shared between threads. The resizes it queues only take
+/// effect once [`WlEglSurface::apply_pending_resize()`] is called.
+#[derive(Debug, Clone)]
+pub struct ResizeHandle {
+    pending: Arc<Mutex<Option<PendingResize>>>,
+}
+
+impl ResizeHandle {
+    /// Queue a resize of the EGL surface
+    ///
+    /// The arguments are the same as for [`WlEglSurface::resize()`]. If a resize is already pending, it is
+    /// replaced by this one, and the displacements of both are added up.
+    pub fn resize(&self, width: i32, height: i32, dx: i32, dy: i32) {
+        let mut pending = self.pending.lock().unwrap();
+        let (dx, dy) = match *pending {
+            Some(ref previous) => (previous.dx + dx, previous.dy + dy),
+            None => (dx, dy),
+        };
+        *pending = Some(PendingResize { width, height, dx, dy });
+    }
+
+    /// Whether a resize is waiting to be applied
+    pub fn is_pending(&self) -> bool {
+        self.pending.lock().unwrap().is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingResize {
+    width: i32,
+    height: i32,
+    dx: i32,
+    dy: i32,
+}
+
 /// EGL surface creation error.
 #[derive(Debug)]
 pub enum Error {
@@ -0,0 +1,111 @@
+//! Rust implementation of `libwayland-egl.so`
+//!
+//! `libwayland-egl` does not do anything but allocating a `wl_egl_window` structure, whose layout is
+//! defined by Mesa in `wayland-egl-backend.h`. The EGL implementation then reads it and registers its
+//! callbacks in it. This module allocates the same structure, so that the library does not need to be
+//! loaded.
+
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+use wayland_sys::client::wl_proxy;
+use wayland_sys::egl::wl_egl_window;
+
+/// Version of the layout, as defined by `WL_EGL_WINDOW_VERSION`
+const WL_EGL_WINDOW_VERSION: isize = 3;
+
+#[repr(C)]
+struct RawWindow {
+    version: isize,
+    width: c_int,
+    height: c_int,
+    dx: c_int,
+    dy: c_int,
+    attached_width: c_int,
+    attached_height: c_int,
+    driver_private: *mut c_void,
+    resize_callback: Option<unsafe extern "C" fn(*mut RawWindow, *mut c_void)>,
+    destroy_window_callback: Option<unsafe extern "C" fn(*mut c_void)>,
+    surface: *mut wl_proxy,
+}
+
+// The layout is fixed by the EGL implementations, make sure it did not change by mistake
+const _: () = assert!(
+    std::mem::size_of::<RawWindow>()
+        == 2 * std::mem::size_of::<isize>()
+            + 6 * std::mem::size_of::<c_int>()
+            + 3 * std::mem::size_of::<*mut c_void>()
+);
+const _: () = assert!(std::mem::align_of::<RawWindow>() == std::mem::align_of::<*mut c_void>());
+
+pub(crate) unsafe fn wl_egl_window_create(
+    surface: *mut wl_proxy,
+    width: c_int,
+    height: c_int,
+) -> *mut wl_egl_window {
+    let window = RawWindow {
+        version: WL_EGL_WINDOW_VERSION,
+        width,
+        height,
+        dx: 0,
+        dy: 0,
+        attached_width: 0,
+        attached_height: 0,
+        driver_private: ptr::null_mut(),
+        resize_callback: None,
+        destroy_window_callback: None,
+        surface,
+    };
+    Box::into_raw(Box::new(window)).cast()
+}
+
+pub(crate) unsafe fn wl_egl_window_destroy(window: *mut wl_egl_window) {
+    // SAFETY: The window was allocated by `wl_egl_window_create()`.
+    let window = unsafe { Box::from_raw(window.cast::<RawWindow>()) };
+    if let Some(callback) = window.destroy_window_callback {
+        // SAFETY: The callback was registered by the EGL implementation along with its data.
+        unsafe { callback(window.driver_private) };
+    }
+}
+
+pub(crate) unsafe fn wl_egl_window_resize(
+    window: *mut wl_egl_window,
+    width: c_int,
+    height: c_int,
+    dx: c_int,
+    dy: c_int,
+) {
+    let window = window.cast::<RawWindow>();
+    // SAFETY: The window was allocated by `wl_egl_window_create()` and is not aliased.
+    unsafe {
+        (*window).width = width;
+        (*window).height = height;
+        (*window).dx = dx;
+        (*window).dy = dy;
+        if let Some(callback) = (*window).resize_callback {
+            callback(window, (*window).driver_private);
+        }
+    }
+}
+
+pub(crate) unsafe fn wl_egl_window_get_attached_size(
+    window: *mut wl_egl_window,
+    width: *mut c_int,
+    height: *mut c_int,
+) {
+    // SAFETY: The window was allocated by `wl_egl_window_create()`.
+    let window = unsafe { &*window.cast::<RawWindow>() };
+    if !width.is_null() {
+        unsafe { *width = window.attached_width };
+    }
+    if !height.is_null() {
+        unsafe { *height = window.attached_height };
+    }
+}
+
+/// Whether an EGL implementation registered itself on the window
+pub(crate) unsafe fn has_driver(window: *mut wl_egl_window) -> bool {
+    // SAFETY: The window was allocated by `wl_egl_window_create()`.
+    let window = unsafe { &*window.cast::<RawWindow>() };
+    !window.driver_private.is_null() && window.resize_callback.is_some()
+}
@@ -0,0 +1,65 @@
+//! Compare two revisions of a protocol file
+//!
+//! ```text
+//! wayland-protocol-diff [--all] <OLD.xml> <NEW.xml>
+//! ```
+//!
+//! Prints the changes between the two files, the breaking ones being marked as such. The exit status
+//! is 1 if there is any breaking change, or with `--all` if there is any change at all, and 2 if the
+//! files could not be read.
+
+use std::fs::File;
+use std::process::exit;
+
+use wayland_protocol_model::{diff::diff, parse::parse, protocol::Protocol};
+
+const USAGE: &str = "Usage: wayland-protocol-diff [--all] <OLD.xml> <NEW.xml>
+
+Print the changes between two revisions of a wayland protocol file.
+
+Options:
+  --all    Exit with status 1 on any change, not only on breaking ones";
+
+fn open(path: &str) -> Protocol {
+    match File::open(path) {
+        Ok(file) => parse(file),
+        Err(e) => {
+            eprintln!("Failed to open {}: {}", path, e);
+            exit(2);
+        }
+    }
+}
+
+fn main() {
+    let mut all = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match &arg[..] {
+            "--all" => all = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+
+    let changes = diff(&open(&paths[0]), &open(&paths[1]));
+    let mut breaking = false;
+    for change in &changes {
+        if change.is_breaking() {
+            breaking = true;
+            println!("{}  [breaking]", change);
+        } else {
+            println!("{}", change);
+        }
+    }
+
+    if breaking || (all && !changes.is_empty()) {
+        exit(1);
+    }
+}
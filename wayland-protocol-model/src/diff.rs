@@ -0,0 +1,556 @@
+//! Comparison of two revisions of a protocol
+//!
+//! Interfaces, messages, enums and enum entries are matched by name between the two revisions. The
+//! differences that can break the programs built against the old revision are reported as breaking,
+//! see [`Change::is_breaking()`].
+
+use std::fmt;
+
+use crate::protocol::{Arg, Enum, Interface, Message, Protocol, Type};
+
+/// Whether a message is a request or an event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// A message sent by the client
+    Request,
+    /// A message sent by the server
+    Event,
+}
+
+impl fmt::Display for MessageKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MessageKind::Request => f.write_str("request"),
+            MessageKind::Event => f.write_str("event"),
+        }
+    }
+}
+
+/// A difference between two revisions of a protocol
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// An interface was added
+    InterfaceAdded {
+        /// Name of the interface
+        interface: String,
+        /// Version of the new interface
+        version: u32,
+    },
+    /// An interface was removed
+    InterfaceRemoved {
+        /// Name of the interface
+        interface: String,
+    },
+    /// The version of an interface changed
+    VersionChanged {
+        /// Name of the interface
+        interface: String,
+        /// Previous version
+        old: u32,
+        /// New version
+        new: u32,
+    },
+    /// A message was added to an interface
+    MessageAdded {
+        /// Name of the interface
+        interface: String,
+        /// Whether the message is a request or an event
+        kind: MessageKind,
+        /// Name of the message
+        name: String,
+        /// Version the message is available since
+        since: u32,
+    },
+    /// A message was removed from an interface
+    MessageRemoved {
+        /// Name of the interface
+        interface: String,
+        /// Whether the message is a request or an event
+        kind: MessageKind,
+        /// Name of the message
+        name: String,
+    },
+    /// A message was moved, changing its opcode
+    OpcodeChanged {
+        /// Name of the interface
+        interface: String,
+        /// Whether the message is a request or an event
+        kind: MessageKind,
+        /// Name of the message
+        name: String,
+        /// Previous opcode
+        old: u16,
+        /// New opcode
+        new: u16,
+    },
+    /// The arguments of a message changed
+    ///
+    /// The signatures are written as `(name: type, ...)`, see [`signature()`].
+    SignatureChanged {
+        /// Name of the interface
+        interface: String,
+        /// Whether the message is a request or an event
+        kind: MessageKind,
+        /// Name of the message
+        name: String,
+        /// Previous signature
+        old: String,
+        /// New signature
+        new: String,
+    },
+    /// The version a message is available since changed
+    SinceChanged {
+        /// Name of the interface
+        interface: String,
+        /// Whether the message is a request or an event
+        kind: MessageKind,
+        /// Name of the message
+        name: String,
+        /// Previous version
+        old: u32,
+        /// New version
+        new: u32,
+    },
+    /// A message became a destructor, or stopped being one
+    DestructorChanged {
+        /// Name of the interface
+        interface: String,
+        /// Whether the message is a request or an event
+        kind: MessageKind,
+        /// Name of the message
+        name: String,
+        /// Whether the message is now a destructor
+        destructor: bool,
+    },
+    /// An enum was added to an interface
+    EnumAdded {
+        /// Name of the interface
+        interface: String,
+        /// Name of the enum
+        name: String,
+    },
+    /// An enum was removed from an interface
+    EnumRemoved {
+        /// Name of the interface
+        interface: String,
+        /// Name of the enum
+        name: String,
+    },
+    /// An enum became a bitfield, or stopped being one
+    BitfieldChanged {
+        /// Name of the interface
+        interface: String,
+        /// Name of the enum
+        name: String,
+        /// Whether the enum is now a bitfield
+        bitfield: bool,
+    },
+    /// An entry was added to an enum
+    EntryAdded {
+        /// Name of the interface
+        interface: String,
+        /// Name of the enum
+        enum_: String,
+        /// Name of the entry
+        name: String,
+        /// Value of the entry
+        value: u32,
+    },
+    /// An entry was removed from an enum
+    EntryRemoved {
+        /// Name of the interface
+        interface: String,
+        /// Name of the enum
+        enum_: String,
+        /// Name of the entry
+        name: String,
+    },
+    /// The value of an entry changed
+    EntryValueChanged {
+        /// Name of the interface
+        interface: String,
+        /// Name of the enum
+        enum_: String,
+        /// Name of the entry
+        name: String,
+        /// Previous value
+        old: u32,
+        /// New value
+        new: u32,
+    },
+}
+
+impl Change {
+    /// Whether this change can break programs built against the old revision of the protocol
+    ///
+    /// Additions are compatible, as are version bumps and messages becoming available in earlier
+    /// versions. Everything else changes either the wire format or the code generated by
+    /// `wayland-scanner`, including renaming an argument.
+    pub fn is_breaking(&self) -> bool {
+        match *self {
+            Change::InterfaceAdded { .. }
+            | Change::MessageAdded { .. }
+            | Change::EnumAdded { .. }
+            | Change::EntryAdded { .. } => false,
+            Change::VersionChanged { old, new, .. } => new < old,
+            Change::SinceChanged { old, new, .. } => new > old,
+            Change::InterfaceRemoved { .. }
+            | Change::MessageRemoved { .. }
+            | Change::OpcodeChanged { .. }
+            | Change::SignatureChanged { .. }
+            | Change::DestructorChanged { .. }
+            | Change::EnumRemoved { .. }
+            | Change::BitfieldChanged { .. }
+            | Change::EntryRemoved { .. }
+            | Change::EntryValueChanged { .. } => true,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::InterfaceAdded { interface, version } => {
+                write!(f, "+ interface {} (version {})", interface, version)
+            }
+            Change::InterfaceRemoved { interface } => write!(f, "- interface {}", interface),
+            Change::VersionChanged { interface, old, new } => {
+                write!(f, "~ interface {}: version {} -> {}", interface, old, new)
+            }
+            Change::MessageAdded { interface, kind, name, since } => {
+                write!(f, "+ {} {}.{} (since {})", kind, interface, name, since)
+            }
+            Change::MessageRemoved { interface, kind, name } => {
+                write!(f, "- {} {}.{}", kind, interface, name)
+            }
+            Change::OpcodeChanged { interface, kind, name, old, new } => {
+                write!(f, "~ {} {}.{}: opcode {} -> {}", kind, interface, name, old, new)
+            }
+            Change::SignatureChanged { interface, kind, name, old, new } => {
+                write!(f, "~ {} {}.{}: {} -> {}", kind, interface, name, old, new)
+            }
+            Change::SinceChanged { interface, kind, name, old, new } => {
+                write!(f, "~ {} {}.{}: since {} -> {}", kind, interface, name, old, new)
+            }
+            Change::DestructorChanged { interface, kind, name, destructor } => {
+                let status = if *destructor { "now" } else { "no longer" };
+                write!(f, "~ {} {}.{}: {} a destructor", kind, interface, name, status)
+            }
+            Change::EnumAdded { interface, name } => write!(f, "+ enum {}.{}", interface, name),
+            Change::EnumRemoved { interface, name } => write!(f, "- enum {}.{}", interface, name),
+            Change::BitfieldChanged { interface, name, bitfield } => {
+                let status = if *bitfield { "now" } else { "no longer" };
+                write!(f, "~ enum {}.{}: {} a bitfield", interface, name, status)
+            }
+            Change::EntryAdded { interface, enum_, name, value } => {
+                write!(f, "+ entry {}.{}.{} = {}", interface, enum_, name, value)
+            }
+            Change::EntryRemoved { interface, enum_, name } => {
+                write!(f, "- entry {}.{}.{}", interface, enum_, name)
+            }
+            Change::EntryValueChanged { interface, enum_, name, old, new } => {
+                write!(f, "~ entry {}.{}.{}: {} -> {}", interface, enum_, name, old, new)
+            }
+        }
+    }
+}
+
+/// Compare two revisions of a protocol
+///
+/// The changes are listed interface by interface, in the order of the old revision followed by the
+/// interfaces that were added.
+pub fn diff(old: &Protocol, new: &Protocol) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for old_iface in &old.interfaces {
+        match new.interfaces.iter().find(|iface| iface.name == old_iface.name) {
+            Some(new_iface) => diff_interface(old_iface, new_iface, &mut changes),
+            None => changes.push(Change::InterfaceRemoved { interface: old_iface.name.clone() }),
+        }
+    }
+    for new_iface in &new.interfaces {
+        if !old.interfaces.iter().any(|iface| iface.name == new_iface.name) {
+            changes.push(Change::InterfaceAdded {
+                interface: new_iface.name.clone(),
+                version: new_iface.version,
+            });
+        }
+    }
+    changes
+}
+
+/// The signature of a message, as in `(serial: uint, surface: object<wl_surface>?)`
+///
+/// The interface of objects and the enum of integers are given in angle brackets, and the
+/// arguments that can be null are followed by a question mark.
+pub fn signature(message: &Message) -> String {
+    let args = message.args.iter().map(|arg: &Arg| {
+        let mut text = format!("{}: {}", arg.name, arg.typ.name());
+        if let Some(target) = arg.interface.as_ref().or(arg.enum_.as_ref()) {
+            text.push('<');
+            text.push_str(target);
+            text.push('>');
+        }
+        if arg.allow_null && arg.typ.nullable() {
+            text.push('?');
+        }
+        text
+    });
+    format!("({})", args.collect::<Vec<_>>().join(", "))
+}
+
+fn diff_interface(old: &Interface, new: &Interface, changes: &mut Vec<Change>) {
+    if old.version != new.version {
+        changes.push(Change::VersionChanged {
+            interface: old.name.clone(),
+            old: old.version,
+            new: new.version,
+        });
+    }
+    diff_messages(&old.name, MessageKind::Request, &old.requests, &new.requests, changes);
+    diff_messages(&old.name, MessageKind::Event, &old.events, &new.events, changes);
+
+    for old_enum in &old.enums {
+        match new.enums.iter().find(|enu| enu.name == old_enum.name) {
+            Some(new_enum) => diff_enum(&old.name, old_enum, new_enum, changes),
+            None => changes.push(Change::EnumRemoved {
+                interface: old.name.clone(),
+                name: old_enum.name.clone(),
+            }),
+        }
+    }
+    for new_enum in &new.enums {
+        if !old.enums.iter().any(|enu| enu.name == new_enum.name) {
+            changes.push(Change::EnumAdded {
+                interface: old.name.clone(),
+                name: new_enum.name.clone(),
+            });
+        }
+    }
+}
+
+fn diff_messages(
+    interface: &str,
+    kind: MessageKind,
+    old: &[Message],
+    new: &[Message],
+    changes: &mut Vec<Change>,
+) {
+    for (old_opcode, old_msg) in old.iter().enumerate() {
+        let (new_opcode, new_msg) =
+            match new.iter().enumerate().find(|(_, msg)| msg.name == old_msg.name) {
+                Some(found) => found,
+                None => {
+                    changes.push(Change::MessageRemoved {
+                        interface: interface.into(),
+                        kind,
+                        name: old_msg.name.clone(),
+                    });
+                    continue;
+                }
+            };
+        if old_opcode != new_opcode {
+            changes.push(Change::OpcodeChanged {
+                interface: interface.into(),
+                kind,
+                name: old_msg.name.clone(),
+                old: old_opcode as u16,
+                new: new_opcode as u16,
+            });
+        }
+        let (old_signature, new_signature) = (signature(old_msg), signature(new_msg));
+        if old_signature != new_signature {
+            changes.push(Change::SignatureChanged {
+                interface: interface.into(),
+                kind,
+                name: old_msg.name.clone(),
+                old: old_signature,
+                new: new_signature,
+            });
+        }
+        if old_msg.since != new_msg.since {
+            changes.push(Change::SinceChanged {
+                interface: interface.into(),
+                kind,
+                name: old_msg.name.clone(),
+                old: old_msg.since,
+                new: new_msg.since,
+            });
+        }
+        let destructor = new_msg.typ == Some(Type::Destructor);
+        if (old_msg.typ == Some(Type::Destructor)) != destructor {
+            changes.push(Change::DestructorChanged {
+                interface: interface.into(),
+                kind,
+                name: old_msg.name.clone(),
+                destructor,
+            });
+        }
+    }
+    for new_msg in new {
+        if !old.iter().any(|msg| msg.name == new_msg.name) {
+            changes.push(Change::MessageAdded {
+                interface: interface.into(),
+                kind,
+                name: new_msg.name.clone(),
+                since: new_msg.since,
+            });
+        }
+    }
+}
+
+fn diff_enum(interface: &str, old: &Enum, new: &Enum, changes: &mut Vec<Change>) {
+    if old.bitfield != new.bitfield {
+        changes.push(Change::BitfieldChanged {
+            interface: interface.into(),
+            name: old.name.clone(),
+            bitfield: new.bitfield,
+        });
+    }
+    for old_entry in &old.entries {
+        match new.entries.iter().find(|entry| entry.name == old_entry.name) {
+            Some(new_entry) if new_entry.value != old_entry.value => {
+                changes.push(Change::EntryValueChanged {
+                    interface: interface.into(),
+                    enum_: old.name.clone(),
+                    name: old_entry.name.clone(),
+                    old: old_entry.value,
+                    new: new_entry.value,
+                })
+            }
+            Some(_) => {}
+            None => changes.push(Change::EntryRemoved {
+                interface: interface.into(),
+                enum_: old.name.clone(),
+                name: old_entry.name.clone(),
+            }),
+        }
+    }
+    for new_entry in &new.entries {
+        if !old.entries.iter().any(|entry| entry.name == new_entry.name) {
+            changes.push(Change::EntryAdded {
+                interface: interface.into(),
+                enum_: old.name.clone(),
+                name: new_entry.name.clone(),
+                value: new_entry.value,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    const OLD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<protocol name="diff_test">
+  <interface name="test_manager" version="2">
+    <request name="create" since="2">
+      <arg name="id" type="new_id" interface="test_object"/>
+    </request>
+    <request name="destroy" type="destructor"/>
+    <request name="legacy"/>
+    <event name="status">
+      <arg name="value" type="uint" enum="status"/>
+    </event>
+    <enum name="status">
+      <entry name="ok" value="0"/>
+      <entry name="busy" value="1"/>
+      <entry name="gone" value="2"/>
+    </enum>
+  </interface>
+  <interface name="test_object" version="1">
+    <request name="set_name">
+      <arg name="name" type="string"/>
+    </request>
+  </interface>
+</protocol>"#;
+
+    const NEW: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<protocol name="diff_test">
+  <interface name="test_manager" version="3">
+    <request name="destroy" type="destructor"/>
+    <request name="create">
+      <arg name="id" type="new_id" interface="test_object"/>
+    </request>
+    <request name="reset" since="3"/>
+    <event name="status">
+      <arg name="value" type="uint" enum="status"/>
+      <arg name="surface" type="object" interface="wl_surface" allow-null="true"/>
+    </event>
+    <enum name="status">
+      <entry name="ok" value="0"/>
+      <entry name="busy" value="3"/>
+      <entry name="idle" value="4"/>
+    </enum>
+    <enum name="flags" bitfield="true">
+      <entry name="none" value="0"/>
+    </enum>
+  </interface>
+  <interface name="test_extension" version="1"/>
+</protocol>"#;
+
+    fn parse_str(text: &str) -> Protocol {
+        parse(text.as_bytes())
+    }
+
+    #[test]
+    fn identical() {
+        assert_eq!(diff(&parse_str(OLD), &parse_str(OLD)), vec![]);
+    }
+
+    #[test]
+    fn changes() {
+        let changes = diff(&parse_str(OLD), &parse_str(NEW));
+        let lines = changes.iter().map(|change| change.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "~ interface test_manager: version 2 -> 3",
+                "~ request test_manager.create: opcode 0 -> 1",
+                "~ request test_manager.create: since 2 -> 1",
+                "~ request test_manager.destroy: opcode 1 -> 0",
+                "- request test_manager.legacy",
+                "+ request test_manager.reset (since 3)",
+                "~ event test_manager.status: (value: uint<status>) -> \
+                 (value: uint<status>, surface: object<wl_surface>?)",
+                "~ entry test_manager.status.busy: 1 -> 3",
+                "- entry test_manager.status.gone",
+                "+ entry test_manager.status.idle = 4",
+                "+ enum test_manager.flags",
+                "- interface test_object",
+                "+ interface test_extension (version 1)",
+            ]
+        );
+        let breaking = changes.iter().map(Change::is_breaking).collect::<Vec<_>>();
+        assert_eq!(
+            breaking,
+            vec![
+                false, true, false, true, true, false, true, true, true, false, false, true, false
+            ]
+        );
+    }
+
+    #[test]
+    fn destructors_and_bitfields() {
+        let new = OLD
+            .replace(r#"<request name="legacy"/>"#, r#"<request name="legacy" type="destructor"/>"#)
+            .replace(r#"<enum name="status">"#, r#"<enum name="status" bitfield="true">"#);
+        assert_eq!(
+            diff(&parse_str(OLD), &parse_str(&new)),
+            vec![
+                Change::DestructorChanged {
+                    interface: "test_manager".into(),
+                    kind: MessageKind::Request,
+                    name: "legacy".into(),
+                    destructor: true,
+                },
+                Change::BitfieldChanged {
+                    interface: "test_manager".into(),
+                    name: "status".into(),
+                    bitfield: true,
+                },
+            ]
+        );
+    }
+}
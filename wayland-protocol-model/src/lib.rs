@@ -0,0 +1,37 @@
+//! Parsed representation of Wayland XML protocol files
+//!
+//! This is the model the `wayland-scanner` crate generates its code from, exposed for the tools
+//! working on protocol files. [`parse::parse()`] reads a protocol file into a [`protocol::Protocol`],
+//! and [`diff::diff()`] compares two revisions of a protocol:
+//!
+//! ```
+//! use wayland_protocol_model::{diff::diff, parse::parse};
+//!
+//! let old = parse(&br#"<?xml version="1.0" encoding="UTF-8"?>
+//! <protocol name="example">
+//!     <interface name="example_manager" version="1">
+//!         <request name="destroy" type="destructor"/>
+//!     </interface>
+//! </protocol>"#[..]);
+//! let new = parse(&br#"<?xml version="1.0" encoding="UTF-8"?>
+//! <protocol name="example">
+//!     <interface name="example_manager" version="2">
+//!         <request name="destroy" type="destructor"/>
+//!         <event name="done" since="2"/>
+//!     </interface>
+//! </protocol>"#[..]);
+//!
+//! for change in diff(&old, &new) {
+//!     assert!(!change.is_breaking());
+//!     println!("{}", change);
+//! }
+//! ```
+//!
+//! This crate also provides the `wayland-protocol-diff` binary, to compare protocol files from the command
+//! line.
+
+#![warn(missing_docs, missing_debug_implementations)]
+
+pub mod diff;
+pub mod parse;
+pub mod protocol;
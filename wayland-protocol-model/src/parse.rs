@@ -1,4 +1,6 @@
-use super::protocol::*;
+//! Parsing of protocol files
+
+use crate::protocol::*;
 use std::{
     io::{BufRead, BufReader, Read},
     str::FromStr,
@@ -26,6 +28,11 @@ macro_rules! extract_end_tag(
     )
 );
 
+/// Parse a protocol file
+///
+/// # Panics
+///
+/// This function panics with a description of the error if the file is not a valid protocol file.
 pub fn parse<S: Read>(stream: S) -> Protocol {
     let mut reader = Reader::from_reader(BufReader::new(stream));
     reader.trim_text(true).expand_empty_elements(true);
@@ -182,6 +189,7 @@ fn parse_request<R: BufRead>(reader: &mut Reader<R>, attrs: Attributes) -> Messa
             b"name" => request.name = decode_utf8_or_panic(attr.value.into_owned()),
             b"type" => request.typ = Some(parse_type(&attr.value)),
             b"since" => request.since = parse_or_panic(&attr.value),
+            b"deprecated-since" => request.deprecated_since = Some(parse_or_panic(&attr.value)),
             _ => {}
         }
     }
@@ -209,11 +217,7 @@ fn parse_enum<R: BufRead>(reader: &mut Reader<R>, attrs: Attributes) -> Enum {
         match attr.key.into_inner() {
             b"name" => enu.name = decode_utf8_or_panic(attr.value.into_owned()),
             b"since" => enu.since = parse_or_panic(&attr.value),
-            b"bitfield" => {
-                if &attr.value[..] == b"true" {
-                    enu.bitfield = true
-                }
-            }
+            b"bitfield" if &attr.value[..] == b"true" => enu.bitfield = true,
             _ => {}
         }
     }
@@ -242,6 +246,7 @@ fn parse_event<R: BufRead>(reader: &mut Reader<R>, attrs: Attributes) -> Message
             b"name" => event.name = decode_utf8_or_panic(attr.value.into_owned()),
             b"type" => event.typ = Some(parse_type(&attr.value)),
             b"since" => event.since = parse_or_panic(&attr.value),
+            b"deprecated-since" => event.deprecated_since = Some(parse_or_panic(&attr.value)),
             _ => {}
         }
     }
@@ -278,11 +283,7 @@ fn parse_arg<R: BufRead>(reader: &mut Reader<R>, attrs: Attributes) -> Arg {
                 )
             }
             b"interface" => arg.interface = Some(parse_or_panic(&attr.value)),
-            b"allow-null" => {
-                if &*attr.value == b"true" {
-                    arg.allow_null = true
-                }
-            }
+            b"allow-null" if &*attr.value == b"true" => arg.allow_null = true,
             b"enum" => arg.enum_ = Some(decode_utf8_or_panic(attr.value.into_owned())),
             _ => {}
         }
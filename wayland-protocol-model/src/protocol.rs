@@ -0,0 +1,251 @@
+//! The contents of a protocol file
+//!
+//! Descriptions are stored as `(summary, text)` pairs.
+
+/// A protocol, as described by the toplevel `<protocol>` tag of a file
+#[derive(Clone, Debug)]
+pub struct Protocol {
+    /// Name of the protocol
+    pub name: String,
+    /// Copyright notice of the file
+    pub copyright: Option<String>,
+    /// Description of the protocol
+    pub description: Option<(String, String)>,
+    /// The interfaces of the protocol, in the order of the file
+    pub interfaces: Vec<Interface>,
+}
+
+impl Protocol {
+    /// An empty protocol with the given name
+    pub fn new(name: String) -> Protocol {
+        Protocol { name, copyright: None, description: None, interfaces: Vec::new() }
+    }
+}
+
+/// An interface of a protocol
+#[derive(Clone, Debug)]
+pub struct Interface {
+    /// Name of the interface
+    pub name: String,
+    /// Latest version of the interface
+    pub version: u32,
+    /// Description of the interface
+    pub description: Option<(String, String)>,
+    /// The requests of the interface, their index being their opcode
+    pub requests: Vec<Message>,
+    /// The events of the interface, their index being their opcode
+    pub events: Vec<Message>,
+    /// The enums of the interface
+    pub enums: Vec<Enum>,
+}
+
+impl Interface {
+    /// An empty interface at version 1
+    pub fn new() -> Interface {
+        Interface {
+            name: String::new(),
+            version: 1,
+            description: None,
+            requests: Vec::new(),
+            events: Vec::new(),
+            enums: Vec::new(),
+        }
+    }
+}
+
+impl Default for Interface {
+    fn default() -> Interface {
+        Interface::new()
+    }
+}
+
+/// A request or an event
+#[derive(Clone, Debug)]
+pub struct Message {
+    /// Name of the message
+    pub name: String,
+    /// The type of the message, only ever [`Type::Destructor`]
+    pub typ: Option<Type>,
+    /// Version of the interface the message was introduced in
+    pub since: u32,
+    /// Version of the interface from which the message is deprecated, if any
+    pub deprecated_since: Option<u32>,
+    /// Description of the message
+    pub description: Option<(String, String)>,
+    /// The arguments of the message
+    pub args: Vec<Arg>,
+}
+
+impl Message {
+    /// A message without arguments, present since version 1
+    pub fn new() -> Message {
+        Message {
+            name: String::new(),
+            typ: None,
+            since: 1,
+            deprecated_since: None,
+            description: None,
+            args: Vec::new(),
+        }
+    }
+
+    /// Whether none of the arguments of the message refers to an object of a specified interface
+    pub fn all_null(&self) -> bool {
+        self.args
+            .iter()
+            .all(|a| !((a.typ == Type::Object || a.typ == Type::NewId) && a.interface.is_some()))
+    }
+}
+
+impl Default for Message {
+    fn default() -> Message {
+        Message::new()
+    }
+}
+
+/// An argument of a message
+#[derive(Clone, Debug)]
+pub struct Arg {
+    /// Name of the argument
+    pub name: String,
+    /// Type of the argument
+    pub typ: Type,
+    /// Interface of the object, for `object` and `new_id` arguments
+    pub interface: Option<String>,
+    /// Short description of the argument
+    pub summary: Option<String>,
+    /// Description of the argument
+    pub description: Option<(String, String)>,
+    /// Whether a null value is allowed, for `object` and `string` arguments
+    pub allow_null: bool,
+    /// The enum giving the meaning of the values, either `enum` or `interface.enum`
+    pub enum_: Option<String>,
+}
+
+impl Arg {
+    /// An `object` argument without name
+    pub fn new() -> Arg {
+        Arg {
+            name: String::new(),
+            typ: Type::Object,
+            interface: None,
+            summary: None,
+            description: None,
+            allow_null: false,
+            enum_: None,
+        }
+    }
+}
+
+impl Default for Arg {
+    fn default() -> Arg {
+        Arg::new()
+    }
+}
+
+/// An enum of an interface
+#[derive(Clone, Debug)]
+pub struct Enum {
+    /// Name of the enum
+    pub name: String,
+    /// Version of the interface the enum was introduced in
+    pub since: u16,
+    /// Description of the enum
+    pub description: Option<(String, String)>,
+    /// The values of the enum
+    pub entries: Vec<Entry>,
+    /// Whether the values are flags, to be combined together
+    pub bitfield: bool,
+}
+
+impl Enum {
+    /// An empty enum, present since version 1
+    pub fn new() -> Enum {
+        Enum {
+            name: String::new(),
+            since: 1,
+            description: None,
+            entries: Vec::new(),
+            bitfield: false,
+        }
+    }
+}
+
+impl Default for Enum {
+    fn default() -> Enum {
+        Enum::new()
+    }
+}
+
+/// A value of an enum
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// Name of the value
+    pub name: String,
+    /// The value itself
+    pub value: u32,
+    /// Version of the interface the value was introduced in
+    pub since: u16,
+    /// Description of the value
+    pub description: Option<(String, String)>,
+    /// Short description of the value
+    pub summary: Option<String>,
+}
+
+impl Entry {
+    /// A zero value, present since version 1
+    pub fn new() -> Entry {
+        Entry { name: String::new(), value: 0, since: 1, description: None, summary: None }
+    }
+}
+
+impl Default for Entry {
+    fn default() -> Entry {
+        Entry::new()
+    }
+}
+
+/// The type of an argument
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Type {
+    /// A signed integer
+    Int,
+    /// An unsigned integer
+    Uint,
+    /// A fixed-point number
+    Fixed,
+    /// A string
+    String,
+    /// An existing object
+    Object,
+    /// An object created by the message
+    NewId,
+    /// An array of bytes
+    Array,
+    /// A file descriptor
+    Fd,
+    /// Not an argument type, marks the messages destroying their object
+    Destructor,
+}
+
+impl Type {
+    /// Whether a value of this type can be null
+    pub fn nullable(self) -> bool {
+        matches!(self, Type::String | Type::Object)
+    }
+
+    /// The name of the type in protocol files
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Int => "int",
+            Type::Uint => "uint",
+            Type::Fixed => "fixed",
+            Type::String => "string",
+            Type::Object => "object",
+            Type::NewId => "new_id",
+            Type::Array => "array",
+            Type::Fd => "fd",
+            Type::Destructor => "destructor",
+        }
+    }
+}
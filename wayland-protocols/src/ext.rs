@@ -2,7 +2,7 @@
 
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "ext-idle-notify")]
 pub mod idle_notify {
     //! This protocol allows clients to monitor user idle status.
 
@@ -15,7 +15,7 @@ pub mod idle_notify {
     }
 }
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "ext-session-lock")]
 pub mod session_lock {
     //! This protocol allows for a privileged Wayland client to lock the session
     //! and display arbitrary graphics while the session is locked.
@@ -36,9 +36,12 @@ pub mod session_lock {
             []
         );
     }
+
+    #[cfg(feature = "server")]
+    pub mod helpers;
 }
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "ext-foreign-toplevel-list")]
 pub mod foreign_toplevel_list {
     //! The purpose of this protocol is to provide protocol object handles for toplevels, possibly
     //! originating from another client.
@@ -58,3 +61,55 @@ pub mod foreign_toplevel_list {
         );
     }
 }
+
+#[cfg(feature = "ext-image-capture-source")]
+pub mod image_capture_source {
+    //! This protocol serves as an intermediary between capturing protocols and
+    //! potential image capture sources such as outputs and toplevels.
+    //!
+    //! This protocol may be extended to support more image capture sources in the
+    //! future, thereby adding those image capture sources to other protocols that
+    //! use the image capture source object without having to modify those
+    //! protocols.
+
+    #[allow(missing_docs)]
+    pub mod v1 {
+        wayland_protocol!(
+            "./protocols/staging/ext-image-capture-source/ext-image-capture-source-v1.xml",
+            [crate::ext::foreign_toplevel_list::v1]
+        );
+    }
+}
+
+#[cfg(feature = "ext-image-copy-capture")]
+pub mod image_copy_capture {
+    //! This protocol allows clients to ask the compositor to capture image sources
+    //! such as outputs and toplevels into user submitted buffers.
+
+    #[allow(missing_docs)]
+    pub mod v1 {
+        wayland_protocol!(
+            "./protocols/staging/ext-image-copy-capture/ext-image-copy-capture-v1.xml",
+            [crate::ext::image_capture_source::v1]
+        );
+    }
+}
+
+#[cfg(feature = "ext-workspace")]
+pub mod workspace {
+    //! List and control workspaces
+    //!
+    //! Workspaces, also called virtual desktops, are groups of surfaces. A
+    //! compositor with a concept of workspaces may only show some such groups of
+    //! surfaces (those of 'active' workspaces) at a time. This protocol lets
+    //! pagers and taskbars enumerate workspaces, grouped in workspace groups,
+    //! and request the compositor to activate, deactivate, remove or assign them.
+
+    #[allow(missing_docs)]
+    pub mod v1 {
+        wayland_protocol!(
+            "./protocols/staging/ext-workspace/ext-workspace-v1.xml",
+            []
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Helpers for the server side state of a locked session
+//!
+//! A session is locked by a single `ext_session_lock_v1` object at a time. Once it is sent the `locked`
+//! event, the session stays locked until this object unlocks it, even if its client dies: another client
+//! may then take over the lock. [`SessionLock`] follows these rules and the lifecycle of the lock
+//! surfaces, and checks the requests of the lock client against the protocol.
+//!
+//! While the session [`is_locked()`](SessionLock::is_locked), the compositor must only show the lock
+//! surface of each output, given by [`output_surface()`](SessionLock::output_surface), or a solid color,
+//! and must not give input to the other clients. Their requests which must not take effect while locked,
+//! such as `xdg_activation_v1.activate`, can be dropped by an interceptor built with
+//! [`deny_while_locked()`].
+//!
+//! The errors are the values of the generated `Error` enums of `ext_session_lock_v1` and
+//! `ext_session_lock_surface_v1`, to give to the `post_typed_error` method of the object the request
+//! was sent to.
+
+use wayland_server::{
+    backend::ObjectId,
+    protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    Client, DataInit, DisplayHandle, Intercept, Resource,
+};
+
+use crate::ext::session_lock::v1::server::{
+    ext_session_lock_surface_v1::{self, ExtSessionLockSurfaceV1},
+    ext_session_lock_v1::{self, ExtSessionLockV1},
+};
+
+/// The state of the session
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum LockState {
+    /// The session is not locked
+    #[default]
+    Unlocked,
+    /// A lock was accepted, and the compositor hides the content of the session before confirming it
+    Locking,
+    /// The lock was sent the `locked` event
+    Locked,
+}
+
+#[derive(Debug)]
+struct LockSurface {
+    lock: ObjectId,
+    lock_surface: ExtSessionLockSurfaceV1,
+    surface: WlSurface,
+    output: WlOutput,
+    // the configures waiting to be acked, with their size
+    pending: Vec<(u32, (u32, u32))>,
+    // the size acked for the next commit
+    acked: Option<(u32, u32)>,
+    ever_acked: bool,
+}
+
+/// The lock of the session and its lock surfaces
+#[derive(Debug, Default)]
+pub struct SessionLock {
+    state: LockState,
+    // the lock the session is locked by, or about to be
+    lock: Option<ExtSessionLockV1>,
+    // the locks sent `finished` after `locked`, which must still be destroyed with `unlock_and_destroy`
+    revoked: Vec<ExtSessionLockV1>,
+    surfaces: Vec<LockSurface>,
+}
+
+impl SessionLock {
+    /// Create the state of an unlocked session
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The state of the session
+    pub fn state(&self) -> LockState {
+        self.state
+    }
+
+    /// Whether the content of the session must be hidden
+    ///
+    /// This is the case as soon as a lock is accepted, before the compositor confirms it with
+    /// [`confirm_locked()`](Self::confirm_locked).
+    pub fn is_locked(&self) -> bool {
+        self.state != LockState::Unlocked
+    }
+
+    /// Whether the client is the one the session is locked by
+    pub fn is_lock_client(&self, client: &Client) -> bool {
+        self.lock
+            .as_ref()
+            .and_then(Resource::client)
+            .map_or(false, |lock_client| lock_client == *client)
+    }
+
+    /// Apply an `ext_session_lock_manager_v1.lock` request
+    ///
+    /// The lock is accepted if the session has no lock, or if the client of its lock died, and is sent
+    /// `finished` otherwise. A lock taking over a locked session is sent `locked` right away. Returns
+    /// whether the lock was accepted.
+    pub fn lock(&mut self, lock: &ExtSessionLockV1) -> bool {
+        if self.lock.as_ref().map_or(false, Resource::is_alive) {
+            lock.finished();
+            return false;
+        }
+        // the lock surfaces of a dead client are not shown anymore
+        self.surfaces.clear();
+        if self.state == LockState::Locked {
+            lock.locked();
+        } else {
+            self.state = LockState::Locking;
+        }
+        self.lock = Some(lock.clone());
+        true
+    }
+
+    /// Send the `locked` event, once every output shows a frame without the content of the session
+    pub fn confirm_locked(&mut self) {
+        if self.state != LockState::Locking {
+            return;
+        }
+        self.state = LockState::Locked;
+        if let Some(ref lock) = self.lock {
+            lock.locked();
+        }
+    }
+
+    /// Unlock the session without the lock client, for example after an authentication done by the
+    /// compositor
+    ///
+    /// The lock is sent the `finished` event.
+    pub fn finish(&mut self) {
+        if let Some(lock) = self.lock.take() {
+            lock.finished();
+            if self.state == LockState::Locked {
+                self.revoked.retain(Resource::is_alive);
+                self.revoked.push(lock);
+            }
+        }
+        self.state = LockState::Unlocked;
+        self.surfaces.clear();
+    }
+
+    /// Apply an `ext_session_lock_v1.destroy` request
+    ///
+    /// A lock destroyed before being sent `locked` unlocks the session.
+    pub fn destroy(&mut self, lock: &ExtSessionLockV1) -> Result<(), ext_session_lock_v1::Error> {
+        if let Some(index) = self.revoked.iter().position(|revoked| revoked == lock) {
+            self.revoked.remove(index);
+            return Err(ext_session_lock_v1::Error::InvalidDestroy);
+        }
+        if self.lock.as_ref() == Some(lock) {
+            if self.state == LockState::Locked {
+                return Err(ext_session_lock_v1::Error::InvalidDestroy);
+            }
+            self.lock = None;
+            self.state = LockState::Unlocked;
+            self.surfaces.clear();
+        }
+        self.surfaces.retain(|surface| surface.lock != lock.id());
+        Ok(())
+    }
+
+    /// Apply an `ext_session_lock_v1.unlock_and_destroy` request
+    pub fn unlock_and_destroy(
+        &mut self,
+        lock: &ExtSessionLockV1,
+    ) -> Result<(), ext_session_lock_v1::Error> {
+        if let Some(index) = self.revoked.iter().position(|revoked| revoked == lock) {
+            self.revoked.remove(index);
+            return Ok(());
+        }
+        if self.lock.as_ref() != Some(lock) || self.state != LockState::Locked {
+            return Err(ext_session_lock_v1::Error::InvalidUnlock);
+        }
+        self.lock = None;
+        self.state = LockState::Unlocked;
+        self.surfaces.clear();
+        Ok(())
+    }
+
+    /// Apply an `ext_session_lock_v1.get_lock_surface` request
+    ///
+    /// Checking that the surface has no role and no buffer is left to the compositor, which knows the
+    /// state of its surfaces. The new lock surface must then be sent its first configure with
+    /// [`configure()`](Self::configure).
+    pub fn get_lock_surface(
+        &mut self,
+        lock: &ExtSessionLockV1,
+        lock_surface: &ExtSessionLockSurfaceV1,
+        surface: &WlSurface,
+        output: &WlOutput,
+    ) -> Result<(), ext_session_lock_v1::Error> {
+        let lock = lock.id();
+        if self.surfaces.iter().any(|s| s.lock == lock && s.output == *output) {
+            return Err(ext_session_lock_v1::Error::DuplicateOutput);
+        }
+        self.surfaces.push(LockSurface {
+            lock,
+            lock_surface: lock_surface.clone(),
+            surface: surface.clone(),
+            output: output.clone(),
+            pending: Vec::new(),
+            acked: None,
+            ever_acked: false,
+        });
+        Ok(())
+    }
+
+    /// Send a configure to the lock surfaces of an output, with the size they must have
+    ///
+    /// Returns whether the output has a lock surface.
+    pub fn configure(&mut self, output: &WlOutput, width: u32, height: u32, serial: u32) -> bool {
+        let mut configured = false;
+        for surface in self.surfaces.iter_mut().filter(|s| s.output == *output) {
+            surface.lock_surface.configure(serial, width, height);
+            surface.pending.push((serial, (width, height)));
+            configured = true;
+        }
+        configured
+    }
+
+    /// Apply an `ext_session_lock_surface_v1.ack_configure` request
+    ///
+    /// Acking a configure drops the older ones.
+    pub fn ack_configure(
+        &mut self,
+        lock_surface: &ExtSessionLockSurfaceV1,
+        serial: u32,
+    ) -> Result<(), ext_session_lock_surface_v1::Error> {
+        let surface = match self.surfaces.iter_mut().find(|s| s.lock_surface == *lock_surface) {
+            Some(surface) => surface,
+            None => return Ok(()),
+        };
+        let index = surface
+            .pending
+            .iter()
+            .position(|&(pending, _)| pending == serial)
+            .ok_or(ext_session_lock_surface_v1::Error::InvalidSerial)?;
+        surface.acked = Some(surface.pending[index].1);
+        surface.ever_acked = true;
+        surface.pending.drain(..=index);
+        Ok(())
+    }
+
+    /// Check a `wl_surface.commit` of a surface, which may be a lock surface
+    ///
+    /// `size` is the size of the surface after the commit, or `None` if it has no buffer attached. The
+    /// commits of the other surfaces are always valid.
+    pub fn commit(
+        &mut self,
+        surface: &WlSurface,
+        size: Option<(u32, u32)>,
+    ) -> Result<(), ext_session_lock_surface_v1::Error> {
+        let surface = match self.surfaces.iter_mut().find(|s| s.surface == *surface) {
+            Some(surface) => surface,
+            None => return Ok(()),
+        };
+        if !surface.ever_acked {
+            return Err(ext_session_lock_surface_v1::Error::CommitBeforeFirstAck);
+        }
+        let size = size.ok_or(ext_session_lock_surface_v1::Error::NullBuffer)?;
+        match surface.acked.take() {
+            Some(acked) if acked != size => {
+                Err(ext_session_lock_surface_v1::Error::DimensionsMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Forget a lock surface, once destroyed
+    ///
+    /// The compositor shows a solid color on its output instead.
+    pub fn lock_surface_destroyed(&mut self, lock_surface: &ExtSessionLockSurfaceV1) {
+        self.surfaces.retain(|s| s.lock_surface != *lock_surface);
+    }
+
+    /// The lock surface role of a surface, if it has one
+    ///
+    /// The errors of [`commit()`](Self::commit) are to be posted on the lock surface.
+    pub fn lock_surface(&self, surface: &WlSurface) -> Option<&ExtSessionLockSurfaceV1> {
+        self.surfaces.iter().find(|s| s.surface == *surface).map(|s| &s.lock_surface)
+    }
+
+    /// The surface to show on an output while the session is locked
+    ///
+    /// A solid color is to be shown if it has none, or if it has no content yet.
+    pub fn output_surface(&self, output: &WlOutput) -> Option<&WlSurface> {
+        let lock = self.lock.as_ref()?.id();
+        self.surfaces.iter().find(|s| s.lock == lock && s.output == *output).map(|s| &s.surface)
+    }
+}
+
+/// Build a request interceptor dropping some requests of the clients while the session is locked
+///
+/// The interceptor is given to [`DataInit::init_with_interceptor()`]. The requests for which `denied`
+/// returns `true` are consumed while the session is locked, unless they come from the lock client. The
+/// requests creating objects must not be denied, as their objects would not be initialized.
+///
+/// ```no_run
+/// # use wayland_protocols::ext::session_lock::helpers::{deny_while_locked, SessionLock};
+/// # use wayland_server::protocol::wl_data_device::{self, WlDataDevice};
+/// # struct State { lock: SessionLock }
+/// # impl AsRef<SessionLock> for State {
+/// #     fn as_ref(&self) -> &SessionLock { &self.lock }
+/// # }
+/// # impl wayland_server::Dispatch<WlDataDevice, ()> for State {
+/// #     fn request(
+/// #         _: &mut Self, _: &wayland_server::Client, _: &WlDataDevice, _: wl_data_device::Request, _: &(),
+/// #         _: &wayland_server::DisplayHandle, _: &mut wayland_server::DataInit<'_, Self>,
+/// #     ) {}
+/// # }
+/// # fn get_data_device(id: wayland_server::New<WlDataDevice>, data_init: &mut wayland_server::DataInit<'_, State>) {
+/// // the clipboard cannot be changed while the session is locked
+/// data_init.init_with_interceptor(
+///     id,
+///     (),
+///     deny_while_locked::<WlDataDevice, _>(|request| {
+///         matches!(request, wl_data_device::Request::SetSelection { .. })
+///     }),
+/// );
+/// # }
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn deny_while_locked<I, D>(
+    denied: fn(&I::Request) -> bool,
+) -> impl Fn(
+    &mut D,
+    &Client,
+    &I,
+    I::Request,
+    &DisplayHandle,
+    &mut DataInit<'_, D>,
+) -> Intercept<I::Request>
+       + Send
+       + Sync
+       + 'static
+where
+    I: Resource + 'static,
+    D: AsRef<SessionLock> + 'static,
+{
+    move |state, client, _, request, _, _| {
+        let lock = state.as_ref();
+        if lock.is_locked() && !lock.is_lock_client(client) && denied(&request) {
+            Intercept::Consume
+        } else {
+            Intercept::Pass(request)
+        }
+    }
+}
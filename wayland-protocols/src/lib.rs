@@ -7,6 +7,10 @@
 //! protocol. The creation of these modules (and the dependency on the associated crate) is
 //! controlled by the two cargo features `client` and `server`.
 //!
+//! With the `serde` cargo feature, the messages of the protocols implement `serde::Serialize`, and
+//! their enums and bitflags `serde::Serialize` and `serde::Deserialize`. See the `serialize` modules
+//! of wayland-client and wayland-server.
+//!
 //! ## Protocol categories
 //!
 //! The protocols provided in this crate are grouped in 4 main categories:
@@ -36,6 +40,14 @@
 //! are still under that umbrella. We can expect them to be replaced by staging and stable protocols in the
 //! long term, but in the meantime you can enable them with the `unstable` cargo feature.
 //!
+//! ## Fine-grained features
+//!
+//! Each staging and unstable protocol can also be enabled on its own, with a feature named after
+//! its module, like `wp-linux-dmabuf` for `wp::linux_dmabuf` or `ext-session-lock` for
+//! `ext::session_lock`. This avoids generating the code of all the protocols of a category when
+//! only a few of them are used. The `staging` and `unstable` features enable all the protocols of
+//! their category, and the protocols depending on others enable them.
+//!
 //! ## Other protocols
 //!
 //! Additionally, more protocol extensions are provided here:
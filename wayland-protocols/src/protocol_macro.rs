@@ -25,7 +25,10 @@ macro_rules! wayland_protocol(
                 }
                 use self::__interfaces::*;
 
+                #[cfg(not(feature = "serde"))]
                 wayland_scanner::generate_client_code!($path);
+                #[cfg(feature = "serde")]
+                wayland_scanner::generate_client_code!($path, serde);
             }
 
             #[cfg(feature = "server")]
@@ -42,7 +45,10 @@ macro_rules! wayland_protocol(
                 }
                 use self::__interfaces::*;
 
+                #[cfg(not(feature = "serde"))]
                 wayland_scanner::generate_server_code!($path);
+                #[cfg(feature = "serde")]
+                wayland_scanner::generate_server_code!($path, serde);
             }
         }
     }
@@ -2,7 +2,7 @@
 
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "wp-content-type")]
 pub mod content_type {
     //! This protocol allows a client to describe the kind of content a surface
     //! will display, to allow the compositor to optimize its behavior for it.
@@ -16,7 +16,7 @@ pub mod content_type {
     }
 }
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "wp-drm-lease")]
 pub mod drm_lease {
     //! This protocol is used by Wayland compositors which act as Direct
     //! Renderering Manager (DRM) masters to lease DRM resources to Wayland
@@ -53,7 +53,7 @@ pub mod drm_lease {
     }
 }
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "wp-tearing-control")]
 pub mod tearing_control {
     //! This protocol provides a way for clients to indicate whether
     //! or not their content is suitable for this kind of presentation.
@@ -70,7 +70,7 @@ pub mod tearing_control {
     }
 }
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "wp-fractional-scale")]
 pub mod fractional_scale {
     //! This protocol allows a compositor to suggest for surfaces to render at
     //! fractional scales.
@@ -100,7 +100,7 @@ pub mod fractional_scale {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-fullscreen-shell")]
 pub mod fullscreen_shell {
     //! Fullscreen shell protocol
 
@@ -113,7 +113,7 @@ pub mod fullscreen_shell {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-idle-inhibit")]
 pub mod idle_inhibit {
     //! Screensaver inhibition protocol
 
@@ -124,9 +124,12 @@ pub mod idle_inhibit {
             []
         );
     }
+
+    #[cfg(feature = "server")]
+    pub mod helpers;
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-input-method")]
 pub mod input_method {
     //! Input method protocol
 
@@ -139,7 +142,7 @@ pub mod input_method {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-input-timestamps")]
 pub mod input_timestamps {
     //! Input timestamps protocol
 
@@ -152,7 +155,7 @@ pub mod input_timestamps {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-keyboard-shortcuts-inhibit")]
 pub mod keyboard_shortcuts_inhibit {
     //! Protocol for inhibiting the compositor keyboard shortcuts
     //!
@@ -169,7 +172,7 @@ pub mod keyboard_shortcuts_inhibit {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-linux-dmabuf")]
 pub mod linux_dmabuf {
     //! Linux DMA-BUF protocol
 
@@ -180,9 +183,11 @@ pub mod linux_dmabuf {
             []
         );
     }
+
+    pub mod feedback;
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-linux-explicit-synchronization")]
 pub mod linux_explicit_synchronization {
     //! Linux explicit synchronization protocol
 
@@ -195,7 +200,7 @@ pub mod linux_explicit_synchronization {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-pointer-constraints")]
 pub mod pointer_constraints {
     //! protocol for constraining pointer motions
     //!
@@ -216,9 +221,11 @@ pub mod pointer_constraints {
             []
         );
     }
+
+    pub mod region;
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-pointer-gestures")]
 pub mod pointer_gestures {
     //! Pointer gestures protocol
 
@@ -240,9 +247,12 @@ pub mod presentation_time {
         "./protocols/stable/presentation-time/presentation-time.xml",
         []
     );
+
+    pub mod feedback;
+    pub mod stats;
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-primary-selection")]
 pub mod primary_selection {
     //! Primary selection protocol
 
@@ -255,7 +265,7 @@ pub mod primary_selection {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-relative-pointer")]
 pub mod relative_pointer {
     //! protocol for relative pointer motion events
     //!
@@ -282,7 +292,7 @@ pub mod relative_pointer {
     }
 }
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "wp-single-pixel-buffer")]
 pub mod single_pixel_buffer {
     //! This protocol extension allows clients to create single-pixel buffers.
     //!
@@ -299,7 +309,7 @@ pub mod single_pixel_buffer {
     }
 }
 
-#[cfg(all(feature = "staging", feature = "unstable"))]
+#[cfg(feature = "wp-cursor-shape")]
 pub mod cursor_shape {
     //! This protocol extension offers a simpler way for clients to set a cursor.
 
@@ -310,9 +320,11 @@ pub mod cursor_shape {
             [crate::wp::tablet::zv2]
         );
     }
+
+    pub mod names;
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-tablet")]
 pub mod tablet {
     //! Wayland protocol for graphics tablets
     //!
@@ -408,9 +420,12 @@ pub mod tablet {
             []
         );
     }
+
+    #[cfg(feature = "client")]
+    pub mod tool;
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "wp-text-input")]
 pub mod text_input {
     //! Text input protocol
 
@@ -438,9 +453,11 @@ pub mod viewporter {
     //! dimensions from the size of the buffer.
 
     wayland_protocol!("./protocols/stable/viewporter/viewporter.xml", []);
+
+    pub mod helpers;
 }
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "wp-security-context")]
 pub mod security_context {
     //! This interface allows a client to register a new Wayland connection to
     //! the compositor and attach a security context to it.
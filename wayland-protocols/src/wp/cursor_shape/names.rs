@@ -0,0 +1,137 @@
+//! Conversion between cursor names and `wp_cursor_shape_device_v1` shapes
+//!
+//! The shapes of the protocol are named after the CSS cursor names, while Xcursor themes also
+//! provide a number of legacy names for the same cursors. This module maps both to the `shape`
+//! enum, so that clients can use the protocol when the compositor supports it, and fall back to
+//! loading the cursor from a theme otherwise.
+
+use std::convert::TryFrom;
+
+/// The shapes of the protocol, with their CSS name followed by legacy Xcursor aliases
+const SHAPES: &[(u32, &[&str])] = &[
+    (1, &["default", "left_ptr", "arrow", "top_left_arrow"]),
+    (2, &["context-menu"]),
+    (3, &["help", "question_arrow", "whats_this", "left_ptr_help"]),
+    (4, &["pointer", "hand2", "hand", "pointing_hand"]),
+    (5, &["progress", "left_ptr_watch", "half-busy"]),
+    (6, &["wait", "watch"]),
+    (7, &["cell", "plus"]),
+    (8, &["crosshair", "cross", "tcross"]),
+    (9, &["text", "xterm", "ibeam"]),
+    (10, &["vertical-text"]),
+    (11, &["alias", "dnd-link", "link"]),
+    (12, &["copy", "dnd-copy"]),
+    (13, &["move", "dnd-move"]),
+    (14, &["no-drop", "dnd-no-drop"]),
+    (15, &["not-allowed", "crossed_circle", "forbidden"]),
+    (16, &["grab", "openhand", "hand1"]),
+    (17, &["grabbing", "closedhand"]),
+    (18, &["e-resize", "right_side"]),
+    (19, &["n-resize", "top_side"]),
+    (20, &["ne-resize", "top_right_corner"]),
+    (21, &["nw-resize", "top_left_corner"]),
+    (22, &["s-resize", "bottom_side"]),
+    (23, &["se-resize", "bottom_right_corner"]),
+    (24, &["sw-resize", "bottom_left_corner"]),
+    (25, &["w-resize", "left_side"]),
+    (26, &["ew-resize", "h_double_arrow", "sb_h_double_arrow"]),
+    (27, &["ns-resize", "v_double_arrow", "sb_v_double_arrow"]),
+    (28, &["nesw-resize", "fd_double_arrow", "size_bdiag"]),
+    (29, &["nwse-resize", "bd_double_arrow", "size_fdiag"]),
+    (30, &["col-resize", "split_h"]),
+    (31, &["row-resize", "split_v"]),
+    (32, &["all-scroll", "fleur", "size_all"]),
+    (33, &["zoom-in"]),
+    (34, &["zoom-out"]),
+];
+
+/// The value of the `shape` enum entry for a CSS or Xcursor cursor name
+///
+/// The protocol entry names (such as `nwse_resize`) are accepted as well.
+pub fn shape_value(name: &str) -> Option<u32> {
+    SHAPES
+        .iter()
+        .find(|(_, names)| names.contains(&name) || names[0].replace('-', "_") == name)
+        .map(|&(value, _)| value)
+}
+
+/// The CSS name of the cursor for a value of the `shape` enum
+pub fn css_name(value: u32) -> Option<&'static str> {
+    xcursor_names(value).first().copied()
+}
+
+/// The names to look up in an Xcursor theme for a value of the `shape` enum, by preference
+///
+/// The CSS name comes first, followed by the legacy names that themes may provide instead. This
+/// is empty if `value` is not a known shape.
+pub fn xcursor_names(value: u32) -> &'static [&'static str] {
+    SHAPES.iter().find(|&&(v, _)| v == value).map(|&(_, names)| names).unwrap_or(&[])
+}
+
+macro_rules! impl_shape_names {
+    ($shape:ty) => {
+        impl $shape {
+            /// The shape for a CSS or Xcursor cursor name, see [`shape_value()`]
+            pub fn from_name(name: &str) -> Option<Self> {
+                shape_value(name).and_then(|value| Self::try_from(value).ok())
+            }
+
+            /// The CSS name of this cursor shape
+            pub fn css_name(self) -> &'static str {
+                // all the shapes of the protocol are in the table
+                css_name(self as u32).unwrap_or("default")
+            }
+
+            /// The names to look up in an Xcursor theme for this shape, see [`xcursor_names()`]
+            pub fn xcursor_names(self) -> &'static [&'static str] {
+                xcursor_names(self as u32)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "client")]
+impl_shape_names!(super::v1::client::wp_cursor_shape_device_v1::Shape);
+#[cfg(feature = "server")]
+impl_shape_names!(super::v1::server::wp_cursor_shape_device_v1::Shape);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        assert_eq!(shape_value("default"), Some(1));
+        assert_eq!(shape_value("left_ptr"), Some(1));
+        assert_eq!(shape_value("nwse-resize"), Some(29));
+        assert_eq!(shape_value("nwse_resize"), Some(29));
+        assert_eq!(shape_value("bd_double_arrow"), Some(29));
+        assert_eq!(shape_value("unknown"), None);
+        assert_eq!(css_name(16), Some("grab"));
+        assert_eq!(css_name(0), None);
+        assert_eq!(xcursor_names(6), &["wait", "watch"]);
+        assert!(xcursor_names(100).is_empty());
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let mut all: Vec<&str> =
+            SHAPES.iter().flat_map(|(_, names)| names.iter().copied()).collect();
+        let len = all.len();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), len);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn client_shapes() {
+        use crate::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape;
+        assert_eq!(Shape::from_name("hand2"), Some(Shape::Pointer));
+        assert_eq!(Shape::NsResize.css_name(), "ns-resize");
+        for value in 1..=34 {
+            let shape = Shape::try_from(value).unwrap();
+            assert_eq!(Shape::from_name(shape.css_name()), Some(shape));
+        }
+    }
+}
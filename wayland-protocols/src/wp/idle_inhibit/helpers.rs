@@ -0,0 +1,71 @@
+//! Helpers for the server side state of the idle inhibitors
+//!
+//! A `zwp_idle_inhibitor_v1` prevents the session from going idle as long as its surface is visible:
+//! the inhibitors of a surface which is unmapped, occluded or on a turned off output are not honored.
+//! [`IdleInhibitors`] tracks the inhibitors and the visibility of their surfaces, which the compositor
+//! updates as it renders them, and tells whether the session must stay awake.
+
+use std::collections::{HashMap, HashSet};
+
+use wayland_server::{backend::ObjectId, protocol::wl_surface::WlSurface, Resource};
+
+use crate::wp::idle_inhibit::zv1::server::zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1;
+
+/// The idle inhibitors of the clients
+#[derive(Debug, Default)]
+pub struct IdleInhibitors {
+    // the surface of each inhibitor
+    inhibitors: HashMap<ObjectId, ObjectId>,
+    visible: HashSet<ObjectId>,
+}
+
+impl IdleInhibitors {
+    /// Create a state without any inhibitor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the inhibitor of a `zwp_idle_inhibit_manager_v1.create_inhibitor` request
+    pub fn create_inhibitor(&mut self, inhibitor: &ZwpIdleInhibitorV1, surface: &WlSurface) {
+        self.inhibitors.insert(inhibitor.id(), surface.id());
+    }
+
+    /// Remove an inhibitor, once destroyed
+    pub fn inhibitor_destroyed(&mut self, inhibitor: &ZwpIdleInhibitorV1) {
+        self.inhibitors.remove(&inhibitor.id());
+    }
+
+    /// Set whether a surface is visible to the user
+    ///
+    /// The surfaces are not visible until set so.
+    pub fn set_visible(&mut self, surface: &WlSurface, visible: bool) {
+        if visible {
+            self.visible.insert(surface.id());
+        } else {
+            self.visible.remove(&surface.id());
+        }
+    }
+
+    /// Forget a surface, once destroyed
+    ///
+    /// Its inhibitors are no longer honored, but are only removed once destroyed.
+    pub fn surface_destroyed(&mut self, surface: &WlSurface) {
+        self.visible.remove(&surface.id());
+    }
+
+    /// The number of inhibitors honored, whose surface is visible
+    pub fn active_count(&self) -> usize {
+        self.inhibitors.values().filter(|surface| self.visible.contains(surface)).count()
+    }
+
+    /// Whether the session must not go idle
+    pub fn is_inhibited(&self) -> bool {
+        self.inhibitors.values().any(|surface| self.visible.contains(surface))
+    }
+
+    /// Whether a surface has inhibitors honored
+    pub fn inhibits(&self, surface: &WlSurface) -> bool {
+        let surface = surface.id();
+        self.visible.contains(&surface) && self.inhibitors.values().any(|s| *s == surface)
+    }
+}
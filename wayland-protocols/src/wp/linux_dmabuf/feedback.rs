@@ -0,0 +1,404 @@
+//! Helpers for the feedback of `zwp_linux_dmabuf_v1` version 4
+//!
+//! Since version 4, the formats and modifiers a compositor supports are sent through a
+//! `zwp_linux_dmabuf_feedback_v1`: a table of all the format and modifier pairs is shared in a file,
+//! and each tranche of preference refers to its pairs with their 16-bit indices in the table.
+//!
+//! On the server side, [`DmabufFeedback`] builds the table of a list of [`Tranche`]s and sends it to
+//! the feedback objects. On the client side, [`ClientDmabufFeedback`] is the user data of a feedback,
+//! which reads the table and gives back the tranches once all the parameters are received.
+
+use std::convert::TryInto;
+use std::fmt;
+
+#[cfg(feature = "client")]
+pub use self::client::ClientDmabufFeedback;
+#[cfg(feature = "server")]
+pub use self::server::DmabufFeedback;
+
+// a format, 4 bytes of padding and a modifier
+const ENTRY_SIZE: usize = 16;
+
+/// A DRM pixel format code, as defined in `drm_fourcc.h`
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fourcc(pub u32);
+
+impl Fourcc {
+    /// The format code of four characters, such as `b"XR24"`
+    pub fn from_chars(chars: [u8; 4]) -> Self {
+        Fourcc(u32::from_le_bytes(chars))
+    }
+
+    /// The four characters of the format code
+    pub fn chars(self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+}
+
+impl fmt::Debug for Fourcc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fourcc({})", self)
+    }
+}
+
+impl fmt::Display for Fourcc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.chars() {
+            if c.is_ascii_graphic() || c == b' ' {
+                write!(f, "{}", c as char)?;
+            } else {
+                write!(f, "\\x{:02x}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A DRM format modifier, as defined in `drm_fourcc.h`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Modifier(pub u64);
+
+impl Modifier {
+    /// `DRM_FORMAT_MOD_LINEAR`, a buffer without tiling
+    pub const LINEAR: Modifier = Modifier(0);
+    /// `DRM_FORMAT_MOD_INVALID`, the format is supported with an implicit modifier
+    pub const INVALID: Modifier = Modifier(0x00ff_ffff_ffff_ffff);
+}
+
+/// A tranche of preference of the dmabuf feedback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tranche {
+    /// The `dev_t` of the device the buffers of this tranche should be allocated for
+    pub target_device: u64,
+    /// Whether the compositor may scan out the buffers of this tranche directly
+    pub scanout: bool,
+    /// The format and modifier pairs of this tranche
+    pub formats: Vec<(Fourcc, Modifier)>,
+}
+
+/// The parameters sent to a dmabuf feedback object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feedback {
+    /// The `dev_t` of the main device of the compositor
+    pub main_device: u64,
+    /// The tranches, in decreasing order of preference
+    pub tranches: Vec<Tranche>,
+}
+
+/// Serialize format and modifier pairs to the format of the format table
+pub fn encode_table(formats: &[(Fourcc, Modifier)]) -> Vec<u8> {
+    let mut table = Vec::with_capacity(formats.len() * ENTRY_SIZE);
+    for &(format, modifier) in formats {
+        table.extend_from_slice(&format.0.to_ne_bytes());
+        table.extend_from_slice(&[0; 4]);
+        table.extend_from_slice(&modifier.0.to_ne_bytes());
+    }
+    table
+}
+
+/// Parse the contents of a format table
+///
+/// A trailing incomplete entry is ignored.
+pub fn decode_table(table: &[u8]) -> Vec<(Fourcc, Modifier)> {
+    table
+        .chunks_exact(ENTRY_SIZE)
+        .map(|entry| {
+            let format = u32::from_ne_bytes(entry[..4].try_into().unwrap());
+            let modifier = u64::from_ne_bytes(entry[8..].try_into().unwrap());
+            (Fourcc(format), Modifier(modifier))
+        })
+        .collect()
+}
+
+#[cfg(any(feature = "server", test))]
+fn encode_device(device: u64) -> Vec<u8> {
+    device.to_ne_bytes().to_vec()
+}
+
+// `dev_t` is 64 bits on most platforms, but only 32 on some
+#[cfg(any(feature = "client", test))]
+fn decode_device(device: &[u8]) -> Option<u64> {
+    match device.len() {
+        8 => Some(u64::from_ne_bytes(device.try_into().unwrap())),
+        4 => Some(u32::from_ne_bytes(device.try_into().unwrap()) as u64),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "server")]
+mod server {
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+    use std::fs::File;
+    use std::io::{self, Write};
+    use std::os::unix::io::{AsFd, FromRawFd, RawFd};
+
+    use nix::{errno::Errno, fcntl, sys::mman, sys::stat};
+
+    use super::{encode_device, encode_table, Tranche};
+    use crate::wp::linux_dmabuf::zv1::server::zwp_linux_dmabuf_feedback_v1::{
+        TrancheFlags, ZwpLinuxDmabufFeedbackV1,
+    };
+
+    // number of indices sent per `tranche_formats` event, to stay well below the size limit of messages
+    const INDICES_PER_EVENT: usize = 1024;
+
+    /// The dmabuf feedback of a compositor, with its format table
+    ///
+    /// The table is shared with the clients through a sealed memory file, which is created once and sent
+    /// to every feedback object. To change the parameters, create a new `DmabufFeedback` and send it to
+    /// the feedback objects again.
+    #[derive(Debug)]
+    pub struct DmabufFeedback {
+        main_device: u64,
+        // the tranches, with the indices of their formats in the table
+        tranches: Vec<(u64, bool, Vec<u16>)>,
+        table: File,
+        table_size: u32,
+    }
+
+    impl DmabufFeedback {
+        /// Build the feedback of a main device and tranches, in decreasing order of preference
+        ///
+        /// The pairs common to several tranches are stored once in the table. Fails if there are more
+        /// than 65536 distinct pairs, or if the file of the table cannot be created.
+        pub fn new(main_device: u64, tranches: &[Tranche]) -> io::Result<Self> {
+            let mut formats = Vec::new();
+            let mut indices = HashMap::new();
+            let mut encoded_tranches = Vec::with_capacity(tranches.len());
+            for tranche in tranches {
+                let mut tranche_indices = Vec::with_capacity(tranche.formats.len());
+                for &pair in &tranche.formats {
+                    let index = *indices.entry(pair).or_insert_with(|| {
+                        formats.push(pair);
+                        formats.len() - 1
+                    });
+                    let index = u16::try_from(index).map_err(|_| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "too many formats for the table",
+                        )
+                    })?;
+                    tranche_indices.push(index);
+                }
+                encoded_tranches.push((tranche.target_device, tranche.scanout, tranche_indices));
+            }
+
+            let table = encode_table(&formats);
+            Ok(DmabufFeedback {
+                main_device,
+                tranches: encoded_tranches,
+                table: create_table_file(&table)?,
+                table_size: table.len() as u32,
+            })
+        }
+
+        /// The number of format and modifier pairs in the table
+        pub fn table_len(&self) -> usize {
+            self.table_size as usize / super::ENTRY_SIZE
+        }
+
+        /// Send all the parameters to a feedback object, followed by `done`
+        pub fn send(&self, feedback: &ZwpLinuxDmabufFeedbackV1) {
+            feedback.format_table(self.table.as_fd(), self.table_size);
+            feedback.main_device(encode_device(self.main_device));
+            for (target_device, scanout, indices) in &self.tranches {
+                feedback.tranche_target_device(encode_device(*target_device));
+                let flags = if *scanout { TrancheFlags::Scanout } else { TrancheFlags::empty() };
+                feedback.tranche_flags(flags);
+                for chunk in indices.chunks(INDICES_PER_EVENT) {
+                    feedback.tranche_formats(chunk.iter().flat_map(|i| i.to_ne_bytes()).collect());
+                }
+                feedback.tranche_done();
+            }
+            feedback.done();
+        }
+    }
+
+    // Create a read-only file with the contents of the table
+    fn create_table_file(table: &[u8]) -> io::Result<File> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        loop {
+            match nix::sys::memfd::memfd_create(
+                std::ffi::CStr::from_bytes_with_nul(b"wayland-dmabuf-feedback\0").unwrap(),
+                nix::sys::memfd::MemFdCreateFlag::MFD_CLOEXEC
+                    | nix::sys::memfd::MemFdCreateFlag::MFD_ALLOW_SEALING,
+            ) {
+                Ok(fd) => {
+                    let mut file = unsafe { File::from_raw_fd(fd) };
+                    file.write_all(table)?;
+                    // the clients cannot modify the table shared with the others
+                    fcntl::fcntl(
+                        fd,
+                        fcntl::FcntlArg::F_ADD_SEALS(
+                            fcntl::SealFlag::F_SEAL_SHRINK
+                                | fcntl::SealFlag::F_SEAL_GROW
+                                | fcntl::SealFlag::F_SEAL_WRITE
+                                | fcntl::SealFlag::F_SEAL_SEAL,
+                        ),
+                    )?;
+                    return Ok(file);
+                }
+                Err(Errno::EINTR) => continue,
+                Err(Errno::ENOSYS) => break,
+                Err(errno) => return Err(errno.into()),
+            }
+        }
+
+        // Fallback to an unlinked POSIX shared memory object, opened again in read-only mode
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        loop {
+            let name = format!(
+                "/wayland-dmabuf-feedback-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            );
+            let fd = match mman::shm_open(
+                name.as_str(),
+                fcntl::OFlag::O_CREAT
+                    | fcntl::OFlag::O_EXCL
+                    | fcntl::OFlag::O_RDWR
+                    | fcntl::OFlag::O_CLOEXEC,
+                stat::Mode::S_IRUSR | stat::Mode::S_IWUSR,
+            ) {
+                Ok(fd) => fd,
+                Err(Errno::EEXIST) | Err(Errno::EINTR) => continue,
+                Err(errno) => return Err(errno.into()),
+            };
+            let result = write_and_reopen(&name, fd, table);
+            let _ = mman::shm_unlink(name.as_str());
+            return result;
+        }
+    }
+
+    fn write_and_reopen(name: &str, fd: RawFd, table: &[u8]) -> io::Result<File> {
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        file.write_all(table)?;
+        let fd = mman::shm_open(
+            name,
+            fcntl::OFlag::O_RDONLY | fcntl::OFlag::O_CLOEXEC,
+            stat::Mode::empty(),
+        )?;
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+}
+
+#[cfg(feature = "client")]
+mod client {
+    use std::fs::File;
+    use std::os::unix::fs::FileExt;
+    use std::sync::Mutex;
+
+    use super::{decode_device, decode_table, Feedback, Fourcc, Modifier, Tranche};
+    use crate::wp::linux_dmabuf::zv1::client::zwp_linux_dmabuf_feedback_v1::{Event, TrancheFlags};
+
+    #[derive(Debug, Default)]
+    struct State {
+        table: Vec<(Fourcc, Modifier)>,
+        main_device: Option<u64>,
+        tranches: Vec<Tranche>,
+        current: Option<Tranche>,
+    }
+
+    /// The user data of a `zwp_linux_dmabuf_feedback_v1`, collecting its events
+    #[derive(Debug, Default)]
+    pub struct ClientDmabufFeedback {
+        state: Mutex<State>,
+    }
+
+    impl ClientDmabufFeedback {
+        /// Create the data of a feedback
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Process an event of the feedback
+        ///
+        /// Returns the parameters once they are all received, at each `done` event. The format table is
+        /// kept from one set of parameters to the next if the compositor does not send a new one. The
+        /// indices which are not in the table are ignored.
+        pub fn event(&self, event: Event) -> Option<Feedback> {
+            let mut state = self.state.lock().unwrap();
+            match event {
+                Event::FormatTable { fd, size } => {
+                    let mut table = vec![0; size as usize];
+                    // an unreadable table is handled as an empty one
+                    if File::from(fd).read_exact_at(&mut table, 0).is_err() {
+                        table.clear();
+                    }
+                    state.table = decode_table(&table);
+                }
+                Event::MainDevice { device } => state.main_device = decode_device(&device),
+                Event::TrancheTargetDevice { device } => {
+                    state.current_tranche().target_device = decode_device(&device).unwrap_or(0);
+                }
+                Event::TrancheFlags { flags } => {
+                    let flags = u32::from(flags);
+                    state.current_tranche().scanout = flags & TrancheFlags::Scanout.bits() != 0;
+                }
+                Event::TrancheFormats { indices } => {
+                    let State { ref table, ref mut current, .. } = *state;
+                    let tranche = current.get_or_insert_with(State::new_tranche);
+                    tranche.formats.extend(indices.chunks_exact(2).filter_map(|index| {
+                        table.get(u16::from_ne_bytes([index[0], index[1]]) as usize).copied()
+                    }));
+                }
+                Event::TrancheDone => {
+                    if let Some(tranche) = state.current.take() {
+                        state.tranches.push(tranche);
+                    }
+                }
+                Event::Done => {
+                    return Some(Feedback {
+                        main_device: state.main_device.take().unwrap_or(0),
+                        tranches: std::mem::take(&mut state.tranches),
+                    });
+                }
+            }
+            None
+        }
+    }
+
+    impl State {
+        fn new_tranche() -> Tranche {
+            Tranche { target_device: 0, scanout: false, formats: Vec::new() }
+        }
+
+        fn current_tranche(&mut self) -> &mut Tranche {
+            self.current.get_or_insert_with(State::new_tranche)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_roundtrip() {
+        let formats = [
+            (Fourcc::from_chars(*b"XR24"), Modifier::LINEAR),
+            (Fourcc::from_chars(*b"AR24"), Modifier::INVALID),
+        ];
+        let table = encode_table(&formats);
+        assert_eq!(table.len(), 32);
+        assert_eq!(&table[..4], b"XR24");
+        assert_eq!(decode_table(&table), formats);
+        // an incomplete entry is ignored
+        assert_eq!(decode_table(&table[..20]), formats[..1]);
+    }
+
+    #[test]
+    fn fourcc_display() {
+        assert_eq!(Fourcc::from_chars(*b"NV12").to_string(), "NV12");
+        assert_eq!(format!("{:?}", Fourcc(0x3231_564e)), "Fourcc(NV12)");
+        assert_eq!(Fourcc(0x0001_4241).to_string(), "AB\\x01\\x00");
+    }
+
+    #[test]
+    fn devices() {
+        assert_eq!(decode_device(&encode_device(0xe280)), Some(0xe280));
+        assert_eq!(decode_device(&0x1234u32.to_ne_bytes()), Some(0x1234));
+        assert_eq!(decode_device(&[1, 2]), None);
+    }
+}
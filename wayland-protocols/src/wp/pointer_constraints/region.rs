@@ -0,0 +1,166 @@
+//! Helpers for building the regions used by pointer constraints
+//!
+//! Both `lock_pointer` and `confine_pointer` take an optional `wl_region`, which is built by a
+//! sequence of `add` and `subtract` requests. [`RegionBuilder`] records such a sequence so that it
+//! can be either sent to the server as a `wl_region`, or evaluated locally (for example by a
+//! compositor that needs to know whether the pointer lies inside a constraint region).
+
+/// An axis-aligned rectangle in surface-local coordinates
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rect {
+    /// X coordinate of the top-left corner
+    pub x: i32,
+    /// Y coordinate of the top-left corner
+    pub y: i32,
+    /// Width of the rectangle
+    pub width: i32,
+    /// Height of the rectangle
+    pub height: i32,
+}
+
+impl Rect {
+    /// Create a new rectangle
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    /// Whether the rectangle has no area
+    ///
+    /// libwayland silently ignores rectangles with a negative size, so they are considered empty.
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    /// Whether the given point lies inside this rectangle
+    ///
+    /// The left and top edges are inclusive, right and bottom edges are exclusive.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        !self.is_empty()
+            && x >= self.x as f64
+            && y >= self.y as f64
+            && x < self.x as f64 + self.width as f64
+            && y < self.y as f64 + self.height as f64
+    }
+}
+
+/// An operation applied to a region
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RegionOp {
+    /// The rectangle is added to the region (`wl_region.add`)
+    Add(Rect),
+    /// The rectangle is subtracted from the region (`wl_region.subtract`)
+    Subtract(Rect),
+}
+
+/// A builder recording the operations making up a `wl_region`
+///
+/// Operations are kept in order, as a subtraction only affects the rectangles added before it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionBuilder {
+    ops: Vec<RegionOp>,
+}
+
+impl RegionBuilder {
+    /// Create a new empty region
+    pub fn new() -> RegionBuilder {
+        RegionBuilder { ops: Vec::new() }
+    }
+
+    /// Create a region made of the union of the given rectangles
+    pub fn from_rects<I: IntoIterator<Item = Rect>>(rects: I) -> RegionBuilder {
+        RegionBuilder { ops: rects.into_iter().map(RegionOp::Add).collect() }
+    }
+
+    /// Add a rectangle to the region
+    pub fn add_rect(mut self, rect: Rect) -> RegionBuilder {
+        self.push(RegionOp::Add(rect));
+        self
+    }
+
+    /// Subtract a rectangle from the region
+    pub fn subtract_rect(mut self, rect: Rect) -> RegionBuilder {
+        self.push(RegionOp::Subtract(rect));
+        self
+    }
+
+    /// Record an operation
+    ///
+    /// This is the non-consuming counterpart of [`add_rect()`](Self::add_rect) and
+    /// [`subtract_rect()`](Self::subtract_rect), meant to be used by servers accumulating the requests
+    /// received on a `wl_region`.
+    pub fn push(&mut self, op: RegionOp) {
+        self.ops.push(op);
+    }
+
+    /// The recorded operations, in order
+    pub fn ops(&self) -> &[RegionOp] {
+        &self.ops
+    }
+
+    /// Whether the given point (in surface-local coordinates) lies inside the region
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        // the last operation touching the point decides
+        self.ops
+            .iter()
+            .rev()
+            .find_map(|op| match op {
+                RegionOp::Add(rect) if rect.contains(x, y) => Some(true),
+                RegionOp::Subtract(rect) if rect.contains(x, y) => Some(false),
+                _ => None,
+            })
+            .unwrap_or(false)
+    }
+
+    /// Send the recorded operations to an existing `wl_region`
+    #[cfg(feature = "client")]
+    pub fn apply_to(&self, region: &wayland_client::protocol::wl_region::WlRegion) {
+        for op in &self.ops {
+            match *op {
+                RegionOp::Add(r) => region.add(r.x, r.y, r.width, r.height),
+                RegionOp::Subtract(r) => region.subtract(r.x, r.y, r.width, r.height),
+            }
+        }
+    }
+
+    /// Create a new `wl_region` from the given compositor, containing the recorded operations
+    #[cfg(feature = "client")]
+    pub fn create<D, U>(
+        &self,
+        compositor: &wayland_client::protocol::wl_compositor::WlCompositor,
+        qh: &wayland_client::QueueHandle<D>,
+        udata: U,
+    ) -> wayland_client::protocol::wl_region::WlRegion
+    where
+        D: wayland_client::Dispatch<wayland_client::protocol::wl_region::WlRegion, U> + 'static,
+        U: Send + Sync + 'static,
+    {
+        let region = compositor.create_region(qh, udata);
+        self.apply_to(&region);
+        region
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_contains() {
+        let region = RegionBuilder::from_rects([Rect::new(0, 0, 100, 100)])
+            .subtract_rect(Rect::new(25, 25, 50, 50))
+            .add_rect(Rect::new(40, 40, 10, 10));
+
+        assert!(region.contains(0.0, 0.0));
+        assert!(!region.contains(100.0, 50.0));
+        assert!(!region.contains(30.0, 30.0));
+        assert!(region.contains(45.5, 45.5));
+        assert!(!region.contains(-1.0, 10.0));
+    }
+
+    #[test]
+    fn empty_rects_are_ignored() {
+        let region = RegionBuilder::new().add_rect(Rect::new(0, 0, -10, 10));
+        assert!(!region.contains(-5.0, 5.0));
+        assert_eq!(region.ops().len(), 1);
+    }
+}
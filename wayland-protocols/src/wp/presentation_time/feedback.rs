@@ -0,0 +1,231 @@
+//! Helpers for the lifecycle of `wp_presentation_feedback` objects
+//!
+//! A feedback is requested for the next commit of a surface, and concerns the content of that commit:
+//! it is either presented, once a frame showing the content reaches the screen, or discarded, if the
+//! content is replaced by a later commit before being shown or if the surface is destroyed. Either event
+//! destroys the feedback.
+//!
+//! On the server side, [`SurfaceFeedback`] follows these rules for a surface, and hands the feedbacks of
+//! the content used by a frame to a [`FrameFeedback`], which sends them the outcome of the frame. On the
+//! client side, [`ClientFeedback`] is the user data of a feedback, which turns its events into a
+//! [`Presented`](super::stats::Presented) timing.
+
+#[cfg(feature = "client")]
+pub use self::client::{ClientFeedback, Outcome};
+#[cfg(feature = "server")]
+pub use self::server::{FrameFeedback, SurfaceFeedback};
+
+#[cfg(feature = "server")]
+mod server {
+    use wayland_server::{protocol::wl_output::WlOutput, Resource};
+
+    use crate::wp::presentation_time::server::wp_presentation_feedback::{
+        Kind, WpPresentationFeedback,
+    };
+
+    /// The presentation feedbacks of a surface
+    ///
+    /// The feedbacks created by `wp_presentation.feedback` are given to [`add()`](Self::add), and belong
+    /// to the content of the next [`commit()`](Self::commit) of the surface. When rendering a frame using
+    /// the content of the surface, take its feedbacks with [`take_committed()`](Self::take_committed).
+    #[derive(Debug, Default)]
+    pub struct SurfaceFeedback {
+        pending: Vec<WpPresentationFeedback>,
+        committed: Vec<WpPresentationFeedback>,
+    }
+
+    impl SurfaceFeedback {
+        /// Create the feedback state of a surface
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Add a feedback requested for the next commit of the surface
+        pub fn add(&mut self, feedback: WpPresentationFeedback) {
+            self.pending.push(feedback);
+        }
+
+        /// Apply a `wl_surface.commit`
+        ///
+        /// The feedbacks of the previous content which no frame used are discarded, as this content will
+        /// never be shown.
+        pub fn commit(&mut self) {
+            for feedback in self.committed.drain(..) {
+                feedback.discarded();
+            }
+            std::mem::swap(&mut self.pending, &mut self.committed);
+        }
+
+        /// Take the feedbacks of the committed content, for a frame about to show it
+        ///
+        /// The later frames are given no feedback until the next commit, their content was already
+        /// presented.
+        pub fn take_committed(&mut self) -> FrameFeedback {
+            FrameFeedback { feedbacks: std::mem::take(&mut self.committed) }
+        }
+
+        /// Discard all the feedbacks, when the surface is destroyed
+        pub fn surface_destroyed(&mut self) {
+            for feedback in self.pending.drain(..).chain(self.committed.drain(..)) {
+                feedback.discarded();
+            }
+        }
+    }
+
+    /// The feedbacks of the contents shown by a frame
+    ///
+    /// The feedbacks of the surfaces of a frame can be gathered with [`merge()`](Self::merge). They are
+    /// sent the outcome of the frame with [`presented()`](Self::presented) or
+    /// [`discarded()`](Self::discarded), and are discarded if it is dropped first.
+    #[derive(Debug, Default)]
+    pub struct FrameFeedback {
+        feedbacks: Vec<WpPresentationFeedback>,
+    }
+
+    impl FrameFeedback {
+        /// Create the empty feedback of a frame
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Add the feedbacks of another surface shown by this frame
+        pub fn merge(&mut self, mut other: FrameFeedback) {
+            self.feedbacks.append(&mut other.feedbacks);
+        }
+
+        /// Whether no client is waiting for the outcome of this frame
+        pub fn is_empty(&self) -> bool {
+            self.feedbacks.is_empty()
+        }
+
+        /// Send that the frame was presented
+        ///
+        /// Each feedback is first sent a `sync_output` event for each of the `outputs` showing the frame
+        /// which were bound by the same client. The arguments are those of the `presented` event: the
+        /// time the frame turned into light, the nanoseconds until the next refresh, zero if unknown, the
+        /// value of the refresh counter of the output, and the kind of presentation.
+        pub fn presented(
+            mut self,
+            outputs: &[WlOutput],
+            tv_sec: u64,
+            tv_nsec: u32,
+            refresh: u32,
+            seq: u64,
+            flags: Kind,
+        ) {
+            for feedback in self.feedbacks.drain(..) {
+                for output in outputs {
+                    if output.id().same_client_as(&feedback.id()) {
+                        feedback.sync_output(output);
+                    }
+                }
+                feedback.presented(
+                    (tv_sec >> 32) as u32,
+                    tv_sec as u32,
+                    tv_nsec,
+                    refresh,
+                    (seq >> 32) as u32,
+                    seq as u32,
+                    flags,
+                );
+            }
+        }
+
+        /// Send that the frame was not presented
+        pub fn discarded(mut self) {
+            for feedback in self.feedbacks.drain(..) {
+                feedback.discarded();
+            }
+        }
+    }
+
+    impl Drop for FrameFeedback {
+        fn drop(&mut self) {
+            for feedback in self.feedbacks.drain(..) {
+                feedback.discarded();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+mod client {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use wayland_client::protocol::wl_output::WlOutput;
+
+    use crate::wp::presentation_time::client::wp_presentation_feedback::Event;
+    use crate::wp::presentation_time::stats::Presented;
+
+    /// The outcome of a presentation feedback
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Outcome {
+        /// The content was presented
+        Presented {
+            /// The timing of the presentation
+            frame: Presented,
+            /// The outputs which showed the content
+            outputs: Vec<WlOutput>,
+        },
+        /// The content was never presented
+        Discarded,
+    }
+
+    /// The user data of a `wp_presentation_feedback`, collecting its events
+    #[derive(Debug, Default)]
+    pub struct ClientFeedback {
+        submitted: Option<Duration>,
+        outputs: Mutex<Vec<WlOutput>>,
+    }
+
+    impl ClientFeedback {
+        /// Create the data of a feedback
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Create the data of a feedback for a content submitted at `submitted`
+        ///
+        /// The time must be read from the clock advertised by `wp_presentation.clock_id`, and is given
+        /// by the [`Presented`] of the outcome.
+        pub fn submitted_at(submitted: Duration) -> Self {
+            ClientFeedback { submitted: Some(submitted), outputs: Mutex::new(Vec::new()) }
+        }
+
+        /// Process an event of the feedback
+        ///
+        /// Returns the outcome of the feedback once it is known, its last event destroying it.
+        pub fn event(&self, event: Event) -> Option<Outcome> {
+            match event {
+                Event::SyncOutput { output } => {
+                    self.outputs.lock().unwrap().push(output);
+                    None
+                }
+                Event::Presented {
+                    tv_sec_hi,
+                    tv_sec_lo,
+                    tv_nsec,
+                    refresh,
+                    seq_hi,
+                    seq_lo,
+                    flags,
+                } => {
+                    let mut frame = Presented::from_event(
+                        tv_sec_hi,
+                        tv_sec_lo,
+                        tv_nsec,
+                        refresh,
+                        seq_hi,
+                        seq_lo,
+                        flags.into(),
+                    );
+                    frame.submitted = self.submitted;
+                    let outputs = std::mem::take(&mut *self.outputs.lock().unwrap());
+                    Some(Outcome::Presented { frame, outputs })
+                }
+                Event::Discarded => Some(Outcome::Discarded),
+            }
+        }
+    }
+}
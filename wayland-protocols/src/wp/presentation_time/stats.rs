@@ -0,0 +1,342 @@
+//! Statistics over presentation feedback
+//!
+//! A client requesting a `wp_presentation_feedback` for each frame it commits learns when the frame
+//! was actually shown, and at which refresh rate. [`PresentationStats`] collects these feedbacks for
+//! each surface to estimate the refresh interval of the output, measure the latency between the
+//! submission of a frame and its presentation, count the refreshes missed by late frames, and
+//! predict when the next refresh will happen.
+//!
+//! All times are durations since the epoch of the clock advertised by `wp_presentation.clock_id`.
+//! The submission times given to [`Presented::submitted`] must be read from that same clock, for
+//! example with `clock_gettime`, right before the `wl_surface.commit` of the frame.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::Duration;
+
+// number of recent frames over which the estimates are computed
+const WINDOW: usize = 32;
+
+/// Combine the three parts of a timestamp, as sent by `wp_presentation_feedback.presented`
+pub fn timestamp_from_parts(tv_sec_hi: u32, tv_sec_lo: u32, tv_nsec: u32) -> Duration {
+    Duration::new(((tv_sec_hi as u64) << 32) | tv_sec_lo as u64, tv_nsec)
+}
+
+/// Combine the two halves of the refresh counter sent by `wp_presentation_feedback.presented`
+pub fn sequence_from_parts(seq_hi: u32, seq_lo: u32) -> u64 {
+    ((seq_hi as u64) << 32) | seq_lo as u64
+}
+
+/// The presentation of a frame, as reported by `wp_presentation_feedback.presented`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Presented {
+    /// The time the frame started to be shown
+    pub time: Duration,
+    /// The refresh interval of the output, zero if unknown
+    pub refresh: Duration,
+    /// The value of the refresh counter of the output, zero if it has none
+    pub sequence: u64,
+    /// Whether the presentation was synchronized to the vertical retrace of the output
+    pub vsync: bool,
+    /// The time the frame was submitted, if known
+    pub submitted: Option<Duration>,
+}
+
+impl Presented {
+    /// Build from the arguments of the `presented` event, without submission time
+    ///
+    /// `flags` are the bits of the `kind` argument.
+    pub fn from_event(
+        tv_sec_hi: u32,
+        tv_sec_lo: u32,
+        tv_nsec: u32,
+        refresh: u32,
+        seq_hi: u32,
+        seq_lo: u32,
+        flags: u32,
+    ) -> Self {
+        Presented {
+            time: timestamp_from_parts(tv_sec_hi, tv_sec_lo, tv_nsec),
+            refresh: Duration::from_nanos(refresh as u64),
+            sequence: sequence_from_parts(seq_hi, seq_lo),
+            // the `vsync` bit of `wp_presentation_feedback.kind`
+            vsync: flags & 0x1 != 0,
+            submitted: None,
+        }
+    }
+
+    /// Set the time the frame was submitted
+    pub fn submitted_at(self, submitted: Duration) -> Self {
+        Presented { submitted: Some(submitted), ..self }
+    }
+}
+
+/// Minimum, mean and maximum of the presentation latency over the recent frames
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Latency {
+    /// Latency of the most recent frame
+    pub last: Duration,
+    /// Smallest latency
+    pub min: Duration,
+    /// Mean latency
+    pub mean: Duration,
+    /// Largest latency
+    pub max: Duration,
+}
+
+/// Presentation statistics of a single surface
+#[derive(Debug, Clone, Default)]
+pub struct SurfaceStats {
+    presented: u64,
+    discarded: u64,
+    missed: u64,
+    last: Option<Presented>,
+    // refresh interval reported by the compositor for the last frame, zero if unknown
+    reported_refresh: Duration,
+    // refresh intervals measured between recent frames
+    intervals: VecDeque<Duration>,
+    latencies: VecDeque<Duration>,
+}
+
+impl SurfaceStats {
+    /// Number of frames presented
+    pub fn presented(&self) -> u64 {
+        self.presented
+    }
+
+    /// Number of frames discarded without being presented
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Number of refreshes missed by frames presented late
+    ///
+    /// A frame is late when it was not shown at the first refresh following its submission; it
+    /// then counts for each refresh it missed. Frames without a submission time are not counted.
+    pub fn missed_refreshes(&self) -> u64 {
+        self.missed
+    }
+
+    /// The most recent presentation
+    pub fn last_presented(&self) -> Option<&Presented> {
+        self.last.as_ref()
+    }
+
+    /// The refresh interval of the output showing the surface
+    ///
+    /// This is the interval reported by the compositor if it did, and otherwise the median of the
+    /// intervals measured between the recent frames, in number of refreshes when the output has a
+    /// refresh counter. Returns `None` until it can be estimated.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        if !self.reported_refresh.is_zero() {
+            return Some(self.reported_refresh);
+        }
+        if self.intervals.is_empty() {
+            return None;
+        }
+        let mut intervals = self.intervals.iter().copied().collect::<Vec<_>>();
+        intervals.sort_unstable();
+        Some(intervals[intervals.len() / 2]).filter(|interval| !interval.is_zero())
+    }
+
+    /// The latency between the submission and the presentation of the recent frames
+    ///
+    /// Returns `None` if no presented frame had a submission time.
+    pub fn latency(&self) -> Option<Latency> {
+        let last = *self.latencies.back()?;
+        let total = self.latencies.iter().sum::<Duration>();
+        Some(Latency {
+            last,
+            min: *self.latencies.iter().min()?,
+            mean: total / self.latencies.len() as u32,
+            max: *self.latencies.iter().max()?,
+        })
+    }
+
+    /// Predict the time of the first refresh strictly after `now`
+    ///
+    /// A frame submitted at `now` can at best be presented at that time. Returns `None` until a
+    /// frame was presented and the refresh interval is known.
+    pub fn next_presentation(&self, now: Duration) -> Option<Duration> {
+        let last = self.last?.time;
+        let refresh = self.refresh_interval()?;
+        if now < last {
+            return Some(last);
+        }
+        let elapsed = (now - last).as_nanos() / refresh.as_nanos();
+        Some(last + nanos((elapsed + 1) * refresh.as_nanos()))
+    }
+
+    fn record_presented(&mut self, frame: Presented) {
+        self.presented += 1;
+        self.reported_refresh = frame.refresh;
+
+        if let Some(last) = self.last.filter(|last| last.time < frame.time) {
+            let elapsed = frame.time - last.time;
+            if frame.vsync && last.vsync && frame.sequence > last.sequence && last.sequence != 0 {
+                let refreshes = (frame.sequence - last.sequence) as u128;
+                push_bounded(&mut self.intervals, nanos(elapsed.as_nanos() / refreshes));
+            } else if frame.refresh.is_zero() {
+                // without refresh counter, missed refreshes only show up as outliers
+                push_bounded(&mut self.intervals, elapsed);
+            }
+
+            if let (Some(submitted), Some(refresh)) = (frame.submitted, self.refresh_interval()) {
+                // the first refresh after the submission, predicted from the previous frame
+                let target = if submitted < last.time {
+                    last.time
+                } else {
+                    let refreshes = (submitted - last.time).as_nanos() / refresh.as_nanos() + 1;
+                    last.time + nanos(refreshes * refresh.as_nanos())
+                };
+                if frame.time > target {
+                    // round to absorb the jitter of the timestamps
+                    let late = (frame.time - target).as_nanos();
+                    let missed = (late + refresh.as_nanos() / 2) / refresh.as_nanos();
+                    self.missed += missed as u64;
+                }
+            }
+        }
+
+        if let Some(submitted) = frame.submitted.filter(|&submitted| submitted <= frame.time) {
+            push_bounded(&mut self.latencies, frame.time - submitted);
+        }
+        self.last = Some(frame);
+    }
+}
+
+fn push_bounded(values: &mut VecDeque<Duration>, value: Duration) {
+    if values.len() == WINDOW {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+fn nanos(nanos: u128) -> Duration {
+    Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+}
+
+/// Presentation statistics of a set of surfaces
+///
+/// `S` is the type identifying a surface, typically its `WlSurface` or `ObjectId`.
+#[derive(Debug)]
+pub struct PresentationStats<S> {
+    surfaces: HashMap<S, SurfaceStats>,
+}
+
+impl<S: Eq + Hash> PresentationStats<S> {
+    /// Create a new empty set of statistics
+    pub fn new() -> Self {
+        PresentationStats { surfaces: HashMap::new() }
+    }
+
+    /// Process a `wp_presentation_feedback.presented` event for a frame of `surface`
+    pub fn presented(&mut self, surface: S, frame: Presented) {
+        self.surfaces.entry(surface).or_default().record_presented(frame);
+    }
+
+    /// Process a `wp_presentation_feedback.discarded` event for a frame of `surface`
+    pub fn discarded(&mut self, surface: S) {
+        self.surfaces.entry(surface).or_default().discarded += 1;
+    }
+
+    /// The statistics of `surface`, if it received any feedback
+    pub fn surface(&self, surface: &S) -> Option<&SurfaceStats> {
+        self.surfaces.get(surface)
+    }
+
+    /// Forget about a destroyed surface
+    pub fn surface_destroyed(&mut self, surface: &S) {
+        self.surfaces.remove(surface);
+    }
+}
+
+impl<S: Eq + Hash> Default for PresentationStats<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFRESH: Duration = Duration::from_nanos(16_666_667);
+
+    fn frame(sequence: u64, refresh: Duration) -> Presented {
+        Presented {
+            time: Duration::from_secs(100) + REFRESH * sequence as u32,
+            refresh,
+            sequence,
+            vsync: true,
+            submitted: None,
+        }
+    }
+
+    #[test]
+    fn parse_event() {
+        let frame = Presented::from_event(1, 2, 500, 16_666_667, 0, 42, 0x1 | 0x2);
+        assert_eq!(frame.time, Duration::new((1 << 32) + 2, 500));
+        assert_eq!(frame.refresh, REFRESH);
+        assert_eq!(frame.sequence, 42);
+        assert!(frame.vsync);
+    }
+
+    #[test]
+    fn estimate_refresh() {
+        let mut stats = PresentationStats::new();
+        stats.presented(0, frame(1, Duration::ZERO));
+        assert_eq!(stats.surface(&0).unwrap().refresh_interval(), None);
+        // a frame every other refresh is measured with the refresh counter
+        stats.presented(0, frame(3, Duration::ZERO));
+        stats.presented(0, frame(4, Duration::ZERO));
+        let surface = stats.surface(&0).unwrap();
+        assert_eq!(surface.refresh_interval(), Some(REFRESH));
+        assert_eq!(surface.presented(), 3);
+
+        // the reported interval takes precedence
+        stats.presented(0, frame(5, Duration::from_millis(10)));
+        assert_eq!(stats.surface(&0).unwrap().refresh_interval(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn missed_refreshes_and_latency() {
+        let mut stats = PresentationStats::new();
+        stats.presented(0, frame(1, REFRESH));
+        let start = frame(1, REFRESH).time;
+
+        // submitted right after the refresh, shown at the next one
+        stats.presented(0, frame(2, REFRESH).submitted_at(start + Duration::from_millis(1)));
+        assert_eq!(stats.surface(&0).unwrap().missed_refreshes(), 0);
+
+        // submitted in the same refresh interval, but shown two refreshes late
+        let submitted = frame(2, REFRESH).time + Duration::from_millis(2);
+        stats.presented(0, frame(5, REFRESH).submitted_at(submitted));
+        let surface = stats.surface(&0).unwrap();
+        assert_eq!(surface.missed_refreshes(), 2);
+
+        let latency = surface.latency().unwrap();
+        assert_eq!(latency.min, REFRESH - Duration::from_millis(1));
+        assert_eq!(latency.max, REFRESH * 3 - Duration::from_millis(2));
+        assert_eq!(latency.last, latency.max);
+    }
+
+    #[test]
+    fn predict_next_presentation() {
+        let mut stats = PresentationStats::new();
+        stats.presented(0, frame(1, REFRESH));
+        let surface = stats.surface(&0).unwrap();
+        let last = frame(1, REFRESH).time;
+
+        assert_eq!(surface.next_presentation(last), Some(last + REFRESH));
+        assert_eq!(surface.next_presentation(last + REFRESH / 2), Some(last + REFRESH));
+        // exactly on a refresh, the next one is the following refresh
+        assert_eq!(surface.next_presentation(last + REFRESH), Some(last + REFRESH * 2));
+        assert_eq!(surface.next_presentation(last + REFRESH * 5 / 2), Some(last + REFRESH * 3));
+
+        stats.discarded(0);
+        assert_eq!(stats.surface(&0).unwrap().discarded(), 1);
+        stats.surface_destroyed(&0);
+        assert!(stats.surface(&0).is_none());
+    }
+}
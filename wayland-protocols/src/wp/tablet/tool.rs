@@ -0,0 +1,230 @@
+//! Batching of the events of tablet tools into frames
+//!
+//! The events of a `zwp_tablet_tool_v2` come in groups ended by a `frame` event: a tool coming in
+//! proximity of a tablet, touching it, moving and pressing its buttons at the same time is sent as one
+//! event for each change, which only make sense together. [`ClientTabletTool`] is the user data of a
+//! tool, which gathers these events into a [`ToolFrame`] carrying the state of the tool at the end of
+//! the frame and the changes of the frame. It also collects the description of the tool sent when it is
+//! added, given by [`ClientTabletTool::info()`].
+//!
+//! The tools are created by the `zwp_tablet_seat_v2.tool_added` event, so their user data is given by
+//! the `event_created_child` method of the `Dispatch` implementation of the tablet seat.
+
+use std::sync::Mutex;
+
+use wayland_client::{protocol::wl_surface::WlSurface, WEnum};
+
+use crate::wp::tablet::zv2::client::{
+    zwp_tablet_tool_v2::{ButtonState, Capability, Event, Type},
+    zwp_tablet_v2::ZwpTabletV2,
+};
+
+/// The description of a tool, sent when it is added
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolInfo {
+    /// The physical type of the tool, if the compositor sent a known one
+    pub tool_type: Option<Type>,
+    /// The unique serial number of the tool, if it has one
+    pub hardware_serial: Option<u64>,
+    /// The hardware id of the tool for Wacom tablets, if it has one
+    pub hardware_id_wacom: Option<u64>,
+    /// The axes the tool has besides its position
+    pub capabilities: Vec<Capability>,
+}
+
+/// The state of a tool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolState {
+    /// The tablet and the surface the tool is in proximity of, if it is
+    pub proximity: Option<(ZwpTabletV2, WlSurface)>,
+    /// The position of the tool, in surface-local coordinates
+    pub position: (f64, f64),
+    /// Whether the tip of the tool touches the tablet
+    pub down: bool,
+    /// The pressure of the tool, normalized between 0 and 65535
+    pub pressure: u32,
+    /// The distance of the tool from the tablet, normalized between 0 and 65535
+    pub distance: u32,
+    /// The tilt of the tool along the x and y axes, in degrees
+    pub tilt: (f64, f64),
+    /// The rotation of the tool around its z axis, in degrees
+    pub rotation: f64,
+    /// The position of the slider of the tool, normalized between -65535 and 65535
+    pub slider: i32,
+    /// The buttons pressed, identified by their linux button codes
+    pub buttons: Vec<u32>,
+}
+
+/// A change of state of a button of the tool
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ButtonChange {
+    /// The serial of the `button` event
+    pub serial: u32,
+    /// The linux button code of the button
+    pub button: u32,
+    /// Whether the button was pressed or released
+    pub pressed: bool,
+}
+
+/// The events of a tool between two `frame` events
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolFrame {
+    /// The timestamp of the frame, in milliseconds
+    pub time: u32,
+    /// The state of the tool at the end of the frame
+    pub state: ToolState,
+    /// The serial of the `proximity_in` event, if the tool came in proximity during the frame
+    pub proximity_in: Option<u32>,
+    /// Whether the tool left proximity during the frame
+    pub proximity_out: bool,
+    /// The serial of the `down` event, if the tool touched the tablet during the frame
+    pub down: Option<u32>,
+    /// Whether the tool stopped touching the tablet during the frame
+    pub up: bool,
+    /// The rotation of the wheel during the frame, in degrees and in discrete clicks
+    pub wheel: (f64, i32),
+    /// The buttons pressed and released during the frame, in order
+    pub buttons: Vec<ButtonChange>,
+}
+
+impl ToolFrame {
+    fn new(state: ToolState) -> Self {
+        ToolFrame {
+            time: 0,
+            state,
+            proximity_in: None,
+            proximity_out: false,
+            down: None,
+            up: false,
+            wheel: (0., 0),
+            buttons: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    info: ToolInfo,
+    done: bool,
+    removed: bool,
+    // the state as of the last frame
+    current: ToolState,
+    // the frame being received
+    frame: Option<ToolFrame>,
+}
+
+impl State {
+    fn frame(&mut self) -> &mut ToolFrame {
+        let State { ref current, ref mut frame, .. } = *self;
+        frame.get_or_insert_with(|| ToolFrame::new(current.clone()))
+    }
+}
+
+/// The user data of a `zwp_tablet_tool_v2`, collecting its events
+#[derive(Debug, Default)]
+pub struct ClientTabletTool {
+    state: Mutex<State>,
+}
+
+impl ClientTabletTool {
+    /// Create the data of a tool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The description of the tool, once it is entirely received
+    pub fn info(&self) -> Option<ToolInfo> {
+        let state = self.state.lock().unwrap();
+        state.done.then(|| state.info.clone())
+    }
+
+    /// Whether the compositor removed the tool, which should then be destroyed
+    pub fn is_removed(&self) -> bool {
+        self.state.lock().unwrap().removed
+    }
+
+    /// The state of the tool as of the last frame
+    pub fn state(&self) -> ToolState {
+        self.state.lock().unwrap().current.clone()
+    }
+
+    /// Process an event of the tool
+    ///
+    /// The events are accumulated until the next `frame` event, which returns them. A tool leaving
+    /// proximity is also released, and its buttons too.
+    pub fn event(&self, event: Event) -> Option<ToolFrame> {
+        let mut state = self.state.lock().unwrap();
+        match event {
+            Event::Type { tool_type } => {
+                state.info.tool_type = match tool_type {
+                    WEnum::Value(tool_type) => Some(tool_type),
+                    WEnum::Unknown(_) => None,
+                };
+            }
+            Event::HardwareSerial { hardware_serial_hi, hardware_serial_lo } => {
+                state.info.hardware_serial =
+                    Some((hardware_serial_hi as u64) << 32 | hardware_serial_lo as u64);
+            }
+            Event::HardwareIdWacom { hardware_id_hi, hardware_id_lo } => {
+                state.info.hardware_id_wacom =
+                    Some((hardware_id_hi as u64) << 32 | hardware_id_lo as u64);
+            }
+            Event::Capability { capability: WEnum::Value(capability) } => {
+                state.info.capabilities.push(capability);
+            }
+            Event::Done => state.done = true,
+            Event::Removed => state.removed = true,
+            Event::ProximityIn { serial, tablet, surface } => {
+                let frame = state.frame();
+                frame.proximity_in = Some(serial);
+                frame.state.proximity = Some((tablet, surface));
+            }
+            Event::ProximityOut => {
+                let frame = state.frame();
+                frame.proximity_out = true;
+                frame.state.proximity = None;
+                frame.state.down = false;
+                frame.state.buttons.clear();
+            }
+            Event::Down { serial } => {
+                let frame = state.frame();
+                frame.down = Some(serial);
+                frame.state.down = true;
+            }
+            Event::Up => {
+                let frame = state.frame();
+                frame.up = true;
+                frame.state.down = false;
+            }
+            Event::Motion { x, y } => state.frame().state.position = (x, y),
+            Event::Pressure { pressure } => state.frame().state.pressure = pressure,
+            Event::Distance { distance } => state.frame().state.distance = distance,
+            Event::Tilt { tilt_x, tilt_y } => state.frame().state.tilt = (tilt_x, tilt_y),
+            Event::Rotation { degrees } => state.frame().state.rotation = degrees,
+            Event::Slider { position } => state.frame().state.slider = position,
+            Event::Wheel { degrees, clicks } => {
+                let frame = state.frame();
+                frame.wheel.0 += degrees;
+                frame.wheel.1 += clicks;
+            }
+            Event::Button { serial, button, state: button_state } => {
+                let pressed = button_state == WEnum::Value(ButtonState::Pressed);
+                let frame = state.frame();
+                frame.state.buttons.retain(|&b| b != button);
+                if pressed {
+                    frame.state.buttons.push(button);
+                }
+                frame.buttons.push(ButtonChange { serial, button, pressed });
+            }
+            Event::Frame { time } => {
+                let mut frame =
+                    state.frame.take().unwrap_or_else(|| ToolFrame::new(state.current.clone()));
+                frame.time = time;
+                state.current = frame.state.clone();
+                return Some(frame);
+            }
+            _ => {}
+        }
+        None
+    }
+}
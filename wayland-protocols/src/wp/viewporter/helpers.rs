@@ -0,0 +1,353 @@
+//! Helpers for computing and validating viewport state
+//!
+//! The rules of `wp_viewport` interact with the buffer transform, the buffer scale and the
+//! limited precision of the `fixed` wire type. [`ViewportState`] gathers the double-buffered state
+//! of a viewport and checks it the same way a compositor would when the surface is committed,
+//! while [`BufferGeometry`] converts between buffer and surface coordinates.
+//!
+//! The validation is available on both sides, so that clients can check their requests before sending
+//! them. Its [`ViewportError`] converts to the generated `wp_viewport::Error` of either side, and can be
+//! given to `post_error` as is.
+
+/// Round a value to the precision of the wayland `fixed` type (1/256)
+///
+/// Source rectangle coordinates are sent as `fixed`, so this is the value the compositor will
+/// actually see when `value` is passed to `wp_viewport.set_source`.
+pub fn to_fixed_precision(value: f64) -> f64 {
+    (value * 256.).round() / 256.
+}
+
+/// An error the compositor would raise for a given viewport state
+///
+/// The discriminants match the values of the `wp_viewport.error` protocol enum.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ViewportError {
+    /// Negative or zero values in the source rectangle or the destination size
+    BadValue = 0,
+    /// No destination size is set and the source size is not an integer
+    BadSize = 1,
+    /// The source rectangle extends outside of the buffer
+    OutOfBuffer = 2,
+}
+
+impl std::error::Error for ViewportError {}
+
+impl std::fmt::Display for ViewportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::BadValue => f.write_str("negative or zero values in viewport parameters"),
+            Self::BadSize => f.write_str("viewport source size is not an integer"),
+            Self::OutOfBuffer => f.write_str("viewport source rectangle extends outside of buffer"),
+        }
+    }
+}
+
+impl From<ViewportError> for u32 {
+    fn from(error: ViewportError) -> u32 {
+        error as u32
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<ViewportError> for super::client::wp_viewport::Error {
+    fn from(error: ViewportError) -> Self {
+        match error {
+            ViewportError::BadValue => Self::BadValue,
+            ViewportError::BadSize => Self::BadSize,
+            ViewportError::OutOfBuffer => Self::OutOfBuffer,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<ViewportError> for super::server::wp_viewport::Error {
+    fn from(error: ViewportError) -> Self {
+        match error {
+            ViewportError::BadValue => Self::BadValue,
+            ViewportError::BadSize => Self::BadSize,
+            ViewportError::OutOfBuffer => Self::OutOfBuffer,
+        }
+    }
+}
+
+/// A viewport source rectangle, in surface-local coordinates before crop and scale
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Source {
+    /// X coordinate of the top-left corner
+    pub x: f64,
+    /// Y coordinate of the top-left corner
+    pub y: f64,
+    /// Width of the rectangle
+    pub width: f64,
+    /// Height of the rectangle
+    pub height: f64,
+}
+
+impl Source {
+    /// Parse the arguments of a `wp_viewport.set_source` request
+    ///
+    /// Returns `Ok(None)` if the arguments unset the source rectangle.
+    pub fn from_request(
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<Option<Source>, ViewportError> {
+        if x == -1. && y == -1. && width == -1. && height == -1. {
+            Ok(None)
+        } else if x < 0. || y < 0. || width <= 0. || height <= 0. {
+            Err(ViewportError::BadValue)
+        } else {
+            Ok(Some(Source { x, y, width, height }))
+        }
+    }
+
+    /// The arguments of the `wp_viewport.set_source` request setting this rectangle
+    ///
+    /// Values are rounded to the precision of the wire format.
+    pub fn to_request(&self) -> (f64, f64, f64, f64) {
+        (
+            to_fixed_precision(self.x),
+            to_fixed_precision(self.y),
+            to_fixed_precision(self.width),
+            to_fixed_precision(self.height),
+        )
+    }
+}
+
+/// Parse the arguments of a `wp_viewport.set_destination` request
+///
+/// Returns `Ok(None)` if the arguments unset the destination size.
+pub fn destination_from_request(
+    width: i32,
+    height: i32,
+) -> Result<Option<(i32, i32)>, ViewportError> {
+    if width == -1 && height == -1 {
+        Ok(None)
+    } else if width <= 0 || height <= 0 {
+        Err(ViewportError::BadValue)
+    } else {
+        Ok(Some((width, height)))
+    }
+}
+
+/// The geometry of a buffer attached to a surface
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BufferGeometry {
+    width: i32,
+    height: i32,
+    scale: i32,
+    transposed: bool,
+}
+
+impl BufferGeometry {
+    /// The geometry of a buffer of the given size in buffer pixels
+    ///
+    /// `scale` is the buffer scale set with `wl_surface.set_buffer_scale`, and `transposed` tells
+    /// whether the buffer transform swaps the axes, which is the case for the `90`, `270`,
+    /// `flipped_90` and `flipped_270` transforms.
+    ///
+    /// Returns `None` if the size is negative, if the scale is not positive, which the compositor
+    /// rejects with the `invalid_scale` error of `wl_surface`, or if the size is not a multiple of the
+    /// scale, which it rejects with the `invalid_size` error of `wl_surface`.
+    pub fn new(width: i32, height: i32, scale: i32, transposed: bool) -> Option<BufferGeometry> {
+        if width < 0 || height < 0 || scale <= 0 || width % scale != 0 || height % scale != 0 {
+            None
+        } else {
+            Some(BufferGeometry { width, height, scale, transposed })
+        }
+    }
+
+    /// Width of the buffer, in buffer pixels
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Height of the buffer, in buffer pixels
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// The buffer scale
+    pub fn scale(&self) -> i32 {
+        self.scale
+    }
+
+    /// Whether the buffer transform swaps the axes
+    pub fn transposed(&self) -> bool {
+        self.transposed
+    }
+
+    /// The size of the buffer in surface-local coordinates, ignoring the viewport
+    pub fn surface_size(&self) -> (i32, i32) {
+        let (w, h) =
+            if self.transposed { (self.height, self.width) } else { (self.width, self.height) };
+        (w / self.scale, h / self.scale)
+    }
+
+    /// Convert a size in buffer pixels to surface-local coordinates, ignoring the viewport
+    pub fn buffer_to_surface(&self, (w, h): (f64, f64)) -> (f64, f64) {
+        let scale = self.scale as f64;
+        if self.transposed {
+            (h / scale, w / scale)
+        } else {
+            (w / scale, h / scale)
+        }
+    }
+
+    /// Convert a size in surface-local coordinates to buffer pixels, ignoring the viewport
+    pub fn surface_to_buffer(&self, (w, h): (f64, f64)) -> (f64, f64) {
+        let scale = self.scale as f64;
+        if self.transposed {
+            (h * scale, w * scale)
+        } else {
+            (w * scale, h * scale)
+        }
+    }
+}
+
+/// The double-buffered state of a `wp_viewport`
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ViewportState {
+    /// The source rectangle, if set
+    pub source: Option<Source>,
+    /// The destination size, if set
+    pub destination: Option<(i32, i32)>,
+}
+
+impl ViewportState {
+    /// Validate this state against the attached buffer, and compute the resulting surface size
+    ///
+    /// `buffer` is `None` if no buffer is attached, in which case the surface has no size and
+    /// `Ok(None)` is returned.
+    pub fn surface_size(
+        &self,
+        buffer: Option<&BufferGeometry>,
+    ) -> Result<Option<(i32, i32)>, ViewportError> {
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => return Ok(None),
+        };
+
+        if let Some(src) = self.source {
+            let (buf_w, buf_h) =
+                buffer.buffer_to_surface((buffer.width as f64, buffer.height as f64));
+            if src.x + src.width > buf_w || src.y + src.height > buf_h {
+                return Err(ViewportError::OutOfBuffer);
+            }
+        }
+
+        match (self.destination, self.source) {
+            (Some(dst), _) => Ok(Some(dst)),
+            (None, Some(src)) => {
+                if src.width.fract() != 0. || src.height.fract() != 0. {
+                    Err(ViewportError::BadSize)
+                } else {
+                    Ok(Some((src.width as i32, src.height as i32)))
+                }
+            }
+            (None, None) => Ok(Some(buffer.surface_size())),
+        }
+    }
+
+    /// Convert a point from surface-local coordinates to buffer-local coordinates before transform
+    /// and scale, taking the viewport into account
+    ///
+    /// This assumes the state has already been validated with [`surface_size()`](Self::surface_size).
+    pub fn surface_to_source(&self, buffer: &BufferGeometry, (x, y): (f64, f64)) -> (f64, f64) {
+        let (buf_w, buf_h) = buffer.buffer_to_surface((buffer.width as f64, buffer.height as f64));
+        let src = self.source.unwrap_or(Source { x: 0., y: 0., width: buf_w, height: buf_h });
+        let (dst_w, dst_h) = match (self.destination, self.source) {
+            (Some((w, h)), _) => (w as f64, h as f64),
+            (None, Some(src)) => (src.width, src.height),
+            (None, None) => (buf_w, buf_h),
+        };
+        (src.x + x * src.width / dst_w, src.y + y * src.height / dst_h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUFFER: BufferGeometry =
+        BufferGeometry { width: 200, height: 100, scale: 2, transposed: false };
+
+    #[test]
+    fn buffer_geometry() {
+        assert_eq!(BufferGeometry::new(200, 100, 2, false), Some(BUFFER));
+        assert_eq!(BufferGeometry::new(200, 100, 0, false), None);
+        assert_eq!(BufferGeometry::new(-1, 100, 1, false), None);
+        assert_eq!(BufferGeometry::new(201, 100, 2, false), None);
+        assert_eq!(BUFFER.surface_size(), (100, 50));
+    }
+
+    #[test]
+    fn parse_requests() {
+        assert_eq!(Source::from_request(-1., -1., -1., -1.), Ok(None));
+        assert_eq!(Source::from_request(0., 0., 0., 10.), Err(ViewportError::BadValue));
+        assert_eq!(Source::from_request(-2., 0., 5., 10.), Err(ViewportError::BadValue));
+        assert_eq!(destination_from_request(-1, -1), Ok(None));
+        assert_eq!(destination_from_request(-1, 10), Err(ViewportError::BadValue));
+        assert_eq!(destination_from_request(10, 20), Ok(Some((10, 20))));
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(u32::from(ViewportError::OutOfBuffer), 2);
+        #[cfg(feature = "client")]
+        assert_eq!(
+            crate::wp::viewporter::client::wp_viewport::Error::from(ViewportError::BadSize),
+            crate::wp::viewporter::client::wp_viewport::Error::BadSize
+        );
+        #[cfg(feature = "server")]
+        assert_eq!(
+            crate::wp::viewporter::server::wp_viewport::Error::from(ViewportError::BadValue),
+            crate::wp::viewporter::server::wp_viewport::Error::BadValue
+        );
+    }
+
+    #[test]
+    fn fixed_rounding() {
+        assert_eq!(to_fixed_precision(0.1), 26. / 256.);
+        assert_eq!(to_fixed_precision(12.5), 12.5);
+    }
+
+    #[test]
+    fn compute_surface_size() {
+        let unset = ViewportState::default();
+        assert_eq!(unset.surface_size(Some(&BUFFER)), Ok(Some((100, 50))));
+        assert_eq!(unset.surface_size(None), Ok(None));
+
+        let crop = ViewportState {
+            source: Some(Source { x: 10., y: 10., width: 20.5, height: 20. }),
+            destination: None,
+        };
+        assert_eq!(crop.surface_size(Some(&BUFFER)), Err(ViewportError::BadSize));
+
+        let scaled = ViewportState { destination: Some((41, 40)), ..crop };
+        assert_eq!(scaled.surface_size(Some(&BUFFER)), Ok(Some((41, 40))));
+
+        let outside = ViewportState {
+            source: Some(Source { x: 60., y: 0., width: 50., height: 10. }),
+            destination: None,
+        };
+        assert_eq!(outside.surface_size(Some(&BUFFER)), Err(ViewportError::OutOfBuffer));
+        // a transposed buffer of the same size has room for it
+        let transposed = BufferGeometry { transposed: true, ..BUFFER };
+        assert_eq!(
+            outside.surface_size(Some(&BufferGeometry { width: 100, height: 240, ..transposed })),
+            Ok(Some((50, 10)))
+        );
+    }
+
+    #[test]
+    fn coordinate_conversion() {
+        let state = ViewportState {
+            source: Some(Source { x: 10., y: 20., width: 50., height: 25. }),
+            destination: Some((100, 100)),
+        };
+        assert_eq!(state.surface_to_source(&BUFFER, (50., 50.)), (35., 32.5));
+        assert_eq!(BUFFER.surface_to_buffer(BUFFER.buffer_to_surface((12., 7.))), (12., 7.));
+    }
+}
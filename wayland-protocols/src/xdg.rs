@@ -2,7 +2,7 @@
 
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "xdg-activation")]
 pub mod activation {
     //! The way for a client to pass focus to another toplevel is as follows.
     //!
@@ -33,7 +33,7 @@ pub mod activation {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "xdg-decoration")]
 pub mod decoration {
     //! This interface allows a compositor to announce support for server-side
     //! decorations.
@@ -58,7 +58,7 @@ pub mod decoration {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "xdg-foreign")]
 pub mod foreign {
     //! Protocol for exporting xdg surface handles
     //!
@@ -100,7 +100,7 @@ pub mod foreign {
     }
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "xdg-output")]
 pub mod xdg_output {
     //! Protocol to describe output regions
     //!
@@ -140,3 +140,19 @@ pub mod shell {
         []
     );
 }
+
+#[cfg(feature = "xdg-system-bell")]
+pub mod system_bell {
+    //! This global interface enables clients to ring the system bell.
+    //!
+    //! Rings are not tied to any surface by default, but a client may pass one of
+    //! its surfaces to let the compositor associate the bell with it.
+
+    /// Version 1
+    pub mod v1 {
+        wayland_protocol!(
+            "./protocols/staging/xdg-system-bell/xdg-system-bell-v1.xml",
+            []
+        );
+    }
+}
@@ -2,7 +2,7 @@
 
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
-#[cfg(feature = "staging")]
+#[cfg(feature = "xwayland-shell")]
 pub mod shell {
     //! This protocol adds a xwayland_surface role which allows an Xwayland
     //! server to associate an X11 window to a wl_surface.
@@ -36,9 +36,12 @@ pub mod shell {
             []
         );
     }
+
+    #[cfg(feature = "server")]
+    pub mod association;
 }
 
-#[cfg(feature = "unstable")]
+#[cfg(feature = "xwayland-keyboard-grab")]
 pub mod keyboard_grab {
     //! Protocol for grabbing the keyboard from Xwayland
     //!
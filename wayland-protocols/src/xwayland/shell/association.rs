@@ -0,0 +1,166 @@
+//! Helpers for matching X11 windows to `wl_surface`s
+//!
+//! The association is a handshake between the two sides of Xwayland: the X11 window receives a
+//! `WL_SURFACE_SERIAL` client message, and the `wl_surface` receives the same serial through
+//! `xwayland_surface_v1.set_serial` followed by a `wl_surface.commit`. Either side can arrive first,
+//! so a compositor needs to keep the unmatched halves around until the other one shows up.
+//! [`Associations`] does this bookkeeping, and is generic over the handles the compositor uses to
+//! identify surfaces and windows.
+//!
+//! This module requires the `server` feature: the errors are the values of the generated
+//! `xwayland_surface_v1::Error` enum, which can be given to `post_error` as is.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::v1::server::xwayland_surface_v1::Error;
+
+/// Combine the two halves of a serial, as sent by `set_serial` or stored in the `l[0]` and `l[1]`
+/// fields of the `WL_SURFACE_SERIAL` client message
+pub fn serial_from_parts(serial_lo: u32, serial_hi: u32) -> u64 {
+    ((serial_hi as u64) << 32) | serial_lo as u64
+}
+
+/// Split a serial into its lower and upper 32 bits
+pub fn serial_to_parts(serial: u64) -> (u32, u32) {
+    (serial as u32, (serial >> 32) as u32)
+}
+
+/// Tracker for the pending halves of the Xwayland surface association handshake
+///
+/// `S` is the type identifying a `wl_surface` (typically its `WlSurface` or `ObjectId`), and `W`
+/// the type identifying an X11 window.
+#[derive(Debug)]
+pub struct Associations<S, W> {
+    // serials set on surfaces but not yet committed
+    pending: HashMap<S, u64>,
+    // serials committed on surfaces, waiting for their window
+    committed: HashMap<u64, S>,
+    // serials received on windows, waiting for their surface
+    windows: HashMap<u64, W>,
+    // surfaces which have been associated once
+    associated: HashMap<S, u64>,
+}
+
+impl<S: Clone + Eq + Hash, W: Clone> Associations<S, W> {
+    /// Create a new empty tracker
+    pub fn new() -> Self {
+        Associations {
+            pending: HashMap::new(),
+            committed: HashMap::new(),
+            windows: HashMap::new(),
+            associated: HashMap::new(),
+        }
+    }
+
+    /// Process a `xwayland_surface_v1.set_serial` request
+    ///
+    /// The serial only takes effect at the next [`commit()`](Self::commit) of the surface.
+    pub fn set_serial(&mut self, surface: S, serial_lo: u32, serial_hi: u32) -> Result<(), Error> {
+        let serial = serial_from_parts(serial_lo, serial_hi);
+        if serial == 0 {
+            return Err(Error::InvalidSerial);
+        }
+        self.pending.insert(surface, serial);
+        Ok(())
+    }
+
+    /// Process a `wl_surface.commit` on `surface`
+    ///
+    /// Returns the window the surface is now associated with, if the matching `WL_SURFACE_SERIAL`
+    /// message was already received.
+    pub fn commit(&mut self, surface: &S) -> Result<Option<W>, Error> {
+        let serial = match self.pending.remove(surface) {
+            Some(serial) => serial,
+            None => return Ok(None),
+        };
+        if self.associated.contains_key(surface) {
+            return Err(Error::AlreadyAssociated);
+        }
+        self.associated.insert(surface.clone(), serial);
+        match self.windows.remove(&serial) {
+            Some(window) => Ok(Some(window)),
+            None => {
+                self.committed.insert(serial, surface.clone());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Process a `WL_SURFACE_SERIAL` client message received on `window`
+    ///
+    /// Returns the surface the window is now associated with, if that surface already committed
+    /// the same serial.
+    pub fn window_serial(&mut self, window: W, serial: u64) -> Option<S> {
+        match self.committed.remove(&serial) {
+            Some(surface) => Some(surface),
+            None => {
+                self.windows.insert(serial, window);
+                None
+            }
+        }
+    }
+
+    /// Forget about a destroyed surface
+    pub fn surface_destroyed(&mut self, surface: &S) {
+        self.pending.remove(surface);
+        if let Some(serial) = self.associated.remove(surface) {
+            self.committed.remove(&serial);
+        }
+    }
+
+    /// Forget about a destroyed or unmapped window
+    pub fn window_destroyed(&mut self, window: &W)
+    where
+        W: PartialEq,
+    {
+        self.windows.retain(|_, w| w != window);
+    }
+}
+
+impl<S: Clone + Eq + Hash, W: Clone> Default for Associations<S, W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serial_parts() {
+        let serial = 0x1234_5678_9abc_def0;
+        let (lo, hi) = serial_to_parts(serial);
+        assert_eq!((lo, hi), (0x9abc_def0, 0x1234_5678));
+        assert_eq!(serial_from_parts(lo, hi), serial);
+    }
+
+    #[test]
+    fn surface_first() {
+        let mut assoc = Associations::<u32, &str>::new();
+        assoc.set_serial(1, 42, 0).unwrap();
+        // nothing happens before the commit
+        assert_eq!(assoc.window_serial("other", 7), None);
+        assert_eq!(assoc.commit(&1), Ok(None));
+        assert_eq!(assoc.window_serial("window", 42), Some(1));
+    }
+
+    #[test]
+    fn window_first() {
+        let mut assoc = Associations::<u32, &str>::new();
+        assert_eq!(assoc.window_serial("window", 42), None);
+        assoc.set_serial(1, 42, 0).unwrap();
+        assert_eq!(assoc.commit(&1), Ok(Some("window")));
+    }
+
+    #[test]
+    fn protocol_errors() {
+        let mut assoc = Associations::<u32, &str>::new();
+        assert_eq!(assoc.set_serial(1, 0, 0), Err(Error::InvalidSerial));
+        assoc.set_serial(1, 1, 0).unwrap();
+        assoc.commit(&1).unwrap();
+        assoc.set_serial(1, 2, 0).unwrap();
+        assert_eq!(assoc.commit(&1), Err(Error::AlreadyAssociated));
+    }
+}
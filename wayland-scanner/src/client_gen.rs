@@ -8,17 +8,29 @@ use crate::{
     Side,
 };
 
-pub fn generate_client_objects(protocol: &Protocol) -> TokenStream {
-    protocol.interfaces.iter().map(generate_objects_for).collect()
+pub fn generate_client_objects(
+    protocol: &Protocol,
+    shared_enums: Option<&TokenStream>,
+    serde: bool,
+) -> TokenStream {
+    protocol
+        .interfaces
+        .iter()
+        .map(|interface| generate_objects_for(interface, shared_enums, serde))
+        .collect()
 }
 
-fn generate_objects_for(interface: &Interface) -> TokenStream {
+fn generate_objects_for(
+    interface: &Interface,
+    shared_enums: Option<&TokenStream>,
+    serde: bool,
+) -> TokenStream {
     let mod_name = Ident::new(&interface.name, Span::call_site());
     let mod_doc = interface.description.as_ref().map(description_to_doc_attr);
     let iface_name = Ident::new(&snake_to_camel(&interface.name), Span::call_site());
     let iface_const_name = format_ident!("{}_INTERFACE", interface.name.to_ascii_uppercase());
 
-    let enums = crate::common::generate_enums_for(interface);
+    let enums = crate::common::gen_enums_or_reexport(interface, Side::Client, shared_enums, serde);
     let sinces = crate::common::gen_msg_constants(&interface.requests, &interface.events);
 
     let requests = crate::common::gen_message_enum(
@@ -33,6 +45,21 @@ fn generate_objects_for(interface: &Interface) -> TokenStream {
         true,
         &interface.events,
     );
+    let serialize = serde.then(|| {
+        let requests = crate::common::gen_serialize_impl(
+            &format_ident!("Request"),
+            Side::Client,
+            false,
+            &interface.requests,
+        );
+        let events = crate::common::gen_serialize_impl(
+            &format_ident!("Event"),
+            Side::Client,
+            true,
+            &interface.events,
+        );
+        quote! { #requests #events }
+    });
 
     let parse_body = crate::common::gen_parse_body(interface, Side::Client);
     let write_body = crate::common::gen_write_body(interface, Side::Client);
@@ -67,6 +94,7 @@ fn generate_objects_for(interface: &Interface) -> TokenStream {
             #sinces
             #requests
             #events
+            #serialize
 
             #doc_attr
             #[derive(Debug, Clone)]
@@ -255,6 +283,7 @@ fn gen_methods(interface: &Interface) -> TokenStream {
             .description
             .as_ref()
             .map(description_to_doc_attr);
+        let deprecated_attr = crate::common::gen_deprecated_attr(request);
 
         match created_interface {
             Some(Some(ref created_interface)) => {
@@ -263,6 +292,7 @@ fn gen_methods(interface: &Interface) -> TokenStream {
                 let created_iface_type = Ident::new(&snake_to_camel(created_interface), Span::call_site());
                 quote! {
                     #doc_attr
+                    #deprecated_attr
                     #[allow(clippy::too_many_arguments)]
                     pub fn #method_name<U: Send + Sync + 'static, D: Dispatch<super::#created_iface_mod::#created_iface_type, U> + 'static>(&self, #(#fn_args,)* qh: &QueueHandle<D>, udata: U) -> super::#created_iface_mod::#created_iface_type {
                         self.send_constructor(
@@ -278,6 +308,7 @@ fn gen_methods(interface: &Interface) -> TokenStream {
                 // a bind-like request
                 quote! {
                     #doc_attr
+                    #deprecated_attr
                     #[allow(clippy::too_many_arguments)]
                     pub fn #method_name<I: Proxy + 'static, U: Send + Sync + 'static, D: Dispatch<I, U> + 'static>(&self, #(#fn_args,)* qh: &QueueHandle<D>, udata: U) -> I {
                         self.send_constructor(
@@ -293,6 +324,7 @@ fn gen_methods(interface: &Interface) -> TokenStream {
                 // a non-creating request
                 quote! {
                     #doc_attr
+                    #deprecated_attr
                     #[allow(clippy::too_many_arguments)]
                     pub fn #method_name(&self, #(#fn_args),*) {
                         let backend = match self.backend.upgrade() {
@@ -321,7 +353,8 @@ mod tests {
         let protocol_file =
             std::fs::File::open("./tests/scanner_assets/test-protocol.xml").unwrap();
         let protocol_parsed = crate::parse::parse(protocol_file);
-        let generated: String = super::generate_client_objects(&protocol_parsed).to_string();
+        let generated: String =
+            super::generate_client_objects(&protocol_parsed, None, false).to_string();
         let generated = crate::format_rust_code(&generated);
 
         let reference =
@@ -6,119 +6,221 @@ use quote::{format_ident, quote, ToTokens};
 
 use crate::{protocol::*, util::*, Side};
 
-pub(crate) fn generate_enums_for(interface: &Interface) -> TokenStream {
-    interface.enums.iter().map(ToTokens::into_token_stream).collect()
+pub(crate) fn generate_enums_for(interface: &Interface, side: Side, serde: bool) -> TokenStream {
+    let serialize = serde.then(|| match side {
+        Side::Client => quote! { super::wayland_client::serialize },
+        Side::Server => quote! { super::wayland_server::serialize },
+    });
+    let mut tokens = TokenStream::new();
+    for enu in &interface.enums {
+        enum_to_tokens(enu, serialize.as_ref(), &mut tokens);
+        if enu.name == "error" && !enu.bitfield {
+            interface_error_to_tokens(interface, side, &mut tokens);
+        }
+    }
+    tokens
 }
 
-impl ToTokens for Enum {
-    fn to_tokens(&self, tokens: &mut TokenStream) {
-        let enum_decl;
-        let enum_impl;
+/// The enums of an interface, or their re-export from the module generated by `generate_enums!`
+pub(crate) fn gen_enums_or_reexport(
+    interface: &Interface,
+    side: Side,
+    shared_enums: Option<&TokenStream>,
+    serde: bool,
+) -> TokenStream {
+    match shared_enums {
+        None => generate_enums_for(interface, side, serde),
+        Some(_) if interface.enums.is_empty() => TokenStream::new(),
+        Some(path) => {
+            let mod_name = Ident::new(&interface.name, Span::call_site());
+            let idents = interface
+                .enums
+                .iter()
+                .map(|enu| Ident::new(&snake_to_camel(&enu.name), Span::call_site()));
+            quote! {
+                pub use super::#path::#mod_name::{#(#idents),*};
+            }
+        }
+    }
+}
 
-        let doc_attr = self.description.as_ref().map(description_to_doc_attr);
-        let ident = Ident::new(&snake_to_camel(&self.name), Span::call_site());
+/// The modules holding the enums of each interface, shared by the client and server code
+pub(crate) fn generate_shared_enums(protocol: &Protocol) -> TokenStream {
+    protocol
+        .interfaces
+        .iter()
+        .filter(|interface| !interface.enums.is_empty())
+        .map(|interface| {
+            let mod_name = Ident::new(&interface.name, Span::call_site());
+            let mod_doc = to_doc_attr(&format!("Enums of the `{}` interface", interface.name));
+            let iface_const_name =
+                format_ident!("{}_INTERFACE", interface.name.to_ascii_uppercase());
+            let mut tokens = TokenStream::new();
+            for enu in &interface.enums {
+                enum_to_tokens(enu, None, &mut tokens);
+                if enu.name == "error" && !enu.bitfield {
+                    quote! {
+                        impl super::wayland_backend::protocol::InterfaceError for Error {
+                            fn interface() -> &'static super::wayland_backend::protocol::Interface {
+                                &super::#iface_const_name
+                            }
+                        }
+                    }
+                    .to_tokens(&mut tokens);
+                }
+            }
+            quote! {
+                #mod_doc
+                pub mod #mod_name {
+                    #tokens
+                }
+            }
+        })
+        .collect()
+}
 
-        if self.bitfield {
-            let entries = self.entries.iter().map(|entry| {
-                let doc_attr = entry
-                    .description
-                    .as_ref()
-                    .map(description_to_doc_attr)
-                    .or_else(|| entry.summary.as_ref().map(|s| to_doc_attr(s)));
+fn interface_error_to_tokens(interface: &Interface, side: Side, tokens: &mut TokenStream) {
+    let iface_const_name = format_ident!("{}_INTERFACE", interface.name.to_ascii_uppercase());
+    let krate = match side {
+        Side::Client => quote! { wayland_client },
+        Side::Server => quote! { wayland_server },
+    };
+    quote! {
+        impl super::#krate::backend::protocol::InterfaceError for Error {
+            fn interface() -> &'static Interface {
+                &super::#iface_const_name
+            }
+        }
+    }
+    .to_tokens(tokens);
+}
 
-                let prefix = if entry.name.chars().next().unwrap().is_numeric() { "_" } else { "" };
-                let ident = format_ident!("{}{}", prefix, snake_to_camel(&entry.name));
+// With a path to the `serialize` module of wayland-client or wayland-server, the enum implements
+// `Serialize` and `Deserialize` as its `u32` value
+fn enum_to_tokens(enu: &Enum, serialize: Option<&TokenStream>, tokens: &mut TokenStream) {
+    let enum_decl;
+    let enum_impl;
 
-                let value = Literal::u32_unsuffixed(entry.value);
+    let doc_attr = enu.description.as_ref().map(description_to_doc_attr);
+    let ident = Ident::new(&snake_to_camel(&enu.name), Span::call_site());
 
-                quote! {
-                    #doc_attr
-                    const #ident = #value;
-                }
-            });
+    if enu.bitfield {
+        let entries = enu.entries.iter().map(|entry| {
+            let doc_attr = entry
+                .description
+                .as_ref()
+                .map(description_to_doc_attr)
+                .or_else(|| entry.summary.as_ref().map(|s| to_doc_attr(s)));
 
-            enum_decl = quote! {
-                bitflags::bitflags! {
-                    #doc_attr
-                    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-                    pub struct #ident: u32 {
-                        #(#entries)*
-                    }
+            let prefix = if entry.name.chars().next().unwrap().is_numeric() { "_" } else { "" };
+            let ident = format_ident!("{}{}", prefix, snake_to_camel(&entry.name));
+
+            let value = Literal::u32_unsuffixed(entry.value);
+
+            quote! {
+                #doc_attr
+                const #ident = #value;
+            }
+        });
+
+        enum_decl = quote! {
+            bitflags::bitflags! {
+                #doc_attr
+                #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+                pub struct #ident: u32 {
+                    #(#entries)*
                 }
-            };
-            enum_impl = quote! {
-                impl std::convert::TryFrom<u32> for #ident {
-                    type Error = ();
-                    fn try_from(val: u32) -> Result<#ident, ()> {
-                        #ident::from_bits(val).ok_or(())
-                    }
+            }
+        };
+        enum_impl = quote! {
+            impl std::convert::TryFrom<u32> for #ident {
+                type Error = ();
+                fn try_from(val: u32) -> Result<#ident, ()> {
+                    #ident::from_bits(val).ok_or(())
                 }
-                impl std::convert::From<#ident> for u32 {
-                    fn from(val: #ident) -> u32 {
-                        val.bits()
-                    }
+            }
+            impl std::convert::From<#ident> for u32 {
+                fn from(val: #ident) -> u32 {
+                    val.bits()
                 }
-            };
-        } else {
-            let variants = self.entries.iter().map(|entry| {
-                let doc_attr = entry
-                    .description
-                    .as_ref()
-                    .map(description_to_doc_attr)
-                    .or_else(|| entry.summary.as_ref().map(|s| to_doc_attr(s)));
-
-                let prefix = if entry.name.chars().next().unwrap().is_numeric() { "_" } else { "" };
-                let variant = format_ident!("{}{}", prefix, snake_to_camel(&entry.name));
+            }
+        };
+    } else {
+        let variants = enu.entries.iter().map(|entry| {
+            let doc_attr = entry
+                .description
+                .as_ref()
+                .map(description_to_doc_attr)
+                .or_else(|| entry.summary.as_ref().map(|s| to_doc_attr(s)));
 
-                let value = Literal::u32_unsuffixed(entry.value);
+            let prefix = if entry.name.chars().next().unwrap().is_numeric() { "_" } else { "" };
+            let variant = format_ident!("{}{}", prefix, snake_to_camel(&entry.name));
 
-                quote! {
-                    #doc_attr
-                    #variant = #value
-                }
-            });
+            let value = Literal::u32_unsuffixed(entry.value);
 
-            enum_decl = quote! {
+            quote! {
                 #doc_attr
-                #[repr(u32)]
-                #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-                #[non_exhaustive]
-                pub enum #ident {
-                    #(#variants,)*
-                }
-            };
+                #variant = #value
+            }
+        });
 
-            let match_arms = self.entries.iter().map(|entry| {
-                let value = Literal::u32_unsuffixed(entry.value);
+        enum_decl = quote! {
+            #doc_attr
+            #[repr(u32)]
+            #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            #[non_exhaustive]
+            pub enum #ident {
+                #(#variants,)*
+            }
+        };
 
-                let prefix = if entry.name.chars().next().unwrap().is_numeric() { "_" } else { "" };
-                let variant = format_ident!("{}{}", prefix, snake_to_camel(&entry.name));
+        let match_arms = enu.entries.iter().map(|entry| {
+            let value = Literal::u32_unsuffixed(entry.value);
 
-                quote! {
-                    #value => Ok(#ident::#variant)
-                }
-            });
+            let prefix = if entry.name.chars().next().unwrap().is_numeric() { "_" } else { "" };
+            let variant = format_ident!("{}{}", prefix, snake_to_camel(&entry.name));
 
-            enum_impl = quote! {
-                impl std::convert::TryFrom<u32> for #ident {
-                    type Error = ();
-                    fn try_from(val: u32) -> Result<#ident, ()> {
-                        match val {
-                            #(#match_arms,)*
-                            _ => Err(())
-                        }
+            quote! {
+                #value => Ok(#ident::#variant)
+            }
+        });
+
+        enum_impl = quote! {
+            impl std::convert::TryFrom<u32> for #ident {
+                type Error = ();
+                fn try_from(val: u32) -> Result<#ident, ()> {
+                    match val {
+                        #(#match_arms,)*
+                        _ => Err(())
                     }
                 }
-                impl std::convert::From<#ident> for u32 {
-                    fn from(val: #ident) -> u32 {
-                        val as u32
-                    }
+            }
+            impl std::convert::From<#ident> for u32 {
+                fn from(val: #ident) -> u32 {
+                    val as u32
                 }
-            };
-        }
+            }
+        };
+    }
 
-        enum_decl.to_tokens(tokens);
-        enum_impl.to_tokens(tokens);
+    enum_decl.to_tokens(tokens);
+    enum_impl.to_tokens(tokens);
+
+    if let Some(serialize) = serialize {
+        let name_str = snake_to_camel(&enu.name);
+        quote! {
+            impl #serialize::serde::Serialize for #ident {
+                fn serialize<S: #serialize::serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                    serializer.serialize_u32(u32::from(*self))
+                }
+            }
+            impl<'de> #serialize::serde::Deserialize<'de> for #ident {
+                fn deserialize<D: #serialize::serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<#ident, D::Error> {
+                    #serialize::deserialize_enum(deserializer, #name_str)
+                }
+            }
+        }
+        .to_tokens(tokens);
     }
 }
 
@@ -154,6 +256,14 @@ pub(crate) fn gen_msg_constants(requests: &[Message], events: &[Message]) -> Tok
     }
 }
 
+/// The `#[deprecated]` attribute of the method sending a message, if it is deprecated
+pub(crate) fn gen_deprecated_attr(msg: &Message) -> Option<TokenStream> {
+    msg.deprecated_since.map(|version| {
+        let note = format!("deprecated since version {} of the interface", version);
+        quote! { #[deprecated(note = #note)] }
+    })
+}
+
 pub(crate) fn gen_message_enum(
     name: &Ident,
     side: Side,
@@ -179,6 +289,9 @@ pub(crate) fn gen_message_enum(
                 write!(docs, "\nOnly available since version {} of the interface", msg.since)
                     .unwrap();
             }
+            if let Some(version) = msg.deprecated_since {
+                write!(docs, "\nDeprecated since version {} of the interface", version).unwrap();
+            }
 
             let doc_attr = to_doc_attr(&docs);
             let msg_name = Ident::new(&snake_to_camel(&msg.name), Span::call_site());
@@ -329,6 +442,78 @@ pub(crate) fn gen_message_enum(
     }
 }
 
+pub(crate) fn gen_serialize_impl(
+    name: &Ident,
+    side: Side,
+    receiver: bool,
+    messages: &[Message],
+) -> TokenStream {
+    let serialize = match side {
+        Side::Client => quote! { super::wayland_client::serialize },
+        Side::Server => quote! { super::wayland_server::serialize },
+    };
+    let name_str = name.to_string();
+
+    let cases = messages.iter().enumerate().map(|(index, msg)| {
+        let msg_name = Ident::new(&snake_to_camel(&msg.name), Span::call_site());
+        let msg_name_str = msg_name.to_string();
+        let index = index as u32;
+        let fields = msg
+            .args
+            .iter()
+            .filter(|arg| {
+                // the fields skipped by gen_message_enum
+                !(arg.typ == Type::NewId
+                    && !receiver
+                    && side == Side::Client
+                    && arg.interface.is_some())
+            })
+            .map(|arg| {
+                let field_name =
+                    format_ident!("{}{}", if is_keyword(&arg.name) { "_" } else { "" }, arg.name);
+                (field_name, arg.name.clone())
+            })
+            .collect::<Vec<_>>();
+        if msg.args.is_empty() {
+            quote! {
+                #name::#msg_name => __serializer.serialize_unit_variant(#name_str, #index, #msg_name_str)
+            }
+        } else {
+            let len = fields.len();
+            let field_names = fields.iter().map(|(field_name, _)| field_name);
+            let serialize_fields = fields.iter().map(|(field_name, field_str)| {
+                quote! { __state.serialize_field(#field_str, &Arg(#field_name))?; }
+            });
+            quote! {
+                #name::#msg_name { #(ref #field_names,)* .. } => {
+                    let mut __state = __serializer.serialize_struct_variant(#name_str, #index, #msg_name_str, #len)?;
+                    #(#serialize_fields)*
+                    __state.end()
+                }
+            }
+        }
+    });
+
+    let (generic, phantom_case) = if !receiver {
+        (quote! { 'a }, quote! { #name::__phantom_lifetime { never, .. } => match never {} })
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    quote! {
+        impl<#generic> #serialize::serde::Serialize for #name<#generic> {
+            fn serialize<S: #serialize::serde::Serializer>(&self, __serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                #[allow(unused_imports)]
+                use #serialize::{Arg, serde::ser::SerializeStructVariant};
+                match *self {
+                    #(#cases,)*
+                    #phantom_case
+                }
+            }
+        }
+    }
+}
+
 pub(crate) fn gen_parse_body(interface: &Interface, side: Side) -> TokenStream {
     let msgs = match side {
         Side::Client => &interface.events,
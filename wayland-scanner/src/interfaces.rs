@@ -67,7 +67,7 @@ fn build_messagedesc_list(list: &[Message]) -> TokenStream {
                     wayland_backend::protocol::ArgumentType::NewId
                 }
             } else {
-                let typ = arg.typ.common_type();
+                let typ = common_type(arg.typ);
                 if arg.typ.nullable() {
                     if arg.allow_null {
                         quote! { wayland_backend::protocol::ArgumentType::#typ(wayland_backend::protocol::AllowNull::Yes) }
@@ -119,6 +119,21 @@ fn build_messagedesc_list(list: &[Message]) -> TokenStream {
     )
 }
 
+// The variant of `wayland_backend::protocol::ArgumentType` for this type
+fn common_type(typ: Type) -> TokenStream {
+    match typ {
+        Type::Int => quote!(Int),
+        Type::Uint => quote!(Uint),
+        Type::Fixed => quote!(Fixed),
+        Type::Array => quote!(Array),
+        Type::Fd => quote!(Fd),
+        Type::String => quote!(Str),
+        Type::Object => quote!(Object),
+        Type::NewId => quote!(NewId),
+        Type::Destructor => panic!("Destructor is not a valid argument type."),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -35,6 +35,44 @@
 //!     wayland_scanner::generate_client_code!("./path/to/the/protocol.xml");
 //! }
 //! ```
+//!
+//! ## Sharing the enums between client and server code
+//!
+//! When the client and server code of a protocol are generated in the same crate, for example by a
+//! compositor test suite, each side gets its own copy of the enums, which are distinct types. The enums
+//! can instead be generated once with [`generate_enums!`], and re-exported by both sides by giving the
+//! path of their module to the `enums` argument of the code generation macros:
+//!
+//! ```rust,ignore
+//! pub mod my_protocol {
+//!     use wayland_client::backend as wayland_backend;
+//!
+//!     pub mod __interfaces {
+//!         use wayland_client::backend as wayland_backend;
+//!         wayland_scanner::generate_interfaces!("./path/to/the/protocol.xml");
+//!     }
+//!
+//!     pub mod types {
+//!         use super::wayland_backend;
+//!         use super::__interfaces::*;
+//!         wayland_scanner::generate_enums!("./path/to/the/protocol.xml");
+//!     }
+//!
+//!     pub mod client {
+//!         use wayland_client;
+//!         use super::__interfaces::*;
+//!         wayland_scanner::generate_client_code!("./path/to/the/protocol.xml", enums = super::types);
+//!     }
+//!
+//!     pub mod server {
+//!         use wayland_server;
+//!         use super::__interfaces::*;
+//!         wayland_scanner::generate_server_code!("./path/to/the/protocol.xml", enums = super::types);
+//!     }
+//! }
+//! ```
+//!
+//! The path is relative to the module in which the macro is invoked.
 
 use std::{ffi::OsString, path::PathBuf};
 
@@ -42,54 +80,102 @@ mod c_interfaces;
 mod client_gen;
 mod common;
 mod interfaces;
-mod parse;
-mod protocol;
+mod render;
 mod server_gen;
 mod token;
 mod util;
 
+use wayland_protocol_model::{parse, protocol};
+
 /// Proc-macro for generating low-level interfaces associated with an XML specification
 #[proc_macro]
 pub fn generate_interfaces(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let path: OsString = token::parse_lit_str_token(stream).into();
-    let path = if let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
-        let mut buf = PathBuf::from(manifest_dir);
-        buf.push(path);
-        buf
-    } else {
-        path.into()
-    };
-    let file = match std::fs::File::open(&path) {
-        Ok(file) => file,
-        Err(e) => panic!("Failed to open protocol file {}: {}", path.display(), e),
-    };
-    let protocol = parse::parse(file);
+    let protocol = parse_protocol_file(stream);
     interfaces::generate(&protocol, true).into()
 }
 
 /// Proc-macro for generating client-side API associated with an XML specification
+///
+/// With an `enums = path::to::module` argument, the enums are re-exported from the module
+/// generated by [`generate_enums!`] instead of being generated, see the [crate documentation](crate).
+///
+/// With a `serde` argument, the `Request` and `Event` enums implement `serde::Serialize`, and the
+/// enums and bitflags of the protocol `serde::Serialize` and `serde::Deserialize`. This requires the
+/// `serde` cargo feature of `wayland-client`, see its `serialize` module.
 #[proc_macro]
 pub fn generate_client_code(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let path: OsString = token::parse_lit_str_token(stream).into();
-    let path = if let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
-        let mut buf = PathBuf::from(manifest_dir);
-        buf.push(path);
-        buf
-    } else {
-        path.into()
-    };
-    let file = match std::fs::File::open(&path) {
-        Ok(file) => file,
-        Err(e) => panic!("Failed to open protocol file {}: {}", path.display(), e),
-    };
-    let protocol = parse::parse(file);
-    client_gen::generate_client_objects(&protocol).into()
+    let (file, args) = token::parse_codegen_args(stream);
+    let protocol = load_protocol_file(file.into());
+    client_gen::generate_client_objects(&protocol, args.enums.as_ref(), args.serde).into()
 }
 
 /// Proc-macro for generating server-side API associated with an XML specification
+///
+/// It accepts the same `enums = path::to::module` and `serde` arguments as
+/// [`generate_client_code!`], the latter requiring the `serde` cargo feature of `wayland-server`.
 #[proc_macro]
 pub fn generate_server_code(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let path: OsString = token::parse_lit_str_token(stream).into();
+    let (file, args) = token::parse_codegen_args(stream);
+    let protocol = load_protocol_file(file.into());
+    server_gen::generate_server_objects(&protocol, args.enums.as_ref(), args.serde).into()
+}
+
+/// Proc-macro for generating the enums associated with an XML specification, in their own modules
+///
+/// Each interface with enums gets a module of the same name holding them. Like the code generated by
+/// [`generate_client_code!`], it expects the low-level interfaces in its parent module, and
+/// `wayland_backend` to be in scope.
+#[proc_macro]
+pub fn generate_enums(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let protocol = parse_protocol_file(stream);
+    common::generate_shared_enums(&protocol).into()
+}
+
+/// Proc-macro rendering the low-level interfaces associated with an XML specification as a string
+///
+/// The code is the one generated by [`generate_interfaces!`], rendered as normalized text without
+/// depending on rustfmt: each item, statement, attribute and field is on its own line, indented by
+/// its nesting. This is intended for snapshot tests, to review changes to the generated code:
+///
+/// ```rust,ignore
+/// #[test]
+/// fn generated_interfaces() {
+///     insta::assert_snapshot!(wayland_scanner::render_interfaces!("./path/to/the/protocol.xml"));
+/// }
+/// ```
+#[proc_macro]
+pub fn render_interfaces(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let protocol = parse_protocol_file(stream);
+    let text = render::render(interfaces::generate(&protocol, true));
+    quote::quote!(#text).into()
+}
+
+/// Proc-macro rendering the client-side API associated with an XML specification as a string
+///
+/// See [`render_interfaces!`] for the format of the string.
+#[proc_macro]
+pub fn render_client_code(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let protocol = parse_protocol_file(stream);
+    let text = render::render(client_gen::generate_client_objects(&protocol, None, false));
+    quote::quote!(#text).into()
+}
+
+/// Proc-macro rendering the server-side API associated with an XML specification as a string
+///
+/// See [`render_interfaces!`] for the format of the string.
+#[proc_macro]
+pub fn render_server_code(stream: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let protocol = parse_protocol_file(stream);
+    let text = render::render(server_gen::generate_server_objects(&protocol, None, false));
+    quote::quote!(#text).into()
+}
+
+// Parse the protocol file whose path, relative to the crate root, is given to the macro
+fn parse_protocol_file(stream: proc_macro::TokenStream) -> protocol::Protocol {
+    load_protocol_file(token::parse_lit_str_token(stream).into())
+}
+
+fn load_protocol_file(path: OsString) -> protocol::Protocol {
     let path = if let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
         let mut buf = PathBuf::from(manifest_dir);
         buf.push(path);
@@ -101,8 +187,7 @@ pub fn generate_server_code(stream: proc_macro::TokenStream) -> proc_macro::Toke
         Ok(file) => file,
         Err(e) => panic!("Failed to open protocol file {}: {}", path.display(), e),
     };
-    let protocol = parse::parse(file);
-    server_gen::generate_server_objects(&protocol).into()
+    parse::parse(file)
 }
 
 #[cfg(test)]
@@ -0,0 +1,188 @@
+//! Rendering of the generated code as normalized text, for snapshot tests
+//!
+//! The output does not depend on rustfmt: each item, statement, attribute and field is on its own
+//! line, indented by its nesting in braces, so that changes to the generated code show up as
+//! readable line diffs.
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+use crate::util::is_keyword;
+
+pub(crate) fn render(tokens: TokenStream) -> String {
+    let mut renderer = Renderer { text: String::new(), indent: 0, state: State::LineStart };
+    renderer.stream(tokens, true);
+    renderer.text.push('\n');
+    renderer.text
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    // nothing written on the current line yet
+    LineStart,
+    // a line break is due before anything but a separator, after a closing brace
+    AfterBlock,
+    // the next token is separated by a space
+    Space,
+    // the next token is glued to the previous one
+    Glued,
+}
+
+struct Renderer {
+    text: String,
+    indent: usize,
+    state: State,
+}
+
+impl Renderer {
+    fn newline(&mut self) {
+        if self.state != State::LineStart {
+            self.text.push('\n');
+            self.state = State::LineStart;
+        }
+    }
+
+    fn write(&mut self, token: &str, glue_before: bool) {
+        match self.state {
+            State::AfterBlock if !glue_before => self.newline(),
+            State::Space if !glue_before => self.text.push(' '),
+            _ => {}
+        }
+        if self.state == State::LineStart {
+            self.text.extend(std::iter::repeat("    ").take(self.indent));
+        }
+        self.text.push_str(token);
+        self.state = State::Space;
+    }
+
+    // whether the text ends with a name or generic arguments, rather than a keyword or an operator
+    fn after_callee(&self) -> bool {
+        if self.text.ends_with('>') {
+            return !self.text.ends_with("->") && !self.text.ends_with("=>");
+        }
+        let word_start =
+            self.text.rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map_or(0, |i| i + 1);
+        let word = &self.text[word_start..];
+        !word.is_empty() && !is_keyword(word)
+    }
+
+    // `in_block` is true for the contents of braces, whose commas end lines
+    fn stream(&mut self, tokens: TokenStream, in_block: bool) {
+        let mut tokens = tokens.into_iter().peekable();
+        // generic parameters are not groups, their commas are tracked separately
+        let mut generics_depth = 0usize;
+        while let Some(token) = tokens.next() {
+            match token {
+                TokenTree::Group(group) => match group.delimiter() {
+                    Delimiter::Brace if group.stream().is_empty() => self.write("{}", false),
+                    Delimiter::Brace => {
+                        self.write("{", false);
+                        self.newline();
+                        self.indent += 1;
+                        self.stream(group.stream(), true);
+                        self.newline();
+                        self.indent -= 1;
+                        self.write("}", false);
+                        self.state = State::AfterBlock;
+                    }
+                    delimiter => {
+                        let (open, close) = match delimiter {
+                            Delimiter::Parenthesis => ("(", ")"),
+                            Delimiter::Bracket => ("[", "]"),
+                            _ => ("", ""),
+                        };
+                        // calls and indexing are glued to what precedes them
+                        let glue = self.state == State::Glued
+                            || (self.state == State::Space && self.after_callee());
+                        self.write(open, glue && !open.is_empty());
+                        self.state = State::Glued;
+                        self.stream(group.stream(), false);
+                        self.write(close, true);
+                    }
+                },
+                TokenTree::Punct(punct) => {
+                    let ch = punct.as_char();
+                    let mut buf = [0; 4];
+                    // paths, generics and field accesses are written without spaces
+                    let glue = matches!(ch, ',' | ';' | '.' | '<' | '?')
+                        || (ch == '>' && generics_depth > 0)
+                        || (ch == ':' && !self.text.ends_with(':'))
+                        // macro invocations
+                        || (ch == '!'
+                            && self.after_callee()
+                            && matches!(tokens.peek(), Some(TokenTree::Group(_))))
+                        || self.state == State::Glued;
+                    self.write(ch.encode_utf8(&mut buf), glue);
+                    match ch {
+                        '<' => generics_depth += 1,
+                        '>' if !self.text.ends_with("->") && !self.text.ends_with("=>") => {
+                            generics_depth = generics_depth.saturating_sub(1)
+                        }
+                        _ => {}
+                    }
+                    match ch {
+                        ';' if in_block => self.newline(),
+                        ',' if in_block && generics_depth == 0 => self.newline(),
+                        '#' if matches!(
+                            tokens.peek(),
+                            Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket
+                        ) =>
+                        {
+                            // attributes get their own line
+                            self.state = State::Glued;
+                            if let Some(attribute) = tokens.next() {
+                                self.stream(std::iter::once(attribute).collect(), false);
+                            }
+                            self.newline();
+                        }
+                        ':' if self.text.ends_with("::") => self.state = State::Glued,
+                        '&' if self.text.ends_with("&&") => {}
+                        '.' | '&' | '*' | '!' | '\'' | '<' => self.state = State::Glued,
+                        _ if punct.spacing() == Spacing::Joint => self.state = State::Glued,
+                        _ => {}
+                    }
+                }
+                TokenTree::Ident(ident) => self.write(&ident.to_string(), false),
+                TokenTree::Literal(literal) => self.write(&literal.to_string(), false),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    #[test]
+    fn render_items() {
+        let tokens = quote! {
+            #[doc = "A thing"]
+            pub struct Thing<'a> { name: &'a str, ids: Vec<u32>, }
+            impl Thing<'_> {
+                fn check(&self, id: u32) -> Result<(), std::io::Error> {
+                    if !self.ids.contains(&id) { return Err(std::io::ErrorKind::Other.into()); }
+                    debug_assert!(id > 0);
+                    Ok(())
+                }
+            }
+        };
+        assert_eq!(
+            super::render(tokens),
+            "\
+#[doc = \"A thing\"]
+pub struct Thing<'a> {
+    name: &'a str,
+    ids: Vec<u32>,
+}
+impl Thing<'_> {
+    fn check(&self, id: u32) -> Result<(), std::io::Error> {
+        if !self.ids.contains(&id) {
+            return Err(std::io::ErrorKind::Other.into());
+        }
+        debug_assert!(id > 0);
+        Ok(())
+    }
+}
+"
+        );
+    }
+}
@@ -8,22 +8,30 @@ use crate::{
     Side,
 };
 
-pub fn generate_server_objects(protocol: &Protocol) -> TokenStream {
+pub fn generate_server_objects(
+    protocol: &Protocol,
+    shared_enums: Option<&TokenStream>,
+    serde: bool,
+) -> TokenStream {
     protocol
         .interfaces
         .iter()
         .filter(|iface| iface.name != "wl_display" && iface.name != "wl_registry")
-        .map(generate_objects_for)
+        .map(|interface| generate_objects_for(interface, shared_enums, serde))
         .collect()
 }
 
-fn generate_objects_for(interface: &Interface) -> TokenStream {
+fn generate_objects_for(
+    interface: &Interface,
+    shared_enums: Option<&TokenStream>,
+    serde: bool,
+) -> TokenStream {
     let mod_name = Ident::new(&interface.name, Span::call_site());
     let mod_doc = interface.description.as_ref().map(description_to_doc_attr);
     let iface_name = Ident::new(&snake_to_camel(&interface.name), Span::call_site());
     let iface_const_name = format_ident!("{}_INTERFACE", interface.name.to_ascii_uppercase());
 
-    let enums = crate::common::generate_enums_for(interface);
+    let enums = crate::common::gen_enums_or_reexport(interface, Side::Server, shared_enums, serde);
     let msg_constants = crate::common::gen_msg_constants(&interface.requests, &interface.events);
 
     let requests = crate::common::gen_message_enum(
@@ -38,10 +46,26 @@ fn generate_objects_for(interface: &Interface) -> TokenStream {
         false,
         &interface.events,
     );
+    let serialize = serde.then(|| {
+        let requests = crate::common::gen_serialize_impl(
+            &format_ident!("Request"),
+            Side::Server,
+            true,
+            &interface.requests,
+        );
+        let events = crate::common::gen_serialize_impl(
+            &format_ident!("Event"),
+            Side::Server,
+            false,
+            &interface.events,
+        );
+        quote! { #requests #events }
+    });
 
     let parse_body = crate::common::gen_parse_body(interface, Side::Server);
     let write_body = crate::common::gen_write_body(interface, Side::Server);
     let methods = gen_methods(interface);
+    let post_typed_error = gen_post_typed_error(interface);
 
     let event_ref = if interface.requests.is_empty() {
         "This interface has no requests."
@@ -72,6 +96,7 @@ fn generate_objects_for(interface: &Interface) -> TokenStream {
             #msg_constants
             #requests
             #events
+            #serialize
 
             #doc_attr
             #[derive(Debug, Clone)]
@@ -170,6 +195,7 @@ fn generate_objects_for(interface: &Interface) -> TokenStream {
             }
 
             impl #iface_name {
+                #post_typed_error
                 #methods
             }
         }
@@ -251,9 +277,11 @@ fn gen_methods(interface: &Interface) -> TokenStream {
             });
 
             let doc_attr = request.description.as_ref().map(description_to_doc_attr);
+            let deprecated_attr = crate::common::gen_deprecated_attr(request);
 
             quote! {
                 #doc_attr
+                #deprecated_attr
                 #[allow(clippy::too_many_arguments)]
                 pub fn #method_name(&self, #(#fn_args),*) {
                     let _ = self.send_event(
@@ -267,6 +295,18 @@ fn gen_methods(interface: &Interface) -> TokenStream {
         .collect()
 }
 
+fn gen_post_typed_error(interface: &Interface) -> Option<TokenStream> {
+    interface.enums.iter().find(|enu| enu.name == "error" && !enu.bitfield)?;
+    Some(quote! {
+        /// Trigger a protocol error on this object, with a value of the `Error` enum of its interface
+        ///
+        /// See [`Resource::post_error()`].
+        pub fn post_typed_error(&self, error: Error, message: impl Into<String>) {
+            self.post_error(error, message)
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -274,7 +314,8 @@ mod tests {
         let protocol_file =
             std::fs::File::open("./tests/scanner_assets/test-protocol.xml").unwrap();
         let protocol_parsed = crate::parse::parse(protocol_file);
-        let generated: String = super::generate_server_objects(&protocol_parsed).to_string();
+        let generated: String =
+            super::generate_server_objects(&protocol_parsed, None, false).to_string();
         let generated = crate::format_rust_code(&generated);
 
         let reference =
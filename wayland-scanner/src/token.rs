@@ -1,3 +1,5 @@
+use proc_macro2::TokenStream;
+
 // `bytes`, `next_chr`, `parse_lit_str`, `parse_lit_str_cooked` and `parse_lit_str_raw` are adapted
 // from syn:
 // https://github.com/dtolnay/syn/blob/362ee2d02df3f1b2e74c7b7a4cf2ed3c106404c9/src/lit.rs#L1062-L1167
@@ -184,3 +186,53 @@ pub fn parse_lit_str_token(mut stream: proc_macro::TokenStream) -> String {
         return parse_lit_str(&literal.to_string());
     }
 }
+
+/// The optional arguments of the code generation macros
+#[derive(Default)]
+pub struct CodegenArgs {
+    /// The module of the shared enums, given by `enums = path::to::module`
+    pub enums: Option<TokenStream>,
+    /// Whether the generated types implement the `serde` traits, given by `serde`
+    pub serde: bool,
+}
+
+/// Parse the arguments of the code generation macros
+///
+/// They are the path of the protocol file, optionally followed by `, enums = path::to::module` and
+/// `, serde`, in any order.
+pub fn parse_codegen_args(stream: proc_macro::TokenStream) -> (String, CodegenArgs) {
+    let mut iter = stream.into_iter().peekable();
+    let file = parse_lit_str_token(iter.next().expect("expected string argument").into());
+    let mut args = CodegenArgs::default();
+    loop {
+        match iter.next() {
+            None => return (file, args),
+            Some(proc_macro::TokenTree::Punct(punct)) if punct.as_char() == ',' => {}
+            Some(token) => panic!("unexpected token `{}`", token),
+        }
+        match iter.next() {
+            // trailing comma
+            None => return (file, args),
+            Some(proc_macro::TokenTree::Ident(ident)) if ident.to_string() == "serde" => {
+                args.serde = true;
+            }
+            Some(proc_macro::TokenTree::Ident(ident)) if ident.to_string() == "enums" => {
+                match iter.next() {
+                    Some(proc_macro::TokenTree::Punct(punct)) if punct.as_char() == '=' => {}
+                    _ => panic!("expected `=` after `enums`"),
+                }
+                let mut module = proc_macro::TokenStream::new();
+                while let Some(token) = iter.next_if(
+                    |token| !matches!(token, proc_macro::TokenTree::Punct(p) if p.as_char() == ','),
+                ) {
+                    module.extend(Some(token));
+                }
+                assert!(!module.is_empty(), "expected a module path after `enums =`");
+                args.enums = Some(module.into());
+            }
+            Some(token) => {
+                panic!("expected `enums = <module path>` or `serde`, found `{}`", token)
+            }
+        }
+    }
+}
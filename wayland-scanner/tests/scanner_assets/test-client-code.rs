@@ -40,6 +40,11 @@ pub mod wl_display {
             val as u32
         }
     }
+    impl super::wayland_client::backend::protocol::InterfaceError for Error {
+        fn interface() -> &'static Interface {
+            &super::WL_DISPLAY_INTERFACE
+        }
+    }
     #[doc = r" The minimal object version supporting this request"]
     pub const REQ_SYNC_SINCE: u32 = 1u32;
     #[doc = r" The wire opcode for this request"]
@@ -810,7 +815,7 @@ pub mod test_global {
         GetSecondary {},
         #[doc = "Only available since version 3 of the interface"]
         GetTertiary {},
-        #[doc = "link a secondary and a tertiary\n\n\n\nOnly available since version 3 of the interface"]
+        #[doc = "link a secondary and a tertiary\n\n\n\nOnly available since version 3 of the interface\nDeprecated since version 5 of the interface"]
         Link { sec: super::secondary::Secondary, ter: Option<super::tertiary::Tertiary>, time: u32 },
         #[doc = "This is a destructor, once sent this object cannot be used any longer.\nOnly available since version 4 of the interface"]
         Destroy,
@@ -860,7 +865,7 @@ pub mod test_global {
             #[doc = "a file descriptor"]
             file_descriptor: OwnedFd,
         },
-        #[doc = "acking the creation of a secondary"]
+        #[doc = "acking the creation of a secondary\n\n\n\nDeprecated since version 2 of the interface"]
         AckSecondary { sec: super::secondary::Secondary },
         #[doc = "create a new quad optionally replacing a previous one"]
         CycleQuad { new_quad: super::quad::Quad, old_quad: Option<super::quad::Quad> },
@@ -1256,6 +1261,7 @@ pub mod test_global {
             .unwrap_or_else(|_| Proxy::inert(self.backend.clone()))
         }
         #[doc = "link a secondary and a tertiary"]
+        #[deprecated(note = "deprecated since version 5 of the interface")]
         #[allow(clippy::too_many_arguments)]
         pub fn link(
             &self,
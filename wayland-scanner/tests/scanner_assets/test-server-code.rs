@@ -241,7 +241,7 @@ pub mod test_global {
             #[doc = "create a tertiary"]
             ter: New<super::tertiary::Tertiary>,
         },
-        #[doc = "link a secondary and a tertiary\n\n\n\nOnly available since version 3 of the interface"]
+        #[doc = "link a secondary and a tertiary\n\n\n\nOnly available since version 3 of the interface\nDeprecated since version 5 of the interface"]
         Link { sec: super::secondary::Secondary, ter: Option<super::tertiary::Tertiary>, time: u32 },
         #[doc = "This is a destructor, once received this object cannot be used any longer.\nOnly available since version 4 of the interface"]
         Destroy,
@@ -286,7 +286,7 @@ pub mod test_global {
             #[doc = "a file descriptor"]
             file_descriptor: std::os::unix::io::BorrowedFd<'a>,
         },
-        #[doc = "acking the creation of a secondary"]
+        #[doc = "acking the creation of a secondary\n\n\n\nDeprecated since version 2 of the interface"]
         AckSecondary { sec: super::secondary::Secondary },
         #[doc = "create a new quad optionally replacing a previous one"]
         CycleQuad { new_quad: super::quad::Quad, old_quad: Option<super::quad::Quad> },
@@ -750,6 +750,7 @@ pub mod test_global {
             });
         }
         #[doc = "acking the creation of a secondary"]
+        #[deprecated(note = "deprecated since version 2 of the interface")]
         #[allow(clippy::too_many_arguments)]
         pub fn ack_secondary(&self, sec: &super::secondary::Secondary) {
             let _ = self.send_event(Event::AckSecondary { sec: sec.clone() });
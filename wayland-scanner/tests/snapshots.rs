@@ -0,0 +1,52 @@
+//! Snapshots of the code generated for the core protocol
+//!
+//! A change to the generated code makes these tests fail with the diff of the snapshot. Once the
+//! change is intended, run them with `UPDATE_SNAPSHOTS=1` to write the new snapshots, to be
+//! reviewed and committed along with the change.
+
+use std::path::PathBuf;
+
+fn check_snapshot(name: &str, rendered: &str) {
+    let path: PathBuf =
+        [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", &format!("{}.snap", name)]
+            .iter()
+            .collect();
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, rendered).unwrap();
+        return;
+    }
+
+    let snapshot = std::fs::read_to_string(&path).unwrap_or_default();
+    if snapshot != rendered {
+        let diff = similar::TextDiff::from_lines(snapshot.as_str(), rendered);
+        print!("{}", diff.unified_diff().context_radius(3).header("snapshot", "generated"));
+        panic!(
+            "The code generated does not match {}, run with UPDATE_SNAPSHOTS=1 to update it",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn wayland_interfaces() {
+    check_snapshot(
+        "wayland-interfaces",
+        wayland_scanner::render_interfaces!("../wayland-client/wayland.xml"),
+    );
+}
+
+#[test]
+fn wayland_client_code() {
+    check_snapshot(
+        "wayland-client-code",
+        wayland_scanner::render_client_code!("../wayland-client/wayland.xml"),
+    );
+}
+
+#[test]
+fn wayland_server_code() {
+    check_snapshot(
+        "wayland-server-code",
+        wayland_scanner::render_server_code!("../wayland-client/wayland.xml"),
+    );
+}
@@ -1,11 +1,20 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 use wayland_backend::{
-    protocol::ProtocolError,
-    server::{ClientData, ClientId, DisconnectReason, InvalidId, ObjectData},
+    protocol::{same_interface, IoStats, Metrics, ProtocolError},
+    server::{
+        ClientData, ClientId, DestroyListenerId, DisconnectReason, InvalidId, ObjectData,
+        OverflowPolicy,
+    },
 };
 
-use crate::{dispatch::ResourceData, Dispatch, DisplayHandle, Resource};
+use crate::{
+    dispatch::ResourceData, security_context::SandboxedClientData, Dispatch, DisplayHandle,
+    Resource, SecurityContext,
+};
 
 /// A struct representing a Wayland client connected to your compositor.
 #[derive(Clone, Debug)]
@@ -15,7 +24,10 @@ pub struct Client {
 }
 
 impl Client {
-    pub(crate) fn from_id(handle: &DisplayHandle, id: ClientId) -> Result<Self, InvalidId> {
+    /// Retrieve the [`Client`] associated with a backend [`ClientId`]
+    ///
+    /// Returns an error if the client is no longer alive.
+    pub fn from_id(handle: &DisplayHandle, id: ClientId) -> Result<Self, InvalidId> {
         let data = handle.handle.get_client_data(id.clone())?;
         Ok(Self { id, data })
     }
@@ -29,7 +41,18 @@ impl Client {
     ///
     /// Returns [`None`] if the provided `Data` type parameter is not the correct one.
     pub fn get_data<Data: ClientData + 'static>(&self) -> Option<&Data> {
-        (*self.data).downcast_ref()
+        match (*self.data).downcast_ref::<SandboxedClientData>() {
+            Some(sandboxed) => (*sandboxed.data).downcast_ref(),
+            None => (*self.data).downcast_ref(),
+        }
+    }
+
+    /// The security context of this client
+    ///
+    /// This is `Some` for the clients accepted on a listener added with
+    /// [`DisplayHandle::add_sandboxed_listener()`], which are sandboxed.
+    pub fn security_context(&self) -> Option<&SecurityContext> {
+        (*self.data).downcast_ref::<SandboxedClientData>().map(|sandboxed| &*sandboxed.context)
     }
 
     /// Access the pid/uid/gid of this client
@@ -46,6 +69,81 @@ impl Client {
         handle.handle.get_client_credentials(self.id.clone())
     }
 
+    /// Access the security label of this client
+    ///
+    /// This is the label given to the client process by the security module of the system (its
+    /// SELinux context or AppArmor profile for example), which cannot be changed by the client
+    /// itself. It is `None` if no security module labels unix sockets.
+    pub fn security_label(&self, handle: &DisplayHandle) -> Result<Option<String>, InvalidId> {
+        handle.handle.get_client_security_label(self.id.clone())
+    }
+
+    /// Set the size in bytes up to which the outgoing buffer of this client can grow
+    ///
+    /// The events are buffered when the client does not read them as fast as they are sent, and
+    /// those which do not fit once the buffer reached this size are handled according to the
+    /// [`set_overflow_policy()`](Client::set_overflow_policy) of the client. This lets a compositor
+    /// tolerate the clients which are slow to read, rather than disconnecting them.
+    ///
+    /// The system backend ignores it, `libwayland-server.so` managing the buffers itself.
+    pub fn set_max_buffer_size(
+        &self,
+        handle: &DisplayHandle,
+        size: usize,
+    ) -> Result<(), InvalidId> {
+        handle.handle.set_client_max_buffer_size(self.id.clone(), size)
+    }
+
+    /// Flush the events sent to this client, and invoke `callback` once they are written to its socket
+    ///
+    /// This tells a compositor when the events of a frame, like the `wl_surface.frame` callbacks, really
+    /// left the process. The callback is invoked by this flush or a later one, if the socket of the client
+    /// is full, and is dropped if the client disconnects first. See
+    /// [`Handle::flush_with_callback()`](crate::backend::Handle::flush_with_callback) for details.
+    pub fn flush_with_callback(
+        &self,
+        handle: &mut DisplayHandle,
+        callback: impl FnOnce() + Send + 'static,
+    ) -> Result<(), InvalidId> {
+        handle.handle.flush_with_callback(self.id.clone(), callback)
+    }
+
+    /// Flush the events sent to this client, and get a [`Barrier`] reached once the client read them
+    ///
+    /// This lets a compositor sequence a destructive change after the clients saw the events announcing
+    /// it, for example destroying a global some time after they received its removal. The barrier is
+    /// checked when the clients are flushed, usually after dispatching their requests. See
+    /// [`Handle::barrier()`](crate::backend::Handle::barrier) for details.
+    pub fn barrier(&self, handle: &mut DisplayHandle) -> Result<Barrier, InvalidId> {
+        let reached = Arc::new(AtomicBool::new(false));
+        let reached2 = reached.clone();
+        handle.handle.barrier(self.id.clone(), move || reached2.store(true, Ordering::Release))?;
+        Ok(Barrier { reached })
+    }
+
+    /// Set what to do with the events which do not fit in the outgoing buffer of this client
+    ///
+    /// The client is disconnected by default. The system backend ignores it.
+    pub fn set_overflow_policy(
+        &self,
+        handle: &DisplayHandle,
+        policy: OverflowPolicy,
+    ) -> Result<(), InvalidId> {
+        handle.handle.set_client_overflow_policy(self.id.clone(), policy)
+    }
+
+    /// Access the counters of the messages exchanged with this client
+    ///
+    /// The counters are empty unless [`DisplayHandle::enable_metrics()`] was called.
+    pub fn metrics(&self, handle: &DisplayHandle) -> Result<Metrics, InvalidId> {
+        handle.handle.get_client_metrics(self.id.clone())
+    }
+
+    /// Access the counters of the bytes, messages and file descriptors exchanged with this client
+    pub fn io_stats(&self, handle: &DisplayHandle) -> Result<IoStats, InvalidId> {
+        handle.handle.get_client_io_stats(self.id.clone())
+    }
+
     /// Create a new Wayland object in the protocol state of this client
     ///
     /// The newly created resource should be immediately sent to the client through an associated event with
@@ -103,10 +201,65 @@ impl Client {
         I::from_id(handle, object_id)
     }
 
+    /// Register a callback invoked once this client has been destroyed
+    ///
+    /// The callback is invoked with the state of your compositor during the
+    /// [`Display::dispatch_clients()`](crate::Display::dispatch_clients) following the
+    /// disconnection of the client, after the destructors of all its resources, and is given the
+    /// reason of the disconnection. Returns an id to remove it with
+    /// [`remove_destroy_listener()`](Client::remove_destroy_listener), or an error if the client is
+    /// no longer alive.
+    pub fn add_destroy_listener<State: 'static>(
+        &self,
+        handle: &DisplayHandle,
+        listener: impl FnOnce(&DisplayHandle, &mut State, ClientId, DisconnectReason) + Send + 'static,
+    ) -> Result<DestroyListenerId, InvalidId> {
+        handle.handle.add_client_destroy_listener::<State>(
+            self.id.clone(),
+            move |handle, state, client_id, reason| {
+                listener(&DisplayHandle::from(handle.clone()), state, client_id, reason)
+            },
+        )
+    }
+
+    /// Remove a destroy listener of this client
+    ///
+    /// Returns `false` if the listener was already invoked or removed.
+    pub fn remove_destroy_listener(&self, handle: &DisplayHandle, id: DestroyListenerId) -> bool {
+        handle.handle.remove_client_destroy_listener(id)
+    }
+
+    /// Access all the live resources of interface `I` of this client
+    ///
+    /// The resources are listed when this method is called, those created afterwards are not
+    /// returned by the iterator. Fails if the client is no longer alive.
+    pub fn resources_of<I: Resource + 'static>(
+        &self,
+        handle: &DisplayHandle,
+    ) -> Result<impl Iterator<Item = I>, InvalidId> {
+        let mut ids = Vec::new();
+        handle.handle.with_all_objects_for(self.id.clone(), |id| {
+            if same_interface(id.interface(), I::interface()) {
+                ids.push(id);
+            }
+        })?;
+        let resources = ids.into_iter().filter_map(|id| I::from_id(handle, id).ok());
+        Ok(resources.collect::<Vec<_>>().into_iter())
+    }
+
     /// Kill this client by triggering a protocol error
     pub fn kill(&self, handle: &DisplayHandle, error: ProtocolError) {
         handle.handle.kill_client(self.id.clone(), DisconnectReason::ProtocolError(error))
     }
+
+    /// Disconnect this client with an implementation error
+    ///
+    /// The error is sent on the `wl_display` of the client, for failures that cannot be attributed
+    /// to a specific object.
+    pub fn post_implementation_error(&self, handle: &DisplayHandle, message: impl Into<String>) {
+        let message = std::ffi::CString::new(message.into()).unwrap();
+        handle.handle.post_implementation_error(self.id.clone(), message)
+    }
 }
 
 impl PartialEq for Client {
@@ -114,3 +267,20 @@ impl PartialEq for Client {
         self.id == other.id
     }
 }
+
+/// A token reached once a client read the events sent before it
+///
+/// See [`Client::barrier()`].
+#[derive(Clone, Debug)]
+pub struct Barrier {
+    reached: Arc<AtomicBool>,
+}
+
+impl Barrier {
+    /// Whether the client read all the events sent before this barrier
+    ///
+    /// A barrier of a client that disconnected is never reached.
+    pub fn is_reached(&self) -> bool {
+        self.reached.load(Ordering::Acquire)
+    }
+}
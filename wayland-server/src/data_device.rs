@@ -0,0 +1,966 @@
+//! A `wl_data_device_manager` global handling the clipboard and drag-and-drop
+//!
+//! The [`DataDeviceManager`] owns the `wl_data_device_manager` global. It tracks the data sources and
+//! data devices of the clients, the selection of each [`Seat`](crate::seat::Seat), and the
+//! drag-and-drop operations in progress. It creates the `wl_data_offer` objects and sends them to the
+//! client having the keyboard focus of the seat, or to the client under the pointer during a drag, and
+//! forwards the transfer requests to the owner of the data.
+//!
+//! The data devices are associated to the seats created by the [`seat`](crate::seat) module, the devices
+//! of other `wl_seat` never receive any event. The compositor reports the keyboard focus with
+//! [`DataDeviceManager::set_focus()`], its state implements [`DataDeviceHandler`] to be notified of the
+//! requests of the clients which need a decision, and it drives the drag-and-drop operations it accepted
+//! with [`DataDeviceManager::start_drag()`], [`DataDeviceManager::drag_motion()`] and
+//! [`DataDeviceManager::drop_drag()`].
+//!
+//! ```
+//! use wayland_server::{
+//!     data_device::{
+//!         DataDeviceData, DataDeviceHandler, DataDeviceManager, DataDeviceManagerData,
+//!         DataOfferData, DataSourceData, DragRequest,
+//!     },
+//!     delegate_dispatch, delegate_global_dispatch,
+//!     protocol::{
+//!         wl_data_device::WlDataDevice, wl_data_device_manager::WlDataDeviceManager,
+//!         wl_data_offer::WlDataOffer, wl_data_source::WlDataSource,
+//!     },
+//!     Display,
+//! };
+//!
+//! struct State {
+//!     data_device: DataDeviceManager,
+//! }
+//!
+//! impl DataDeviceHandler for State {
+//!     fn start_drag(&mut self, request: DragRequest) {
+//!         // check the serial against the pointer button pressed on the origin surface first
+//!         self.data_device.start_drag(request);
+//!     }
+//! }
+//!
+//! delegate_global_dispatch!(State: [WlDataDeviceManager: DataDeviceManagerData] => DataDeviceManager);
+//! delegate_dispatch!(State: [WlDataDeviceManager: DataDeviceManagerData] => DataDeviceManager);
+//! delegate_dispatch!(State: [WlDataDevice: DataDeviceData] => DataDeviceManager);
+//! delegate_dispatch!(State: [WlDataSource: DataSourceData] => DataDeviceManager);
+//! delegate_dispatch!(State: [WlDataOffer: DataOfferData] => DataDeviceManager);
+//!
+//! let display = Display::<State>::new().unwrap();
+//! let state = State { data_device: DataDeviceManager::new::<State>(&display.handle()) };
+//! ```
+
+use std::{
+    os::unix::io::{AsFd, OwnedFd},
+    sync::{Arc, Mutex},
+};
+
+use wayland_backend::server::{ClientId, GlobalId};
+
+use crate::{
+    protocol::{
+        wl_data_device, wl_data_device_manager, wl_data_device_manager::DndAction, wl_data_offer,
+        wl_data_source, wl_surface,
+    },
+    seat::{Seat, SeatData},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum, Weak,
+};
+
+/// Handler for the requests of the clients which need a decision of the compositor
+pub trait DataDeviceHandler {
+    /// A client started a drag-and-drop operation
+    ///
+    /// The compositor should check that the serial is the one of a pointer button press or touch down
+    /// on the origin surface, which is still pressed, and that the icon surface has no other role. It
+    /// then starts the operation with [`DataDeviceManager::start_drag()`], or ignores the request.
+    fn start_drag(&mut self, request: DragRequest);
+
+    /// A client set or cleared the selection of a seat
+    ///
+    /// The mime types of the new selection are provided. The default implementation does nothing.
+    fn new_selection(&mut self, seat: &SeatData, mime_types: Option<Vec<String>>) {
+        let _ = (seat, mime_types);
+    }
+
+    /// A client asked for the contents of a selection set by the compositor
+    ///
+    /// The data should be written to the file descriptor, which is then closed. The default
+    /// implementation closes it right away.
+    fn send_selection(&mut self, seat: &SeatData, mime_type: String, fd: OwnedFd) {
+        let _ = (seat, mime_type, fd);
+    }
+}
+
+/// A drag-and-drop operation requested by a client
+#[derive(Debug, Clone)]
+pub struct DragRequest {
+    /// The seat of the data device used by the client
+    pub seat: SeatData,
+    /// The source of the data, `None` for a drag-and-drop inside the client
+    pub source: Option<wl_data_source::WlDataSource>,
+    /// The surface the drag starts from
+    pub origin: wl_surface::WlSurface,
+    /// The surface to display under the pointer during the drag
+    pub icon: Option<wl_surface::WlSurface>,
+    /// The serial of the event which started the drag
+    pub serial: u32,
+}
+
+/// A `wl_data_device_manager` global
+///
+/// See [the module level documentation](self).
+#[derive(Debug)]
+pub struct DataDeviceManager {
+    id: GlobalId,
+    data: DataDeviceManagerData,
+}
+
+/// The global data of a [`DataDeviceManager`], and the user data of its resources
+#[derive(Debug, Clone)]
+pub struct DataDeviceManagerData {
+    inner: Arc<Mutex<ManagerState>>,
+}
+
+/// The user data of the `wl_data_device` resources of a [`DataDeviceManager`]
+#[derive(Debug)]
+pub struct DataDeviceData {
+    manager: DataDeviceManagerData,
+    seat: Option<SeatData>,
+}
+
+/// The user data of the `wl_data_source` resources of a [`DataDeviceManager`]
+#[derive(Debug)]
+pub struct DataSourceData {
+    manager: DataDeviceManagerData,
+    inner: Mutex<SourceState>,
+}
+
+/// The user data of the `wl_data_offer` resources of a [`DataDeviceManager`]
+#[derive(Debug)]
+pub struct DataOfferData {
+    manager: DataDeviceManagerData,
+    seat: SeatData,
+    kind: OfferKind,
+    inner: Mutex<OfferState>,
+}
+
+impl DataSourceData {
+    /// The mime types offered by the source
+    pub fn mime_types(&self) -> Vec<String> {
+        self.inner.lock().unwrap().mime_types.clone()
+    }
+
+    /// The drag-and-drop actions supported by the source
+    pub fn dnd_actions(&self) -> DndAction {
+        self.inner.lock().unwrap().actions
+    }
+}
+
+type CreateOffer =
+    fn(&wl_data_device::WlDataDevice, DataOfferData) -> Option<wl_data_offer::WlDataOffer>;
+
+struct ManagerState {
+    seats: Vec<SeatSelection>,
+    create_offer: CreateOffer,
+}
+
+impl std::fmt::Debug for ManagerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ManagerState").field("seats", &self.seats).finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct SeatSelection {
+    seat: SeatData,
+    devices: Vec<Weak<wl_data_device::WlDataDevice>>,
+    focus: Option<ClientId>,
+    selection: Option<Selection>,
+    // incremented for every new selection, the offers of older selections are inert
+    generation: u64,
+    drag: Option<Drag>,
+}
+
+#[derive(Debug, Clone)]
+enum Selection {
+    Client(wl_data_source::WlDataSource),
+    Compositor(Vec<String>),
+}
+
+#[derive(Debug)]
+struct Drag {
+    source: Option<wl_data_source::WlDataSource>,
+    origin: Option<ClientId>,
+    icon: Option<wl_surface::WlSurface>,
+    focus: Option<(wl_surface::WlSurface, Vec<wl_data_offer::WlDataOffer>)>,
+}
+
+#[derive(Debug)]
+struct SourceState {
+    mime_types: Vec<String>,
+    actions: DndAction,
+    used: bool,
+}
+
+impl Default for SourceState {
+    fn default() -> SourceState {
+        SourceState { mime_types: Vec::new(), actions: DndAction::empty(), used: false }
+    }
+}
+
+#[derive(Debug)]
+enum OfferKind {
+    Selection { generation: u64, source: Option<wl_data_source::WlDataSource> },
+    Drag { source: wl_data_source::WlDataSource },
+}
+
+#[derive(Debug)]
+struct OfferState {
+    accepted: Option<String>,
+    action: DndAction,
+    active: bool,
+    dropped: bool,
+    finished: bool,
+}
+
+impl Default for OfferState {
+    fn default() -> OfferState {
+        OfferState {
+            accepted: None,
+            action: DndAction::empty(),
+            active: false,
+            dropped: false,
+            finished: false,
+        }
+    }
+}
+
+fn client_id(resource: &impl Resource) -> Option<ClientId> {
+    resource.client().map(|client| client.id())
+}
+
+fn create_offer<D>(
+    device: &wl_data_device::WlDataDevice,
+    data: DataOfferData,
+) -> Option<wl_data_offer::WlDataOffer>
+where
+    D: Dispatch<wl_data_offer::WlDataOffer, DataOfferData> + 'static,
+{
+    let handle = DisplayHandle::from(device.handle().upgrade()?);
+    let client = device.client()?;
+    client.create_resource::<_, _, D>(&handle, device.version(), data).ok()
+}
+
+// The preferred action if both sides support it, else the first action they share
+fn choose_action(source: DndAction, destination: DndAction, preferred: DndAction) -> DndAction {
+    let shared = source & destination;
+    if !preferred.is_empty() && shared.contains(preferred) {
+        return preferred;
+    }
+    [DndAction::Copy, DndAction::Move, DndAction::Ask]
+        .iter()
+        .copied()
+        .find(|&action| shared.contains(action))
+        .unwrap_or(DndAction::None)
+}
+
+fn valid_actions(actions: WEnum<DndAction>) -> Option<DndAction> {
+    match actions {
+        WEnum::Value(actions) => Some(actions),
+        WEnum::Unknown(_) => None,
+    }
+}
+
+impl SeatSelection {
+    fn devices_of(&mut self, client: &ClientId) -> Vec<wl_data_device::WlDataDevice> {
+        self.devices.retain(|device| device.upgrade().is_ok());
+        self.devices
+            .iter()
+            .filter_map(|device| device.upgrade().ok())
+            .filter(|device| client_id(device).as_ref() == Some(client))
+            .collect()
+    }
+
+    fn mime_types(&self) -> Vec<String> {
+        match self.selection {
+            Some(Selection::Client(ref source)) => {
+                source.data::<DataSourceData>().map(|data| data.mime_types()).unwrap_or_default()
+            }
+            Some(Selection::Compositor(ref mime_types)) => mime_types.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    // Send the current selection to the devices of the focused client
+    fn send_selection(&mut self, manager: &DataDeviceManagerData, create_offer: CreateOffer) {
+        let focus = match self.focus.clone() {
+            Some(focus) => focus,
+            None => return,
+        };
+        for device in self.devices_of(&focus) {
+            self.send_selection_to(&device, manager, create_offer);
+        }
+    }
+
+    fn send_selection_to(
+        &self,
+        device: &wl_data_device::WlDataDevice,
+        manager: &DataDeviceManagerData,
+        create_offer: CreateOffer,
+    ) {
+        let source = match self.selection {
+            Some(Selection::Client(ref source)) => Some(source.clone()),
+            Some(Selection::Compositor(_)) => None,
+            None => {
+                device.selection(None);
+                return;
+            }
+        };
+        let data = DataOfferData {
+            manager: manager.clone(),
+            seat: self.seat.clone(),
+            kind: OfferKind::Selection { generation: self.generation, source },
+            inner: Mutex::new(OfferState::default()),
+        };
+        if let Some(offer) = create_offer(device, data) {
+            device.data_offer(&offer);
+            for mime_type in self.mime_types() {
+                offer.offer(mime_type);
+            }
+            device.selection(Some(&offer));
+        }
+    }
+
+    fn set_selection(
+        &mut self,
+        selection: Option<Selection>,
+        manager: &DataDeviceManagerData,
+        create_offer: CreateOffer,
+    ) {
+        if let Some(Selection::Client(old)) = self.selection.take() {
+            let replaced = match selection {
+                Some(Selection::Client(ref new)) => new != &old,
+                _ => true,
+            };
+            if replaced {
+                old.cancelled();
+            }
+        }
+        self.selection = selection;
+        self.generation += 1;
+        self.send_selection(manager, create_offer);
+    }
+
+    // Send leave to the devices under the drag, the offers of the drag become inert
+    fn drag_leave(&mut self) {
+        let focus = self.drag.as_mut().and_then(|drag| drag.focus.take());
+        if let Some((surface, offers)) = focus {
+            for offer in offers {
+                if let Some(data) = offer.data::<DataOfferData>() {
+                    data.inner.lock().unwrap().active = false;
+                }
+            }
+            if let Some(client) = client_id(&surface) {
+                for device in self.devices_of(&client) {
+                    device.leave();
+                }
+            }
+        }
+    }
+}
+
+impl ManagerState {
+    fn seat(&mut self, seat: &SeatData) -> &mut SeatSelection {
+        let index = match self.seats.iter().position(|selection| &selection.seat == seat) {
+            Some(index) => index,
+            None => {
+                self.seats.push(SeatSelection {
+                    seat: seat.clone(),
+                    devices: Vec::new(),
+                    focus: None,
+                    selection: None,
+                    generation: 0,
+                    drag: None,
+                });
+                self.seats.len() - 1
+            }
+        };
+        &mut self.seats[index]
+    }
+}
+
+impl DataDeviceManager {
+    /// Create a `wl_data_device_manager` global
+    pub fn new<D>(handle: &DisplayHandle) -> DataDeviceManager
+    where
+        D: GlobalDispatch<wl_data_device_manager::WlDataDeviceManager, DataDeviceManagerData>
+            + Dispatch<wl_data_device_manager::WlDataDeviceManager, DataDeviceManagerData>
+            + Dispatch<wl_data_device::WlDataDevice, DataDeviceData>
+            + Dispatch<wl_data_source::WlDataSource, DataSourceData>
+            + Dispatch<wl_data_offer::WlDataOffer, DataOfferData>
+            + DataDeviceHandler
+            + 'static,
+    {
+        let data = DataDeviceManagerData {
+            inner: Arc::new(Mutex::new(ManagerState {
+                seats: Vec::new(),
+                create_offer: create_offer::<D>,
+            })),
+        };
+        let id = handle
+            .create_global::<D, wl_data_device_manager::WlDataDeviceManager, _>(3, data.clone());
+        DataDeviceManager { id, data }
+    }
+
+    /// The id of the global
+    pub fn global(&self) -> GlobalId {
+        self.id.clone()
+    }
+
+    /// Set the client having the keyboard focus of a seat
+    ///
+    /// This client receives the selection of the seat, now and whenever it changes, and is the only one
+    /// allowed to change it.
+    pub fn set_focus(&self, seat: &Seat, client: Option<&Client>) {
+        let mut state = self.data.inner.lock().unwrap();
+        let create_offer = state.create_offer;
+        let selection = state.seat(seat.data());
+        let focus = client.map(Client::id);
+        if selection.focus == focus {
+            return;
+        }
+        selection.focus = focus;
+        selection.send_selection(&self.data, create_offer);
+    }
+
+    /// The mime types of the selection of a seat
+    pub fn selection(&self, seat: &Seat) -> Option<Vec<String>> {
+        let mut state = self.data.inner.lock().unwrap();
+        let selection = state.seat(seat.data());
+        selection.selection.as_ref().map(|_| selection.mime_types())
+    }
+
+    /// Set the selection of a seat to data provided by the compositor, or clear it
+    ///
+    /// The source of the previous selection is cancelled. The contents are requested with
+    /// [`DataDeviceHandler::send_selection()`].
+    pub fn set_selection(&self, seat: &Seat, mime_types: Option<Vec<String>>) {
+        let mut state = self.data.inner.lock().unwrap();
+        let create_offer = state.create_offer;
+        state.seat(seat.data()).set_selection(
+            mime_types.map(Selection::Compositor),
+            &self.data,
+            create_offer,
+        );
+    }
+
+    /// Start a drag-and-drop operation requested by a client
+    ///
+    /// A drag already in progress on the seat is cancelled. Does nothing if the source has already been
+    /// used, or was destroyed.
+    pub fn start_drag(&self, request: DragRequest) {
+        if let Some(ref source) = request.source {
+            let data = match source.data::<DataSourceData>() {
+                Some(data) if source.is_alive() => data,
+                _ => return,
+            };
+            let mut source_state = data.inner.lock().unwrap();
+            if source_state.used {
+                return;
+            }
+            source_state.used = true;
+        }
+        let mut state = self.data.inner.lock().unwrap();
+        let selection = state.seat(&request.seat);
+        selection.drag_leave();
+        if let Some(source) = selection.drag.take().and_then(|drag| drag.source) {
+            source.cancelled();
+        }
+        selection.drag = Some(Drag {
+            source: request.source,
+            origin: client_id(&request.origin),
+            icon: request.icon,
+            focus: None,
+        });
+    }
+
+    /// The icon of the drag in progress on a seat
+    pub fn drag_icon(&self, seat: &Seat) -> Option<wl_surface::WlSurface> {
+        let mut state = self.data.inner.lock().unwrap();
+        state.seat(seat.data()).drag.as_ref().and_then(|drag| drag.icon.clone())
+    }
+
+    /// Check whether a drag-and-drop operation is in progress on a seat
+    pub fn is_dragging(&self, seat: &Seat) -> bool {
+        let mut state = self.data.inner.lock().unwrap();
+        state.seat(seat.data()).drag.is_some()
+    }
+
+    /// Move the drag in progress on a seat above a surface, or outside of any surface
+    ///
+    /// The coordinates are relative to the surface. When the surface changes, the previous one receives
+    /// `leave` and the new one `enter`, with a new offer of the data; otherwise the surface receives
+    /// `motion`. A drag without a source is only visible to the surfaces of the client which started it.
+    pub fn drag_motion(
+        &self,
+        seat: &Seat,
+        focus: Option<(&wl_surface::WlSurface, f64, f64)>,
+        time: u32,
+    ) {
+        let mut state = self.data.inner.lock().unwrap();
+        let create_offer = state.create_offer;
+        let selection = state.seat(seat.data());
+        let drag = match selection.drag {
+            Some(ref drag) => drag,
+            None => return,
+        };
+        // a drag without source is restricted to the origin client
+        let focus = focus.filter(|(surface, ..)| {
+            drag.source.is_some() || (drag.origin.is_some() && client_id(*surface) == drag.origin)
+        });
+        let current = drag.focus.as_ref().map(|(surface, _)| surface);
+        if current.is_some() && current == focus.map(|(surface, ..)| surface) {
+            let (surface, _) = drag.focus.as_ref().unwrap();
+            if let (Some(client), Some((_, x, y))) = (client_id(surface), focus) {
+                for device in selection.devices_of(&client) {
+                    device.motion(time, x, y);
+                }
+            }
+            return;
+        }
+
+        selection.drag_leave();
+        let (surface, x, y) = match focus {
+            Some(focus) => focus,
+            None => return,
+        };
+        let client = match client_id(surface) {
+            Some(client) => client,
+            None => return,
+        };
+        let source = selection.drag.as_ref().and_then(|drag| drag.source.clone());
+        let (mime_types, actions) = match source.as_ref().and_then(|s| s.data::<DataSourceData>()) {
+            Some(data) => (data.mime_types(), data.dnd_actions()),
+            None => (Vec::new(), DndAction::empty()),
+        };
+        let serial = selection.seat.next_serial();
+        let mut offers = Vec::new();
+        for device in selection.devices_of(&client) {
+            let offer = source.as_ref().and_then(|source| {
+                let data = DataOfferData {
+                    manager: self.data.clone(),
+                    seat: selection.seat.clone(),
+                    kind: OfferKind::Drag { source: source.clone() },
+                    inner: Mutex::new(OfferState { active: true, ..Default::default() }),
+                };
+                create_offer(&device, data)
+            });
+            if let Some(ref offer) = offer {
+                device.data_offer(offer);
+                for mime_type in &mime_types {
+                    offer.offer(mime_type.clone());
+                }
+                if offer.version() >= 3 {
+                    offer.source_actions(actions);
+                }
+                offers.push(offer.clone());
+            }
+            device.enter(serial, surface, x, y, offer.as_ref());
+        }
+        if let Some(ref mut drag) = selection.drag {
+            drag.focus = Some((surface.clone(), offers));
+        }
+    }
+
+    /// Drop the data of the drag in progress on a seat on the surface under it
+    ///
+    /// If the target accepted a mime type and, for version 3 or later, an action was negotiated, the
+    /// target receives `drop` and then does the transfer with the source. Otherwise the drag is
+    /// cancelled. The drag operation of the seat ends in both cases.
+    pub fn drop_drag(&self, seat: &Seat) {
+        let mut state = self.data.inner.lock().unwrap();
+        let selection = state.seat(seat.data());
+        let (source, focus) = match selection.drag {
+            Some(ref drag) => (drag.source.clone(), drag.focus.clone()),
+            None => return,
+        };
+        let accepted = match (&focus, &source) {
+            // a drag inside a client is always dropped on its surfaces
+            (Some(_), None) => true,
+            (Some((_, offers)), Some(source)) => offers.iter().any(|offer| {
+                let offer_state = offer.data::<DataOfferData>().unwrap().inner.lock().unwrap();
+                offer_state.accepted.is_some()
+                    && (offer.version() < 3
+                        || source.version() < 3
+                        || !offer_state.action.is_empty())
+            }),
+            (None, _) => false,
+        };
+        if let (true, Some((surface, offers))) = (accepted, focus) {
+            if let Some(client) = client_id(&surface) {
+                for device in selection.devices_of(&client) {
+                    device.drop();
+                }
+            }
+            for offer in offers {
+                offer.data::<DataOfferData>().unwrap().inner.lock().unwrap().dropped = true;
+            }
+            if let Some(ref source) = source {
+                if source.version() >= 3 {
+                    source.dnd_drop_performed();
+                }
+            }
+        }
+        selection.drag_leave();
+        selection.drag = None;
+        if let (false, Some(source)) = (accepted, source) {
+            source.cancelled();
+        }
+    }
+
+    /// Cancel the drag in progress on a seat
+    pub fn cancel_drag(&self, seat: &Seat) {
+        let mut state = self.data.inner.lock().unwrap();
+        let selection = state.seat(seat.data());
+        selection.drag_leave();
+        if let Some(source) = selection.drag.take().and_then(|drag| drag.source) {
+            source.cancelled();
+        }
+    }
+}
+
+impl<D> GlobalDispatch<wl_data_device_manager::WlDataDeviceManager, DataDeviceManagerData, D>
+    for DataDeviceManager
+where
+    D: GlobalDispatch<wl_data_device_manager::WlDataDeviceManager, DataDeviceManagerData>
+        + Dispatch<wl_data_device_manager::WlDataDeviceManager, DataDeviceManagerData>
+        + 'static,
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_data_device_manager::WlDataDeviceManager>,
+        global_data: &DataDeviceManagerData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, global_data.clone());
+    }
+}
+
+impl<D> Dispatch<wl_data_device_manager::WlDataDeviceManager, DataDeviceManagerData, D>
+    for DataDeviceManager
+where
+    D: Dispatch<wl_data_device_manager::WlDataDeviceManager, DataDeviceManagerData>
+        + Dispatch<wl_data_device::WlDataDevice, DataDeviceData>
+        + Dispatch<wl_data_source::WlDataSource, DataSourceData>
+        + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &wl_data_device_manager::WlDataDeviceManager,
+        request: wl_data_device_manager::Request,
+        data: &DataDeviceManagerData,
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wl_data_device_manager::Request::CreateDataSource { id } => {
+                let data =
+                    DataSourceData { manager: data.clone(), inner: Mutex::new(Default::default()) };
+                data_init.init(id, data);
+            }
+            wl_data_device_manager::Request::GetDataDevice { id, seat } => {
+                let seat = seat.data::<SeatData>().cloned();
+                let device = data_init
+                    .init(id, DataDeviceData { manager: data.clone(), seat: seat.clone() });
+                if let Some(seat) = seat {
+                    let mut state = data.inner.lock().unwrap();
+                    let create_offer = state.create_offer;
+                    let selection = state.seat(&seat);
+                    selection.devices.push(device.downgrade());
+                    if selection.focus.is_some() && selection.focus == client_id(&device) {
+                        selection.send_selection_to(&device, data, create_offer);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<D> Dispatch<wl_data_device::WlDataDevice, DataDeviceData, D> for DataDeviceManager
+where
+    D: Dispatch<wl_data_device::WlDataDevice, DataDeviceData> + DataDeviceHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        client: &Client,
+        _: &wl_data_device::WlDataDevice,
+        request: wl_data_device::Request,
+        data: &DataDeviceData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        let seat = match data.seat {
+            Some(ref seat) => seat,
+            None => return,
+        };
+        match request {
+            wl_data_device::Request::StartDrag { source, origin, icon, serial } => {
+                if client_id(&origin) != Some(client.id()) {
+                    return;
+                }
+                state.start_drag(DragRequest { seat: seat.clone(), source, origin, icon, serial });
+            }
+            wl_data_device::Request::SetSelection { source, .. } => {
+                let mime_types = match source {
+                    Some(ref source) => {
+                        let source_data = source.data::<DataSourceData>().unwrap();
+                        let mut source_state = source_data.inner.lock().unwrap();
+                        if source_state.used {
+                            source.post_error(
+                                wl_data_source::Error::InvalidSource,
+                                "the source has already been used",
+                            );
+                            return;
+                        }
+                        source_state.used = true;
+                        Some(source_state.mime_types.clone())
+                    }
+                    None => None,
+                };
+                {
+                    let mut manager = data.manager.inner.lock().unwrap();
+                    let create_offer = manager.create_offer;
+                    let selection = manager.seat(seat);
+                    // only the client having the keyboard focus can change the selection
+                    if selection.focus != Some(client.id()) {
+                        if let Some(source) = source {
+                            source.cancelled();
+                        }
+                        return;
+                    }
+                    selection.set_selection(
+                        source.map(Selection::Client),
+                        &data.manager,
+                        create_offer,
+                    );
+                }
+                state.new_selection(seat, mime_types);
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        _: &mut D,
+        _: ClientId,
+        device: &wl_data_device::WlDataDevice,
+        data: &DataDeviceData,
+    ) {
+        if let Some(ref seat) = data.seat {
+            let id = device.id();
+            let mut manager = data.manager.inner.lock().unwrap();
+            manager.seat(seat).devices.retain(|device| device.id() != id);
+        }
+    }
+}
+
+impl<D> Dispatch<wl_data_source::WlDataSource, DataSourceData, D> for DataDeviceManager
+where
+    D: Dispatch<wl_data_source::WlDataSource, DataSourceData> + DataDeviceHandler + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        source: &wl_data_source::WlDataSource,
+        request: wl_data_source::Request,
+        data: &DataSourceData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        let mut source_state = data.inner.lock().unwrap();
+        match request {
+            wl_data_source::Request::Offer { mime_type } => source_state.mime_types.push(mime_type),
+            wl_data_source::Request::SetActions { dnd_actions } => {
+                if source_state.used {
+                    source.post_error(
+                        wl_data_source::Error::InvalidSource,
+                        "set_actions on a source already used",
+                    );
+                    return;
+                }
+                match valid_actions(dnd_actions) {
+                    Some(actions) => source_state.actions = actions,
+                    None => source.post_error(
+                        wl_data_source::Error::InvalidActionMask,
+                        "invalid drag-and-drop actions",
+                    ),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _: ClientId,
+        source: &wl_data_source::WlDataSource,
+        data: &DataSourceData,
+    ) {
+        let mut cleared = Vec::new();
+        {
+            let mut manager = data.manager.inner.lock().unwrap();
+            let create_offer = manager.create_offer;
+            for selection in &mut manager.seats {
+                if matches!(selection.selection, Some(Selection::Client(ref s)) if s == source) {
+                    selection.selection = None;
+                    selection.generation += 1;
+                    selection.send_selection(&data.manager, create_offer);
+                    cleared.push(selection.seat.clone());
+                }
+                let dragged = selection
+                    .drag
+                    .as_ref()
+                    .map_or(false, |drag| drag.source.as_ref() == Some(source));
+                if dragged {
+                    selection.drag_leave();
+                    selection.drag = None;
+                }
+            }
+        }
+        for seat in cleared {
+            state.new_selection(&seat, None);
+        }
+    }
+}
+
+impl<D> Dispatch<wl_data_offer::WlDataOffer, DataOfferData, D> for DataDeviceManager
+where
+    D: Dispatch<wl_data_offer::WlDataOffer, DataOfferData> + DataDeviceHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        offer: &wl_data_offer::WlDataOffer,
+        request: wl_data_offer::Request,
+        data: &DataOfferData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        match (request, &data.kind) {
+            (
+                wl_data_offer::Request::Receive { mime_type, fd },
+                OfferKind::Selection { generation, source },
+            ) => {
+                let current = {
+                    let mut manager = data.manager.inner.lock().unwrap();
+                    manager.seat(&data.seat).generation == *generation
+                };
+                // the fd is closed when dropped, the client sees the end of an empty transfer
+                if !current {
+                    return;
+                }
+                match source {
+                    Some(source) => source.send(mime_type, fd.as_fd()),
+                    None => state.send_selection(&data.seat, mime_type, fd),
+                }
+            }
+            (wl_data_offer::Request::Receive { mime_type, fd }, OfferKind::Drag { source }) => {
+                let offer_state = data.inner.lock().unwrap();
+                if offer_state.active || (offer_state.dropped && !offer_state.finished) {
+                    source.send(mime_type, fd.as_fd());
+                }
+            }
+            (wl_data_offer::Request::Accept { mime_type, .. }, OfferKind::Drag { source }) => {
+                let mut offer_state = data.inner.lock().unwrap();
+                if offer_state.active && offer_state.accepted != mime_type {
+                    offer_state.accepted = mime_type.clone();
+                    source.target(mime_type);
+                }
+            }
+            (wl_data_offer::Request::SetActions { dnd_actions, preferred_action }, kind) => {
+                let source = match kind {
+                    OfferKind::Drag { source } => source,
+                    OfferKind::Selection { .. } => {
+                        offer.post_error(
+                            wl_data_offer::Error::InvalidOffer,
+                            "set_actions on a selection offer",
+                        );
+                        return;
+                    }
+                };
+                let (actions, preferred) =
+                    match (valid_actions(dnd_actions), valid_actions(preferred_action)) {
+                        (Some(actions), Some(preferred)) => (actions, preferred),
+                        _ => {
+                            offer.post_error(
+                                wl_data_offer::Error::InvalidActionMask,
+                                "invalid drag-and-drop actions",
+                            );
+                            return;
+                        }
+                    };
+                if preferred.bits().count_ones() > 1 || !actions.contains(preferred) {
+                    offer.post_error(
+                        wl_data_offer::Error::InvalidAction,
+                        "the preferred action is not a single supported action",
+                    );
+                    return;
+                }
+                let mut offer_state = data.inner.lock().unwrap();
+                if !offer_state.active || offer_state.dropped {
+                    return;
+                }
+                let source_actions =
+                    source.data::<DataSourceData>().map_or(DndAction::empty(), |d| d.dnd_actions());
+                let action = choose_action(source_actions, actions, preferred);
+                if action != offer_state.action {
+                    offer_state.action = action;
+                    offer.action(action);
+                    if source.version() >= 3 {
+                        source.action(action);
+                    }
+                }
+            }
+            (wl_data_offer::Request::Finish, kind) => {
+                let source = match kind {
+                    OfferKind::Drag { source } => source,
+                    OfferKind::Selection { .. } => {
+                        offer.post_error(
+                            wl_data_offer::Error::InvalidOffer,
+                            "finish on a selection offer",
+                        );
+                        return;
+                    }
+                };
+                let mut offer_state = data.inner.lock().unwrap();
+                if !offer_state.dropped
+                    || offer_state.finished
+                    || offer_state.accepted.is_none()
+                    || offer_state.action.is_empty()
+                {
+                    offer.post_error(
+                        wl_data_offer::Error::InvalidFinish,
+                        "finish before a successful drop",
+                    );
+                    return;
+                }
+                offer_state.finished = true;
+                if source.version() >= 3 {
+                    source.dnd_finished();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(_: &mut D, _: ClientId, offer: &wl_data_offer::WlDataOffer, data: &DataOfferData) {
+        // a target going away in the middle of the transfer cancels it
+        if let OfferKind::Drag { ref source } = data.kind {
+            let offer_state = data.inner.lock().unwrap();
+            if offer_state.dropped && !offer_state.finished && offer.version() >= 3 {
+                source.cancelled();
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::any::Any;
 use std::os::unix::io::OwnedFd;
 use std::sync::Arc;
 
@@ -110,6 +111,10 @@ pub trait Dispatch<I: Resource, UserData, State = Self>: Sized {
     /// You are given the [`ObjectId`] and [`ClientId`] associated with the destroyed object for cleanup
     /// convenience.
     ///
+    /// When a client is disconnected, its remaining resources are destroyed in the reverse order of their
+    /// creation: a `wl_subsurface` is destroyed before its `wl_surface`. See
+    /// [`ObjectData::destroyed()`] for the limits of the system backend.
+    ///
     /// By default this method does nothing.
     fn destroyed(
         _state: &mut State,
@@ -120,19 +125,43 @@ pub trait Dispatch<I: Resource, UserData, State = Self>: Sized {
     }
 }
 
+/// What a request interceptor does with a request, see [`DataInit::init_with_interceptor()`]
+#[derive(Debug)]
+pub enum Intercept<R> {
+    /// Give the request, modified or not, to the [`Dispatch`] implementation
+    Pass(R),
+    /// Consume the request, the [`Dispatch`] implementation does not see it
+    Consume,
+}
+
+type Interceptor<I, D> = Box<
+    dyn Fn(
+            &mut D,
+            &Client,
+            &I,
+            <I as Resource>::Request,
+            &DisplayHandle,
+            &mut DataInit<'_, D>,
+        ) -> Intercept<<I as Resource>::Request>
+        + Send
+        + Sync,
+>;
+
 /// The [`ObjectData`] implementation that is internally used by this crate
 #[derive(Debug)]
 pub struct ResourceData<I, U> {
     marker: std::marker::PhantomData<fn(I)>,
     /// The user-data associated with this object
     pub udata: U,
+    // an `Interceptor<I, D>`, whose state type is only known when dispatching
+    interceptor: Option<Arc<dyn Any + Send + Sync>>,
 }
 
 /// A newly created object that needs to be initialized. See [`DataInit`].
 #[derive(Debug)]
 #[must_use = "The protocol object must be initialized using DataInit"]
 pub struct New<I> {
-    id: I,
+    pub(crate) id: I,
 }
 
 impl<I> New<I> {
@@ -143,6 +172,15 @@ impl<I> New<I> {
     }
 }
 
+impl<I: Resource> New<I> {
+    /// The version of the new object
+    ///
+    /// For an object created by binding a global, this is the version requested by the client.
+    pub fn version(&self) -> u32 {
+        self.id.version()
+    }
+}
+
 /// Helper to initialize client-created objects
 ///
 /// This helper is provided to you in your [`Dispatch`] and [`GlobalDispatch`](super::GlobalDispatch) to
@@ -173,6 +211,45 @@ impl<'a, D> DataInit<'a, D> {
         obj
     }
 
+    /// Initialize an object by assigning it its user-data and an interceptor of its requests
+    ///
+    /// The interceptor is given each request of the object before the [`Dispatch`] implementation. It
+    /// returns [`Intercept::Pass`] with the request, possibly modified, to dispatch it, or
+    /// [`Intercept::Consume`] to drop it, for example to hold back a privileged request until the user
+    /// allows it and handle it then.
+    ///
+    /// The objects created by a consumed request must be initialized by the interceptor, with the
+    /// [`DataInit`] it is given. A consumed destructor request still destroys its object.
+    pub fn init_with_interceptor<I: Resource + 'static, U: Send + Sync + 'static>(
+        &mut self,
+        resource: New<I>,
+        data: U,
+        interceptor: impl Fn(
+                &mut D,
+                &Client,
+                &I,
+                I::Request,
+                &DisplayHandle,
+                &mut DataInit<'_, D>,
+            ) -> Intercept<I::Request>
+            + Send
+            + Sync
+            + 'static,
+    ) -> I
+    where
+        D: Dispatch<I, U> + 'static,
+    {
+        let interceptor: Interceptor<I, D> = Box::new(interceptor);
+        let arc = Arc::new(ResourceData::<I, _> {
+            interceptor: Some(Arc::new(interceptor)),
+            ..ResourceData::new(data)
+        });
+        *self.store = Some(arc.clone() as Arc<_>);
+        let mut obj = resource.id;
+        obj.__set_object_data(arc);
+        obj
+    }
+
     /// Set a custom [`ObjectData`] for this object
     ///
     /// This object data is not managed by `wayland-server`, as a result you will not
@@ -213,7 +290,16 @@ impl<'a, D> DataInit<'a, D> {
 
 impl<I, U> ResourceData<I, U> {
     pub(crate) fn new(udata: U) -> Self {
-        ResourceData { marker: std::marker::PhantomData, udata }
+        ResourceData { marker: std::marker::PhantomData, udata, interceptor: None }
+    }
+
+    /// The data of the same resource with another user data, keeping its interceptor
+    pub(crate) fn replace_udata<V>(&self, udata: V) -> ResourceData<I, V> {
+        ResourceData {
+            marker: std::marker::PhantomData,
+            udata,
+            interceptor: self.interceptor.clone(),
+        }
     }
 }
 
@@ -244,7 +330,7 @@ impl<I: Resource + 'static, U: Send + Sync + 'static, D: Dispatch<I, U> + 'stati
                 crate::log_warn!("Dispatching error encountered: {:?}, killing client.", e);
                 handle.kill_client(
                     client.id(),
-                    DisconnectReason::ProtocolError(ProtocolError {
+                    DisconnectReason::InvalidMessage(ProtocolError {
                         code: 1,
                         object_id: 0,
                         object_interface: "wl_display".into(),
@@ -260,7 +346,28 @@ impl<I: Resource + 'static, U: Send + Sync + 'static, D: Dispatch<I, U> + 'stati
         let udata = resource.data::<U>().expect("Wrong user_data value for object");
 
         let mut new_data = None;
+        // The error is None since the creating object posts an error.
+        let mut data_init = DataInit { store: &mut new_data, error: &mut None };
 
+        let interceptor =
+            self.interceptor.as_ref().and_then(|i| i.downcast_ref::<Interceptor<I, D>>());
+        let request = match interceptor {
+            Some(interceptor) => {
+                match interceptor(data, &client, &resource, request, &dhandle, &mut data_init) {
+                    Intercept::Pass(request) => request,
+                    Intercept::Consume => return new_data,
+                }
+            }
+            None => request,
+        };
+
+        crate::trace_span!(
+            "dispatch",
+            client = ?client.id(),
+            interface = I::interface().name,
+            id = sender_id,
+            opcode
+        );
         <D as Dispatch<I, U>>::request(
             data,
             &client,
@@ -268,8 +375,7 @@ impl<I: Resource + 'static, U: Send + Sync + 'static, D: Dispatch<I, U> + 'stati
             request,
             udata,
             &dhandle,
-            // The error is None since the creating object posts an error.
-            &mut DataInit { store: &mut new_data, error: &mut None },
+            &mut data_init,
         );
 
         new_data
@@ -1,17 +1,24 @@
 use std::{
-    os::unix::io::{AsFd, AsRawFd, BorrowedFd},
+    ops::RangeInclusive,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd},
     os::unix::net::UnixStream,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use wayland_backend::{
-    protocol::ObjectInfo,
-    server::{Backend, ClientData, GlobalId, Handle, InitError, InvalidId, ObjectId},
+    protocol::{IoStats, MessageLimits, ObjectInfo},
+    server::{
+        Backend, ClientData, GlobalId, Handle, InitError, InvalidId, KillPolicy,
+        MalformedRequestPolicy, ObjectId, PauseError, ProtocolLogger, RateLimit, WakeupHandle,
+    },
 };
 
 use crate::{
-    global::{GlobalData, GlobalDispatch},
-    Client, Resource,
+    dispatch::ResourceData,
+    global::{Global, GlobalData, GlobalDispatch},
+    security_context::SandboxedClientData,
+    Client, Dispatch, Resource, SecurityContext,
 };
 
 /// The Wayland display
@@ -30,6 +37,10 @@ use crate::{
 /// To ensure all clients receive the events your compositor sends them, you also need to regularly invoke
 /// the [`flush_clients()`](Display::flush_clients) method, which will write the outgoing buffers into the
 /// sockets.
+///
+/// Other threads can interrupt the wait of the main loop on this file descriptor through a
+/// [`wakeup_handle()`](Display::wakeup_handle), for example to have it process data they made
+/// available to the compositor state.
 #[derive(Debug)]
 pub struct Display<State: 'static> {
     backend: Backend<State>,
@@ -59,11 +70,86 @@ impl<State: 'static> Display<State> {
         self.backend.dispatch_all_clients(state)
     }
 
+    /// Dispatch the requests received from a single client, leaving the other clients pending
+    ///
+    /// This allows running a synchronous sub-loop for one client, for example while it completes a
+    /// handshake, without processing the requests of everyone else.
+    ///
+    /// **Note:** This is only supported by the rust backend. With the system backend, this does the
+    /// same as [`dispatch_clients()`](Display::dispatch_clients).
+    pub fn dispatch_client(
+        &mut self,
+        client: &Client,
+        state: &mut State,
+    ) -> std::io::Result<usize> {
+        self.backend.dispatch_single_client(state, client.id.clone())
+    }
+
     /// Flush outgoing buffers into their respective sockets.
     pub fn flush_clients(&mut self) -> std::io::Result<()> {
         self.backend.flush(None)
     }
 
+    /// Invoke the destructors pending since the last dispatch
+    ///
+    /// The [`Dispatch::destroyed()`](crate::Dispatch::destroyed) methods of the resources destroyed outside
+    /// of [`dispatch_clients()`](Display::dispatch_clients), for example those of a client killed with
+    /// [`Client::kill()`], are normally invoked during the next dispatch. This invokes them right away,
+    /// followed by the destroy listeners of the destroyed clients.
+    pub fn flush_destructors(&mut self, state: &mut State) {
+        self.backend.flush_destructors(state)
+    }
+
+    /// Disconnect all clients before the compositor exits
+    ///
+    /// This stops accepting clients on the sockets added with [`add_listener()`](Display::add_listener),
+    /// flushes the events sent so far, and disconnects all the clients. If an `error` message is provided,
+    /// it is sent to each of them as an implementation error first. The
+    /// [`Dispatch::destroyed()`](crate::Dispatch::destroyed) methods of all their resources are then invoked
+    /// with `state`, client by client, followed by their
+    /// [destroy listeners](crate::Client::add_destroy_listener).
+    ///
+    /// Without this, dropping the [`Display`] does not invoke the destructors of the remaining resources.
+    /// The [`ListeningSocket`](crate::ListeningSocket)s owned by the compositor are not affected, and must
+    /// be dropped separately.
+    pub fn shutdown(&mut self, state: &mut State, error: Option<String>) {
+        let error = error.map(|error| std::ffi::CString::new(error).unwrap());
+        self.backend.shutdown(state, error)
+    }
+
+    /// Retrieve a handle to wake up the main loop from other threads
+    ///
+    /// Calling [`wake()`](WakeupHandle::wake) on it makes the file descriptor of this display readable
+    /// until the next [`dispatch_clients()`](Display::dispatch_clients), so that a loop waiting on it
+    /// returns immediately.
+    pub fn wakeup_handle(&self) -> std::io::Result<WakeupHandle> {
+        self.backend.wakeup_handle()
+    }
+
+    /// Accept new clients on a listening socket
+    ///
+    /// Where [`ListeningSocket`](crate::ListeningSocket) binds a socket in `XDG_RUNTIME_DIR`, this
+    /// takes any socket accepting stream connections, like an abstract Unix socket or a vsock socket
+    /// for the guests of a virtual machine. The socket is monitored by the file descriptor of this
+    /// display, and its pending connections are accepted during
+    /// [`dispatch_clients()`](Display::dispatch_clients): each of them is given to `accept`, which
+    /// returns the [`ClientData`] of the new client, or `None` to refuse it. This happens before any
+    /// protocol state is created for the client, and `accept` can read the credentials of the peer with
+    /// [`Credentials::from_peer()`](crate::backend::Credentials::from_peer).
+    ///
+    /// Returns an error of kind [`InvalidInput`](std::io::ErrorKind::InvalidInput) if the file
+    /// descriptor is not a listening socket.
+    pub fn add_listener<F>(
+        &mut self,
+        listener: impl Into<OwnedFd>,
+        accept: F,
+    ) -> std::io::Result<()>
+    where
+        F: FnMut(&mut State, &UnixStream) -> Option<Arc<dyn ClientData>> + Send + 'static,
+    {
+        self.backend.add_listener(listener.into(), accept)
+    }
+
     /// Access the underlying [`Backend`] of this [`Display`]
     pub fn backend(&mut self) -> &mut Backend<State> {
         &mut self.backend
@@ -134,10 +220,68 @@ impl DisplayHandle {
         self.handle.create_global::<State>(
             I::interface(),
             version,
-            Arc::new(GlobalData { data, _types: std::marker::PhantomData }),
+            Arc::new(GlobalData {
+                data,
+                bound: None,
+                min_version: 0,
+                _types: std::marker::PhantomData,
+            }),
         )
     }
 
+    /// Create a new protocol global supporting a range of versions
+    ///
+    /// This works like [`create_global()`](DisplayHandle::create_global), advertising the global with the
+    /// highest version of `versions`. Clients binding it with a version lower than the lowest one are sent
+    /// a `wl_display.error` instead, without invoking [`GlobalDispatch::bind()`], which can thus rely on
+    /// [`New::version()`](crate::New::version) being in the range.
+    pub fn create_global_with_versions<State, I: Resource + 'static, U: Send + Sync + 'static>(
+        &self,
+        versions: RangeInclusive<u32>,
+        data: U,
+    ) -> GlobalId
+    where
+        State: GlobalDispatch<I, U> + 'static,
+    {
+        self.handle.create_global::<State>(
+            I::interface(),
+            *versions.end(),
+            Arc::new(GlobalData {
+                data,
+                bound: None,
+                min_version: *versions.start(),
+                _types: std::marker::PhantomData,
+            }),
+        )
+    }
+
+    /// Create a new protocol global, keeping track of the resources bound from it
+    ///
+    /// This works like [`create_global()`](DisplayHandle::create_global), but the returned
+    /// [`Global`] can iterate over the resources created by clients binding the global, to
+    /// broadcast an event to them.
+    pub fn create_tracked_global<State, I: Resource + 'static, U: Send + Sync + 'static>(
+        &self,
+        version: u32,
+        data: U,
+    ) -> Global<I>
+    where
+        State: GlobalDispatch<I, U> + 'static,
+    {
+        let bound = Arc::new(Mutex::new(Vec::new()));
+        let id = self.handle.create_global::<State>(
+            I::interface(),
+            version,
+            Arc::new(GlobalData {
+                data,
+                bound: Some(bound.clone()),
+                min_version: 0,
+                _types: std::marker::PhantomData,
+            }),
+        );
+        Global::new(id, self.handle.downgrade(), bound)
+    }
+
     /// Disable this global
     ///
     /// Clients will be notified of the global removal, and it will not be advertized to new clients. However
@@ -161,6 +305,87 @@ impl DisplayHandle {
         self.handle.remove_global::<State>(id)
     }
 
+    /// Disable this global now, and remove it after a grace period
+    ///
+    /// This does what [`remove_global()`](DisplayHandle::remove_global) recommends: clients are
+    /// notified of the removal right away but can still bind the global during the `grace` period.
+    /// The global is then removed by the first [`Display::dispatch_clients()`] after the grace
+    /// period, which invokes `finalize` with your compositor state.
+    pub fn remove_global_after<State: 'static>(
+        &self,
+        id: GlobalId,
+        grace: Duration,
+        finalize: impl FnOnce(&DisplayHandle, &mut State) + Send + 'static,
+    ) {
+        self.handle.remove_global_after::<State>(id, grace, move |handle, state| {
+            finalize(&DisplayHandle::from(handle.clone()), state)
+        })
+    }
+
+    /// Replace the user data of a global, to hand it to a new [`GlobalDispatch`] implementation
+    ///
+    /// The global is kept, so its clients remain connected and are not notified. The clients binding it
+    /// from now on are given to the `GlobalDispatch<I, U>` implementation, with the data `upgrade` computes
+    /// from the previous one, as a compositor plugin would upgrade its handler in place. The version range
+    /// of the global is kept, as is the tracking of the resources bound from it if it was created with
+    /// [`create_tracked_global()`](Self::create_tracked_global), and the resources already bound can be
+    /// upgraded as well with [`replace_resource_data()`](Self::replace_resource_data).
+    ///
+    /// Returns an error if the global was removed, or if it is not an `I` global with data of type `Old`.
+    pub fn replace_global<State, I, Old, U>(
+        &self,
+        id: &GlobalId,
+        upgrade: impl FnOnce(&Old) -> U,
+    ) -> Result<(), InvalidId>
+    where
+        State: GlobalDispatch<I, U> + 'static,
+        I: Resource + 'static,
+        Old: Send + Sync + 'static,
+        U: Send + Sync + 'static,
+    {
+        let handler = self.handle.get_global_handler::<State>(id.clone())?;
+        let old = handler
+            .into_any_arc()
+            .downcast::<GlobalData<I, Old, State>>()
+            .map_err(|_| InvalidId)?;
+        self.handle.set_global_handler::<State>(
+            id.clone(),
+            Arc::new(GlobalData {
+                data: upgrade(&old.data),
+                bound: old.bound.clone(),
+                min_version: old.min_version,
+                _types: std::marker::PhantomData,
+            }),
+        )
+    }
+
+    /// Replace the user data of a resource, to hand its requests to a new [`Dispatch`] implementation
+    ///
+    /// The next requests of the resource are given to the `Dispatch<I, U>` implementation, with the data
+    /// `upgrade` computes from the previous one. The interceptor the resource may have is kept. The
+    /// handles of the resource created before still give the previous data with [`Resource::data()`],
+    /// unlike those created afterwards, like the ones given to the requests.
+    ///
+    /// Returns an error if the resource is dead, or if its data is not of type `Old`.
+    pub fn replace_resource_data<State, I, Old, U>(
+        &self,
+        resource: &I,
+        upgrade: impl FnOnce(&Old) -> U,
+    ) -> Result<(), InvalidId>
+    where
+        State: Dispatch<I, U> + 'static,
+        I: Resource + 'static,
+        Old: Send + Sync + 'static,
+        U: Send + Sync + 'static,
+    {
+        let data = self.handle.get_object_data_any(resource.id())?;
+        let old = data.downcast::<ResourceData<I, Old>>().map_err(|_| InvalidId)?;
+        self.handle.set_object_data::<State>(
+            resource.id(),
+            Arc::new(old.replace_udata(upgrade(&old.udata))),
+        )
+    }
+
     /// Access the protocol information for a Wayland object
     ///
     /// Returns an error if the object is no longer valid.
@@ -168,6 +393,99 @@ impl DisplayHandle {
         self.handle.object_info(id)
     }
 
+    /// Start counting the messages exchanged with each client, by interface
+    ///
+    /// The counters of a client can then be retrieved with [`Client::metrics()`].
+    pub fn enable_metrics(&self) {
+        self.handle.enable_metrics()
+    }
+
+    /// Get the I/O counters summed over the clients currently connected
+    ///
+    /// The counters of a single client are given by [`Client::io_stats()`].
+    pub fn io_stats(&self) -> IoStats {
+        self.handle.io_stats()
+    }
+
+    /// Set a hook receiving every request before it is dispatched and every event before it is sent
+    ///
+    /// This is the equivalent of `wl_display_add_protocol_logger()`, see
+    /// [`Handle::set_protocol_logger()`] for details. Passing `None` removes the logger.
+    pub fn set_protocol_logger(&self, logger: Option<Arc<dyn ProtocolLogger>>) {
+        self.handle.set_protocol_logger(logger)
+    }
+
+    /// Limit the requests dispatched for each client per dispatch, against the clients flooding
+    /// the compositor
+    ///
+    /// See [`RateLimit`] for details. Passing `None` removes the limits.
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.handle.set_rate_limit(limit)
+    }
+
+    /// Set what happens to the requests of the clients that cannot be decoded
+    ///
+    /// By default the clients sending them are disconnected with a protocol error, see
+    /// [`MalformedRequestPolicy`] for the alternative. Only the rust backend applies this policy.
+    pub fn set_malformed_request_policy(&self, policy: MalformedRequestPolicy) {
+        self.handle.set_malformed_request_policy(policy)
+    }
+
+    /// Set whether the clients are disconnected when the backend runs into an error with them
+    ///
+    /// By default they are disconnected right away, see [`KillPolicy`] for how to defer or prevent it
+    /// depending on the reason. Only the rust backend applies this policy.
+    pub fn set_kill_policy(&self, policy: KillPolicy) {
+        self.handle.set_kill_policy(policy)
+    }
+
+    /// Set the maximum size and number of file descriptors of the messages exchanged with the clients
+    ///
+    /// The requests exceeding them are handled by the [malformed request
+    /// policy](DisplayHandle::set_malformed_request_policy). See [`Handle::set_message_limits()`] for
+    /// details. Only the rust backend applies these limits.
+    pub fn set_message_limits(&self, limits: MessageLimits) {
+        self.handle.set_message_limits(limits)
+    }
+
+    /// Accept the clients of a sandbox on a listening socket, until `close_fd` is hung up
+    ///
+    /// This implements the lifecycle of the listeners registered with
+    /// `wp_security_context_v1.commit`: the clients accepted on `listener` are tagged with the
+    /// `context` given to the security context object, which [`Client::security_context()`]
+    /// returns, and the listener is closed once the other end of `close_fd` is closed. Sandboxed
+    /// clients must not be able to create other security contexts, so the
+    /// [`can_view()`](GlobalDispatch::can_view) method of the `wp_security_context_manager_v1`
+    /// global should hide it from them.
+    ///
+    /// The `accept` callback is invoked for each new client with its security context, and returns
+    /// its [`ClientData`], or `None` to refuse it. [`Client::get_data()`] returns this data as usual.
+    ///
+    /// The connections are accepted during [`Display::dispatch_clients()`], which must be the
+    /// `Display` of this `State`.
+    pub fn add_sandboxed_listener<State: 'static, F>(
+        &self,
+        listener: impl Into<OwnedFd>,
+        close_fd: impl Into<OwnedFd>,
+        context: SecurityContext,
+        mut accept: F,
+    ) -> std::io::Result<()>
+    where
+        F: FnMut(&mut State, &UnixStream, &SecurityContext) -> Option<Arc<dyn ClientData>>
+            + Send
+            + 'static,
+    {
+        let context = Arc::new(context);
+        self.handle.add_listener::<State, _>(
+            listener.into(),
+            Some(close_fd.into()),
+            move |state, stream| {
+                let data = accept(state, stream, &context)?;
+                Some(Arc::new(SandboxedClientData { context: context.clone(), data }) as Arc<_>)
+            },
+        )
+    }
+
     /// Send an event to given Wayland object
     ///
     /// This is intended to be a low-level method. You can alternatively use the methods on the
@@ -190,6 +508,16 @@ impl DisplayHandle {
         self.handle.post_error(resource.id(), code, std::ffi::CString::new(error).unwrap())
     }
 
+    /// Pause or resume the dispatch of the requests of this object
+    ///
+    /// The requests of a paused object are queued, and given to its [`Dispatch`](crate::Dispatch)
+    /// implementation in order once it is resumed, for example to hold the commits of a surface back
+    /// during a transaction. See [`Handle::set_object_paused()`] for details. Only the rust backend
+    /// pauses objects, the system backend returns [`PauseError::Unsupported`].
+    pub fn set_paused<I: Resource>(&self, resource: &I, paused: bool) -> Result<(), PauseError> {
+        self.handle.set_object_paused(resource.id(), paused)
+    }
+
     /// Access the object data associated with this object
     ///
     /// This is intended to be a low-level method. See [`Resource::object_data()`], for a more convenient
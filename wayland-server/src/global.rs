@@ -1,14 +1,20 @@
 use std::os::unix::io::OwnedFd;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use wayland_backend::server::{
-    ClientData, ClientId, GlobalHandler, GlobalId, Handle, ObjectData, ObjectId,
+    ClientData, ClientId, GlobalHandler, GlobalId, Handle, ObjectData, ObjectId, WeakHandle,
 };
 
-use crate::{Client, DataInit, DisplayHandle, New, Resource};
+use crate::{
+    protocol::__interfaces::WL_DISPLAY_INTERFACE, Client, DataInit, DisplayHandle, New, Resource,
+};
 
 pub(crate) struct GlobalData<I, U, D> {
     pub(crate) data: U,
+    /// The resources created by binding this global, if it is tracked by a [`Global`]
+    pub(crate) bound: Option<Arc<Mutex<Vec<ObjectId>>>>,
+    /// The binds with a lower version are refused with a protocol error
+    pub(crate) min_version: u32,
     pub(crate) _types: std::marker::PhantomData<(I, D)>,
 }
 
@@ -36,6 +42,23 @@ impl<I: Resource + 'static, U: Send + Sync + 'static, D: GlobalDispatch<I, U> +
         let resource = <I as Resource>::from_id(&handle, object_id)
             .expect("Wrong object_id in GlobalHandler ?!");
 
+        if resource.version() < self.min_version {
+            // this is what libwayland does for versions above the one of the global
+            if let Ok(display) =
+                handle.handle.object_for_protocol_id(client.id(), &WL_DISPLAY_INTERFACE, 1)
+            {
+                let message = format!(
+                    "invalid version for global {}: have at least {}, wanted {}",
+                    I::interface().name,
+                    self.min_version,
+                    resource.version()
+                );
+                // wl_display.error.invalid_object
+                handle.handle.post_error(display, 0, std::ffi::CString::new(message).unwrap());
+            }
+            return Arc::new(ProtocolErrorData);
+        }
+
         let mut new_data = None;
         let mut protocol_error = None;
 
@@ -49,7 +72,14 @@ impl<I: Resource + 'static, U: Send + Sync + 'static, D: GlobalDispatch<I, U> +
         );
 
         match new_data {
-            Some(data) => data,
+            Some(data) => {
+                if let Some(ref bound) = self.bound {
+                    let mut bound = bound.lock().unwrap();
+                    bound.retain(|id| handle.object_info(id.clone()).is_ok());
+                    bound.push(resource.id());
+                }
+                data
+            }
             None => match protocol_error {
                 Some((code, msg)) => {
                     resource.post_error(code, msg);
@@ -65,6 +95,65 @@ impl<I: Resource + 'static, U: Send + Sync + 'static, D: GlobalDispatch<I, U> +
     }
 }
 
+/// A global keeping track of the resources bound from it
+///
+/// It is created by [`DisplayHandle::create_tracked_global()`](crate::DisplayHandle::create_tracked_global),
+/// and gives access to the resources created by clients binding the global, for example to send
+/// them an event when the state it represents changes.
+pub struct Global<I> {
+    id: GlobalId,
+    handle: WeakHandle,
+    bound: Arc<Mutex<Vec<ObjectId>>>,
+    _interface: std::marker::PhantomData<fn() -> I>,
+}
+
+impl<I: Resource + 'static> Global<I> {
+    pub(crate) fn new(id: GlobalId, handle: WeakHandle, bound: Arc<Mutex<Vec<ObjectId>>>) -> Self {
+        Global { id, handle, bound, _interface: std::marker::PhantomData }
+    }
+
+    /// The id of this global, to disable or remove it
+    pub fn id(&self) -> GlobalId {
+        self.id.clone()
+    }
+
+    /// Invoke a closure on every live resource bound from this global, by all clients
+    ///
+    /// The resources destroyed by their client are skipped. Nothing is done if the display no
+    /// longer exists.
+    pub fn for_each_bound(&self, mut f: impl FnMut(&I)) {
+        let handle = match self.handle.upgrade() {
+            Some(handle) => DisplayHandle::from(handle),
+            None => return,
+        };
+        let resources = {
+            let mut bound = self.bound.lock().unwrap();
+            bound.retain(|id| handle.object_info(id.clone()).is_ok());
+            bound.iter().filter_map(|id| I::from_id(&handle, id.clone()).ok()).collect::<Vec<_>>()
+        };
+        for resource in &resources {
+            f(resource);
+        }
+    }
+}
+
+impl<I> Clone for Global<I> {
+    fn clone(&self) -> Self {
+        Global {
+            id: self.id.clone(),
+            handle: self.handle.clone(),
+            bound: self.bound.clone(),
+            _interface: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<I> std::fmt::Debug for Global<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Global").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
 struct ProtocolErrorData;
 
 impl<D> ObjectData<D> for ProtocolErrorData {
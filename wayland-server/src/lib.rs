@@ -42,6 +42,11 @@
 //! those messages are printed to stderr. If you activate the `log` cargo feature, they will instead be
 //! piped through the `log` crate.
 //!
+//! With the `tracing` cargo feature, the dispatching of each request to its [`Dispatch`] implementation
+//! happens in a `dispatch` span of the `tracing` crate, with the `client`, `interface`, `id` and `opcode`
+//! of the request. The feature enables the spans of the rust backend as well, about which see the
+//! documentation of `wayland-backend`.
+//!
 //! ## Advanced use
 //!
 //! ### Bypassing [`Dispatch`]
@@ -86,23 +91,40 @@ use wayland_backend::{
 };
 
 mod client;
+pub mod data_device;
 mod dispatch;
 mod display;
 mod global;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod output;
+#[cfg(feature = "replay")]
+pub mod replay;
+pub mod seat;
+mod security_context;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod shm;
 mod socket;
+mod user_data;
 
-pub use client::Client;
-pub use dispatch::{DataInit, Dispatch, New, ResourceData};
+pub use client::{Barrier, Client};
+pub use dispatch::{DataInit, Dispatch, Intercept, New, ResourceData};
 pub use display::{Display, DisplayHandle};
-pub use global::GlobalDispatch;
+pub use global::{Global, GlobalDispatch};
+pub use security_context::SecurityContext;
 pub use socket::{BindError, ListeningSocket};
+pub use user_data::UserDataMap;
 
 /// Backend reexports
 pub mod backend {
+    pub use wayland_backend::debug;
     pub use wayland_backend::protocol;
     pub use wayland_backend::server::{
-        Backend, ClientData, ClientId, Credentials, DisconnectReason, GlobalHandler, GlobalId,
-        Handle, InitError, InvalidId, ObjectData, ObjectId, WeakHandle,
+        Backend, ClientData, ClientId, Credentials, DestroyListenerId, DisconnectReason,
+        GlobalHandler, GlobalId, Handle, InitError, InvalidId, KillDecision, KillPolicy,
+        ObjectData, ObjectId, OverflowPolicy, PauseError, ProtocolLogger, RateLimit, WakeupHandle,
+        WeakHandle,
     };
     pub use wayland_backend::smallvec;
 }
@@ -120,7 +142,10 @@ pub mod protocol {
     pub mod __interfaces {
         wayland_scanner::generate_interfaces!("wayland.xml");
     }
+    #[cfg(not(feature = "serde"))]
     wayland_scanner::generate_server_code!("wayland.xml");
+    #[cfg(feature = "serde")]
+    wayland_scanner::generate_server_code!("wayland.xml", serde);
 }
 
 // internal imports for dispatching logging depending on the `log` feature
@@ -133,6 +158,20 @@ use std::{
     eprintln as log_error, eprintln as log_warn, eprintln as log_info, eprintln as log_debug,
 };
 
+// internal spans of the `tracing` crate, which expand to nothing without the `tracing` feature
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($name: literal $(, $($fields: tt)*)?) => {
+        let _span = tracing::trace_span!($name $(, $($fields)*)?).entered();
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($args: tt)*) => {};
+}
+#[allow(unused_imports)]
+use trace_span;
+
 /// Trait representing a Wayland interface
 pub trait Resource: Clone + std::fmt::Debug + Sized {
     /// The event enum for this interface
@@ -159,6 +198,17 @@ pub trait Resource: Clone + std::fmt::Debug + Sized {
     /// The version of this object
     fn version(&self) -> u32;
 
+    /// Checks if the version of this object supports the event with the given opcode
+    ///
+    /// The opcodes are given by the `EVT_*_OPCODE` constants of the interface modules. Sending an
+    /// event which the version of the object does not support is a protocol error.
+    fn version_supports(&self, opcode: u16) -> bool {
+        Self::interface()
+            .events
+            .get(opcode as usize)
+            .map_or(false, |event| self.version() >= event.since)
+    }
+
     /// Checks if the Wayland object associated with this proxy is still alive
     fn is_alive(&self) -> bool {
         if let Some(handle) = self.handle().upgrade() {
@@ -171,6 +221,13 @@ pub trait Resource: Clone + std::fmt::Debug + Sized {
     /// Access the user-data associated with this object
     fn data<U: 'static>(&self) -> Option<&U>;
 
+    /// Access the [`UserDataMap`] associated with this object
+    ///
+    /// Returns [`None`] if the user data of this object is not a [`UserDataMap`].
+    fn user_data_map(&self) -> Option<&UserDataMap> {
+        self.data::<UserDataMap>()
+    }
+
     /// Access the raw data associated with this object.
     ///
     /// It is given to you as a `dyn Any`, and you are responsible for downcasting it.
@@ -0,0 +1,526 @@
+//! A scripted compositor, for testing client code
+//!
+//! The [`MockCompositor`] is an in-process Wayland server that does not implement any protocol logic
+//! by itself. It advertises the globals you declare, records every request it receives, and runs the
+//! handlers you registered for some of them, which can reply with events. This allows writing
+//! deterministic tests for `wayland-client` based code without running a real compositor:
+//!
+//! ```no_run
+//! use wayland_server::mock::MockCompositor;
+//! use wayland_server::backend::protocol::Argument;
+//! use wayland_server::protocol::wl_seat;
+//! use wayland_server::Resource;
+//!
+//! let mut compositor = MockCompositor::new().unwrap();
+//! compositor.add_global(wl_seat::WlSeat::interface(), 7);
+//! compositor.on_bind(wl_seat::WlSeat::interface(), |reply| {
+//!     // advertise a keyboard as soon as the seat is bound
+//!     reply.reply("capabilities", vec![Argument::Uint(wl_seat::Capability::Keyboard.bits())]);
+//! });
+//!
+//! // connect the client under test to this socket
+//! let socket = compositor.add_client().unwrap();
+//! # let _ = socket;
+//!
+//! // alternate between running the client code and processing its requests
+//! compositor.dispatch().unwrap();
+//! let request = compositor.expect_request(wl_seat::WlSeat::interface(), "get_keyboard");
+//! # let _ = request;
+//! ```
+//!
+//! Beyond checking requests one at a time, [`MockCompositor::wait_for()`] waits for a set of
+//! [`expect()`]ed requests, possibly ordered relative to others, while the client runs in another
+//! thread:
+//!
+//! ```no_run
+//! # use wayland_server::mock::MockCompositor;
+//! # let mut compositor = MockCompositor::new().unwrap();
+//! use std::time::Duration;
+//! use wayland_server::mock::expect;
+//! use wayland_server::protocol::wl_surface::WlSurface;
+//!
+//! compositor.wait_for(
+//!     &[expect::<WlSurface>("commit").after::<WlSurface>("attach")],
+//!     Duration::from_secs(1),
+//! );
+//! ```
+//!
+//! This module is only available with the `mock` cargo feature.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::os::unix::io::{OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use wayland_backend::protocol::{Argument, Interface, Message};
+use wayland_backend::server::{
+    ClientData, ClientId, GlobalHandler, GlobalId, Handle, InitError, InvalidId, ObjectData,
+    ObjectId,
+};
+
+use crate::{Display, DisplayHandle, Resource};
+
+type RequestHandler = Box<dyn FnMut(&MockReply<'_>, &MockRequest) + Send>;
+type BindHandler = Box<dyn FnMut(&MockReply<'_>) + Send>;
+
+/// A scripted Wayland compositor
+///
+/// See the [module-level documentation](self) for an overview.
+pub struct MockCompositor {
+    display: Display<()>,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl fmt::Debug for MockCompositor {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockCompositor")
+            .field("requests", &self.state.lock().unwrap().requests)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    requests: VecDeque<MockRequest>,
+    request_handlers: Vec<(&'static str, u16, RequestHandler)>,
+    bind_handlers: Vec<(&'static str, BindHandler)>,
+}
+
+impl MockCompositor {
+    /// Create a new mock compositor, without any global
+    pub fn new() -> Result<Self, InitError> {
+        Ok(Self { display: Display::new()?, state: Arc::new(Mutex::new(MockState::default())) })
+    }
+
+    /// Get a handle to the underlying display
+    ///
+    /// This can be used to interact with the clients beyond what the mock compositor provides.
+    pub fn handle(&self) -> DisplayHandle {
+        self.display.handle()
+    }
+
+    /// Advertise a global to the clients
+    pub fn add_global(&mut self, interface: &'static Interface, version: u32) -> GlobalId {
+        let handler = Arc::new(MockObject { state: self.state.clone() });
+        self.display.backend().handle().create_global(interface, version, handler)
+    }
+
+    /// Run `handler` each time a global of given interface is bound
+    ///
+    /// The events sent with [`MockReply::reply()`] are sent to the newly created object.
+    pub fn on_bind<F>(&mut self, interface: &'static Interface, handler: F)
+    where
+        F: FnMut(&MockReply<'_>) + Send + 'static,
+    {
+        self.state.lock().unwrap().bind_handlers.push((interface.name, Box::new(handler)));
+    }
+
+    /// Run `handler` each time the given request is received
+    ///
+    /// The events sent with [`MockReply::reply()`] are sent to the object the request was sent to.
+    /// The handler is run before the request is recorded.
+    ///
+    /// **Panic:** this method panics if `interface` does not have a request named `request`.
+    pub fn on_request<F>(&mut self, interface: &'static Interface, request: &str, handler: F)
+    where
+        F: FnMut(&MockReply<'_>, &MockRequest) + Send + 'static,
+    {
+        let opcode =
+            interface.requests.iter().position(|desc| desc.name == request).unwrap_or_else(|| {
+                panic!("Interface {} has no request {}", interface.name, request)
+            });
+        self.state.lock().unwrap().request_handlers.push((
+            interface.name,
+            opcode as u16,
+            Box::new(handler),
+        ));
+    }
+
+    /// Create a new client, returning the socket it should connect to
+    ///
+    /// Typically given to `Connection::from_socket()` from `wayland-client`.
+    pub fn add_client(&mut self) -> std::io::Result<UnixStream> {
+        let (server, client) = UnixStream::pair()?;
+        self.display.handle().insert_client(server, Arc::new(MockClientData))?;
+        Ok(client)
+    }
+
+    /// Process the requests of the clients and flush the resulting events
+    ///
+    /// This does not block, the requests the clients did not send yet will be processed by the next
+    /// call.
+    pub fn dispatch(&mut self) -> std::io::Result<()> {
+        self.display.dispatch_clients(&mut ())?;
+        self.display.flush_clients()
+    }
+
+    /// Retrieve all the requests that were received and not yet retrieved, in order
+    pub fn take_requests(&mut self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.drain(..).collect()
+    }
+
+    /// Retrieve the oldest received request, asserting its interface and name
+    ///
+    /// **Panic:** this method panics if no request was received, or if it does not match.
+    pub fn expect_request(&mut self, interface: &'static Interface, request: &str) -> MockRequest {
+        let next = self.state.lock().unwrap().requests.pop_front();
+        match next {
+            Some(next) if next.interface().name == interface.name && next.name() == request => next,
+            Some(next) => panic!(
+                "Expected request {}.{}, got {}.{}",
+                interface.name,
+                request,
+                next.interface().name,
+                next.name()
+            ),
+            None => panic!("Expected request {}.{}, got nothing", interface.name, request),
+        }
+    }
+
+    /// Assert that all received requests have been retrieved
+    ///
+    /// **Panic:** this method panics if there are requests that were not retrieved.
+    pub fn expect_no_request(&mut self) {
+        let state = self.state.lock().unwrap();
+        if let Some(next) = state.requests.front() {
+            panic!("Expected no request, got {}.{}", next.interface().name, next.name());
+        }
+    }
+
+    /// Dispatch the clients until a distinct request matching each of the expectations is received
+    ///
+    /// The requests may arrive in any order, and other requests may be received in between. The
+    /// matched requests are retrieved and returned in the order of the expectations, the others stay
+    /// recorded.
+    ///
+    /// **Panic:** this method panics if the expectations are not met within `timeout`, listing the
+    /// unmet expectations and the requests received.
+    pub fn wait_for(
+        &mut self,
+        expectations: &[Expectation],
+        timeout: Duration,
+    ) -> Vec<MockRequest> {
+        self.wait(expectations, false, timeout)
+    }
+
+    /// Dispatch the clients until requests matching the expectations are received in order
+    ///
+    /// Other requests may be received in between. As with [`wait_for()`](Self::wait_for), the matched
+    /// requests are retrieved and returned, the others stay recorded.
+    ///
+    /// **Panic:** this method panics if the expectations are not met within `timeout`.
+    pub fn wait_for_sequence(
+        &mut self,
+        expectations: &[Expectation],
+        timeout: Duration,
+    ) -> Vec<MockRequest> {
+        self.wait(expectations, true, timeout)
+    }
+
+    fn wait(
+        &mut self,
+        expectations: &[Expectation],
+        ordered: bool,
+        timeout: Duration,
+    ) -> Vec<MockRequest> {
+        let deadline = Instant::now() + timeout;
+        let mut received = Vec::new();
+        loop {
+            if let Err(e) = self.dispatch() {
+                panic!("Failed to dispatch the clients: {}", e);
+            }
+            received.extend(self.take_requests());
+            let keys = received.iter().map(|r| (r.interface().name, r.name())).collect::<Vec<_>>();
+            let matches = match_expectations(expectations, &keys, ordered);
+
+            if matches.iter().all(Option::is_some) {
+                let mut received = received.into_iter().map(Some).collect::<Vec<_>>();
+                let matched =
+                    matches.iter().map(|&i| received[i.unwrap()].take().unwrap()).collect();
+                // put the other requests back
+                let mut state = self.state.lock().unwrap();
+                for request in received.into_iter().flatten().rev() {
+                    state.requests.push_front(request);
+                }
+                return matched;
+            }
+
+            if Instant::now() >= deadline {
+                let unmet = expectations.iter().zip(&matches).filter(|(_, m)| m.is_none());
+                let mut text = format!("Unmet expectations after {:?}:\n", timeout);
+                for (expectation, _) in unmet {
+                    text += &format!("  {}\n", expectation);
+                }
+                text += "Received requests:\n";
+                for request in &received {
+                    text += &format!("  {}\n", request);
+                }
+                panic!("{}", text);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Expect a request sent to an object of interface `I`, see [`MockCompositor::wait_for()`]
+///
+/// **Panic:** this function panics if the interface has no request named `request`.
+pub fn expect<I: Resource>(request: &str) -> Expectation {
+    let (interface, request) = request_desc(I::interface(), request);
+    Expectation { interface, request, after: Vec::new() }
+}
+
+/// A request expected by the [`MockCompositor`], created with [`expect()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    interface: &'static str,
+    request: &'static str,
+    after: Vec<(&'static str, &'static str)>,
+}
+
+impl Expectation {
+    /// Only match the request if it was received after the given request to an object of interface `I`
+    ///
+    /// **Panic:** this method panics if the interface has no request named `request`.
+    pub fn after<I: Resource>(mut self, request: &str) -> Self {
+        self.after.push(request_desc(I::interface(), request));
+        self
+    }
+}
+
+impl fmt::Display for Expectation {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.interface, self.request)?;
+        for (i, (interface, request)) in self.after.iter().enumerate() {
+            let sep = if i == 0 { "after" } else { "and" };
+            write!(f, " {} {}.{}", sep, interface, request)?;
+        }
+        Ok(())
+    }
+}
+
+fn request_desc(interface: &'static Interface, request: &str) -> (&'static str, &'static str) {
+    match interface.requests.iter().find(|desc| desc.name == request) {
+        Some(desc) => (interface.name, desc.name),
+        None => panic!("Interface {} has no request {}", interface.name, request),
+    }
+}
+
+// Assign a distinct received message to each expectation, returning the index of the message
+// matched by each of them
+fn match_expectations(
+    expectations: &[Expectation],
+    received: &[(&str, &str)],
+    ordered: bool,
+) -> Vec<Option<usize>> {
+    let candidates = expectations
+        .iter()
+        .map(|expectation| {
+            (0..received.len())
+                .filter(|&i| received[i] == (expectation.interface, expectation.request))
+                .filter(|&i| expectation.after.iter().all(|after| received[..i].contains(after)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    if ordered {
+        // the earliest match of each expectation leaves the most room to the next ones
+        let mut next = 0;
+        return candidates
+            .iter()
+            .map(|candidates| {
+                let found = candidates.iter().copied().find(|&i| i >= next);
+                next = found.map_or(usize::MAX, |i| i + 1);
+                found
+            })
+            .collect();
+    }
+
+    // bipartite matching, `owner` giving the expectation each message is assigned to
+    let mut owner = vec![None; received.len()];
+    for expectation in 0..expectations.len() {
+        augment(expectation, &candidates, &mut owner, &mut vec![false; received.len()]);
+    }
+    let mut matches = vec![None; expectations.len()];
+    for (i, expectation) in owner.into_iter().enumerate() {
+        if let Some(expectation) = expectation {
+            matches[expectation] = Some(i);
+        }
+    }
+    matches
+}
+
+// Find an assignment for `expectation`, moving the other expectations to their next candidates
+fn augment(
+    expectation: usize,
+    candidates: &[Vec<usize>],
+    owner: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for &i in &candidates[expectation] {
+        if !visited[i] {
+            visited[i] = true;
+            if owner[i].map_or(true, |other| augment(other, candidates, owner, visited)) {
+                owner[i] = Some(expectation);
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// A request received by the [`MockCompositor`]
+#[derive(Debug)]
+pub struct MockRequest {
+    /// The client that sent this request
+    pub client: ClientId,
+    /// The content of the request
+    pub message: Message<ObjectId, OwnedFd>,
+}
+
+impl fmt::Display for MockRequest {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}(", self.message.sender_id, self.name())?;
+        for (i, arg) in self.message.args.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            write!(f, "{}{}", sep, arg)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl MockRequest {
+    /// Interface of the object the request was sent to
+    pub fn interface(&self) -> &'static Interface {
+        self.message.sender_id.interface()
+    }
+
+    /// Name of the request
+    pub fn name(&self) -> &'static str {
+        self.interface().requests[self.message.opcode as usize].name
+    }
+
+    /// The object created by this request, if any
+    pub fn new_id(&self) -> Option<ObjectId> {
+        self.message.args.iter().find_map(|arg| match arg {
+            Argument::NewId(id) => Some(id.clone()),
+            _ => None,
+        })
+    }
+}
+
+/// Context given to the handlers of a [`MockCompositor`]
+#[derive(Debug)]
+pub struct MockReply<'a> {
+    handle: &'a Handle,
+    client: ClientId,
+    object: ObjectId,
+}
+
+impl<'a> MockReply<'a> {
+    /// The client that triggered this handler
+    pub fn client(&self) -> &ClientId {
+        &self.client
+    }
+
+    /// The object that was bound or that received the request
+    pub fn object(&self) -> &ObjectId {
+        &self.object
+    }
+
+    /// The backend handle of the compositor
+    pub fn handle(&self) -> &Handle {
+        self.handle
+    }
+
+    /// Send an event to the object that was bound or that received the request
+    ///
+    /// **Panic:** this method panics if the interface of the object has no event named `event`.
+    pub fn reply(&self, event: &str, args: Vec<Argument<ObjectId, RawFd>>) {
+        // The object can only be dead if the client disconnected, there is nobody to send the event to
+        let _ = self.send_event(&self.object, event, args);
+    }
+
+    /// Send an event to any object
+    ///
+    /// **Panic:** this method panics if the interface of the object has no event named `event`.
+    pub fn send_event(
+        &self,
+        object: &ObjectId,
+        event: &str,
+        args: Vec<Argument<ObjectId, RawFd>>,
+    ) -> Result<(), InvalidId> {
+        let interface = object.interface();
+        let opcode = interface
+            .events
+            .iter()
+            .position(|desc| desc.name == event)
+            .unwrap_or_else(|| panic!("Interface {} has no event {}", interface.name, event));
+        self.handle.send_event(Message {
+            sender_id: object.clone(),
+            opcode: opcode as u16,
+            args: args.into(),
+        })
+    }
+}
+
+struct MockObject {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl ObjectData<()> for MockObject {
+    fn request(
+        self: Arc<Self>,
+        handle: &Handle,
+        _: &mut (),
+        client: ClientId,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData<()>>> {
+        let request = MockRequest { client: client.clone(), message: msg };
+        let child = request.new_id().map(|_| self.clone() as Arc<dyn ObjectData<()>>);
+
+        let mut state = self.state.lock().unwrap();
+        let reply = MockReply { handle, client, object: request.message.sender_id.clone() };
+        for (interface, opcode, handler) in &mut state.request_handlers {
+            if *interface == request.interface().name && *opcode == request.message.opcode {
+                handler(&reply, &request);
+            }
+        }
+        state.requests.push_back(request);
+
+        child
+    }
+
+    fn destroyed(self: Arc<Self>, _: &Handle, _: &mut (), _: ClientId, _: ObjectId) {}
+}
+
+impl GlobalHandler<()> for MockObject {
+    fn bind(
+        self: Arc<Self>,
+        handle: &Handle,
+        _: &mut (),
+        client: ClientId,
+        _: GlobalId,
+        object: ObjectId,
+    ) -> Arc<dyn ObjectData<()>> {
+        let mut state = self.state.lock().unwrap();
+        let reply = MockReply { handle, client, object };
+        for (interface, handler) in &mut state.bind_handlers {
+            if *interface == reply.object.interface().name {
+                handler(&reply);
+            }
+        }
+        drop(state);
+
+        self
+    }
+}
+
+struct MockClientData;
+
+impl ClientData for MockClientData {}
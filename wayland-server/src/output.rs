@@ -0,0 +1,403 @@
+//! A `wl_output` global tracking the state of a display
+//!
+//! The [`Output`] owns the `wl_output` global of a display of the compositor. It sends the
+//! `geometry`, `mode`, `scale`, `name` and `description` events to the clients binding it, followed by
+//! `done`, and sends the events describing what changed, again followed by `done`, whenever the state
+//! of the display changes. The events which do not exist in the version of a `wl_output` are skipped.
+//!
+//! Delegate the `wl_output` interface to it from your state:
+//!
+//! ```
+//! use wayland_server::{
+//!     delegate_dispatch, delegate_global_dispatch,
+//!     output::{Mode, Output, OutputData, PhysicalProperties},
+//!     protocol::wl_output::{Subpixel, WlOutput},
+//!     Display,
+//! };
+//!
+//! struct State;
+//!
+//! delegate_global_dispatch!(State: [WlOutput: OutputData] => Output);
+//! delegate_dispatch!(State: [WlOutput: OutputData] => Output);
+//!
+//! let display = Display::<State>::new().unwrap();
+//! let output = Output::new::<State>(
+//!     &display.handle(),
+//!     "DP-1",
+//!     PhysicalProperties {
+//!         size: (600, 340),
+//!         subpixel: Subpixel::Unknown,
+//!         make: "Example".into(),
+//!         model: "Monitor".into(),
+//!     },
+//! );
+//! let mode = Mode { width: 1920, height: 1080, refresh: 60_000 };
+//! output.set_preferred_mode(mode);
+//! output.change_current_state(Some(mode), None, Some(2), Some((0, 0)));
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use wayland_backend::server::{ClientId, GlobalId};
+
+use crate::{
+    protocol::wl_output, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    Weak,
+};
+
+/// A mode of an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mode {
+    /// Width of the mode, in hardware pixels
+    pub width: i32,
+    /// Height of the mode, in hardware pixels
+    pub height: i32,
+    /// Refresh rate of the mode, in mHz
+    pub refresh: i32,
+}
+
+/// The properties of an output which cannot change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhysicalProperties {
+    /// Width and height of the output, in millimeters
+    pub size: (i32, i32),
+    /// Subpixel orientation of the output
+    pub subpixel: wl_output::Subpixel,
+    /// Manufacturer of the output
+    pub make: String,
+    /// Model of the output
+    pub model: String,
+}
+
+/// A `wl_output` global
+///
+/// See [the module level documentation](self).
+#[derive(Debug)]
+pub struct Output {
+    id: GlobalId,
+    data: OutputData,
+}
+
+/// The global data of an [`Output`], and the user data of its `wl_output` resources
+#[derive(Debug, Clone)]
+pub struct OutputData {
+    inner: Arc<Mutex<OutputState>>,
+}
+
+#[derive(Debug)]
+struct OutputState {
+    name: String,
+    description: Option<String>,
+    physical: PhysicalProperties,
+    modes: Vec<Mode>,
+    current_mode: Option<Mode>,
+    preferred_mode: Option<Mode>,
+    position: (i32, i32),
+    transform: wl_output::Transform,
+    scale: i32,
+    resources: Vec<Weak<wl_output::WlOutput>>,
+}
+
+impl OutputState {
+    fn send_geometry(&self, output: &wl_output::WlOutput) {
+        output.geometry(
+            self.position.0,
+            self.position.1,
+            self.physical.size.0,
+            self.physical.size.1,
+            self.physical.subpixel,
+            self.physical.make.clone(),
+            self.physical.model.clone(),
+            self.transform,
+        );
+    }
+
+    fn send_mode(&self, output: &wl_output::WlOutput, mode: Mode) {
+        let mut flags = wl_output::Mode::empty();
+        if Some(mode) == self.current_mode {
+            flags |= wl_output::Mode::Current;
+        }
+        if Some(mode) == self.preferred_mode {
+            flags |= wl_output::Mode::Preferred;
+        }
+        output.mode(flags, mode.width, mode.height, mode.refresh);
+    }
+
+    fn send_all(&self, output: &wl_output::WlOutput) {
+        self.send_geometry(output);
+        for &mode in &self.modes {
+            self.send_mode(output, mode);
+        }
+        if output.version() >= 2 {
+            output.scale(self.scale);
+        }
+        if output.version() >= 4 {
+            output.name(self.name.clone());
+            if let Some(ref description) = self.description {
+                output.description(description.clone());
+            }
+        }
+        if output.version() >= 2 {
+            output.done();
+        }
+    }
+
+    // Invoke `f` on all the bound outputs, and send `done` to those supporting it
+    fn send_update(&mut self, f: impl Fn(&Self, &wl_output::WlOutput)) {
+        let resources = std::mem::take(&mut self.resources);
+        self.resources = resources
+            .into_iter()
+            .filter(|resource| match resource.upgrade() {
+                Ok(output) => {
+                    f(self, &output);
+                    if output.version() >= 2 {
+                        output.done();
+                    }
+                    true
+                }
+                Err(_) => false,
+            })
+            .collect();
+    }
+}
+
+impl Output {
+    /// Create a `wl_output` global
+    ///
+    /// The name identifies the output to the clients, it should be unique and stable across
+    /// restarts of the compositor, like the name of the connector of a monitor.
+    pub fn new<D>(
+        handle: &DisplayHandle,
+        name: impl Into<String>,
+        physical: PhysicalProperties,
+    ) -> Output
+    where
+        D: GlobalDispatch<wl_output::WlOutput, OutputData>
+            + Dispatch<wl_output::WlOutput, OutputData>
+            + 'static,
+    {
+        let data = OutputData {
+            inner: Arc::new(Mutex::new(OutputState {
+                name: name.into(),
+                description: None,
+                physical,
+                modes: Vec::new(),
+                current_mode: None,
+                preferred_mode: None,
+                position: (0, 0),
+                transform: wl_output::Transform::Normal,
+                scale: 1,
+                resources: Vec::new(),
+            })),
+        };
+        let id = handle.create_global::<D, wl_output::WlOutput, _>(4, data.clone());
+        Output { id, data }
+    }
+
+    /// The id of the global
+    pub fn global(&self) -> GlobalId {
+        self.id.clone()
+    }
+
+    /// The name of the output
+    pub fn name(&self) -> String {
+        self.data.inner.lock().unwrap().name.clone()
+    }
+
+    /// The physical properties of the output
+    pub fn physical_properties(&self) -> PhysicalProperties {
+        self.data.inner.lock().unwrap().physical.clone()
+    }
+
+    /// The modes of the output
+    pub fn modes(&self) -> Vec<Mode> {
+        self.data.inner.lock().unwrap().modes.clone()
+    }
+
+    /// The current mode of the output
+    pub fn current_mode(&self) -> Option<Mode> {
+        self.data.inner.lock().unwrap().current_mode
+    }
+
+    /// The preferred mode of the output
+    pub fn preferred_mode(&self) -> Option<Mode> {
+        self.data.inner.lock().unwrap().preferred_mode
+    }
+
+    /// The position of the output in the global compositor space
+    pub fn position(&self) -> (i32, i32) {
+        self.data.inner.lock().unwrap().position
+    }
+
+    /// The transform of the output
+    pub fn transform(&self) -> wl_output::Transform {
+        self.data.inner.lock().unwrap().transform
+    }
+
+    /// The scale of the output
+    pub fn scale(&self) -> i32 {
+        self.data.inner.lock().unwrap().scale
+    }
+
+    /// Check whether a `wl_output` resource belongs to this output
+    pub fn owns(&self, output: &wl_output::WlOutput) -> bool {
+        output.data::<OutputData>().map_or(false, |data| Arc::ptr_eq(&data.inner, &self.data.inner))
+    }
+
+    /// Set the human-readable description of the output
+    ///
+    /// It is sent to the clients binding the output with version 4 or later.
+    pub fn set_description(&self, description: impl Into<String>) {
+        let mut state = self.data.inner.lock().unwrap();
+        let description = description.into();
+        if state.description.as_ref() == Some(&description) {
+            return;
+        }
+        state.description = Some(description.clone());
+        let resources = std::mem::take(&mut state.resources);
+        state.resources = resources
+            .into_iter()
+            .filter(|resource| match resource.upgrade() {
+                Ok(output) => {
+                    if output.version() >= 4 {
+                        output.description(description.clone());
+                        output.done();
+                    }
+                    true
+                }
+                Err(_) => false,
+            })
+            .collect();
+    }
+
+    /// Add a mode to the output
+    ///
+    /// The mode is advertised to the clients binding the output afterwards. Adding a mode that the output
+    /// already has does nothing.
+    pub fn add_mode(&self, mode: Mode) {
+        let mut state = self.data.inner.lock().unwrap();
+        if !state.modes.contains(&mode) {
+            state.modes.push(mode);
+        }
+    }
+
+    /// Remove a mode from the output
+    ///
+    /// If it was the current or preferred mode, the output no longer has one. The clients which already
+    /// bound the output are not notified, as the protocol has no event for it.
+    pub fn delete_mode(&self, mode: Mode) {
+        let mut state = self.data.inner.lock().unwrap();
+        state.modes.retain(|&m| m != mode);
+        if state.current_mode == Some(mode) {
+            state.current_mode = None;
+        }
+        if state.preferred_mode == Some(mode) {
+            state.preferred_mode = None;
+        }
+    }
+
+    /// Set the preferred mode of the output, adding it to its modes if needed
+    pub fn set_preferred_mode(&self, mode: Mode) {
+        let mut state = self.data.inner.lock().unwrap();
+        if !state.modes.contains(&mode) {
+            state.modes.push(mode);
+        }
+        state.preferred_mode = Some(mode);
+    }
+
+    /// Change the current state of the output
+    ///
+    /// The values set to `None` are left unchanged. A new current mode is added to the modes of the
+    /// output if needed. The clients which bound the output receive the events describing the changes,
+    /// followed by `done`; nothing is sent if nothing changed.
+    pub fn change_current_state(
+        &self,
+        mode: Option<Mode>,
+        transform: Option<wl_output::Transform>,
+        scale: Option<i32>,
+        position: Option<(i32, i32)>,
+    ) {
+        let mut state = self.data.inner.lock().unwrap();
+
+        let mode = mode.filter(|&mode| state.current_mode != Some(mode));
+        if let Some(mode) = mode {
+            if !state.modes.contains(&mode) {
+                state.modes.push(mode);
+            }
+            state.current_mode = Some(mode);
+        }
+        let mut geometry_changed = false;
+        if let Some(transform) = transform.filter(|&transform| transform != state.transform) {
+            state.transform = transform;
+            geometry_changed = true;
+        }
+        if let Some(position) = position.filter(|&position| position != state.position) {
+            state.position = position;
+            geometry_changed = true;
+        }
+        let scale = scale.filter(|&scale| scale != state.scale);
+        if let Some(scale) = scale {
+            state.scale = scale;
+        }
+
+        if !geometry_changed && mode.is_none() && scale.is_none() {
+            return;
+        }
+        state.send_update(|state, output| {
+            if geometry_changed {
+                state.send_geometry(output);
+            }
+            if let Some(mode) = mode {
+                state.send_mode(output, mode);
+            }
+            if let Some(scale) = scale {
+                if output.version() >= 2 {
+                    output.scale(scale);
+                }
+            }
+        });
+    }
+}
+
+impl<D> GlobalDispatch<wl_output::WlOutput, OutputData, D> for Output
+where
+    D: GlobalDispatch<wl_output::WlOutput, OutputData>
+        + Dispatch<wl_output::WlOutput, OutputData>
+        + 'static,
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_output::WlOutput>,
+        global_data: &OutputData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let output = data_init.init(resource, global_data.clone());
+        let mut state = global_data.inner.lock().unwrap();
+        state.send_all(&output);
+        state.resources.push(output.downgrade());
+    }
+}
+
+impl<D> Dispatch<wl_output::WlOutput, OutputData, D> for Output
+where
+    D: Dispatch<wl_output::WlOutput, OutputData> + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &wl_output::WlOutput,
+        _: wl_output::Request,
+        _: &OutputData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        // wl_output.release is the only request, the resource is destroyed by the backend
+    }
+
+    fn destroyed(_: &mut D, _: ClientId, output: &wl_output::WlOutput, data: &OutputData) {
+        let id = output.id();
+        data.inner.lock().unwrap().resources.retain(|resource| resource.id() != id);
+    }
+}
@@ -0,0 +1,728 @@
+//! Replay of recorded client sessions into a server
+//!
+//! A [`Capture`] is the list of requests a client sent during a session, parsed from the output of
+//! a program run with `WAYLAND_DEBUG=1`. Both the format of libwayland and the one of the rust
+//! backends are supported, and the log can be recorded on either side of the connection.
+//!
+//! A [`Replay`] connects a synthetic client to your [`Display`] and sends these requests again, one
+//! at a time, dispatching the server between each of them. This makes a bug reported along with a
+//! trace reproducible in a test, independently of the client that triggered it:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use wayland_server::replay::{Capture, Pacing, RecordedBy, Replay};
+//! use wayland_server::{protocol::wl_compositor, Display, Resource};
+//! # struct State;
+//! # struct ClientState;
+//! # impl wayland_server::backend::ClientData for ClientState {}
+//!
+//! let log = std::fs::read_to_string("client.log").unwrap();
+//! let capture = Capture::from_debug_log(&log, RecordedBy::Client);
+//!
+//! let mut display = Display::<State>::new().unwrap();
+//! // ... create the globals of your compositor ...
+//! let mut replay = Replay::new(
+//!     capture,
+//!     &[wl_compositor::WlCompositor::interface()],
+//!     &mut display.handle(),
+//!     Arc::new(ClientState),
+//! )
+//! .unwrap();
+//! replay.set_pacing(Pacing::Immediate);
+//! replay.run(&mut display, &mut State).unwrap();
+//! ```
+//!
+//! The content of file descriptors and of arrays is not part of the log. File descriptors are replaced by
+//! empty temporary files, except for `wl_shm.create_pool` for which the file has the size of the pool, and
+//! arrays printed by libwayland are replaced by zeroes. [`Replay::set_fd_source()`] allows providing
+//! meaningful file descriptors instead.
+//!
+//! This module is only available with the `replay` cargo feature.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use wayland_backend::protocol::{Argument, ArgumentType, Interface, Message};
+use wayland_backend::rs::client::{Backend, ObjectData, ObjectId, WaylandError};
+use wayland_backend::server::{ClientData, ClientId};
+
+use crate::{Display, DisplayHandle};
+
+type FdSource = Box<dyn FnMut(&CapturedRequest) -> std::io::Result<OwnedFd> + Send>;
+
+/// Which side of the connection printed a debug log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedBy {
+    /// The log was printed by the client, its requests are the messages it sent
+    Client,
+    /// The log was printed by the server, the requests are the messages it received
+    ///
+    /// The log must only contain the messages of a single client.
+    Server,
+}
+
+/// The requests sent by a client during a recorded session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capture {
+    requests: Vec<CapturedRequest>,
+}
+
+/// A request of a [`Capture`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedRequest {
+    /// Time of the request, relative to the first request of the capture
+    pub time: Duration,
+    /// Interface of the object the request was sent to
+    pub interface: String,
+    /// Protocol id of the object the request was sent to
+    pub object: u32,
+    /// Name of the request
+    pub name: String,
+    /// Arguments of the request, as printed in the log
+    pub args: Vec<String>,
+}
+
+impl fmt::Display for CapturedRequest {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}.{}({})", self.interface, self.object, self.name, self.args.join(", "))
+    }
+}
+
+impl Capture {
+    /// Create a capture from a list of requests
+    pub fn new(requests: Vec<CapturedRequest>) -> Self {
+        Self { requests }
+    }
+
+    /// Extract the requests of a client from `WAYLAND_DEBUG` output
+    ///
+    /// The lines that are not messages, as well as the events, are ignored.
+    pub fn from_debug_log(log: &str, recorded_by: RecordedBy) -> Self {
+        let mut requests = Vec::new();
+        let mut start = None;
+        for line in log.lines() {
+            let (time, sent, mut request) = match parse_line(line) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            if sent != (recorded_by == RecordedBy::Client) {
+                continue;
+            }
+            let start = *start.get_or_insert(time);
+            request.time = time.saturating_sub(start);
+            requests.push(request);
+        }
+        Self { requests }
+    }
+
+    /// The requests of this capture, in order
+    pub fn requests(&self) -> &[CapturedRequest] {
+        &self.requests
+    }
+}
+
+/// Pacing of the requests of a [`Replay`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Send each request as soon as the server processed the previous one
+    Immediate,
+    /// Wait between the requests as much as during the recorded session, divided by `speed`
+    Recorded {
+        /// How much faster than the recorded session the requests are sent
+        speed: f64,
+    },
+}
+
+/// An error while replaying a capture
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The request targets or references an object that does not exist
+    UnknownObject {
+        /// The request that failed
+        request: String,
+        /// Protocol id of the unknown object
+        id: u32,
+    },
+    /// The request creates an object with an interface that was not provided
+    UnknownInterface {
+        /// The request that failed
+        request: String,
+        /// Name of the interface
+        interface: String,
+    },
+    /// The interface of the target object has no request of this name
+    UnknownRequest {
+        /// The request that failed
+        request: String,
+    },
+    /// An argument does not match the signature of the request
+    InvalidArgument {
+        /// The request that failed
+        request: String,
+        /// The argument, as printed in the log
+        argument: String,
+    },
+    /// The connection was closed by the server, or failed
+    Connection(WaylandError),
+    /// The file descriptor source, or dispatching the server, failed
+    Io(std::io::Error),
+}
+
+impl std::error::Error for ReplayError {
+    #[cfg_attr(coverage, coverage(off))]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Connection(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ReplayError {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownObject { request, id } => {
+                write!(f, "Request {} uses the unknown object {}", request, id)
+            }
+            Self::UnknownInterface { request, interface } => {
+                write!(
+                    f,
+                    "Request {} creates an object of unknown interface {}",
+                    request, interface
+                )
+            }
+            Self::UnknownRequest { request } => write!(f, "Unknown request {}", request),
+            Self::InvalidArgument { request, argument } => {
+                write!(f, "Invalid argument {} for request {}", argument, request)
+            }
+            Self::Connection(e) => write!(f, "Connection error: {}", e),
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplayError {
+    #[cfg_attr(coverage, coverage(off))]
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A synthetic client replaying a [`Capture`]
+///
+/// See the [module-level documentation](self) for an overview. The requests can be sent one by one
+/// with [`Replay::step()`], or all of them with [`Replay::run()`] which also dispatches the server.
+pub struct Replay {
+    capture: Capture,
+    position: usize,
+    started: Option<Instant>,
+    pacing: Pacing,
+    backend: Backend,
+    interfaces: Vec<&'static Interface>,
+    // maps the protocol ids of the capture to the objects of the synthetic client
+    objects: Arc<Mutex<HashMap<u32, ObjectId>>>,
+    fd_source: FdSource,
+    client_id: ClientId,
+}
+
+impl fmt::Debug for Replay {
+    #[cfg_attr(coverage, coverage(off))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replay")
+            .field("capture", &self.capture)
+            .field("position", &self.position)
+            .field("pacing", &self.pacing)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Replay {
+    /// Insert the synthetic client in the display
+    ///
+    /// `interfaces` are the interfaces of the globals the client binds with `wl_registry.bind`, the
+    /// interfaces of the other objects are deduced from the requests creating them. The client is
+    /// associated with the provided [`ClientData`].
+    pub fn new(
+        capture: Capture,
+        interfaces: &[&'static Interface],
+        handle: &mut DisplayHandle,
+        data: Arc<dyn ClientData>,
+    ) -> std::io::Result<Self> {
+        let (server, client) = UnixStream::pair()?;
+        let client_id = handle.insert_client(server, data)?.id();
+        let backend = Backend::connect(client).map_err(|_| ErrorKind::Unsupported)?;
+        let objects = Arc::new(Mutex::new(HashMap::from([(1, backend.display_id())])));
+        Ok(Self {
+            capture,
+            position: 0,
+            started: None,
+            pacing: Pacing::Immediate,
+            backend,
+            interfaces: interfaces.to_vec(),
+            objects,
+            fd_source: Box::new(placeholder_fd),
+            client_id,
+        })
+    }
+
+    /// Set the pacing of [`Replay::run()`], [`Pacing::Immediate`] by default
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+    }
+
+    /// Provide the file descriptors sent in place of the recorded ones
+    ///
+    /// The closure is invoked for each file descriptor argument, with the request it is sent with.
+    pub fn set_fd_source<F>(&mut self, source: F)
+    where
+        F: FnMut(&CapturedRequest) -> std::io::Result<OwnedFd> + Send + 'static,
+    {
+        self.fd_source = Box::new(source);
+    }
+
+    /// The id of the synthetic client
+    pub fn client_id(&self) -> ClientId {
+        self.client_id.clone()
+    }
+
+    /// The next request to be sent, if any
+    pub fn next_request(&self) -> Option<&CapturedRequest> {
+        self.capture.requests.get(self.position)
+    }
+
+    /// Send the next request to the server
+    ///
+    /// This does not wait for the server to process it. Returns `false` if all the requests have
+    /// already been sent.
+    pub fn step(&mut self) -> Result<bool, ReplayError> {
+        let request = match self.capture.requests.get(self.position) {
+            Some(request) => request,
+            None => return Ok(false),
+        };
+        self.position += 1;
+        let rendered = request.to_string();
+
+        let target =
+            self.objects.lock().unwrap().get(&request.object).cloned().ok_or(
+                ReplayError::UnknownObject { request: rendered.clone(), id: request.object },
+            )?;
+        let interface = target.interface();
+        let (opcode, desc) = interface
+            .requests
+            .iter()
+            .enumerate()
+            .find(|(_, desc)| desc.name == request.name)
+            .ok_or_else(|| ReplayError::UnknownRequest { request: rendered.clone() })?;
+        if desc.signature.len() != request.args.len() {
+            return Err(ReplayError::InvalidArgument {
+                request: rendered,
+                argument: request.args.join(", "),
+            });
+        }
+
+        let mut args = Vec::with_capacity(desc.signature.len());
+        let mut fds = Vec::new();
+        let mut new_id = None;
+        for (kind, text) in desc.signature.iter().zip(&request.args) {
+            let invalid = || ReplayError::InvalidArgument {
+                request: rendered.clone(),
+                argument: text.clone(),
+            };
+            let arg =
+                match kind {
+                    ArgumentType::Int => Argument::Int(text.parse().map_err(|_| invalid())?),
+                    ArgumentType::Uint => Argument::Uint(text.parse().map_err(|_| invalid())?),
+                    ArgumentType::Fixed => Argument::Fixed(parse_fixed(text).ok_or_else(invalid)?),
+                    ArgumentType::Str(_) => Argument::Str(parse_str(text).ok_or_else(invalid)?),
+                    ArgumentType::Array => {
+                        Argument::Array(Box::new(parse_array(text).ok_or_else(invalid)?))
+                    }
+                    ArgumentType::Fd => {
+                        let fd = (self.fd_source)(request)?;
+                        let raw = fd.as_raw_fd();
+                        fds.push(fd);
+                        Argument::Fd(raw)
+                    }
+                    ArgumentType::Object(_) => match parse_object(text).ok_or_else(invalid)? {
+                        (_, 0) => Argument::Object(ObjectId::null()),
+                        (_, id) => {
+                            let object = self.objects.lock().unwrap().get(&id).cloned();
+                            Argument::Object(object.ok_or(ReplayError::UnknownObject {
+                                request: rendered.clone(),
+                                id,
+                            })?)
+                        }
+                    },
+                    ArgumentType::NewId => {
+                        let (_, id) = parse_object(text.strip_prefix("new id ").unwrap_or(text))
+                            .ok_or_else(invalid)?;
+                        new_id = Some(id);
+                        Argument::NewId(ObjectId::null())
+                    }
+                };
+            args.push(arg);
+        }
+
+        let child_spec = match (new_id, desc.child_interface) {
+            (None, _) => None,
+            (Some(_), Some(child)) => {
+                let version =
+                    self.backend.info(target.clone()).map(|info| info.version).unwrap_or(1);
+                Some((child, version))
+            }
+            // a request creating an object of any interface, as `wl_registry.bind`, which
+            // specifies the interface and version just before the new id
+            (Some(_), None) => {
+                let (name, version) = match args[..] {
+                    [.., Argument::Str(Some(ref name)), Argument::Uint(version), Argument::NewId(_)] => {
+                        (name.to_string_lossy().into_owned(), version)
+                    }
+                    _ => {
+                        return Err(ReplayError::InvalidArgument {
+                            request: rendered,
+                            argument: request.args.join(", "),
+                        })
+                    }
+                };
+                let child = self.interfaces.iter().find(|interface| interface.name == name).ok_or(
+                    ReplayError::UnknownInterface { request: rendered.clone(), interface: name },
+                )?;
+                Some((*child, version))
+            }
+        };
+
+        let data = child_spec.map(|_| Arc::new(ReplayData { objects: self.objects.clone() }) as _);
+        let created = self
+            .backend
+            .send_request(
+                Message { sender_id: target, opcode: opcode as u16, args: args.into() },
+                data,
+                child_spec,
+            )
+            .map_err(|_| ReplayError::UnknownObject { request: rendered, id: request.object })?;
+        if let Some(id) = new_id {
+            self.objects.lock().unwrap().insert(id, created);
+        }
+        drop(fds);
+        self.backend.flush().map_err(ReplayError::Connection)?;
+        Ok(true)
+    }
+
+    /// Process the events the server sent so far
+    ///
+    /// This does not block. The objects created by the server are registered, so that the following
+    /// requests can use them.
+    pub fn process_events(&mut self) -> Result<(), ReplayError> {
+        loop {
+            let guard = match self.backend.prepare_read() {
+                Some(guard) => guard,
+                None => {
+                    self.backend.dispatch_inner_queue().map_err(ReplayError::Connection)?;
+                    continue;
+                }
+            };
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(ReplayError::Connection(e)),
+            }
+        }
+    }
+
+    /// Send all the remaining requests, dispatching the display after each of them
+    pub fn run<D>(&mut self, display: &mut Display<D>, state: &mut D) -> Result<(), ReplayError> {
+        self.run_with(|| {
+            display.dispatch_clients(state)?;
+            display.flush_clients()
+        })
+    }
+
+    /// Send all the remaining requests, invoking `dispatch` after each of them
+    ///
+    /// The closure is expected to dispatch the clients of the server and flush their events, as
+    /// [`Replay::run()`] does for a [`Display`].
+    pub fn run_with<F>(&mut self, mut dispatch: F) -> Result<(), ReplayError>
+    where
+        F: FnMut() -> std::io::Result<()>,
+    {
+        while let Some(time) = self.next_request().map(|request| request.time) {
+            if let Pacing::Recorded { speed } = self.pacing {
+                let started = *self.started.get_or_insert_with(Instant::now);
+                let due = started + time.div_f64(speed);
+                let now = Instant::now();
+                if due > now {
+                    std::thread::sleep(due - now);
+                }
+            }
+            self.step()?;
+            dispatch()?;
+            self.process_events()?;
+        }
+        Ok(())
+    }
+}
+
+// Records the objects created by the server, which have the same ids as in the recorded session
+struct ReplayData {
+    objects: Arc<Mutex<HashMap<u32, ObjectId>>>,
+}
+
+impl ObjectData for ReplayData {
+    fn event(
+        self: Arc<Self>,
+        _: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        for arg in msg.args.iter() {
+            if let Argument::NewId(id) = arg {
+                self.objects.lock().unwrap().insert(id.protocol_id(), id.clone());
+                return Some(self);
+            }
+        }
+        None
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
+
+fn placeholder_fd(request: &CapturedRequest) -> std::io::Result<OwnedFd> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "wayland-replay-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+    std::fs::remove_file(&path)?;
+    if request.interface == "wl_shm" && request.name == "create_pool" {
+        if let Some(size) = request.args.last().and_then(|size| size.parse::<u64>().ok()) {
+            file.set_len(size)?;
+        }
+    }
+    Ok(file.into())
+}
+
+// Parses a line of debug output into the timestamp, whether the message was sent, and the message
+fn parse_line(line: &str) -> Option<(Duration, bool, CapturedRequest)> {
+    let rest = line.trim_end().strip_prefix('[')?;
+    let (timestamp, rest) = rest.split_once(']')?;
+    let time = parse_timestamp(timestamp.trim())?;
+    let mut rest = rest.trim_start();
+
+    if let Some(queued) = rest.strip_prefix('{') {
+        rest = queued.split_once('}')?.1.trim_start();
+    }
+    if rest.starts_with("discarded ") {
+        return None;
+    }
+    let mut sent = false;
+    if let Some(after) = rest.strip_prefix("->") {
+        sent = true;
+        rest = after.trim_start();
+    } else if let Some(after) = rest.strip_prefix("<-") {
+        rest = after.trim_start();
+    }
+
+    let (head, args) = rest.split_once('(')?;
+    // the rust backends put a space, and sometimes a comma, before the arguments
+    let head = head.trim_end().trim_end_matches(',');
+    let (target, name) = head.split_once('.')?;
+    let (interface, object) = parse_object(target)?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let args = split_args(args.strip_suffix(')')?).into_iter().map(str::to_owned).collect();
+
+    let request = CapturedRequest {
+        time,
+        interface: interface.to_owned(),
+        object,
+        name: name.to_owned(),
+        args,
+    };
+    Some((time, sent, request))
+}
+
+// libwayland prints milliseconds with 3 decimals, the rust backends print seconds with 6 decimals
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let (int, frac) = timestamp.split_once('.')?;
+    let int = int.parse::<u64>().ok()?;
+    let frac_value = frac.parse::<u64>().ok()?;
+    match frac.len() {
+        3 => Some(Duration::from_millis(int) + Duration::from_micros(frac_value)),
+        6 => Some(Duration::from_secs(int) + Duration::from_micros(frac_value)),
+        _ => None,
+    }
+}
+
+fn parse_object(text: &str) -> Option<(&str, u32)> {
+    if text == "nil" {
+        return Some(("", 0));
+    }
+    let (interface, id) = text.rsplit_once('@')?;
+    Some((interface, id.parse().ok()?))
+}
+
+// Split the arguments on the commas that are not part of a string or an array
+fn split_args(args: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                result.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = args[start..].trim();
+    if !last.is_empty() || !result.is_empty() {
+        result.push(last);
+    }
+    result
+}
+
+// libwayland prints fixed point values as decimals, the rust backends print their raw value
+fn parse_fixed(text: &str) -> Option<i32> {
+    if text.contains('.') {
+        Some((text.parse::<f64>().ok()? * 256.).round() as i32)
+    } else {
+        text.parse().ok()
+    }
+}
+
+// libwayland prints strings verbatim between quotes, the rust backends print them as a debug-formatted
+// `Option<CString>`
+fn parse_str(text: &str) -> Option<Option<Box<CString>>> {
+    if text == "nil" || text == "None" {
+        return Some(None);
+    }
+    let string = match text.strip_prefix("Some(").and_then(|s| s.strip_suffix(')')) {
+        Some(debug) => unescape(debug.strip_prefix('"')?.strip_suffix('"')?)?,
+        None => text.strip_prefix('"')?.strip_suffix('"')?.to_owned(),
+    };
+    Some(Some(Box::new(CString::new(string).ok()?)))
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            't' => result.push('\t'),
+            '0' => result.push('\0'),
+            'x' => {
+                let code: String = chars.by_ref().take(2).collect();
+                result.push(u8::from_str_radix(&code, 16).ok()? as char);
+            }
+            'u' => {
+                let code: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                result.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            other => result.push(other),
+        }
+    }
+    Some(result)
+}
+
+// libwayland only prints the size of arrays, the rust backends print their content
+fn parse_array(text: &str) -> Option<Vec<u8>> {
+    if let Some(size) = text.strip_prefix("array[").and_then(|s| s.strip_suffix(']')) {
+        return Some(vec![0; size.parse().ok()?]);
+    }
+    if text == "array" {
+        return Some(Vec::new());
+    }
+    let content = text.strip_prefix('[')?.strip_suffix(']')?.trim();
+    if content.is_empty() {
+        return Some(Vec::new());
+    }
+    content.split(',').map(|byte| byte.trim().parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_client_log() {
+        let log = "\
+[3626514.330] {Default Queue}  -> wl_display@1.get_registry(new id wl_registry@2)
+[3626514.400] {Default Queue} wl_registry@2.global(1, \"wl_compositor\", 6)
+[3626515.330]  -> wl_registry@2.bind(1, \"wl_compositor\", 4, new id [unknown]@3)
+hello from the client
+[3626516.330]  -> wl_surface@4.attach(wl_buffer@5, 0, 0)";
+        let capture = Capture::from_debug_log(log, RecordedBy::Client);
+        let requests = capture.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].time, Duration::ZERO);
+        assert_eq!(requests[0].args, ["new id wl_registry@2"]);
+        assert_eq!(requests[1].time, Duration::from_millis(1));
+        assert_eq!(requests[1].name, "bind");
+        assert_eq!(requests[2].interface, "wl_surface");
+        assert_eq!(requests[2].object, 4);
+        assert_eq!(requests[2].args, ["wl_buffer@5", "0", "0"]);
+    }
+
+    #[test]
+    fn parse_server_log() {
+        let log = "\
+[1700000000.000100] <- wl_display@1.get_registry, (wl_registry@2)
+[1700000000.000200] -> wl_registry@2.global, (1, Some(\"wl_compositor\"), 6)
+[1700000000.100100] <- wl_registry@2.bind, (1, Some(\"wl_compositor\"), 4, wl_compositor@3)";
+        let capture = Capture::from_debug_log(log, RecordedBy::Server);
+        let requests = capture.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].time, Duration::from_millis(100));
+        assert_eq!(requests[1].args[1], "Some(\"wl_compositor\")");
+    }
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(parse_fixed("1.500000"), Some(384));
+        assert_eq!(parse_fixed("384"), Some(384));
+        assert_eq!(parse_str("nil"), Some(None));
+        assert_eq!(
+            parse_str("Some(\"a \\\"b\\\"\\n\")").unwrap().unwrap().to_str().unwrap(),
+            "a \"b\"\n"
+        );
+        assert_eq!(parse_str("\"a, b\"").unwrap().unwrap().to_str().unwrap(), "a, b");
+        assert_eq!(parse_array("array[3]"), Some(vec![0, 0, 0]));
+        assert_eq!(parse_array("[1, 2, 3]"), Some(vec![1, 2, 3]));
+        assert_eq!(parse_object("[unknown]@3"), Some(("[unknown]", 3)));
+        assert_eq!(parse_object("nil"), Some(("", 0)));
+    }
+}
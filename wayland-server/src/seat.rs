@@ -0,0 +1,583 @@
+//! A `wl_seat` global tracking the input devices of the clients
+//!
+//! The [`Seat`] owns a `wl_seat` global and its capabilities. It creates and tracks the
+//! `wl_pointer`, `wl_keyboard` and `wl_touch` objects of the clients, and sends the input events of the
+//! compositor to the objects of the client having the focus, with a new serial for the events that need
+//! one. Version-specific events, like `wl_pointer.frame`, are only sent to the objects supporting them.
+//!
+//! Delegate the seat interfaces to it from your state:
+//!
+//! ```
+//! use wayland_server::{
+//!     delegate_dispatch, delegate_global_dispatch,
+//!     protocol::{
+//!         wl_keyboard::WlKeyboard, wl_pointer::WlPointer, wl_seat::{Capability, WlSeat},
+//!         wl_touch::WlTouch,
+//!     },
+//!     seat::{Seat, SeatData},
+//!     Display,
+//! };
+//!
+//! struct State;
+//!
+//! delegate_global_dispatch!(State: [WlSeat: SeatData] => Seat);
+//! delegate_dispatch!(State: [WlSeat: SeatData] => Seat);
+//! delegate_dispatch!(State: [WlPointer: SeatData] => Seat);
+//! delegate_dispatch!(State: [WlKeyboard: SeatData] => Seat);
+//! delegate_dispatch!(State: [WlTouch: SeatData] => Seat);
+//!
+//! let display = Display::<State>::new().unwrap();
+//! let seat = Seat::new::<State>(&display.handle(), "seat0");
+//! seat.set_capabilities(Capability::Pointer | Capability::Keyboard);
+//! ```
+
+use std::{
+    os::unix::io::{AsFd, OwnedFd},
+    sync::{Arc, Mutex},
+};
+
+use wayland_backend::server::{ClientId, GlobalId};
+
+use crate::{
+    protocol::{wl_keyboard, wl_pointer, wl_seat, wl_surface, wl_touch},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, Weak,
+};
+
+/// A `wl_seat` global
+///
+/// See [the module level documentation](self).
+#[derive(Debug)]
+pub struct Seat {
+    id: GlobalId,
+    data: SeatData,
+}
+
+/// The global data of a [`Seat`], and the user data of its resources
+#[derive(Debug, Clone)]
+pub struct SeatData {
+    inner: Arc<Mutex<SeatState>>,
+}
+
+/// The cursor image requested by the client having the pointer focus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorImage {
+    /// The surface of the cursor, `None` to hide the cursor
+    pub surface: Option<wl_surface::WlSurface>,
+    /// The position of the hotspot in the surface
+    pub hotspot: (i32, i32),
+}
+
+#[derive(Debug)]
+struct Keymap {
+    format: wl_keyboard::KeymapFormat,
+    fd: OwnedFd,
+    size: u32,
+}
+
+#[derive(Debug)]
+struct SeatState {
+    name: String,
+    capabilities: wl_seat::Capability,
+    serial: u32,
+    seats: Vec<Weak<wl_seat::WlSeat>>,
+    pointers: Vec<Weak<wl_pointer::WlPointer>>,
+    keyboards: Vec<Weak<wl_keyboard::WlKeyboard>>,
+    touches: Vec<Weak<wl_touch::WlTouch>>,
+    pointer_focus: Option<(wl_surface::WlSurface, u32)>,
+    keyboard_focus: Option<wl_surface::WlSurface>,
+    touch_points: Vec<(i32, wl_surface::WlSurface)>,
+    cursor: Option<CursorImage>,
+    keymap: Option<Keymap>,
+    repeat_info: (i32, i32),
+}
+
+impl PartialEq for SeatData {
+    fn eq(&self, other: &SeatData) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for SeatData {}
+
+impl SeatData {
+    // Serials of the events sent by the other helpers on behalf of the seat
+    pub(crate) fn next_serial(&self) -> u32 {
+        self.inner.lock().unwrap().next_serial()
+    }
+}
+
+impl SeatState {
+    fn next_serial(&mut self) -> u32 {
+        self.serial = self.serial.wrapping_add(1);
+        self.serial
+    }
+}
+
+// The live objects of the list belonging to the client of the surface
+fn objects_of<I: Resource>(list: &mut Vec<Weak<I>>, surface: &wl_surface::WlSurface) -> Vec<I> {
+    list.retain(|object| object.upgrade().is_ok());
+    let client = client_id(surface);
+    list.iter()
+        .filter_map(|object| object.upgrade().ok())
+        .filter(|object| client.is_some() && client_id(object) == client)
+        .collect()
+}
+
+fn client_id(resource: &impl Resource) -> Option<ClientId> {
+    resource.client().map(|client| client.id())
+}
+
+impl Seat {
+    /// Create a `wl_seat` global without any capability
+    pub fn new<D>(handle: &DisplayHandle, name: impl Into<String>) -> Seat
+    where
+        D: GlobalDispatch<wl_seat::WlSeat, SeatData>
+            + Dispatch<wl_seat::WlSeat, SeatData>
+            + Dispatch<wl_pointer::WlPointer, SeatData>
+            + Dispatch<wl_keyboard::WlKeyboard, SeatData>
+            + Dispatch<wl_touch::WlTouch, SeatData>
+            + 'static,
+    {
+        let data = SeatData {
+            inner: Arc::new(Mutex::new(SeatState {
+                name: name.into(),
+                capabilities: wl_seat::Capability::empty(),
+                serial: 0,
+                seats: Vec::new(),
+                pointers: Vec::new(),
+                keyboards: Vec::new(),
+                touches: Vec::new(),
+                pointer_focus: None,
+                keyboard_focus: None,
+                touch_points: Vec::new(),
+                cursor: None,
+                keymap: None,
+                repeat_info: (25, 600),
+            })),
+        };
+        let id = handle.create_global::<D, wl_seat::WlSeat, _>(9, data.clone());
+        Seat { id, data }
+    }
+
+    /// The id of the global
+    pub fn global(&self) -> GlobalId {
+        self.id.clone()
+    }
+
+    /// The data shared by the resources of the seat
+    ///
+    /// Two [`SeatData`] are equal if they belong to the same seat.
+    pub fn data(&self) -> &SeatData {
+        &self.data
+    }
+
+    /// The capabilities of the seat
+    pub fn capabilities(&self) -> wl_seat::Capability {
+        self.data.inner.lock().unwrap().capabilities
+    }
+
+    /// Change the capabilities of the seat, and advertise them to the clients
+    pub fn set_capabilities(&self, capabilities: wl_seat::Capability) {
+        let mut state = self.data.inner.lock().unwrap();
+        if state.capabilities == capabilities {
+            return;
+        }
+        state.capabilities = capabilities;
+        state.seats.retain(|seat| match seat.upgrade() {
+            Ok(seat) => {
+                seat.capabilities(capabilities);
+                true
+            }
+            Err(_) => false,
+        });
+    }
+
+    /// Set the keymap sent to the keyboards
+    ///
+    /// The keymap is sent to the keyboards which already exist and to the ones created afterwards. The
+    /// file must not be modified afterwards, as it is shared with all the clients.
+    pub fn set_keymap(&self, format: wl_keyboard::KeymapFormat, fd: OwnedFd, size: u32) {
+        let mut state = self.data.inner.lock().unwrap();
+        state.keyboards.retain(|keyboard| match keyboard.upgrade() {
+            Ok(keyboard) => {
+                keyboard.keymap(format, fd.as_fd(), size);
+                true
+            }
+            Err(_) => false,
+        });
+        state.keymap = Some(Keymap { format, fd, size });
+    }
+
+    /// Set the key repeat rate, in characters per second, and delay, in milliseconds
+    ///
+    /// The new values are sent to the keyboards supporting `wl_keyboard.repeat_info`. The default is
+    /// 25 characters per second after 600 milliseconds.
+    pub fn set_repeat_info(&self, rate: i32, delay: i32) {
+        let mut state = self.data.inner.lock().unwrap();
+        state.repeat_info = (rate, delay);
+        state.keyboards.retain(|keyboard| match keyboard.upgrade() {
+            Ok(keyboard) => {
+                if keyboard.version() >= 4 {
+                    keyboard.repeat_info(rate, delay);
+                }
+                true
+            }
+            Err(_) => false,
+        });
+    }
+
+    /// The cursor image requested by the client having the pointer focus
+    ///
+    /// Requests of other clients, and requests with the serial of an older `wl_pointer.enter`, are
+    /// ignored. This is reset when the pointer focus changes.
+    pub fn cursor_image(&self) -> Option<CursorImage> {
+        self.data.inner.lock().unwrap().cursor.clone()
+    }
+
+    /// The surface having the pointer focus
+    pub fn pointer_focus(&self) -> Option<wl_surface::WlSurface> {
+        self.data.inner.lock().unwrap().pointer_focus.as_ref().map(|(surface, _)| surface.clone())
+    }
+
+    /// The surface having the keyboard focus
+    pub fn keyboard_focus(&self) -> Option<wl_surface::WlSurface> {
+        self.data.inner.lock().unwrap().keyboard_focus.clone()
+    }
+
+    /// Move the pointer focus to a surface, or remove it
+    ///
+    /// The previously focused surface receives `wl_pointer.leave`, and the new one `wl_pointer.enter`.
+    /// Does nothing if the surface already has the focus.
+    pub fn set_pointer_focus(&self, focus: Option<(&wl_surface::WlSurface, f64, f64)>) {
+        let mut state = self.data.inner.lock().unwrap();
+        if state.pointer_focus.as_ref().map(|(surface, _)| surface)
+            == focus.map(|(surface, ..)| surface)
+        {
+            return;
+        }
+        if let Some((surface, _)) = state.pointer_focus.take() {
+            let serial = state.next_serial();
+            for pointer in objects_of(&mut state.pointers, &surface) {
+                pointer.leave(serial, &surface);
+                if pointer.version() >= 5 {
+                    pointer.frame();
+                }
+            }
+        }
+        state.cursor = None;
+        if let Some((surface, x, y)) = focus {
+            let serial = state.next_serial();
+            for pointer in objects_of(&mut state.pointers, surface) {
+                pointer.enter(serial, surface, x, y);
+                if pointer.version() >= 5 {
+                    pointer.frame();
+                }
+            }
+            state.pointer_focus = Some((surface.clone(), serial));
+        }
+    }
+
+    /// Send a pointer motion, in the coordinates of the focused surface
+    pub fn pointer_motion(&self, time: u32, x: f64, y: f64) {
+        self.with_focused_pointers(|pointer| pointer.motion(time, x, y));
+    }
+
+    /// Send a pointer button event to the focused surface, returns its serial
+    pub fn pointer_button(&self, time: u32, button: u32, state: wl_pointer::ButtonState) -> u32 {
+        let serial = self.data.inner.lock().unwrap().next_serial();
+        self.with_focused_pointers(|pointer| pointer.button(serial, time, button, state));
+        serial
+    }
+
+    /// Send a scroll event to the focused surface
+    pub fn pointer_axis(&self, time: u32, axis: wl_pointer::Axis, value: f64) {
+        self.with_focused_pointers(|pointer| pointer.axis(time, axis, value));
+    }
+
+    /// Mark the end of a group of pointer events, for the pointers supporting `wl_pointer.frame`
+    pub fn pointer_frame(&self) {
+        self.with_focused_pointers(|pointer| {
+            if pointer.version() >= 5 {
+                pointer.frame();
+            }
+        });
+    }
+
+    fn with_focused_pointers(&self, f: impl Fn(&wl_pointer::WlPointer)) {
+        let mut state = self.data.inner.lock().unwrap();
+        if let Some((surface, _)) = state.pointer_focus.clone() {
+            objects_of(&mut state.pointers, &surface).iter().for_each(f);
+        }
+    }
+
+    /// Move the keyboard focus to a surface, or remove it
+    ///
+    /// The previously focused surface receives `wl_keyboard.leave`, and the new one `wl_keyboard.enter`
+    /// with the currently pressed keys. Does nothing if the surface already has the focus.
+    pub fn set_keyboard_focus(&self, focus: Option<&wl_surface::WlSurface>, keys: &[u32]) {
+        let mut state = self.data.inner.lock().unwrap();
+        if state.keyboard_focus.as_ref() == focus {
+            return;
+        }
+        if let Some(surface) = state.keyboard_focus.take() {
+            let serial = state.next_serial();
+            for keyboard in objects_of(&mut state.keyboards, &surface) {
+                keyboard.leave(serial, &surface);
+            }
+        }
+        if let Some(surface) = focus {
+            let serial = state.next_serial();
+            let keys = keys.iter().flat_map(|key| key.to_ne_bytes()).collect::<Vec<u8>>();
+            for keyboard in objects_of(&mut state.keyboards, surface) {
+                keyboard.enter(serial, surface, keys.clone());
+            }
+            state.keyboard_focus = Some(surface.clone());
+        }
+    }
+
+    /// Send a key event to the focused surface, returns its serial
+    pub fn keyboard_key(&self, time: u32, key: u32, key_state: wl_keyboard::KeyState) -> u32 {
+        let mut state = self.data.inner.lock().unwrap();
+        let serial = state.next_serial();
+        if let Some(surface) = state.keyboard_focus.clone() {
+            for keyboard in objects_of(&mut state.keyboards, &surface) {
+                keyboard.key(serial, time, key, key_state);
+            }
+        }
+        serial
+    }
+
+    /// Send the state of the modifiers to the focused surface, returns the serial of the event
+    pub fn keyboard_modifiers(&self, depressed: u32, latched: u32, locked: u32, group: u32) -> u32 {
+        let mut state = self.data.inner.lock().unwrap();
+        let serial = state.next_serial();
+        if let Some(surface) = state.keyboard_focus.clone() {
+            for keyboard in objects_of(&mut state.keyboards, &surface) {
+                keyboard.modifiers(serial, depressed, latched, locked, group);
+            }
+        }
+        serial
+    }
+
+    /// Start a touch point on a surface, returns the serial of the event
+    ///
+    /// The following events of this touch point are sent to the same surface.
+    pub fn touch_down(
+        &self,
+        surface: &wl_surface::WlSurface,
+        time: u32,
+        id: i32,
+        x: f64,
+        y: f64,
+    ) -> u32 {
+        let mut state = self.data.inner.lock().unwrap();
+        let serial = state.next_serial();
+        for touch in objects_of(&mut state.touches, surface) {
+            touch.down(serial, time, surface, id, x, y);
+        }
+        state.touch_points.retain(|&(point, _)| point != id);
+        state.touch_points.push((id, surface.clone()));
+        serial
+    }
+
+    /// End a touch point, returns the serial of the event
+    pub fn touch_up(&self, time: u32, id: i32) -> u32 {
+        let mut state = self.data.inner.lock().unwrap();
+        let serial = state.next_serial();
+        if let Some(index) = state.touch_points.iter().position(|&(point, _)| point == id) {
+            let (_, surface) = state.touch_points.remove(index);
+            for touch in objects_of(&mut state.touches, &surface) {
+                touch.up(serial, time, id);
+            }
+        }
+        serial
+    }
+
+    /// Move a touch point, in the coordinates of the surface it started on
+    pub fn touch_motion(&self, time: u32, id: i32, x: f64, y: f64) {
+        let mut state = self.data.inner.lock().unwrap();
+        if let Some((_, surface)) =
+            state.touch_points.iter().find(|&&(point, _)| point == id).cloned()
+        {
+            for touch in objects_of(&mut state.touches, &surface) {
+                touch.motion(time, id, x, y);
+            }
+        }
+    }
+
+    /// Mark the end of a group of touch events, for the clients having touch points
+    pub fn touch_frame(&self) {
+        self.with_touched_clients(|touch| touch.frame());
+    }
+
+    /// Cancel all the touch points, the compositor took over the touch sequence
+    pub fn touch_cancel(&self) {
+        self.with_touched_clients(|touch| touch.cancel());
+        self.data.inner.lock().unwrap().touch_points.clear();
+    }
+
+    fn with_touched_clients(&self, f: impl Fn(&wl_touch::WlTouch)) {
+        let mut state = self.data.inner.lock().unwrap();
+        let mut touches: Vec<wl_touch::WlTouch> = Vec::new();
+        for (_, surface) in state.touch_points.clone() {
+            for touch in objects_of(&mut state.touches, &surface) {
+                if !touches.contains(&touch) {
+                    touches.push(touch);
+                }
+            }
+        }
+        touches.iter().for_each(f);
+    }
+}
+
+impl<D> GlobalDispatch<wl_seat::WlSeat, SeatData, D> for Seat
+where
+    D: GlobalDispatch<wl_seat::WlSeat, SeatData>
+        + Dispatch<wl_seat::WlSeat, SeatData>
+        + Dispatch<wl_pointer::WlPointer, SeatData>
+        + Dispatch<wl_keyboard::WlKeyboard, SeatData>
+        + Dispatch<wl_touch::WlTouch, SeatData>
+        + 'static,
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_seat::WlSeat>,
+        global_data: &SeatData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let seat = data_init.init(resource, global_data.clone());
+        let mut state = global_data.inner.lock().unwrap();
+        seat.capabilities(state.capabilities);
+        if seat.version() >= 2 {
+            seat.name(state.name.clone());
+        }
+        state.seats.push(seat.downgrade());
+    }
+}
+
+impl<D> Dispatch<wl_seat::WlSeat, SeatData, D> for Seat
+where
+    D: Dispatch<wl_seat::WlSeat, SeatData>
+        + Dispatch<wl_pointer::WlPointer, SeatData>
+        + Dispatch<wl_keyboard::WlKeyboard, SeatData>
+        + Dispatch<wl_touch::WlTouch, SeatData>
+        + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &wl_seat::WlSeat,
+        request: wl_seat::Request,
+        data: &SeatData,
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        // the objects of a missing capability are created inert, they never receive events
+        match request {
+            wl_seat::Request::GetPointer { id } => {
+                let pointer = data_init.init(id, data.clone());
+                data.inner.lock().unwrap().pointers.push(pointer.downgrade());
+            }
+            wl_seat::Request::GetKeyboard { id } => {
+                let keyboard = data_init.init(id, data.clone());
+                let mut state = data.inner.lock().unwrap();
+                if let Some(ref keymap) = state.keymap {
+                    keyboard.keymap(keymap.format, keymap.fd.as_fd(), keymap.size);
+                }
+                if keyboard.version() >= 4 {
+                    keyboard.repeat_info(state.repeat_info.0, state.repeat_info.1);
+                }
+                state.keyboards.push(keyboard.downgrade());
+            }
+            wl_seat::Request::GetTouch { id } => {
+                let touch = data_init.init(id, data.clone());
+                data.inner.lock().unwrap().touches.push(touch.downgrade());
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(_: &mut D, _: ClientId, seat: &wl_seat::WlSeat, data: &SeatData) {
+        let id = seat.id();
+        data.inner.lock().unwrap().seats.retain(|seat| seat.id() != id);
+    }
+}
+
+impl<D> Dispatch<wl_pointer::WlPointer, SeatData, D> for Seat
+where
+    D: Dispatch<wl_pointer::WlPointer, SeatData> + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        pointer: &wl_pointer::WlPointer,
+        request: wl_pointer::Request,
+        data: &SeatData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        if let wl_pointer::Request::SetCursor { serial, surface, hotspot_x, hotspot_y } = request {
+            let mut state = data.inner.lock().unwrap();
+            let focused = match state.pointer_focus {
+                Some((ref focus, enter_serial)) => {
+                    enter_serial == serial && client_id(focus) == client_id(pointer)
+                }
+                None => false,
+            };
+            if focused {
+                state.cursor = Some(CursorImage { surface, hotspot: (hotspot_x, hotspot_y) });
+            }
+        }
+    }
+
+    fn destroyed(_: &mut D, _: ClientId, pointer: &wl_pointer::WlPointer, data: &SeatData) {
+        let id = pointer.id();
+        data.inner.lock().unwrap().pointers.retain(|pointer| pointer.id() != id);
+    }
+}
+
+impl<D> Dispatch<wl_keyboard::WlKeyboard, SeatData, D> for Seat
+where
+    D: Dispatch<wl_keyboard::WlKeyboard, SeatData> + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &wl_keyboard::WlKeyboard,
+        _: wl_keyboard::Request,
+        _: &SeatData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        // wl_keyboard.release is the only request, the resource is destroyed by the backend
+    }
+
+    fn destroyed(_: &mut D, _: ClientId, keyboard: &wl_keyboard::WlKeyboard, data: &SeatData) {
+        let id = keyboard.id();
+        data.inner.lock().unwrap().keyboards.retain(|keyboard| keyboard.id() != id);
+    }
+}
+
+impl<D> Dispatch<wl_touch::WlTouch, SeatData, D> for Seat
+where
+    D: Dispatch<wl_touch::WlTouch, SeatData> + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &wl_touch::WlTouch,
+        _: wl_touch::Request,
+        _: &SeatData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        // wl_touch.release is the only request, the resource is destroyed by the backend
+    }
+
+    fn destroyed(_: &mut D, _: ClientId, touch: &wl_touch::WlTouch, data: &SeatData) {
+        let id = touch.id();
+        data.inner.lock().unwrap().touches.retain(|touch| touch.id() != id);
+    }
+}
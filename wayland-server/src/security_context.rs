@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use wayland_backend::server::{ClientData, ClientId, DisconnectReason};
+
+/// The security context attached to the clients of a sandbox
+///
+/// Sandbox engines like Flatpak create a listening socket for each sandboxed app, and register
+/// it along with this metadata using the `wp_security_context_v1` protocol. The compositor then
+/// knows which clients come from inside the sandbox, and can restrict the globals they see. See
+/// [`DisplayHandle::add_sandboxed_listener()`](crate::DisplayHandle::add_sandboxed_listener) and
+/// [`Client::security_context()`](crate::Client::security_context).
+///
+/// None of these fields are authenticated by the compositor, they are only as trustworthy as the
+/// client which registered the listener.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SecurityContext {
+    /// The reverse-DNS name of the sandbox engine, like `org.flatpak`
+    pub sandbox_engine: Option<String>,
+    /// The id of the application in the sandbox engine
+    pub app_id: Option<String>,
+    /// The id of the running instance of the application
+    pub instance_id: Option<String>,
+}
+
+/// The data of a client accepted on a sandboxed listener, wrapping the data given by the compositor
+pub(crate) struct SandboxedClientData {
+    pub(crate) context: Arc<SecurityContext>,
+    pub(crate) data: Arc<dyn ClientData>,
+}
+
+impl ClientData for SandboxedClientData {
+    fn initialized(&self, client_id: ClientId) {
+        self.data.initialized(client_id)
+    }
+
+    fn disconnected(&self, client_id: ClientId, reason: DisconnectReason) {
+        self.data.disconnected(client_id, reason)
+    }
+
+    fn debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.data.debug(f)
+    }
+}
@@ -0,0 +1,142 @@
+//! Serialization of the messages with `serde`
+//!
+//! With the `serde` cargo feature, the `Request` and `Event` enums of the core protocol implement
+//! [`serde::Serialize`], for example to record the messages of a compositor in a structured log.
+//! The enums of other protocols implement it when they are generated with the `serde` argument of
+//! [`generate_server_code!`](wayland_scanner::generate_server_code), which relies on this module.
+//!
+//! The messages only implement `Serialize`: their objects and file descriptors only make sense
+//! within the connection they come from, and cannot be rebuilt from a serialized form. Objects are
+//! serialized as their interface and protocol id, file descriptors as a `Fd` placeholder.
+//!
+//! The enums and bitflags of the protocols, which are plain values, implement both `Serialize` and
+//! [`serde::Deserialize`], as their `u32` value. The enums shared through `generate_enums!` do not.
+
+use std::{
+    convert::TryFrom,
+    os::unix::io::{BorrowedFd, OwnedFd},
+};
+
+pub use serde;
+use serde::{
+    de::Error as _, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{backend::protocol::Interface, backend::ObjectId, New, Resource, WEnum};
+
+/// An argument of a message that can be serialized
+///
+/// This trait is implemented for the types of the fields of the generated message enums.
+pub trait SerializeArg {
+    /// Serialize this argument
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+/// Wrapper implementing [`Serialize`] for an argument of a message
+///
+/// This is only meant to be used by the code generated by `wayland-scanner`.
+#[derive(Debug)]
+pub struct Arg<'a, T: ?Sized>(pub &'a T);
+
+impl<'a, T: SerializeArg + ?Sized> Serialize for Arg<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_arg(serializer)
+    }
+}
+
+/// Deserialize an enum of a protocol from its `u32` value
+///
+/// This is only meant to be used by the code generated by `wayland-scanner`.
+pub fn deserialize_enum<'de, D, T>(deserializer: D, name: &'static str) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u32>,
+{
+    let value = u32::deserialize(deserializer)?;
+    T::try_from(value).map_err(|_| {
+        D::Error::custom(format_args!("invalid value {} for the enum {}", value, name))
+    })
+}
+
+macro_rules! serialize_arg_as_serialize {
+    ($($ty:ty),*) => {
+        $(
+            impl SerializeArg for $ty {
+                fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    self.serialize(serializer)
+                }
+            }
+        )*
+    };
+}
+
+serialize_arg_as_serialize!(u32, i32, f64, String, Vec<u8>);
+
+impl SerializeArg for OwnedFd {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_struct("Fd")
+    }
+}
+
+impl<'a> SerializeArg for BorrowedFd<'a> {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_struct("Fd")
+    }
+}
+
+impl SerializeArg for ObjectId {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Object", 2)?;
+        state.serialize_field("interface", self.interface().name)?;
+        state.serialize_field("id", &self.protocol_id())?;
+        state.end()
+    }
+}
+
+impl<I: Resource> SerializeArg for I {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.id().serialize_arg(serializer)
+    }
+}
+
+impl<I: Resource> SerializeArg for New<I> {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.id.serialize_arg(serializer)
+    }
+}
+
+impl<T: Into<u32> + Copy> SerializeArg for WEnum<T> {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            WEnum::Value(value) => serializer.serialize_u32(value.into()),
+            WEnum::Unknown(value) => serializer.serialize_u32(value),
+        }
+    }
+}
+
+impl<T: SerializeArg> SerializeArg for Option<T> {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Some(ref value) => serializer.serialize_some(&Arg(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl SerializeArg for &'static Interface {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+impl<A: SerializeArg, B: SerializeArg> SerializeArg for (A, B) {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (Arg(&self.0), Arg(&self.1)).serialize(serializer)
+    }
+}
+
+impl<A: SerializeArg, B: SerializeArg, C: SerializeArg> SerializeArg for (A, B, C) {
+    fn serialize_arg<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (Arg(&self.0), Arg(&self.1), Arg(&self.2)).serialize(serializer)
+    }
+}
@@ -0,0 +1,680 @@
+//! Access to the shared memory buffers of the clients
+//!
+//! Clients share the contents of their software-rendered buffers through a memory file they send with
+//! `wl_shm.create_pool`. Mapping this file, and reading from it, is unsafe in two ways: the client can
+//! resize the pool, and it can truncate the file behind the back of the compositor, in which case reading
+//! the mapped memory raises a `SIGBUS` signal that kills the compositor.
+//!
+//! This module wraps these operations, like libwayland's `wl_shm_buffer_begin_access`. Use a [`ShmPool`]
+//! as the user data of your `wl_shm_pool` resources and a [`ShmBuffer`] as the user data of the
+//! `wl_buffer`s created from them, then read the contents of a buffer with [`with_buffer_contents()`].
+//!
+//! During such an access, a `SIGBUS` handler replaces the memory of a truncated pool with zeroed pages
+//! and the access returns [`BufferAccessError::BadMap`], at which point the compositor should post the
+//! `wl_shm` `invalid_fd` error to the client. The handler is installed the first time a buffer is
+//! accessed, and forwards the signals raised outside of buffer accesses to the previous handler.
+//!
+//! The contents are given as a raw pointer rather than a slice: the client shares the memory and can
+//! write to it at any time, and the `SIGBUS` handler can replace it during the access, which would
+//! break the guarantees of a `&[u8]`. Copy the contents out of the pool, for example with
+//! [`std::ptr::copy_nonoverlapping`], rather than building references to them.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use wayland_server::{
+//!     protocol::{wl_buffer, wl_shm, wl_shm_pool},
+//!     shm::{with_buffer_contents, ShmBuffer, ShmPool},
+//!     Client, DataInit, Dispatch, DisplayHandle, Resource,
+//! };
+//! # struct State;
+//! impl Dispatch<wl_shm::WlShm, ()> for State {
+//!     fn request(
+//!         _: &mut State,
+//!         _: &Client,
+//!         shm: &wl_shm::WlShm,
+//!         request: wl_shm::Request,
+//!         _: &(),
+//!         _: &DisplayHandle,
+//!         data_init: &mut DataInit<'_, State>,
+//!     ) {
+//!         if let wl_shm::Request::CreatePool { id, fd, size } = request {
+//!             match ShmPool::new(fd, size) {
+//!                 Ok(pool) => {
+//!                     data_init.init(id, pool);
+//!                 }
+//!                 Err(_) => shm.post_error(wl_shm::Error::InvalidFd, "could not map the pool"),
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! impl Dispatch<wl_shm_pool::WlShmPool, ShmPool> for State {
+//!     fn request(
+//!         _: &mut State,
+//!         _: &Client,
+//!         resource: &wl_shm_pool::WlShmPool,
+//!         request: wl_shm_pool::Request,
+//!         pool: &ShmPool,
+//!         _: &DisplayHandle,
+//!         data_init: &mut DataInit<'_, State>,
+//!     ) {
+//!         match request {
+//!             wl_shm_pool::Request::CreateBuffer { id, offset, width, height, stride, format } => {
+//!                 match ShmBuffer::new(pool, offset, width, height, stride, format) {
+//!                     Ok(buffer) => {
+//!                         data_init.init(id, buffer);
+//!                     }
+//!                     Err(error) => resource.post_error(error, "invalid buffer"),
+//!                 }
+//!             }
+//!             wl_shm_pool::Request::Resize { size } => {
+//!                 if pool.resize(size).is_err() {
+//!                     resource.post_error(wl_shm::Error::InvalidFd, "could not map the pool");
+//!                 }
+//!             }
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//! # impl Dispatch<wl_buffer::WlBuffer, ShmBuffer> for State {
+//! #     fn request(
+//! #         _: &mut State,
+//! #         _: &Client,
+//! #         _: &wl_buffer::WlBuffer,
+//! #         _: wl_buffer::Request,
+//! #         _: &ShmBuffer,
+//! #         _: &DisplayHandle,
+//! #         _: &mut DataInit<'_, State>,
+//! #     ) {}
+//! # }
+//!
+//! // later, when the buffer is committed to a surface
+//! fn upload(buffer: &wl_buffer::WlBuffer) {
+//!     let result = with_buffer_contents(buffer, |ptr, len, info| {
+//!         let mut contents = vec![0u8; len];
+//!         // the client can modify the memory meanwhile, which at worst tears the image
+//!         unsafe { std::ptr::copy_nonoverlapping(ptr, contents.as_mut_ptr(), len) };
+//!         /* upload the `info.height` rows of `info.stride` bytes of the image */
+//!     });
+//! }
+//! ```
+
+use std::{
+    cell::Cell,
+    fmt, io,
+    num::NonZeroUsize,
+    os::unix::io::{AsRawFd, OwnedFd},
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Arc, Mutex, Once,
+    },
+};
+
+use nix::{
+    libc,
+    sys::{
+        mman,
+        signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    },
+};
+
+use wayland_backend::server::{ClientId, GlobalId};
+
+use crate::{
+    protocol::{wl_buffer, wl_shm, wl_shm_pool},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum, Weak,
+};
+
+/// An error when accessing the contents of a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferAccessError {
+    /// The buffer was not created from a [`ShmPool`]
+    NotShmBuffer,
+    /// The client truncated the file of the pool, the access was aborted
+    BadMap,
+}
+
+impl std::error::Error for BufferAccessError {}
+
+impl fmt::Display for BufferAccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BufferAccessError::NotShmBuffer => {
+                write!(f, "The buffer is not a shared memory buffer")
+            }
+            BufferAccessError::BadMap => {
+                write!(f, "The shared memory file of the buffer was truncated by the client")
+            }
+        }
+    }
+}
+
+/// The memory file of a `wl_shm_pool`, mapped in the memory of the compositor
+///
+/// This is the user data to give to the `wl_shm_pool` resources. It is a handle to a reference-counted
+/// mapping that stays alive as long as buffers created from it do, even after the `wl_shm_pool` was
+/// destroyed.
+#[derive(Debug, Clone)]
+pub struct ShmPool {
+    // the current mapping, the previous ones stay alive until the accesses to them end
+    map: Arc<Mutex<Arc<MemMap>>>,
+    // the formats of the `ShmGlobal` the pool was created from
+    formats: Option<Arc<Mutex<ShmFormats>>>,
+}
+
+impl ShmPool {
+    /// Map the memory file sent by a client with `wl_shm.create_pool`
+    ///
+    /// Fails if the size is not positive or if the file cannot be mapped, the compositor should then
+    /// post the `invalid_fd` error of `wl_shm`.
+    pub fn new(fd: OwnedFd, size: i32) -> io::Result<ShmPool> {
+        let map = MemMap::new(Arc::new(fd), size)?;
+        Ok(ShmPool { map: Arc::new(Mutex::new(Arc::new(map))), formats: None })
+    }
+
+    /// Current size of the pool, in bytes
+    pub fn size(&self) -> usize {
+        self.map.lock().unwrap().size
+    }
+
+    /// Remap the pool after a `wl_shm_pool.resize` request
+    ///
+    /// A pool cannot shrink, sizes smaller than the current one are ignored. Fails if the file cannot be
+    /// mapped with the new size, in which case the previous mapping is kept.
+    pub fn resize(&self, size: i32) -> io::Result<()> {
+        let mut map = self.map.lock().unwrap();
+        if size as usize > map.size {
+            *map = Arc::new(MemMap::new(map.fd.clone(), size)?);
+        }
+        Ok(())
+    }
+}
+
+/// Description of the image stored in a [`ShmBuffer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferInfo {
+    /// Offset of the image in its pool, in bytes
+    pub offset: i32,
+    /// Width of the image, in pixels
+    pub width: i32,
+    /// Height of the image, in pixels
+    pub height: i32,
+    /// Number of bytes between the starts of two consecutive rows
+    pub stride: i32,
+    /// Pixel format of the image, as sent by the client
+    pub format: WEnum<wl_shm::Format>,
+}
+
+/// A `wl_buffer` created from a [`ShmPool`]
+///
+/// This is the user data to give to the `wl_buffer` resources created by `wl_shm_pool.create_buffer`.
+#[derive(Debug, Clone)]
+pub struct ShmBuffer {
+    pool: ShmPool,
+    info: BufferInfo,
+}
+
+impl ShmBuffer {
+    /// Check the parameters of a `wl_shm_pool.create_buffer` request
+    ///
+    /// Returns the error the compositor should post on the `wl_shm_pool` if the buffer does not fit in
+    /// the pool. The format is not checked, compare it with the formats you advertised.
+    pub fn new(
+        pool: &ShmPool,
+        offset: i32,
+        width: i32,
+        height: i32,
+        stride: i32,
+        format: WEnum<wl_shm::Format>,
+    ) -> Result<ShmBuffer, wl_shm::Error> {
+        let fits = (stride as i64) * (height as i64) + (offset as i64) <= pool.size() as i64;
+        if offset < 0 || width <= 0 || height <= 0 || stride < width || !fits {
+            return Err(wl_shm::Error::InvalidStride);
+        }
+        Ok(ShmBuffer {
+            pool: pool.clone(),
+            info: BufferInfo { offset, width, height, stride, format },
+        })
+    }
+
+    /// Description of the image of this buffer
+    pub fn info(&self) -> BufferInfo {
+        self.info
+    }
+
+    /// Access the contents of this buffer
+    ///
+    /// The closure receives a pointer to the `stride * height` bytes of the image, and their length.
+    /// The pointer is valid for reads of this length until the closure returns. The memory is shared
+    /// with the client, which can write to it at any time: the closure must not create references to
+    /// it, but copy it out with raw pointer reads. If the client truncates the file of the pool during
+    /// the access, the remaining contents read as zeroes and [`BufferAccessError::BadMap`] is returned
+    /// instead of the result of the closure.
+    ///
+    /// The pool is not locked during the access: the closure can access other buffers, including
+    /// buffers of the same pool, and the pool can be resized meanwhile.
+    pub fn with_contents<T, F>(&self, f: F) -> Result<T, BufferAccessError>
+    where
+        F: FnOnce(*const u8, usize, BufferInfo) -> T,
+    {
+        place_sigbus_handler();
+        // the mapping stays alive until the end of the access, even if the pool is resized
+        let map = self.pool.map.lock().unwrap().clone();
+        let start = self.info.offset as usize;
+        let len = (self.info.stride as usize) * (self.info.height as usize);
+        // `ShmBuffer::new` checked that the image fits in the pool, which only grows
+        let ptr = unsafe { map.ptr.add(start) } as *const u8;
+
+        let access = Access::start(map);
+        let result = f(ptr, len, self.info);
+
+        if access.faulted.get() {
+            Err(BufferAccessError::BadMap)
+        } else {
+            Ok(result)
+        }
+    }
+}
+
+/// A `wl_shm` global whose list of supported formats can grow at runtime
+///
+/// This global handles the `wl_shm`, `wl_shm_pool` and `wl_buffer` requests on its own, using
+/// [`ShmPool`] and [`ShmBuffer`] as the user data of the pools and buffers, and rejects the buffers
+/// of unsupported formats. Delegate these interfaces to it from your state:
+///
+/// ```
+/// use wayland_server::{
+///     delegate_dispatch, delegate_global_dispatch,
+///     protocol::{wl_buffer::WlBuffer, wl_shm::WlShm, wl_shm_pool::WlShmPool},
+///     shm::{ShmBuffer, ShmGlobal, ShmGlobalData, ShmPool},
+/// };
+///
+/// struct State;
+///
+/// delegate_global_dispatch!(State: [WlShm: ShmGlobalData] => ShmGlobal);
+/// delegate_dispatch!(State: [WlShm: ShmGlobalData] => ShmGlobal);
+/// delegate_dispatch!(State: [WlShmPool: ShmPool] => ShmGlobal);
+/// delegate_dispatch!(State: [WlBuffer: ShmBuffer] => ShmGlobal);
+/// ```
+#[derive(Debug)]
+pub struct ShmGlobal {
+    id: GlobalId,
+    data: ShmGlobalData,
+}
+
+/// The global data of a [`ShmGlobal`]
+#[derive(Debug, Clone)]
+pub struct ShmGlobalData {
+    formats: Arc<Mutex<ShmFormats>>,
+}
+
+#[derive(Debug)]
+struct ShmFormats {
+    formats: Vec<wl_shm::Format>,
+    resources: Vec<Weak<wl_shm::WlShm>>,
+}
+
+impl ShmGlobal {
+    /// Create a `wl_shm` global supporting the given formats
+    ///
+    /// The `argb8888` and `xrgb8888` formats, which all compositors must support, are always included.
+    pub fn new<D>(
+        handle: &DisplayHandle,
+        formats: impl IntoIterator<Item = wl_shm::Format>,
+    ) -> ShmGlobal
+    where
+        D: GlobalDispatch<wl_shm::WlShm, ShmGlobalData>
+            + Dispatch<wl_shm::WlShm, ShmGlobalData>
+            + Dispatch<wl_shm_pool::WlShmPool, ShmPool>
+            + Dispatch<wl_buffer::WlBuffer, ShmBuffer>
+            + 'static,
+    {
+        let mut list = vec![wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888];
+        for format in formats {
+            if !list.contains(&format) {
+                list.push(format);
+            }
+        }
+        let data = ShmGlobalData {
+            formats: Arc::new(Mutex::new(ShmFormats { formats: list, resources: Vec::new() })),
+        };
+        let id = handle.create_global::<D, wl_shm::WlShm, _>(1, data.clone());
+        ShmGlobal { id, data }
+    }
+
+    /// The id of the global
+    pub fn global(&self) -> GlobalId {
+        self.id.clone()
+    }
+
+    /// The formats currently supported
+    pub fn formats(&self) -> Vec<wl_shm::Format> {
+        self.data.formats.lock().unwrap().formats.clone()
+    }
+
+    /// Add a supported format
+    ///
+    /// The format is advertised to the clients which already bound the global, and to the ones binding
+    /// it later. Returns `false` if the format was already supported.
+    pub fn add_format(&self, format: wl_shm::Format) -> bool {
+        let mut formats = self.data.formats.lock().unwrap();
+        if formats.formats.contains(&format) {
+            return false;
+        }
+        formats.formats.push(format);
+        formats.resources.retain(|resource| match resource.upgrade() {
+            Ok(shm) => {
+                shm.format(format);
+                true
+            }
+            Err(_) => false,
+        });
+        true
+    }
+}
+
+impl<D> GlobalDispatch<wl_shm::WlShm, ShmGlobalData, D> for ShmGlobal
+where
+    D: GlobalDispatch<wl_shm::WlShm, ShmGlobalData>
+        + Dispatch<wl_shm::WlShm, ShmGlobalData>
+        + Dispatch<wl_shm_pool::WlShmPool, ShmPool>
+        + Dispatch<wl_buffer::WlBuffer, ShmBuffer>
+        + 'static,
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_shm::WlShm>,
+        global_data: &ShmGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let shm = data_init.init(resource, global_data.clone());
+        let mut formats = global_data.formats.lock().unwrap();
+        for &format in &formats.formats {
+            shm.format(format);
+        }
+        formats.resources.push(shm.downgrade());
+    }
+}
+
+impl<D> Dispatch<wl_shm::WlShm, ShmGlobalData, D> for ShmGlobal
+where
+    D: Dispatch<wl_shm::WlShm, ShmGlobalData> + Dispatch<wl_shm_pool::WlShmPool, ShmPool> + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        shm: &wl_shm::WlShm,
+        request: wl_shm::Request,
+        data: &ShmGlobalData,
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let wl_shm::Request::CreatePool { id, fd, size } = request;
+        if size <= 0 {
+            shm.post_error(wl_shm::Error::InvalidStride, format!("invalid size ({})", size));
+            return;
+        }
+        match ShmPool::new(fd, size) {
+            Ok(mut pool) => {
+                pool.formats = Some(data.formats.clone());
+                data_init.init(id, pool);
+            }
+            Err(e) => shm.post_error(wl_shm::Error::InvalidFd, format!("failed mmap of fd: {}", e)),
+        }
+    }
+
+    fn destroyed(_: &mut D, _: ClientId, shm: &wl_shm::WlShm, data: &ShmGlobalData) {
+        let id = shm.id();
+        data.formats.lock().unwrap().resources.retain(|resource| resource.id() != id);
+    }
+}
+
+impl<D> Dispatch<wl_shm_pool::WlShmPool, ShmPool, D> for ShmGlobal
+where
+    D: Dispatch<wl_shm_pool::WlShmPool, ShmPool>
+        + Dispatch<wl_buffer::WlBuffer, ShmBuffer>
+        + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        resource: &wl_shm_pool::WlShmPool,
+        request: wl_shm_pool::Request,
+        pool: &ShmPool,
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wl_shm_pool::Request::CreateBuffer { id, offset, width, height, stride, format } => {
+                let supported = match (format, &pool.formats) {
+                    (WEnum::Value(format), Some(formats)) => {
+                        formats.lock().unwrap().formats.contains(&format)
+                    }
+                    (WEnum::Value(_), None) => true,
+                    (WEnum::Unknown(_), _) => false,
+                };
+                if !supported {
+                    resource.post_error(
+                        wl_shm::Error::InvalidFormat,
+                        format!("invalid format 0x{:x}", u32::from(format)),
+                    );
+                    return;
+                }
+                match ShmBuffer::new(pool, offset, width, height, stride, format) {
+                    Ok(buffer) => {
+                        data_init.init(id, buffer);
+                    }
+                    Err(error) => resource.post_error(
+                        error,
+                        format!(
+                            "invalid width, height or stride ({}x{}, {})",
+                            width, height, stride
+                        ),
+                    ),
+                }
+            }
+            wl_shm_pool::Request::Resize { size } => {
+                if (size as i64) < pool.size() as i64 {
+                    resource.post_error(wl_shm::Error::InvalidStride, "shrinking pool invalid");
+                } else if let Err(e) = pool.resize(size) {
+                    resource.post_error(wl_shm::Error::InvalidFd, format!("failed mremap: {}", e));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<wl_buffer::WlBuffer, ShmBuffer, D> for ShmGlobal
+where
+    D: Dispatch<wl_buffer::WlBuffer, ShmBuffer> + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &wl_buffer::WlBuffer,
+        _: wl_buffer::Request,
+        _: &ShmBuffer,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        // wl_buffer.destroy is the only request, the resource is destroyed by the backend
+    }
+}
+
+/// Access the contents of a buffer whose user data is a [`ShmBuffer`]
+///
+/// See [`ShmBuffer::with_contents()`].
+pub fn with_buffer_contents<T, F>(
+    buffer: &wl_buffer::WlBuffer,
+    f: F,
+) -> Result<T, BufferAccessError>
+where
+    F: FnOnce(*const u8, usize, BufferInfo) -> T,
+{
+    buffer.data::<ShmBuffer>().ok_or(BufferAccessError::NotShmBuffer)?.with_contents(f)
+}
+
+#[derive(Debug)]
+struct MemMap {
+    fd: Arc<OwnedFd>,
+    ptr: *mut u8,
+    size: usize,
+}
+
+// The mapping is read-only, it is only replaced by zeroed memory from the SIGBUS handler
+unsafe impl Send for MemMap {}
+unsafe impl Sync for MemMap {}
+
+impl MemMap {
+    fn new(fd: Arc<OwnedFd>, size: i32) -> io::Result<MemMap> {
+        let ptr = map(&fd, size)?;
+        Ok(MemMap { fd, ptr, size: size as usize })
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        (self.ptr as usize) <= addr && addr < (self.ptr as usize) + self.size
+    }
+
+    // Replace the mapping with zeroed memory, this is called from the SIGBUS handler
+    fn nullify(&self) -> bool {
+        let ret = unsafe {
+            libc::mmap(
+                self.ptr.cast(),
+                self.size,
+                libc::PROT_READ,
+                libc::MAP_ANONYMOUS | libc::MAP_PRIVATE | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        ret != libc::MAP_FAILED
+    }
+
+    fn unmap(&self) {
+        let _ = unsafe { mman::munmap(self.ptr.cast(), self.size) };
+    }
+}
+
+impl Drop for MemMap {
+    fn drop(&mut self) {
+        self.unmap();
+    }
+}
+
+fn map(fd: &OwnedFd, size: i32) -> io::Result<*mut u8> {
+    let size = NonZeroUsize::new(size.max(0) as usize)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let ptr = unsafe {
+        mman::mmap(
+            None,
+            size,
+            mman::ProtFlags::PROT_READ,
+            mman::MapFlags::MAP_SHARED,
+            fd.as_raw_fd(),
+            0,
+        )?
+    };
+    Ok(ptr.cast())
+}
+
+// An access to a mapping by this thread, with the accesses it is nested in
+struct Access {
+    map: Arc<MemMap>,
+    faulted: Cell<bool>,
+    outer: *const Access,
+}
+
+impl Access {
+    fn start(map: Arc<MemMap>) -> Box<Access> {
+        let outer = SIGBUS_GUARD.with(|guard| guard.get());
+        let access = Box::new(Access { map, faulted: Cell::new(false), outer });
+        SIGBUS_GUARD.with(|guard| guard.set(&*access));
+        access
+    }
+}
+
+impl Drop for Access {
+    fn drop(&mut self) {
+        SIGBUS_GUARD.with(|guard| guard.set(self.outer));
+    }
+}
+
+thread_local! {
+    // The innermost access of this thread
+    static SIGBUS_GUARD: Cell<*const Access> = const { Cell::new(ptr::null()) };
+}
+
+static SIGBUS_INIT: Once = Once::new();
+static OLD_SIGBUS_HANDLER: AtomicPtr<SigAction> = AtomicPtr::new(ptr::null_mut());
+
+fn place_sigbus_handler() {
+    SIGBUS_INIT.call_once(|| {
+        let action = SigAction::new(
+            SigHandler::SigAction(sigbus_handler),
+            SaFlags::SA_NODEFER,
+            SigSet::empty(),
+        );
+        match unsafe { signal::sigaction(Signal::SIGBUS, &action) } {
+            Ok(old) => {
+                OLD_SIGBUS_HANDLER.store(Box::into_raw(Box::new(old)), Ordering::Release);
+            }
+            Err(e) => {
+                crate::log_error!("Failed to install the SIGBUS handler of shm buffers: {}", e);
+            }
+        }
+    });
+}
+
+// Forward a signal raised outside of buffer accesses to the previous handler
+//
+// A previous handler is called directly, so that this one stays installed for the later accesses.
+// Without one, the default action is restored and the faulting instruction raises the signal again,
+// which terminates the process.
+fn reraise_sigbus(signum: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+    let old = OLD_SIGBUS_HANDLER.load(Ordering::Acquire);
+    match unsafe { old.as_ref() }.map(SigAction::handler) {
+        Some(SigHandler::Handler(handler)) => handler(signum),
+        Some(SigHandler::SigAction(handler)) => handler(signum, info, context),
+        Some(SigHandler::SigDfl) | Some(SigHandler::SigIgn) | None => {
+            let action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+            let _ = unsafe { signal::sigaction(Signal::SIGBUS, &action) };
+        }
+    }
+}
+
+extern "C" fn sigbus_handler(
+    signum: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let addr = unsafe { siginfo_si_addr(info) } as usize;
+    let handled = SIGBUS_GUARD.with(|guard| {
+        let mut access = guard.get();
+        while let Some(current) = unsafe { access.as_ref() } {
+            if current.map.contains(addr) {
+                current.faulted.set(true);
+                return current.map.nullify();
+            }
+            access = current.outer;
+        }
+        false
+    });
+    if !handled {
+        reraise_sigbus(signum, info, context);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn siginfo_si_addr(info: *mut libc::siginfo_t) -> *mut libc::c_void {
+    unsafe { (*info).si_addr() }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+unsafe fn siginfo_si_addr(info: *mut libc::siginfo_t) -> *mut libc::c_void {
+    unsafe { (*info).si_addr }
+}
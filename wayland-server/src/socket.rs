@@ -17,14 +17,29 @@ use std::{
 
 use nix::fcntl::{flock, FlockArg};
 
+use wayland_backend::server::Credentials;
+
+/// A filter deciding which peers may connect to a [`ListeningSocket`]
+type AcceptFilter = Box<dyn Fn(&Credentials) -> bool + Send + Sync>;
+
 /// An utility representing a unix socket on which your compositor is listening for new clients
-#[derive(Debug)]
 pub struct ListeningSocket {
     listener: UnixListener,
     _lock: File,
     socket_path: PathBuf,
     lock_path: PathBuf,
     socket_name: Option<OsString>,
+    filter: Option<AcceptFilter>,
+}
+
+impl std::fmt::Debug for ListeningSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ListeningSocket")
+            .field("socket_path", &self.socket_path)
+            .field("socket_name", &self.socket_name)
+            .field("filtered", &self.filter.is_some())
+            .finish_non_exhaustive()
+    }
 }
 
 impl ListeningSocket {
@@ -136,21 +151,43 @@ impl ListeningSocket {
 
         listener.set_nonblocking(true).map_err(BindError::Io)?;
 
-        Ok(Self { listener, _lock, socket_path, lock_path, socket_name: None })
+        Ok(Self { listener, _lock, socket_path, lock_path, socket_name: None, filter: None })
     }
 
     /// Try to accept a new connection to the listening socket
     ///
-    /// This method will never block, and return `Ok(None)` if no new connection is available.
+    /// This method will never block, and return `Ok(None)` if no new connection is available. The
+    /// connections refused by the [filter](ListeningSocket::set_filter) are closed and skipped.
     #[must_use = "the client must be initialized by the display using `Display::insert_client` or else the client will hang forever"]
     pub fn accept(&self) -> io::Result<Option<UnixStream>> {
-        match self.listener.accept() {
-            Ok((stream, _)) => Ok(Some(stream)),
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
-            Err(e) => Err(e),
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            let accepted = match self.filter {
+                // the peer may have closed the connection already
+                Some(ref filter) => {
+                    Credentials::from_peer(stream.as_fd()).map_or(false, |c| filter(&c))
+                }
+                None => true,
+            };
+            if accepted {
+                return Ok(Some(stream));
+            }
         }
     }
 
+    /// Only accept the connections of the peers for which `filter` returns `true`
+    ///
+    /// The filter is invoked with the credentials of the peer when its connection is accepted, so that
+    /// unwanted clients are disconnected before any protocol state is created for them, for example to
+    /// restrict the socket to a single user. The credentials are only available on linux.
+    pub fn set_filter(&mut self, filter: impl Fn(&Credentials) -> bool + Send + Sync + 'static) {
+        self.filter = Some(Box::new(filter));
+    }
+
     /// Returns the name of the listening socket.
     ///
     /// Will only be [`Some`] if that socket was created with [`bind`](ListeningSocket::bind) or
@@ -0,0 +1,90 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+};
+
+/// A map of typed values which can be modified after the creation of a resource
+///
+/// The user data of a resource is given once, when initializing it with [`DataInit`](crate::DataInit),
+/// and is only accessible by shared reference afterwards. Using a [`UserDataMap`] as the user data allows to
+/// insert, replace and remove values of any type during the lifetime of the resource, for example for the
+/// per-role state of a `wl_surface`. It holds at most one value of each type, and is retrieved from the
+/// resource with [`Resource::user_data_map()`](crate::Resource::user_data_map).
+///
+/// The map is protected by a mutex, locked for the duration of each method call: the closures given to
+/// [`with()`](UserDataMap::with) and [`with_mut()`](UserDataMap::with_mut) must not access the same map, or
+/// they will deadlock.
+#[derive(Default)]
+pub struct UserDataMap {
+    values: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl UserDataMap {
+    /// Create an empty map
+    pub fn new() -> UserDataMap {
+        UserDataMap::default()
+    }
+
+    /// Insert a value, returning the value of the same type it replaces, if any
+    pub fn insert<T: Send + 'static>(&self, value: T) -> Option<T> {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .map(|previous| *previous.downcast::<T>().unwrap())
+    }
+
+    /// Remove the value of a type, returning it
+    pub fn remove<T: Send + 'static>(&self) -> Option<T> {
+        self.values
+            .lock()
+            .unwrap()
+            .remove(&TypeId::of::<T>())
+            .map(|value| *value.downcast::<T>().unwrap())
+    }
+
+    /// Whether the map holds a value of this type
+    pub fn contains<T: Send + 'static>(&self) -> bool {
+        self.values.lock().unwrap().contains_key(&TypeId::of::<T>())
+    }
+
+    /// A copy of the value of a type
+    pub fn get<T: Clone + Send + 'static>(&self) -> Option<T> {
+        self.with(T::clone)
+    }
+
+    /// Access the value of a type
+    ///
+    /// Returns `None` without calling `f` if the map holds no value of this type.
+    pub fn with<T: Send + 'static, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let values = self.values.lock().unwrap();
+        values.get(&TypeId::of::<T>()).map(|value| f(value.downcast_ref::<T>().unwrap()))
+    }
+
+    /// Modify the value of a type in place
+    ///
+    /// Returns `None` without calling `f` if the map holds no value of this type.
+    pub fn with_mut<T: Send + 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut values = self.values.lock().unwrap();
+        values.get_mut(&TypeId::of::<T>()).map(|value| f(value.downcast_mut::<T>().unwrap()))
+    }
+
+    /// Modify the value of a type in place, inserting it first if the map holds none
+    pub fn with_mut_or_insert<T: Send + 'static, R>(
+        &self,
+        default: impl FnOnce() -> T,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> R {
+        let mut values = self.values.lock().unwrap();
+        let value = values.entry(TypeId::of::<T>()).or_insert_with(|| Box::new(default()));
+        f(value.downcast_mut::<T>().unwrap())
+    }
+}
+
+impl fmt::Debug for UserDataMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UserDataMap").field("len", &self.values.lock().unwrap().len()).finish()
+    }
+}
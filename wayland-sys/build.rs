@@ -2,20 +2,40 @@ use pkg_config::Config;
 
 fn main() {
     if std::env::var_os("CARGO_FEATURE_DLOPEN").is_some() {
+        // `dlopen` wins over `static`, so that enabling all features still builds
+        if std::env::var_os("CARGO_FEATURE_STATIC").is_some() {
+            println!("cargo:warning=The `static` feature of wayland-sys is ignored, as `dlopen` is enabled.");
+        }
         // Do not link to anything
         return;
     }
 
+    // With the `static` feature, pkg-config is queried with `--static`, so that the private
+    // dependencies of the libraries (libffi, and libm/librt for some builds) are linked as well.
+    // This requires the static archives (`libwayland-*.a`) to be installed, which most
+    // distributions do not ship by default.
+    let statik = std::env::var_os("CARGO_FEATURE_STATIC").is_some();
+    let probe = |name: &str| {
+        let mut config = Config::new();
+        if statik {
+            config.statik(true);
+        }
+        config.probe(name).unwrap();
+    };
+
     if std::env::var_os("CARGO_FEATURE_CLIENT").is_some() {
-        Config::new().probe("wayland-client").unwrap();
+        probe("wayland-client");
     }
     if std::env::var_os("CARGO_FEATURE_CURSOR").is_some() {
-        Config::new().probe("wayland-cursor").unwrap();
+        probe("wayland-cursor");
     }
     if std::env::var_os("CARGO_FEATURE_EGL").is_some() {
-        Config::new().probe("wayland-egl").unwrap();
+        probe("wayland-egl");
     }
     if std::env::var_os("CARGO_FEATURE_SERVER").is_some() {
-        Config::new().probe("wayland-server").unwrap();
+        probe("wayland-server");
+    }
+    if std::env::var_os("CARGO_FEATURE_XKBCOMMON").is_some() {
+        probe("xkbcommon");
     }
 }
@@ -120,3 +120,41 @@ pub fn is_lib_available() -> bool {
 pub fn is_lib_available() -> bool {
     wayland_client_option().is_some()
 }
+
+/// Detect the version of the available `libwayland-client.so`
+///
+/// Returns `None` if the library cannot be loaded. The returned version is a lower bound, see
+/// [`LibVersion`] for details. Probing the library is not free, so the result should be stored
+/// rather than queried repeatedly.
+#[cfg(feature = "client")]
+pub fn lib_version() -> Option<LibVersion> {
+    if !is_lib_available() {
+        return None;
+    }
+    probe_lib_version(
+        &["libwayland-client.so.0", "libwayland-client.so"],
+        LibVersion::new(1, 15),
+        &[
+            (LibVersion::new(1, 23), b"wl_display_dispatch_timeout\0"),
+            (LibVersion::new(1, 20), b"wl_proxy_marshal_flags\0"),
+            (LibVersion::new(1, 18), b"wl_proxy_set_tag\0"),
+        ],
+    )
+}
+
+/// Look up `wl_display_create_queue_with_name`, only available since libwayland 1.23
+///
+/// Returns `None` if the available `libwayland-client.so` is too old or cannot be loaded.
+#[cfg(feature = "client")]
+pub fn wl_display_create_queue_with_name_option(
+) -> Option<unsafe extern "C" fn(*mut wl_display, *const c_char) -> *mut wl_event_queue> {
+    if !is_lib_available() {
+        return None;
+    }
+    unsafe {
+        probe_symbol(
+            &["libwayland-client.so.0", "libwayland-client.so"],
+            b"wl_display_create_queue_with_name\0",
+        )
+    }
+}
@@ -46,6 +46,149 @@ pub struct wl_array {
     pub data: *mut c_void,
 }
 
+impl wl_list {
+    /// Iterate over the elements of the list with the given head
+    ///
+    /// The iterator yields pointers to the `wl_list` links of the elements, from which the
+    /// elements can be retrieved using the offset of the link in their type. The next link is
+    /// read before yielding the current one, so the current element may be removed from the list
+    /// (and freed) while iterating.
+    ///
+    /// # Safety
+    ///
+    /// `head` must point to a valid, initialized list, and the list must not be modified during
+    /// the iteration other than by removing the element that was just yielded.
+    pub unsafe fn iter(head: *mut wl_list) -> WlListIter {
+        // Safety: head is a valid list
+        WlListIter { head, next: unsafe { (*head).next } }
+    }
+
+    /// Whether the list with the given head is empty
+    ///
+    /// # Safety
+    ///
+    /// `head` must point to a valid, initialized list.
+    pub unsafe fn is_empty(head: *const wl_list) -> bool {
+        // Safety: head is a valid list
+        unsafe { std::ptr::eq((*head).next, head) }
+    }
+}
+
+/// An iterator over the links of a `wl_list`
+///
+/// See [`wl_list::iter()`].
+#[derive(Debug)]
+pub struct WlListIter {
+    head: *mut wl_list,
+    next: *mut wl_list,
+}
+
+impl Iterator for WlListIter {
+    type Item = *mut wl_list;
+
+    fn next(&mut self) -> Option<*mut wl_list> {
+        if self.next == self.head {
+            return None;
+        }
+        let current = self.next;
+        // Safety: the contract of wl_list::iter() ensures the link is still valid
+        self.next = unsafe { (*current).next };
+        Some(current)
+    }
+}
+
+/// Retrieve the element containing a `wl_list` link
+///
+/// `offset` is the offset of the link within `T`, as given by `memoffset::offset_of!` for
+/// example.
+///
+/// # Safety
+///
+/// `link` must point to the link field located at `offset` within a valid `T`.
+pub unsafe fn wl_list_container<T>(link: *mut wl_list, offset: usize) -> *mut T {
+    // Safety: the link is inside a T at the given offset
+    unsafe { (link as *mut u8).sub(offset) as *mut T }
+}
+
+/// Error returned when the contents of a `wl_array` cannot be viewed as a slice of some type
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WlArrayError {
+    /// The size of the array is not a multiple of the size of the type
+    Size,
+    /// The data of the array is not properly aligned for the type
+    Alignment,
+}
+
+impl fmt::Display for WlArrayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WlArrayError::Size => f.write_str("array size is not a multiple of the element size"),
+            WlArrayError::Alignment => f.write_str("array data is misaligned for the element type"),
+        }
+    }
+}
+
+impl std::error::Error for WlArrayError {}
+
+impl wl_array {
+    /// The contents of the array as bytes
+    ///
+    /// # Safety
+    ///
+    /// The array must be valid: `data` must point to at least `size` initialized bytes (or be
+    /// null if `size` is 0), which must not be mutated for the lifetime of the returned slice.
+    pub unsafe fn as_bytes(&self) -> &[u8] {
+        if self.size == 0 {
+            &[]
+        } else {
+            // Safety: data points to size bytes
+            unsafe { std::slice::from_raw_parts(self.data as *const u8, self.size) }
+        }
+    }
+
+    /// The contents of the array as a slice of `T`
+    ///
+    /// This fails if the size of the array is not a multiple of the size of `T`, or if its data is
+    /// not aligned for `T`. Arrays allocated by libwayland are suitably aligned for any primitive
+    /// type.
+    ///
+    /// # Safety
+    ///
+    /// The array must be valid as for [`wl_array::as_bytes()`], and any bit-pattern must be a
+    /// valid `T` (for example integer types or `#[repr(C)]` structs of them).
+    pub unsafe fn as_slice<T: Copy>(&self) -> Result<&[T], WlArrayError> {
+        let elem_size = std::mem::size_of::<T>();
+        if self.size == 0 {
+            return Ok(&[]);
+        }
+        if elem_size == 0 || self.size % elem_size != 0 {
+            return Err(WlArrayError::Size);
+        }
+        if self.data as usize % std::mem::align_of::<T>() != 0 {
+            return Err(WlArrayError::Alignment);
+        }
+        // Safety: size and alignment were checked, the caller guarantees validity
+        Ok(unsafe { std::slice::from_raw_parts(self.data as *const T, self.size / elem_size) })
+    }
+
+    /// The contents of the array as a mutable slice of `T`
+    ///
+    /// See [`wl_array::as_slice()`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`wl_array::as_slice()`], and the contents must not be accessed through any other
+    /// pointer for the lifetime of the returned slice.
+    pub unsafe fn as_mut_slice<T: Copy>(&mut self) -> Result<&mut [T], WlArrayError> {
+        let len = unsafe { self.as_slice::<T>() }?.len();
+        if len == 0 {
+            return Ok(&mut []);
+        }
+        // Safety: checked by as_slice
+        Ok(unsafe { std::slice::from_raw_parts_mut(self.data as *mut T, len) })
+    }
+}
+
 pub type wl_fixed_t = i32;
 
 pub fn wl_fixed_to_double(f: wl_fixed_t) -> f64 {
@@ -87,3 +230,139 @@ pub type wl_dispatcher_func_t = unsafe extern "C" fn(
 ) -> c_int;
 
 pub type wl_log_func_t = unsafe extern "C" fn(*const c_char, *const c_void);
+
+/// A version of a libwayland library
+///
+/// libwayland does not export its version at runtime, so this is a lower bound detected by
+/// probing for symbols introduced in each version. Only versions introducing new symbols can be
+/// told apart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LibVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl LibVersion {
+    pub const fn new(major: u32, minor: u32) -> LibVersion {
+        LibVersion { major, minor }
+    }
+}
+
+impl fmt::Display for LibVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Open the first available library out of `sonames` and return the newest version for which
+/// the marker symbol is present, or `base` if none is.
+///
+/// `markers` must be sorted from newest to oldest.
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) fn probe_lib_version(
+    sonames: &[&str],
+    base: LibVersion,
+    markers: &[(LibVersion, &[u8])],
+) -> Option<LibVersion> {
+    // The library is reference-counted by the dynamic loader, so if it is already loaded
+    // (linked or through dlopen) this returns the same instance.
+    let lib = sonames.iter().find_map(|name| unsafe { dlib::Library::new(name) }.ok())?;
+    let version = markers
+        .iter()
+        .find(|(_, symbol)| unsafe { lib.get::<*const c_void>(symbol) }.is_ok())
+        .map(|&(version, _)| version)
+        .unwrap_or(base);
+    Some(version)
+}
+
+/// Look up a symbol that may be missing from older versions of a library
+///
+/// The library is kept loaded for the rest of the program, so the returned symbol remains valid.
+///
+/// # Safety
+///
+/// `T` must be the correct function pointer type for `symbol`, which must be nul-terminated.
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) unsafe fn probe_symbol<T: Copy>(sonames: &[&str], symbol: &[u8]) -> Option<T> {
+    let lib = sonames.iter().find_map(|name| unsafe { dlib::Library::new(name) }.ok())?;
+    let sym = unsafe { lib.get::<T>(symbol) }.ok().map(|sym| *sym);
+    std::mem::forget(lib);
+    sym
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array_slices() {
+        let mut storage = [1u32, 2, 3];
+        let mut array = wl_array { size: 12, alloc: 12, data: storage.as_mut_ptr() as *mut c_void };
+        unsafe {
+            assert_eq!(array.as_slice::<u32>(), Ok(&[1u32, 2, 3][..]));
+            assert_eq!(array.as_bytes().len(), 12);
+            assert_eq!(array.as_slice::<u64>(), Err(WlArrayError::Size));
+            array.as_mut_slice::<u32>().unwrap()[1] = 5;
+        }
+        assert_eq!(storage, [1, 5, 3]);
+
+        let mut misaligned = wl_array {
+            size: 4,
+            alloc: 4,
+            data: unsafe { (storage.as_mut_ptr() as *mut u8).add(1) } as *mut c_void,
+        };
+        unsafe {
+            assert_eq!(misaligned.as_slice::<u32>(), Err(WlArrayError::Alignment));
+            assert_eq!(misaligned.as_mut_slice::<u8>().unwrap().len(), 4);
+        }
+
+        let empty = wl_array { size: 0, alloc: 0, data: std::ptr::null_mut() };
+        unsafe { assert!(empty.as_slice::<u32>().unwrap().is_empty()) };
+    }
+
+    #[repr(C)]
+    struct Element {
+        value: u32,
+        link: wl_list,
+    }
+
+    #[test]
+    fn list_iteration() {
+        let null = std::ptr::null_mut();
+        let mut head = wl_list { prev: null, next: null };
+        let mut elements: Vec<Element> = (0..3)
+            .map(|value| Element { value, link: wl_list { prev: null, next: null } })
+            .collect();
+
+        let head_ptr = &mut head as *mut wl_list;
+        unsafe {
+            (*head_ptr).prev = head_ptr;
+            (*head_ptr).next = head_ptr;
+            assert!(wl_list::is_empty(head_ptr));
+            assert_eq!(wl_list::iter(head_ptr).count(), 0);
+            // link the elements, inserting at the tail
+            for element in elements.iter_mut() {
+                let link = &mut element.link as *mut wl_list;
+                (*link).prev = (*head_ptr).prev;
+                (*link).next = head_ptr;
+                (*(*head_ptr).prev).next = link;
+                (*head_ptr).prev = link;
+            }
+            assert!(!wl_list::is_empty(head_ptr));
+
+            let offset = &elements[0].link as *const wl_list as usize
+                - &elements[0] as *const Element as usize;
+            let values: Vec<u32> = wl_list::iter(head_ptr)
+                .map(|link| (*wl_list_container::<Element>(link, offset)).value)
+                .collect();
+            assert_eq!(values, [0, 1, 2]);
+
+            // removing the current element while iterating is allowed
+            for link in wl_list::iter(head_ptr) {
+                (*(*link).prev).next = (*link).next;
+                (*(*link).next).prev = (*link).prev;
+            }
+            assert!(wl_list::is_empty(head_ptr));
+        }
+    }
+}
@@ -26,6 +26,21 @@
 //! Each module except `common` corresponds to a system library. They all define a function named
 //! `is_lib_available()` which returns whether the library could be loaded. They always return true
 //! if the feature `dlopen` is absent, as we link against the library directly in that case.
+//!
+//! The `cursor` and `egl` modules follow the same scheme as `client` and `server`: with `dlopen`, none
+//! of the libraries are linked, and each of them is only loaded the first time it is used. As such, a
+//! binary can use `wayland_egl_option()` or `is_lib_available()` to gracefully handle the absence of
+//! `libwayland-egl.so`, while still requiring `libwayland-client.so`.
+//!
+//! The `xkbcommon` module is not part of libwayland, but binds the few functions of `libxkbcommon.so`
+//! needed to interpret the keymaps sent by `wl_keyboard`. It is enabled by the `xkbcommon` feature.
+//!
+//! Conversely, the `static` feature links the libraries statically, for fully self-contained
+//! binaries. This requires the static archives of libwayland (and of its `libffi` dependency) to be
+//! available through `pkg-config --static`. Note that EGL and Vulkan drivers are loaded at runtime and
+//! link `libwayland-client.so` dynamically: they would then use a distinct copy of the library, so the
+//! `static` feature is mostly useful for programs that do not use hardware rendering. It has no
+//! effect if the `dlopen` feature is enabled as well.
 #![allow(non_camel_case_types)]
 #![forbid(improper_ctypes, unsafe_op_in_unsafe_fn)]
 // Doc feature labels can be tested locally by running RUSTDOCFLAGS="--cfg=docsrs" cargo +nightly doc -p <crate>
@@ -35,7 +50,7 @@
 // it's what happens when running `cargo test --all` from the workspace root),
 // dlib isn't actually used. This is not an issue, so don't warn about it.
 #[allow(unused_imports)]
-#[cfg(any(feature = "client", feature = "server"))]
+#[cfg(any(feature = "client", feature = "server", feature = "xkbcommon"))]
 #[macro_use]
 extern crate dlib;
 
@@ -51,6 +66,9 @@ pub mod egl;
 #[cfg(all(feature = "cursor", feature = "client"))]
 pub mod cursor;
 
+#[cfg(feature = "xkbcommon")]
+pub mod xkbcommon;
+
 #[cfg(feature = "server")]
 pub use libc::{gid_t, pid_t, uid_t};
 
@@ -21,6 +21,11 @@ pub enum wl_global {}
 pub enum wl_resource {}
 pub enum wl_shm_buffer {}
 
+pub const WL_EVENT_READABLE: u32 = 0x01;
+pub const WL_EVENT_WRITABLE: u32 = 0x02;
+pub const WL_EVENT_HANGUP: u32 = 0x04;
+pub const WL_EVENT_ERROR: u32 = 0x08;
+
 pub type wl_event_loop_fd_func_t = unsafe extern "C" fn(c_int, u32, *mut c_void) -> c_int;
 pub type wl_event_loop_timer_func_t = unsafe extern "C" fn(*mut c_void) -> c_int;
 pub type wl_event_loop_signal_func_t = unsafe extern "C" fn(c_int, *mut c_void) -> c_int;
@@ -60,6 +65,7 @@ external_library!(WaylandServer, "wayland-server",
         fn wl_client_get_link(*mut wl_client) -> *mut wl_list,
         fn wl_client_from_link(*mut wl_list) -> *mut wl_client,
         fn wl_client_for_each_resource(*mut wl_client, wl_client_for_each_resource_iterator_func_t, *mut c_void) -> (),
+        fn wl_client_get_fd(*mut wl_client) -> c_int,
     // wl_display
         fn wl_client_create(*mut wl_display, c_int) -> *mut wl_client,
         fn wl_display_create() -> *mut wl_display,
@@ -189,6 +195,27 @@ pub fn is_lib_available() -> bool {
     wayland_server_option().is_some()
 }
 
+/// Detect the version of the available `libwayland-server.so`
+///
+/// Returns `None` if the library cannot be loaded. The returned version is a lower bound, see
+/// [`LibVersion`] for details. Probing the library is not free, so the result should be stored
+/// rather than queried repeatedly.
+#[cfg(feature = "server")]
+pub fn lib_version() -> Option<LibVersion> {
+    if !is_lib_available() {
+        return None;
+    }
+    probe_lib_version(
+        &["libwayland-server.so.0", "libwayland-server.so"],
+        LibVersion::new(1, 15),
+        &[
+            (LibVersion::new(1, 23), b"wl_client_set_max_buffer_size\0"),
+            (LibVersion::new(1, 22), b"wl_global_get_name\0"),
+            (LibVersion::new(1, 21), b"wl_signal_emit_mutable\0"),
+        ],
+    )
+}
+
 #[cfg(feature = "server")]
 pub mod signal {
     #![allow(clippy::cast_ptr_alignment, clippy::missing_safety_doc)]
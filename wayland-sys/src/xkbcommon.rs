@@ -0,0 +1,77 @@
+//! Bindings to the `xkbcommon.so` library
+//!
+//! Only the functions needed to decode the keys of a `wl_keyboard` are bound. The created handle is
+//! named `xkbcommon_handle()`.
+
+#[cfg(feature = "dlopen")]
+use once_cell::sync::Lazy;
+use std::os::raw::{c_char, c_int};
+
+pub enum xkb_context {}
+pub enum xkb_keymap {}
+pub enum xkb_state {}
+
+pub const XKB_CONTEXT_NO_FLAGS: c_int = 0;
+pub const XKB_KEYMAP_FORMAT_TEXT_V1: c_int = 1;
+pub const XKB_KEYMAP_COMPILE_NO_FLAGS: c_int = 0;
+pub const XKB_STATE_MODS_EFFECTIVE: c_int = 1 << 3;
+
+pub const XKB_MOD_NAME_SHIFT: &[u8] = b"Shift\0";
+pub const XKB_MOD_NAME_CAPS: &[u8] = b"Lock\0";
+pub const XKB_MOD_NAME_CTRL: &[u8] = b"Control\0";
+pub const XKB_MOD_NAME_ALT: &[u8] = b"Mod1\0";
+pub const XKB_MOD_NAME_NUM: &[u8] = b"Mod2\0";
+pub const XKB_MOD_NAME_LOGO: &[u8] = b"Mod4\0";
+
+external_library!(XkbCommon, "xkbcommon",
+    functions:
+        fn xkb_context_new(c_int) -> *mut xkb_context,
+        fn xkb_context_unref(*mut xkb_context) -> (),
+        fn xkb_keymap_new_from_buffer(*mut xkb_context, *const c_char, usize, c_int, c_int) -> *mut xkb_keymap,
+        fn xkb_keymap_unref(*mut xkb_keymap) -> (),
+        fn xkb_keymap_key_repeats(*mut xkb_keymap, u32) -> c_int,
+        fn xkb_state_new(*mut xkb_keymap) -> *mut xkb_state,
+        fn xkb_state_unref(*mut xkb_state) -> (),
+        fn xkb_state_update_mask(*mut xkb_state, u32, u32, u32, u32, u32, u32) -> c_int,
+        fn xkb_state_key_get_one_sym(*mut xkb_state, u32) -> u32,
+        fn xkb_state_key_get_utf8(*mut xkb_state, u32, *mut c_char, usize) -> c_int,
+        fn xkb_state_mod_name_is_active(*mut xkb_state, *const c_char, c_int) -> c_int,
+);
+
+#[cfg(feature = "dlopen")]
+pub fn xkbcommon_option() -> Option<&'static XkbCommon> {
+    static XKBCOMMON_OPTION: Lazy<Option<XkbCommon>> = Lazy::new(|| {
+        let versions = ["libxkbcommon.so.0", "libxkbcommon.so"];
+
+        for ver in &versions {
+            match unsafe { XkbCommon::open(ver) } {
+                Ok(h) => return Some(h),
+                Err(::dlib::DlError::CantOpen(_)) => continue,
+                Err(::dlib::DlError::MissingSymbol(s)) => {
+                    log::error!("Found library {} cannot be used: symbol {} is missing.", ver, s);
+                    return None;
+                }
+            }
+        }
+        None
+    });
+
+    XKBCOMMON_OPTION.as_ref()
+}
+
+#[cfg(feature = "dlopen")]
+pub fn xkbcommon_handle() -> &'static XkbCommon {
+    static XKBCOMMON_HANDLE: Lazy<&'static XkbCommon> =
+        Lazy::new(|| xkbcommon_option().expect("Library libxkbcommon.so could not be loaded."));
+
+    &XKBCOMMON_HANDLE
+}
+
+#[cfg(not(feature = "dlopen"))]
+pub fn is_lib_available() -> bool {
+    true
+}
+#[cfg(feature = "dlopen")]
+pub fn is_lib_available() -> bool {
+    xkbcommon_option().is_some()
+}
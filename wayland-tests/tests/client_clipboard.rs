@@ -0,0 +1,341 @@
+use std::io::{Read, Write};
+use std::os::unix::io::AsFd;
+use std::sync::Mutex;
+
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestClient, TestServer};
+
+use wayc::clipboard::{Clipboard, ClipboardError};
+
+use ways::data_device::{
+    DataDeviceData, DataDeviceHandler, DataDeviceManager, DataDeviceManagerData, DataOfferData,
+    DataSourceData, DragRequest,
+};
+use ways::protocol::{
+    wl_data_device, wl_data_device_manager, wl_data_offer, wl_data_source, wl_keyboard, wl_pointer,
+    wl_seat, wl_touch,
+};
+use ways::seat::{Seat, SeatData};
+use ways::Resource;
+
+use wayland_protocols::wp::primary_selection::zv1::{
+    client::zwp_primary_selection_device_manager_v1 as c_manager,
+    server::{
+        zwp_primary_selection_device_manager_v1 as s_manager,
+        zwp_primary_selection_device_v1 as s_device, zwp_primary_selection_offer_v1 as s_offer,
+        zwp_primary_selection_source_v1 as s_source,
+    },
+};
+
+#[test]
+fn clipboard_copy_paste() {
+    let mut server = TestServer::new();
+    let mut server_ddata = ServerHandler {
+        seat: Seat::new::<ServerHandler>(&server.display.handle(), "seat0"),
+        data_device: DataDeviceManager::new::<ServerHandler>(&server.display.handle()),
+        primary_devices: Vec::new(),
+        primary_selection: None,
+    };
+
+    let (s_client_a, mut client_a) = server.add_client();
+    let mut ddata_a = ClientHandler { globals: globals::GlobalList::new() };
+    let (s_client_b, mut client_b) = server.add_client();
+    let mut ddata_b = ClientHandler { globals: globals::GlobalList::new() };
+    let clipboard_a = setup(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata);
+    let clipboard_b = setup(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata);
+    assert_eq!(clipboard_a.mime_types(), None);
+
+    // the focused client copies, and sees its own selection
+    server_ddata.data_device.set_focus(&server_ddata.seat, Some(&s_client_a));
+    clipboard_a
+        .copy(vec!["text/plain".into(), "text/html".into()], 0, |mime_type, fd| {
+            assert_eq!(mime_type, "text/plain");
+            std::fs::File::from(fd).write_all(b"I like trains!").unwrap();
+        })
+        .unwrap();
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    assert!(clipboard_a.owns_selection());
+    assert_eq!(clipboard_a.mime_types(), Some(vec!["text/plain".into(), "text/html".into()]));
+
+    // another client pastes it in the first mime type it prefers
+    server_ddata.data_device.set_focus(&server_ddata.seat, Some(&s_client_b));
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert!(matches!(clipboard_b.paste(&["image/png"]), Err(ClipboardError::NoMatchingMimeType)));
+    let (mime_type, fd) = clipboard_b.paste(&["image/png", "text/plain"]).unwrap();
+    assert_eq!(mime_type, "text/plain");
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    let mut contents = String::new();
+    std::fs::File::from(fd).read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "I like trains!");
+
+    // copying from the other client cancels the first selection
+    clipboard_b.copy(vec!["text/plain".into()], 0, |_, _| {}).unwrap();
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    assert!(!clipboard_a.owns_selection());
+    assert!(clipboard_b.owns_selection());
+    assert_eq!(clipboard_b.mime_types(), Some(vec!["text/plain".into()]));
+
+    // clearing the selection removes it from the focused client
+    clipboard_b.clear(0);
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert_eq!(clipboard_b.mime_types(), None);
+    assert!(matches!(clipboard_b.paste(&[]), Err(ClipboardError::NoSelection)));
+}
+
+#[test]
+fn clipboard_primary_selection() {
+    let mut server = TestServer::new();
+    let mut server_ddata = ServerHandler {
+        seat: Seat::new::<ServerHandler>(&server.display.handle(), "seat0"),
+        data_device: DataDeviceManager::new::<ServerHandler>(&server.display.handle()),
+        primary_devices: Vec::new(),
+        primary_selection: None,
+    };
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, s_manager::ZwpPrimarySelectionDeviceManagerV1, _>(1, ());
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let seat = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_seat::WlSeat, _, _>(&qh, &registry, 9..10, ())
+        .unwrap();
+    let manager = client_ddata
+        .globals
+        .bind::<c_manager::ZwpPrimarySelectionDeviceManagerV1, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+
+    // only a primary selection manager is accepted
+    assert!(matches!(
+        Clipboard::new_primary(&seat, &seat),
+        Err(ClipboardError::NotPrimarySelection)
+    ));
+
+    let clipboard = Clipboard::new_primary(&manager, &seat).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(clipboard.mime_types(), None);
+
+    clipboard
+        .copy(vec!["text/plain".into()], 0, |mime_type, fd| {
+            assert_eq!(mime_type, "text/plain");
+            std::fs::File::from(fd).write_all(b"I like trains!").unwrap();
+        })
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(clipboard.owns_selection());
+    assert_eq!(clipboard.mime_types(), Some(vec!["text/plain".into()]));
+
+    let (_, fd) = clipboard.paste(&[]).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let mut contents = String::new();
+    std::fs::File::from(fd).read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "I like trains!");
+
+    clipboard.clear(0);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(!clipboard.owns_selection());
+    assert_eq!(clipboard.mime_types(), None);
+}
+
+fn setup(
+    client: &mut TestClient<ClientHandler>,
+    server: &mut TestServer<ServerHandler>,
+    client_ddata: &mut ClientHandler,
+    server_ddata: &mut ServerHandler,
+) -> Clipboard {
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    let seat = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_seat::WlSeat, _, _>(&qh, &registry, 9..10, ())
+        .unwrap();
+    let manager = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_data_device_manager::WlDataDeviceManager, _, _>(
+            &qh,
+            &registry,
+            3..4,
+            (),
+        )
+        .unwrap();
+    let clipboard = Clipboard::new(&manager, &seat).unwrap();
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    clipboard
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    seat: Seat,
+    data_device: DataDeviceManager,
+    primary_devices: Vec<s_device::ZwpPrimarySelectionDeviceV1>,
+    primary_selection: Option<s_source::ZwpPrimarySelectionSourceV1>,
+}
+
+impl DataDeviceHandler for ServerHandler {
+    fn start_drag(&mut self, _: DragRequest) {
+        panic!("Unexpected drag!");
+    }
+}
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_pointer::WlPointer: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_keyboard::WlKeyboard: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_touch::WlTouch: SeatData] => Seat);
+
+ways::delegate_global_dispatch!(ServerHandler:
+    [wl_data_device_manager::WlDataDeviceManager: DataDeviceManagerData] => DataDeviceManager
+);
+ways::delegate_dispatch!(ServerHandler:
+    [wl_data_device_manager::WlDataDeviceManager: DataDeviceManagerData] => DataDeviceManager
+);
+ways::delegate_dispatch!(ServerHandler: [wl_data_device::WlDataDevice: DataDeviceData] => DataDeviceManager);
+ways::delegate_dispatch!(ServerHandler: [wl_data_source::WlDataSource: DataSourceData] => DataDeviceManager);
+ways::delegate_dispatch!(ServerHandler: [wl_data_offer::WlDataOffer: DataOfferData] => DataDeviceManager);
+
+// A primary selection shared by all the clients
+impl ways::GlobalDispatch<s_manager::ZwpPrimarySelectionDeviceManagerV1, ()> for ServerHandler {
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<s_manager::ZwpPrimarySelectionDeviceManagerV1>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl ways::Dispatch<s_manager::ZwpPrimarySelectionDeviceManagerV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &s_manager::ZwpPrimarySelectionDeviceManagerV1,
+        request: s_manager::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            s_manager::Request::CreateSource { id } => {
+                data_init.init(id, Mutex::new(Vec::new()));
+            }
+            s_manager::Request::GetDevice { id, .. } => {
+                state.primary_devices.push(data_init.init(id, ()));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ways::Dispatch<s_device::ZwpPrimarySelectionDeviceV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        client: &ways::Client,
+        _: &s_device::ZwpPrimarySelectionDeviceV1,
+        request: s_device::Request,
+        _: &(),
+        dh: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let s_device::Request::SetSelection { source, .. } = request {
+            if let Some(old) = std::mem::replace(&mut state.primary_selection, source.clone()) {
+                old.cancelled();
+            }
+            for device in &state.primary_devices {
+                let source = match source {
+                    Some(ref source) => source,
+                    None => {
+                        device.selection(None);
+                        continue;
+                    }
+                };
+                let offer = client
+                    .create_resource::<s_offer::ZwpPrimarySelectionOfferV1, _, Self>(
+                        dh,
+                        device.version(),
+                        source.clone(),
+                    )
+                    .unwrap();
+                device.data_offer(&offer);
+                for mime_type in source.data::<Mutex<Vec<String>>>().unwrap().lock().unwrap().iter()
+                {
+                    offer.offer(mime_type.clone());
+                }
+                device.selection(Some(&offer));
+            }
+        }
+    }
+}
+
+impl ways::Dispatch<s_source::ZwpPrimarySelectionSourceV1, Mutex<Vec<String>>> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &s_source::ZwpPrimarySelectionSourceV1,
+        request: s_source::Request,
+        mime_types: &Mutex<Vec<String>>,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let s_source::Request::Offer { mime_type } = request {
+            mime_types.lock().unwrap().push(mime_type);
+        }
+    }
+}
+
+impl ways::Dispatch<s_offer::ZwpPrimarySelectionOfferV1, s_source::ZwpPrimarySelectionSourceV1>
+    for ServerHandler
+{
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &s_offer::ZwpPrimarySelectionOfferV1,
+        request: s_offer::Request,
+        source: &s_source::ZwpPrimarySelectionSourceV1,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let s_offer::Request::Receive { mime_type, fd } = request {
+            source.send(mime_type, fd.as_fd());
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_seat::WlSeat,
+    wayc::protocol::wl_data_device_manager::WlDataDeviceManager,
+    c_manager::ZwpPrimarySelectionDeviceManagerV1
+]);
@@ -0,0 +1,298 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::Resource;
+
+use ways::protocol::{
+    wl_buffer, wl_compositor, wl_keyboard, wl_pointer, wl_seat, wl_shm, wl_shm_pool, wl_surface,
+    wl_touch,
+};
+use ways::seat::{Seat, SeatData};
+use ways::shm::{ShmBuffer, ShmGlobal, ShmGlobalData, ShmPool};
+
+use wayc::protocol::{
+    wl_compositor::WlCompositor as ClientCompositor, wl_pointer::WlPointer as ClientPointer,
+    wl_seat::WlSeat as ClientSeat, wl_shm::WlShm as ClientShm,
+};
+use wayland_cursor::{CursorManager, CursorShape};
+use wayland_protocols::wp::cursor_shape::v1::{
+    client::wp_cursor_shape_manager_v1::WpCursorShapeManagerV1 as ClientShapeManager,
+    server::{wp_cursor_shape_device_v1 as s_device, wp_cursor_shape_manager_v1 as s_manager},
+};
+
+#[test]
+fn cursor_manager_uses_shape_protocol() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, s_manager::WpCursorShapeManagerV1, _>(1, ());
+    let mut server_ddata = ServerHandler::new(&server);
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+    let (compositor, shm, shape_manager, pointer) =
+        setup(&mut client, &mut server, &mut client_ddata, &mut server_ddata);
+    client_ddata.cursors =
+        Some(CursorManager::new(&client.conn, &compositor, &shm, shape_manager.as_ref()));
+    assert!(client_ddata.cursors.as_ref().unwrap().uses_shape_protocol());
+
+    // the default cursor is set when the pointer enters
+    let surface = server_ddata.surfaces[0].clone();
+    server_ddata.seat.set_pointer_focus(Some((&surface, 0.0, 0.0)));
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let serial = client_ddata.enter_serial;
+    assert_eq!(server_ddata.shapes, [(serial, s_device::Shape::Default)]);
+
+    // a new cursor is applied right away while the pointer is over the app
+    assert!(client_ddata.cursors.as_mut().unwrap().set_cursor(&pointer, Some(CursorShape::Text)));
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.shapes[1..], [(serial, s_device::Shape::Text)]);
+
+    // and on the next enter otherwise, with its serial
+    server_ddata.seat.set_pointer_focus(None);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    client_ddata.cursors.as_mut().unwrap().set_cursor(&pointer, Some(CursorShape::Pointer));
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.shapes.len(), 2);
+    server_ddata.seat.set_pointer_focus(Some((&surface, 0.0, 0.0)));
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_ne!(client_ddata.enter_serial, serial);
+    assert_eq!(server_ddata.shapes[2..], [(client_ddata.enter_serial, s_device::Shape::Pointer)]);
+    assert_eq!(server_ddata.seat.cursor_image(), None);
+}
+
+#[test]
+fn cursor_manager_falls_back_to_theme() {
+    let mut server = TestServer::new();
+    let mut server_ddata = ServerHandler::new(&server);
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+    let (compositor, shm, shape_manager, pointer) =
+        setup(&mut client, &mut server, &mut client_ddata, &mut server_ddata);
+    assert!(shape_manager.is_none());
+    let mut cursors = CursorManager::new(&client.conn, &compositor, &shm, None);
+    assert!(!cursors.uses_shape_protocol());
+    // without this theme, the built-in arrow of the `fallback_theme` feature is used
+    cursors.set_theme("wayland-rs-missing-theme", 24);
+    cursors.set_scale(2);
+    client_ddata.cursors = Some(cursors);
+
+    let surface = server_ddata.surfaces[0].clone();
+    server_ddata.seat.set_pointer_focus(Some((&surface, 0.0, 0.0)));
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the cursor is displayed on a surface of the manager, scaled for HiDPI
+    assert_eq!(server_ddata.surfaces.len(), 2);
+    let cursor_surface = server_ddata.surfaces[1].clone();
+    let image = server_ddata.seat.cursor_image().unwrap();
+    assert_eq!(image.surface, Some(cursor_surface));
+    assert_eq!(image.hotspot, (0, 0));
+    assert_eq!(server_ddata.buffer_scale, 2);
+    let buffer = server_ddata.attached.as_ref().unwrap().info();
+    assert_eq!((buffer.width, buffer.height), (24, 38));
+
+    // hiding the cursor unsets it
+    client_ddata.cursors.as_mut().unwrap().set_cursor(&pointer, None);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.seat.cursor_image().unwrap().surface, None);
+}
+
+fn setup(
+    client: &mut TestClient<ClientHandler>,
+    server: &mut TestServer<ServerHandler>,
+    client_ddata: &mut ClientHandler,
+    server_ddata: &mut ServerHandler,
+) -> (ClientCompositor, ClientShm, Option<ClientShapeManager>, ClientPointer) {
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    let compositor =
+        client_ddata.globals.bind::<ClientCompositor, _, _>(&qh, &registry, 4..5, ()).unwrap();
+    let shm = client_ddata.globals.bind::<ClientShm, _, _>(&qh, &registry, 1..2, ()).unwrap();
+    let seat = client_ddata.globals.bind::<ClientSeat, _, _>(&qh, &registry, 9..10, ()).unwrap();
+    let shape_manager =
+        client_ddata.globals.bind::<ClientShapeManager, _, _>(&qh, &registry, 1..2, ()).ok();
+    // the surface of the app
+    compositor.create_surface(&qh, ());
+    let pointer = seat.get_pointer(&qh, ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    (compositor, shm, shape_manager, pointer)
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    seat: Seat,
+    _shm: ShmGlobal,
+    surfaces: Vec<wl_surface::WlSurface>,
+    buffer_scale: i32,
+    attached: Option<ShmBuffer>,
+    shapes: Vec<(u32, s_device::Shape)>,
+}
+
+impl ServerHandler {
+    fn new(server: &TestServer<ServerHandler>) -> ServerHandler {
+        let handle = server.display.handle();
+        handle.create_global::<ServerHandler, wl_compositor::WlCompositor, _>(4, ());
+        let seat = Seat::new::<ServerHandler>(&handle, "seat0");
+        seat.set_capabilities(wl_seat::Capability::Pointer);
+        ServerHandler {
+            seat,
+            _shm: ShmGlobal::new::<ServerHandler>(&handle, []),
+            surfaces: Vec::new(),
+            buffer_scale: 1,
+            attached: None,
+            shapes: Vec::new(),
+        }
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            state.surfaces.push(init.init(id, ()));
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+impl ways::Dispatch<wl_surface::WlSurface, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_surface::WlSurface,
+        request: wl_surface::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => {
+                state.attached = buffer.map(|buffer| buffer.data::<ShmBuffer>().unwrap().clone());
+            }
+            wl_surface::Request::SetBufferScale { scale } => state.buffer_scale = scale,
+            _ => {}
+        }
+    }
+}
+
+impl ways::Dispatch<s_manager::WpCursorShapeManagerV1, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &s_manager::WpCursorShapeManagerV1,
+        request: s_manager::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let s_manager::Request::GetPointer { cursor_shape_device, .. } = request {
+            init.init(cursor_shape_device, ());
+        }
+    }
+}
+
+impl ways::Dispatch<s_device::WpCursorShapeDeviceV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &s_device::WpCursorShapeDeviceV1,
+        request: s_device::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let s_device::Request::SetShape { serial, shape: ways::WEnum::Value(shape) } = request {
+            state.shapes.push((serial, shape));
+        }
+    }
+}
+
+server_ignore_global_impl!(ServerHandler => [
+    wl_compositor::WlCompositor,
+    s_manager::WpCursorShapeManagerV1
+]);
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_pointer::WlPointer: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_keyboard::WlKeyboard: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_touch::WlTouch: SeatData] => Seat);
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_shm::WlShm: ShmGlobalData] => ShmGlobal);
+ways::delegate_dispatch!(ServerHandler: [wl_shm::WlShm: ShmGlobalData] => ShmGlobal);
+ways::delegate_dispatch!(ServerHandler: [wl_shm_pool::WlShmPool: ShmPool] => ShmGlobal);
+ways::delegate_dispatch!(ServerHandler: [wl_buffer::WlBuffer: ShmBuffer] => ShmGlobal);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    cursors: Option<CursorManager>,
+    enter_serial: u32,
+}
+
+impl ClientHandler {
+    fn new() -> ClientHandler {
+        ClientHandler { globals: globals::GlobalList::new(), cursors: None, enter_serial: 0 }
+    }
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<ClientPointer, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        pointer: &ClientPointer,
+        event: wayc::protocol::wl_pointer::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        use wayc::protocol::wl_pointer::Event;
+        match event {
+            Event::Enter { serial, .. } => {
+                state.enter_serial = serial;
+                state.cursors.as_mut().unwrap().pointer_enter(pointer, serial);
+            }
+            Event::Leave { .. } => state.cursors.as_mut().unwrap().pointer_leave(pointer),
+            _ => {}
+        }
+    }
+}
+
+client_ignore_impl!(ClientHandler => [
+    ClientCompositor,
+    ClientShm,
+    ClientSeat,
+    ClientShapeManager,
+    wayc::protocol::wl_surface::WlSurface
+]);
@@ -0,0 +1,121 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::protocol::{wl_buffer, wl_shm, wl_shm_pool};
+use ways::shm::{ShmBuffer, ShmGlobal, ShmGlobalData, ShmPool};
+
+use wayland_cursor::CursorTheme;
+
+/// Encode an Xcursor file with one square image for each nominal size
+fn xcursor_file(sizes: &[u32]) -> Vec<u8> {
+    let mut file = Vec::new();
+    let push = |file: &mut Vec<u8>, value: u32| file.extend_from_slice(&value.to_le_bytes());
+    // header: magic, header size, version, number of table of contents entries
+    file.extend_from_slice(b"Xcur");
+    push(&mut file, 16);
+    push(&mut file, 0x1_0000);
+    push(&mut file, sizes.len() as u32);
+    let mut position = 16 + 12 * sizes.len() as u32;
+    for &size in sizes {
+        // image chunks: type, nominal size, position
+        push(&mut file, 0xfffd_0002);
+        push(&mut file, size);
+        push(&mut file, position);
+        position += 36 + size * size * 4;
+    }
+    for &size in sizes {
+        // chunk header size, type, nominal size, version, width, height, hotspot, delay
+        for value in [36, 0xfffd_0002, size, 1, size, size, size / 2, size / 2, 0] {
+            push(&mut file, value);
+        }
+        for _ in 0..size * size {
+            push(&mut file, 0xff00_00ff);
+        }
+    }
+    file
+}
+
+#[test]
+fn theme_from_memory_at_several_scales() {
+    let mut server = TestServer::new();
+    let mut server_ddata =
+        ServerHandler { _shm: ShmGlobal::new::<ServerHandler>(&server.display.handle(), []) };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let shm = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_shm::WlShm, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    let mut theme = CursorTheme::load_from_memory(
+        &client.conn,
+        shm,
+        vec![("left_ptr", xcursor_file(&[24, 48])), ("broken", b"Xcur".to_vec())],
+        24,
+    )
+    .unwrap();
+
+    // the nominal size is used by default, the scaled one on request
+    let cursor = theme.get_cursor("left_ptr").unwrap();
+    assert_eq!(cursor[0].dimensions(), (24, 24));
+    assert_eq!(cursor[0].hotspot(), (12, 12));
+    let cursor = theme.get_cursor_for_scale("left_ptr", 2).unwrap();
+    assert_eq!(cursor[0].dimensions(), (48, 48));
+    // the nearest size is picked when the theme does not provide the exact one
+    let cursor = theme.get_cursor_for_scale("left_ptr", 3).unwrap();
+    assert_eq!(cursor[0].dimensions(), (48, 48));
+    assert_eq!(theme.loaded_cursors().count(), 3);
+    assert_eq!(theme.loader("left_ptr").path(), None);
+
+    // invalid files are replaced by the built-in arrow of the `fallback_theme` feature
+    let cursor = theme.get_cursor("broken").unwrap();
+    assert_eq!(cursor[0].dimensions(), (12, 19));
+
+    // evicting a cursor releases all its sizes
+    assert!(theme.evict("left_ptr"));
+    assert_eq!(theme.loaded_cursors().collect::<Vec<_>>(), ["broken"]);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    _shm: ShmGlobal,
+}
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_shm::WlShm: ShmGlobalData] => ShmGlobal);
+ways::delegate_dispatch!(ServerHandler: [wl_shm::WlShm: ShmGlobalData] => ShmGlobal);
+ways::delegate_dispatch!(ServerHandler: [wl_shm_pool::WlShmPool: ShmPool] => ShmGlobal);
+ways::delegate_dispatch!(ServerHandler: [wl_buffer::WlBuffer: ShmBuffer] => ShmGlobal);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [wayc::protocol::wl_shm::WlShm]);
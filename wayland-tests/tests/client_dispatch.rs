@@ -1,3 +1,4 @@
+#[macro_use]
 mod helpers;
 
 use helpers::*;
@@ -30,3 +31,344 @@ fn client_roundtrip() {
 
     server_thread.join().unwrap();
 }
+
+#[test]
+fn client_connect_from_fd() {
+    use std::os::unix::{io::OwnedFd, net::UnixStream};
+
+    let kill_switch = Arc::new(AtomicBool::new(false));
+    let server_kill_switch = kill_switch.clone();
+
+    let mut server = TestServer::<()>::new();
+    let (server_socket, client_socket) = UnixStream::pair().unwrap();
+    server.display.handle().insert_client(server_socket, Arc::new(DumbClientData)).unwrap();
+    let conn = wayc::Connection::from_fd(OwnedFd::from(client_socket)).unwrap();
+
+    let server_thread = ::std::thread::spawn(move || loop {
+        server.display.dispatch_clients(&mut ()).unwrap();
+        server.display.flush_clients().unwrap();
+        if server_kill_switch.load(Ordering::Acquire) {
+            break;
+        }
+    });
+
+    conn.roundtrip().unwrap();
+
+    kill_switch.store(true, Ordering::Release);
+
+    server_thread.join().unwrap();
+
+    // a file descriptor that is not a socket is rejected
+    let file = std::fs::File::open("/dev/null").unwrap();
+    assert!(matches!(
+        wayc::Connection::from_fd(OwnedFd::from(file)),
+        Err(wayc::ConnectError::InvalidFd)
+    ));
+}
+
+#[test]
+fn client_queue_waker() {
+    let kill_switch = Arc::new(AtomicBool::new(false));
+    let server_kill_switch = kill_switch.clone();
+
+    let mut server = TestServer::new();
+    let (_, client) = server.add_client::<()>();
+    let mut event_queue = client.conn.new_event_queue::<()>();
+    let waker = event_queue.waker().unwrap();
+
+    // the server sends nothing, only the waker can end the dispatch
+    let waker_thread = ::std::thread::spawn(move || {
+        ::std::thread::sleep(::std::time::Duration::from_millis(50));
+        waker.wake();
+    });
+    assert_eq!(event_queue.blocking_dispatch(&mut ()).unwrap(), 0);
+    waker_thread.join().unwrap();
+
+    // the cancelled read leaves the connection usable
+    let server_thread = ::std::thread::spawn(move || loop {
+        server.display.dispatch_clients(&mut ()).unwrap();
+        server.display.flush_clients().unwrap();
+        if server_kill_switch.load(Ordering::Acquire) {
+            break;
+        }
+    });
+    event_queue.roundtrip(&mut ()).unwrap();
+
+    kill_switch.store(true, Ordering::Release);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn client_roundtrip_timeout() {
+    let mut server = TestServer::new();
+    let (_, client) = server.add_client::<()>();
+    let mut event_queue = client.conn.new_event_queue::<()>();
+
+    // the server does not answer
+    match event_queue.roundtrip_timeout(&mut (), ::std::time::Duration::from_millis(50)) {
+        Err(wayc::DispatchError::Backend(wayc::backend::WaylandError::Io(e))) => {
+            assert_eq!(e.kind(), ::std::io::ErrorKind::TimedOut)
+        }
+        ret => panic!("Unexpected result: {:?}", ret),
+    }
+
+    // the connection remains usable
+    let server_thread = ::std::thread::spawn(move || {
+        for _ in 0..10 {
+            ::std::thread::sleep(::std::time::Duration::from_millis(10));
+            server.answer(&mut ());
+        }
+    });
+    event_queue.roundtrip_timeout(&mut (), ::std::time::Duration::from_secs(5)).unwrap();
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn client_roundtrip_timeout_streaming() {
+    use ways::protocol::wl_output::WlOutput as ServerOutput;
+
+    struct ServerHandler;
+    server_ignore_impl!(ServerHandler => [ServerOutput]);
+    server_ignore_global_impl!(ServerHandler => [ServerOutput]);
+
+    // a slow client, so that new events are always waiting when it is done with the previous ones
+    struct ClientHandler;
+    impl wayc::Dispatch<wayc::protocol::wl_registry::WlRegistry, ()> for ClientHandler {
+        fn event(
+            _: &mut Self,
+            _: &wayc::protocol::wl_registry::WlRegistry,
+            _: wayc::protocol::wl_registry::Event,
+            _: &(),
+            _: &wayc::Connection,
+            _: &wayc::QueueHandle<Self>,
+        ) {
+            ::std::thread::sleep(::std::time::Duration::from_millis(1));
+        }
+    }
+
+    let kill_switch = Arc::new(AtomicBool::new(false));
+    let server_kill_switch = kill_switch.clone();
+
+    let mut server = TestServer::<ServerHandler>::new();
+    let (_, client) = server.add_client::<ClientHandler>();
+    let mut event_queue = client.conn.new_event_queue::<ClientHandler>();
+
+    client.display.get_registry(&event_queue.handle(), ());
+    client.conn.flush().unwrap();
+    server.answer(&mut ServerHandler);
+
+    // the server keeps advertising globals but never answers the sync
+    let server_thread = ::std::thread::spawn(move || {
+        while !server_kill_switch.load(Ordering::Acquire) {
+            server.display.handle().create_global::<ServerHandler, ServerOutput, _>(1, ());
+            let _ = server.display.flush_clients();
+            ::std::thread::sleep(::std::time::Duration::from_micros(500));
+        }
+    });
+
+    let start = ::std::time::Instant::now();
+    let ret =
+        event_queue.roundtrip_timeout(&mut ClientHandler, ::std::time::Duration::from_millis(50));
+    let elapsed = start.elapsed();
+
+    kill_switch.store(true, Ordering::Release);
+    server_thread.join().unwrap();
+
+    match ret {
+        Err(wayc::DispatchError::Backend(wayc::backend::WaylandError::Io(e))) => {
+            assert_eq!(e.kind(), ::std::io::ErrorKind::TimedOut)
+        }
+        ret => panic!("Unexpected result: {:?}", ret),
+    }
+    // the deadline is checked between the batches of events, not only when waiting for some
+    assert!(elapsed < ::std::time::Duration::from_millis(500), "roundtrip took {:?}", elapsed);
+}
+
+#[test]
+fn client_connection_set() {
+    use wayc::{connections::ConnectionSet, protocol::wl_callback};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Server {
+        Host,
+        Nested,
+    }
+
+    struct State {
+        done: Vec<Server>,
+    }
+
+    impl wayc::Dispatch<wl_callback::WlCallback, Server> for State {
+        fn event(
+            state: &mut Self,
+            _: &wl_callback::WlCallback,
+            event: wl_callback::Event,
+            server: &Server,
+            _: &wayc::Connection,
+            _: &wayc::QueueHandle<Self>,
+        ) {
+            if let wl_callback::Event::Done { .. } = event {
+                state.done.push(*server);
+            }
+        }
+    }
+
+    let kill_switch = Arc::new(AtomicBool::new(false));
+    let mut connections = ConnectionSet::<Server, State>::new();
+    let mut server_threads = Vec::new();
+    for key in [Server::Host, Server::Nested] {
+        let mut server = TestServer::<()>::new();
+        let (_, client) = server.add_client::<State>();
+        assert!(connections.insert(key, client.event_queue).is_none());
+        let server_kill_switch = kill_switch.clone();
+        server_threads.push(::std::thread::spawn(move || loop {
+            server.display.dispatch_clients(&mut ()).unwrap();
+            server.display.flush_clients().unwrap();
+            if server_kill_switch.load(Ordering::Acquire) {
+                break;
+            }
+        }));
+    }
+    assert_eq!(connections.fds().count(), 2);
+
+    // the requests are routed to the connection of their objects
+    let nested_display = connections.connection(&Server::Nested).unwrap().display();
+    assert_eq!(connections.key_of(&nested_display), Some(&Server::Nested));
+    let qh = connections.handle_of(&nested_display).unwrap();
+    nested_display.sync(&qh, Server::Nested);
+
+    let mut state = State { done: Vec::new() };
+    while state.done.is_empty() {
+        connections.blocking_dispatch(&mut state).unwrap();
+    }
+    assert_eq!(state.done, [Server::Nested]);
+
+    let host_display = connections.connection(&Server::Host).unwrap().display();
+    host_display.sync(&connections.handle(&Server::Host).unwrap(), Server::Host);
+    while state.done.len() < 2 {
+        connections.blocking_dispatch(&mut state).unwrap();
+    }
+    assert_eq!(state.done, [Server::Nested, Server::Host]);
+
+    assert!(connections.remove(&Server::Host).is_some());
+    assert_eq!(connections.key_of(&host_display), None);
+
+    kill_switch.store(true, Ordering::Release);
+    for thread in server_threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn client_dispatch_async() {
+    use std::{
+        future::Future,
+        task::{Context, Poll, Wake, Waker},
+    };
+    use wayc::protocol::wl_callback;
+
+    struct ThreadWaker(::std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(::std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => ::std::thread::park(),
+            }
+        }
+    }
+
+    struct State {
+        done: bool,
+    }
+
+    impl wayc::Dispatch<wl_callback::WlCallback, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &wl_callback::WlCallback,
+            event: wl_callback::Event,
+            _: &(),
+            _: &wayc::Connection,
+            _: &wayc::QueueHandle<Self>,
+        ) {
+            if let wl_callback::Event::Done { .. } = event {
+                state.done = true;
+            }
+        }
+    }
+
+    let kill_switch = Arc::new(AtomicBool::new(false));
+    let server_kill_switch = kill_switch.clone();
+
+    let mut server = TestServer::new();
+    let (_, mut client) = server.add_client::<State>();
+
+    let server_thread = ::std::thread::spawn(move || loop {
+        server.display.dispatch_clients(&mut ()).unwrap();
+        server.display.flush_clients().unwrap();
+        if server_kill_switch.load(Ordering::Acquire) {
+            break;
+        }
+    });
+
+    client.display.sync(&client.event_queue.handle(), ());
+    let mut state = State { done: false };
+    // the socket is reported readable right away, reads that would block are retried
+    while !state.done {
+        block_on(client.event_queue.dispatch_async(&mut state, || std::future::ready(Ok(()))))
+            .unwrap();
+    }
+
+    kill_switch.store(true, Ordering::Release);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn client_queue_priorities() {
+    use wayc::{protocol::wl_callback, QueuePriority};
+
+    struct State {
+        done: Vec<QueuePriority>,
+    }
+
+    impl wayc::Dispatch<wl_callback::WlCallback, QueuePriority> for State {
+        fn event(
+            state: &mut Self,
+            _: &wl_callback::WlCallback,
+            event: wl_callback::Event,
+            priority: &QueuePriority,
+            _: &wayc::Connection,
+            _: &wayc::QueueHandle<Self>,
+        ) {
+            if let wl_callback::Event::Done { .. } = event {
+                state.done.push(*priority);
+            }
+        }
+    }
+
+    let mut server = TestServer::new();
+    let (_, mut client) = server.add_client::<State>();
+    let qh = client.event_queue.handle();
+
+    for priority in [QueuePriority::Low, QueuePriority::Normal, QueuePriority::High] {
+        client.display.sync(&qh.with_priority(priority), priority);
+    }
+    client.conn.flush().unwrap();
+    server.display.dispatch_clients(&mut ()).unwrap();
+    server.display.flush_clients().unwrap();
+
+    // the events are all read at once, and dispatched from the highest priority
+    client.conn.prepare_read().unwrap().read().unwrap();
+    let mut state = State { done: Vec::new() };
+    assert_eq!(client.event_queue.dispatch_pending(&mut state).unwrap(), 3);
+    assert_eq!(state.done, [QueuePriority::High, QueuePriority::Normal, QueuePriority::Low]);
+}
@@ -0,0 +1,111 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayc::backend::FlushPolicy;
+use ways::protocol::{wl_compositor, wl_surface};
+
+#[test]
+fn dispatching_follows_the_flush_policy() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler { surfaces: 0 };
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    assert_eq!(client.conn.flush_policy(), FlushPolicy::Dispatch);
+
+    // by default, waiting for the server flushes the requests
+    let waker = client.event_queue.waker().unwrap();
+    compositor.create_surface(&qh, ());
+    waker.wake();
+    client.event_queue.blocking_dispatch(&mut client_ddata).unwrap();
+    server.answer(&mut server_ddata);
+    assert_eq!(server_ddata.surfaces, 1);
+
+    // otherwise they wait for an explicit flush
+    for policy in [FlushPolicy::OnFull, FlushPolicy::Manual] {
+        client.conn.set_flush_policy(policy);
+        compositor.create_surface(&qh, ());
+        waker.wake();
+        client.event_queue.blocking_dispatch(&mut client_ddata).unwrap();
+        server.answer(&mut server_ddata);
+        assert_eq!(server_ddata.surfaces, 1);
+
+        client.conn.flush().unwrap();
+        assert_eq!(client.conn.pending_bytes(), 0);
+        server.answer(&mut server_ddata);
+        assert_eq!(server_ddata.surfaces, 2);
+        server_ddata.surfaces = 1;
+    }
+
+    // roundtrips flush whatever the policy, even if the server does not answer in time
+    compositor.create_surface(&qh, ());
+    assert!(client
+        .event_queue
+        .roundtrip_timeout(&mut client_ddata, std::time::Duration::from_millis(10))
+        .is_err());
+    server.answer(&mut server_ddata);
+    assert_eq!(server_ddata.surfaces, 2);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    surfaces: usize,
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            init.init(id, ());
+            state.surfaces += 1;
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [wl_surface::WlSurface]);
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface
+]);
@@ -0,0 +1,165 @@
+#[macro_use]
+mod helpers;
+
+use futures_util::FutureExt;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayc::frame::FrameScheduler;
+use ways::protocol::{wl_callback, wl_compositor, wl_surface};
+
+#[test]
+fn coalesced_frame_requests() {
+    let (mut server, mut client, mut server_ddata, mut client_ddata, surface) = setup();
+    let scheduler = FrameScheduler::new();
+
+    let mut first = scheduler.request_frame(&surface).unwrap();
+    let second = scheduler.request_frame(&surface).unwrap();
+    surface.commit();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    // both requests share the same callback
+    assert_eq!(server_ddata.callbacks.len(), 1);
+    assert_eq!((&mut first).now_or_never(), None);
+
+    server_ddata.callbacks.remove(0).done(42);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(first.now_or_never(), Some(42));
+    assert_eq!(second.now_or_never(), Some(42));
+
+    // the next request gets a new callback
+    let third = scheduler.request_frame(&surface).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.callbacks.len(), 1);
+    server_ddata.callbacks.remove(0).done(58);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(third.now_or_never(), Some(58));
+}
+
+#[test]
+fn throttled_redraws() {
+    let (mut server, mut client, mut server_ddata, mut client_ddata, surface) = setup();
+    let scheduler = FrameScheduler::new();
+    assert!(scheduler.take_redraws().is_empty());
+    let mut redraws = scheduler.redraws();
+    assert_eq!((&mut redraws).now_or_never(), None);
+
+    // several redraws before drawing are drawn once
+    scheduler.request_redraw(&surface);
+    scheduler.request_redraw(&surface);
+    assert_eq!(redraws.now_or_never(), Some(vec![surface.clone()]));
+    surface.commit();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.callbacks.len(), 1);
+
+    // the next redraw waits for the frame
+    scheduler.request_redraw(&surface);
+    assert!(scheduler.take_redraws().is_empty());
+    assert_eq!(scheduler.redraws().now_or_never(), None);
+    server_ddata.callbacks.remove(0).done(16);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(scheduler.take_redraws(), std::slice::from_ref(&surface));
+    assert!(scheduler.take_redraws().is_empty());
+
+    // a frame without redraw requested draws nothing
+    surface.commit();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    server_ddata.callbacks.remove(0).done(32);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(scheduler.take_redraws().is_empty());
+
+    scheduler.request_redraw(&surface);
+    scheduler.forget(&surface);
+    assert!(scheduler.take_redraws().is_empty());
+}
+
+fn setup() -> (
+    TestServer<ServerHandler>,
+    helpers::TestClient<ClientHandler>,
+    ServerHandler,
+    ClientHandler,
+    wayc::protocol::wl_surface::WlSurface,
+) {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler { callbacks: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let qh = client.event_queue.handle();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let surface = compositor.create_surface(&qh, ());
+    (server, client, server_ddata, client_ddata, surface)
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    callbacks: Vec<wl_callback::WlCallback>,
+}
+
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+server_ignore_impl!(ServerHandler => [wl_callback::WlCallback]);
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl ways::Dispatch<wl_surface::WlSurface, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_surface::WlSurface,
+        request: wl_surface::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_surface::Request::Frame { callback } = request {
+            state.callbacks.push(data_init.init(callback, ()));
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface
+]);
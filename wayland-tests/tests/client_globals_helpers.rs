@@ -13,8 +13,11 @@ use ways::protocol::wl_compositor::WlCompositor as ServerCompositor;
 use ways::protocol::wl_output::WlOutput as ServerOutput;
 use ways::protocol::wl_shell::WlShell as ServerShell;
 
-use wayc::globals::{registry_queue_init, Global, GlobalListContents};
-use wayc::protocol::{wl_compositor, wl_registry, wl_subcompositor};
+use wayc::globals::{registry_queue_init, Global, GlobalListContents, GlobalTracker, TrackerEvent};
+use wayc::{
+    protocol::{wl_compositor, wl_output, wl_registry, wl_subcompositor},
+    Proxy,
+};
 
 #[test]
 fn client_global_helpers_init() {
@@ -145,6 +148,115 @@ fn client_global_helpers_dynamic() {
     server_thread.join().unwrap();
 }
 
+#[test]
+fn client_global_helpers_wait_for() {
+    let kill_switch = Arc::new(AtomicBool::new(false));
+    let server_kill_switch = kill_switch.clone();
+
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, ServerCompositor, _>(4, ());
+    server.display.handle().create_global::<ServerHandler, ServerShell, _>(1, ());
+
+    let (_, client) = server.add_client::<()>();
+
+    let (tx, rx) = sync_channel(0);
+
+    let server_thread = ::std::thread::spawn(move || loop {
+        if let Ok(()) = rx.try_recv() {
+            server.display.handle().create_global::<ServerHandler, ServerOutput, _>(2, ());
+        }
+        server.display.dispatch_clients(&mut ServerHandler).unwrap();
+        server.display.flush_clients().unwrap();
+        if server_kill_switch.load(Ordering::Acquire) {
+            break;
+        }
+    });
+
+    let (globals, mut queue) = registry_queue_init::<ClientHandler>(&client.conn).unwrap();
+    let mut state = ClientHandler(false);
+
+    // an advertised global is returned immediately
+    assert_eq!(
+        globals.wait_for::<wl_compositor::WlCompositor, _>(&mut queue, &mut state, 1..=5).unwrap(),
+        Global { name: 1, interface: "wl_compositor".into(), version: 4 }
+    );
+
+    // the wl_output is created later
+    tx.send(()).unwrap();
+    assert_eq!(
+        globals.wait_for::<wl_output::WlOutput, _>(&mut queue, &mut state, 2..=4).unwrap(),
+        Global { name: 3, interface: "wl_output".into(), version: 2 }
+    );
+    assert!(state.0);
+
+    // cleanup
+    kill_switch.store(true, Ordering::Release);
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn client_global_tracker() {
+    let kill_switch = Arc::new(AtomicBool::new(false));
+    let server_kill_switch = kill_switch.clone();
+
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, ServerCompositor, _>(4, ());
+    server.display.handle().create_global::<ServerHandler, ServerOutput, _>(2, ());
+
+    let (_, client) = server.add_client::<()>();
+
+    let (tx, rx) = sync_channel(0);
+
+    let server_thread = ::std::thread::spawn(move || {
+        let mut outputs = Vec::new();
+        loop {
+            match rx.try_recv() {
+                Ok(true) => outputs.push(
+                    server.display.handle().create_global::<ServerHandler, ServerOutput, _>(3, ()),
+                ),
+                Ok(false) => {
+                    server.display.handle().remove_global::<ServerHandler>(outputs.remove(0))
+                }
+                Err(_) => {}
+            }
+            server.display.dispatch_clients(&mut ServerHandler).unwrap();
+            server.display.flush_clients().unwrap();
+            if server_kill_switch.load(Ordering::Acquire) {
+                break;
+            }
+        }
+    });
+
+    let (globals, mut queue) = registry_queue_init::<TrackerHandler>(&client.conn).unwrap();
+    let mut state = TrackerHandler { outputs: GlobalTracker::new(1..=2), added: 0, removed: 0 };
+
+    // the initial output is bound
+    let bound = state.outputs.init(&globals, &queue.handle(), || ());
+    assert_eq!(bound.len(), 1);
+    assert_eq!(bound[0].version(), 2);
+
+    // a second output appears and is bound too
+    tx.send(true).unwrap();
+    while state.added == 0 {
+        queue.blocking_dispatch(&mut state).unwrap();
+    }
+    let names = state.outputs.instances().iter().map(|&(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(names, [2, 3]);
+    assert!(state.outputs.instances().iter().all(|(_, output)| output.version() == 2));
+
+    // and is forgotten once removed
+    tx.send(false).unwrap();
+    while state.removed == 0 {
+        queue.blocking_dispatch(&mut state).unwrap();
+    }
+    assert_eq!(state.outputs.instances().len(), 1);
+    assert_eq!(state.outputs.instances()[0].0, 2);
+
+    // cleanup
+    kill_switch.store(true, Ordering::Release);
+    server_thread.join().unwrap();
+}
+
 #[test]
 #[should_panic]
 fn too_high_global_version() {
@@ -215,3 +327,31 @@ client_ignore_impl!(ClientHandler => [
     wl_compositor::WlCompositor,
     wl_subcompositor::WlSubcompositor
 ]);
+
+struct TrackerHandler {
+    outputs: GlobalTracker<wl_output::WlOutput>,
+    added: usize,
+    removed: usize,
+}
+
+impl wayc::Dispatch<wl_registry::WlRegistry, GlobalListContents> for TrackerHandler {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &wayc::Connection,
+        qh: &wayc::QueueHandle<Self>,
+    ) {
+        match state.outputs.handle_event(registry, &event, qh, || ()) {
+            Some(TrackerEvent::Added(_)) => state.added += 1,
+            Some(TrackerEvent::Removed(name, _)) => {
+                assert_eq!(name, 3);
+                state.removed += 1;
+            }
+            None => {}
+        }
+    }
+}
+
+client_ignore_impl!(TrackerHandler => [wl_output::WlOutput]);
@@ -0,0 +1,227 @@
+#![cfg(feature = "xkbcommon")]
+
+use std::{io::Write, os::unix::io::OwnedFd, time::Duration};
+
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::protocol::{wl_compositor, wl_keyboard, wl_pointer, wl_seat, wl_surface, wl_touch};
+use ways::seat::{Seat, SeatData};
+
+use wayc::keyboard::{KeyEvent, Keyboard, KeyboardEvent, Modifiers, RepeatInfo};
+use wayc::protocol::{
+    wl_compositor::WlCompositor as ClientCompositor, wl_keyboard::WlKeyboard as ClientKeyboard,
+    wl_seat::WlSeat as ClientSeat,
+};
+
+// A self-contained keymap with a letter and a shift key, to not depend on the system xkb data
+const KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes "test" {
+        minimum = 8;
+        maximum = 255;
+        <AC01> = 38;
+        <LFSH> = 50;
+    };
+    xkb_types "test" {
+        type "ONE_LEVEL" {
+            modifiers = none;
+            level_name[Level1] = "Any";
+        };
+        type "ALPHABETIC" {
+            modifiers = Shift + Lock;
+            map[Shift] = Level2;
+            map[Lock] = Level2;
+            level_name[Level1] = "Base";
+            level_name[Level2] = "Caps";
+        };
+    };
+    xkb_compat "test" {
+        interpret Any + AnyOf(all) {
+            action = SetMods(modifiers = modMapMods, clearLocks);
+        };
+    };
+    xkb_symbols "test" {
+        key <AC01> { type = "ALPHABETIC", [ a, A ] };
+        key <LFSH> { type = "ONE_LEVEL", repeat = No, [ Shift_L ] };
+        modifier_map Shift { <LFSH> };
+    };
+};
+"#;
+
+const KEY_A: u32 = 30;
+const KEY_LEFTSHIFT: u32 = 42;
+// keysyms of xkbcommon-keysyms.h
+const KEYSYM_A: u32 = 0x41;
+const KEYSYM_LOWER_A: u32 = 0x61;
+const KEYSYM_SHIFT_L: u32 = 0xffe1;
+
+fn keymap_fd() -> (OwnedFd, u32) {
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(KEYMAP.as_bytes()).unwrap();
+    file.write_all(&[0]).unwrap();
+    (file.into(), KEYMAP.len() as u32 + 1)
+}
+
+#[test]
+fn keyboard_decodes_keys() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let seat = Seat::new::<ServerHandler>(&server.display.handle(), "seat0");
+    seat.set_capabilities(wl_seat::Capability::Keyboard);
+    let (fd, size) = keymap_fd();
+    seat.set_keymap(wl_keyboard::KeymapFormat::XkbV1, fd, size);
+    seat.set_repeat_info(30, 200);
+    let mut server_ddata = ServerHandler { surfaces: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler {
+        globals: globals::GlobalList::new(),
+        keyboard: Keyboard::new().unwrap(),
+        events: Vec::new(),
+    };
+
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let client_seat =
+        client_ddata.globals.bind::<ClientSeat, _, _>(&qh, &registry, 9..10, ()).unwrap();
+    let compositor =
+        client_ddata.globals.bind::<ClientCompositor, _, _>(&qh, &registry, 1..2, ()).unwrap();
+    compositor.create_surface(&qh, ());
+    client_seat.get_keyboard(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(client_ddata.keyboard.has_keymap());
+    assert_eq!(
+        client_ddata.keyboard.repeat_info(),
+        RepeatInfo { rate: 30, delay: Duration::from_millis(200) }
+    );
+    assert!(client_ddata.events.is_empty());
+
+    // keys are decoded according to the state of the modifiers
+    let surface = server_ddata.surfaces[0].clone();
+    seat.set_keyboard_focus(Some(&surface), &[KEY_A]);
+    seat.keyboard_key(1, KEY_LEFTSHIFT, wl_keyboard::KeyState::Pressed);
+    seat.keyboard_modifiers(1, 0, 0, 0);
+    seat.keyboard_key(2, KEY_A, wl_keyboard::KeyState::Released);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let events = std::mem::take(&mut client_ddata.events);
+    assert_eq!(events.len(), 4);
+    assert!(matches!(
+        &events[0],
+        KeyboardEvent::Enter { keys, .. }
+            if keys == &[KeyEvent { time: 0, raw_code: KEY_A, keysym: KEYSYM_LOWER_A, utf8: Some("a".into()) }]
+    ));
+    assert!(matches!(
+        &events[1],
+        KeyboardEvent::Press { event, .. }
+            if event.keysym == KEYSYM_SHIFT_L && event.utf8.is_none()
+    ));
+    assert!(matches!(
+        &events[2],
+        KeyboardEvent::Modifiers { modifiers, .. }
+            if *modifiers == Modifiers { shift: true, ..Default::default() }
+    ));
+    assert!(matches!(
+        &events[3],
+        KeyboardEvent::Release { event, .. }
+            if event.time == 2 && event.keysym == KEYSYM_A && event.utf8.as_deref() == Some("A")
+    ));
+
+    // keys which do not repeat are not repeated
+    assert_eq!(client_ddata.keyboard.repeat_deadline(), None);
+
+    // a held key repeats after the delay, at the repeat rate
+    seat.keyboard_key(100, KEY_A, wl_keyboard::KeyState::Pressed);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let deadline = client_ddata.keyboard.repeat_deadline().unwrap();
+    assert!(client_ddata.keyboard.repeat(deadline - Duration::from_millis(1)).is_empty());
+    let repeats = client_ddata.keyboard.repeat(deadline);
+    assert!(matches!(
+        &repeats[..],
+        [KeyboardEvent::Repeat(KeyEvent { time: 300, keysym: KEYSYM_A, .. })]
+    ));
+    let repeats = client_ddata.keyboard.repeat(deadline + Duration::from_secs(1) / 30 * 2);
+    assert_eq!(repeats.len(), 2);
+
+    // and stops when released
+    seat.keyboard_key(500, KEY_A, wl_keyboard::KeyState::Released);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(client_ddata.keyboard.repeat_deadline(), None);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    surfaces: Vec<wl_surface::WlSurface>,
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            state.surfaces.push(init.init(id, ()));
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [wl_surface::WlSurface]);
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_pointer::WlPointer: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_keyboard::WlKeyboard: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_touch::WlTouch: SeatData] => Seat);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    keyboard: Keyboard,
+    events: Vec<KeyboardEvent>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<ClientKeyboard, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientKeyboard,
+        event: wayc::protocol::wl_keyboard::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.events.extend(state.keyboard.handle_event(event));
+    }
+}
+
+client_ignore_impl!(ClientHandler => [
+    ClientSeat,
+    ClientCompositor,
+    wayc::protocol::wl_surface::WlSurface
+]);
@@ -187,6 +187,71 @@ fn dead_object_argument() {
     assert!(client_ddata.entered);
 }
 
+#[test]
+fn send_constructor_with_version() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_compositor::WlCompositor, _>(1, ());
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(3, ());
+    let mut server_ddata = ServerHandler { output: None };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the registry binds objects with the version given in the request
+    let name = client_ddata
+        .globals
+        .list()
+        .iter()
+        .find(|global| global.interface == "wl_output")
+        .map(|global| global.name)
+        .unwrap();
+    let output: wayc::protocol::wl_output::WlOutput = registry
+        .send_constructor_with_version(
+            wayc::protocol::wl_registry::Request::Bind {
+                name,
+                id: (wayc::protocol::wl_output::WlOutput::interface(), 3),
+            },
+            3,
+            client.event_queue.handle().make_data::<wayc::protocol::wl_output::WlOutput, _>(()),
+        )
+        .unwrap();
+    assert_eq!(output.version(), 3);
+
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            0,
+        )
+        .unwrap();
+    let surface: wayc::protocol::wl_surface::WlSurface = compositor
+        .send_constructor_with_version(
+            wayc::protocol::wl_compositor::Request::CreateSurface {},
+            1,
+            client.event_queue.handle().make_data::<wayc::protocol::wl_surface::WlSurface, _>(()),
+        )
+        .unwrap();
+    assert_eq!(surface.version(), 1);
+    output.release();
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    assert_eq!(server_ddata.output.as_ref().map(|output| output.version()), Some(3));
+    assert!(client_ddata.entered);
+}
+
 struct ServerHandler {
     output: Option<ways::protocol::wl_output::WlOutput>,
 }
@@ -0,0 +1,82 @@
+#![cfg(feature = "raw-window-handle")]
+
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
+};
+use ways::protocol::{wl_compositor, wl_surface};
+
+#[test]
+fn surface_handles_are_libwayland_pointers() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler;
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let surface = compositor.create_surface(&qh, ());
+
+    let handle = wayc::SurfaceHandle::new(&client.conn, &surface);
+    match handle.raw_display_handle() {
+        RawDisplayHandle::Wayland(display) => {
+            assert_eq!(display.display, client.conn.backend().display_ptr().cast())
+        }
+        _ => panic!("Not a Wayland display handle"),
+    }
+    match handle.raw_window_handle() {
+        RawWindowHandle::Wayland(window) => {
+            assert_eq!(window.surface, wayc::Proxy::id(&surface).as_ptr().cast())
+        }
+        _ => panic!("Not a Wayland window handle"),
+    }
+
+    // the handle of a destroyed surface is null
+    surface.destroy();
+    match surface.raw_window_handle() {
+        RawWindowHandle::Wayland(window) => assert!(window.surface.is_null()),
+        _ => panic!("Not a Wayland window handle"),
+    }
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler;
+
+server_ignore_impl!(ServerHandler => [wl_compositor::WlCompositor, wl_surface::WlSurface]);
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface
+]);
@@ -0,0 +1,111 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{wayc, ways, DumbClientData};
+
+use ways::protocol::wl_output::WlOutput as ServerOutput;
+
+use std::cell::RefCell;
+use std::os::unix::net::UnixStream;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use wayc::{globals::GlobalListContents, protocol::wl_registry, reconnect::Reconnect};
+
+// run a compositor advertising `outputs` globals in its own thread, until `stop` is set
+fn spawn_server(outputs: usize, stop: Arc<AtomicBool>) -> UnixStream {
+    let (server_socket, client_socket) = UnixStream::pair().unwrap();
+    std::thread::spawn(move || {
+        let mut display = ways::Display::<ServerData>::new().unwrap();
+        for _ in 0..outputs {
+            display.handle().create_global::<ServerData, ServerOutput, _>(1, ());
+        }
+        display.handle().insert_client(server_socket, Arc::new(DumbClientData)).unwrap();
+        while !stop.load(Ordering::SeqCst) {
+            display.dispatch_clients(&mut ServerData).unwrap();
+            display.flush_clients().unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    });
+    client_socket
+}
+
+#[test]
+fn reconnect_after_compositor_restart() {
+    let servers = Rc::new(RefCell::new(Vec::<Arc<AtomicBool>>::new()));
+    let servers2 = servers.clone();
+    let mut reconnect = Reconnect::new(
+        move || {
+            let stop = Arc::new(AtomicBool::new(false));
+            let mut servers = servers2.borrow_mut();
+            // each new compositor advertises one more output than the previous one
+            let socket = spawn_server(servers.len() + 1, stop.clone());
+            servers.push(stop);
+            Ok(wayc::Connection::from_socket(socket).unwrap())
+        },
+        |state: &mut ClientHandler, _, globals, _| {
+            state.generations.push(globals.contents().clone_list().len());
+        },
+    );
+    reconnect.set_retry_delay(Duration::from_millis(1));
+    reconnect.on_connection_lost(|state, _| state.lost += 1);
+
+    let mut state = ClientHandler { generations: Vec::new(), lost: 0 };
+    reconnect.connect(&mut state).unwrap();
+    assert!(reconnect.connection().is_some());
+    assert_eq!(state.generations, [1]);
+
+    // the compositor goes away
+    servers.borrow()[0].store(true, Ordering::SeqCst);
+    while state.lost == 0 {
+        reconnect.blocking_dispatch(&mut state).unwrap();
+    }
+
+    // and the globals of the new one were given to the setup callback
+    assert_eq!(state.lost, 1);
+    assert_eq!(state.generations, [1, 2]);
+    assert_eq!(servers.borrow().len(), 2);
+
+    servers.borrow()[1].store(true, Ordering::SeqCst);
+}
+
+#[test]
+fn reconnect_gives_up() {
+    let mut reconnect = Reconnect::new(
+        || Err(wayc::ConnectError::NoCompositor),
+        |_: &mut ClientHandler, _, _, _| panic!("Connected to no compositor"),
+    );
+    reconnect.set_retry_delay(Duration::from_millis(1));
+    reconnect.set_max_attempts(Some(3));
+
+    let mut state = ClientHandler { generations: Vec::new(), lost: 0 };
+    assert!(matches!(
+        reconnect.blocking_dispatch(&mut state),
+        Err(wayc::reconnect::ReconnectError::Connect(wayc::ConnectError::NoCompositor))
+    ));
+    assert!(reconnect.connection().is_none());
+}
+
+struct ClientHandler {
+    generations: Vec<usize>,
+    lost: usize,
+}
+
+impl wayc::Dispatch<wl_registry::WlRegistry, GlobalListContents> for ClientHandler {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+    }
+}
+
+struct ServerData;
+
+server_ignore_impl!(ServerData => [ServerOutput]);
+server_ignore_global_impl!(ServerData => [ServerOutput]);
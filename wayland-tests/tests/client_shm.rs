@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::io::OwnedFd;
+
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayc::shm::{DoubleMemPool, MemPool, ShmError};
+
+use ways::protocol::wl_buffer::WlBuffer as ServerBuffer;
+
+#[test]
+fn mem_pool_buffer_release() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_compositor::WlCompositor, _>(1, ());
+    server.display.handle().create_global::<ServerHandler, ways::protocol::wl_shm::WlShm, _>(1, ());
+    let mut server_ddata = ServerHandler::default();
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let shm = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_shm::WlShm, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+    let surface = compositor.create_surface(&client.event_queue.handle(), ());
+
+    let mut pool = MemPool::new(&shm, 16).unwrap();
+    // a buffer which does not fit is refused before reaching the server
+    assert!(matches!(
+        pool.create_buffer(8, 2, 2, 8, wayc::protocol::wl_shm::Format::Argb8888),
+        Err(ShmError::OutOfBounds)
+    ));
+    pool.resize(32).unwrap();
+    let buffer = pool.create_buffer(16, 2, 2, 8, wayc::protocol::wl_shm::Format::Argb8888).unwrap();
+    pool.buffer_contents(&buffer).unwrap().copy_from_slice(b"I like trains!!!");
+    buffer.attach_to(&surface);
+    assert!(buffer.is_busy());
+    assert!(pool.is_used());
+    assert!(pool.buffer_contents(&buffer).is_none());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the server sees the resized pool and what the client wrote
+    assert_eq!(server_ddata.pool_size, Some(32));
+    let mut contents = Vec::new();
+    File::from(server_ddata.fd.take().unwrap()).read_to_end(&mut contents).unwrap();
+    assert_eq!(&contents[16..], b"I like trains!!!");
+    let server_buffer = server_ddata.attached.take().unwrap();
+    assert_eq!(server_ddata.buffers, std::slice::from_ref(&server_buffer));
+
+    // once released, the buffer can be drawn into again
+    server_buffer.release();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(!buffer.is_busy());
+    assert!(!pool.is_used());
+    assert_eq!(pool.buffer_contents(&buffer).unwrap(), b"I like trains!!!");
+
+    // the pools of a double pool are used alternately
+    let mut double = DoubleMemPool::new(&shm, 16).unwrap();
+    let first = double.pool().unwrap();
+    let busy = first.create_buffer(0, 2, 2, 8, wayc::protocol::wl_shm::Format::Argb8888).unwrap();
+    // the other pool does not give access to the buffer
+    assert!(double.pool().unwrap().buffer_contents(&busy).is_none());
+    busy.attach_to(&surface);
+    // only the second pool is free
+    let second = double.pool().unwrap();
+    assert!(!second.is_used());
+    let other = second.create_buffer(0, 2, 2, 8, wayc::protocol::wl_shm::Format::Argb8888).unwrap();
+    other.attach_to(&surface);
+    assert!(double.pool().is_none());
+}
+
+/*
+ * Server Handler
+ */
+
+#[derive(Default)]
+struct ServerHandler {
+    fd: Option<OwnedFd>,
+    pool_size: Option<i32>,
+    buffers: Vec<ServerBuffer>,
+    attached: Option<ServerBuffer>,
+}
+
+impl ways::Dispatch<ways::protocol::wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &ways::protocol::wl_compositor::WlCompositor,
+        request: ways::protocol::wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let ways::protocol::wl_compositor::Request::CreateSurface { id } = request {
+            init.init(id, ());
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+impl ways::Dispatch<ways::protocol::wl_surface::WlSurface, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &ways::protocol::wl_surface::WlSurface,
+        request: ways::protocol::wl_surface::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let ways::protocol::wl_surface::Request::Attach { buffer, .. } = request {
+            state.attached = buffer;
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+impl ways::Dispatch<ways::protocol::wl_shm::WlShm, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &ways::protocol::wl_shm::WlShm,
+        request: ways::protocol::wl_shm::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let ways::protocol::wl_shm::Request::CreatePool { fd, size, id } = request {
+            // only keep the first pool
+            if state.fd.is_none() {
+                state.fd = Some(fd);
+                state.pool_size = Some(size);
+            }
+            init.init(id, ());
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+impl ways::Dispatch<ways::protocol::wl_shm_pool::WlShmPool, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &ways::protocol::wl_shm_pool::WlShmPool,
+        request: ways::protocol::wl_shm_pool::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            ways::protocol::wl_shm_pool::Request::CreateBuffer { id, .. } => {
+                state.buffers.push(init.init(id, ()));
+            }
+            ways::protocol::wl_shm_pool::Request::Resize { size } => {
+                state.pool_size = Some(size);
+            }
+            _ => {}
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [
+    ways::protocol::wl_buffer::WlBuffer
+]);
+
+server_ignore_global_impl!(ServerHandler => [
+    ways::protocol::wl_shm::WlShm,
+    ways::protocol::wl_compositor::WlCompositor
+]);
+
+/*
+ * Client Handler
+ */
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    wayc::protocol::wl_shm::WlShm
+]);
@@ -0,0 +1,172 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::protocol::{wl_compositor, wl_keyboard, wl_pointer, wl_seat, wl_surface, wl_touch};
+use ways::seat::{Seat, SeatData};
+
+use wayc::protocol::{
+    wl_compositor::WlCompositor as ClientCompositor, wl_seat::WlSeat as ClientSeat,
+    wl_touch::WlTouch as ClientTouch,
+};
+use wayc::touch::{Touch, TouchEvent};
+
+#[test]
+fn touch_batches_frames() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let seat = Seat::new::<ServerHandler>(&server.display.handle(), "seat0");
+    seat.set_capabilities(wl_seat::Capability::Touch);
+    let mut server_ddata = ServerHandler { surfaces: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler {
+        globals: globals::GlobalList::new(),
+        touch: Touch::new(),
+        events: Vec::new(),
+    };
+
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let client_seat =
+        client_ddata.globals.bind::<ClientSeat, _, _>(&qh, &registry, 9..10, ()).unwrap();
+    let compositor =
+        client_ddata.globals.bind::<ClientCompositor, _, _>(&qh, &registry, 1..2, ()).unwrap();
+    let client_surface = compositor.create_surface(&qh, ());
+    client_seat.get_touch(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let surface = server_ddata.surfaces[0].clone();
+
+    // the points going down together are delivered in one frame
+    let first = seat.touch_down(&surface, 1, 0, 1.0, 2.0);
+    let second = seat.touch_down(&surface, 1, 1, 5.0, 5.0);
+    seat.touch_frame();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let events = std::mem::take(&mut client_ddata.events);
+    assert_eq!(events.len(), 1);
+    let frame = match &events[0] {
+        TouchEvent::Frame(frame) => frame,
+        event => panic!("Unexpected event: {:?}", event),
+    };
+    assert_eq!(frame.updates.len(), 2);
+    assert_eq!(frame.updates[0].down, Some(first));
+    assert_eq!(frame.updates[0].point.surface, client_surface);
+    assert_eq!(frame.updates[0].point.position, (1.0, 2.0));
+    assert_eq!(frame.updates[1].down, Some(second));
+    assert_eq!(client_ddata.touch.points().len(), 2);
+
+    // the motions of a point are summed over the frame
+    seat.touch_motion(2, 0, 4.0, 6.0);
+    seat.touch_motion(3, 0, 5.0, 8.0);
+    seat.touch_frame();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let events = std::mem::take(&mut client_ddata.events);
+    let frame = match &events[..] {
+        [TouchEvent::Frame(frame)] => frame,
+        events => panic!("Unexpected events: {:?}", events),
+    };
+    assert_eq!(frame.updates.len(), 1);
+    let update = &frame.updates[0];
+    assert_eq!((update.point.id, update.down, update.up), (0, None, None));
+    assert_eq!(update.delta, (4.0, 6.0));
+    assert_eq!(update.point.position, (5.0, 8.0));
+    assert_eq!(update.time, 3);
+
+    // a point going up is forgotten
+    let up = seat.touch_up(4, 1);
+    seat.touch_frame();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let events = std::mem::take(&mut client_ddata.events);
+    assert!(matches!(
+        &events[..],
+        [TouchEvent::Frame(frame)] if frame.updates.len() == 1 && frame.updates[0].up == Some(up)
+    ));
+    assert_eq!(client_ddata.touch.points().len(), 1);
+    assert_eq!(client_ddata.touch.points()[0].position, (5.0, 8.0));
+
+    // cancelling gives back the remaining points
+    seat.touch_cancel();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let events = std::mem::take(&mut client_ddata.events);
+    assert!(matches!(
+        &events[..],
+        [TouchEvent::Cancel(points)] if points.len() == 1 && points[0].id == 0
+    ));
+    assert!(client_ddata.touch.points().is_empty());
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    surfaces: Vec<wl_surface::WlSurface>,
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            state.surfaces.push(init.init(id, ()));
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [wl_surface::WlSurface]);
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_pointer::WlPointer: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_keyboard::WlKeyboard: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_touch::WlTouch: SeatData] => Seat);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    touch: Touch,
+    events: Vec<TouchEvent>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<ClientTouch, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientTouch,
+        event: wayc::protocol::wl_touch::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.events.extend(state.touch.handle_event(event));
+    }
+}
+
+client_ignore_impl!(ClientHandler => [
+    ClientSeat,
+    ClientCompositor,
+    wayc::protocol::wl_surface::WlSurface
+]);
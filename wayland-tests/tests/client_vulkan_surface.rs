@@ -0,0 +1,123 @@
+#![cfg(feature = "vk")]
+
+use std::os::raw::c_void;
+use std::sync::Mutex;
+
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayland_vk::{
+    Error, SurfaceLoader, VkInstance, VkResult, VkSurfaceKHR, VkWaylandSurfaceCreateInfoKHR,
+    VK_STRUCTURE_TYPE_WAYLAND_SURFACE_CREATE_INFO_KHR, VK_SUCCESS,
+};
+use ways::protocol::{wl_compositor, wl_surface};
+
+// the display and surface pointers given to the fake vkCreateWaylandSurfaceKHR
+static CREATED: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+unsafe extern "system" fn create_wayland_surface(
+    _: VkInstance,
+    info: *const VkWaylandSurfaceCreateInfoKHR,
+    _: *const c_void,
+    surface: *mut VkSurfaceKHR,
+) -> VkResult {
+    let info = unsafe { &*info };
+    assert_eq!(info.s_type, VK_STRUCTURE_TYPE_WAYLAND_SURFACE_CREATE_INFO_KHR);
+    assert!(info.p_next.is_null());
+    let mut created = CREATED.lock().unwrap();
+    created.push((info.display as usize, info.surface as usize));
+    unsafe { *surface = created.len() as u64 };
+    VK_SUCCESS
+}
+
+unsafe extern "system" fn get_instance_proc_addr(
+    _: VkInstance,
+    name: *const std::os::raw::c_char,
+) -> Option<unsafe extern "system" fn()> {
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    if name.to_bytes() == b"vkCreateWaylandSurfaceKHR" {
+        // SAFETY: the loader transmutes it back to its signature
+        Some(unsafe {
+            std::mem::transmute::<
+                wayland_vk::PFN_vkCreateWaylandSurfaceKHR,
+                unsafe extern "system" fn(),
+            >(create_wayland_surface)
+        })
+    } else {
+        None
+    }
+}
+
+#[test]
+fn vulkan_surface_from_wl_surface() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler;
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let surface = compositor.create_surface(&qh, ());
+
+    let instance = 0x1000 as VkInstance;
+    let loader = unsafe { SurfaceLoader::new(instance, get_instance_proc_addr) }.unwrap();
+    assert_eq!(loader.instance(), instance);
+    let vk_surface =
+        unsafe { loader.create_surface(&client.conn, &surface, std::ptr::null()) }.unwrap();
+    assert_eq!(vk_surface, 1);
+    assert_eq!(
+        CREATED.lock().unwrap()[0],
+        (client.conn.backend().display_ptr() as usize, wayc::Proxy::id(&surface).as_ptr() as usize)
+    );
+
+    // surfaces of another connection are rejected
+    let (_, other) = server.add_client::<()>();
+    let result = unsafe { loader.create_surface(&other.conn, &surface, std::ptr::null()) };
+    assert_eq!(result, Err(Error::ForeignSurface));
+
+    // as are destroyed surfaces
+    surface.destroy();
+    let result = unsafe { loader.create_surface(&client.conn, &surface, std::ptr::null()) };
+    assert_eq!(result, Err(Error::InvalidId));
+    assert_eq!(CREATED.lock().unwrap().len(), 1);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler;
+
+server_ignore_impl!(ServerHandler => [wl_compositor::WlCompositor, wl_surface::WlSurface]);
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface
+]);
@@ -3,9 +3,11 @@ mod helpers;
 
 use helpers::{globals, roundtrip, wayc, ways, TestServer};
 
+use ways::backend::DisconnectReason;
+
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, Mutex,
 };
 
 #[test]
@@ -116,6 +118,170 @@ fn client_destructor_cleanup() {
     assert!(destructor_called.load(Ordering::Acquire));
 }
 
+#[test]
+fn client_destroy_listener() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(3, ());
+    let mut server_ddata = ServerHandler { destructor_called: Arc::new(AtomicBool::new(false)) };
+
+    let (s_client, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            3..4,
+            (),
+        )
+        .unwrap();
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let handle = server.display.handle();
+    let listener_called = Arc::new(AtomicBool::new(false));
+    let called = listener_called.clone();
+    let client_id = s_client.id();
+    s_client
+        .add_destroy_listener(&handle, move |_, state: &mut ServerHandler, id, reason| {
+            assert_eq!(id, client_id);
+            assert!(matches!(reason, DisconnectReason::ConnectionClosed));
+            // the resources of the client were destroyed first
+            assert!(state.destructor_called.load(Ordering::Acquire));
+            called.store(true, Ordering::Release);
+        })
+        .unwrap();
+    let removed = s_client
+        .add_destroy_listener(&handle, |_, _: &mut ServerHandler, _, _| {
+            panic!("removed listener was invoked")
+        })
+        .unwrap();
+    assert!(s_client.remove_destroy_listener(&handle, removed));
+    assert!(!s_client.remove_destroy_listener(&handle, removed));
+
+    ::std::mem::drop(client);
+
+    server.answer(&mut server_ddata);
+
+    assert!(listener_called.load(Ordering::Acquire));
+    assert!(s_client.add_destroy_listener(&handle, |_, _: &mut ServerHandler, _, _| ()).is_err());
+}
+
+#[test]
+fn display_shutdown() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(3, ());
+    let mut server_ddata = ServerHandler { destructor_called: Arc::new(AtomicBool::new(false)) };
+
+    let disconnected = Arc::new(AtomicBool::new(false));
+    let (s_client, mut client) =
+        server.add_client_with_data(Arc::new(DestructorClientData(disconnected.clone())));
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            3..4,
+            (),
+        )
+        .unwrap();
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let handle = server.display.handle();
+    let listener_called = Arc::new(AtomicBool::new(false));
+    let called = listener_called.clone();
+    s_client
+        .add_destroy_listener(&handle, move |_, state: &mut ServerHandler, _, reason| {
+            assert!(matches!(reason, DisconnectReason::ServerShutdown));
+            assert!(state.destructor_called.load(Ordering::Acquire));
+            called.store(true, Ordering::Release);
+        })
+        .unwrap();
+
+    // the client is still connected, its resources are destroyed without dispatching
+    server.display.shutdown(&mut server_ddata, Some("compositor exiting".into()));
+
+    assert!(disconnected.load(Ordering::Acquire));
+    assert!(server_ddata.destructor_called.load(Ordering::Acquire));
+    assert!(listener_called.load(Ordering::Acquire));
+    assert!(s_client.add_destroy_listener(&handle, |_, _: &mut ServerHandler, _, _| ()).is_err());
+
+    // the client received the error before its connection was closed
+    let read = client.conn.prepare_read().unwrap().read();
+    assert!(read.is_err());
+    let error = client.conn.protocol_error().unwrap();
+    assert_eq!(error.object_interface, "wl_display");
+}
+
+#[test]
+fn destructor_order_and_flush() {
+    let mut server = TestServer::new();
+    let destroyed = DestroyOrder::default();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_compositor::WlCompositor, _>(
+            1,
+            destroyed.clone(),
+        );
+    let mut server_ddata = ServerHandler { destructor_called: Arc::new(AtomicBool::new(false)) };
+
+    let (s_client, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+    compositor.create_surface(&client.event_queue.handle(), ());
+    compositor.create_region(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    s_client.kill(
+        &server.display.handle(),
+        wayland_backend::protocol::ProtocolError {
+            code: 0,
+            object_id: 1,
+            object_interface: "wl_display".into(),
+            message: "killed".into(),
+        },
+    );
+    // the destructors are pending until the next dispatch
+    assert!(destroyed.0.lock().unwrap().is_empty());
+
+    // they are invoked in the reverse order of the creation of the resources
+    server.display.flush_destructors(&mut server_ddata);
+    assert_eq!(*destroyed.0.lock().unwrap(), ["wl_region", "wl_surface", "wl_compositor"]);
+}
+
 struct DestructorClientData(Arc<AtomicBool>);
 
 impl ways::backend::ClientData for DestructorClientData {
@@ -171,6 +337,99 @@ impl ways::Dispatch<ways::protocol::wl_output::WlOutput, ServerUData> for Server
     }
 }
 
+#[derive(Clone, Default)]
+struct DestroyOrder(Arc<Mutex<Vec<&'static str>>>);
+
+impl ways::GlobalDispatch<ways::protocol::wl_compositor::WlCompositor, DestroyOrder>
+    for ServerHandler
+{
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        compositor: ways::New<ways::protocol::wl_compositor::WlCompositor>,
+        order: &DestroyOrder,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init(compositor, order.clone());
+    }
+}
+
+impl ways::Dispatch<ways::protocol::wl_compositor::WlCompositor, DestroyOrder> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &ways::protocol::wl_compositor::WlCompositor,
+        request: ways::protocol::wl_compositor::Request,
+        order: &DestroyOrder,
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            ways::protocol::wl_compositor::Request::CreateSurface { id } => {
+                data_init.init(id, order.clone());
+            }
+            ways::protocol::wl_compositor::Request::CreateRegion { id } => {
+                data_init.init(id, order.clone());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        _: &mut Self,
+        _: ways::backend::ClientId,
+        _: &ways::protocol::wl_compositor::WlCompositor,
+        order: &DestroyOrder,
+    ) {
+        order.0.lock().unwrap().push("wl_compositor");
+    }
+}
+
+impl ways::Dispatch<ways::protocol::wl_surface::WlSurface, DestroyOrder> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &ways::protocol::wl_surface::WlSurface,
+        _: ways::protocol::wl_surface::Request,
+        _: &DestroyOrder,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+    }
+
+    fn destroyed(
+        _: &mut Self,
+        _: ways::backend::ClientId,
+        _: &ways::protocol::wl_surface::WlSurface,
+        order: &DestroyOrder,
+    ) {
+        order.0.lock().unwrap().push("wl_surface");
+    }
+}
+
+impl ways::Dispatch<ways::protocol::wl_region::WlRegion, DestroyOrder> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &ways::protocol::wl_region::WlRegion,
+        _: ways::protocol::wl_region::Request,
+        _: &DestroyOrder,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+    }
+
+    fn destroyed(
+        _: &mut Self,
+        _: ways::backend::ClientId,
+        _: &ways::protocol::wl_region::WlRegion,
+        order: &DestroyOrder,
+    ) {
+        order.0.lock().unwrap().push("wl_region");
+    }
+}
+
 struct ClientHandler {
     globals: globals::GlobalList,
 }
@@ -192,5 +451,8 @@ wayc::delegate_dispatch!(ClientHandler:
 );
 
 client_ignore_impl!(ClientHandler => [
-    wayc::protocol::wl_output::WlOutput
+    wayc::protocol::wl_output::WlOutput,
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    wayc::protocol::wl_region::WlRegion
 ]);
@@ -145,6 +145,33 @@ fn invalid_global_version() {
     assert!(roundtrip(&mut client, &mut server, &mut client_ddata, &mut ServerHandler).is_err());
 }
 
+#[test]
+fn global_version_range() {
+    use wayc::protocol::wl_output::WlOutput;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global_with_versions::<ServerHandler, ServerOutput, _>(2..=3, ());
+
+    // the global is advertised with the highest version, and can be bound in the range
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut ServerHandler).unwrap();
+    assert_eq!(client_ddata.globals.list()[0].version, 3);
+    registry.bind::<WlOutput, _, _>(1, 2, &client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut ServerHandler).unwrap();
+
+    // a lower version kills the client
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    registry.bind::<WlOutput, _, _>(1, 1, &client.event_queue.handle(), ());
+    assert!(roundtrip(&mut client, &mut server, &mut client_ddata, &mut ServerHandler).is_err());
+}
+
 #[test]
 fn wrong_global_id() {
     use wayc::protocol::wl_compositor::WlCompositor;
@@ -0,0 +1,164 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayland_protocols::wp::idle_inhibit::{
+    helpers::IdleInhibitors,
+    zv1::{client as inhibit_client, server as inhibit_server},
+};
+
+use inhibit_server::{zwp_idle_inhibit_manager_v1, zwp_idle_inhibitor_v1};
+use ways::protocol::{wl_compositor, wl_surface};
+
+#[test]
+fn inhibitors_of_visible_surfaces() {
+    let mut server = TestServer::new();
+    let handle = server.display.handle();
+    handle.create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    handle.create_global::<ServerHandler, zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1, _>(
+        1,
+        (),
+    );
+    let mut server_ddata =
+        ServerHandler { inhibitors: IdleInhibitors::new(), surfaces: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let qh = client.event_queue.handle();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let manager = client_ddata
+        .globals
+        .bind::<inhibit_client::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1, _, _>(
+            &qh,
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+    let surface = compositor.create_surface(&qh, ());
+    let first = manager.create_inhibitor(&surface, &qh, ());
+    let second = manager.create_inhibitor(&surface, &qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the inhibitors are only honored while their surface is visible
+    let server_surface = server_ddata.surfaces[0].clone();
+    assert!(!server_ddata.inhibitors.is_inhibited());
+    server_ddata.inhibitors.set_visible(&server_surface, true);
+    assert!(server_ddata.inhibitors.is_inhibited());
+    assert!(server_ddata.inhibitors.inhibits(&server_surface));
+    assert_eq!(server_ddata.inhibitors.active_count(), 2);
+
+    first.destroy();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.inhibitors.active_count(), 1);
+    server_ddata.inhibitors.set_visible(&server_surface, false);
+    assert!(!server_ddata.inhibitors.is_inhibited());
+
+    server_ddata.inhibitors.set_visible(&server_surface, true);
+    second.destroy();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(!server_ddata.inhibitors.is_inhibited());
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    inhibitors: IdleInhibitors,
+    surfaces: Vec<wl_surface::WlSurface>,
+}
+
+server_ignore_global_impl!(ServerHandler => [
+    wl_compositor::WlCompositor,
+    zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1
+]);
+
+server_ignore_impl!(ServerHandler => [wl_surface::WlSurface]);
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            state.surfaces.push(data_init.init(id, ()));
+        }
+    }
+}
+
+impl ways::Dispatch<zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+        request: zwp_idle_inhibit_manager_v1::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let zwp_idle_inhibit_manager_v1::Request::CreateInhibitor { id, surface } = request {
+            let inhibitor = data_init.init(id, ());
+            state.inhibitors.create_inhibitor(&inhibitor, &surface);
+        }
+    }
+}
+
+impl ways::Dispatch<zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+        _: zwp_idle_inhibitor_v1::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _: ways::backend::ClientId,
+        inhibitor: &zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+        _: &(),
+    ) {
+        state.inhibitors.inhibitor_destroyed(inhibitor);
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    inhibit_client::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+    inhibit_client::zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1
+]);
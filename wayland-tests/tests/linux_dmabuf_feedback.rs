@@ -0,0 +1,140 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayland_protocols::wp::linux_dmabuf::{
+    feedback::{ClientDmabufFeedback, DmabufFeedback, Feedback, Fourcc, Modifier, Tranche},
+    zv1::{client as dmabuf_client, server as dmabuf_server},
+};
+
+use dmabuf_server::{zwp_linux_dmabuf_feedback_v1, zwp_linux_dmabuf_v1};
+
+fn tranches() -> Vec<Tranche> {
+    let xrgb = Fourcc::from_chars(*b"XR24");
+    let argb = Fourcc::from_chars(*b"AR24");
+    vec![
+        Tranche {
+            target_device: 0xe281,
+            scanout: true,
+            formats: vec![(xrgb, Modifier(0x0100_0000_0000_0001)), (xrgb, Modifier::LINEAR)],
+        },
+        Tranche {
+            target_device: 0xe280,
+            scanout: false,
+            formats: vec![
+                (xrgb, Modifier::LINEAR),
+                (argb, Modifier::LINEAR),
+                (argb, Modifier::INVALID),
+            ],
+        },
+    ]
+}
+
+#[test]
+fn dmabuf_feedback() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1, _>(4, ());
+    let feedback = DmabufFeedback::new(0xe280, &tranches()).unwrap();
+    // the pair common to both tranches is stored once
+    assert_eq!(feedback.table_len(), 4);
+    let mut server_ddata = ServerHandler { feedback };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata =
+        ClientHandler { globals: globals::GlobalList::new(), feedbacks: Vec::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let qh = client.event_queue.handle();
+    let dmabuf = client_ddata
+        .globals
+        .bind::<dmabuf_client::zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1, _, _>(
+            &qh,
+            &registry,
+            4..5,
+            (),
+        )
+        .unwrap();
+    let default_feedback = dmabuf.get_default_feedback(&qh, ClientDmabufFeedback::new());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let expected = Feedback { main_device: 0xe280, tranches: tranches() };
+    assert_eq!(client_ddata.feedbacks, std::slice::from_ref(&expected));
+
+    // every feedback object is sent the whole table and tranches
+    default_feedback.destroy();
+    dmabuf.get_default_feedback(&qh, ClientDmabufFeedback::new());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(client_ddata.feedbacks, [expected.clone(), expected]);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    feedback: DmabufFeedback,
+}
+
+server_ignore_global_impl!(ServerHandler => [zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1]);
+
+server_ignore_impl!(ServerHandler => [zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1]);
+
+impl ways::Dispatch<zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+        request: zwp_linux_dmabuf_v1::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let zwp_linux_dmabuf_v1::Request::GetDefaultFeedback { id } = request {
+            state.feedback.send(&data_init.init(id, ()));
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    feedbacks: Vec<Feedback>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [dmabuf_client::zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1]);
+
+impl
+    wayc::Dispatch<
+        dmabuf_client::zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1,
+        ClientDmabufFeedback,
+    > for ClientHandler
+{
+    fn event(
+        state: &mut Self,
+        _: &dmabuf_client::zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1,
+        event: dmabuf_client::zwp_linux_dmabuf_feedback_v1::Event,
+        data: &ClientDmabufFeedback,
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.feedbacks.extend(data.event(event));
+    }
+}
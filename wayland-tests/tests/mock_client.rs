@@ -0,0 +1,143 @@
+mod helpers;
+
+use helpers::{wayc, ways};
+
+use std::time::Duration;
+
+use wayc::backend::{protocol::Argument, ObjectId};
+use wayc::mock::{expect, MockClient};
+use wayc::protocol::{wl_keyboard, wl_seat};
+use wayc::Proxy;
+
+use ways::mock::MockCompositor;
+use ways::Resource;
+
+#[test]
+fn record_events() {
+    let mut compositor = MockCompositor::new().unwrap();
+    compositor.add_global(ways::protocol::wl_seat::WlSeat::interface(), 5);
+    compositor.on_bind(ways::protocol::wl_seat::WlSeat::interface(), |reply| {
+        reply.reply("capabilities", vec![Argument::Uint(wl_seat::Capability::Keyboard.bits())]);
+        reply.reply("name", vec![Argument::Str(Some(Box::new(c_string("seat0"))))]);
+    });
+    compositor.on_request(
+        ways::protocol::wl_seat::WlSeat::interface(),
+        "get_keyboard",
+        |reply, request| {
+            let keyboard = request.new_id().unwrap();
+            reply
+                .send_event(&keyboard, "repeat_info", vec![Argument::Int(25), Argument::Int(600)])
+                .unwrap();
+        },
+    );
+
+    let conn = wayc::Connection::from_socket(compositor.add_client().unwrap()).unwrap();
+    let client = MockClient::new(conn).unwrap();
+
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+    client.dispatch().unwrap();
+
+    assert_eq!(client.globals().len(), 1);
+    assert_eq!(client.globals()[0].interface, "wl_seat");
+    client.expect_no_event();
+
+    let seat = client.bind(wl_seat::WlSeat::interface(), 7);
+    assert_eq!(client.connection().object_info(seat.clone()).unwrap().version, 5);
+    let keyboard =
+        client.send(&seat, "get_keyboard", vec![Argument::NewId(ObjectId::null())]).unwrap();
+    let keyboard = keyboard.unwrap();
+
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+    client.dispatch().unwrap();
+
+    let capabilities = client.expect_event(wl_seat::WlSeat::interface(), "capabilities");
+    assert!(matches!(
+        capabilities.message.args[0],
+        Argument::Uint(caps) if caps == wl_seat::Capability::Keyboard.bits()
+    ));
+    let name = client.expect_event(wl_seat::WlSeat::interface(), "name");
+    assert!(name.time >= capabilities.time);
+    let repeat_info = client.expect_event(wl_keyboard::WlKeyboard::interface(), "repeat_info");
+    assert_eq!(repeat_info.message.sender_id, keyboard);
+    client.expect_no_event();
+
+    compositor.expect_request(ways::protocol::wl_seat::WlSeat::interface(), "get_keyboard");
+}
+
+#[test]
+fn expected_events() {
+    let mut compositor = MockCompositor::new().unwrap();
+    compositor.add_global(ways::protocol::wl_seat::WlSeat::interface(), 5);
+    compositor.add_global(ways::protocol::wl_output::WlOutput::interface(), 4);
+    compositor.on_bind(ways::protocol::wl_seat::WlSeat::interface(), |reply| {
+        reply.reply("name", vec![Argument::Str(Some(Box::new(c_string("seat0"))))]);
+        reply.reply("capabilities", vec![Argument::Uint(wl_seat::Capability::Keyboard.bits())]);
+    });
+
+    let conn = wayc::Connection::from_socket(compositor.add_client().unwrap()).unwrap();
+    let client = MockClient::new(conn).unwrap();
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+    client.dispatch().unwrap();
+
+    client.bind(wl_seat::WlSeat::interface(), 5);
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+
+    let events = client.wait_for_sequence(
+        &[expect::<wl_seat::WlSeat>("name"), expect::<wl_seat::WlSeat>("capabilities")],
+        Duration::from_secs(5),
+    );
+    assert_eq!(events[0].name(), "name");
+    assert_eq!(events[1].name(), "capabilities");
+    client.expect_no_event();
+}
+
+#[test]
+#[should_panic(expected = "Unmet expectations after 50ms:
+  wl_seat.name after wl_seat.capabilities
+Received events:
+  wl_seat@")]
+fn unmet_expectation() {
+    let mut compositor = MockCompositor::new().unwrap();
+    compositor.add_global(ways::protocol::wl_seat::WlSeat::interface(), 5);
+    compositor.on_bind(ways::protocol::wl_seat::WlSeat::interface(), |reply| {
+        reply.reply("name", vec![Argument::Str(Some(Box::new(c_string("seat0"))))]);
+        reply.reply("capabilities", vec![Argument::Uint(wl_seat::Capability::Keyboard.bits())]);
+    });
+
+    let conn = wayc::Connection::from_socket(compositor.add_client().unwrap()).unwrap();
+    let client = MockClient::new(conn).unwrap();
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+    client.dispatch().unwrap();
+
+    client.bind(wl_seat::WlSeat::interface(), 5);
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+
+    client.wait_for(
+        &[expect::<wl_seat::WlSeat>("name").after::<wl_seat::WlSeat>("capabilities")],
+        Duration::from_millis(50),
+    );
+}
+
+#[test]
+#[should_panic(expected = "No wl_output global was advertised")]
+fn bind_missing_global() {
+    let mut compositor = MockCompositor::new().unwrap();
+    let conn = wayc::Connection::from_socket(compositor.add_client().unwrap()).unwrap();
+    let client = MockClient::new(conn).unwrap();
+
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+    client.dispatch().unwrap();
+
+    client.bind(wayc::protocol::wl_output::WlOutput::interface(), 1);
+}
+
+fn c_string(s: &str) -> std::ffi::CString {
+    std::ffi::CString::new(s).unwrap()
+}
@@ -0,0 +1,209 @@
+mod helpers;
+
+use helpers::{wayc, ways};
+
+use std::time::Duration;
+
+use ways::backend::protocol::Argument;
+use ways::mock::{expect, MockCompositor};
+use ways::protocol::wl_seat::WlSeat as ServerSeat;
+use ways::Resource;
+
+use wayc::backend::ObjectId;
+use wayc::mock::MockClient;
+
+use wayc::protocol::{wl_keyboard, wl_registry, wl_seat};
+use wayc::Proxy;
+
+#[test]
+fn scripted_seat() {
+    let mut compositor = MockCompositor::new().unwrap();
+    compositor.add_global(ways::protocol::wl_seat::WlSeat::interface(), 5);
+    compositor.on_bind(ways::protocol::wl_seat::WlSeat::interface(), |reply| {
+        reply.reply("capabilities", vec![Argument::Uint(wl_seat::Capability::Keyboard.bits())]);
+    });
+    compositor.on_request(
+        ways::protocol::wl_seat::WlSeat::interface(),
+        "get_keyboard",
+        |reply, request| {
+            let keyboard = request.new_id().unwrap();
+            reply
+                .send_event(&keyboard, "repeat_info", vec![Argument::Int(25), Argument::Int(600)])
+                .unwrap();
+        },
+    );
+
+    let conn = wayc::Connection::from_socket(compositor.add_client().unwrap()).unwrap();
+    let mut event_queue = conn.new_event_queue();
+    let mut client = ClientHandler { repeat_info: None };
+
+    conn.display().get_registry(&event_queue.handle(), ());
+    event_queue.flush().unwrap();
+
+    // registry advertisement, then seat capabilities, then keyboard repeat info
+    while client.repeat_info.is_none() {
+        compositor.dispatch().unwrap();
+        event_queue.blocking_dispatch(&mut client).unwrap();
+        event_queue.flush().unwrap();
+    }
+
+    assert_eq!(client.repeat_info, Some((25, 600)));
+    let request = compositor.expect_request(wl_seat::WlSeat::interface(), "get_keyboard");
+    assert_eq!(
+        request.new_id().unwrap().interface().name,
+        wl_keyboard::WlKeyboard::interface().name
+    );
+    compositor.expect_no_request();
+}
+
+#[test]
+#[should_panic(expected = "Expected request wl_seat.get_pointer, got wl_seat.get_keyboard")]
+fn unexpected_request() {
+    let mut compositor = MockCompositor::new().unwrap();
+    compositor.add_global(ways::protocol::wl_seat::WlSeat::interface(), 5);
+    compositor.on_bind(ways::protocol::wl_seat::WlSeat::interface(), |reply| {
+        reply.reply("capabilities", vec![Argument::Uint(wl_seat::Capability::Keyboard.bits())]);
+    });
+
+    let conn = wayc::Connection::from_socket(compositor.add_client().unwrap()).unwrap();
+    let mut event_queue = conn.new_event_queue();
+    let mut client = ClientHandler { repeat_info: None };
+
+    conn.display().get_registry(&event_queue.handle(), ());
+    event_queue.flush().unwrap();
+    // registry advertisement, then seat capabilities
+    for _ in 0..2 {
+        compositor.dispatch().unwrap();
+        event_queue.blocking_dispatch(&mut client).unwrap();
+        event_queue.flush().unwrap();
+    }
+    compositor.dispatch().unwrap();
+
+    compositor.expect_request(wl_seat::WlSeat::interface(), "get_pointer");
+}
+
+#[test]
+fn expected_requests() {
+    let (mut compositor, client) = seat_client();
+    let seat = client.bind(wl_seat::WlSeat::interface(), 5);
+    client.send(&seat, "get_keyboard", vec![Argument::NewId(ObjectId::null())]).unwrap();
+    client.send(&seat, "get_pointer", vec![Argument::NewId(ObjectId::null())]).unwrap();
+    client.send(&seat, "release", vec![]).unwrap();
+    client.dispatch().unwrap();
+
+    // matched in any order, returned in the order of the expectations
+    let requests = compositor.wait_for(
+        &[
+            expect::<ServerSeat>("get_pointer").after::<ServerSeat>("get_keyboard"),
+            expect::<ServerSeat>("get_keyboard"),
+        ],
+        Duration::from_secs(5),
+    );
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].name(), "get_pointer");
+    assert_eq!(requests[1].name(), "get_keyboard");
+
+    // the other requests stay recorded
+    compositor.expect_request(wl_seat::WlSeat::interface(), "release");
+    compositor.expect_no_request();
+}
+
+#[test]
+#[should_panic(expected = "Unmet expectations after 50ms:
+  wl_seat.get_keyboard after wl_seat.get_pointer
+Received requests:
+  wl_seat@")]
+fn unmet_expectation() {
+    let (mut compositor, client) = seat_client();
+    let seat = client.bind(wl_seat::WlSeat::interface(), 5);
+    client.send(&seat, "get_keyboard", vec![Argument::NewId(ObjectId::null())]).unwrap();
+    client.send(&seat, "get_pointer", vec![Argument::NewId(ObjectId::null())]).unwrap();
+    client.dispatch().unwrap();
+
+    compositor.wait_for(
+        &[expect::<ServerSeat>("get_keyboard").after::<ServerSeat>("get_pointer")],
+        Duration::from_millis(50),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Unmet expectations")]
+fn unordered_sequence() {
+    let (mut compositor, client) = seat_client();
+    let seat = client.bind(wl_seat::WlSeat::interface(), 5);
+    client.send(&seat, "get_keyboard", vec![Argument::NewId(ObjectId::null())]).unwrap();
+    client.send(&seat, "get_pointer", vec![Argument::NewId(ObjectId::null())]).unwrap();
+    client.dispatch().unwrap();
+
+    compositor.wait_for_sequence(
+        &[expect::<ServerSeat>("get_pointer"), expect::<ServerSeat>("get_keyboard")],
+        Duration::from_millis(50),
+    );
+}
+
+// A mock compositor with a seat global, and a mock client which received it
+fn seat_client() -> (MockCompositor, MockClient) {
+    let mut compositor = MockCompositor::new().unwrap();
+    compositor.add_global(ServerSeat::interface(), 5);
+    let conn = wayc::Connection::from_socket(compositor.add_client().unwrap()).unwrap();
+    let client = MockClient::new(conn).unwrap();
+    client.dispatch().unwrap();
+    compositor.dispatch().unwrap();
+    client.dispatch().unwrap();
+    (compositor, client)
+}
+
+struct ClientHandler {
+    repeat_info: Option<(i32, i32)>,
+}
+
+impl wayc::Dispatch<wl_registry::WlRegistry, ()> for ClientHandler {
+    fn event(
+        _: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &wayc::Connection,
+        qh: &wayc::QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global { name, interface, version } = event {
+            if interface == "wl_seat" {
+                registry.bind::<wl_seat::WlSeat, _, _>(name, version, qh, ());
+            }
+        }
+    }
+}
+
+impl wayc::Dispatch<wl_seat::WlSeat, ()> for ClientHandler {
+    fn event(
+        _: &mut Self,
+        seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &wayc::Connection,
+        qh: &wayc::QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities { capabilities: wayc::WEnum::Value(capabilities) } =
+            event
+        {
+            if capabilities.contains(wl_seat::Capability::Keyboard) {
+                seat.get_keyboard(qh, ());
+            }
+        }
+    }
+}
+
+impl wayc::Dispatch<wl_keyboard::WlKeyboard, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        if let wl_keyboard::Event::RepeatInfo { rate, delay } = event {
+            state.repeat_info = Some((rate, delay));
+        }
+    }
+}
@@ -0,0 +1,208 @@
+#[macro_use]
+mod helpers;
+
+use std::time::Duration;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayland_protocols::wp::presentation_time::{
+    client as pt_client,
+    feedback::{ClientFeedback, FrameFeedback, Outcome, SurfaceFeedback},
+    server as pt_server,
+};
+
+use ways::protocol::{wl_compositor, wl_output, wl_surface};
+
+#[test]
+fn presentation_feedback() {
+    let mut server = TestServer::new();
+    let handle = server.display.handle();
+    handle.create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    handle.create_global::<ServerHandler, wl_output::WlOutput, _>(1, ());
+    handle.create_global::<ServerHandler, pt_server::wp_presentation::WpPresentation, _>(1, ());
+    let mut server_ddata = ServerHandler { feedback: SurfaceFeedback::new(), outputs: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata =
+        ClientHandler { globals: globals::GlobalList::new(), outcomes: Vec::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let qh = client.event_queue.handle();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let output = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let presentation = client_ddata
+        .globals
+        .bind::<pt_client::wp_presentation::WpPresentation, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let surface = compositor.create_surface(&qh, ());
+
+    // the first content is superseded before any frame uses it
+    presentation.feedback(&surface, &qh, ClientFeedback::new());
+    surface.commit();
+    presentation.feedback(&surface, &qh, ClientFeedback::submitted_at(Duration::from_secs(7)));
+    surface.commit();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(client_ddata.outcomes, [Outcome::Discarded]);
+
+    // the second content is presented, and the next frames have no feedback
+    let mut frame = FrameFeedback::new();
+    frame.merge(server_ddata.feedback.take_committed());
+    assert!(!frame.is_empty());
+    assert!(server_ddata.feedback.take_committed().is_empty());
+    frame.presented(
+        &server_ddata.outputs,
+        (1 << 32) + 8,
+        500,
+        16_666_667,
+        42,
+        pt_server::wp_presentation_feedback::Kind::Vsync,
+    );
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    match &client_ddata.outcomes[1] {
+        Outcome::Presented { frame, outputs } => {
+            assert_eq!(frame.time, Duration::new((1 << 32) + 8, 500));
+            assert_eq!(frame.refresh, Duration::from_nanos(16_666_667));
+            assert_eq!(frame.sequence, 42);
+            assert!(frame.vsync);
+            assert_eq!(frame.submitted, Some(Duration::from_secs(7)));
+            assert_eq!(outputs, &[output]);
+        }
+        outcome => panic!("unexpected outcome {:?}", outcome),
+    }
+
+    // the pending feedbacks are discarded with the surface
+    presentation.feedback(&surface, &qh, ClientFeedback::new());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    server_ddata.feedback.surface_destroyed();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(client_ddata.outcomes.len(), 3);
+    assert_eq!(client_ddata.outcomes[2], Outcome::Discarded);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    feedback: SurfaceFeedback,
+    outputs: Vec<wl_output::WlOutput>,
+}
+
+server_ignore_global_impl!(ServerHandler => [
+    wl_compositor::WlCompositor,
+    pt_server::wp_presentation::WpPresentation
+]);
+
+server_ignore_impl!(ServerHandler => [
+    wl_output::WlOutput,
+    pt_server::wp_presentation_feedback::WpPresentationFeedback
+]);
+
+impl ways::GlobalDispatch<wl_output::WlOutput, ()> for ServerHandler {
+    fn bind(
+        state: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<wl_output::WlOutput>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        state.outputs.push(data_init.init(resource, ()));
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl ways::Dispatch<wl_surface::WlSurface, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_surface::WlSurface,
+        request: wl_surface::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_surface::Request::Commit = request {
+            state.feedback.commit();
+        }
+    }
+}
+
+impl ways::Dispatch<pt_server::wp_presentation::WpPresentation, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &pt_server::wp_presentation::WpPresentation,
+        request: pt_server::wp_presentation::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let pt_server::wp_presentation::Request::Feedback { callback, .. } = request {
+            state.feedback.add(data_init.init(callback, ()));
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    outcomes: Vec<Outcome>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    wayc::protocol::wl_output::WlOutput,
+    pt_client::wp_presentation::WpPresentation
+]);
+
+impl wayc::Dispatch<pt_client::wp_presentation_feedback::WpPresentationFeedback, ClientFeedback>
+    for ClientHandler
+{
+    fn event(
+        state: &mut Self,
+        _: &pt_client::wp_presentation_feedback::WpPresentationFeedback,
+        event: pt_client::wp_presentation_feedback::Event,
+        data: &ClientFeedback,
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.outcomes.extend(data.event(event));
+    }
+}
@@ -57,6 +57,43 @@ fn client_receive_generic_error() {
     }
 }
 
+#[test]
+fn client_receive_typed_error() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, ways::protocol::wl_shm::WlShm, _>(1, ());
+
+    let (s_client, mut client) = server.add_client();
+
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut ServerHandler).unwrap();
+
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_shm::WlShm, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut ServerHandler).unwrap();
+
+    let shm = s_client
+        .object_from_protocol_id::<ways::protocol::wl_shm::WlShm>(&server.display.handle(), 3)
+        .unwrap();
+    shm.post_typed_error(ways::protocol::wl_shm::Error::InvalidFd, "not a memfd");
+
+    assert!(roundtrip(&mut client, &mut server, &mut client_ddata, &mut ServerHandler).is_err());
+    let error = client.conn.protocol_error().unwrap();
+    assert_eq!(error.code_as(), Some(wayc::protocol::wl_shm::Error::InvalidFd));
+    // the error was not raised by a wl_data_offer
+    assert_eq!(error.code_as::<wayc::protocol::wl_data_offer::Error>(), None);
+}
+
 struct ClientHandler {
     globals: globals::GlobalList,
 }
@@ -78,14 +115,17 @@ wayc::delegate_dispatch!(ClientHandler:
 );
 
 client_ignore_impl!(ClientHandler => [
-    wayc::protocol::wl_compositor::WlCompositor
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_shm::WlShm
 ]);
 
 struct ServerHandler;
 
 server_ignore_impl!(ServerHandler => [
-    ways::protocol::wl_compositor::WlCompositor
+    ways::protocol::wl_compositor::WlCompositor,
+    ways::protocol::wl_shm::WlShm
 ]);
 server_ignore_global_impl!(ServerHandler => [
-    ways::protocol::wl_compositor::WlCompositor
+    ways::protocol::wl_compositor::WlCompositor,
+    ways::protocol::wl_shm::WlShm
 ]);
@@ -0,0 +1,67 @@
+mod helpers;
+
+use std::fs::File;
+use std::sync::Arc;
+
+use helpers::ways;
+
+use ways::backend::protocol::Argument;
+use ways::mock::MockCompositor;
+use ways::protocol::{wl_keyboard, wl_seat, wl_shm};
+use ways::replay::{Capture, Pacing, RecordedBy, Replay, ReplayError};
+use ways::Resource;
+
+const CLIENT_LOG: &str = "\
+[10.000] {Default Queue}  -> wl_display@1.get_registry(new id wl_registry@2)
+[10.050] {Default Queue} wl_registry@2.global(1, \"wl_seat\", 5)
+[10.100]  -> wl_registry@2.bind(1, \"wl_seat\", 5, new id [unknown]@3)
+[10.200]  -> wl_seat@3.get_keyboard(new id wl_keyboard@4)
+[10.300]  -> wl_registry@2.bind(2, \"wl_shm\", 1, new id [unknown]@5)
+[10.400]  -> wl_shm@5.create_pool(new id wl_shm_pool@6, fd 12, 4096)
+[10.500]  -> wl_keyboard@4.release()";
+
+#[test]
+fn replay_client_log() {
+    let mut compositor = MockCompositor::new().unwrap();
+    compositor.add_global(wl_seat::WlSeat::interface(), 5);
+    compositor.add_global(wl_shm::WlShm::interface(), 1);
+
+    let capture = Capture::from_debug_log(CLIENT_LOG, RecordedBy::Client);
+    assert_eq!(capture.requests().len(), 6);
+    let mut replay = Replay::new(
+        capture,
+        &[wl_seat::WlSeat::interface(), wl_shm::WlShm::interface()],
+        &mut compositor.handle(),
+        Arc::new(()),
+    )
+    .unwrap();
+    replay.set_pacing(Pacing::Recorded { speed: 10. });
+    replay.run_with(|| compositor.dispatch()).unwrap();
+    assert!(replay.next_request().is_none());
+
+    // the registry requests are handled by the backend and not recorded by the mock compositor
+
+    compositor.expect_request(wl_seat::WlSeat::interface(), "get_keyboard");
+    let create_pool = compositor.expect_request(wl_shm::WlShm::interface(), "create_pool");
+    match create_pool.message.args[..] {
+        [_, Argument::Fd(ref fd), Argument::Int(4096)] => {
+            let file = File::from(fd.try_clone().unwrap());
+            assert_eq!(file.metadata().unwrap().len(), 4096);
+        }
+        ref args => panic!("Unexpected arguments {:?}", args),
+    }
+    compositor.expect_request(wl_keyboard::WlKeyboard::interface(), "release");
+    compositor.expect_no_request();
+}
+
+#[test]
+fn replay_unknown_object() {
+    let compositor = MockCompositor::new().unwrap();
+    let capture = Capture::from_debug_log(
+        "[1700000000.000100] -> wl_surface@3.commit ()",
+        RecordedBy::Client,
+    );
+    let mut replay = Replay::new(capture, &[], &mut compositor.handle(), Arc::new(())).unwrap();
+    assert!(matches!(replay.step(), Err(ReplayError::UnknownObject { id: 3, .. })));
+    assert!(!replay.step().unwrap());
+}
@@ -0,0 +1,202 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayc::protocol::{wl_display, wl_keyboard, wl_registry, wl_surface};
+use wayc::Proxy;
+
+pub mod lamp {
+    pub mod __interfaces {
+        wayland_scanner::generate_interfaces!("tests/assets/test-lamp.xml");
+    }
+
+    pub mod types {
+        use super::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_enums!("tests/assets/test-lamp.xml");
+    }
+
+    pub mod client {
+        use super::__interfaces::*;
+        use wayland_client;
+        wayland_scanner::generate_client_code!(
+            "tests/assets/test-lamp.xml",
+            enums = super::types,
+            serde
+        );
+    }
+
+    pub mod server {
+        use super::__interfaces::*;
+        use wayland_server;
+        wayland_scanner::generate_server_code!("tests/assets/test-lamp.xml", serde);
+    }
+}
+
+use lamp::types::test_lamp::Color;
+
+fn json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+#[test]
+fn serialize_core_messages() {
+    let (_, client) = TestServer::<()>::new().add_client::<()>();
+
+    let request =
+        wl_registry::Request::Bind { name: 3, id: (wl_surface::WlSurface::interface(), 2) };
+    assert_eq!(json(&request), r#"{"Bind":{"name":3,"id":["wl_surface",2]}}"#);
+
+    let event = wl_display::Event::Error {
+        object_id: client.display.id(),
+        code: 1,
+        message: "invalid method".into(),
+    };
+    assert_eq!(
+        json(&event),
+        r#"{"Error":{"object_id":{"interface":"wl_display","id":1},"code":1,"message":"invalid method"}}"#
+    );
+    assert_eq!(
+        json(&wl_keyboard::Event::Key {
+            serial: 1,
+            time: 2,
+            key: 30,
+            state: wayc::WEnum::Value(wl_keyboard::KeyState::Pressed),
+        }),
+        r#"{"Key":{"serial":1,"time":2,"key":30,"state":1}}"#
+    );
+    assert_eq!(json(&ways::protocol::wl_output::Event::Done), r#""Done""#);
+}
+
+#[test]
+fn serde_protocol_enums() {
+    use wayc::protocol::{wl_seat, wl_shm};
+
+    assert_eq!(json(&wl_shm::Format::Xrgb8888), "1");
+    assert_eq!(serde_json::from_str::<wl_shm::Format>("1").unwrap(), wl_shm::Format::Xrgb8888);
+    assert!(serde_json::from_str::<wl_shm::Format>("7").is_err());
+
+    let capabilities = wl_seat::Capability::Pointer | wl_seat::Capability::Keyboard;
+    assert_eq!(json(&capabilities), "3");
+    assert_eq!(serde_json::from_str::<wl_seat::Capability>("3").unwrap(), capabilities);
+}
+
+#[test]
+fn serialize_extension_messages() {
+    use wayland_protocols::xdg::shell::{client::xdg_toplevel, server::xdg_wm_base};
+
+    assert_eq!(
+        json(&xdg_toplevel::Request::SetTitle { title: "trains".into() }),
+        r#"{"SetTitle":{"title":"trains"}}"#
+    );
+    assert_eq!(json(&xdg_wm_base::Event::Ping { serial: 7 }), r#"{"Ping":{"serial":7}}"#);
+    assert_eq!(
+        serde_json::from_str::<xdg_toplevel::State>("4").unwrap(),
+        xdg_toplevel::State::Activated
+    );
+}
+
+#[test]
+fn serialize_received_messages() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, lamp::server::test_lamp::TestLamp, ()>(1, ());
+    let mut server_ddata = ServerHandler { requests: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata =
+        ClientHandler { globals: globals::GlobalList::new(), events: Vec::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let lamp = client_ddata
+        .globals
+        .bind::<lamp::client::test_lamp::TestLamp, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    lamp.set_color(Color::Blue);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    assert_eq!(server_ddata.requests, [r#"{"SetColor":{"color":2}}"#]);
+    assert_eq!(client_ddata.events, [r#"{"Color":{"color":2}}"#]);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    requests: Vec<String>,
+}
+
+impl ways::GlobalDispatch<lamp::server::test_lamp::TestLamp, ()> for ServerHandler {
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<lamp::server::test_lamp::TestLamp>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl ways::Dispatch<lamp::server::test_lamp::TestLamp, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        resource: &lamp::server::test_lamp::TestLamp,
+        request: lamp::server::test_lamp::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        state.requests.push(json(&request));
+        if let lamp::server::test_lamp::Request::SetColor { color: ways::WEnum::Value(color) } =
+            request
+        {
+            resource.color(color);
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    events: Vec<String>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<lamp::client::test_lamp::TestLamp, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &lamp::client::test_lamp::TestLamp,
+        event: lamp::client::test_lamp::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.events.push(json(&event));
+    }
+}
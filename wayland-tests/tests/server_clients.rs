@@ -80,6 +80,290 @@ fn client_credentials() {
     assert_credentials(credentials.unwrap());
 }
 
+#[test]
+fn client_security_label() {
+    let mut server = TestServer::<()>::new();
+
+    let (s_client, _) = server.add_client::<()>();
+
+    // whether there is a label depends on the security modules of the system running the tests
+    let label = s_client.security_label(&server.display.handle()).unwrap();
+    assert_ne!(label.as_deref(), Some(""));
+}
+
+// libwayland manages the buffers of the clients itself
+#[cfg(not(feature = "server_system"))]
+#[test]
+fn client_overflow_policy() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(2, ());
+    let mut server_ddata = ServerHandler {};
+
+    let (s_client, mut client) = server.add_client_with_data(Arc::new(MyClientData {
+        has_compositor: AtomicBool::new(false),
+        has_output: AtomicBool::new(false),
+    }));
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            2..3,
+            (),
+        )
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let handle = server.display.handle();
+    let output = s_client
+        .resources_of::<ways::protocol::wl_output::WlOutput>(&handle)
+        .unwrap()
+        .next()
+        .unwrap();
+
+    // the client does not read its socket while the compositor floods it
+    let dropped = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let dropped2 = dropped.clone();
+    s_client.set_max_buffer_size(&handle, 64 * 1024).unwrap();
+    s_client
+        .set_overflow_policy(
+            &handle,
+            ways::backend::OverflowPolicy::Callback(Arc::new(move |_, interface, _| {
+                assert_eq!(interface.name, "wl_output");
+                dropped2.fetch_add(1, Ordering::SeqCst);
+                true
+            })),
+        )
+        .unwrap();
+    for _ in 0..100_000 {
+        output.scale(1);
+    }
+    let dropped = dropped.load(Ordering::SeqCst);
+    assert!(dropped > 0 && dropped < 100_000);
+
+    // the events which did not fit were discarded, but the client is still connected
+    drain(&mut client, &mut client_ddata).unwrap();
+    server.answer(&mut server_ddata);
+    drain(&mut client, &mut client_ddata).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // with the default policy, it is disconnected
+    s_client.set_overflow_policy(&handle, ways::backend::OverflowPolicy::Kill).unwrap();
+    for _ in 0..100_000 {
+        output.scale(1);
+    }
+    server.answer(&mut server_ddata);
+    assert!(drain(&mut client, &mut client_ddata).is_err());
+}
+
+#[cfg(not(feature = "server_system"))]
+#[test]
+fn dispatch_single_client() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(1, ());
+    let mut server_ddata = ServerHandler {};
+
+    let (s_client_a, mut client_a) = server.add_client::<ClientHandler>();
+    let (_, mut client_b) = server.add_client::<ClientHandler>();
+    let mut ddata_a = ClientHandler::new();
+    let mut ddata_b = ClientHandler::new();
+
+    client_a.display.get_registry(&client_a.event_queue.handle(), ());
+    client_b.display.get_registry(&client_b.event_queue.handle(), ());
+    client_a.conn.flush().unwrap();
+    client_b.conn.flush().unwrap();
+
+    // only the requests of the first client are processed
+    assert_eq!(server.display.dispatch_client(&s_client_a, &mut server_ddata).unwrap(), 1);
+    server.display.flush_clients().unwrap();
+    drain(&mut client_a, &mut ddata_a).unwrap();
+    drain(&mut client_b, &mut ddata_b).unwrap();
+    assert_eq!(ddata_a.globals.list().len(), 1);
+    assert!(ddata_b.globals.list().is_empty());
+
+    server.answer(&mut server_ddata);
+    drain(&mut client_b, &mut ddata_b).unwrap();
+    assert_eq!(ddata_b.globals.list().len(), 1);
+}
+
+// libwayland invokes the callback as soon as it has flushed
+#[cfg(not(feature = "server_system"))]
+#[test]
+fn client_flush_with_callback() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(2, ());
+    let mut server_ddata = ServerHandler {};
+
+    let (s_client, mut client) = server.add_client_with_data(Arc::new(MyClientData {
+        has_compositor: AtomicBool::new(false),
+        has_output: AtomicBool::new(false),
+    }));
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            2..3,
+            (),
+        )
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let mut handle = server.display.handle();
+    let output = s_client
+        .resources_of::<ways::protocol::wl_output::WlOutput>(&handle)
+        .unwrap()
+        .next()
+        .unwrap();
+
+    // nothing is buffered, the callback is invoked right away
+    let drained = Arc::new(AtomicBool::new(false));
+    let drained2 = drained.clone();
+    s_client
+        .flush_with_callback(&mut handle, move || drained2.store(true, Ordering::SeqCst))
+        .unwrap();
+    assert!(drained.load(Ordering::SeqCst));
+
+    // the client does not read its socket, so the events stay buffered
+    s_client.set_max_buffer_size(&handle, 4 * 1024 * 1024).unwrap();
+    for _ in 0..100_000 {
+        output.scale(1);
+    }
+    let drained = Arc::new(AtomicBool::new(false));
+    let drained2 = drained.clone();
+    s_client
+        .flush_with_callback(&mut handle, move || drained2.store(true, Ordering::SeqCst))
+        .unwrap();
+    assert!(!drained.load(Ordering::SeqCst));
+
+    // once the client has read enough, a later flush writes the rest and invokes the callback
+    while !drained.load(Ordering::SeqCst) {
+        drain(&mut client, &mut client_ddata).unwrap();
+        server.display.flush_clients().unwrap();
+    }
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+}
+
+// libwayland does not report whether the client read its socket
+#[cfg(all(not(feature = "server_system"), any(target_os = "linux", target_os = "android")))]
+#[test]
+fn client_barrier() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(2, ());
+    let mut server_ddata = ServerHandler {};
+
+    let (s_client, mut client) = server.add_client_with_data(Arc::new(MyClientData {
+        has_compositor: AtomicBool::new(false),
+        has_output: AtomicBool::new(false),
+    }));
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            2..3,
+            (),
+        )
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let mut handle = server.display.handle();
+    let output = s_client
+        .resources_of::<ways::protocol::wl_output::WlOutput>(&handle)
+        .unwrap()
+        .next()
+        .unwrap();
+
+    // the events are written, but not read yet
+    output.scale(2);
+    let barrier = s_client.barrier(&mut handle).unwrap();
+    assert!(!barrier.is_reached());
+    server.display.flush_clients().unwrap();
+    assert!(!barrier.is_reached());
+
+    // the barrier is reached at the first flush after the client read them
+    drain(&mut client, &mut client_ddata).unwrap();
+    assert!(!barrier.is_reached());
+    server.display.flush_clients().unwrap();
+    assert!(barrier.is_reached());
+
+    // the events sent after a barrier are not waited for
+    let barrier = s_client.barrier(&mut handle).unwrap();
+    output.scale(1);
+    server.display.flush_clients().unwrap();
+    assert!(barrier.is_reached());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+}
+
+// the credentials are only available on linux
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn listening_socket_filter() {
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+
+    let path =
+        std::env::temp_dir().join(format!("wayland-rs-test-socket-filter-{}", std::process::id()));
+    let mut listening = ways::ListeningSocket::bind_absolute(path.clone()).unwrap();
+
+    // the peer credentials are given to the filter
+    listening.set_filter(|credentials| credentials.pid == std::process::id() as i32);
+    let _client = UnixStream::connect(&path).unwrap();
+    assert!(listening.accept().unwrap().is_some());
+
+    // refused connections are closed, and not returned
+    listening.set_filter(|_| false);
+    let mut client = UnixStream::connect(&path).unwrap();
+    assert!(listening.accept().unwrap().is_none());
+    assert_eq!(client.read(&mut [0; 4]).unwrap(), 0);
+}
+
+// read and dispatch the events of a client until its socket is empty or closed
+#[cfg(not(feature = "server_system"))]
+fn drain(
+    client: &mut helpers::TestClient<ClientHandler>,
+    client_ddata: &mut ClientHandler,
+) -> Result<(), wayc::backend::WaylandError> {
+    loop {
+        let read = client.conn.prepare_read().map(|guard| guard.read()).unwrap_or(Ok(0));
+        client.event_queue.dispatch_pending(client_ddata).unwrap();
+        match read {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(wayc::backend::WaylandError::Io(e))
+                if e.kind() == std::io::ErrorKind::WouldBlock =>
+            {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 #[cfg(any(not(feature = "server_system"), not(target_os = "freebsd")))]
 fn assert_credentials(credentials: ways::backend::Credentials) {
     assert!(credentials.pid != 0);
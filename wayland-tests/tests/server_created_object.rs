@@ -336,6 +336,79 @@ fn creation_destruction_queue_dispatch_race() {
     assert!(client_ddata.received_dead);
 }
 
+#[test]
+fn data_offer_event_streams() {
+    use futures_util::{FutureExt, StreamExt};
+    use wayc::protocol::wl_data_offer::Event as CDOEvt;
+
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, ServerSeat, _>(1, ());
+    server.display.handle().create_global::<ServerHandler, ServerDDMgr, _>(3, ());
+    let mut server_ddata = ServerHandler { data_device: None };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let seat = client_ddata
+        .globals
+        .bind::<ClientSeat, _, _>(&client.event_queue.handle(), &registry, 1..2, ())
+        .unwrap();
+    let ddmgr = client_ddata
+        .globals
+        .bind::<ClientDDMgr, _, _>(&client.event_queue.handle(), &registry, 3..4, ())
+        .unwrap();
+
+    let client_dd = ddmgr.get_data_device(&seat, &client.event_queue.handle(), ());
+    let mut dd_events = client_dd.event_stream();
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the offer is described right away, before the client gets to give it a stream
+    let server_dd = server_ddata.data_device.take().unwrap();
+    let s_client = server.display.handle().get_client(server_dd.id()).unwrap();
+    let offer = s_client
+        .create_resource::<ServerDO, (), ServerHandler>(
+            &server.display.handle(),
+            server_dd.version(),
+            (),
+        )
+        .unwrap();
+    server_dd.data_offer(&offer);
+    offer.offer("text/plain".into());
+    offer.offer("text/html".into());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the events went to the streams rather than the event queue
+    assert!(client_ddata.data_offer.is_none());
+    let client_do = match dd_events.next().now_or_never() {
+        Some(Some(CDDEvt::DataOffer { id })) => id,
+        other => panic!("Unexpected event: {:?}", other),
+    };
+    assert!(dd_events.next().now_or_never().is_none());
+
+    let mut do_events = client_do.event_stream_bounded(1);
+    assert!(do_events.next().now_or_never().is_some());
+    // the events taken over from the child count in the capacity of the stream
+    assert_eq!(do_events.lagged(), 1);
+
+    offer.offer("text/uri-list".into());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    match do_events.next().now_or_never() {
+        Some(Some(CDOEvt::Offer { mime_type })) => assert_eq!(mime_type, "text/uri-list"),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+    assert!(client_ddata.received.is_none());
+
+    // the stream ends with its object
+    client_do.destroy();
+    assert!(matches!(do_events.next().now_or_never(), Some(None)));
+}
+
 struct ClientHandler {
     globals: globals::GlobalList,
     data_offer: Option<ClientDO>,
@@ -0,0 +1,355 @@
+use std::io::{Read, Write};
+use std::os::unix::io::AsFd;
+use std::os::unix::net::UnixStream;
+
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::data_device::{
+    DataDeviceData, DataDeviceHandler, DataDeviceManager, DataDeviceManagerData, DataOfferData,
+    DataSourceData, DragRequest,
+};
+use ways::protocol::{
+    wl_compositor, wl_data_device, wl_data_device_manager, wl_data_offer, wl_data_source,
+    wl_keyboard, wl_pointer, wl_seat, wl_surface, wl_touch,
+};
+use ways::seat::{Seat, SeatData};
+
+use wayc::protocol::{
+    wl_compositor::WlCompositor as ClientCompositor,
+    wl_data_device::WlDataDevice as ClientDevice,
+    wl_data_device_manager::{DndAction, WlDataDeviceManager as ClientManager},
+    wl_data_offer::WlDataOffer as ClientOffer,
+    wl_data_source::WlDataSource as ClientSource,
+    wl_seat::WlSeat as ClientSeat,
+    wl_surface::WlSurface as ClientSurface,
+};
+
+#[test]
+fn selection_transfer() {
+    let mut server = TestServer::new();
+    let mut server_ddata = ServerHandler::new(&server);
+
+    let (s_client_a, mut client_a) = server.add_client();
+    let mut ddata_a = ClientHandler::new();
+    let (s_client_b, mut client_b) = server.add_client();
+    let mut ddata_b = ClientHandler::new();
+    let (_, manager_a, device_a) =
+        setup(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata);
+    setup(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata);
+
+    // only the focused client can set the selection
+    let source = manager_a.create_data_source(&client_a.event_queue.handle(), ());
+    source.offer("text/plain".into());
+    device_a.set_selection(Some(&source), 0);
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    assert_eq!(ddata_a.events, ["source cancelled"]);
+    assert!(server_ddata.selections.is_empty());
+
+    ddata_a.events.clear();
+    server_ddata.data_device.set_focus(&server_ddata.seat, Some(&s_client_a));
+    let source = manager_a.create_data_source(&client_a.event_queue.handle(), ());
+    source.offer("text/plain".into());
+    source.offer("text/html".into());
+    device_a.set_selection(Some(&source), 0);
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.selections, [Some(vec!["text/plain".into(), "text/html".into()])]);
+    assert_eq!(
+        server_ddata.data_device.selection(&server_ddata.seat),
+        Some(vec!["text/plain".into(), "text/html".into()])
+    );
+
+    // the client receiving the focus gets an offer of the selection, and reads it from the source
+    server_ddata.data_device.set_focus(&server_ddata.seat, Some(&s_client_b));
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert_eq!(
+        ddata_b.events,
+        ["data_offer", "offer text/plain", "offer text/html", "selection true"]
+    );
+    let offer = ddata_b.offers.last().unwrap().clone();
+    let (mut reader, writer) = UnixStream::pair().unwrap();
+    offer.receive("text/plain".into(), writer.as_fd());
+    drop(writer);
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "I like trains!");
+
+    // destroying the source clears the selection
+    ddata_b.events.clear();
+    source.destroy();
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert_eq!(ddata_b.events, ["selection false"]);
+    assert_eq!(server_ddata.selections.last(), Some(&None));
+}
+
+#[test]
+fn drag_and_drop() {
+    let mut server = TestServer::new();
+    let mut server_ddata = ServerHandler::new(&server);
+
+    let (_, mut client_a) = server.add_client();
+    let mut ddata_a = ClientHandler::new();
+    let (_, mut client_b) = server.add_client();
+    let mut ddata_b = ClientHandler::new();
+    let (origin, manager_a, device_a) =
+        setup(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata);
+    setup(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata);
+    let target = server_ddata.surfaces[1].clone();
+
+    let source = manager_a.create_data_source(&client_a.event_queue.handle(), ());
+    source.offer("text/plain".into());
+    source.set_actions(DndAction::Copy | DndAction::Move);
+    device_a.start_drag(Some(&source), &origin, None, 0);
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    assert!(server_ddata.data_device.is_dragging(&server_ddata.seat));
+
+    // the target receives an offer when the drag enters its surface
+    server_ddata.data_device.drag_motion(&server_ddata.seat, Some((&target, 1.0, 2.0)), 0);
+    server_ddata.data_device.drag_motion(&server_ddata.seat, Some((&target, 3.0, 4.0)), 1);
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert_eq!(
+        ddata_b.events,
+        ["data_offer", "offer text/plain", "source_actions 3", "enter 1 2", "motion 3 4"]
+    );
+
+    // the choices of the target are forwarded to the source
+    let offer = ddata_b.offers.last().unwrap().clone();
+    offer.accept(0, Some("text/plain".into()));
+    offer.set_actions(DndAction::Copy | DndAction::Ask, DndAction::Ask);
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    assert_eq!(ddata_a.events, ["source target text/plain", "source action 1"]);
+
+    // after the drop, the target does the transfer and finishes the operation
+    ddata_a.events.clear();
+    ddata_b.events.clear();
+    server_ddata.data_device.drop_drag(&server_ddata.seat);
+    assert!(!server_ddata.data_device.is_dragging(&server_ddata.seat));
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert_eq!(ddata_b.events, ["drop", "leave"]);
+    let (mut reader, writer) = UnixStream::pair().unwrap();
+    offer.receive("text/plain".into(), writer.as_fd());
+    offer.finish();
+    drop(writer);
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "I like trains!");
+    assert_eq!(ddata_a.events, ["source dnd_drop_performed", "source dnd_finished"]);
+}
+
+fn setup(
+    client: &mut TestClient<ClientHandler>,
+    server: &mut TestServer<ServerHandler>,
+    client_ddata: &mut ClientHandler,
+    server_ddata: &mut ServerHandler,
+) -> (ClientSurface, ClientManager, ClientDevice) {
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    let compositor =
+        client_ddata.globals.bind::<ClientCompositor, _, _>(&qh, &registry, 1..2, ()).unwrap();
+    let seat = client_ddata.globals.bind::<ClientSeat, _, _>(&qh, &registry, 1..10, ()).unwrap();
+    let manager =
+        client_ddata.globals.bind::<ClientManager, _, _>(&qh, &registry, 3..4, ()).unwrap();
+    let surface = compositor.create_surface(&qh, ());
+    let device = manager.get_data_device(&seat, &qh, ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    (surface, manager, device)
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    seat: Seat,
+    data_device: DataDeviceManager,
+    surfaces: Vec<wl_surface::WlSurface>,
+    selections: Vec<Option<Vec<String>>>,
+}
+
+impl ServerHandler {
+    fn new(server: &TestServer<ServerHandler>) -> ServerHandler {
+        let handle = server.display.handle();
+        handle.create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+        ServerHandler {
+            seat: Seat::new::<ServerHandler>(&handle, "seat0"),
+            data_device: DataDeviceManager::new::<ServerHandler>(&handle),
+            surfaces: Vec::new(),
+            selections: Vec::new(),
+        }
+    }
+}
+
+impl DataDeviceHandler for ServerHandler {
+    fn start_drag(&mut self, request: DragRequest) {
+        self.data_device.start_drag(request);
+    }
+
+    fn new_selection(&mut self, _: &SeatData, mime_types: Option<Vec<String>>) {
+        self.selections.push(mime_types);
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            state.surfaces.push(init.init(id, ()));
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [wl_surface::WlSurface]);
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_pointer::WlPointer: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_keyboard::WlKeyboard: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_touch::WlTouch: SeatData] => Seat);
+
+ways::delegate_global_dispatch!(ServerHandler:
+    [wl_data_device_manager::WlDataDeviceManager: DataDeviceManagerData] => DataDeviceManager
+);
+ways::delegate_dispatch!(ServerHandler:
+    [wl_data_device_manager::WlDataDeviceManager: DataDeviceManagerData] => DataDeviceManager
+);
+ways::delegate_dispatch!(ServerHandler: [wl_data_device::WlDataDevice: DataDeviceData] => DataDeviceManager);
+ways::delegate_dispatch!(ServerHandler: [wl_data_source::WlDataSource: DataSourceData] => DataDeviceManager);
+ways::delegate_dispatch!(ServerHandler: [wl_data_offer::WlDataOffer: DataOfferData] => DataDeviceManager);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    events: Vec<String>,
+    offers: Vec<ClientOffer>,
+}
+
+impl ClientHandler {
+    fn new() -> ClientHandler {
+        ClientHandler {
+            globals: globals::GlobalList::new(),
+            events: Vec::new(),
+            offers: Vec::new(),
+        }
+    }
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<ClientDevice, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientDevice,
+        event: wayc::protocol::wl_data_device::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        use wayc::protocol::wl_data_device::Event;
+        let event = match event {
+            Event::DataOffer { id } => {
+                state.offers.push(id);
+                "data_offer".into()
+            }
+            Event::Enter { x, y, .. } => format!("enter {} {}", x, y),
+            Event::Motion { x, y, .. } => format!("motion {} {}", x, y),
+            Event::Leave => "leave".into(),
+            Event::Drop => "drop".into(),
+            Event::Selection { id } => format!("selection {}", id.is_some()),
+            _ => panic!("Unexpected event!"),
+        };
+        state.events.push(event);
+    }
+
+    wayc::event_created_child!(ClientHandler, ClientDevice, [
+        wayc::protocol::wl_data_device::EVT_DATA_OFFER_OPCODE => (ClientOffer, ())
+    ]);
+}
+
+impl wayc::Dispatch<ClientOffer, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientOffer,
+        event: wayc::protocol::wl_data_offer::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        use wayc::protocol::wl_data_offer::Event;
+        let event = match event {
+            Event::Offer { mime_type } => format!("offer {}", mime_type),
+            Event::SourceActions { source_actions: wayc::WEnum::Value(actions) } => {
+                format!("source_actions {}", actions.bits())
+            }
+            Event::Action { dnd_action: wayc::WEnum::Value(action) } => {
+                format!("offer action {}", action.bits())
+            }
+            _ => panic!("Unexpected event!"),
+        };
+        state.events.push(event);
+    }
+}
+
+impl wayc::Dispatch<ClientSource, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientSource,
+        event: wayc::protocol::wl_data_source::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        use wayc::protocol::wl_data_source::Event;
+        let event = match event {
+            Event::Send { fd, .. } => {
+                std::fs::File::from(fd).write_all(b"I like trains!").unwrap();
+                return;
+            }
+            Event::Target { mime_type } => format!("source target {}", mime_type.unwrap()),
+            Event::Action { dnd_action: wayc::WEnum::Value(action) } => {
+                format!("source action {}", action.bits())
+            }
+            Event::Cancelled => "source cancelled".into(),
+            Event::DndDropPerformed => "source dnd_drop_performed".into(),
+            Event::DndFinished => "source dnd_finished".into(),
+            _ => panic!("Unexpected event!"),
+        };
+        state.events.push(event);
+    }
+}
+
+client_ignore_impl!(ClientHandler => [
+    ClientCompositor,
+    ClientSurface,
+    ClientSeat,
+    ClientManager
+]);
@@ -0,0 +1,171 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::protocol::wl_output::{self, WlOutput as ServerOutput};
+use ways::Resource;
+
+#[test]
+fn replace_global_and_resources() {
+    let mut server = TestServer::new();
+    let global = server
+        .display
+        .handle()
+        .create_tracked_global::<ServerHandler, ServerOutput, _>(3, First(1));
+    let mut server_ddata = ServerHandler { binds: Vec::new(), releases: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let first = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            3..4,
+            (),
+        )
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the global is upgraded in place, and the new binds use its new implementation
+    let handle = server.display.handle();
+    handle
+        .replace_global::<ServerHandler, ServerOutput, First, Second>(&global.id(), |old| {
+            Second(old.0 + 1)
+        })
+        .unwrap();
+    // the data of the global is no longer of the previous type
+    assert!(handle
+        .replace_global::<ServerHandler, ServerOutput, First, Second>(&global.id(), |old| {
+            Second(old.0)
+        })
+        .is_err());
+
+    let second = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            3..4,
+            (),
+        )
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.binds, ["first 1", "second 2"]);
+    assert_eq!(client_ddata.globals.list().len(), 1);
+
+    // the resources bound before are upgraded as well, and are still tracked
+    let mut bound = 0;
+    global.for_each_bound(|output| {
+        bound += 1;
+        if output.data::<First>().is_some() {
+            handle
+                .replace_resource_data::<ServerHandler, _, First, Second>(output, |old| {
+                    Second(old.0 * 10)
+                })
+                .unwrap();
+        }
+    });
+    assert_eq!(bound, 2);
+
+    first.release();
+    second.release();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.releases, ["second 10", "second 2"]);
+}
+
+/*
+ * Server Handler
+ */
+
+struct First(u32);
+struct Second(u32);
+
+struct ServerHandler {
+    binds: Vec<String>,
+    releases: Vec<String>,
+}
+
+impl ways::GlobalDispatch<ServerOutput, First> for ServerHandler {
+    fn bind(
+        state: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<ServerOutput>,
+        data: &First,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        state.binds.push(format!("first {}", data.0));
+        data_init.init(resource, First(data.0));
+    }
+}
+
+impl ways::GlobalDispatch<ServerOutput, Second> for ServerHandler {
+    fn bind(
+        state: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<ServerOutput>,
+        data: &Second,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        state.binds.push(format!("second {}", data.0));
+        data_init.init(resource, Second(data.0));
+    }
+}
+
+impl ways::Dispatch<ServerOutput, First> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &ServerOutput,
+        request: wl_output::Request,
+        data: &First,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_output::Request::Release = request {
+            state.releases.push(format!("first {}", data.0));
+        }
+    }
+}
+
+impl ways::Dispatch<ServerOutput, Second> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &ServerOutput,
+        request: wl_output::Request,
+        data: &Second,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_output::Request::Release = request {
+            state.releases.push(format!("second {}", data.0));
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [wayc::protocol::wl_output::WlOutput]);
@@ -0,0 +1,155 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::output::{Mode, Output, OutputData, PhysicalProperties};
+
+use wayc::protocol::wl_output::{self, Event};
+
+#[test]
+fn output_events() {
+    let mut server = TestServer::new();
+    let output = Output::new::<ServerHandler>(
+        &server.display.handle(),
+        "DP-1",
+        PhysicalProperties {
+            size: (600, 340),
+            subpixel: ways::protocol::wl_output::Subpixel::None,
+            make: "Example".into(),
+            model: "Monitor".into(),
+        },
+    );
+    let mode = Mode { width: 1920, height: 1080, refresh: 60_000 };
+    output.set_preferred_mode(mode);
+    output.change_current_state(Some(mode), None, Some(2), Some((10, 20)));
+    output.set_description("Example Monitor");
+    let mut server_ddata = ServerHandler;
+
+    let (s_client, mut client) = server.add_client();
+    let mut client_ddata =
+        ClientHandler { globals: globals::GlobalList::new(), events: Vec::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the latest version receives the whole state at bind
+    let v4 = client_ddata
+        .globals
+        .bind::<wl_output::WlOutput, _, _>(&client.event_queue.handle(), &registry, 4..5, ())
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let geometry = || Event::Geometry {
+        x: 10,
+        y: 20,
+        physical_width: 600,
+        physical_height: 340,
+        subpixel: wayc::WEnum::Value(wl_output::Subpixel::None),
+        make: "Example".into(),
+        model: "Monitor".into(),
+        transform: wayc::WEnum::Value(wl_output::Transform::Normal),
+    };
+    let current_mode = || Event::Mode {
+        flags: wayc::WEnum::Value(wl_output::Mode::Current | wl_output::Mode::Preferred),
+        width: 1920,
+        height: 1080,
+        refresh: 60_000,
+    };
+    assert_eq!(
+        client_ddata.events,
+        debug([
+            geometry(),
+            current_mode(),
+            Event::Scale { factor: 2 },
+            Event::Name { name: "DP-1".into() },
+            Event::Description { description: "Example Monitor".into() },
+            Event::Done,
+        ])
+    );
+    let resource = s_client
+        .resources_of::<ways::protocol::wl_output::WlOutput>(&server.display.handle())
+        .unwrap()
+        .next()
+        .unwrap();
+    assert!(output.owns(&resource));
+
+    // version 1 has neither scale, name, description nor done
+    client_ddata.events.clear();
+    let name = client_ddata.globals.list()[0].name;
+    registry.bind::<wl_output::WlOutput, _, _>(name, 1, &client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(client_ddata.events, debug([geometry(), current_mode()]));
+
+    // a change sends the modified state, followed by done, to all the outputs
+    client_ddata.events.clear();
+    let new_mode = Mode { width: 1280, height: 720, refresh: 60_000 };
+    output.change_current_state(Some(new_mode), None, Some(2), None);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let mode_event = || Event::Mode {
+        flags: wayc::WEnum::Value(wl_output::Mode::Current),
+        width: 1280,
+        height: 720,
+        refresh: 60_000,
+    };
+    assert_eq!(client_ddata.events, debug([mode_event(), Event::Done, mode_event()]));
+    assert_eq!(output.modes(), [mode, new_mode]);
+
+    // unchanged states are not sent
+    client_ddata.events.clear();
+    output.change_current_state(Some(new_mode), None, Some(2), Some((10, 20)));
+    output.set_description("Example Monitor");
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(client_ddata.events.is_empty());
+
+    // released outputs no longer receive events, version 1 outputs cannot be released
+    v4.release();
+    output.change_current_state(None, Some(ways::protocol::wl_output::Transform::_90), None, None);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(client_ddata.events.len(), 1);
+}
+
+// the events do not implement PartialEq, compare their debug representations
+fn debug(events: impl IntoIterator<Item = Event>) -> Vec<String> {
+    events.into_iter().map(|event| format!("{:?}", event)).collect()
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler;
+
+ways::delegate_global_dispatch!(ServerHandler: [ways::protocol::wl_output::WlOutput: OutputData] => Output);
+ways::delegate_dispatch!(ServerHandler: [ways::protocol::wl_output::WlOutput: OutputData] => Output);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    events: Vec<String>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<wl_output::WlOutput, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.events.push(format!("{:?}", event));
+    }
+}
@@ -0,0 +1,194 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::protocol::{wl_callback, wl_compositor, wl_region, wl_surface};
+
+#[test]
+#[cfg(not(feature = "server_system"))]
+fn paused_resource_requests_are_deferred() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler { requests: Vec::new(), surface: None };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    let surface = compositor.create_surface(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let server_surface = server_ddata.surface.clone().unwrap();
+    server.display.handle().set_paused(&server_surface, true).unwrap();
+
+    // the requests of the surface are held back, including the ones of the callback it creates
+    surface.commit();
+    surface.frame(&client.event_queue.handle(), ());
+    let region = compositor.create_region(&client.event_queue.handle(), ());
+    region.destroy();
+    surface.commit();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.requests, ["create_surface", "create_region", "region.destroy"]);
+
+    server_ddata.requests.clear();
+    server.display.handle().set_paused(&server_surface, false).unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.requests, ["surface.commit", "surface.frame", "surface.commit"]);
+}
+
+#[test]
+#[cfg(feature = "server_system")]
+fn pausing_is_unsupported_by_the_system_backend() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler { requests: Vec::new(), surface: None };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    let surface = compositor.create_surface(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let server_surface = server_ddata.surface.clone().unwrap();
+    assert_eq!(
+        server.display.handle().set_paused(&server_surface, true),
+        Err(ways::backend::PauseError::Unsupported)
+    );
+
+    // the requests are still dispatched as they are read
+    surface.commit();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.requests, ["create_surface", "surface.commit"]);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    requests: Vec<&'static str>,
+    surface: Option<wl_surface::WlSurface>,
+}
+
+impl ways::GlobalDispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<wl_compositor::WlCompositor>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                state.requests.push("create_surface");
+                state.surface = Some(data_init.init(id, ()));
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                state.requests.push("create_region");
+                data_init.init(id, ());
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ways::Dispatch<wl_surface::WlSurface, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_surface::WlSurface,
+        request: wl_surface::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_surface::Request::Commit => state.requests.push("surface.commit"),
+            wl_surface::Request::Frame { callback } => {
+                state.requests.push("surface.frame");
+                data_init.init(callback, ());
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ways::Dispatch<wl_region::WlRegion, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_region::WlRegion,
+        request: wl_region::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_region::Request::Destroy = request {
+            state.requests.push("region.destroy");
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [wl_callback::WlCallback]);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    wayc::protocol::wl_region::WlRegion,
+    wayc::protocol::wl_callback::WlCallback
+]);
@@ -0,0 +1,152 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::protocol::{wl_compositor, wl_region, wl_surface};
+
+#[test]
+fn interceptor_passes_modifies_and_consumes() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler::default();
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler { globals: globals::GlobalList::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    compositor.create_surface(&client.event_queue.handle(), ());
+    let region = compositor.create_region(&client.event_queue.handle(), ());
+    region.add(0, 0, 10, 10);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the region was created by the interceptor, and its requests modified
+    assert_eq!(server_ddata.compositor_requests, ["create_surface"]);
+    assert_eq!(server_ddata.held_regions, 1);
+    assert_eq!(server_ddata.rectangles, [(0, 0, 20, 10)]);
+}
+
+/*
+ * Server Handler
+ */
+
+#[derive(Default)]
+struct ServerHandler {
+    compositor_requests: Vec<&'static str>,
+    held_regions: usize,
+    rectangles: Vec<(i32, i32, i32, i32)>,
+}
+
+impl ways::GlobalDispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<wl_compositor::WlCompositor>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init_with_interceptor(resource, (), |state, _, _, request, _, data_init| {
+            match request {
+                // creating regions is a privileged request, which is handled here
+                wl_compositor::Request::CreateRegion { id } => {
+                    state.held_regions += 1;
+                    data_init.init_with_interceptor(
+                        id,
+                        (),
+                        |_, _, _, request, _, _| match request {
+                            wl_region::Request::Add { x, y, width, height } => {
+                                ways::Intercept::Pass(wl_region::Request::Add {
+                                    x,
+                                    y,
+                                    width: 2 * width,
+                                    height,
+                                })
+                            }
+                            request => ways::Intercept::Pass(request),
+                        },
+                    );
+                    ways::Intercept::Consume
+                }
+                request => ways::Intercept::Pass(request),
+            }
+        });
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                state.compositor_requests.push("create_surface");
+                data_init.init(id, ());
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                state.compositor_requests.push("create_region");
+                data_init.init(id, ());
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl ways::Dispatch<wl_region::WlRegion, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_region::WlRegion,
+        request: wl_region::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_region::Request::Add { x, y, width, height } = request {
+            state.rectangles.push((x, y, width, height));
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [wl_surface::WlSurface]);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    wayc::protocol::wl_region::WlRegion
+]);
@@ -101,6 +101,87 @@ fn resource_user_data() {
     assert_eq!(cloned.data::<UData>().unwrap().0, 1000);
 }
 
+#[test]
+fn resource_user_data_map() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let mut server_ddata = ServerHandler { outputs: Vec::new() };
+
+    let (s_client, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let compositor = s_client
+        .resources_of::<wl_compositor::WlCompositor>(&server.display.handle())
+        .unwrap()
+        .next()
+        .unwrap();
+    let map = compositor.user_data_map().unwrap();
+
+    // values can be replaced, modified and removed after the creation of the resource
+    assert_eq!(map.insert(UData(1)).map(|d| d.0), None);
+    assert_eq!(map.insert(UData(2)).map(|d| d.0), Some(1));
+    assert_eq!(map.with_mut(|d: &mut UData| std::mem::replace(&mut d.0, 3)), Some(2));
+    assert_eq!(map.with(|d: &UData| d.0), Some(3));
+    assert_eq!(map.with_mut_or_insert(|| 10u32, |n| *n + 1), 11);
+    assert_eq!(map.get::<u32>(), Some(10));
+    assert_eq!(map.remove::<UData>().map(|d| d.0), Some(3));
+    assert!(!map.contains::<UData>());
+    assert!(map.contains::<u32>());
+}
+
+#[test]
+fn resource_version_supports() {
+    use wayc::Proxy;
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(2, ());
+    let mut server_ddata = ServerHandler { outputs: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // the output is bound below its latest version
+    let client_output = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            2..3,
+            (),
+        )
+        .unwrap();
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let output = &server_ddata.outputs[0];
+    assert_eq!(output.version(), 2);
+    assert!(output.version_supports(wl_output::EVT_SCALE_OPCODE));
+    assert!(!output.version_supports(wl_output::EVT_NAME_OPCODE));
+    // unknown opcodes are never supported
+    assert!(!output.version_supports(42));
+
+    assert!(!client_output.version_supports(wayc::protocol::wl_output::REQ_RELEASE_OPCODE));
+}
+
 #[test]
 fn dead_resources() {
     let mut server = TestServer::new();
@@ -199,6 +280,110 @@ fn get_resource() {
         .is_ok());
 }
 
+#[test]
+fn resources_of() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(3, ());
+    let mut server_ddata = ServerHandler { outputs: Vec::new() };
+
+    let (s_client, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // create two outputs
+    let client_outputs = (0..2)
+        .map(|_| {
+            client_ddata
+                .globals
+                .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+                    &client.event_queue.handle(),
+                    &registry,
+                    3..4,
+                    (),
+                )
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let outputs = s_client
+        .resources_of::<wl_output::WlOutput>(&server.display.handle())
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(outputs.len(), 2);
+    assert!(outputs.iter().all(|output| server_ddata.outputs.contains(output)));
+    // no compositor was bound
+    assert_eq!(
+        s_client
+            .resources_of::<wl_compositor::WlCompositor>(&server.display.handle())
+            .unwrap()
+            .count(),
+        0
+    );
+
+    // only the live resources are listed
+    client_outputs[0].release();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let outputs = s_client
+        .resources_of::<wl_output::WlOutput>(&server.display.handle())
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(outputs, &server_ddata.outputs[1..]);
+}
+
+#[test]
+fn tracked_global() {
+    let mut server = TestServer::new();
+    let global = server
+        .display
+        .handle()
+        .create_tracked_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(3, ());
+    let mut server_ddata = ServerHandler { outputs: Vec::new() };
+
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+        let (_, mut client) = server.add_client();
+        let mut client_ddata = ClientHandler::new();
+        let registry = client.display.get_registry(&client.event_queue.handle(), ());
+        roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+        let output = client_ddata
+            .globals
+            .bind::<wayc::protocol::wl_output::WlOutput, _, _>(
+                &client.event_queue.handle(),
+                &registry,
+                3..4,
+                (),
+            )
+            .unwrap();
+        roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+        clients.push((client, client_ddata, output));
+    }
+    // the resources of other globals are not listed
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, ways::protocol::wl_output::WlOutput, _>(3, ());
+
+    let mut bound = Vec::new();
+    global.for_each_bound(|output| bound.push(output.clone()));
+    assert_eq!(bound, server_ddata.outputs);
+
+    // the resources released by their client are skipped
+    let (ref mut client, ref mut client_ddata, ref output) = clients[0];
+    output.release();
+    roundtrip(client, &mut server, client_ddata, &mut server_ddata).unwrap();
+    let mut bound = Vec::new();
+    global.for_each_bound(|output| bound.push(output.clone()));
+    assert_eq!(bound, &server_ddata.outputs[1..]);
+}
+
 struct ClientHandler {
     globals: globals::GlobalList,
 }
@@ -219,7 +404,7 @@ wayc::delegate_dispatch!(ClientHandler:
     [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
 );
 
-client_ignore_impl!(ClientHandler => [ClientOutput]);
+client_ignore_impl!(ClientHandler => [ClientOutput, wayc::protocol::wl_compositor::WlCompositor]);
 
 struct ServerHandler {
     outputs: Vec<wl_output::WlOutput>,
@@ -253,3 +438,29 @@ impl ways::Dispatch<wl_output::WlOutput, UData> for ServerHandler {
     ) {
     }
 }
+
+impl ways::GlobalDispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        compositor: ways::New<wl_compositor::WlCompositor>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init(compositor, ways::UserDataMap::new());
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ways::UserDataMap> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        _: wl_compositor::Request,
+        _: &ways::UserDataMap,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+    }
+}
@@ -0,0 +1,244 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestClient, TestServer};
+
+use ways::protocol::{wl_compositor, wl_keyboard, wl_pointer, wl_seat, wl_surface, wl_touch};
+use ways::seat::{CursorImage, Seat, SeatData};
+
+use wayc::protocol::{
+    wl_compositor::WlCompositor as ClientCompositor, wl_keyboard::WlKeyboard as ClientKeyboard,
+    wl_pointer::WlPointer as ClientPointer, wl_seat::WlSeat as ClientSeat,
+};
+
+#[test]
+fn seat_events_follow_focus() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    let seat = Seat::new::<ServerHandler>(&server.display.handle(), "seat0");
+    seat.set_capabilities(wl_seat::Capability::Pointer);
+    let mut server_ddata = ServerHandler { surfaces: Vec::new() };
+
+    let (_, mut client_a) = server.add_client();
+    let mut ddata_a = ClientHandler::new();
+    let (_, mut client_b) = server.add_client();
+    let mut ddata_b = ClientHandler::new();
+
+    // the first client gets a surface, both get a pointer and a keyboard
+    let (_, pointer_a) = setup(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata, true);
+    let (seat_b, _) = setup(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata, false);
+    assert_eq!(ddata_a.events, ["capabilities 1", "name seat0", "keyboard repeat_info 25 600"]);
+    assert_eq!(server_ddata.surfaces.len(), 1);
+    let surface = server_ddata.surfaces[0].clone();
+
+    // capability changes are advertised to all the clients
+    seat.set_capabilities(wl_seat::Capability::Pointer | wl_seat::Capability::Keyboard);
+    seat.set_repeat_info(30, 500);
+
+    // only the client of the focused surface receives the input events
+    seat.set_pointer_focus(Some((&surface, 1.0, 2.0)));
+    seat.pointer_motion(10, 3.0, 4.0);
+    let button_serial = seat.pointer_button(11, 0x110, wl_pointer::ButtonState::Pressed);
+    seat.pointer_frame();
+    seat.set_keyboard_focus(Some(&surface), &[30]);
+    let key_serial = seat.keyboard_key(12, 30, wl_keyboard::KeyState::Released);
+    seat.set_pointer_focus(None);
+    assert!(button_serial < key_serial);
+
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert_eq!(
+        ddata_a.events[3..],
+        [
+            "capabilities 3",
+            "keyboard repeat_info 30 500",
+            "pointer enter 1 2",
+            "pointer frame",
+            "pointer motion 3 4",
+            &format!("pointer button {}", button_serial),
+            "pointer frame",
+            "keyboard enter [30, 0, 0, 0]",
+            &format!("keyboard key {}", key_serial),
+            "pointer leave",
+            "pointer frame",
+        ]
+    );
+    assert_eq!(
+        ddata_b.events,
+        [
+            "capabilities 1",
+            "name seat0",
+            "keyboard repeat_info 25 600",
+            "capabilities 3",
+            "keyboard repeat_info 30 500",
+        ]
+    );
+
+    // the cursor requests of the focused client are honored, others are ignored
+    seat.set_pointer_focus(Some((&surface, 0.0, 0.0)));
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    let enter_serial = ddata_a.enter_serial;
+    pointer_a.set_cursor(enter_serial, None, 4, 5);
+    roundtrip(&mut client_a, &mut server, &mut ddata_a, &mut server_ddata).unwrap();
+    assert_eq!(seat.cursor_image(), Some(CursorImage { surface: None, hotspot: (4, 5) }));
+
+    let pointer_b = seat_b.get_pointer(&client_b.event_queue.handle(), ());
+    pointer_b.set_cursor(enter_serial, None, 7, 7);
+    roundtrip(&mut client_b, &mut server, &mut ddata_b, &mut server_ddata).unwrap();
+    assert_eq!(seat.cursor_image(), Some(CursorImage { surface: None, hotspot: (4, 5) }));
+}
+
+fn setup(
+    client: &mut TestClient<ClientHandler>,
+    server: &mut TestServer<ServerHandler>,
+    client_ddata: &mut ClientHandler,
+    server_ddata: &mut ServerHandler,
+    with_surface: bool,
+) -> (ClientSeat, ClientPointer) {
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    let seat = client_ddata.globals.bind::<ClientSeat, _, _>(&qh, &registry, 9..10, ()).unwrap();
+    if with_surface {
+        let compositor =
+            client_ddata.globals.bind::<ClientCompositor, _, _>(&qh, &registry, 1..2, ()).unwrap();
+        compositor.create_surface(&qh, ());
+    }
+    let pointer = seat.get_pointer(&qh, ());
+    seat.get_keyboard(&qh, ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    (seat, pointer)
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    surfaces: Vec<wl_surface::WlSurface>,
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            state.surfaces.push(init.init(id, ()));
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+server_ignore_impl!(ServerHandler => [wl_surface::WlSurface]);
+server_ignore_global_impl!(ServerHandler => [wl_compositor::WlCompositor]);
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_pointer::WlPointer: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_keyboard::WlKeyboard: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_touch::WlTouch: SeatData] => Seat);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    events: Vec<String>,
+    enter_serial: u32,
+}
+
+impl ClientHandler {
+    fn new() -> ClientHandler {
+        ClientHandler { globals: globals::GlobalList::new(), events: Vec::new(), enter_serial: 0 }
+    }
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<ClientSeat, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientSeat,
+        event: wayc::protocol::wl_seat::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        use wayc::protocol::wl_seat::Event;
+        match event {
+            Event::Capabilities { capabilities: wayc::WEnum::Value(capabilities) } => {
+                state.events.push(format!("capabilities {}", capabilities.bits()))
+            }
+            Event::Name { name } => state.events.push(format!("name {}", name)),
+            _ => panic!("Unexpected event!"),
+        }
+    }
+}
+
+impl wayc::Dispatch<ClientPointer, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientPointer,
+        event: wayc::protocol::wl_pointer::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        use wayc::protocol::wl_pointer::Event;
+        let event = match event {
+            Event::Enter { serial, surface_x, surface_y, .. } => {
+                state.enter_serial = serial;
+                format!("pointer enter {} {}", surface_x, surface_y)
+            }
+            Event::Leave { .. } => "pointer leave".into(),
+            Event::Motion { surface_x, surface_y, .. } => {
+                format!("pointer motion {} {}", surface_x, surface_y)
+            }
+            Event::Button { serial, .. } => format!("pointer button {}", serial),
+            Event::Frame => "pointer frame".into(),
+            _ => panic!("Unexpected event!"),
+        };
+        state.events.push(event);
+    }
+}
+
+impl wayc::Dispatch<ClientKeyboard, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &ClientKeyboard,
+        event: wayc::protocol::wl_keyboard::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        use wayc::protocol::wl_keyboard::Event;
+        let event = match event {
+            Event::Enter { keys, .. } => format!("keyboard enter {:?}", keys),
+            Event::Key { serial, .. } => format!("keyboard key {}", serial),
+            Event::RepeatInfo { rate, delay } => format!("keyboard repeat_info {} {}", rate, delay),
+            _ => panic!("Unexpected event!"),
+        };
+        state.events.push(event);
+    }
+}
+
+client_ignore_impl!(ClientHandler => [
+    ClientCompositor,
+    wayc::protocol::wl_surface::WlSurface
+]);
@@ -0,0 +1,207 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestClient, TestServer};
+
+use std::os::unix::{
+    io::{AsFd, OwnedFd},
+    net::{UnixListener, UnixStream},
+};
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols::wp::security_context::v1::{
+    client as secc,
+    server::{wp_security_context_manager_v1 as manager, wp_security_context_v1 as context},
+};
+
+#[test]
+fn sandboxed_listener() {
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, manager::WpSecurityContextManagerV1, _>(1, ());
+    let mut server_ddata = ServerHandler { sandboxed: Vec::new() };
+
+    // the sandbox engine registers a listener
+    let (_, mut engine) = server.add_client();
+    let mut engine_ddata = ClientHandler::new();
+    let registry = engine.display.get_registry(&engine.event_queue.handle(), ());
+    roundtrip(&mut engine, &mut server, &mut engine_ddata, &mut server_ddata).unwrap();
+    let manager = engine_ddata
+        .globals
+        .bind::<secc::wp_security_context_manager_v1::WpSecurityContextManagerV1, _, _>(
+            &engine.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sandbox-0");
+    let listener = UnixListener::bind(&path).unwrap();
+    let (close_fd, close_peer) = UnixStream::pair().unwrap();
+    let context = manager.create_listener(
+        listener.as_fd(),
+        close_fd.as_fd(),
+        &engine.event_queue.handle(),
+        (),
+    );
+    context.set_sandbox_engine("org.example.sandbox".into());
+    context.set_app_id("org.example.App".into());
+    context.set_instance_id("42".into());
+    context.commit();
+    context.destroy();
+    roundtrip(&mut engine, &mut server, &mut engine_ddata, &mut server_ddata).unwrap();
+    drop((listener, close_fd));
+
+    // the clients of the sandbox are tagged with its security context
+    let mut sandboxed = TestClient::new(UnixStream::connect(&path).unwrap());
+    // the connection is accepted before its requests are read
+    server.answer(&mut server_ddata);
+    let mut sandboxed_ddata = ClientHandler::new();
+    sandboxed.display.get_registry(&sandboxed.event_queue.handle(), ());
+    roundtrip(&mut sandboxed, &mut server, &mut sandboxed_ddata, &mut server_ddata).unwrap();
+    // and cannot see the manager global
+    assert!(sandboxed_ddata.globals.list().is_empty());
+
+    assert_eq!(server_ddata.sandboxed.len(), 1);
+    let client_id = server_ddata.sandboxed[0].id.lock().unwrap().clone().unwrap();
+    let client = ways::Client::from_id(&server.display.handle(), client_id).unwrap();
+    let expected = ways::SecurityContext {
+        sandbox_engine: Some("org.example.sandbox".into()),
+        app_id: Some("org.example.App".into()),
+        instance_id: Some("42".into()),
+    };
+    assert_eq!(client.security_context(), Some(&expected));
+    // the data given by the compositor remains accessible
+    assert!(client.get_data::<SandboxedData>().is_some());
+
+    // hanging up the close fd closes the listener
+    drop(close_peer);
+    server.answer(&mut server_ddata);
+    assert!(UnixStream::connect(&path).is_err());
+}
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+}
+
+impl ClientHandler {
+    fn new() -> ClientHandler {
+        ClientHandler { globals: Default::default() }
+    }
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    secc::wp_security_context_manager_v1::WpSecurityContextManagerV1,
+    secc::wp_security_context_v1::WpSecurityContextV1
+]);
+
+// the data of the sandboxed clients, recording their id
+#[derive(Default)]
+struct SandboxedData {
+    id: Mutex<Option<ways::backend::ClientId>>,
+}
+
+impl ways::backend::ClientData for SandboxedData {
+    fn initialized(&self, client_id: ways::backend::ClientId) {
+        *self.id.lock().unwrap() = Some(client_id);
+    }
+}
+
+struct ServerHandler {
+    sandboxed: Vec<Arc<SandboxedData>>,
+}
+
+// the state of a wp_security_context_v1 until it is committed
+#[derive(Default)]
+struct PendingContext {
+    context: ways::SecurityContext,
+    fds: Option<(OwnedFd, OwnedFd)>,
+}
+
+impl ways::GlobalDispatch<manager::WpSecurityContextManagerV1, ()> for ServerHandler {
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<manager::WpSecurityContextManagerV1>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    // sandboxed clients must not create nested security contexts
+    fn can_view(client: ways::Client, _: &()) -> bool {
+        client.security_context().is_none()
+    }
+}
+
+impl ways::Dispatch<manager::WpSecurityContextManagerV1, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &manager::WpSecurityContextManagerV1,
+        request: manager::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let manager::Request::CreateListener { id, listen_fd, close_fd } = request {
+            let pending = PendingContext { fds: Some((listen_fd, close_fd)), ..Default::default() };
+            data_init.init(id, Mutex::new(pending));
+        }
+    }
+}
+
+impl ways::Dispatch<context::WpSecurityContextV1, Mutex<PendingContext>> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &context::WpSecurityContextV1,
+        request: context::Request,
+        data: &Mutex<PendingContext>,
+        dhandle: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        let mut pending = data.lock().unwrap();
+        match request {
+            context::Request::SetSandboxEngine { name } => {
+                pending.context.sandbox_engine = Some(name)
+            }
+            context::Request::SetAppId { app_id } => pending.context.app_id = Some(app_id),
+            context::Request::SetInstanceId { instance_id } => {
+                pending.context.instance_id = Some(instance_id)
+            }
+            context::Request::Commit => {
+                let (listen_fd, close_fd) = pending.fds.take().unwrap();
+                dhandle
+                    .add_sandboxed_listener::<ServerHandler, _>(
+                        listen_fd,
+                        close_fd,
+                        pending.context.clone(),
+                        |state, _, _| {
+                            let data = Arc::new(SandboxedData::default());
+                            state.sandboxed.push(data.clone());
+                            Some(data)
+                        },
+                    )
+                    .unwrap();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,244 @@
+extern crate tempfile;
+
+use std::io::Write;
+use std::os::unix::io::AsFd;
+
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use ways::protocol::{wl_buffer, wl_shm, wl_shm_pool};
+use ways::shm::{
+    with_buffer_contents, BufferAccessError, BufferInfo, ShmBuffer, ShmGlobal, ShmGlobalData,
+    ShmPool,
+};
+
+#[test]
+fn shm_buffer_contents() {
+    let mut server = TestServer::new();
+    server.display.handle().create_global::<ServerHandler, wl_shm::WlShm, _>(1, ());
+    let mut server_ddata = ServerHandler { buffers: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata =
+        ClientHandler { globals: globals::GlobalList::new(), formats: Vec::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let shm = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_shm::WlShm, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(b"I like trains!!!").unwrap();
+    file.flush().unwrap();
+    let pool = shm.create_pool(file.as_fd(), 16, &client.event_queue.handle(), ());
+    let format = wayc::protocol::wl_shm::Format::Argb8888;
+    pool.create_buffer(8, 1, 2, 4, format, &client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // grow the file and the pool, the new buffer is only valid in the resized pool
+    file.write_all(b"and more trains!").unwrap();
+    file.flush().unwrap();
+    pool.resize(32);
+    pool.create_buffer(16, 4, 1, 16, format, &client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    assert_eq!(server_ddata.buffers.len(), 2);
+    let first = with_buffer_contents(&server_ddata.buffers[0], |ptr, len, info| {
+        assert_eq!(
+            info,
+            BufferInfo {
+                offset: 8,
+                width: 1,
+                height: 2,
+                stride: 4,
+                format: ways::WEnum::Value(wl_shm::Format::Argb8888)
+            }
+        );
+        copy_contents(ptr, len)
+    });
+    assert_eq!(first.unwrap(), b"rains!!!");
+    let second = with_buffer_contents(&server_ddata.buffers[1], copy_buffer);
+    assert_eq!(second.unwrap(), b"and more trains!");
+
+    // accesses can be nested, even in the same pool
+    let nested = with_buffer_contents(&server_ddata.buffers[1], |ptr, len, _| {
+        let outer = copy_contents(ptr, len);
+        with_buffer_contents(&server_ddata.buffers[0], |ptr, len, _| {
+            [outer, copy_contents(ptr, len)].concat()
+        })
+    });
+    assert_eq!(nested.unwrap().unwrap(), b"and more trains!rains!!!");
+
+    // the client truncates the file, reading the buffer fails instead of crashing the server
+    file.set_len(0).unwrap();
+    let truncated = with_buffer_contents(&server_ddata.buffers[1], copy_buffer);
+    assert_eq!(truncated, Err(BufferAccessError::BadMap));
+}
+
+fn copy_contents(ptr: *const u8, len: usize) -> Vec<u8> {
+    let mut contents = vec![0; len];
+    unsafe { std::ptr::copy_nonoverlapping(ptr, contents.as_mut_ptr(), len) };
+    contents
+}
+
+fn copy_buffer(ptr: *const u8, len: usize, _: BufferInfo) -> Vec<u8> {
+    copy_contents(ptr, len)
+}
+
+#[test]
+fn shm_global_add_format() {
+    let mut server = TestServer::new();
+    let shm_global = ShmGlobal::new::<GlobalHandler>(
+        &server.display.handle(),
+        [wl_shm::Format::Xrgb8888, wl_shm::Format::Rgb565],
+    );
+    let mut server_ddata = GlobalHandler;
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata =
+        ClientHandler { globals: globals::GlobalList::new(), formats: Vec::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_shm::WlShm, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    use wayc::protocol::wl_shm::Format;
+    assert_eq!(client_ddata.formats, [Format::Argb8888, Format::Xrgb8888, Format::Rgb565]);
+
+    // formats added later are sent to the clients which already bound the global
+    assert!(shm_global.add_format(wl_shm::Format::Abgr8888));
+    assert!(!shm_global.add_format(wl_shm::Format::Rgb565));
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(
+        client_ddata.formats,
+        [Format::Argb8888, Format::Xrgb8888, Format::Rgb565, Format::Abgr8888]
+    );
+    assert_eq!(shm_global.formats().len(), 4);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    buffers: Vec<wl_buffer::WlBuffer>,
+}
+
+impl ways::Dispatch<wl_shm::WlShm, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &wl_shm::WlShm,
+        request: wl_shm::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_shm::Request::CreatePool { fd, size, id } = request {
+            init.init(id, ShmPool::new(fd, size).unwrap());
+        } else {
+            panic!("Unexpected request!");
+        }
+    }
+}
+
+impl ways::Dispatch<wl_shm_pool::WlShmPool, ShmPool> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_shm_pool::WlShmPool,
+        request: wl_shm_pool::Request,
+        pool: &ShmPool,
+        _: &ways::DisplayHandle,
+        init: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_shm_pool::Request::CreateBuffer { id, offset, width, height, stride, format } => {
+                let buffer = ShmBuffer::new(pool, offset, width, height, stride, format).unwrap();
+                state.buffers.push(init.init(id, buffer));
+            }
+            wl_shm_pool::Request::Resize { size } => pool.resize(size).unwrap(),
+            _ => {}
+        }
+    }
+}
+
+impl ways::Dispatch<wl_buffer::WlBuffer, ShmBuffer> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &wl_buffer::WlBuffer,
+        _: wl_buffer::Request,
+        _: &ShmBuffer,
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+    }
+}
+
+server_ignore_global_impl!(ServerHandler => [wl_shm::WlShm]);
+
+struct GlobalHandler;
+
+ways::delegate_global_dispatch!(GlobalHandler: [wl_shm::WlShm: ShmGlobalData] => ShmGlobal);
+ways::delegate_dispatch!(GlobalHandler: [wl_shm::WlShm: ShmGlobalData] => ShmGlobal);
+ways::delegate_dispatch!(GlobalHandler: [wl_shm_pool::WlShmPool: ShmPool] => ShmGlobal);
+ways::delegate_dispatch!(GlobalHandler: [wl_buffer::WlBuffer: ShmBuffer] => ShmGlobal);
+
+/*
+ * Client Handler
+ */
+struct ClientHandler {
+    globals: globals::GlobalList,
+    formats: Vec<wayc::protocol::wl_shm::Format>,
+}
+
+impl wayc::Dispatch<wayc::protocol::wl_shm::WlShm, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &wayc::protocol::wl_shm::WlShm,
+        event: wayc::protocol::wl_shm::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        if let wayc::protocol::wl_shm::Event::Format { format: wayc::WEnum::Value(format) } = event
+        {
+            state.formats.push(format);
+        }
+    }
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_shm_pool::WlShmPool,
+    wayc::protocol::wl_buffer::WlBuffer
+]);
@@ -0,0 +1,354 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestClient, TestServer};
+
+use wayland_protocols::ext::session_lock::{
+    helpers::{deny_while_locked, LockState, SessionLock},
+    v1::{client as lock_client, server as lock_server},
+};
+
+use lock_server::{ext_session_lock_manager_v1, ext_session_lock_surface_v1, ext_session_lock_v1};
+use ways::protocol::{wl_compositor, wl_output, wl_surface};
+
+#[test]
+fn lock_and_unlock() {
+    let mut server = setup();
+    let mut server_ddata = ServerHandler::new();
+
+    let (_, mut locker) = server.add_client();
+    let mut locker_ddata = ClientHandler::new();
+    let (_, mut other) = server.add_client();
+    let mut other_ddata = ClientHandler::new();
+    let (locker_globals, locker_surface) =
+        bind(&mut locker, &mut server, &mut locker_ddata, &mut server_ddata);
+    let (other_globals, other_surface) =
+        bind(&mut other, &mut server, &mut other_ddata, &mut server_ddata);
+
+    let qh = locker.event_queue.handle();
+    let lock = locker_globals.manager.lock(&qh, ());
+    let lock_surface = lock.get_lock_surface(&locker_surface, &locker_globals.output, &qh, ());
+    roundtrip(&mut locker, &mut server, &mut locker_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.lock.state(), LockState::Locking);
+
+    // the lock surface is configured before being committed
+    let output = server_ddata.outputs[0].clone();
+    assert!(server_ddata.lock.configure(&output, 100, 50, 1));
+    roundtrip(&mut locker, &mut server, &mut locker_ddata, &mut server_ddata).unwrap();
+    assert_eq!(locker_ddata.configures, [1]);
+    lock_surface.ack_configure(1);
+    locker_surface.commit();
+    roundtrip(&mut locker, &mut server, &mut locker_ddata, &mut server_ddata).unwrap();
+    assert!(server_ddata.lock.output_surface(&output).is_some());
+
+    server_ddata.lock.confirm_locked();
+    roundtrip(&mut locker, &mut server, &mut locker_ddata, &mut server_ddata).unwrap();
+    assert_eq!(locker_ddata.events, ["locked"]);
+
+    // the other clients cannot lock the session, and their commits are dropped
+    other_globals.manager.lock(&other.event_queue.handle(), ());
+    other_surface.commit();
+    roundtrip(&mut other, &mut server, &mut other_ddata, &mut server_ddata).unwrap();
+    assert_eq!(other_ddata.events, ["finished"]);
+    assert_eq!(server_ddata.commits, 1);
+
+    lock.unlock_and_destroy();
+    roundtrip(&mut locker, &mut server, &mut locker_ddata, &mut server_ddata).unwrap();
+    assert!(!server_ddata.lock.is_locked());
+    other_surface.commit();
+    roundtrip(&mut other, &mut server, &mut other_ddata, &mut server_ddata).unwrap();
+    assert_eq!(server_ddata.commits, 2);
+}
+
+#[test]
+fn lock_surface_errors() {
+    let mut server = setup();
+    let mut server_ddata = ServerHandler::new();
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+    let (globals, surface) = bind(&mut client, &mut server, &mut client_ddata, &mut server_ddata);
+    let qh = client.event_queue.handle();
+    let lock = globals.manager.lock(&qh, ());
+    lock.get_lock_surface(&surface, &globals.output, &qh, ());
+    // the lock surface was not configured yet
+    surface.commit();
+    assert!(roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).is_err());
+    let error = client.conn.protocol_error().unwrap();
+    assert_eq!(error.object_interface, "ext_session_lock_surface_v1");
+    assert_eq!(error.code, ext_session_lock_surface_v1::Error::CommitBeforeFirstAck as u32);
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler::new();
+    let (globals, surface) = bind(&mut client, &mut server, &mut client_ddata, &mut server_ddata);
+    let qh = client.event_queue.handle();
+    let lock = globals.manager.lock(&qh, ());
+    // the lock of the dead client is taken over
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert_eq!(client_ddata.events, Vec::<&str>::new());
+    lock.get_lock_surface(&surface, &globals.output, &qh, ());
+    let second = globals.compositor.create_surface(&qh, ());
+    lock.get_lock_surface(&second, &globals.output, &qh, ());
+    assert!(roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).is_err());
+    let error = client.conn.protocol_error().unwrap();
+    assert_eq!(error.object_interface, "ext_session_lock_v1");
+    assert_eq!(error.code, ext_session_lock_v1::Error::DuplicateOutput as u32);
+    assert_eq!(server_ddata.lock.state(), LockState::Locking);
+}
+
+struct Globals {
+    compositor: wayc::protocol::wl_compositor::WlCompositor,
+    output: wayc::protocol::wl_output::WlOutput,
+    manager: lock_client::ext_session_lock_manager_v1::ExtSessionLockManagerV1,
+}
+
+fn setup() -> TestServer<ServerHandler> {
+    let server = TestServer::new();
+    let handle = server.display.handle();
+    handle.create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    handle.create_global::<ServerHandler, wl_output::WlOutput, _>(1, ());
+    handle.create_global::<ServerHandler, ext_session_lock_manager_v1::ExtSessionLockManagerV1, _>(
+        1,
+        (),
+    );
+    server
+}
+
+fn bind(
+    client: &mut TestClient<ClientHandler>,
+    server: &mut TestServer<ServerHandler>,
+    client_ddata: &mut ClientHandler,
+    server_ddata: &mut ServerHandler,
+) -> (Globals, wayc::protocol::wl_surface::WlSurface) {
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(client, server, client_ddata, server_ddata).unwrap();
+    let qh = client.event_queue.handle();
+    let globals = Globals {
+        compositor: client_ddata.globals.bind(&qh, &registry, 1..2, ()).unwrap(),
+        output: client_ddata.globals.bind(&qh, &registry, 1..2, ()).unwrap(),
+        manager: client_ddata.globals.bind(&qh, &registry, 1..2, ()).unwrap(),
+    };
+    let surface = globals.compositor.create_surface(&qh, ());
+    (globals, surface)
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    lock: SessionLock,
+    outputs: Vec<wl_output::WlOutput>,
+    commits: usize,
+}
+
+impl ServerHandler {
+    fn new() -> Self {
+        ServerHandler { lock: SessionLock::new(), outputs: Vec::new(), commits: 0 }
+    }
+}
+
+impl AsRef<SessionLock> for ServerHandler {
+    fn as_ref(&self) -> &SessionLock {
+        &self.lock
+    }
+}
+
+server_ignore_global_impl!(ServerHandler => [
+    wl_compositor::WlCompositor,
+    ext_session_lock_manager_v1::ExtSessionLockManagerV1
+]);
+
+server_ignore_impl!(ServerHandler => [wl_output::WlOutput]);
+
+impl ways::GlobalDispatch<wl_output::WlOutput, ()> for ServerHandler {
+    fn bind(
+        state: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<wl_output::WlOutput>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        state.outputs.push(data_init.init(resource, ()));
+    }
+}
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        _: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            data_init.init_with_interceptor(
+                id,
+                (),
+                deny_while_locked::<wl_surface::WlSurface, _>(|request| {
+                    matches!(request, wl_surface::Request::Commit)
+                }),
+            );
+        }
+    }
+}
+
+impl ways::Dispatch<wl_surface::WlSurface, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        surface: &wl_surface::WlSurface,
+        request: wl_surface::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_surface::Request::Commit = request {
+            state.commits += 1;
+            if let Err(error) = state.lock.commit(surface, Some((100, 50))) {
+                let lock_surface = state.lock.lock_surface(surface).unwrap();
+                lock_surface.post_typed_error(error, format!("{:?}", error));
+            }
+        }
+    }
+}
+
+impl ways::Dispatch<ext_session_lock_manager_v1::ExtSessionLockManagerV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &ext_session_lock_manager_v1::ExtSessionLockManagerV1,
+        request: ext_session_lock_manager_v1::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let ext_session_lock_manager_v1::Request::Lock { id } = request {
+            let lock = data_init.init(id, ());
+            state.lock.lock(&lock);
+        }
+    }
+}
+
+impl ways::Dispatch<ext_session_lock_v1::ExtSessionLockV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        lock: &ext_session_lock_v1::ExtSessionLockV1,
+        request: ext_session_lock_v1::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        let result = match request {
+            ext_session_lock_v1::Request::GetLockSurface { id, surface, output } => {
+                let lock_surface = data_init.init(id, ());
+                state.lock.get_lock_surface(lock, &lock_surface, &surface, &output)
+            }
+            ext_session_lock_v1::Request::Destroy => state.lock.destroy(lock),
+            ext_session_lock_v1::Request::UnlockAndDestroy => state.lock.unlock_and_destroy(lock),
+            _ => unreachable!(),
+        };
+        if let Err(error) = result {
+            lock.post_typed_error(error, format!("{:?}", error));
+        }
+    }
+}
+
+impl ways::Dispatch<ext_session_lock_surface_v1::ExtSessionLockSurfaceV1, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        lock_surface: &ext_session_lock_surface_v1::ExtSessionLockSurfaceV1,
+        request: ext_session_lock_surface_v1::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_session_lock_surface_v1::Request::AckConfigure { serial } => {
+                if let Err(error) = state.lock.ack_configure(lock_surface, serial) {
+                    lock_surface.post_typed_error(error, format!("{:?}", error));
+                }
+            }
+            ext_session_lock_surface_v1::Request::Destroy => {
+                state.lock.lock_surface_destroyed(lock_surface)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    events: Vec<&'static str>,
+    configures: Vec<u32>,
+}
+
+impl ClientHandler {
+    fn new() -> Self {
+        ClientHandler {
+            globals: globals::GlobalList::new(),
+            events: Vec::new(),
+            configures: Vec::new(),
+        }
+    }
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    wayc::protocol::wl_output::WlOutput,
+    lock_client::ext_session_lock_manager_v1::ExtSessionLockManagerV1
+]);
+
+impl wayc::Dispatch<lock_client::ext_session_lock_v1::ExtSessionLockV1, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &lock_client::ext_session_lock_v1::ExtSessionLockV1,
+        event: lock_client::ext_session_lock_v1::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.events.push(match event {
+            lock_client::ext_session_lock_v1::Event::Locked => "locked",
+            lock_client::ext_session_lock_v1::Event::Finished => "finished",
+            _ => unreachable!(),
+        });
+    }
+}
+
+impl wayc::Dispatch<lock_client::ext_session_lock_surface_v1::ExtSessionLockSurfaceV1, ()>
+    for ClientHandler
+{
+    fn event(
+        state: &mut Self,
+        _: &lock_client::ext_session_lock_surface_v1::ExtSessionLockSurfaceV1,
+        event: lock_client::ext_session_lock_surface_v1::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        if let lock_client::ext_session_lock_surface_v1::Event::Configure { serial, .. } = event {
+            state.configures.push(serial);
+        }
+    }
+}
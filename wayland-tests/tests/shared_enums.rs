@@ -0,0 +1,145 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+pub mod lamp {
+    pub mod __interfaces {
+        wayland_scanner::generate_interfaces!("tests/assets/test-lamp.xml");
+    }
+
+    pub mod types {
+        use super::__interfaces::*;
+        use wayland_backend;
+        wayland_scanner::generate_enums!("tests/assets/test-lamp.xml");
+    }
+
+    pub mod client {
+        use super::__interfaces::*;
+        use wayland_client;
+        wayland_scanner::generate_client_code!("tests/assets/test-lamp.xml", enums = super::types);
+    }
+
+    pub mod server {
+        use super::__interfaces::*;
+        use wayland_server;
+        wayland_scanner::generate_server_code!("tests/assets/test-lamp.xml", enums = super::types);
+    }
+}
+
+use lamp::types::test_lamp::Color;
+
+#[test]
+fn client_and_server_share_enums() {
+    // both sides use the same types
+    let _: lamp::client::test_lamp::Color = lamp::server::test_lamp::Color::Green;
+    let _: lamp::server::test_lamp::Error = lamp::client::test_lamp::Error::Broken;
+
+    let mut server = TestServer::new();
+    server
+        .display
+        .handle()
+        .create_global::<ServerHandler, lamp::server::test_lamp::TestLamp, ()>(1, ());
+    let mut server_ddata = ServerHandler { colors: Vec::new() };
+
+    let (_, mut client) = server.add_client();
+    let mut client_ddata =
+        ClientHandler { globals: globals::GlobalList::new(), colors: Vec::new() };
+
+    let registry = client.display.get_registry(&client.event_queue.handle(), ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let lamp = client_ddata
+        .globals
+        .bind::<lamp::client::test_lamp::TestLamp, _, _>(
+            &client.event_queue.handle(),
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+
+    lamp.set_color(Color::Blue);
+    lamp.set_color(Color::Red);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    assert_eq!(server_ddata.colors, [Color::Blue, Color::Red]);
+    assert_eq!(client_ddata.colors, [Color::Blue, Color::Red]);
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    colors: Vec<Color>,
+}
+
+impl ways::GlobalDispatch<lamp::server::test_lamp::TestLamp, ()> for ServerHandler {
+    fn bind(
+        _: &mut Self,
+        _: &ways::DisplayHandle,
+        _: &ways::Client,
+        resource: ways::New<lamp::server::test_lamp::TestLamp>,
+        _: &(),
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl ways::Dispatch<lamp::server::test_lamp::TestLamp, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        resource: &lamp::server::test_lamp::TestLamp,
+        request: lamp::server::test_lamp::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        _: &mut ways::DataInit<'_, Self>,
+    ) {
+        match request {
+            lamp::server::test_lamp::Request::SetColor { color: ways::WEnum::Value(color) } => {
+                state.colors.push(color);
+                resource.color(color);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    colors: Vec<Color>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+impl wayc::Dispatch<lamp::client::test_lamp::TestLamp, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &lamp::client::test_lamp::TestLamp,
+        event: lamp::client::test_lamp::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        match event {
+            lamp::client::test_lamp::Event::Color { color: wayc::WEnum::Value(color) } => {
+                state.colors.push(color)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
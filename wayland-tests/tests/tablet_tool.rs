@@ -0,0 +1,259 @@
+#[macro_use]
+mod helpers;
+
+use helpers::{globals, roundtrip, wayc, ways, TestServer};
+
+use wayland_protocols::wp::tablet::{
+    tool::{ButtonChange, ClientTabletTool, ToolFrame},
+    zv2::{client as tablet_client, server as tablet_server},
+};
+
+use tablet_server::{zwp_tablet_manager_v2, zwp_tablet_seat_v2, zwp_tablet_tool_v2, zwp_tablet_v2};
+use wayc::Proxy;
+use ways::protocol::{wl_compositor, wl_keyboard, wl_pointer, wl_seat, wl_surface, wl_touch};
+use ways::seat::{Seat, SeatData};
+
+const BTN_STYLUS: u32 = 0x14b;
+
+#[test]
+fn tool_events_are_batched() {
+    let mut server = TestServer::new();
+    let handle = server.display.handle();
+    handle.create_global::<ServerHandler, wl_compositor::WlCompositor, _>(1, ());
+    handle.create_global::<ServerHandler, zwp_tablet_manager_v2::ZwpTabletManagerV2, _>(1, ());
+    let _seat = Seat::new::<ServerHandler>(&handle, "seat0");
+    let mut server_ddata = ServerHandler { surfaces: Vec::new(), tablet_seats: Vec::new() };
+
+    let (server_client, mut client) = server.add_client();
+    let mut client_ddata = ClientHandler {
+        globals: globals::GlobalList::new(),
+        frames: Vec::new(),
+        tools: Vec::new(),
+    };
+
+    let qh = client.event_queue.handle();
+    let registry = client.display.get_registry(&qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    let client_seat = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_seat::WlSeat, _, _>(&qh, &registry, 1..10, ())
+        .unwrap();
+    let compositor = client_ddata
+        .globals
+        .bind::<wayc::protocol::wl_compositor::WlCompositor, _, _>(&qh, &registry, 1..2, ())
+        .unwrap();
+    let manager = client_ddata
+        .globals
+        .bind::<tablet_client::zwp_tablet_manager_v2::ZwpTabletManagerV2, _, _>(
+            &qh,
+            &registry,
+            1..2,
+            (),
+        )
+        .unwrap();
+    let client_surface = compositor.create_surface(&qh, ());
+    manager.get_tablet_seat(&client_seat, &qh, ());
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    // add a tablet and a pen, and describe the pen
+    let dh = server.display.handle();
+    let tablet_seat = server_ddata.tablet_seats[0].clone();
+    let tablet = server_client
+        .create_resource::<zwp_tablet_v2::ZwpTabletV2, (), ServerHandler>(&dh, 1, ())
+        .unwrap();
+    tablet_seat.tablet_added(&tablet);
+    tablet.done();
+    let tool = server_client
+        .create_resource::<zwp_tablet_tool_v2::ZwpTabletToolV2, (), ServerHandler>(&dh, 1, ())
+        .unwrap();
+    tablet_seat.tool_added(&tool);
+    tool._type(zwp_tablet_tool_v2::Type::Pen);
+    tool.hardware_serial(1, 2);
+    tool.capability(zwp_tablet_tool_v2::Capability::Pressure);
+    tool.capability(zwp_tablet_tool_v2::Capability::Tilt);
+    tool.done();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let client_tool = client_ddata.tools[0].clone();
+    let data = client_tool.data::<ClientTabletTool>().unwrap();
+    let info = data.info().unwrap();
+    assert_eq!(info.tool_type, Some(tablet_client::zwp_tablet_tool_v2::Type::Pen));
+    assert_eq!(info.hardware_serial, Some(1 << 32 | 2));
+    assert_eq!(info.hardware_id_wacom, None);
+    assert_eq!(info.capabilities.len(), 2);
+    assert!(client_ddata.frames.is_empty());
+
+    // the pen comes in proximity and touches the tablet in a single frame
+    let surface = server_ddata.surfaces[0].clone();
+    tool.proximity_in(10, &tablet, &surface);
+    tool.motion(3.0, 4.0);
+    tool.down(11);
+    tool.pressure(1000);
+    tool.tilt(5.0, -5.0);
+    tool.button(12, BTN_STYLUS, zwp_tablet_tool_v2::ButtonState::Pressed);
+    tool.wheel(15.0, 1);
+    tool.wheel(15.0, 1);
+    tool.frame(100);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let frames = std::mem::take(&mut client_ddata.frames);
+    assert_eq!(frames.len(), 1);
+    let frame = &frames[0];
+    assert_eq!(frame.time, 100);
+    assert_eq!((frame.proximity_in, frame.down, frame.up), (Some(10), Some(11), false));
+    assert_eq!(frame.wheel, (30.0, 2));
+    assert_eq!(frame.buttons, [ButtonChange { serial: 12, button: BTN_STYLUS, pressed: true }]);
+    let (_, proximity_surface) = frame.state.proximity.clone().unwrap();
+    assert_eq!(proximity_surface, client_surface);
+    assert_eq!(frame.state.position, (3.0, 4.0));
+    assert!(frame.state.down);
+    assert_eq!(frame.state.pressure, 1000);
+    assert_eq!(frame.state.tilt, (5.0, -5.0));
+    assert_eq!(frame.state.buttons, [BTN_STYLUS]);
+
+    // the state is kept across frames, and reset when leaving proximity
+    tool.pressure(2000);
+    tool.frame(110);
+    tool.up();
+    tool.proximity_out();
+    tool.frame(120);
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+
+    let frames = std::mem::take(&mut client_ddata.frames);
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].state.down);
+    assert_eq!(frames[0].state.pressure, 2000);
+    assert_eq!(frames[0].state.buttons, [BTN_STYLUS]);
+    assert_eq!(frames[0].proximity_in, None);
+    assert!(frames[1].up && frames[1].proximity_out);
+    assert!(frames[1].state.proximity.is_none());
+    assert!(!frames[1].state.down);
+    assert!(frames[1].state.buttons.is_empty());
+    assert_eq!(data.state(), frames[1].state);
+
+    tool.removed();
+    roundtrip(&mut client, &mut server, &mut client_ddata, &mut server_ddata).unwrap();
+    assert!(data.is_removed());
+}
+
+/*
+ * Server Handler
+ */
+
+struct ServerHandler {
+    surfaces: Vec<wl_surface::WlSurface>,
+    tablet_seats: Vec<zwp_tablet_seat_v2::ZwpTabletSeatV2>,
+}
+
+server_ignore_global_impl!(ServerHandler => [
+    wl_compositor::WlCompositor,
+    zwp_tablet_manager_v2::ZwpTabletManagerV2
+]);
+
+server_ignore_impl!(ServerHandler => [
+    wl_surface::WlSurface,
+    zwp_tablet_seat_v2::ZwpTabletSeatV2,
+    zwp_tablet_v2::ZwpTabletV2,
+    zwp_tablet_tool_v2::ZwpTabletToolV2
+]);
+
+impl ways::Dispatch<wl_compositor::WlCompositor, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            state.surfaces.push(data_init.init(id, ()));
+        }
+    }
+}
+
+impl ways::Dispatch<zwp_tablet_manager_v2::ZwpTabletManagerV2, ()> for ServerHandler {
+    fn request(
+        state: &mut Self,
+        _: &ways::Client,
+        _: &zwp_tablet_manager_v2::ZwpTabletManagerV2,
+        request: zwp_tablet_manager_v2::Request,
+        _: &(),
+        _: &ways::DisplayHandle,
+        data_init: &mut ways::DataInit<'_, Self>,
+    ) {
+        if let zwp_tablet_manager_v2::Request::GetTabletSeat { tablet_seat, .. } = request {
+            state.tablet_seats.push(data_init.init(tablet_seat, ()));
+        }
+    }
+}
+
+ways::delegate_global_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_seat::WlSeat: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_pointer::WlPointer: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_keyboard::WlKeyboard: SeatData] => Seat);
+ways::delegate_dispatch!(ServerHandler: [wl_touch::WlTouch: SeatData] => Seat);
+
+/*
+ * Client Handler
+ */
+
+struct ClientHandler {
+    globals: globals::GlobalList,
+    frames: Vec<ToolFrame>,
+    tools: Vec<tablet_client::zwp_tablet_tool_v2::ZwpTabletToolV2>,
+}
+
+impl AsMut<globals::GlobalList> for ClientHandler {
+    fn as_mut(&mut self) -> &mut globals::GlobalList {
+        &mut self.globals
+    }
+}
+
+wayc::delegate_dispatch!(ClientHandler:
+    [wayc::protocol::wl_registry::WlRegistry: ()] => globals::GlobalList
+);
+
+client_ignore_impl!(ClientHandler => [
+    wayc::protocol::wl_seat::WlSeat,
+    wayc::protocol::wl_compositor::WlCompositor,
+    wayc::protocol::wl_surface::WlSurface,
+    tablet_client::zwp_tablet_manager_v2::ZwpTabletManagerV2,
+    tablet_client::zwp_tablet_v2::ZwpTabletV2
+]);
+
+impl wayc::Dispatch<tablet_client::zwp_tablet_seat_v2::ZwpTabletSeatV2, ()> for ClientHandler {
+    fn event(
+        state: &mut Self,
+        _: &tablet_client::zwp_tablet_seat_v2::ZwpTabletSeatV2,
+        event: tablet_client::zwp_tablet_seat_v2::Event,
+        _: &(),
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        if let tablet_client::zwp_tablet_seat_v2::Event::ToolAdded { id } = event {
+            state.tools.push(id);
+        }
+    }
+
+    wayc::event_created_child!(ClientHandler, tablet_client::zwp_tablet_seat_v2::ZwpTabletSeatV2, [
+        tablet_client::zwp_tablet_seat_v2::EVT_TABLET_ADDED_OPCODE => (tablet_client::zwp_tablet_v2::ZwpTabletV2, ()),
+        tablet_client::zwp_tablet_seat_v2::EVT_TOOL_ADDED_OPCODE => (tablet_client::zwp_tablet_tool_v2::ZwpTabletToolV2, ClientTabletTool::new())
+    ]);
+}
+
+impl wayc::Dispatch<tablet_client::zwp_tablet_tool_v2::ZwpTabletToolV2, ClientTabletTool>
+    for ClientHandler
+{
+    fn event(
+        state: &mut Self,
+        _: &tablet_client::zwp_tablet_tool_v2::ZwpTabletToolV2,
+        event: tablet_client::zwp_tablet_tool_v2::Event,
+        data: &ClientTabletTool,
+        _: &wayc::Connection,
+        _: &wayc::QueueHandle<Self>,
+    ) {
+        state.frames.extend(data.event(event));
+    }
+}
@@ -0,0 +1,201 @@
+#![warn(missing_docs, missing_debug_implementations)]
+#![forbid(improper_ctypes, unsafe_op_in_unsafe_fn)]
+
+//! Vulkan WSI utilities
+//!
+//! This crate creates a `VkSurfaceKHR` from a wayland surface, using the `vkCreateWaylandSurfaceKHR`
+//! function of the `VK_KHR_wayland_surface` instance extension.
+//!
+//! It does not depend on any Vulkan bindings. The function is either loaded with the
+//! `vkGetInstanceProcAddr` of the Vulkan loader, using [`SurfaceLoader::new()`], or given directly with
+//! [`SurfaceLoader::from_fn()`]. The Vulkan handles are given as their raw values, which have the same
+//! representation in all Vulkan bindings: with `ash` for example, a `vk::Instance` is converted with
+//! `Handle::as_raw() as VkInstance` and the created surface with `vk::SurfaceKHR::from_raw()`.
+//!
+//! See [`SurfaceLoader`] documentation for details.
+//!
+//! ## Backend
+//!
+//! The Vulkan implementation talks to the compositor through the `wl_display` and `wl_surface` of
+//! `libwayland-client.so`, so this crate enables the `client_system` backend of `wayland-backend`.
+//! With the `dlopen` cargo feature, `libwayland-client.so` is loaded at runtime.
+
+use std::{
+    ffi::CStr,
+    fmt,
+    os::raw::{c_char, c_void},
+};
+
+use wayland_client::{protocol::wl_surface::WlSurface, Connection, Proxy};
+use wayland_sys::client::{wl_display, wl_proxy};
+
+/// A raw `VkInstance` handle
+pub type VkInstance = *mut c_void;
+/// A raw `VkSurfaceKHR` handle
+pub type VkSurfaceKHR = u64;
+/// A raw `VkResult` value
+pub type VkResult = i32;
+
+/// The `VK_SUCCESS` result
+pub const VK_SUCCESS: VkResult = 0;
+/// The `VK_STRUCTURE_TYPE_WAYLAND_SURFACE_CREATE_INFO_KHR` structure type
+pub const VK_STRUCTURE_TYPE_WAYLAND_SURFACE_CREATE_INFO_KHR: i32 = 1_000_006_000;
+
+/// Signature of `vkGetInstanceProcAddr`
+#[allow(non_camel_case_types)]
+pub type PFN_vkGetInstanceProcAddr =
+    unsafe extern "system" fn(VkInstance, *const c_char) -> Option<unsafe extern "system" fn()>;
+
+/// Signature of `vkCreateWaylandSurfaceKHR`
+///
+/// The allocator is a `*const VkAllocationCallbacks`.
+#[allow(non_camel_case_types)]
+pub type PFN_vkCreateWaylandSurfaceKHR = unsafe extern "system" fn(
+    VkInstance,
+    *const VkWaylandSurfaceCreateInfoKHR,
+    *const c_void,
+    *mut VkSurfaceKHR,
+) -> VkResult;
+
+/// The `VkWaylandSurfaceCreateInfoKHR` structure
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VkWaylandSurfaceCreateInfoKHR {
+    /// Always [`VK_STRUCTURE_TYPE_WAYLAND_SURFACE_CREATE_INFO_KHR`]
+    pub s_type: i32,
+    /// Extension structures
+    pub p_next: *const c_void,
+    /// Reserved, must be 0
+    pub flags: u32,
+    /// The `wl_display` of the connection
+    pub display: *mut wl_display,
+    /// The `wl_surface`
+    pub surface: *mut wl_proxy,
+}
+
+/// Creates Vulkan surfaces from wayland surfaces
+///
+/// The loader is bound to the `VkInstance` it was created for, on which the `VK_KHR_wayland_surface`
+/// extension must be enabled.
+///
+/// Vulkan requires the `wl_display` and `wl_surface` to outlive the `VkSurfaceKHR`: you must always
+/// destroy the Vulkan surface with `vkDestroySurfaceKHR` *before* destroying the [`WlSurface`] and
+/// dropping the last handle to the [`Connection`]. The surface must also not be given a role or a buffer
+/// except through Vulkan while the Vulkan surface exists.
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceLoader {
+    instance: VkInstance,
+    create_surface: PFN_vkCreateWaylandSurfaceKHR,
+}
+
+impl SurfaceLoader {
+    /// Load `vkCreateWaylandSurfaceKHR` for an instance
+    ///
+    /// Returns `None` if the function is not provided, typically because the `VK_KHR_wayland_surface`
+    /// extension was not enabled when creating the instance.
+    ///
+    /// # Safety
+    ///
+    /// `instance` must be a valid `VkInstance`, that outlives the loader, and `get_instance_proc_addr`
+    /// its `vkGetInstanceProcAddr`.
+    pub unsafe fn new(
+        instance: VkInstance,
+        get_instance_proc_addr: PFN_vkGetInstanceProcAddr,
+    ) -> Option<Self> {
+        let name = CStr::from_bytes_with_nul(b"vkCreateWaylandSurfaceKHR\0").unwrap();
+        let function = unsafe { get_instance_proc_addr(instance, name.as_ptr()) }?;
+        // SAFETY: the function returned for this name has this signature
+        let create_surface = unsafe {
+            std::mem::transmute::<unsafe extern "system" fn(), PFN_vkCreateWaylandSurfaceKHR>(
+                function,
+            )
+        };
+        Some(Self { instance, create_surface })
+    }
+
+    /// Use an already loaded `vkCreateWaylandSurfaceKHR`
+    ///
+    /// # Safety
+    ///
+    /// `instance` must be a valid `VkInstance`, that outlives the loader, and `create_surface` the
+    /// `vkCreateWaylandSurfaceKHR` function of this instance.
+    pub unsafe fn from_fn(
+        instance: VkInstance,
+        create_surface: PFN_vkCreateWaylandSurfaceKHR,
+    ) -> Self {
+        Self { instance, create_surface }
+    }
+
+    /// The instance the surfaces are created for
+    pub fn instance(&self) -> VkInstance {
+        self.instance
+    }
+
+    /// Create a Vulkan surface from a wayland surface
+    ///
+    /// This method checks that the surface is still alive and was created on the provided connection,
+    /// as Vulkan would otherwise be given a dangling pointer or a surface it cannot reach through the
+    /// `wl_display`.
+    ///
+    /// # Safety
+    ///
+    /// `allocator` must be null or a valid `*const VkAllocationCallbacks`. The created surface must be
+    /// destroyed before `surface` and `conn`, see the [`SurfaceLoader`] documentation.
+    pub unsafe fn create_surface(
+        &self,
+        conn: &Connection,
+        surface: &WlSurface,
+        allocator: *const c_void,
+    ) -> Result<VkSurfaceKHR, Error> {
+        let backend = conn.backend();
+        if surface.backend() != &backend.downgrade() {
+            return Err(Error::ForeignSurface);
+        }
+
+        // ObjectId::as_ptr() returns NULL if the surface is no longer alive
+        let surface_ptr = surface.id().as_ptr();
+        if surface_ptr.is_null() {
+            return Err(Error::InvalidId);
+        }
+
+        let info = VkWaylandSurfaceCreateInfoKHR {
+            s_type: VK_STRUCTURE_TYPE_WAYLAND_SURFACE_CREATE_INFO_KHR,
+            p_next: std::ptr::null(),
+            flags: 0,
+            // valid as long as the backend is, which the caller keeps alive
+            display: backend.display_ptr(),
+            surface: surface_ptr,
+        };
+        let mut vk_surface = 0;
+        let result =
+            unsafe { (self.create_surface)(self.instance, &info, allocator, &mut vk_surface) };
+        if result == VK_SUCCESS {
+            Ok(vk_surface)
+        } else {
+            Err(Error::Vulkan(result))
+        }
+    }
+}
+
+/// Vulkan surface creation error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The wayland surface is no longer alive.
+    InvalidId,
+    /// The wayland surface was not created on the provided connection.
+    ForeignSurface,
+    /// `vkCreateWaylandSurfaceKHR` failed with this `VkResult`.
+    Vulkan(VkResult),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidId => write!(f, "surface is no longer alive"),
+            Error::ForeignSurface => write!(f, "surface belongs to another connection"),
+            Error::Vulkan(result) => write!(f, "vkCreateWaylandSurfaceKHR failed: {}", result),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Benchmarks of the serialization of messages to the wire format and of their parsing
+//!
+//! They measure the byte layout alone, without any IO, for messages typical of the protocol traffic.
+//!
+//! ```text
+//! cargo bench -p wayland-wire [FILTER]
+//! ```
+//!
+//! Only the benchmarks whose name contains `FILTER` are run.
+
+use std::ffi::CString;
+use std::time::{Duration, Instant};
+
+use wayland_wire::{
+    parse_message, smallvec::smallvec, write_to_buffers, AllowNull, Argument, ArgumentType, Message,
+};
+
+const LARGE_STRING_LEN: usize = 2000;
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let filter = filter.as_deref().unwrap_or("");
+
+    // wl_pointer.motion
+    let motion = Message {
+        sender_id: 12,
+        opcode: 2,
+        args: smallvec![
+            Argument::Uint(123_456),
+            Argument::Fixed(10 << 8),
+            Argument::Fixed(20 << 8)
+        ],
+    };
+    let motion_signature = [ArgumentType::Uint, ArgumentType::Fixed, ArgumentType::Fixed];
+    roundtrip("motion", filter, &motion, &motion_signature);
+
+    // wl_registry.global with a long interface name
+    let global = Message {
+        sender_id: 2,
+        opcode: 0,
+        args: smallvec![
+            Argument::Uint(1),
+            Argument::Str(Some(Box::new(CString::new("x".repeat(LARGE_STRING_LEN)).unwrap()))),
+            Argument::Uint(1),
+        ],
+    };
+    let global_signature =
+        [ArgumentType::Uint, ArgumentType::Str(AllowNull::No), ArgumentType::Uint];
+    roundtrip("large_string", filter, &global, &global_signature);
+
+    // wl_shm.create_pool
+    let create_pool = Message {
+        sender_id: 3,
+        opcode: 0,
+        args: smallvec![Argument::NewId(8), Argument::Fd(0u8), Argument::Int(4096)],
+    };
+    let create_pool_signature = [ArgumentType::NewId, ArgumentType::Fd, ArgumentType::Int];
+    roundtrip("fd", filter, &create_pool, &create_pool_signature);
+
+    // wl_keyboard.enter with the keys pressed
+    let enter = Message {
+        sender_id: 14,
+        opcode: 1,
+        args: smallvec![
+            Argument::Uint(42),
+            Argument::Object(5),
+            Argument::Array(Box::new((0..64).collect())),
+        ],
+    };
+    let enter_signature =
+        [ArgumentType::Uint, ArgumentType::Object(AllowNull::No), ArgumentType::Array];
+    roundtrip("array", filter, &enter, &enter_signature);
+}
+
+/// Benchmark the serialization and the parsing of a message
+fn roundtrip(name: &str, filter: &str, msg: &Message<u32, u8>, signature: &[ArgumentType]) {
+    let mut payload = vec![0u32; 1024];
+    let mut fds = [0u8; 4];
+    let (len, fd_count) = write_to_buffers(msg, &mut payload, &mut fds).unwrap();
+
+    let mut total = 0;
+    bench(&format!("write/{}", name), filter, || {
+        total += write_to_buffers(msg, &mut payload, &mut fds).unwrap().0;
+    });
+    bench(&format!("parse/{}", name), filter, || {
+        total += parse_message(&payload[..len], signature, &fds[..fd_count]).unwrap().0.args.len();
+    });
+    // the results are used so that the work cannot be optimized away
+    assert_ne!(total, usize::MAX);
+}
+
+/// Run `routine` with an increasing number of iterations until it takes long enough to be measured,
+/// and print the time per iteration along with the throughput of messages
+fn bench(name: &str, filter: &str, mut routine: impl FnMut()) {
+    if !name.contains(filter) {
+        return;
+    }
+    let mut run = |iterations: u32| {
+        for _ in 0..iterations {
+            routine();
+        }
+    };
+    // warm up
+    run(100);
+
+    let mut iterations = 100;
+    let elapsed = loop {
+        let start = Instant::now();
+        run(iterations);
+        let elapsed = start.elapsed();
+        if elapsed >= Duration::from_millis(500) {
+            break elapsed;
+        }
+        iterations *= 2;
+    };
+
+    let per_iter = elapsed / iterations;
+    let throughput = iterations as f64 / elapsed.as_secs_f64();
+    println!("{:<24} {:>12.2?}/iter {:>14.0} msg/s", name, per_iter, throughput);
+}
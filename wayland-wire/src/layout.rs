@@ -0,0 +1,294 @@
+//! Serialization of the messages to the wire format
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{ffi::CStr, ptr};
+
+use smallvec::SmallVec;
+
+use crate::message::{Argument, ArgumentType, Message};
+
+/// Error generated when the buffers are too small to serialize a message in them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferTooSmall;
+
+#[cfg(feature = "std")]
+impl std::error::Error for BufferTooSmall {}
+
+impl core::fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("The provided buffer is too small to hold message content.")
+    }
+}
+
+/// Error generated when trying to deserialize a message from buffers
+#[derive(Debug, Clone)]
+pub enum MessageParseError {
+    /// The message references a FD but the buffer FD is empty
+    MissingFD,
+    /// More data is needed to deserialize the message
+    MissingData,
+    /// The message is malformed and cannot be parsed
+    Malformed,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MessageParseError {}
+
+impl core::fmt::Display for MessageParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            Self::MissingFD => {
+                f.write_str("The message references a FD but the buffer FD is empty.")
+            }
+            Self::MissingData => f.write_str("More data is needed to deserialize the message"),
+            Self::Malformed => f.write_str("The message is malformed and cannot be parsed"),
+        }
+    }
+}
+
+/// Serialize the contents of this message into provided buffers
+///
+/// Returns the number of elements written in each buffer. The file descriptors are copied as they are
+/// in `fds`, in the order of the arguments.
+pub fn write_to_buffers<Fd: Copy>(
+    msg: &Message<u32, Fd>,
+    payload: &mut [u32],
+    mut fds: &mut [Fd],
+) -> Result<(usize, usize), BufferTooSmall> {
+    let orig_payload_len = payload.len();
+    let orig_fds_len = fds.len();
+    // Helper function to write a u32 or a Fd to its buffer
+    fn write_buf<T>(u: T, payload: &mut [T]) -> Result<&mut [T], BufferTooSmall> {
+        if let Some((head, tail)) = payload.split_first_mut() {
+            *head = u;
+            Ok(tail)
+        } else {
+            Err(BufferTooSmall)
+        }
+    }
+
+    // Helper function to write byte arrays in payload
+    fn write_array_to_payload<'a>(
+        array: &[u8],
+        payload: &'a mut [u32],
+    ) -> Result<&'a mut [u32], BufferTooSmall> {
+        let array_len = array.len();
+        let word_len = array_len / 4 + usize::from(array_len % 4 != 0);
+        // need enough space to store the whole array with padding and a size header
+        if payload.len() < 1 + word_len {
+            return Err(BufferTooSmall);
+        }
+        // size header
+        payload[0] = array_len as u32;
+        let (buffer_slice, rest) = payload[1..].split_at_mut(word_len);
+        unsafe {
+            ptr::copy(array.as_ptr(), buffer_slice.as_mut_ptr() as *mut u8, array_len);
+        }
+        Ok(rest)
+    }
+
+    let free_size = payload.len();
+    if free_size < 2 {
+        return Err(BufferTooSmall);
+    }
+
+    let (header, mut payload) = payload.split_at_mut(2);
+
+    // write the contents in the buffer
+    for arg in &msg.args {
+        // Just to make the borrow checker happy
+        let old_payload = payload;
+        match *arg {
+            Argument::Int(i) => payload = write_buf(i as u32, old_payload)?,
+            Argument::Uint(u) => payload = write_buf(u, old_payload)?,
+            Argument::Fixed(f) => payload = write_buf(f as u32, old_payload)?,
+            Argument::Str(Some(ref s)) => {
+                payload = write_array_to_payload(s.as_bytes_with_nul(), old_payload)?;
+            }
+            Argument::Str(None) => {
+                payload = write_array_to_payload(&[], old_payload)?;
+            }
+            Argument::Object(o) => payload = write_buf(o, old_payload)?,
+            Argument::NewId(n) => payload = write_buf(n, old_payload)?,
+            Argument::Array(ref a) => {
+                payload = write_array_to_payload(a, old_payload)?;
+            }
+            Argument::Fd(fd) => {
+                let old_fds = fds;
+                fds = write_buf(fd, old_fds)?;
+                payload = old_payload;
+            }
+        }
+    }
+
+    let wrote_size = (free_size - payload.len()) * 4;
+    header[0] = msg.sender_id;
+    header[1] = ((wrote_size as u32) << 16) | u32::from(msg.opcode);
+    Ok((orig_payload_len - payload.len(), orig_fds_len - fds.len()))
+}
+
+/// Attempts to parse a single wayland message with the given signature.
+///
+/// If the buffers contains several messages, only the first one will be parsed,
+/// and the unused tail of the buffers is returned. If a single message was present,
+/// the returned slices should thus be empty.
+///
+/// Errors if the message is malformed.
+#[allow(clippy::type_complexity)]
+pub fn parse_message<'a, 'b, Fd: Copy>(
+    raw: &'a [u32],
+    signature: &[ArgumentType],
+    fds: &'b [Fd],
+) -> Result<(Message<u32, Fd>, &'a [u32], &'b [Fd]), MessageParseError> {
+    // helper function to read arrays
+    fn read_array_from_payload(
+        array_len: usize,
+        payload: &[u32],
+    ) -> Result<(&[u8], &[u32]), MessageParseError> {
+        let word_len = array_len / 4 + usize::from(array_len % 4 != 0);
+        if word_len > payload.len() {
+            return Err(MessageParseError::MissingData);
+        }
+        let (array_contents, rest) = payload.split_at(word_len);
+        let array =
+            unsafe { core::slice::from_raw_parts(array_contents.as_ptr() as *const u8, array_len) };
+        Ok((array, rest))
+    }
+
+    if raw.len() < 2 {
+        return Err(MessageParseError::MissingData);
+    }
+
+    let sender_id = raw[0];
+    let word_2 = raw[1];
+    let opcode = (word_2 & 0x0000_FFFF) as u16;
+    let len = (word_2 >> 16) as usize / 4;
+
+    if len < 2 {
+        return Err(MessageParseError::Malformed);
+    } else if len > raw.len() {
+        return Err(MessageParseError::MissingData);
+    }
+
+    let (mut payload, rest) = raw.split_at(len);
+    payload = &payload[2..];
+    let mut fds = fds;
+
+    let arguments = signature
+        .iter()
+        .map(|argtype| {
+            if let ArgumentType::Fd = *argtype {
+                // don't consume input but fd
+                if let Some((&front, tail)) = fds.split_first() {
+                    fds = tail;
+                    Ok(Argument::Fd(front))
+                } else {
+                    Err(MessageParseError::MissingFD)
+                }
+            } else if let Some((&front, mut tail)) = payload.split_first() {
+                let arg = match *argtype {
+                    ArgumentType::Int => Ok(Argument::Int(front as i32)),
+                    ArgumentType::Uint => Ok(Argument::Uint(front)),
+                    ArgumentType::Fixed => Ok(Argument::Fixed(front as i32)),
+                    ArgumentType::Str(_) => {
+                        read_array_from_payload(front as usize, tail).and_then(|(v, rest)| {
+                            tail = rest;
+                            if !v.is_empty() {
+                                match CStr::from_bytes_with_nul(v) {
+                                    Ok(s) => Ok(Argument::Str(Some(Box::new(s.into())))),
+                                    Err(_) => Err(MessageParseError::Malformed),
+                                }
+                            } else {
+                                Ok(Argument::Str(None))
+                            }
+                        })
+                    }
+                    ArgumentType::Object(_) => Ok(Argument::Object(front)),
+                    ArgumentType::NewId => Ok(Argument::NewId(front)),
+                    ArgumentType::Array => {
+                        read_array_from_payload(front as usize, tail).map(|(v, rest)| {
+                            tail = rest;
+                            Argument::Array(Box::new(Vec::from(v)))
+                        })
+                    }
+                    ArgumentType::Fd => unreachable!(),
+                };
+                payload = tail;
+                arg
+            } else {
+                Err(MessageParseError::MissingData)
+            }
+        })
+        .collect::<Result<SmallVec<_>, MessageParseError>>()?;
+
+    let msg = Message { sender_id, opcode, args: arguments };
+    Ok((msg, rest, fds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::AllowNull;
+    use alloc::{ffi::CString, vec};
+    use smallvec::smallvec;
+
+    // The file descriptors are opaque values moved to their own buffer, in order
+    #[test]
+    fn fds_are_moved_in_order() {
+        let msg: Message<u32, u16> = Message {
+            sender_id: 7,
+            opcode: 2,
+            args: smallvec![Argument::Fd(11), Argument::Str(None), Argument::Fd(12)],
+        };
+        let mut payload = [0; 8];
+        let mut fds = [0; 4];
+        assert_eq!(write_to_buffers(&msg, &mut payload, &mut fds), Ok((3, 2)));
+        assert_eq!(fds[..2], [11, 12]);
+
+        let signature = [ArgumentType::Fd, ArgumentType::Str(AllowNull::Yes), ArgumentType::Fd];
+        let (parsed, _, rest_fds) = parse_message(&payload[..3], &signature, &fds[..3]).unwrap();
+        assert_eq!(rest_fds, [0]);
+        assert!(matches!(
+            parsed.args[..],
+            [Argument::Fd(11), Argument::Str(None), Argument::Fd(12)]
+        ));
+
+        // the file descriptors are also checked for space
+        assert_eq!(write_to_buffers(&msg, &mut payload, &mut fds[..1]), Err(BufferTooSmall));
+        assert!(matches!(
+            parse_message(&payload[..3], &signature, &fds[..1]),
+            Err(MessageParseError::MissingFD)
+        ));
+    }
+
+    #[test]
+    fn strings_and_arrays_are_padded() {
+        let msg: Message<u32, ()> = Message {
+            sender_id: 1,
+            opcode: 0,
+            args: smallvec![
+                Argument::Str(Some(Box::new(CString::new("abcd").unwrap()))),
+                Argument::Array(Box::new(vec![1, 2, 3, 4, 5])),
+            ],
+        };
+        let mut payload = [0; 16];
+        // header, length and 2 words for "abcd\0", length and 2 words for the array
+        assert_eq!(write_to_buffers(&msg, &mut payload, &mut []), Ok((8, 0)));
+        assert_eq!(payload[1] >> 16, 32);
+        assert_eq!(write_to_buffers(&msg, &mut payload[..7], &mut []), Err(BufferTooSmall));
+
+        let signature = [ArgumentType::Str(AllowNull::No), ArgumentType::Array];
+        let (parsed, _, _) = parse_message::<()>(&payload[..8], &signature, &[]).unwrap();
+        match &parsed.args[..] {
+            [Argument::Str(Some(s)), Argument::Array(a)] => {
+                assert_eq!(s.as_bytes(), b"abcd");
+                assert_eq!(a[..], [1, 2, 3, 4, 5]);
+            }
+            args => panic!("unexpected arguments {:?}", args),
+        }
+        assert!(matches!(
+            parse_message::<()>(&payload[..7], &signature, &[]),
+            Err(MessageParseError::MissingData)
+        ));
+    }
+}
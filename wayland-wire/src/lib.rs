@@ -0,0 +1,59 @@
+//! Message model and byte layout of the Wayland wire protocol
+//!
+//! This crate provides the representation of the messages exchanged by the Wayland clients and
+//! servers ([`Message`] and its [`Argument`]s), and their serialization to the wire format
+//! ([`write_to_buffers()`] and [`parse_message()`]), without any of the socket IO. It is the core
+//! `wayland-backend` builds its rust backends on, and re-exports in its `protocol` module.
+//!
+//! ## `no_std` support
+//!
+//! The crate only requires `alloc`. Its `std` cargo feature, enabled by default, adds the
+//! [`std::error::Error`] implementations of the errors and, on unix, the comparison and display of the
+//! messages whose file descriptors implement `AsRawFd`. Without it, the crate is `no_std`, for example to
+//! reuse the wire format in an embedded device or in WebAssembly.
+//!
+//! The file descriptors are not part of the byte stream: they are sent alongside it over the unix socket.
+//! The messages are thus generic over their file descriptor type, and the marshalling only moves them
+//! between the messages and a separate buffer, so that they can be represented by any [`Copy`] value, such
+//! as an index, where file descriptors don't exist.
+//!
+//! ```
+//! use wayland_wire::{
+//!     parse_message, smallvec, write_to_buffers, AllowNull, Argument, ArgumentType, Message,
+//! };
+//!
+//! let msg: Message<u32, u8> = Message {
+//!     sender_id: 3,
+//!     opcode: 1,
+//!     args: smallvec::smallvec![Argument::Uint(42), Argument::Fd(0), Argument::Object(0)],
+//! };
+//! let mut payload = [0u32; 16];
+//! let mut fds = [0u8; 4];
+//! let (len, fd_count) = write_to_buffers(&msg, &mut payload, &mut fds).unwrap();
+//! assert_eq!((len, fd_count), (4, 1));
+//!
+//! let signature = [ArgumentType::Uint, ArgumentType::Fd, ArgumentType::Object(AllowNull::Yes)];
+//! let (parsed, rest, _) = parse_message(&payload[..len], &signature, &fds[..fd_count]).unwrap();
+//! assert!(rest.is_empty());
+//! assert_eq!(parsed.opcode, 1);
+//! assert!(matches!(parsed.args[0], Argument::Uint(42)));
+//! ```
+
+#![no_std]
+#![warn(missing_docs, missing_debug_implementations)]
+#![deny(unsafe_op_in_unsafe_fn)]
+// Doc feature labels can be tested locally by running RUSTDOCFLAGS="--cfg=docsrs" cargo +nightly doc -p <crate>
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+/// Reexport of the `smallvec` crate, which is part of `wayland-wire`'s public API.
+pub extern crate smallvec;
+
+mod layout;
+mod message;
+
+pub use layout::{parse_message, write_to_buffers, BufferTooSmall, MessageParseError};
+pub use message::{AllowNull, Argument, ArgumentType, Message, INLINE_ARGS};
@@ -0,0 +1,175 @@
+//! The representation of the messages and of their arguments
+
+use alloc::{boxed::Box, ffi::CString, vec::Vec};
+
+#[cfg(all(feature = "std", unix))]
+use std::os::unix::io::AsRawFd;
+
+/// Describes whether an argument may have a null value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AllowNull {
+    /// Null values are allowed.
+    Yes,
+    /// Null values are forbidden.
+    No,
+}
+
+/// Enum of possible argument types as recognized by the wire
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ArgumentType {
+    /// An integer argument. Represented by a [`i32`].
+    Int,
+    /// An unsigned integer argument. Represented by a [`u32`].
+    Uint,
+    /// A signed fixed point number with 1/256 precision
+    Fixed,
+    /// A string. This is represented as a [`CString`] in a message.
+    Str(AllowNull),
+    /// Id of a wayland object
+    Object(AllowNull),
+    /// Id of a newly created wayland object
+    NewId,
+    /// `Vec<u8>`
+    Array,
+    /// A file descriptor argument. Represented by a `RawFd`.
+    Fd,
+}
+
+impl ArgumentType {
+    /// Returns true if the type of the argument is the same.
+    pub fn same_type(self, other: Self) -> bool {
+        core::mem::discriminant(&self) == core::mem::discriminant(&other)
+    }
+}
+
+/// Enum of possible argument of the protocol
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub enum Argument<Id, Fd> {
+    /// An integer argument. Represented by a [`i32`].
+    Int(i32),
+    /// An unsigned integer argument. Represented by a [`u32`].
+    Uint(u32),
+    /// A signed fixed point number with 1/256 precision
+    Fixed(i32),
+    /// CString
+    ///
+    /// The value is boxed to reduce the stack size of Argument. The performance
+    /// impact is negligible as `string` arguments are pretty rare in the protocol.
+    Str(Option<Box<CString>>),
+    /// Id of a wayland object
+    Object(Id),
+    /// Id of a newly created wayland object
+    NewId(Id),
+    /// `Vec<u8>`
+    ///
+    /// The value is boxed to reduce the stack size of Argument. The performance
+    /// impact is negligible as `array` arguments are pretty rare in the protocol.
+    Array(Box<Vec<u8>>),
+    /// A file descriptor argument. Represented by a `RawFd`.
+    Fd(Fd),
+}
+
+impl<Id, Fd> Argument<Id, Fd> {
+    /// Retrieve the type of a given argument instance
+    pub fn get_type(&self) -> ArgumentType {
+        match *self {
+            Self::Int(_) => ArgumentType::Int,
+            Self::Uint(_) => ArgumentType::Uint,
+            Self::Fixed(_) => ArgumentType::Fixed,
+            Self::Str(_) => ArgumentType::Str(AllowNull::Yes),
+            Self::Object(_) => ArgumentType::Object(AllowNull::Yes),
+            Self::NewId(_) => ArgumentType::NewId,
+            Self::Array(_) => ArgumentType::Array,
+            Self::Fd(_) => ArgumentType::Fd,
+        }
+    }
+
+    fn map_fd<T>(self, f: &mut impl FnMut(Fd) -> T) -> Argument<Id, T> {
+        match self {
+            Self::Int(val) => Argument::Int(val),
+            Self::Uint(val) => Argument::Uint(val),
+            Self::Fixed(val) => Argument::Fixed(val),
+            Self::Str(val) => Argument::Str(val),
+            Self::Object(val) => Argument::Object(val),
+            Self::NewId(val) => Argument::NewId(val),
+            Self::Array(val) => Argument::Array(val),
+            Self::Fd(val) => Argument::Fd(f(val)),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<Id: PartialEq, Fd: AsRawFd> PartialEq for Argument<Id, Fd> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a == b,
+            (Self::Uint(a), Self::Uint(b)) => a == b,
+            (Self::Fixed(a), Self::Fixed(b)) => a == b,
+            (Self::Str(a), Self::Str(b)) => a == b,
+            (Self::Object(a), Self::Object(b)) => a == b,
+            (Self::NewId(a), Self::NewId(b)) => a == b,
+            (Self::Array(a), Self::Array(b)) => a == b,
+            (Self::Fd(a), Self::Fd(b)) => a.as_raw_fd() == b.as_raw_fd(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<Id: Eq, Fd: AsRawFd> Eq for Argument<Id, Fd> {}
+
+#[cfg(all(feature = "std", unix))]
+impl<Id: core::fmt::Display, Fd: AsRawFd> core::fmt::Display for Argument<Id, Fd> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Uint(value) => write!(f, "{}", value),
+            Self::Fixed(value) => write!(f, "{}", value),
+            Self::Str(value) => write!(f, "{:?}", value),
+            Self::Object(value) => write!(f, "{}", value),
+            Self::NewId(value) => write!(f, "{}", value),
+            Self::Array(value) => write!(f, "{:?}", value),
+            Self::Fd(value) => write!(f, "{}", value.as_raw_fd()),
+        }
+    }
+}
+
+/// Number of arguments that are stocked inline in a `Message` before allocating
+///
+/// This is a ad-hoc number trying to reach a good balance between avoiding too many allocations
+/// and keeping the stack size of `Message` small.
+// Note: Keep in sync with `wayland_scanner::common::gen_write_body`.
+pub const INLINE_ARGS: usize = 4;
+
+/// Represents a message that has been sent from some object.
+#[derive(Clone, Debug)]
+pub struct Message<Id, Fd> {
+    /// The id of the object that sent the message.
+    pub sender_id: Id,
+    /// The opcode of the message.
+    pub opcode: u16,
+    /// The arguments of the message.
+    pub args: smallvec::SmallVec<[Argument<Id, Fd>; INLINE_ARGS]>,
+}
+
+impl<Id, Fd> Message<Id, Fd> {
+    /// Map some closure on all Fd contained in this message, to change the Fd generic parameter.
+    pub fn map_fd<T>(self, mut f: impl FnMut(Fd) -> T) -> Message<Id, T> {
+        Message {
+            sender_id: self.sender_id,
+            opcode: self.opcode,
+            args: self.args.into_iter().map(move |arg| arg.map_fd(&mut f)).collect(),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<Id: PartialEq, Fd: AsRawFd> PartialEq for Message<Id, Fd> {
+    fn eq(&self, other: &Self) -> bool {
+        self.sender_id == other.sender_id && self.opcode == other.opcode && self.args == other.args
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl<Id: Eq, Fd: AsRawFd> Eq for Message<Id, Fd> {}